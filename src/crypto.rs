@@ -0,0 +1,221 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Signature and key agreement primitives for all supported key types. Public keys of elliptic
+//! curve keys are handled in their compressed SEC1 form, ECDSA signatures are 64 bytes `r || s`
+//! over the SHA-256 hash of the message, as used in JOSE.
+
+use crate::multikey::KeyType;
+use k256::{
+    ecdsa::signature::{Signer as _, Verifier as _},
+    elliptic_curve::sec1::ToEncodedPoint,
+};
+use rand_core::OsRng;
+#[cfg(feature = "bbs")]
+use zkryptium::{
+    bbsplus::keys::BBSplusSecretKey,
+    keys::pair::KeyPair,
+    schemes::{algorithms::BbsBls12381Sha256, generics::Signature},
+};
+
+const BLS12_381_G2_SIGNING: &str = "BLS12-381 G2 keys can only be used for BBS signatures";
+#[cfg(not(feature = "bbs"))]
+const BBS_FEATURE_REQUIRED: &str = "BLS12-381 G2 keys require the bbs feature";
+
+/// Generates a new random secret key of given type.
+pub(crate) fn generate_secret_key(
+    key_type: KeyType,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(match key_type {
+        KeyType::Ed25519 => ed25519_dalek::SigningKey::generate(&mut OsRng)
+            .to_bytes()
+            .to_vec(),
+        KeyType::Secp256k1 => k256::SecretKey::random(&mut OsRng).to_bytes().to_vec(),
+        KeyType::P256 => p256::SecretKey::random(&mut OsRng).to_bytes().to_vec(),
+        KeyType::X25519 => x25519_dalek::StaticSecret::random_from_rng(OsRng)
+            .to_bytes()
+            .to_vec(),
+        #[cfg(feature = "bbs")]
+        KeyType::Bls12381G2 => KeyPair::<BbsBls12381Sha256>::random()?
+            .into_parts()
+            .0
+            .to_bytes()
+            .to_vec(),
+        #[cfg(not(feature = "bbs"))]
+        KeyType::Bls12381G2 => return Err(Box::from(BBS_FEATURE_REQUIRED)),
+    })
+}
+
+/// Derives the public key of given secret key.
+pub(crate) fn get_public_key(
+    key_type: KeyType,
+    secret_key: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(match key_type {
+        KeyType::Ed25519 => ed25519_dalek::SigningKey::from_bytes(&to_array(secret_key)?)
+            .verifying_key()
+            .to_bytes()
+            .to_vec(),
+        KeyType::Secp256k1 => k256::SecretKey::from_slice(secret_key)?
+            .public_key()
+            .to_encoded_point(true)
+            .as_bytes()
+            .to_vec(),
+        KeyType::P256 => p256::SecretKey::from_slice(secret_key)?
+            .public_key()
+            .to_encoded_point(true)
+            .as_bytes()
+            .to_vec(),
+        KeyType::X25519 => {
+            x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(to_array(secret_key)?))
+                .as_bytes()
+                .to_vec()
+        }
+        #[cfg(feature = "bbs")]
+        KeyType::Bls12381G2 => BBSplusSecretKey::from_bytes(secret_key)?
+            .public_key()
+            .to_bytes()
+            .to_vec(),
+        #[cfg(not(feature = "bbs"))]
+        KeyType::Bls12381G2 => return Err(Box::from(BBS_FEATURE_REQUIRED)),
+    })
+}
+
+/// Signs given message with given secret key.
+pub(crate) fn sign(
+    key_type: KeyType,
+    secret_key: &[u8],
+    message: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(match key_type {
+        KeyType::Ed25519 => {
+            let signing_key = ed25519_dalek::SigningKey::from_bytes(&to_array(secret_key)?);
+            let signature: ed25519_dalek::Signature = signing_key.sign(message);
+            signature.to_bytes().to_vec()
+        }
+        KeyType::Secp256k1 => {
+            let signing_key = k256::ecdsa::SigningKey::from_slice(secret_key)?;
+            let signature: k256::ecdsa::Signature = signing_key.sign(message);
+            signature.to_bytes().to_vec()
+        }
+        KeyType::P256 => {
+            let signing_key = p256::ecdsa::SigningKey::from_slice(secret_key)?;
+            let signature: p256::ecdsa::Signature = signing_key.sign(message);
+            signature.to_bytes().to_vec()
+        }
+        KeyType::X25519 => return Err(Box::from("X25519 keys cannot be used for signing")),
+        KeyType::Bls12381G2 => return Err(Box::from(BLS12_381_G2_SIGNING)),
+    })
+}
+
+/// Checks if given signature is a valid signature of given message. Returns `false` for invalid
+/// signatures and errors for malformed keys.
+pub(crate) fn verify(
+    key_type: KeyType,
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(match key_type {
+        KeyType::Ed25519 => {
+            let verifying_key = ed25519_dalek::VerifyingKey::from_bytes(&to_array(public_key)?)?;
+            match ed25519_dalek::Signature::from_slice(signature) {
+                Ok(signature) => verifying_key.verify_strict(message, &signature).is_ok(),
+                Err(_) => false,
+            }
+        }
+        KeyType::Secp256k1 => {
+            let verifying_key = k256::ecdsa::VerifyingKey::from_sec1_bytes(public_key)?;
+            match k256::ecdsa::Signature::from_slice(signature) {
+                Ok(signature) => verifying_key.verify(message, &signature).is_ok(),
+                Err(_) => false,
+            }
+        }
+        KeyType::P256 => {
+            let verifying_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key)?;
+            match p256::ecdsa::Signature::from_slice(signature) {
+                Ok(signature) => verifying_key.verify(message, &signature).is_ok(),
+                Err(_) => false,
+            }
+        }
+        KeyType::X25519 => return Err(Box::from("X25519 keys cannot be used for signing")),
+        KeyType::Bls12381G2 => return Err(Box::from(BLS12_381_G2_SIGNING)),
+    })
+}
+
+/// Computes the raw shared secret of given secret key and another party's public key.
+pub(crate) fn agree(
+    key_type: KeyType,
+    secret_key: &[u8],
+    public_key: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(match key_type {
+        KeyType::Ed25519 => return Err(Box::from("Ed25519 keys cannot be used for key agreement")),
+        KeyType::Bls12381G2 => return Err(Box::from(BLS12_381_G2_SIGNING)),
+        KeyType::Secp256k1 => {
+            let secret_key = k256::SecretKey::from_slice(secret_key)?;
+            let public_key = k256::PublicKey::from_sec1_bytes(public_key)?;
+            k256::ecdh::diffie_hellman(secret_key.to_nonzero_scalar(), public_key.as_affine())
+                .raw_secret_bytes()
+                .to_vec()
+        }
+        KeyType::P256 => {
+            let secret_key = p256::SecretKey::from_slice(secret_key)?;
+            let public_key = p256::PublicKey::from_sec1_bytes(public_key)?;
+            p256::ecdh::diffie_hellman(secret_key.to_nonzero_scalar(), public_key.as_affine())
+                .raw_secret_bytes()
+                .to_vec()
+        }
+        KeyType::X25519 => {
+            let secret_key = x25519_dalek::StaticSecret::from(to_array(secret_key)?);
+            let shared_secret =
+                secret_key.diffie_hellman(&x25519_dalek::PublicKey::from(to_array(public_key)?));
+            if !shared_secret.was_contributory() {
+                return Err(Box::from("X25519 public key is of low order"));
+            }
+            shared_secret.as_bytes().to_vec()
+        }
+    })
+}
+
+/// Signs given messages and header with a BBS signature of the `BLS12-381-SHA-256` ciphersuite.
+#[cfg(feature = "bbs")]
+pub(crate) fn sign_bbs(
+    secret_key: &[u8],
+    header: &[u8],
+    messages: &[Vec<u8>],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let secret_key = BBSplusSecretKey::from_bytes(secret_key)?;
+    let signature = Signature::<BbsBls12381Sha256>::sign(
+        Some(messages),
+        &secret_key,
+        &secret_key.public_key(),
+        Some(header),
+    )?;
+    Ok(signature.to_bytes().to_vec())
+}
+
+fn to_array(bytes: &[u8]) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+    if bytes.len() != 32 {
+        return Err(Box::from(format!(
+            "expected a key with 32 bytes, got {} bytes",
+            bytes.len()
+        )));
+    }
+    let mut array = [0u8; 32];
+    array.copy_from_slice(bytes);
+    Ok(array)
+}
@@ -0,0 +1,251 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Handling of [Data Integrity](https://www.w3.org/TR/vc-data-integrity/) proofs shared by all
+//! cryptosuites: proof configuration, checks of proof options and resolution of the verification
+//! method, as well as the CBOR encoding of selective disclosure proof values.
+
+use crate::{verification_method, Vade};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use ciborium::value::Value as CborValue;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+
+pub(crate) const PROOF_TYPE: &str = "DataIntegrityProof";
+
+/// Parses given payload as JSON object.
+pub(crate) fn parse_document(
+    payload: &str,
+) -> Result<Map<String, Value>, Box<dyn std::error::Error>> {
+    match serde_json::from_str(payload)? {
+        Value::Object(document) => Ok(document),
+        _ => Err(Box::from("payload must be a JSON object")),
+    }
+}
+
+/// Returns the proof options a proof signs: the proof without its value, in the context of the
+/// document.
+pub(crate) fn get_proof_config(document: &Map<String, Value>, proof: &Map<String, Value>) -> Value {
+    let mut proof_config = proof.clone();
+    proof_config.remove("proofValue");
+    if let Some(context) = document.get("@context") {
+        proof_config
+            .entry("@context")
+            .or_insert_with(|| context.clone());
+    }
+    Value::Object(proof_config)
+}
+
+/// Hashes the concatenation of given N-Quads with SHA-256.
+pub(crate) fn hash_nquads<'a, I: Iterator<Item = &'a String>>(nquads: I) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for nquad in nquads {
+        hasher.update(nquad.as_bytes());
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Checks type, purpose, `challenge`, `domain` and expiration of given proof.
+///
+/// # Arguments
+///
+/// * `proof` - proof to check
+/// * `proof_purpose` - expected proof purpose
+/// * `challenge` - challenge the proof has to include, if any
+/// * `domain` - domain the proof has to include, if any
+pub(crate) fn check_proof_options(
+    proof: &Map<String, Value>,
+    proof_purpose: &str,
+    challenge: Option<&str>,
+    domain: Option<&str>,
+) -> Result<(), String> {
+    let get = |name: &str| proof.get(name).and_then(|value| value.as_str());
+    if get("type") != Some(PROOF_TYPE) {
+        return Err(format!(
+            r#"unsupported proof type "{}""#,
+            get("type").unwrap_or_default()
+        ));
+    }
+    if get("proofPurpose") != Some(proof_purpose) {
+        return Err(format!(
+            r#"expected proof purpose "{}", got "{}""#,
+            proof_purpose,
+            get("proofPurpose").unwrap_or_default()
+        ));
+    }
+    if let Some(challenge) = challenge {
+        if get("challenge") != Some(challenge) {
+            return Err("challenge does not match".to_string());
+        }
+    }
+    if let Some(domain) = domain {
+        let matches = match proof.get("domain") {
+            Some(Value::Array(domains)) => domains.iter().any(|d| d == domain),
+            Some(value) => value == domain,
+            None => false,
+        };
+        if !matches {
+            return Err("domain does not match".to_string());
+        }
+    }
+    for name in ["created", "expires"].iter() {
+        if let Some(value) = get(name) {
+            let time = DateTime::parse_from_rfc3339(value)
+                .map_err(|e| format!(r#"invalid {} "{}"; {}"#, name, value, e))?;
+            if *name == "expires" && time < Utc::now() {
+                return Err("proof expired".to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the verification method of given proof and checks, that it is authorized for the
+/// proof's purpose and, if given, belongs to the controller.
+///
+/// # Arguments
+///
+/// * `resolver` - `Vade` instance to resolve DIDs other than `did:key` with
+/// * `proof` - proof to get verification method of
+/// * `controller` - DID, that has to control the verification method, if known
+pub(crate) async fn resolve_verification_method(
+    resolver: Option<&mut Vade>,
+    proof: &Map<String, Value>,
+    controller: Option<&str>,
+) -> Result<Value, String> {
+    let method_id = proof
+        .get("verificationMethod")
+        .and_then(Value::as_str)
+        .ok_or("proof has no verificationMethod")?;
+    let proof_purpose = proof
+        .get("proofPurpose")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    verification_method::resolve_authorized_method(resolver, method_id, controller, proof_purpose)
+        .await
+}
+
+/// Returns the DID of the issuer of given credential. Issuers identified otherwise, e.g. by an
+/// URL, cannot be matched against the controller of verification methods and are rejected.
+///
+/// # Arguments
+///
+/// * `credential` - credential to get issuer of
+pub(crate) fn get_issuer_did(credential: &Map<String, Value>) -> Result<&str, String> {
+    get_did(credential, "issuer").ok_or_else(|| "issuer is no DID".to_string())
+}
+
+/// Returns the DID of the holder of given presentation, if it has a holder. Holders identified
+/// otherwise are rejected.
+///
+/// # Arguments
+///
+/// * `presentation` - presentation to get holder of
+pub(crate) fn get_holder_did(presentation: &Map<String, Value>) -> Result<Option<&str>, String> {
+    match presentation.get("holder") {
+        Some(_) => get_did(presentation, "holder")
+            .map(Some)
+            .ok_or_else(|| "holder is no DID".to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Returns the value or `id` of given property, if it is a DID.
+fn get_did<'a>(document: &'a Map<String, Value>, property: &str) -> Option<&'a str> {
+    match document.get(property) {
+        Some(Value::Object(object)) => object.get("id").and_then(Value::as_str),
+        Some(value) => value.as_str(),
+        None => None,
+    }
+    .filter(|did| did.starts_with("did:"))
+}
+
+/// Encodes proof value components as base64url multibase of a tagged CBOR array.
+///
+/// # Arguments
+///
+/// * `tag` - CBOR tag identifying the kind of proof value
+/// * `components` - components of proof value
+pub(crate) fn encode_proof_value(
+    tag: u64,
+    components: Vec<CborValue>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(
+        &CborValue::Tag(tag, Box::new(CborValue::Array(components))),
+        &mut bytes,
+    )?;
+    Ok(format!("u{}", URL_SAFE_NO_PAD.encode(bytes)))
+}
+
+/// Decodes the components of a proof value encoded with
+/// [`encode_proof_value`](fn.encode_proof_value.html).
+///
+/// # Arguments
+///
+/// * `proof_value` - encoded proof value
+/// * `tag` - expected CBOR tag
+pub(crate) fn decode_proof_value(
+    proof_value: &str,
+    tag: u64,
+) -> Result<Vec<CborValue>, Box<dyn std::error::Error>> {
+    let bytes = URL_SAFE_NO_PAD.decode(
+        proof_value
+            .strip_prefix('u')
+            .ok_or("proof value is not base64url encoded")?,
+    )?;
+    match ciborium::de::from_reader(bytes.as_slice())? {
+        CborValue::Tag(found, components) if found == tag => match *components {
+            CborValue::Array(components) => Ok(components),
+            _ => Err(Box::from("invalid proof value")),
+        },
+        _ => Err(Box::from("unexpected proof value header")),
+    }
+}
+
+/// Decodes a CBOR array of JSON pointers.
+pub(crate) fn decode_pointers(
+    value: &CborValue,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    value
+        .as_array()
+        .and_then(|pointers| {
+            pointers
+                .iter()
+                .map(|pointer| pointer.as_text().map(|text| text.to_string()))
+                .collect::<Option<Vec<String>>>()
+        })
+        .ok_or_else(|| Box::from("invalid JSON pointers"))
+}
+
+/// Decodes a CBOR array of indexes.
+pub(crate) fn decode_indexes(value: &CborValue) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+    value
+        .as_array()
+        .and_then(|indexes| {
+            indexes
+                .iter()
+                .map(|index| {
+                    index
+                        .as_integer()
+                        .and_then(|index| usize::try_from(index).ok())
+                })
+                .collect::<Option<Vec<usize>>>()
+        })
+        .ok_or_else(|| Box::from("invalid indexes"))
+}
@@ -0,0 +1,94 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::Vade;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+
+/// Result of a DID resolution as described in the
+/// [DID resolution specification](https://w3c-ccg.github.io/did-resolution/#did-resolution-result).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidResolutionResult {
+    /// resolved DID document, `None` if resolution failed
+    #[serde(default)]
+    pub did_document: Option<Value>,
+    /// metadata about the resolution process, e.g. an `error` or the `contentType`
+    #[serde(default)]
+    pub did_resolution_metadata: Map<String, Value>,
+    /// metadata about the DID document, e.g. `created` or `deactivated`
+    #[serde(default)]
+    pub did_document_metadata: Map<String, Value>,
+}
+
+impl DidResolutionResult {
+    /// Error reported in the resolution metadata, e.g. "notFound" or "invalidDid".
+    pub fn error(&self) -> Option<&str> {
+        self.did_resolution_metadata
+            .get("error")
+            .and_then(|error| error.as_str())
+    }
+}
+
+/// Resolves DIDs with the plugins of the [`Vade`](https://docs.rs/vade/*/vade/struct.Vade.html)
+/// instance, that hosts a plugin. `Vade` hands it to all plugins with
+/// [`set_host_resolver`](https://docs.rs/vade/*/vade/trait.VadePlugin.html#method.set_host_resolver)
+/// and, while a plugin runs `vc_verify`, `vp_verify` or `vc_zkp_verify_proof`, lends it all other
+/// registered plugins. Verifying plugins can so resolve the DIDs of issuers and holders, whose
+/// DID methods are served by plugins registered next to them. Outside of these calls, no DIDs
+/// can be resolved.
+pub struct HostResolver {
+    resolver: RefCell<Option<Vade>>,
+}
+
+impl HostResolver {
+    pub(crate) fn new() -> Self {
+        HostResolver {
+            resolver: RefCell::new(None),
+        }
+    }
+
+    /// Resolves given DID with the plugins lent to the resolver. Returns the results of all
+    /// plugins, that handled the DID, like
+    /// [`Vade::did_resolve`](https://docs.rs/vade/*/vade/struct.Vade.html#method.did_resolve).
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to resolve
+    pub async fn did_resolve(
+        &self,
+        did: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let mut resolver = self
+            .take()
+            .ok_or("no plugins available to resolve DIDs with")?;
+        let results = resolver.did_resolve(did).await;
+        self.lend(Some(resolver));
+        results
+    }
+
+    /// Lends given `Vade` instance to the resolver, `None` leaves it without plugins.
+    pub(crate) fn lend(&self, resolver: Option<Vade>) {
+        *self.resolver.borrow_mut() = resolver;
+    }
+
+    /// Takes the lent `Vade` instance, if any, until it is given back with
+    /// [`lend`](#method.lend).
+    pub(crate) fn take(&self) -> Option<Vade> {
+        self.resolver.borrow_mut().take()
+    }
+}
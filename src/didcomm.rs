@@ -0,0 +1,1010 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! DIDComm Messaging v2 messages and their envelopes: plaintext messages, signed messages as
+//! JWS and encrypted messages as JWE in General JSON Serialization. Messages are encrypted with
+//! authcrypt (ECDH-1PU+A256KW, A256CBC-HS512) or anoncrypt (ECDH-ES+A256KW, A256CBC-HS512 or
+//! A256GCM) to X25519 or P-256 `keyAgreement` keys, that are resolved with `Vade::did_resolve`.
+
+use crate::{
+    crypto, jwk, jwt,
+    key_store::KeyReference,
+    multikey::{self, KeyType},
+    plugins::parse_options,
+    verification_method, Vade,
+};
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256, Sha512};
+use std::rc::Rc;
+
+const TYP_PLAIN: &str = "application/didcomm-plain+json";
+const TYP_SIGNED: &str = "application/didcomm-signed+json";
+const TYP_ENCRYPTED: &str = "application/didcomm-encrypted+json";
+const ALG_AUTHCRYPT: &str = "ECDH-1PU+A256KW";
+const ALG_ANONCRYPT: &str = "ECDH-ES+A256KW";
+const ENC_CBC_HS: &str = "A256CBC-HS512";
+const ENC_GCM: &str = "A256GCM";
+pub(crate) const PROBLEM_REPORT: &str = "https://didcomm.org/report-problem/2.0/problem-report";
+/// envelopes around a plaintext message: signed messages may be encrypted, encrypted messages
+/// may be wrapped in anoncrypt once more
+const MAX_NESTING: usize = 3;
+
+/// Plaintext DIDComm v2 message. Headers, that are not modeled explicitly, are kept in `headers`.
+///
+/// # Example
+///
+/// ```
+/// use serde_json::json;
+/// use vade::DidCommMessage;
+///
+/// let message = DidCommMessage::new(
+///     "https://didcomm.org/basicmessage/2.0/message",
+///     json!({ "content": "hello" }),
+/// )
+/// .with_from("did:example:alice")
+/// .with_to(&["did:example:bob"]);
+/// assert_eq!(message.to.as_ref().map(Vec::len), Some(1));
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DidCommMessage {
+    /// unique id of message
+    pub id: String,
+    /// media type of message, "application/didcomm-plain+json"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+    /// message type URI, e.g. `https://didcomm.org/basicmessage/2.0/message`
+    #[serde(rename = "type")]
+    pub message_type: String,
+    /// DID of sender
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// DIDs of recipients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<Vec<String>>,
+    /// id of thread, the message belongs to, defaults to the message's id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thid: Option<String>,
+    /// id of parent thread
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pthid: Option<String>,
+    /// creation time as unix timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_time: Option<i64>,
+    /// expiration time as unix timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_time: Option<i64>,
+    /// protocol specific content
+    #[serde(default)]
+    pub body: Value,
+    /// attachments as described by DIDComm v2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachments: Option<Vec<Value>>,
+    /// further headers
+    #[serde(flatten)]
+    pub headers: Map<String, Value>,
+}
+
+impl DidCommMessage {
+    /// Creates a new message with a random id and the current time as `created_time`.
+    ///
+    /// # Arguments
+    ///
+    /// * `message_type` - message type URI
+    /// * `body` - protocol specific content
+    pub fn new(message_type: &str, body: Value) -> Self {
+        DidCommMessage {
+            id: get_random_id(),
+            typ: Some(TYP_PLAIN.to_string()),
+            message_type: message_type.to_string(),
+            from: None,
+            to: None,
+            thid: None,
+            pthid: None,
+            created_time: Some(chrono::Utc::now().timestamp()),
+            expires_time: None,
+            body,
+            attachments: None,
+            headers: Map::new(),
+        }
+    }
+
+    /// Sets the sender of message.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - DID of sender
+    pub fn with_from(mut self, from: &str) -> Self {
+        self.from = Some(from.to_string());
+        self
+    }
+
+    /// Sets the recipients of message.
+    ///
+    /// # Arguments
+    ///
+    /// * `to` - DIDs of recipients
+    pub fn with_to(mut self, to: &[&str]) -> Self {
+        self.to = Some(to.iter().map(|to| to.to_string()).collect());
+        self
+    }
+
+    /// Sets the thread of message.
+    ///
+    /// # Arguments
+    ///
+    /// * `thid` - id of thread
+    pub fn with_thid(mut self, thid: &str) -> Self {
+        self.thid = Some(thid.to_string());
+        self
+    }
+
+    /// Creates a reply to this message in the same thread, sent from its first recipient to its
+    /// sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `message_type` - message type URI of reply
+    /// * `body` - protocol specific content
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::DidCommMessage;
+    /// use serde_json::json;
+    ///
+    /// let ping = DidCommMessage::new("https://didcomm.org/trust-ping/2.0/ping", json!({}))
+    ///     .with_from("did:example:alice")
+    ///     .with_to(&["did:example:bob"]);
+    /// let response = ping.reply("https://didcomm.org/trust-ping/2.0/ping-response", json!({}));
+    /// assert_eq!(response.thid, Some(ping.id));
+    /// assert_eq!(response.to, Some(vec!["did:example:alice".to_string()]));
+    /// ```
+    pub fn reply(&self, message_type: &str, body: Value) -> Self {
+        DidCommMessage {
+            from: self.to.as_ref().and_then(|to| to.first().cloned()),
+            to: self.from.as_ref().map(|from| vec![from.to_string()]),
+            thid: Some(self.thid.clone().unwrap_or_else(|| self.id.clone())),
+            ..DidCommMessage::new(message_type, body)
+        }
+    }
+
+    /// Creates a `problem-report` reply to this message, that references this message's thread
+    /// as parent thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - problem code, e.g. "e.p.msg.unsupported-protocol"
+    /// * `comment` - human readable description, that may contain placeholders like `{1}` for
+    ///   `args`
+    /// * `args` - values for placeholders in `comment`
+    pub fn problem_report(&self, code: &str, comment: &str, args: &[&str]) -> Self {
+        let mut body = json!({ "code": code, "comment": comment });
+        if !args.is_empty() {
+            body["args"] = json!(args);
+        }
+        DidCommMessage {
+            pthid: Some(self.thid.clone().unwrap_or_else(|| self.id.clone())),
+            ..self.reply(PROBLEM_REPORT, body)
+        }
+    }
+
+    /// Parses a plaintext message.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - message as JSON
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(serde_json::from_str(json).map_err(|e| format!("invalid DIDComm message; {}", e))?)
+    }
+}
+
+/// Information about how a message has been packed, as found while unpacking it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnpackMetadata {
+    /// `true` if message has been encrypted
+    pub encrypted: bool,
+    /// `true` if the sender has been authenticated with authcrypt
+    pub authenticated: bool,
+    /// `true` if message has been signed by its sender
+    pub non_repudiation: bool,
+    /// `true` if message has been encrypted with anoncrypt only
+    pub anonymous_sender: bool,
+    /// `keyAgreement` key of sender, if encrypted with authcrypt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_from_kid: Option<String>,
+    /// `keyAgreement` key, the message has been decrypted with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_to_kid: Option<String>,
+    /// key, the message has been signed with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sign_from: Option<String>,
+    /// content encryption algorithm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enc: Option<String>,
+}
+
+/// Result of `Vade::didcomm_unpack`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnpackedMessage {
+    /// plaintext message
+    pub message: DidCommMessage,
+    /// how message has been packed
+    pub metadata: UnpackMetadata,
+}
+
+/// Key of sender or signer, given as `kid` DID URL and either `secretKey` or key store `keyId`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyOptions {
+    kid: String,
+    secret_key: Option<String>,
+    key_id: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackOptions {
+    /// "plaintext", "signed", "anoncrypt" or "authcrypt", defaults to "authcrypt" if a sender is
+    /// given and to "anoncrypt" otherwise
+    packing: Option<String>,
+    /// `keyAgreement` key of sender for authcrypt
+    sender: Option<KeyOptions>,
+    /// `authentication` key of sender to sign message with before encrypting it
+    signer: Option<KeyOptions>,
+    /// content encryption algorithm for anoncrypt
+    enc: Option<String>,
+    /// `true` to wrap authcrypted messages in anoncrypt to hide the sender from intermediaries
+    protect_sender: Option<bool>,
+    /// DIDs or DID URLs of keys to encrypt for, default to the message's `to`
+    to: Option<Vec<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UnpackOptions {
+    /// secret key to decrypt with, keys of the registered key store are used otherwise
+    secret_key: Option<String>,
+    /// id of key in key store to decrypt with
+    key_id: Option<String>,
+}
+
+/// Resolved `keyAgreement` key of a recipient.
+struct RecipientKey {
+    kid: String,
+    key_type: KeyType,
+    public_key: Vec<u8>,
+}
+
+/// Packs given plaintext message according to given options.
+///
+/// # Arguments
+///
+/// * `vade` - `Vade` instance to resolve DIDs and get keys from
+/// * `options` - JSON with `packing`, `sender`, `signer`, `enc`, `protectSender` and `to`
+/// * `message` - plaintext message as JSON
+pub(crate) async fn pack(
+    vade: &mut Vade,
+    options: &str,
+    message: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let options: PackOptions = parse_options(options)?;
+    let mut message = DidCommMessage::from_json(message)?;
+    message.typ = Some(TYP_PLAIN.to_string());
+    let packing = options.packing.clone().unwrap_or_else(|| {
+        match options.sender {
+            Some(_) => "authcrypt",
+            None => "anoncrypt",
+        }
+        .to_string()
+    });
+    let plaintext = serde_json::to_string(&message)?;
+    let inner = match (packing.as_str(), &options.signer) {
+        ("plaintext", _) => return Ok(plaintext),
+        ("signed", None) => return Err(Box::from("signed messages require a signer")),
+        (_, Some(signer)) => sign(vade, &message, signer, &plaintext).await?,
+        (_, None) => plaintext,
+    };
+    if packing == "signed" {
+        return Ok(inner);
+    }
+
+    let recipients = match &options.to {
+        Some(to) => to.clone(),
+        None => message.to.clone().unwrap_or_default(),
+    };
+    match packing.as_str() {
+        "authcrypt" => {
+            let sender = options
+                .sender
+                .as_ref()
+                .ok_or("authcrypt requires a sender key")?;
+            if options.enc.as_deref().is_some_and(|enc| enc != ENC_CBC_HS) {
+                return Err(Box::from(format!("authcrypt requires {}", ENC_CBC_HS)));
+            }
+            let authcrypted = encrypt(
+                vade,
+                &message,
+                &recipients,
+                Some(sender),
+                ENC_CBC_HS,
+                &inner,
+            )
+            .await?;
+            if options.protect_sender != Some(true) {
+                return Ok(authcrypted);
+            }
+            encrypt(vade, &message, &recipients, None, ENC_CBC_HS, &authcrypted).await
+        }
+        "anoncrypt" => {
+            let enc = options.enc.as_deref().unwrap_or(ENC_CBC_HS);
+            encrypt(vade, &message, &recipients, None, enc, &inner).await
+        }
+        packing => Err(Box::from(format!(r#"unsupported packing "{}""#, packing))),
+    }
+}
+
+/// Unpacks given message, that may be plaintext, signed or encrypted, and checks, that senders
+/// and signers match the `from` of the plaintext message.
+///
+/// # Arguments
+///
+/// * `vade` - `Vade` instance to resolve DIDs and get keys from
+/// * `options` - JSON with optional `secretKey` or `keyId` to decrypt with
+/// * `message` - packed message
+pub(crate) async fn unpack(
+    vade: &mut Vade,
+    options: &str,
+    message: &str,
+) -> Result<UnpackedMessage, Box<dyn std::error::Error>> {
+    let options: UnpackOptions = parse_options(options)?;
+    let mut metadata = UnpackMetadata::default();
+    let mut current = message.to_string();
+    // one pass per envelope and a last one for the plaintext message
+    for _ in 0..=MAX_NESTING {
+        let envelope: Value = serde_json::from_str(&current)
+            .map_err(|e| format!("invalid DIDComm message; {}", e))?;
+        if envelope.get("ciphertext").is_some() {
+            current = decrypt(vade, &options, &envelope, &mut metadata).await?;
+        } else if envelope.get("signatures").is_some() {
+            let (plaintext, kid) = verify(vade, &envelope).await?;
+            metadata.non_repudiation = true;
+            metadata.sign_from = Some(kid);
+            current = plaintext;
+        } else {
+            let message = DidCommMessage::from_json(&current)?;
+            check_sender(&message, &metadata)?;
+            return Ok(UnpackedMessage { message, metadata });
+        }
+    }
+    Err(Box::from("DIDComm message is nested too deeply"))
+}
+
+/// Creates a JWS in General JSON Serialization of given plaintext message.
+async fn sign(
+    vade: &mut Vade,
+    message: &DidCommMessage,
+    signer: &KeyOptions,
+    plaintext: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let from = message
+        .from
+        .as_deref()
+        .ok_or("signed messages require a from")?;
+    let key = get_key(vade, signer).await?;
+    let method = verification_method::resolve_authorized_method(
+        Some(vade),
+        &signer.kid,
+        Some(from),
+        "authentication",
+    )
+    .await?;
+    check_public_key(&method, key.info().public_key.as_str())?;
+    let (alg, _) = jwt::get_algorithm(key.info().key_type)?;
+    let protected = URL_SAFE_NO_PAD.encode(json!({ "typ": TYP_SIGNED, "alg": alg }).to_string());
+    let payload = URL_SAFE_NO_PAD.encode(plaintext);
+    let signature = key
+        .sign(format!("{}.{}", protected, payload).as_bytes())
+        .await?;
+    Ok(json!({
+        "payload": payload,
+        "signatures": [{
+            "protected": protected,
+            "signature": URL_SAFE_NO_PAD.encode(signature),
+            "header": { "kid": signer.kid },
+        }],
+    })
+    .to_string())
+}
+
+/// Verifies a JWS in General JSON Serialization and returns its payload and the signer's key.
+async fn verify(
+    vade: &mut Vade,
+    envelope: &Value,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let payload = envelope["payload"]
+        .as_str()
+        .ok_or("signed DIDComm message has no payload")?;
+    let signature = &envelope["signatures"][0];
+    let protected = signature["protected"]
+        .as_str()
+        .ok_or("signed DIDComm message has no protected header")?;
+    let kid = signature["header"]["kid"]
+        .as_str()
+        .ok_or("signed DIDComm message has no kid")?;
+    let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(protected)?)?;
+    let signer = kid.split('#').next().unwrap_or_default();
+    let (key_type, public_key) =
+        jwt::resolve_signer_key(Some(vade), kid, signer, "authentication").await?;
+    let envelope = jwt::Envelope {
+        alg: header["alg"].as_str().unwrap_or_default().to_string(),
+        kid: kid.to_string(),
+        typ: header["typ"].as_str().unwrap_or_default().to_string(),
+        payload: URL_SAFE_NO_PAD.decode(payload)?,
+        signing_input: format!("{}.{}", protected, payload).into_bytes(),
+        signature: URL_SAFE_NO_PAD.decode(signature["signature"].as_str().unwrap_or_default())?,
+    };
+    jwt::verify_signature(&envelope, key_type, &public_key)
+        .map_err(|e| format!("invalid signed DIDComm message; {}", e))?;
+    Ok((String::from_utf8(envelope.payload)?, kid.to_string()))
+}
+
+/// Encrypts given content for all `keyAgreement` keys of given recipients.
+async fn encrypt(
+    vade: &mut Vade,
+    message: &DidCommMessage,
+    recipients: &[String],
+    sender: Option<&KeyOptions>,
+    enc: &str,
+    content: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if recipients.is_empty() {
+        return Err(Box::from(
+            "encrypted messages require at least one recipient",
+        ));
+    }
+    let sender = match sender {
+        Some(sender) => {
+            let from = message.from.as_deref().ok_or("authcrypt requires a from")?;
+            let key = get_key(vade, sender).await?;
+            let method = verification_method::resolve_authorized_method(
+                Some(vade),
+                &sender.kid,
+                Some(from),
+                "keyAgreement",
+            )
+            .await?;
+            check_public_key(&method, &key.info().public_key)?;
+            Some((sender.kid.as_str(), key))
+        }
+        None => None,
+    };
+    let key_type = sender.as_ref().map(|(_, key)| key.info().key_type);
+    let keys = resolve_recipient_keys(vade, recipients, key_type).await?;
+    let key_type = keys[0].key_type;
+
+    let ephemeral_secret = crypto::generate_secret_key(key_type)?;
+    let ephemeral_public = crypto::get_public_key(key_type, &ephemeral_secret)?;
+    let mut kids: Vec<&str> = keys.iter().map(|key| key.kid.as_str()).collect();
+    kids.sort_unstable();
+    let apv = Sha256::digest(kids.join(".").as_bytes()).to_vec();
+    let mut header = json!({
+        "typ": TYP_ENCRYPTED,
+        "alg": if sender.is_some() { ALG_AUTHCRYPT } else { ALG_ANONCRYPT },
+        "enc": enc,
+        "apv": URL_SAFE_NO_PAD.encode(&apv),
+        "epk": jwk::public_key_to_jwk(key_type, &ephemeral_public)?,
+    });
+    if let Some((kid, _)) = &sender {
+        header["skid"] = Value::from(*kid);
+        header["apu"] = Value::from(URL_SAFE_NO_PAD.encode(kid));
+    }
+    let protected = URL_SAFE_NO_PAD.encode(header.to_string());
+
+    let mut cek = vec![0u8; get_key_length(enc)?];
+    OsRng.fill_bytes(&mut cek);
+    let (iv, ciphertext, tag) =
+        encrypt_content(enc, &cek, protected.as_bytes(), content.as_bytes())?;
+
+    let mut encrypted_keys = Vec::new();
+    for key in keys.iter() {
+        let public_key = multikey::encode_public_key(key.key_type, &key.public_key);
+        let mut secret = crypto::agree(key_type, &ephemeral_secret, &key.public_key)?;
+        let (alg, apu, cc_tag) = match &sender {
+            Some((kid, sender_key)) => {
+                secret.extend_from_slice(&sender_key.agree(&public_key).await?);
+                (ALG_AUTHCRYPT, kid.as_bytes(), tag.as_slice())
+            }
+            None => (ALG_ANONCRYPT, &b""[..], &b""[..]),
+        };
+        let kek = concat_kdf(&secret, alg, apu, &apv, cc_tag);
+        let encrypted_key = aes_kw::KekAes256::new(&kek.into()).wrap_vec(&cek)?;
+        encrypted_keys.push(json!({
+            "header": { "kid": key.kid },
+            "encrypted_key": URL_SAFE_NO_PAD.encode(encrypted_key),
+        }));
+    }
+
+    Ok(json!({
+        "protected": protected,
+        "recipients": encrypted_keys,
+        "iv": URL_SAFE_NO_PAD.encode(iv),
+        "ciphertext": URL_SAFE_NO_PAD.encode(ciphertext),
+        "tag": URL_SAFE_NO_PAD.encode(tag),
+    })
+    .to_string())
+}
+
+/// Decrypts a JWE in General JSON Serialization with one of the recipient keys, that are held in
+/// key store or given as option.
+async fn decrypt(
+    vade: &mut Vade,
+    options: &UnpackOptions,
+    envelope: &Value,
+    metadata: &mut UnpackMetadata,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let get = |name: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let value = envelope[name]
+            .as_str()
+            .ok_or_else(|| format!(r#"encrypted DIDComm message has no "{}""#, name))?;
+        Ok(URL_SAFE_NO_PAD.decode(value)?)
+    };
+    let protected = envelope["protected"]
+        .as_str()
+        .ok_or("encrypted DIDComm message has no protected header")?;
+    let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(protected)?)?;
+    let alg = header["alg"].as_str().unwrap_or_default();
+    let enc = header["enc"].as_str().unwrap_or_default();
+    if alg != ALG_AUTHCRYPT && alg != ALG_ANONCRYPT {
+        return Err(Box::from(format!(r#"unsupported algorithm "{}""#, alg)));
+    }
+    if alg == ALG_AUTHCRYPT && enc != ENC_CBC_HS {
+        return Err(Box::from(format!("authcrypt requires {}", ENC_CBC_HS)));
+    }
+    let recipients = envelope["recipients"]
+        .as_array()
+        .ok_or("encrypted DIDComm message has no recipients")?;
+    let mut kids: Vec<&str> = recipients
+        .iter()
+        .filter_map(|recipient| recipient["header"]["kid"].as_str())
+        .collect();
+    kids.sort_unstable();
+    let apv = Sha256::digest(kids.join(".").as_bytes()).to_vec();
+    if header["apv"].as_str() != Some(&URL_SAFE_NO_PAD.encode(&apv)) {
+        return Err(Box::from(
+            "apv does not match recipients of encrypted DIDComm message",
+        ));
+    }
+    let (epk_type, epk) = jwk::public_key_from_jwk(&header["epk"])?;
+    let epk = multikey::encode_public_key(epk_type, &epk);
+
+    let sender = match alg {
+        ALG_AUTHCRYPT => {
+            let skid = header["skid"]
+                .as_str()
+                .ok_or("authcrypt message has no skid")?;
+            if header["apu"].as_str() != Some(&URL_SAFE_NO_PAD.encode(skid)) {
+                return Err(Box::from(
+                    "apu does not match skid of encrypted DIDComm message",
+                ));
+            }
+            let method = verification_method::resolve_authorized_method(
+                Some(vade),
+                skid,
+                None,
+                "keyAgreement",
+            )
+            .await?;
+            let (key_type, public_key) = verification_method::get_public_key(&method)?;
+            Some((
+                skid.to_string(),
+                multikey::encode_public_key(key_type, &public_key),
+            ))
+        }
+        _ => None,
+    };
+
+    let (kid, key, encrypted_key) = find_recipient_key(vade, options, recipients).await?;
+    let mut secret = key.agree(&epk).await?;
+    let tag = get("tag")?;
+    let (apu, cc_tag) = match &sender {
+        Some((skid, sender_key)) => {
+            secret.extend_from_slice(&key.agree(sender_key).await?);
+            (skid.as_bytes(), tag.as_slice())
+        }
+        None => (&b""[..], &b""[..]),
+    };
+    let kek = concat_kdf(&secret, alg, apu, &apv, cc_tag);
+    let cek = aes_kw::KekAes256::new(&kek.into())
+        .unwrap_vec(&encrypted_key)
+        .map_err(|_| "could not decrypt content encryption key")?;
+    let content = decrypt_content(
+        enc,
+        &cek,
+        protected.as_bytes(),
+        &get("iv")?,
+        &get("ciphertext")?,
+        &tag,
+    )?;
+
+    if metadata.encrypted {
+        // the outer envelope has been an anoncrypt wrapper, keep the inner envelope's metadata
+        metadata.anonymous_sender = false;
+    }
+    metadata.encrypted = true;
+    metadata.enc = Some(enc.to_string());
+    metadata.encrypted_to_kid = Some(kid);
+    match sender {
+        Some((skid, _)) => {
+            metadata.authenticated = true;
+            metadata.encrypted_from_kid = Some(skid);
+        }
+        None if !metadata.authenticated => metadata.anonymous_sender = true,
+        None => (),
+    }
+    Ok(String::from_utf8(content)?)
+}
+
+/// Checks that authcrypt senders and signers are keys of the message's `from`.
+fn check_sender(
+    message: &DidCommMessage,
+    metadata: &UnpackMetadata,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for kid in [&metadata.encrypted_from_kid, &metadata.sign_from]
+        .iter()
+        .filter_map(|kid| kid.as_ref())
+    {
+        let did = kid.split('#').next().unwrap_or_default();
+        if message.from.as_deref() != Some(did) {
+            return Err(Box::from(format!(
+                r#"key "{}" does not belong to sender "{}""#,
+                kid,
+                message.from.as_deref().unwrap_or_default()
+            )));
+        }
+    }
+    Ok(())
+}
+
+async fn get_key(
+    vade: &Vade,
+    options: &KeyOptions,
+) -> Result<KeyReference, Box<dyn std::error::Error>> {
+    KeyReference::from_options(
+        vade.key_store.as_ref(),
+        options.secret_key.as_deref(),
+        options.key_id.as_deref(),
+    )
+    .await
+}
+
+fn check_public_key(method: &Value, public_key: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (key_type, method_key) = verification_method::get_public_key(method)?;
+    if multikey::encode_public_key(key_type, &method_key) != public_key {
+        return Err(Box::from(format!(
+            r#"key does not match verification method "{}""#,
+            method["id"].as_str().unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+/// Resolves the `keyAgreement` keys of given DIDs or DID URLs, that are of given type or, if not
+/// given, the type of the first key found.
+async fn resolve_recipient_keys(
+    vade: &mut Vade,
+    recipients: &[String],
+    mut key_type: Option<KeyType>,
+) -> Result<Vec<RecipientKey>, Box<dyn std::error::Error>> {
+    let mut keys = Vec::new();
+    for recipient in recipients.iter() {
+        let methods = if recipient.contains('#') {
+            vec![
+                verification_method::resolve_authorized_method(
+                    Some(vade),
+                    recipient,
+                    None,
+                    "keyAgreement",
+                )
+                .await?,
+            ]
+        } else {
+            let document = verification_method::resolve_did_document(Some(vade), recipient).await?;
+            get_key_agreement_methods(&document)?
+        };
+        let mut found = false;
+        for method in methods.iter() {
+            let (method_type, public_key) = match verification_method::get_public_key(method) {
+                Ok(key) => key,
+                Err(_) => continue,
+            };
+            if method_type != KeyType::X25519 && method_type != KeyType::P256 {
+                continue;
+            }
+            if *key_type.get_or_insert(method_type) != method_type {
+                continue;
+            }
+            found = true;
+            keys.push(RecipientKey {
+                kid: method["id"].as_str().unwrap_or_default().to_string(),
+                key_type: method_type,
+                public_key,
+            });
+        }
+        if !found {
+            return Err(Box::from(format!(
+                r#""{}" has no {} keyAgreement key"#,
+                recipient,
+                match key_type {
+                    Some(key_type) => format!("{:?}", key_type),
+                    None => "X25519 or P-256".to_string(),
+                }
+            )));
+        }
+    }
+    Ok(keys)
+}
+
+/// Returns the `keyAgreement` verification methods of given DID document with absolute ids.
+fn get_key_agreement_methods(document: &Value) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let document_id = document["id"].as_str().unwrap_or_default();
+    let absolute = |id: &str| match id.starts_with('#') {
+        true => format!("{}{}", document_id, id),
+        false => id.to_string(),
+    };
+    let mut methods = Vec::new();
+    for entry in document["keyAgreement"].as_array().into_iter().flatten() {
+        let mut method = match entry {
+            Value::String(reference) => {
+                verification_method::find_verification_method(document, &absolute(reference))?.0
+            }
+            method => method.clone(),
+        };
+        method["id"] = Value::from(absolute(method["id"].as_str().unwrap_or_default()));
+        methods.push(method);
+    }
+    Ok(methods)
+}
+
+/// Finds the first recipient of an encrypted message, whose key is given as option or held in
+/// the registered key store.
+async fn find_recipient_key(
+    vade: &mut Vade,
+    options: &UnpackOptions,
+    recipients: &[Value],
+) -> Result<(String, KeyReference, Vec<u8>), Box<dyn std::error::Error>> {
+    let key_store = vade.key_store.as_ref().map(Rc::clone);
+    let given = match (&options.secret_key, &options.key_id) {
+        (None, None) => None,
+        (secret_key, key_id) => Some(
+            KeyReference::from_options(
+                key_store.as_ref(),
+                secret_key.as_deref(),
+                key_id.as_deref(),
+            )
+            .await?,
+        ),
+    };
+    let stored = match (&given, &key_store) {
+        (None, Some(key_store)) => key_store.list_keys().await?,
+        _ => Vec::new(),
+    };
+    let mut given = given;
+    for recipient in recipients.iter() {
+        let kid = recipient["header"]["kid"].as_str().unwrap_or_default();
+        let method = match verification_method::resolve_authorized_method(
+            Some(vade),
+            kid,
+            None,
+            "keyAgreement",
+        )
+        .await
+        {
+            Ok(method) => method,
+            Err(_) => continue,
+        };
+        let (key_type, public_key) = verification_method::get_public_key(&method)?;
+        let public_key = multikey::encode_public_key(key_type, &public_key);
+        let key = match given.take() {
+            Some(key) if key.info().public_key == public_key => Some(key),
+            Some(key) => {
+                given = Some(key);
+                None
+            }
+            None => stored
+                .iter()
+                .find(|key| key.public_key == public_key)
+                .zip(key_store.as_ref())
+                .map(|(info, key_store)| {
+                    KeyReference::KeyStore(Rc::clone(key_store), info.clone())
+                }),
+        };
+        if let Some(key) = key {
+            let encrypted_key = URL_SAFE_NO_PAD.decode(
+                recipient["encrypted_key"]
+                    .as_str()
+                    .ok_or("recipient has no encrypted_key")?,
+            )?;
+            return Ok((kid.to_string(), key, encrypted_key));
+        }
+    }
+    Err(Box::from("no key found to decrypt DIDComm message with"))
+}
+
+fn get_key_length(enc: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    match enc {
+        ENC_CBC_HS => Ok(64),
+        ENC_GCM => Ok(32),
+        enc => Err(Box::from(format!(
+            r#"unsupported content encryption "{}""#,
+            enc
+        ))),
+    }
+}
+
+/// Encrypts content and returns the initialization vector, ciphertext and authentication tag.
+#[allow(clippy::type_complexity)]
+fn encrypt_content(
+    enc: &str,
+    cek: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    match enc {
+        ENC_CBC_HS => {
+            let mut iv = vec![0u8; 16];
+            OsRng.fill_bytes(&mut iv);
+            let (mac_key, enc_key) = cek.split_at(32);
+            let ciphertext = cbc::Encryptor::<aes::Aes256>::new_from_slices(enc_key, &iv)
+                .map_err(|e| e.to_string())?
+                .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+            let tag = get_cbc_hs_mac(mac_key, aad, &iv, &ciphertext)?
+                .finalize()
+                .into_bytes()[..32]
+                .to_vec();
+            Ok((iv, ciphertext, tag))
+        }
+        ENC_GCM => {
+            let mut iv = vec![0u8; 12];
+            OsRng.fill_bytes(&mut iv);
+            let mut ciphertext = Aes256Gcm::new_from_slice(cek)
+                .map_err(|e| e.to_string())?
+                .encrypt(
+                    Nonce::from_slice(&iv),
+                    Payload {
+                        msg: plaintext,
+                        aad,
+                    },
+                )
+                .map_err(|_| "could not encrypt DIDComm message")?;
+            let tag = ciphertext.split_off(ciphertext.len() - 16);
+            Ok((iv, ciphertext, tag))
+        }
+        enc => Err(Box::from(format!(
+            r#"unsupported content encryption "{}""#,
+            enc
+        ))),
+    }
+}
+
+fn decrypt_content(
+    enc: &str,
+    cek: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if cek.len() != get_key_length(enc)? {
+        return Err(Box::from("content encryption key has an invalid length"));
+    }
+    let (iv_length, tag_length) = match enc {
+        ENC_CBC_HS => (16, 32),
+        _ => (12, 16),
+    };
+    if iv.len() != iv_length {
+        return Err(Box::from("initialization vector has an invalid length"));
+    }
+    if tag.len() != tag_length {
+        return Err(Box::from("authentication tag has an invalid length"));
+    }
+    match enc {
+        ENC_CBC_HS => {
+            let (mac_key, enc_key) = cek.split_at(32);
+            get_cbc_hs_mac(mac_key, aad, iv, ciphertext)?
+                .verify_truncated_left(tag)
+                .map_err(|_| "could not decrypt DIDComm message")?;
+            Ok(cbc::Decryptor::<aes::Aes256>::new_from_slices(enc_key, iv)
+                .map_err(|e| e.to_string())?
+                .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+                .map_err(|_| "could not decrypt DIDComm message")?)
+        }
+        _ => {
+            let mut ciphertext = ciphertext.to_vec();
+            ciphertext.extend_from_slice(tag);
+            Ok(Aes256Gcm::new_from_slice(cek)
+                .map_err(|e| e.to_string())?
+                .decrypt(
+                    Nonce::from_slice(iv),
+                    Payload {
+                        msg: &ciphertext,
+                        aad,
+                    },
+                )
+                .map_err(|_| "could not decrypt DIDComm message")?)
+        }
+    }
+}
+
+/// Returns the HMAC of A256CBC-HS512 as described in RFC 7518, section 5.2.2.1.
+fn get_cbc_hs_mac(
+    mac_key: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+) -> Result<Hmac<Sha512>, Box<dyn std::error::Error>> {
+    let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(mac_key)?;
+    mac.update(aad);
+    mac.update(iv);
+    mac.update(ciphertext);
+    mac.update(&((aad.len() as u64) * 8).to_be_bytes());
+    Ok(mac)
+}
+
+/// Derives the 256 bit key wrapping key with the Concat KDF of NIST SP 800-56A. For ECDH-1PU the
+/// tag of the encrypted content is appended to `SuppPubInfo`.
+fn concat_kdf(secret: &[u8], alg: &str, apu: &[u8], apv: &[u8], cc_tag: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(1u32.to_be_bytes());
+    hasher.update(secret);
+    for value in [alg.as_bytes(), apu, apv].iter() {
+        hasher.update((value.len() as u32).to_be_bytes());
+        hasher.update(value);
+    }
+    hasher.update(256u32.to_be_bytes());
+    if !cc_tag.is_empty() {
+        hasher.update((cc_tag.len() as u32).to_be_bytes());
+        hasher.update(cc_tag);
+    }
+    hasher.finalize().into()
+}
+
+/// Returns a random UUID v4.
+pub(crate) fn get_random_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
@@ -0,0 +1,659 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Routing of received DIDComm messages to handlers registered per protocol (PIURI) or message
+//! type. Versions are matched by DIDComm rules: major versions have to be equal, messages with
+//! other minor versions are handled by the handler for the highest minor version available.
+//! Protocol engines share their thread handling with [`Threads`].
+
+use crate::{
+    didcomm::{self, DidCommMessage, UnpackedMessage, PROBLEM_REPORT},
+    plugins::parse_options,
+    Clock, ThreadStore, Vade,
+};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::rc::Rc;
+
+/// Handles received DIDComm messages of one or more protocols. Handlers are registered on `Vade`
+/// with
+/// [`register_didcomm_handler`](https://docs.rs/vade/*/vade/struct.Vade.html#method.register_didcomm_handler)
+/// and called by `didcomm_receive` for messages of their protocols.
+///
+/// # Example
+///
+/// ```
+/// use async_trait::async_trait;
+/// use serde_json::json;
+/// use vade::{DidCommHandler, DidCommMessage, UnpackedMessage, Vade};
+///
+/// struct TrustPing;
+///
+/// #[async_trait(?Send)]
+/// impl DidCommHandler for TrustPing {
+///     fn message_types(&self) -> Vec<String> {
+///         vec!["https://didcomm.org/trust-ping/2.0/ping".to_string()]
+///     }
+///
+///     async fn handle(
+///         &self,
+///         _vade: &mut Vade,
+///         unpacked: &UnpackedMessage,
+///     ) -> Result<Option<DidCommMessage>, Box<dyn std::error::Error>> {
+///         Ok(Some(unpacked.message.reply(
+///             "https://didcomm.org/trust-ping/2.0/ping-response",
+///             json!({}),
+///         )))
+///     }
+/// }
+/// ```
+#[async_trait(?Send)]
+pub trait DidCommHandler {
+    /// Returns the protocols (PIURIs like `https://didcomm.org/basicmessage/2.0`) and message
+    /// types (like `https://didcomm.org/trust-ping/2.0/ping`) handled by this handler.
+    fn message_types(&self) -> Vec<String>;
+
+    /// Handles a received message and returns an optional reply to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance, the message has been received with
+    /// * `unpacked` - unpacked message with metadata about how it has been packed
+    async fn handle(
+        &self,
+        vade: &mut Vade,
+        unpacked: &UnpackedMessage,
+    ) -> Result<Option<DidCommMessage>, Box<dyn std::error::Error>>;
+}
+
+/// Result of an action of a protocol engine, that has sent a message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtocolAction<T> {
+    /// updated state of thread
+    pub exchange: T,
+    /// results of `didcomm_send` for the sent message
+    pub results: Vec<Option<String>>,
+}
+
+/// Message type URI split into protocol, version and message name,
+/// e.g. `https://didcomm.org/trust-ping/2.0/ping`.
+struct MessageType {
+    /// document URI and protocol name, e.g. `https://didcomm.org/trust-ping`
+    protocol: String,
+    major: u32,
+    minor: u32,
+    /// message name, `None` for PIURIs
+    name: Option<String>,
+}
+
+impl MessageType {
+    fn parse(uri: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let invalid = || format!(r#"invalid DIDComm message type "{}""#, uri);
+        let (rest, last) = uri.rsplit_once('/').ok_or_else(invalid)?;
+        let (protocol, version, name) = match parse_version(last) {
+            Some(version) => (rest, version, None),
+            None => {
+                let (protocol, version) = rest.rsplit_once('/').ok_or_else(invalid)?;
+                (
+                    protocol,
+                    parse_version(version).ok_or_else(invalid)?,
+                    Some(last.to_string()),
+                )
+            }
+        };
+        if protocol.is_empty() || name.as_deref() == Some("") {
+            return Err(Box::from(invalid()));
+        }
+        Ok(MessageType {
+            protocol: protocol.to_string(),
+            major: version.0,
+            minor: version.1,
+            name,
+        })
+    }
+
+    fn version(&self) -> String {
+        format!("{}.{}", self.major, self.minor)
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+#[derive(Default, Deserialize)]
+struct ReceiveOptions {
+    /// options for `didcomm::pack` to pack replies with, replies are returned as plaintext
+    /// otherwise
+    pack: Option<Value>,
+}
+
+/// Outcome of routing a received message.
+pub(crate) enum Routed {
+    /// message has been handled, contains the reply, if any
+    Handled(Option<String>),
+    /// no handler supports the message, contains a `problem-report` for its sender, if it can
+    /// be replied to
+    Unsupported(Option<String>),
+}
+
+/// Checks, that all message types of given handler are valid PIURIs or message type URIs.
+pub(crate) fn check_handler(
+    handler: &dyn DidCommHandler,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for message_type in handler.message_types() {
+        MessageType::parse(&message_type)?;
+    }
+    Ok(())
+}
+
+/// Unpacks given message and passes it to the best matching registered handler. Returns `None`
+/// if no handlers are registered or the message cannot be unpacked or has no valid type, so it is
+/// left to plugins.
+///
+/// # Arguments
+///
+/// * `vade` - `Vade` instance with registered handlers
+/// * `options` - JSON with options for `didcomm::unpack` and optional `pack` options to pack
+///   replies with
+/// * `message` - received message
+pub(crate) async fn route(
+    vade: &mut Vade,
+    options: &str,
+    message: &str,
+) -> Result<Option<Routed>, Box<dyn std::error::Error>> {
+    if vade.didcomm_handlers.is_empty() {
+        return Ok(None);
+    }
+    let unpacked = match didcomm::unpack(vade, options, message).await {
+        Ok(unpacked) => unpacked,
+        Err(_) => return Ok(None),
+    };
+    let received = &unpacked.message;
+    let message_type = match MessageType::parse(&received.message_type) {
+        Ok(message_type) => message_type,
+        Err(_) => return Ok(None),
+    };
+    let handlers: Vec<(MessageType, Rc<dyn DidCommHandler>)> = vade
+        .didcomm_handlers
+        .iter()
+        .flat_map(|handler| {
+            handler
+                .message_types()
+                .iter()
+                .filter_map(|uri| MessageType::parse(uri).ok())
+                .filter(|supported| supported.protocol == message_type.protocol)
+                .map(|supported| (supported, Rc::clone(handler)))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let same_major: Vec<&(MessageType, Rc<dyn DidCommHandler>)> = handlers
+        .iter()
+        .filter(|(supported, _)| supported.major == message_type.major)
+        .collect();
+    let handler = same_major
+        .iter()
+        .rev()
+        .filter(|(supported, _)| supported.name.is_none() || supported.name == message_type.name)
+        .max_by_key(|(supported, _)| {
+            (
+                supported.minor == message_type.minor,
+                supported.minor,
+                supported.name.is_some(),
+            )
+        });
+    let problem = match (handler, handlers.is_empty(), same_major.is_empty()) {
+        (Some((_, handler)), _, _) => {
+            let handler = Rc::clone(handler);
+            let reply = handler.handle(vade, &unpacked).await?;
+            return Ok(Some(Routed::Handled(match reply {
+                Some(reply) => Some(serialize_reply(vade, options, &reply).await?),
+                None => None,
+            })));
+        }
+        (None, true, _) => received.problem_report(
+            "e.p.msg.unsupported-protocol",
+            "Protocol {1} is not supported.",
+            &[&message_type.protocol],
+        ),
+        (None, false, true) => {
+            let mut versions: Vec<String> = handlers
+                .iter()
+                .map(|(supported, _)| supported.version())
+                .collect();
+            versions.sort();
+            versions.dedup();
+            received.problem_report(
+                "e.p.msg.unsupported-version",
+                "Version {1} of protocol {2} is not supported, supported versions are {3}.",
+                &[
+                    &message_type.version(),
+                    &message_type.protocol,
+                    &versions.join(", "),
+                ],
+            )
+        }
+        (None, false, false) => received.problem_report(
+            "e.p.msg.unsupported-message-type",
+            "Message type {1} is not supported.",
+            &[&received.message_type],
+        ),
+    };
+    if received.message_type == PROBLEM_REPORT || received.from.is_none() {
+        return Ok(Some(Routed::Unsupported(None)));
+    }
+    Ok(Some(Routed::Unsupported(Some(
+        serialize_reply(vade, options, &problem).await?,
+    ))))
+}
+
+/// Packs given reply with the `pack` options of `didcomm_receive` or returns it as plaintext JSON.
+async fn serialize_reply(
+    vade: &mut Vade,
+    options: &str,
+    reply: &DidCommMessage,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let reply = serde_json::to_string(reply)?;
+    let options: ReceiveOptions = parse_options(options)?;
+    match options.pack {
+        Some(pack) => didcomm::pack(vade, &pack.to_string(), &reply).await,
+        None => Ok(reply),
+    }
+}
+
+/// Returns the first result of a plugin for a `vc_zkp_*` function of a protocol engine, parsed as
+/// JSON if possible.
+pub(crate) fn get_first_result(
+    function: &str,
+    method: &str,
+    results: Vec<Option<String>>,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let result = results.into_iter().flatten().next().ok_or_else(|| {
+        format!(
+            r#"no plugin returned a result for {} with method "{}""#,
+            function, method
+        )
+    })?;
+    Ok(serde_json::from_str(&result).unwrap_or(Value::String(result)))
+}
+
+/// Adds given artifacts of a thread to given payload, unless it has them already.
+pub(crate) fn add_artifacts(
+    payload: &str,
+    artifacts: &[(&str, &Option<Value>)],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut payload: Map<String, Value> = match payload.trim() {
+        "" => Map::new(),
+        payload => serde_json::from_str(payload)
+            .map_err(|e| format!("payload has to be a JSON object; {}", e))?,
+    };
+    for (name, artifact) in artifacts.iter() {
+        if let Some(artifact) = artifact {
+            payload
+                .entry(name.to_string())
+                .or_insert_with(|| artifact.clone());
+        }
+    }
+    Ok(Value::Object(payload).to_string())
+}
+
+/// Returns the JSON data of the first attachment of given message, that has any.
+pub(crate) fn get_attachment(message: &DidCommMessage) -> Option<Value> {
+    message
+        .attachments
+        .iter()
+        .flatten()
+        .find_map(|attachment| attachment["data"].get("json").cloned())
+}
+
+/// Returns the serialized name of given role or state.
+pub(crate) fn get_name<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// State of a protocol thread, as persisted by protocol engines with [`Threads`].
+pub(crate) trait Exchange: Clone + Serialize + DeserializeOwned {
+    type Role: Copy + PartialEq + Serialize;
+    type State: Copy + PartialEq + Serialize;
+
+    fn thid(&self) -> &str;
+    fn version(&self) -> &str;
+    fn role(&self) -> Self::Role;
+    fn state(&self) -> Self::State;
+    fn counterparty(&self) -> &str;
+    fn is_final(&self) -> bool;
+    /// `expires_time` of the last message of the thread, if the exchange supports timeouts
+    fn expires_time(&self) -> Option<i64>;
+    /// Updates the exchange after sending or receiving a message with given `expires_time`.
+    fn touch(&mut self, expires_time: Option<i64>, now: i64);
+    /// Abandons the exchange with given problem report body.
+    fn abandon(&mut self, problem_report: Value, now: i64);
+
+    fn is_expired(&self, now: i64) -> bool {
+        !self.is_final() && self.expires_time().is_some_and(|expires| expires < now)
+    }
+}
+
+/// Thread handling shared by protocol engines: persists exchanges in a [`ThreadStore`], sends
+/// their messages, checks senders and timeouts of received messages and answers unexpected
+/// ones with problem reports.
+#[derive(Clone)]
+pub(crate) struct Threads {
+    /// protocol name, e.g. "present-proof"
+    name: &'static str,
+    /// name of exchanges in errors, e.g. "proof exchange"
+    exchange_name: &'static str,
+    /// supported versions, the first one is used for new exchanges by default
+    pub(crate) versions: &'static [&'static str],
+    pub(crate) did: String,
+    pub(crate) method: String,
+    pub(crate) send_options: String,
+    pub(crate) clock: Option<Rc<dyn Clock>>,
+    store: Rc<dyn ThreadStore>,
+}
+
+impl Threads {
+    pub(crate) fn new(
+        name: &'static str,
+        exchange_name: &'static str,
+        versions: &'static [&'static str],
+        did: &str,
+        method: &str,
+        store: Box<dyn ThreadStore>,
+    ) -> Self {
+        Threads {
+            name,
+            exchange_name,
+            versions,
+            did: did.to_string(),
+            method: method.to_string(),
+            send_options: String::new(),
+            clock: None,
+            store: Rc::from(store),
+        }
+    }
+
+    /// Returns the PIURIs of all supported versions.
+    pub(crate) fn message_types(&self) -> Vec<String> {
+        self.versions
+            .iter()
+            .map(|version| format!("{}/{}", self.protocol(), version))
+            .collect()
+    }
+
+    pub(crate) fn now(&self, vade: &Vade) -> i64 {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => vade.now(),
+        }
+    }
+
+    pub(crate) fn get_exchange<T: Exchange>(
+        &self,
+        thid: &str,
+    ) -> Result<Option<T>, Box<dyn std::error::Error>> {
+        match self.store.load(&self.get_key(thid))? {
+            Some(state) => Ok(Some(serde_json::from_str(&state).map_err(|e| {
+                format!(
+                    r#"invalid state of {} "{}"; {}"#,
+                    self.exchange_name, thid, e
+                )
+            })?)),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn list_exchanges<T: Exchange>(&self) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+        let prefix = self.get_key("");
+        let mut exchanges = Vec::new();
+        for key in self.store.list()? {
+            if let Some(thid) = key.strip_prefix(&prefix) {
+                exchanges.extend(self.get_exchange(thid)?);
+            }
+        }
+        Ok(exchanges)
+    }
+
+    pub(crate) fn save<T: Exchange>(&self, exchange: &T) -> Result<(), Box<dyn std::error::Error>> {
+        self.store.save(
+            &self.get_key(exchange.thid()),
+            &serde_json::to_string(exchange)?,
+        )
+    }
+
+    /// Loads an exchange to continue with a function of the engine, that is only allowed for
+    /// given role and state.
+    pub(crate) fn continue_exchange<T: Exchange>(
+        &self,
+        vade: &Vade,
+        thid: &str,
+        role: T::Role,
+        state: T::State,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let exchange: T = self
+            .get_exchange(thid)?
+            .ok_or_else(|| format!(r#"unknown {} "{}""#, self.exchange_name, thid))?;
+        if exchange.role() != role || exchange.state() != state {
+            return Err(Box::from(format!(
+                r#"{} "{}" is in state "{}" as {}, expected state "{}" as {}"#,
+                self.exchange_name,
+                thid,
+                get_name(&exchange.state()),
+                get_name(&exchange.role()),
+                get_name(&state),
+                get_name(&role),
+            )));
+        }
+        if exchange.is_expired(self.now(vade)) {
+            return Err(Box::from(self.get_expired_comment(thid)));
+        }
+        Ok(exchange)
+    }
+
+    /// Creates a message of given exchange to its counterparty.
+    pub(crate) fn get_message<T: Exchange>(
+        &self,
+        exchange: &T,
+        name: &str,
+        body: Value,
+        attachment: Option<&Value>,
+    ) -> DidCommMessage {
+        let message_type = format!("{}/{}/{}", self.protocol(), exchange.version(), name);
+        let attachments = attachment.map(|attachment| {
+            vec![json!({
+                "id": name,
+                "media_type": "application/json",
+                "format": self.method,
+                "data": { "json": attachment },
+            })]
+        });
+        DidCommMessage {
+            attachments,
+            ..DidCommMessage::new(&message_type, body)
+                .with_from(&self.did)
+                .with_to(&[exchange.counterparty()])
+                .with_thid(exchange.thid())
+        }
+    }
+
+    /// Creates a problem report, that abandons given exchange.
+    pub(crate) fn get_problem_report<T: Exchange>(
+        &self,
+        exchange: &T,
+        code: &str,
+        comment: &str,
+    ) -> DidCommMessage {
+        DidCommMessage {
+            pthid: Some(exchange.thid().to_string()),
+            ..self.get_message(
+                exchange,
+                "problem-report",
+                json!({ "code": code, "comment": comment }),
+                None,
+            )
+        }
+    }
+
+    /// Sends given message of given exchange and saves the exchange afterwards.
+    pub(crate) async fn send<T: Exchange>(
+        &self,
+        vade: &mut Vade,
+        mut exchange: T,
+        message: &DidCommMessage,
+    ) -> Result<ProtocolAction<T>, Box<dyn std::error::Error>> {
+        let results = vade
+            .didcomm_send(&self.send_options, &serde_json::to_string(message)?)
+            .await?;
+        exchange.touch(message.expires_time, self.now(vade));
+        self.save(&exchange)?;
+        Ok(ProtocolAction { exchange, results })
+    }
+
+    /// Abandons all exchanges, that have not been continued until the `expires_time` of their
+    /// last message, and sends a problem report with code "e.p.req.time" to their counterparty.
+    pub(crate) async fn abandon_expired<T: Exchange>(
+        &self,
+        vade: &mut Vade,
+    ) -> Result<Vec<ProtocolAction<T>>, Box<dyn std::error::Error>> {
+        let now = self.now(vade);
+        let mut actions = Vec::new();
+        for mut exchange in self.list_exchanges::<T>()? {
+            if !exchange.is_expired(now) {
+                continue;
+            }
+            let message = self.get_problem_report(
+                &exchange,
+                "e.p.req.time",
+                &self.get_expired_comment(exchange.thid()),
+            );
+            exchange.abandon(message.body.clone(), now);
+            actions.push(self.send(vade, exchange, &message).await?);
+        }
+        Ok(actions)
+    }
+
+    /// Handles a received message of the engine's protocol. Problem reports abandon their
+    /// exchange. Other messages are passed to `apply` with the stored exchange of their thread,
+    /// if any, their version and message name, and the exchange returned by it is saved.
+    /// Messages have to be authcrypted by the counterparty of an existing exchange and must not
+    /// be expired, unexpected messages are answered with a problem report.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance, the message has been received with
+    /// * `unpacked` - received message
+    /// * `apply` - function, that applies the message to its exchange or returns a problem code
+    ///   and comment, if it is not expected
+    pub(crate) fn handle<T, F>(
+        &self,
+        vade: &Vade,
+        unpacked: &UnpackedMessage,
+        apply: F,
+    ) -> Result<Option<DidCommMessage>, Box<dyn std::error::Error>>
+    where
+        T: Exchange,
+        F: FnOnce(Option<T>, &str, &str, &str, i64) -> Result<T, (&'static str, String)>,
+    {
+        let message = &unpacked.message;
+        let from = match (&message.from, unpacked.metadata.authenticated) {
+            (Some(from), true) => from,
+            _ => {
+                return Err(Box::from(format!(
+                    "{} messages have to be authcrypted",
+                    self.name
+                )))
+            }
+        };
+        let (version, name) = message
+            .message_type
+            .strip_prefix(&self.protocol())
+            .and_then(|rest| rest.trim_start_matches('/').split_once('/'))
+            .ok_or_else(|| format!("invalid {} message type", self.name))?;
+        let major = version.split('.').next().unwrap_or_default();
+        let version = self
+            .versions
+            .iter()
+            .find(|supported| supported.split('.').next() == Some(major))
+            .unwrap_or(&self.versions[0]);
+        let now = self.now(vade);
+        let thid = message.thid.as_ref().unwrap_or(&message.id);
+        if name == "problem-report" {
+            let thid = message.pthid.as_ref().unwrap_or(thid);
+            if let Some(mut exchange) = self.get_exchange::<T>(thid)? {
+                if exchange.counterparty() == from && !exchange.is_final() {
+                    exchange.abandon(message.body.clone(), now);
+                    self.save(&exchange)?;
+                }
+            }
+            return Ok(None);
+        }
+        let exchange = self.get_exchange::<T>(thid)?;
+        let expired = message.expires_time.is_some_and(|expires| expires < now)
+            || exchange
+                .as_ref()
+                .is_some_and(|exchange| exchange.is_expired(now));
+        let result = match &exchange {
+            Some(exchange) if exchange.counterparty() != from => Err((
+                "e.p.trust.unexpected-sender",
+                format!(
+                    r#"{} "{}" does not belong to "{}""#,
+                    self.exchange_name, thid, from
+                ),
+            )),
+            _ if expired => Err(("e.p.req.time", self.get_expired_comment(thid))),
+            _ => apply(exchange.clone(), version, name, from, now),
+        };
+        match result {
+            Ok(mut exchange) => {
+                exchange.touch(message.expires_time, now);
+                self.save(&exchange)?;
+                Ok(None)
+            }
+            Err((code, comment)) => {
+                let report = DidCommMessage {
+                    message_type: format!("{}/{}/problem-report", self.protocol(), version),
+                    from: Some(self.did.clone()),
+                    ..message.problem_report(code, &comment, &[])
+                };
+                let abandoned = exchange.filter(|exchange| {
+                    expired && exchange.counterparty() == from && !exchange.is_final()
+                });
+                if let Some(mut exchange) = abandoned {
+                    exchange.abandon(report.body.clone(), now);
+                    self.save(&exchange)?;
+                }
+                Ok(Some(report))
+            }
+        }
+    }
+
+    fn protocol(&self) -> String {
+        format!("https://didcomm.org/{}", self.name)
+    }
+
+    fn get_key(&self, thid: &str) -> String {
+        format!("{}/{}", self.name, thid)
+    }
+
+    fn get_expired_comment(&self, thid: &str) -> String {
+        format!(r#"{} "{}" has expired"#, self.exchange_name, thid)
+    }
+}
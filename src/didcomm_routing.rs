@@ -0,0 +1,667 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! DIDComm Routing 2.0: messages are wrapped in `forward` envelopes for the `routingKeys` of the
+//! recipient's `DIDCommMessaging` service, and mediators queue forwarded messages per recipient
+//! until they are picked up with Message Pickup 3.0.
+
+use crate::{
+    didcomm::{self, DidCommMessage, UnpackMetadata},
+    verification_method, Vade,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::{cell::RefCell, collections::HashMap};
+
+const FORWARD: &str = "https://didcomm.org/routing/2.0/forward";
+const PICKUP: &str = "https://didcomm.org/messagepickup/3.0/";
+const COORDINATE_MEDIATION: &str = "https://didcomm.org/coordinate-mediation/3.0/";
+/// default number of messages a [`Mediator`] queues per recipient
+const MAX_MESSAGES: usize = 100;
+/// default number of bytes of all messages a [`Mediator`] queues
+const MAX_SIZE: usize = 10 * 1024 * 1024;
+/// default number of seconds a [`Mediator`] keeps messages without `expires_time` (7 days)
+const LIFETIME: i64 = 7 * 24 * 60 * 60;
+
+/// Message prepared by `Vade::didcomm_send`, that is returned if no plugin sends it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PreparedMessage {
+    message: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service_endpoint: Option<String>,
+}
+
+/// Endpoint and routing keys of a `DIDCommMessaging` service.
+struct Service {
+    uri: String,
+    routing_keys: Vec<String>,
+}
+
+/// Message to send, as prepared by [`prepare_send`].
+pub(crate) struct Prepared {
+    /// packed and routed message
+    pub(crate) message: String,
+    /// options for plugins with the `serviceEndpoint` of the recipient
+    pub(crate) options: String,
+    /// message and endpoint as JSON, that is returned if no plugin sends the message
+    pub(crate) result: String,
+}
+
+/// Packs given message with the `pack` options and wraps it in `forward` envelopes according to
+/// the service of its first recipient. Returns `None` if no `pack` options are given.
+pub(crate) async fn prepare_send(
+    vade: &mut Vade,
+    options: &str,
+    message: &str,
+) -> Result<Option<Prepared>, Box<dyn std::error::Error>> {
+    let mut options: Map<String, Value> = match options.trim() {
+        "" => Map::new(),
+        options => serde_json::from_str(options)?,
+    };
+    let pack_options = match options.remove("pack") {
+        Some(pack_options) => pack_options,
+        None => return Ok(None),
+    };
+    let packed = didcomm::pack(vade, &pack_options.to_string(), message).await?;
+
+    let recipient = match pack_options["to"].as_array().and_then(|to| to.first()) {
+        Some(to) => to.as_str().unwrap_or_default().to_string(),
+        None => DidCommMessage::from_json(message)?
+            .to
+            .and_then(|to| to.into_iter().next())
+            .unwrap_or_default(),
+    };
+    let did = recipient.split('#').next().unwrap_or_default().to_string();
+    let (routed, endpoint) = match resolve_service(vade, &did).await? {
+        Some(service) => (
+            wrap_in_forwards(vade, &recipient, &service.routing_keys, &packed).await?,
+            Some(service.uri),
+        ),
+        None => (packed, None),
+    };
+
+    let result = serde_json::to_string(&PreparedMessage {
+        message: serde_json::from_str(&routed)?,
+        service_endpoint: endpoint.clone(),
+    })?;
+    if let Some(endpoint) = endpoint {
+        options.insert("serviceEndpoint".to_string(), Value::from(endpoint));
+    }
+    Ok(Some(Prepared {
+        message: routed,
+        options: Value::Object(options).to_string(),
+        result,
+    }))
+}
+
+/// Resolves the first `DIDCommMessaging` service of given DID. If its endpoint is a DID, e.g.
+/// of a mediator, that DID's service is used with its routing keys put in front.
+async fn resolve_service(
+    vade: &mut Vade,
+    did: &str,
+) -> Result<Option<Service>, Box<dyn std::error::Error>> {
+    let mut service = match find_service(vade, did).await? {
+        Some(service) => service,
+        None => return Ok(None),
+    };
+    if service.uri.starts_with("did:") {
+        let mediator = find_service(vade, &service.uri)
+            .await?
+            .ok_or_else(|| format!(r#""{}" has no DIDCommMessaging service"#, service.uri))?;
+        let mut routing_keys = mediator.routing_keys;
+        routing_keys.append(&mut service.routing_keys);
+        service = Service {
+            uri: mediator.uri,
+            routing_keys,
+        };
+    }
+    Ok(Some(service))
+}
+
+async fn find_service(
+    vade: &mut Vade,
+    did: &str,
+) -> Result<Option<Service>, Box<dyn std::error::Error>> {
+    let document = verification_method::resolve_did_document(Some(vade), did).await?;
+    let services = document["service"].as_array().cloned().unwrap_or_default();
+    for service in services.iter() {
+        let is_messaging = match &service["type"] {
+            Value::String(service_type) => service_type == "DIDCommMessaging",
+            Value::Array(types) => types.iter().any(|t| t == "DIDCommMessaging"),
+            _ => false,
+        };
+        if !is_messaging {
+            continue;
+        }
+        let endpoint = match &service["serviceEndpoint"] {
+            Value::Array(endpoints) => endpoints.first().cloned().unwrap_or_default(),
+            endpoint => endpoint.clone(),
+        };
+        let (uri, routing_keys) = match &endpoint {
+            Value::String(uri) => (uri.to_string(), Vec::new()),
+            endpoint => (
+                endpoint["uri"].as_str().unwrap_or_default().to_string(),
+                endpoint["routingKeys"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .map(|key| match key.starts_with('#') {
+                        true => format!("{}{}", did, key),
+                        false => key.to_string(),
+                    })
+                    .collect(),
+            ),
+        };
+        if !uri.is_empty() {
+            return Ok(Some(Service { uri, routing_keys }));
+        }
+    }
+    Ok(None)
+}
+
+/// Wraps given packed message in anoncrypted `forward` messages, the last routing key is wrapped
+/// first, so the message is sent to the first routing key.
+async fn wrap_in_forwards(
+    vade: &mut Vade,
+    recipient: &str,
+    routing_keys: &[String],
+    packed: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut next = recipient.to_string();
+    let mut message = packed.to_string();
+    for routing_key in routing_keys.iter().rev() {
+        let forward = DidCommMessage {
+            to: Some(vec![routing_key.to_string()]),
+            attachments: Some(vec![json!({
+                "id": didcomm::get_random_id(),
+                "data": { "json": serde_json::from_str::<Value>(&message)? },
+            })]),
+            ..DidCommMessage::new(FORWARD, json!({ "next": next }))
+        };
+        message = didcomm::pack(
+            vade,
+            &json!({ "packing": "anoncrypt" }).to_string(),
+            &serde_json::to_string(&forward)?,
+        )
+        .await?;
+        next = routing_key.to_string();
+    }
+    Ok(message)
+}
+
+/// Message queued by a [`Mediator`].
+struct QueuedMessage {
+    id: String,
+    message: Value,
+    received_time: i64,
+    expires_time: i64,
+    size: usize,
+}
+
+/// Mediator for recipients, that are offline most of the time. Recipients request mediation and
+/// register their DIDs or keys with Coordinate Mediation 3.0, mediation is granted to every
+/// authenticated requester. A mediator accepts `forward` messages, that are encrypted for its
+/// keys and whose `next` has been registered, queues the forwarded messages per recipient in
+/// memory and delivers them on Message Pickup 3.0 requests, that are authcrypted by the
+/// recipient. Replies are authcrypted with the mediator's `keyAgreement` key.
+///
+/// Queued messages are dropped after the `expires_time` of their forward, but at the latest
+/// after 7 days. By default, 100 messages are queued per recipient and 10 MiB of messages in
+/// total, forwards exceeding these limits are answered with an `e.p.xfer.queue-full` problem
+/// report. Limits can be changed with [`with_limits`](Mediator::with_limits) and
+/// [`with_lifetime`](Mediator::with_lifetime).
+///
+/// A mediator is registered on `Vade` with
+/// [`register_mediator`](https://docs.rs/vade/*/vade/struct.Vade.html#method.register_mediator),
+/// incoming messages are handled in `didcomm_receive`. Live delivery is not supported.
+///
+/// # Example
+///
+/// ```
+/// use vade::{Mediator, Vade};
+///
+/// let mut vade = Vade::new();
+/// // // register key store holding the mediator's keys e.g. with
+/// // vade.register_key_store(Box::from(key_store));
+/// vade.register_mediator(Mediator::new(
+///     "did:example:mediator",
+///     "did:example:mediator#key-x25519",
+///     "z6LS...",
+/// ));
+/// ```
+pub struct Mediator {
+    did: String,
+    kid: String,
+    key_id: String,
+    /// DIDs and keys registered by recipients, mediation has been granted to
+    keylists: RefCell<HashMap<String, Vec<String>>>,
+    queues: RefCell<HashMap<String, Vec<QueuedMessage>>>,
+    max_messages: usize,
+    max_size: usize,
+    lifetime: i64,
+}
+
+impl Mediator {
+    /// Creates a new `Mediator` with empty queues.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID of mediator
+    /// * `kid` - DID URL of mediator's `keyAgreement` key to authcrypt replies with
+    /// * `key_id` - id of this key in the registered key store
+    pub fn new(did: &str, kid: &str, key_id: &str) -> Self {
+        Mediator {
+            did: did.to_string(),
+            kid: kid.to_string(),
+            key_id: key_id.to_string(),
+            keylists: RefCell::new(HashMap::new()),
+            queues: RefCell::new(HashMap::new()),
+            max_messages: MAX_MESSAGES,
+            max_size: MAX_SIZE,
+            lifetime: LIFETIME,
+        }
+    }
+
+    /// Sets the maximum number of queued messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_messages` - number of messages, that are queued per recipient
+    /// * `max_size` - number of bytes of all queued messages
+    pub fn with_limits(mut self, max_messages: usize, max_size: usize) -> Self {
+        self.max_messages = max_messages;
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets the maximum time messages are queued.
+    ///
+    /// # Arguments
+    ///
+    /// * `lifetime` - seconds after which queued messages are dropped, if the `expires_time` of
+    ///   their forward is not earlier
+    pub fn with_lifetime(mut self, lifetime: i64) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Returns the number of messages queued for given recipient.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient` - DID or DID URL of a key, the messages have been forwarded to
+    pub fn message_count(&self, recipient: &str) -> usize {
+        self.queues.borrow().get(recipient).map_or(0, Vec::len)
+    }
+
+    /// Handles forward, coordinate mediation and pickup messages. Returns `None` for other
+    /// messages, that are left to plugins, and the reply, if any, otherwise. Invalid requests are
+    /// answered with a problem report, that is authcrypted for authenticated requesters and
+    /// plaintext otherwise.
+    pub(crate) async fn receive(
+        &self,
+        vade: &mut Vade,
+        options: &str,
+        message: &str,
+    ) -> Result<Option<Option<String>>, Box<dyn std::error::Error>> {
+        let unpacked = match didcomm::unpack(vade, options, message).await {
+            Ok(unpacked) => unpacked,
+            Err(_) => return Ok(None),
+        };
+        self.remove_expired(vade.now());
+        let request = unpacked.message;
+        if request.message_type == FORWARD {
+            return match self.receive_forward(&request, &unpacked.metadata, vade.now()) {
+                Ok(()) => Ok(Some(None)),
+                Err((code, comment)) => {
+                    let report = DidCommMessage {
+                        from: Some(self.did.clone()),
+                        ..request.problem_report(code, &comment, &[])
+                    };
+                    Ok(Some(Some(serde_json::to_string(&report)?)))
+                }
+            };
+        }
+        let (pickup, name) = match (
+            request.message_type.strip_prefix(PICKUP),
+            request.message_type.strip_prefix(COORDINATE_MEDIATION),
+        ) {
+            (Some(name), _) => (true, name),
+            (None, Some(name)) => (false, name),
+            (None, None) => return Ok(None),
+        };
+        let requester = match (&request.from, unpacked.metadata.authenticated) {
+            (Some(from), true) => from.to_string(),
+            (Some(_), false) => {
+                let report = DidCommMessage {
+                    from: Some(self.did.clone()),
+                    ..request.problem_report(
+                        "e.p.trust.crypto",
+                        "Pickup and coordinate mediation requests have to be authcrypted.",
+                        &[],
+                    )
+                };
+                return Ok(Some(Some(serde_json::to_string(&report)?)));
+            }
+            (None, _) => return Ok(Some(None)),
+        };
+        let reply = match pickup {
+            true => self.handle_pickup(&requester, name, &request),
+            false => self.handle_coordinate_mediation(&requester, name, &request),
+        }
+        .unwrap_or_else(|(code, comment)| request.problem_report(code, &comment, &[]));
+        let reply = DidCommMessage {
+            from: Some(self.did.clone()),
+            to: Some(vec![requester]),
+            thid: Some(request.thid.unwrap_or(request.id)),
+            ..reply
+        };
+        let options = json!({ "sender": { "kid": self.kid, "keyId": self.key_id } });
+        let packed =
+            didcomm::pack(vade, &options.to_string(), &serde_json::to_string(&reply)?).await?;
+        Ok(Some(Some(packed)))
+    }
+
+    /// Returns the reply to given coordinate mediation request of given authenticated requester
+    /// or the code and comment of a problem report. Recipients can only register their own DIDs
+    /// and keys.
+    fn handle_coordinate_mediation(
+        &self,
+        requester: &str,
+        name: &str,
+        request: &DidCommMessage,
+    ) -> Result<DidCommMessage, (&'static str, String)> {
+        let mut keylists = self.keylists.borrow_mut();
+        if name == "mediate-request" {
+            keylists.entry(requester.to_string()).or_default();
+            return Ok(DidCommMessage::new(
+                &format!("{}mediate-grant", COORDINATE_MEDIATION),
+                json!({ "routing_did": [self.did] }),
+            ));
+        }
+        let keylist = keylists.get_mut(requester).ok_or_else(|| {
+            (
+                "e.p.req.not-granted",
+                format!(r#"Mediation has not been granted to "{}"."#, requester),
+            )
+        })?;
+        Ok(match name {
+            "recipient-update" => {
+                let updated: Vec<Value> = request.body["updates"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|update| {
+                        let recipient = update["recipient_did"].as_str().unwrap_or_default();
+                        let action = update["action"].as_str().unwrap_or_default();
+                        let known = keylist.iter().any(|entry| entry == recipient);
+                        let result = match action {
+                            _ if !is_own(requester, recipient) => "client_error",
+                            "add" if known => "no_change",
+                            "add" => {
+                                keylist.push(recipient.to_string());
+                                "success"
+                            }
+                            "remove" if known => {
+                                keylist.retain(|entry| entry != recipient);
+                                "success"
+                            }
+                            "remove" => "no_change",
+                            _ => "client_error",
+                        };
+                        json!({ "recipient_did": recipient, "action": action, "result": result })
+                    })
+                    .collect();
+                DidCommMessage::new(
+                    &format!("{}recipient-update-response", COORDINATE_MEDIATION),
+                    json!({ "updated": updated }),
+                )
+            }
+            "recipient-query" => {
+                let dids: Vec<Value> = keylist
+                    .iter()
+                    .map(|recipient| json!({ "recipient_did": recipient }))
+                    .collect();
+                DidCommMessage::new(
+                    &format!("{}recipient", COORDINATE_MEDIATION),
+                    json!({ "dids": dids }),
+                )
+            }
+            name => {
+                return Err((
+                    "e.p.msg.unsupported-message-type",
+                    format!(
+                        "Message type {}{} is not supported.",
+                        COORDINATE_MEDIATION, name
+                    ),
+                ))
+            }
+        })
+    }
+
+    /// Returns the reply to given pickup request of given authenticated requester or the code and
+    /// comment of a problem report.
+    fn handle_pickup(
+        &self,
+        requester: &str,
+        name: &str,
+        request: &DidCommMessage,
+    ) -> Result<DidCommMessage, (&'static str, String)> {
+        let recipient_did = request.body["recipient_did"].as_str();
+        if recipient_did.is_some_and(|did| !is_own(requester, did)) {
+            return Err((
+                "e.p.req.unauthorized",
+                format!(
+                    r#""{}" cannot pick up messages for "{}"."#,
+                    requester,
+                    recipient_did.unwrap_or_default()
+                ),
+            ));
+        }
+        Ok(match name {
+            "status-request" => self.get_status(requester, recipient_did),
+            "delivery-request" => {
+                let limit = request.body["limit"].as_u64().ok_or((
+                    "e.p.msg.invalid",
+                    "delivery-request has no limit.".to_string(),
+                ))?;
+                let attachments: Vec<Value> = self
+                    .get_messages(requester, recipient_did)
+                    .into_iter()
+                    .take(limit as usize)
+                    .map(|(id, message)| json!({ "id": id, "data": { "json": message } }))
+                    .collect();
+                match attachments.is_empty() {
+                    true => self.get_status(requester, recipient_did),
+                    false => {
+                        let mut body = json!({});
+                        if let Some(recipient_did) = recipient_did {
+                            body["recipient_did"] = Value::from(recipient_did);
+                        }
+                        DidCommMessage {
+                            attachments: Some(attachments),
+                            ..DidCommMessage::new(&format!("{}delivery", PICKUP), body)
+                        }
+                    }
+                }
+            }
+            "messages-received" => {
+                let ids: Vec<&str> = request.body["message_id_list"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .collect();
+                for (recipient, queue) in self.queues.borrow_mut().iter_mut() {
+                    if is_own(requester, recipient) {
+                        queue.retain(|queued| !ids.contains(&queued.id.as_str()));
+                    }
+                }
+                self.get_status(requester, recipient_did)
+            }
+            "live-delivery-change" => request.problem_report(
+                "e.m.live-mode-not-supported",
+                "Connection does not support Live Delivery",
+                &[],
+            ),
+            name => {
+                return Err((
+                    "e.p.msg.unsupported-message-type",
+                    format!("Message type {}{} is not supported.", PICKUP, name),
+                ))
+            }
+        })
+    }
+
+    /// Queues the message attached to given forward, if the forward has been encrypted for the
+    /// mediator and its `next` has been registered by a recipient, or returns the code and
+    /// comment of a problem report.
+    fn receive_forward(
+        &self,
+        forward: &DidCommMessage,
+        metadata: &UnpackMetadata,
+        now: i64,
+    ) -> Result<(), (&'static str, String)> {
+        let for_mediator = |kid: &str| is_own(&self.did, kid);
+        if !metadata
+            .encrypted_to_kid
+            .as_deref()
+            .is_some_and(for_mediator)
+            || !forward.to.iter().flatten().all(|to| for_mediator(to))
+        {
+            return Err((
+                "e.p.trust.crypto",
+                "Forward messages have to be encrypted for the mediator.".to_string(),
+            ));
+        }
+        let invalid = |comment: &str| ("e.p.msg.invalid", comment.to_string());
+        let next = forward.body["next"]
+            .as_str()
+            .ok_or_else(|| invalid("Forward message has no next."))?;
+        let granted = self
+            .keylists
+            .borrow()
+            .values()
+            .flatten()
+            .any(|recipient| recipient == next || is_own(recipient, next));
+        if !granted {
+            return Err((
+                "e.p.req.not-granted",
+                format!(r#"Mediation has not been granted for "{}"."#, next),
+            ));
+        }
+        let data = forward
+            .attachments
+            .as_ref()
+            .and_then(|attachments| attachments.first())
+            .map(|attachment| &attachment["data"])
+            .ok_or_else(|| invalid("Forward message has no attachment."))?;
+        let message = match (data.get("json"), data["base64"].as_str()) {
+            (Some(message), _) => message.clone(),
+            (None, Some(encoded)) => URL_SAFE_NO_PAD
+                .decode(encoded)
+                .ok()
+                .and_then(|decoded| serde_json::from_slice(&decoded).ok())
+                .ok_or_else(|| invalid("Forward message has an invalid attachment."))?,
+            (None, None) => return Err(invalid("Forward message has no attached message.")),
+        };
+        let expires_time = forward.expires_time.map_or(now + self.lifetime, |expires| {
+            expires.min(now + self.lifetime)
+        });
+        if expires_time < now {
+            return Err(("e.p.req.time", "Forward message has expired.".to_string()));
+        }
+        let size = message.to_string().len();
+        let mut queues = self.queues.borrow_mut();
+        let total_size: usize = queues.values().flatten().map(|queued| queued.size).sum();
+        let queue = queues.entry(next.to_string()).or_default();
+        if queue.len() >= self.max_messages || total_size + size > self.max_size {
+            return Err((
+                "e.p.xfer.queue-full",
+                format!(r#"Queue of "{}" is full."#, next),
+            ));
+        }
+        queue.push(QueuedMessage {
+            id: didcomm::get_random_id(),
+            message,
+            received_time: now,
+            expires_time,
+            size,
+        });
+        Ok(())
+    }
+
+    /// Drops queued messages, that have expired at given time.
+    fn remove_expired(&self, now: i64) {
+        let mut queues = self.queues.borrow_mut();
+        for queue in queues.values_mut() {
+            queue.retain(|queued| queued.expires_time >= now);
+        }
+        queues.retain(|_, queue| !queue.is_empty());
+    }
+
+    fn get_messages(&self, requester: &str, recipient_did: Option<&str>) -> Vec<(String, Value)> {
+        let queues = self.queues.borrow();
+        let mut messages: Vec<&QueuedMessage> = queues
+            .iter()
+            .filter(|(recipient, _)| matches(requester, recipient_did, recipient))
+            .flat_map(|(_, queue)| queue.iter())
+            .collect();
+        messages.sort_by_key(|queued| queued.received_time);
+        messages
+            .into_iter()
+            .map(|queued| (queued.id.clone(), queued.message.clone()))
+            .collect()
+    }
+
+    fn get_status(&self, requester: &str, recipient_did: Option<&str>) -> DidCommMessage {
+        let queues = self.queues.borrow();
+        let messages: Vec<&QueuedMessage> = queues
+            .iter()
+            .filter(|(recipient, _)| matches(requester, recipient_did, recipient))
+            .flat_map(|(_, queue)| queue.iter())
+            .collect();
+        let mut body = json!({
+            "message_count": messages.len(),
+            "total_bytes": messages.iter().map(|queued| queued.size).sum::<usize>(),
+            "live_delivery": false,
+        });
+        if let Some(recipient_did) = recipient_did {
+            body["recipient_did"] = Value::from(recipient_did);
+        }
+        if let Some(oldest) = messages.iter().map(|queued| queued.received_time).min() {
+            body["oldest_received_time"] = Value::from(oldest);
+        }
+        if let Some(newest) = messages.iter().map(|queued| queued.received_time).max() {
+            body["newest_received_time"] = Value::from(newest);
+        }
+        DidCommMessage::new(&format!("{}status", PICKUP), body)
+    }
+}
+
+/// Checks if given recipient is the requester's DID or one of its keys.
+fn is_own(requester: &str, recipient: &str) -> bool {
+    recipient.split('#').next() == Some(requester)
+}
+
+/// Checks if given recipient's messages are requested, optionally limited to a `recipient_did`.
+fn matches(requester: &str, recipient_did: Option<&str>, recipient: &str) -> bool {
+    is_own(requester, recipient)
+        && recipient_did.is_none_or(|did| recipient == did || is_own(did, recipient))
+}
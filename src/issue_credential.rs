@@ -0,0 +1,660 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Issue Credential 3.0 and its Aries predecessor 2.0 in DIDComm v2 messages as protocol engine,
+//! that drives the `vc_zkp_*` credential functions for issuers and holders and keeps the state of
+//! every thread in a [`ThreadStore`].
+
+use crate::{
+    didcomm::{get_random_id, DidCommMessage},
+    didcomm_protocols::{
+        add_artifacts, get_attachment, get_first_result, get_name, Exchange, ProtocolAction,
+        Threads,
+    },
+    DidCommHandler, ThreadStore, UnpackedMessage, Vade,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const VERSIONS: &[&str] = &["3.0", "2.0"];
+
+/// Role in a credential exchange.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssuanceRole {
+    /// issues credentials
+    Issuer,
+    /// requests and receives credentials
+    Holder,
+}
+
+/// State of a credential exchange.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssuanceState {
+    /// holder has sent a proposal
+    ProposalSent,
+    /// issuer has received a proposal
+    ProposalReceived,
+    /// issuer has sent an offer
+    OfferSent,
+    /// holder has received an offer
+    OfferReceived,
+    /// holder has sent a credential request
+    RequestSent,
+    /// issuer has received a credential request
+    RequestReceived,
+    /// issuer has issued the credential
+    CredentialIssued,
+    /// holder has received the issued credential
+    CredentialReceived,
+    /// holder has finished and acknowledged the credential, issuer has received the ack
+    Done,
+    /// exchange has been abandoned with a problem report
+    Abandoned,
+}
+
+/// State of a credential exchange thread, as persisted by [`IssueCredential`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialExchange {
+    /// id of thread
+    pub thid: String,
+    /// protocol version, "3.0" or "2.0"
+    pub version: String,
+    /// own role in exchange
+    pub role: IssuanceRole,
+    /// current state
+    pub state: IssuanceState,
+    /// DID of issuer or holder on the other side
+    pub counterparty: String,
+    /// result of `vc_zkp_create_credential_proposal`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proposal: Option<Value>,
+    /// result of `vc_zkp_create_credential_offer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offer: Option<Value>,
+    /// result of `vc_zkp_request_credential`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<Value>,
+    /// result of `vc_zkp_issue_credential`, replaced by the result of `vc_zkp_finish_credential`
+    /// for holders
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<Value>,
+    /// body of problem report, the exchange has been abandoned with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub problem_report: Option<Value>,
+    /// time of last change as unix timestamp
+    pub updated_time: i64,
+}
+
+impl Exchange for CredentialExchange {
+    type Role = IssuanceRole;
+    type State = IssuanceState;
+
+    fn thid(&self) -> &str {
+        &self.thid
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn role(&self) -> IssuanceRole {
+        self.role
+    }
+
+    fn state(&self) -> IssuanceState {
+        self.state
+    }
+
+    fn counterparty(&self) -> &str {
+        &self.counterparty
+    }
+
+    fn is_final(&self) -> bool {
+        matches!(self.state, IssuanceState::Done | IssuanceState::Abandoned)
+    }
+
+    fn expires_time(&self) -> Option<i64> {
+        None
+    }
+
+    fn touch(&mut self, _expires_time: Option<i64>, now: i64) {
+        self.updated_time = now;
+    }
+
+    fn abandon(&mut self, problem_report: Value, now: i64) {
+        self.state = IssuanceState::Abandoned;
+        self.problem_report = Some(problem_report);
+        self.updated_time = now;
+    }
+}
+
+/// Steps of exchange, that call a `vc_zkp_*` function and send its result.
+#[derive(Clone, Copy)]
+enum Step {
+    Propose,
+    Offer,
+    Request,
+    Issue,
+    Finish,
+}
+
+impl Step {
+    fn message_name(self) -> &'static str {
+        match self {
+            Step::Propose => "propose-credential",
+            Step::Offer => "offer-credential",
+            Step::Request => "request-credential",
+            Step::Issue => "issue-credential",
+            Step::Finish => "ack",
+        }
+    }
+
+    fn next_state(self) -> IssuanceState {
+        match self {
+            Step::Propose => IssuanceState::ProposalSent,
+            Step::Offer => IssuanceState::OfferSent,
+            Step::Request => IssuanceState::RequestSent,
+            Step::Issue => IssuanceState::CredentialIssued,
+            Step::Finish => IssuanceState::Done,
+        }
+    }
+
+    async fn call(
+        self,
+        vade: &mut Vade,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let (function, results) = match self {
+            Step::Propose => (
+                "vc_zkp_create_credential_proposal",
+                vade.vc_zkp_create_credential_proposal(method, options, payload)
+                    .await?,
+            ),
+            Step::Offer => (
+                "vc_zkp_create_credential_offer",
+                vade.vc_zkp_create_credential_offer(method, options, payload)
+                    .await?,
+            ),
+            Step::Request => (
+                "vc_zkp_request_credential",
+                vade.vc_zkp_request_credential(method, options, payload)
+                    .await?,
+            ),
+            Step::Issue => (
+                "vc_zkp_issue_credential",
+                vade.vc_zkp_issue_credential(method, options, payload)
+                    .await?,
+            ),
+            Step::Finish => (
+                "vc_zkp_finish_credential",
+                vade.vc_zkp_finish_credential(method, options, payload)
+                    .await?,
+            ),
+        };
+        get_first_result(function, method, results)
+    }
+}
+
+/// Protocol engine for Issue Credential 3.0 (`https://didcomm.org/issue-credential/3.0`) and
+/// Issue Credential 2.0 as adopted from Aries (`https://didcomm.org/issue-credential/2.0`).
+///
+/// Both versions are exchanged as DIDComm v2 messages with `type`, `thid` and `attachments`.
+/// The Aries RFC 0453 wire format of 2.0 with `@type`, `~thread` and `offers~attach` in DIDComm
+/// v1 envelopes is not supported, so version 2.0 only works with agents sending 2.0 messages in
+/// the DIDComm v2 format.
+///
+/// Applications start and continue exchanges with the engine's functions for their role, that
+/// call the matching `vc_zkp_*` function of `Vade` and send its result as attachment with
+/// `didcomm_send`. Artifacts of the thread so far are added to the payload of these calls as
+/// `proposal`, `offer`, `request` and `credential`, unless the payload has these properties
+/// already. Messages of the other side are handled, after the engine has been registered with
+/// [`register_didcomm_handler`](https://docs.rs/vade/*/vade/struct.Vade.html#method.register_didcomm_handler),
+/// by `didcomm_receive`. Functions and messages are only accepted in the order of the protocol:
+///
+/// - holder: [`propose`](#method.propose) (optional), receive offer,
+///   [`request`](#method.request), receive credential, [`finish`](#method.finish) (sends ack)
+/// - issuer: receive proposal and [`offer_for_proposal`](#method.offer_for_proposal) or
+///   [`offer`](#method.offer), receive request, [`issue`](#method.issue), receive ack
+///
+/// Unexpected messages and messages of other senders than the counterparty of their thread are
+/// answered with a `problem-report`, received problem reports abandon their thread. Every change
+/// is saved in the engine's [`ThreadStore`], so exchanges can be continued after restarts.
+///
+/// # Example
+///
+/// ```
+/// use vade::{InMemoryThreadStore, IssueCredential, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     // // register plugins for "did:example" and a key store e.g. with
+///     // vade.register_plugin(example_plugin);
+///     let issuance = IssueCredential::new(
+///         "did:example:holder",
+///         "did:example",
+///         Box::from(InMemoryThreadStore::new()),
+///     )
+///     .with_send_options(r###"{ "pack": { "sender": { "kid": "did:example:holder#key-1", "keyId": "z6LS..." } } }"###);
+///     vade.register_didcomm_handler(Box::from(issuance.clone()))?;
+///     let proposed = issuance
+///         .propose(&mut vade, "did:example:issuer", "", r#"{ "schema": "did:example:schema" }"#)
+///         .await?;
+///     println!("proposed credential in thread {}", proposed.exchange.thid);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct IssueCredential {
+    threads: Threads,
+    version: String,
+}
+
+impl IssueCredential {
+    /// Creates a new `IssueCredential` engine, that starts exchanges with version 3.0.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - own DID, messages are sent from
+    /// * `method` - method to call `vc_zkp_*` functions for (e.g. "did:example")
+    /// * `store` - store to persist exchanges in
+    pub fn new(did: &str, method: &str, store: Box<dyn ThreadStore>) -> Self {
+        IssueCredential {
+            threads: Threads::new(
+                "issue-credential",
+                "credential exchange",
+                VERSIONS,
+                did,
+                method,
+                store,
+            ),
+            version: VERSIONS[0].to_string(),
+        }
+    }
+
+    /// Starts new exchanges with given protocol version instead of 3.0. Received messages are
+    /// answered in the version of their exchange. Version 2.0 is sent as DIDComm v2 message, not
+    /// in the Aries RFC 0453 wire format.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - "3.0" or "2.0"
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// Sends messages with given options for `didcomm_send`, e.g. with `pack` options to
+    /// authcrypt and route them.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - options for `didcomm_send`
+    pub fn with_send_options(mut self, options: &str) -> Self {
+        self.threads.send_options = options.to_string();
+        self
+    }
+
+    /// Returns the exchange with given thread id, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `thid` - id of thread
+    pub fn get_exchange(
+        &self,
+        thid: &str,
+    ) -> Result<Option<CredentialExchange>, Box<dyn std::error::Error>> {
+        self.threads.get_exchange(thid)
+    }
+
+    /// Returns all exchanges of the engine's store.
+    pub fn list_exchanges(&self) -> Result<Vec<CredentialExchange>, Box<dyn std::error::Error>> {
+        self.threads.list_exchanges()
+    }
+
+    /// Creates a credential proposal with `vc_zkp_create_credential_proposal` and sends it to
+    /// given issuer in a new exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `issuer` - DID of issuer
+    /// * `options` - options for `vc_zkp_create_credential_proposal`
+    /// * `payload` - payload for `vc_zkp_create_credential_proposal`
+    pub async fn propose(
+        &self,
+        vade: &mut Vade,
+        issuer: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<CredentialExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.start(vade, IssuanceRole::Holder, issuer, Step::Propose)?;
+        self.run(vade, exchange, Step::Propose, options, payload)
+            .await
+    }
+
+    /// Creates a credential offer with `vc_zkp_create_credential_offer` and sends it to given
+    /// holder in a new exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `holder` - DID of holder
+    /// * `options` - options for `vc_zkp_create_credential_offer`
+    /// * `payload` - payload for `vc_zkp_create_credential_offer`
+    pub async fn offer(
+        &self,
+        vade: &mut Vade,
+        holder: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<CredentialExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.start(vade, IssuanceRole::Issuer, holder, Step::Offer)?;
+        self.run(vade, exchange, Step::Offer, options, payload)
+            .await
+    }
+
+    /// Answers a received proposal with a credential offer created with
+    /// `vc_zkp_create_credential_offer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `thid` - id of exchange in state "proposal-received"
+    /// * `options` - options for `vc_zkp_create_credential_offer`
+    /// * `payload` - payload for `vc_zkp_create_credential_offer`, `proposal` is added
+    pub async fn offer_for_proposal(
+        &self,
+        vade: &mut Vade,
+        thid: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<CredentialExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.threads.continue_exchange(
+            vade,
+            thid,
+            IssuanceRole::Issuer,
+            IssuanceState::ProposalReceived,
+        )?;
+        self.run(vade, exchange, Step::Offer, options, payload)
+            .await
+    }
+
+    /// Answers a received offer with a credential request created with
+    /// `vc_zkp_request_credential`.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `thid` - id of exchange in state "offer-received"
+    /// * `options` - options for `vc_zkp_request_credential`
+    /// * `payload` - payload for `vc_zkp_request_credential`, `offer` is added
+    pub async fn request(
+        &self,
+        vade: &mut Vade,
+        thid: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<CredentialExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.threads.continue_exchange(
+            vade,
+            thid,
+            IssuanceRole::Holder,
+            IssuanceState::OfferReceived,
+        )?;
+        self.run(vade, exchange, Step::Request, options, payload)
+            .await
+    }
+
+    /// Answers a received credential request with a credential issued with
+    /// `vc_zkp_issue_credential`.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `thid` - id of exchange in state "request-received"
+    /// * `options` - options for `vc_zkp_issue_credential`
+    /// * `payload` - payload for `vc_zkp_issue_credential`, `offer` and `request` are added
+    pub async fn issue(
+        &self,
+        vade: &mut Vade,
+        thid: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<CredentialExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.threads.continue_exchange(
+            vade,
+            thid,
+            IssuanceRole::Issuer,
+            IssuanceState::RequestReceived,
+        )?;
+        self.run(vade, exchange, Step::Issue, options, payload)
+            .await
+    }
+
+    /// Finishes a received credential with `vc_zkp_finish_credential`, stores the result as
+    /// the exchange's `credential` and acknowledges it to the issuer.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `thid` - id of exchange in state "credential-received"
+    /// * `options` - options for `vc_zkp_finish_credential`
+    /// * `payload` - payload for `vc_zkp_finish_credential`, `offer`, `request` and
+    ///   `credential` are added
+    pub async fn finish(
+        &self,
+        vade: &mut Vade,
+        thid: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<CredentialExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.threads.continue_exchange(
+            vade,
+            thid,
+            IssuanceRole::Holder,
+            IssuanceState::CredentialReceived,
+        )?;
+        self.run(vade, exchange, Step::Finish, options, payload)
+            .await
+    }
+
+    fn start(
+        &self,
+        vade: &Vade,
+        role: IssuanceRole,
+        counterparty: &str,
+        step: Step,
+    ) -> Result<CredentialExchange, Box<dyn std::error::Error>> {
+        if !VERSIONS.contains(&self.version.as_str()) {
+            return Err(Box::from(format!(
+                r#"unsupported issue-credential version "{}""#,
+                self.version
+            )));
+        }
+        Ok(get_exchange(
+            &get_random_id(),
+            &self.version,
+            role,
+            step.next_state(),
+            counterparty,
+            self.threads.now(vade),
+        ))
+    }
+
+    /// Calls the zkp function of given step, sends its result to the counterparty and saves the
+    /// updated exchange.
+    async fn run(
+        &self,
+        vade: &mut Vade,
+        mut exchange: CredentialExchange,
+        step: Step,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<CredentialExchange>, Box<dyn std::error::Error>> {
+        let payload = add_artifacts(
+            payload,
+            &[
+                ("proposal", &exchange.proposal),
+                ("offer", &exchange.offer),
+                ("request", &exchange.request),
+                ("credential", &exchange.credential),
+            ],
+        )?;
+        let result = step
+            .call(vade, &self.threads.method, options, &payload)
+            .await?;
+        let message = match step {
+            Step::Finish => self.get_message(
+                &exchange,
+                step.message_name(),
+                json!({ "status": "OK" }),
+                None,
+            ),
+            _ => self.get_message(&exchange, step.message_name(), json!({}), Some(&result)),
+        };
+        match step {
+            Step::Propose => exchange.proposal = Some(result),
+            Step::Offer => exchange.offer = Some(result),
+            Step::Request => exchange.request = Some(result),
+            Step::Issue | Step::Finish => exchange.credential = Some(result),
+        }
+        exchange.state = step.next_state();
+        self.threads.send(vade, exchange, &message).await
+    }
+
+    fn get_message(
+        &self,
+        exchange: &CredentialExchange,
+        name: &str,
+        body: Value,
+        attachment: Option<&Value>,
+    ) -> DidCommMessage {
+        let mut message = self.threads.get_message(exchange, name, body, attachment);
+        if attachment.is_some() && exchange.version.starts_with("2.") {
+            message.body["formats"] = json!([{ "attach_id": name, "format": self.threads.method }]);
+        }
+        message
+    }
+
+    /// Applies a received message to its exchange. Returns the updated exchange or a problem
+    /// code and comment, if the message is not expected.
+    fn apply(
+        exchange: Option<CredentialExchange>,
+        version: &str,
+        name: &str,
+        message: &DidCommMessage,
+        from: &str,
+        now: i64,
+    ) -> Result<CredentialExchange, (&'static str, String)> {
+        use IssuanceRole::{Holder, Issuer};
+        use IssuanceState::*;
+        let thid = message.thid.clone().unwrap_or_else(|| message.id.clone());
+        let current = exchange
+            .as_ref()
+            .map(|exchange| (exchange.role, exchange.state));
+        let (role, next) = match (current, name) {
+            (None, "propose-credential") => (Issuer, ProposalReceived),
+            (None, "offer-credential") | (Some((Holder, ProposalSent)), "offer-credential") => {
+                (Holder, OfferReceived)
+            }
+            (Some((Issuer, OfferSent)), "request-credential") => (Issuer, RequestReceived),
+            (Some((Holder, RequestSent)), "issue-credential") => (Holder, CredentialReceived),
+            (Some((Issuer, CredentialIssued)), "ack") => (Issuer, Done),
+            (None, _) => {
+                return Err((
+                    "e.p.msg.unexpected-message",
+                    format!(r#"unknown credential exchange "{}""#, thid),
+                ))
+            }
+            (Some((role, state)), name) => {
+                return Err((
+                    "e.p.msg.unexpected-message",
+                    format!(
+                        r#"unexpected message "{}" in state "{}" as {}"#,
+                        name,
+                        get_name(&state),
+                        get_name(&role),
+                    ),
+                ))
+            }
+        };
+        let attachment = get_attachment(message);
+        if next != Done && attachment.is_none() {
+            return Err((
+                "e.p.msg.missing-attachment",
+                format!(r#"message "{}" has no JSON attachment"#, name),
+            ));
+        }
+        let mut exchange =
+            exchange.unwrap_or_else(|| get_exchange(&thid, version, role, next, from, now));
+        match next {
+            ProposalReceived => exchange.proposal = attachment,
+            OfferReceived => exchange.offer = attachment,
+            RequestReceived => exchange.request = attachment,
+            CredentialReceived => exchange.credential = attachment,
+            _ => (),
+        }
+        exchange.state = next;
+        Ok(exchange)
+    }
+}
+
+#[async_trait(?Send)]
+impl DidCommHandler for IssueCredential {
+    fn message_types(&self) -> Vec<String> {
+        self.threads.message_types()
+    }
+
+    async fn handle(
+        &self,
+        vade: &mut Vade,
+        unpacked: &UnpackedMessage,
+    ) -> Result<Option<DidCommMessage>, Box<dyn std::error::Error>> {
+        self.threads
+            .handle(vade, unpacked, |exchange, version, name, from, now| {
+                Self::apply(exchange, version, name, &unpacked.message, from, now)
+            })
+    }
+}
+
+fn get_exchange(
+    thid: &str,
+    version: &str,
+    role: IssuanceRole,
+    state: IssuanceState,
+    counterparty: &str,
+    now: i64,
+) -> CredentialExchange {
+    CredentialExchange {
+        thid: thid.to_string(),
+        version: version.to_string(),
+        role,
+        state,
+        counterparty: counterparty.to_string(),
+        proposal: None,
+        offer: None,
+        request: None,
+        credential: None,
+        problem_report: None,
+        updated_time: now,
+    }
+}
@@ -0,0 +1,109 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! JSON Canonicalization Scheme as defined in [RFC 8785](https://tools.ietf.org/html/rfc8785).
+
+use serde_json::{Number, Value};
+
+/// Serializes given value in its canonical form, that can be used as input for hashes and
+/// signatures. Object keys are sorted by their UTF-16 code units, whitespace is omitted and
+/// numbers are formatted like ECMAScript does.
+///
+/// # Arguments
+///
+/// * `value` - value to canonicalize
+pub fn canonicalize(value: &Value) -> String {
+    let mut output = String::new();
+    write_value(value, &mut output);
+    output
+}
+
+fn write_value(value: &Value, output: &mut String) {
+    match value {
+        Value::Null => output.push_str("null"),
+        Value::Bool(boolean) => output.push_str(if *boolean { "true" } else { "false" }),
+        Value::Number(number) => output.push_str(&format_number(number)),
+        Value::String(string) => write_string(string, output),
+        Value::Array(array) => {
+            output.push('[');
+            for (index, entry) in array.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_value(entry, output);
+            }
+            output.push(']');
+        }
+        Value::Object(object) => {
+            let mut keys: Vec<&String> = object.keys().collect();
+            keys.sort_by(|a, b| a.encode_utf16().cmp(b.encode_utf16()));
+            output.push('{');
+            for (index, key) in keys.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_string(key, output);
+                output.push(':');
+                write_value(&object[key.as_str()], output);
+            }
+            output.push('}');
+        }
+    }
+}
+
+fn write_string(string: &str, output: &mut String) {
+    // serde_json escapes strings exactly like ECMAScript's `JSON.stringify`
+    output.push_str(&Value::from(string).to_string());
+}
+
+fn format_number(number: &Number) -> String {
+    // integers are serialized as IEEE 754 doubles too, e.g. 2^60 as "1152921504606847000"
+    let float = number.as_f64().unwrap_or(0.0);
+    if float == 0.0 {
+        return "0".to_string();
+    }
+    // `{:e}` yields the shortest representation that round trips, e.g. "1.5e-7"
+    let scientific = format!("{:e}", float.abs());
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap_or(0));
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let exponent: i32 = exponent[1..].parse().unwrap_or(0);
+    let digit_count = digits.len() as i32;
+    // position of the decimal point relative to the start of `digits`
+    let point = exponent + 1;
+
+    let formatted = if digit_count <= point && point <= 21 {
+        format!("{}{}", digits, "0".repeat((point - digit_count) as usize))
+    } else if 0 < point && point <= 21 {
+        let (integer, fraction) = digits.split_at(point as usize);
+        format!("{}.{}", integer, fraction)
+    } else if -6 < point && point <= 0 {
+        format!("0.{}{}", "0".repeat((-point) as usize), digits)
+    } else {
+        let sign = if point - 1 < 0 { "-" } else { "+" };
+        let (first, rest) = digits.split_at(1);
+        if rest.is_empty() {
+            format!("{}e{}{}", first, sign, (point - 1).abs())
+        } else {
+            format!("{}.{}e{}{}", first, rest, sign, (point - 1).abs())
+        }
+    };
+
+    if float < 0.0 {
+        format!("-{}", formatted)
+    } else {
+        formatted
+    }
+}
@@ -0,0 +1,2853 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Offline [JSON-LD 1.1](https://www.w3.org/TR/json-ld11-api/) processing: expansion, compaction
+//! and [RDFC-1.0](https://www.w3.org/TR/rdf-canon/) canonicalization, as needed for Data Integrity
+//! proofs. Contexts are loaded with a [`DocumentLoader`](trait.DocumentLoader.html), the default
+//! [`ContextLoader`](struct.ContextLoader.html) serves the contexts bundled with `vade` and
+//! configured ones and never fetches remote contexts. Properties and types not defined by the
+//! active context are rejected instead of being dropped, as dropped data would not be signed.
+
+use crate::{
+    jcs,
+    rdfc::{self, Quad, Term, XSD_STRING},
+};
+use serde_json::{json, Map, Value};
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+const RDF_FIRST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#first";
+const RDF_REST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#rest";
+const RDF_NIL: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#nil";
+const RDF_JSON: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#JSON";
+const RDF_LANG_STRING: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString";
+const XSD_BOOLEAN: &str = "http://www.w3.org/2001/XMLSchema#boolean";
+const XSD_DOUBLE: &str = "http://www.w3.org/2001/XMLSchema#double";
+const XSD_INTEGER: &str = "http://www.w3.org/2001/XMLSchema#integer";
+/// prefix of IRIs standing in for blank nodes in skolemized documents
+const SKOLEM_PREFIX: &str = "urn:bnid:";
+const MAX_REMOTE_CONTEXTS: usize = 32;
+
+const KEYWORDS: [&str; 23] = [
+    "@base",
+    "@container",
+    "@context",
+    "@direction",
+    "@graph",
+    "@id",
+    "@import",
+    "@included",
+    "@index",
+    "@json",
+    "@language",
+    "@list",
+    "@nest",
+    "@none",
+    "@prefix",
+    "@propagate",
+    "@protected",
+    "@reverse",
+    "@set",
+    "@type",
+    "@value",
+    "@version",
+    "@vocab",
+];
+
+/// Contexts bundled with `vade` by their URL.
+const BUNDLED_CONTEXTS: [(&str, &str); 14] = [
+    (
+        "https://www.w3.org/2018/credentials/v1",
+        ssi_contexts::CREDENTIALS_V1,
+    ),
+    (
+        "https://www.w3.org/ns/credentials/v2",
+        ssi_contexts::CREDENTIALS_V2,
+    ),
+    (
+        "https://www.w3.org/2018/credentials/examples/v1",
+        ssi_contexts::CREDENTIALS_EXAMPLES_V1,
+    ),
+    (
+        "https://www.w3.org/ns/credentials/examples/v2",
+        ssi_contexts::CREDENTIALS_EXAMPLES_V2,
+    ),
+    ("https://www.w3.org/ns/did/v1", ssi_contexts::DID_V1),
+    ("https://w3id.org/did/v1", ssi_contexts::DID_V1),
+    ("https://w3id.org/security/v1", ssi_contexts::SECURITY_V1),
+    ("https://w3id.org/security/v2", ssi_contexts::SECURITY_V2),
+    (
+        "https://w3id.org/security/multikey/v1",
+        ssi_contexts::W3ID_MULTIKEY_V1,
+    ),
+    (
+        "https://w3id.org/security/data-integrity/v1",
+        ssi_contexts::W3ID_DATA_INTEGRITY_V1,
+    ),
+    (
+        "https://w3id.org/security/data-integrity/v2",
+        ssi_contexts::W3ID_DATA_INTEGRITY_V2,
+    ),
+    (
+        "https://w3id.org/security/suites/ed25519-2020/v1",
+        ssi_contexts::W3ID_ED2020_V1,
+    ),
+    (
+        "https://w3id.org/security/suites/jws-2020/v1",
+        ssi_contexts::W3ID_JWS2020_V1,
+    ),
+    (
+        "https://w3id.org/vc/status-list/2021/v1",
+        ssi_contexts::STATUS_LIST_2021_V1,
+    ),
+];
+
+/// Loads the JSON-LD contexts documents reference by URL. Implement this trait to serve contexts
+/// from other sources than [`ContextLoader`](struct.ContextLoader.html), e.g. from a database or,
+/// deliberately, from the network.
+///
+/// # Example
+///
+/// ```
+/// use serde_json::{json, Value};
+/// use vade::json_ld::DocumentLoader;
+///
+/// struct SingleContextLoader {}
+///
+/// impl DocumentLoader for SingleContextLoader {
+///     fn load_context(&self, url: &str) -> Result<Value, Box<dyn std::error::Error>> {
+///         match url {
+///             "https://example.org/context/v1" => Ok(json!({
+///                 "@context": { "@vocab": "https://example.org/vocab#" }
+///             })),
+///             _ => Err(Box::from(format!(r#"unknown context "{}""#, url))),
+///         }
+///     }
+/// }
+/// ```
+pub trait DocumentLoader {
+    /// Returns the context document with given URL, whose `@context` entry holds the context.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - absolute URL of context
+    fn load_context(&self, url: &str) -> Result<Value, Box<dyn std::error::Error>>;
+}
+
+/// Serves the contexts bundled with `vade` (W3C credentials v1 and v2, DID v1, security and Data
+/// Integrity suites, status lists) and contexts configured with
+/// [`with_context`](#method.with_context). Contexts are pinned to their bundled or configured
+/// content and never fetched from the network, other contexts fail to load.
+///
+/// # Example
+///
+/// ```
+/// use serde_json::json;
+/// use vade::json_ld::{self, ContextLoader};
+///
+/// fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let loader = ContextLoader::new().with_context(
+///         "https://example.org/context/v1",
+///         json!({ "@context": { "@vocab": "https://example.org/vocab#" } }),
+///     );
+///     let document = json!({
+///         "@context": "https://example.org/context/v1",
+///         "name": "Example",
+///     });
+///     println!("{}", json_ld::canonicalize(&document, &loader)?);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct ContextLoader {
+    contexts: HashMap<String, Value>,
+}
+
+impl ContextLoader {
+    /// Creates a new `ContextLoader`, that serves the bundled contexts.
+    pub fn new() -> Self {
+        ContextLoader {
+            contexts: HashMap::new(),
+        }
+    }
+
+    /// Serves given context document for given URL. A context configured for the URL of a
+    /// bundled context replaces the bundled one.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL documents reference the context with
+    /// * `document` - context document with an `@context` entry
+    pub fn with_context(mut self, url: &str, document: Value) -> Self {
+        self.contexts.insert(url.to_string(), document);
+        self
+    }
+}
+
+impl DocumentLoader for ContextLoader {
+    fn load_context(&self, url: &str) -> Result<Value, Box<dyn std::error::Error>> {
+        if let Some(document) = self.contexts.get(url) {
+            return Ok(document.clone());
+        }
+        let document = BUNDLED_CONTEXTS
+            .iter()
+            .find(|(bundled, _)| *bundled == url)
+            .map(|(_, document)| *document)
+            .ok_or_else(|| {
+                format!(
+                    r#"context "{}" is neither bundled nor configured, remote contexts are not fetched"#,
+                    url
+                )
+            })?;
+        Ok(serde_json::from_str(document)?)
+    }
+}
+
+/// Expands given JSON-LD document. Properties and types not defined by the active context are
+/// rejected.
+///
+/// # Arguments
+///
+/// * `document` - compacted JSON-LD document
+/// * `loader` - loader of referenced contexts
+///
+/// # Example
+///
+/// ```
+/// use serde_json::json;
+/// use vade::json_ld::{self, ContextLoader};
+///
+/// fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let document = json!({
+///         "@context": "https://www.w3.org/ns/credentials/v2",
+///         "type": "VerifiableCredential",
+///     });
+///     let expanded = json_ld::expand(&document, &ContextLoader::new())?;
+///     assert_eq!(
+///         expanded[0]["@type"][0],
+///         "https://www.w3.org/2018/credentials#VerifiableCredential"
+///     );
+///     Ok(())
+/// }
+/// ```
+pub fn expand(
+    document: &Value,
+    loader: &dyn DocumentLoader,
+) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    Processor::new(loader).expand(document)
+}
+
+/// Compacts given JSON-LD document with given context.
+///
+/// # Arguments
+///
+/// * `document` - JSON-LD document, either expanded or compacted with another context
+/// * `context` - context to compact with, either the context itself or a document with an
+///   `@context` entry
+/// * `loader` - loader of referenced contexts
+///
+/// # Example
+///
+/// ```
+/// use serde_json::json;
+/// use vade::json_ld::{self, ContextLoader};
+///
+/// fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let expanded = json!([{
+///         "@type": ["https://www.w3.org/2018/credentials#VerifiableCredential"],
+///     }]);
+///     let context = json!("https://www.w3.org/ns/credentials/v2");
+///     let compacted = json_ld::compact(&expanded, &context, &ContextLoader::new())?;
+///     assert_eq!(compacted["type"], "VerifiableCredential");
+///     Ok(())
+/// }
+/// ```
+pub fn compact(
+    document: &Value,
+    context: &Value,
+    loader: &dyn DocumentLoader,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let context = match context {
+        Value::Object(object) if object.contains_key("@context") => &object["@context"],
+        context => context,
+    };
+    let processor = Processor::new(loader);
+    let expanded = processor.expand(document)?;
+    let active = processor.process_context(
+        &Context::default(),
+        context,
+        None,
+        &mut Vec::new(),
+        false,
+        true,
+    )?;
+    let compacted = processor.compact_element(&active, None, &Value::Array(expanded))?;
+    let mut result = match compacted {
+        Value::Array(items) if items.is_empty() => Map::new(),
+        Value::Array(items) => {
+            let mut result = Map::new();
+            result.insert(
+                compact_iri(&active, "@graph", None, true, false)?,
+                Value::Array(items),
+            );
+            result
+        }
+        Value::Object(result) => result,
+        _ => Map::new(),
+    };
+    let is_empty = match context {
+        Value::Null => true,
+        Value::Array(items) => items.is_empty(),
+        Value::Object(object) => object.is_empty(),
+        _ => false,
+    };
+    if !is_empty {
+        let mut with_context = Map::new();
+        with_context.insert("@context".to_string(), context.clone());
+        with_context.extend(result);
+        result = with_context;
+    }
+    Ok(Value::Object(result))
+}
+
+/// Canonicalizes given JSON-LD document with
+/// [RDFC-1.0](https://www.w3.org/TR/rdf-canon/) (formerly URDNA2015) and returns the canonical
+/// N-Quads.
+///
+/// # Arguments
+///
+/// * `document` - compacted JSON-LD document
+/// * `loader` - loader of referenced contexts
+pub fn canonicalize(
+    document: &Value,
+    loader: &dyn DocumentLoader,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let (nquads, _) = rdfc::canonicalize(&to_rdf(document, loader)?)?;
+    Ok(nquads.concat())
+}
+
+/// Converts given JSON-LD document to an RDF dataset.
+///
+/// # Arguments
+///
+/// * `document` - compacted JSON-LD document
+/// * `loader` - loader of referenced contexts
+pub(crate) fn to_rdf(
+    document: &Value,
+    loader: &dyn DocumentLoader,
+) -> Result<Vec<Quad>, Box<dyn std::error::Error>> {
+    Ok(RdfWriter::new().write(&expand(document, loader)?))
+}
+
+/// Assigns IRIs to all blank nodes of given document, so that they keep their identity when parts
+/// of the document are converted to RDF on their own. Use
+/// [`deskolemize`](fn.deskolemize.html) to turn the IRIs back into blank nodes.
+///
+/// # Arguments
+///
+/// * `document` - compacted JSON-LD document
+/// * `loader` - loader of referenced contexts
+pub(crate) fn skolemize(
+    document: &Value,
+    loader: &dyn DocumentLoader,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let processor = Processor::new(loader);
+    *processor.blank_nodes.borrow_mut() = Some(Vec::new());
+    processor.expand(document)?;
+    let blank_nodes = processor
+        .blank_nodes
+        .borrow_mut()
+        .take()
+        .unwrap_or_default();
+
+    let mut skolemized = document.clone();
+    for (index, (path, id_key)) in blank_nodes.into_iter().enumerate() {
+        let node = match skolemized.pointer_mut(&path) {
+            Some(Value::Object(node)) => node,
+            _ => continue,
+        };
+        match id_key {
+            Some(id_key) => {
+                if let Some(label) = node[&id_key].as_str().and_then(|id| id.strip_prefix("_:")) {
+                    let id = format!("{}e{}", SKOLEM_PREFIX, label);
+                    node.insert(id_key, Value::from(id));
+                }
+            }
+            None => {
+                let id = format!("{}s{}", SKOLEM_PREFIX, index);
+                node.insert("@id".to_string(), Value::from(id));
+            }
+        }
+    }
+    Ok(skolemized)
+}
+
+/// Turns IRIs assigned by [`skolemize`](fn.skolemize.html) back into blank nodes.
+///
+/// # Arguments
+///
+/// * `quads` - quads of a skolemized document
+pub(crate) fn deskolemize(quads: &[Quad]) -> Vec<Quad> {
+    let map = |term: &Term| match term {
+        Term::Iri(iri) if iri.starts_with(SKOLEM_PREFIX) => {
+            Term::Blank(iri[SKOLEM_PREFIX.len()..].to_string())
+        }
+        term => term.clone(),
+    };
+    quads
+        .iter()
+        .map(|quad| Quad {
+            subject: map(&quad.subject),
+            predicate: quad.predicate.clone(),
+            object: map(&quad.object),
+            graph: quad.graph.as_ref().map(map),
+        })
+        .collect()
+}
+
+fn error(code: &str, detail: &str) -> Box<dyn std::error::Error> {
+    Box::from(format!("{}; {}", code, detail))
+}
+
+fn is_keyword(value: &str) -> bool {
+    KEYWORDS.contains(&value)
+}
+
+fn looks_like_keyword(value: &str) -> bool {
+    value.len() > 1 && value.starts_with('@') && value[1..].chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn is_absolute_iri(value: &str) -> bool {
+    match value.find(':') {
+        Some(index) if index > 0 => {
+            let scheme = &value[..index];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+fn as_array(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items,
+        value => vec![value],
+    }
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Adds given value to the entry of given key, converting the entry to an array if needed.
+fn add_value(object: &mut Map<String, Value>, key: &str, value: Value, as_array: bool) {
+    if let Value::Array(items) = value {
+        if as_array && !object.contains_key(key) {
+            object.insert(key.to_string(), Value::Array(Vec::new()));
+        }
+        for item in items.into_iter() {
+            add_value(object, key, item, as_array);
+        }
+        return;
+    }
+    match object.get_mut(key) {
+        Some(Value::Array(items)) => items.push(value),
+        Some(existing) => {
+            let previous = existing.take();
+            *existing = Value::Array(vec![previous, value]);
+        }
+        None if as_array => {
+            object.insert(key.to_string(), Value::Array(vec![value]));
+        }
+        None => {
+            object.insert(key.to_string(), value);
+        }
+    }
+}
+
+fn is_graph_object(value: &Value) -> bool {
+    match value {
+        Value::Object(object) => {
+            object.contains_key("@graph")
+                && object
+                    .keys()
+                    .all(|key| key == "@graph" || key == "@id" || key == "@index")
+        }
+        _ => false,
+    }
+}
+
+/// Resolves a relative IRI reference against given base IRI as in RFC 3986 section 5.2.
+fn resolve_iri(base: Option<&str>, reference: &str) -> String {
+    let base = match base {
+        Some(base) => base,
+        None => return reference.to_string(),
+    };
+    if is_absolute_iri(reference) {
+        return reference.to_string();
+    }
+    let (base_scheme, base_rest) = match base.find(':') {
+        Some(index) => (&base[..index], &base[index + 1..]),
+        None => return reference.to_string(),
+    };
+    let split_authority = |rest: &str| -> (Option<String>, String) {
+        match rest.strip_prefix("//") {
+            Some(rest) => {
+                let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+                (Some(rest[..end].to_string()), rest[end..].to_string())
+            }
+            None => (None, rest.to_string()),
+        }
+    };
+    let strip_fragment = |value: &str| value.split('#').next().unwrap_or_default().to_string();
+    let (base_authority, base_path_query) = split_authority(&strip_fragment(base_rest));
+    let (base_path, base_query) = match base_path_query.find('?') {
+        Some(index) => (
+            base_path_query[..index].to_string(),
+            Some(base_path_query[index..].to_string()),
+        ),
+        None => (base_path_query, None),
+    };
+
+    let (authority, path, query_fragment) = if reference.starts_with("//") {
+        let (authority, rest) = split_authority(reference);
+        let end = rest.find(['?', '#']).unwrap_or(rest.len());
+        (
+            authority,
+            remove_dot_segments(&rest[..end]),
+            rest[end..].to_string(),
+        )
+    } else {
+        let end = reference.find(['?', '#']).unwrap_or(reference.len());
+        let (path, query_fragment) = reference.split_at(end);
+        let path = if path.is_empty() {
+            base_path.clone()
+        } else if path.starts_with('/') {
+            remove_dot_segments(path)
+        } else {
+            let merged = if base_authority.is_some() && base_path.is_empty() {
+                format!("/{}", path)
+            } else {
+                match base_path.rfind('/') {
+                    Some(index) => format!("{}{}", &base_path[..=index], path),
+                    None => path.to_string(),
+                }
+            };
+            remove_dot_segments(&merged)
+        };
+        let query_fragment = if reference.is_empty() || reference.starts_with('#') {
+            format!(
+                "{}{}",
+                base_query.clone().unwrap_or_default(),
+                query_fragment
+            )
+        } else {
+            query_fragment.to_string()
+        };
+        (base_authority.clone(), path, query_fragment)
+    };
+
+    match authority {
+        Some(authority) => format!("{}://{}{}{}", base_scheme, authority, path, query_fragment),
+        None => format!("{}:{}{}", base_scheme, path, query_fragment),
+    }
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let mut output: Vec<&str> = Vec::new();
+    let segments: Vec<&str> = path.split('/').collect();
+    for (index, segment) in segments.iter().enumerate() {
+        let is_last = index == segments.len() - 1;
+        match *segment {
+            "." => {
+                if is_last {
+                    output.push("");
+                }
+            }
+            ".." => {
+                if output.len() > 1 {
+                    output.pop();
+                }
+                if is_last {
+                    output.push("");
+                }
+            }
+            segment => output.push(segment),
+        }
+    }
+    output.join("/")
+}
+
+#[derive(Clone, Default)]
+struct Context {
+    terms: HashMap<String, TermDefinition>,
+    base: Option<String>,
+    original_base: Option<String>,
+    vocab: Option<String>,
+    language: Option<String>,
+    direction: Option<String>,
+    previous: Option<Box<Context>>,
+    /// inverse context, created when first compacting with the context
+    inverse: OnceCell<Rc<InverseContext>>,
+}
+
+/// Terms by `@language`, `@type` or `@any` and the language, direction or type of their values
+type TypeLanguageMap = HashMap<String, HashMap<String, String>>;
+
+/// Terms by IRI and container mapping, see the
+/// [inverse context](https://www.w3.org/TR/json-ld11-api/#inverse-context-creation)
+type InverseContext = HashMap<String, HashMap<String, TypeLanguageMap>>;
+
+#[derive(Clone, Default, PartialEq)]
+struct TermDefinition {
+    iri: Option<String>,
+    prefix: bool,
+    protected: bool,
+    reverse: bool,
+    type_mapping: Option<String>,
+    /// `Some(None)` if the language is explicitly set to null
+    language: Option<Option<String>>,
+    direction: Option<Option<String>>,
+    container: Vec<String>,
+    context: Option<Value>,
+    base_url: Option<String>,
+    nest: Option<String>,
+    index: Option<String>,
+}
+
+impl TermDefinition {
+    fn has_container(&self, container: &str) -> bool {
+        self.container.iter().any(|c| c == container)
+    }
+}
+
+impl Context {
+    fn has_protected_terms(&self) -> bool {
+        self.terms.values().any(|term| term.protected)
+    }
+
+    fn container(&self, term: &str) -> Vec<String> {
+        self.terms
+            .get(term)
+            .map(|term| term.container.clone())
+            .unwrap_or_default()
+    }
+
+    /// Expands given value to an IRI without defining new terms.
+    fn expand_iri(&self, value: &str, document_relative: bool, vocab: bool) -> Option<String> {
+        if is_keyword(value) {
+            return Some(value.to_string());
+        }
+        if looks_like_keyword(value) {
+            return None;
+        }
+        if let Some(term) = self.terms.get(value) {
+            if term.iri.as_deref().map(is_keyword).unwrap_or(false) || vocab {
+                return term.iri.clone();
+            }
+        }
+        if let Some(index) = value
+            .get(1..)
+            .and_then(|rest| rest.find(':'))
+            .map(|i| i + 1)
+        {
+            let (prefix, suffix) = (&value[..index], &value[index + 1..]);
+            if prefix == "_" || suffix.starts_with("//") {
+                return Some(value.to_string());
+            }
+            if let Some(term) = self.terms.get(prefix) {
+                if let (Some(iri), true) = (&term.iri, term.prefix) {
+                    return Some(format!("{}{}", iri, suffix));
+                }
+            }
+            if is_absolute_iri(value) {
+                return Some(value.to_string());
+            }
+        }
+        if vocab {
+            if let Some(vocab) = &self.vocab {
+                return Some(format!("{}{}", vocab, value));
+            }
+        }
+        if document_relative {
+            return Some(resolve_iri(self.base.as_deref(), value));
+        }
+        Some(value.to_string())
+    }
+
+    /// Returns the default language and direction as used as key of an inverse context.
+    fn default_language(&self) -> String {
+        match (&self.language, &self.direction) {
+            (Some(language), Some(direction)) => format!("{}_{}", language, direction),
+            (Some(language), None) => language.clone(),
+            (None, Some(direction)) => format!("_{}", direction),
+            (None, None) => "@none".to_string(),
+        }
+    }
+
+    fn inverse(&self) -> &InverseContext {
+        self.inverse.get_or_init(|| Rc::new(self.create_inverse()))
+    }
+
+    fn create_inverse(&self) -> InverseContext {
+        let default_language = self.default_language();
+        let mut terms: Vec<&String> = self.terms.keys().collect();
+        terms.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+        let mut result = InverseContext::new();
+        for term in terms.into_iter() {
+            let definition = &self.terms[term];
+            let iri = match &definition.iri {
+                Some(iri) => iri,
+                None => continue,
+            };
+            let container = match definition.container.is_empty() {
+                true => "@none".to_string(),
+                false => definition.container.concat(),
+            };
+            let entry = result
+                .entry(iri.clone())
+                .or_default()
+                .entry(container)
+                .or_default();
+            let mut set = |type_language: &str, key: &str| {
+                entry
+                    .entry(type_language.to_string())
+                    .or_default()
+                    .entry(key.to_string())
+                    .or_insert_with(|| term.clone());
+            };
+            set("@any", "@none");
+            if definition.reverse {
+                set("@type", "@reverse");
+            } else if definition.type_mapping.as_deref() == Some("@none") {
+                set("@language", "@any");
+                set("@type", "@any");
+            } else if let Some(type_mapping) = &definition.type_mapping {
+                set("@type", type_mapping);
+            } else {
+                match (&definition.language, &definition.direction) {
+                    (Some(language), Some(direction)) => {
+                        let key = match (language, direction) {
+                            (Some(language), Some(direction)) => {
+                                format!("{}_{}", language, direction)
+                            }
+                            (Some(language), None) => language.clone(),
+                            (None, Some(direction)) => format!("_{}", direction),
+                            (None, None) => "@null".to_string(),
+                        };
+                        set("@language", &key);
+                    }
+                    (Some(language), None) => {
+                        set("@language", language.as_deref().unwrap_or("@null"));
+                    }
+                    (None, Some(direction)) => {
+                        let key = match direction {
+                            Some(direction) => format!("_{}", direction),
+                            None => "@none".to_string(),
+                        };
+                        set("@language", &key);
+                    }
+                    (None, None) => {
+                        set("@language", &default_language);
+                        set("@language", "@none");
+                        set("@type", "@none");
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Selects the term for given IRI, that fits the first of given containers and preferred
+    /// values.
+    fn select_term(
+        &self,
+        iri: &str,
+        containers: &[&str],
+        type_language: &str,
+        preferred_values: &[String],
+    ) -> Option<String> {
+        let container_map = self.inverse().get(iri)?;
+        for container in containers.iter() {
+            let type_language_map = match container_map
+                .get(*container)
+                .and_then(|entry| entry.get(type_language))
+            {
+                Some(type_language_map) => type_language_map,
+                None => continue,
+            };
+            for value in preferred_values.iter() {
+                if let Some(term) = type_language_map.get(value) {
+                    return Some(term.clone());
+                }
+            }
+        }
+        None
+    }
+}
+
+/// State of the local context, whose terms are currently defined.
+struct TermScope<'a> {
+    local: &'a Map<String, Value>,
+    defined: HashMap<String, bool>,
+    base_url: Option<&'a str>,
+    protected: bool,
+    override_protected: bool,
+    remote_contexts: &'a [String],
+}
+
+/// JSON pointer of a blank node and the key of its blank node identifier, if it has one
+type BlankNodePath = (String, Option<String>);
+
+struct Processor<'a> {
+    loader: &'a dyn DocumentLoader,
+    /// paths of blank nodes if recorded
+    blank_nodes: RefCell<Option<Vec<BlankNodePath>>>,
+}
+
+impl<'a> Processor<'a> {
+    fn new(loader: &'a dyn DocumentLoader) -> Self {
+        Processor {
+            loader,
+            blank_nodes: RefCell::new(None),
+        }
+    }
+
+    fn expand(&self, document: &Value) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+        let expanded = self.expand_element(&Context::default(), None, document, None, "", false)?;
+        let expanded = match expanded {
+            Value::Object(mut object) if object.len() == 1 && object.contains_key("@graph") => {
+                object.remove("@graph").unwrap_or(Value::Null)
+            }
+            expanded => expanded,
+        };
+        Ok(match expanded {
+            Value::Null => Vec::new(),
+            expanded => as_array(expanded),
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_context(
+        &self,
+        active: &Context,
+        local: &Value,
+        base_url: Option<&str>,
+        remote_contexts: &mut Vec<String>,
+        override_protected: bool,
+        mut propagate: bool,
+    ) -> Result<Context, Box<dyn std::error::Error>> {
+        let mut result = active.clone();
+        result.inverse = OnceCell::new();
+        if let Some(value) = local.get("@propagate") {
+            propagate = value
+                .as_bool()
+                .ok_or_else(|| error("invalid @propagate value", &value.to_string()))?;
+        }
+        if !propagate && result.previous.is_none() {
+            result.previous = Some(Box::new(active.clone()));
+        }
+
+        for context in as_array(local.clone()).iter() {
+            let context = match context {
+                Value::Null => {
+                    if !override_protected && result.has_protected_terms() {
+                        return Err(error("invalid context nullification", "protected terms"));
+                    }
+                    let previous = result.clone();
+                    result = Context {
+                        base: active.original_base.clone(),
+                        original_base: active.original_base.clone(),
+                        ..Context::default()
+                    };
+                    if !propagate {
+                        result.previous = Some(Box::new(previous));
+                    }
+                    continue;
+                }
+                Value::String(url) => {
+                    let url = resolve_iri(base_url, url);
+                    if remote_contexts.contains(&url) {
+                        continue;
+                    }
+                    if remote_contexts.len() >= MAX_REMOTE_CONTEXTS {
+                        return Err(error("context overflow", &url));
+                    }
+                    remote_contexts.push(url.clone());
+                    let loaded = self
+                        .loader
+                        .load_context(&url)
+                        .map_err(|e| error("loading remote context failed", &e.to_string()))?;
+                    let loaded = loaded
+                        .get("@context")
+                        .ok_or_else(|| error("invalid remote context", &url))?;
+                    result = self.process_context(
+                        &result,
+                        loaded,
+                        Some(&url),
+                        &mut remote_contexts.clone(),
+                        false,
+                        true,
+                    )?;
+                    continue;
+                }
+                Value::Object(context) => context,
+                _ => return Err(error("invalid local context", &context.to_string())),
+            };
+
+            let mut context = context.clone();
+            if let Some(version) = context.get("@version") {
+                if version.as_f64() != Some(1.1) {
+                    return Err(error("invalid @version value", &version.to_string()));
+                }
+            }
+            if let Some(import) = context.remove("@import") {
+                let url = resolve_iri(
+                    base_url,
+                    import
+                        .as_str()
+                        .ok_or_else(|| error("invalid @import value", &import.to_string()))?,
+                );
+                let imported = self
+                    .loader
+                    .load_context(&url)
+                    .map_err(|e| error("loading remote context failed", &e.to_string()))?;
+                let imported = match imported.get("@context") {
+                    Some(Value::Object(imported)) if !imported.contains_key("@import") => {
+                        imported.clone()
+                    }
+                    _ => return Err(error("invalid remote context", &url)),
+                };
+                for (key, value) in imported.into_iter() {
+                    context.entry(key).or_insert(value);
+                }
+            }
+            if let Some(base) = context.get("@base") {
+                if remote_contexts.is_empty() {
+                    result.base = match base {
+                        Value::Null => None,
+                        Value::String(base) if is_absolute_iri(base) => Some(base.clone()),
+                        Value::String(base) if result.base.is_some() => {
+                            Some(resolve_iri(result.base.as_deref(), base))
+                        }
+                        _ => return Err(error("invalid base IRI", &base.to_string())),
+                    };
+                }
+            }
+            if let Some(vocab) = context.get("@vocab") {
+                result.vocab = match vocab {
+                    Value::Null => None,
+                    Value::String(vocab) => match result.expand_iri(vocab, true, true) {
+                        Some(iri) if is_absolute_iri(&iri) || iri.starts_with("_:") => Some(iri),
+                        _ => return Err(error("invalid vocab mapping", vocab)),
+                    },
+                    _ => return Err(error("invalid vocab mapping", &vocab.to_string())),
+                };
+            }
+            if let Some(language) = context.get("@language") {
+                result.language = match language {
+                    Value::Null => None,
+                    Value::String(language) => Some(language.to_lowercase()),
+                    _ => return Err(error("invalid default language", &language.to_string())),
+                };
+            }
+            if let Some(direction) = context.get("@direction") {
+                result.direction = match direction {
+                    Value::Null => None,
+                    Value::String(direction) if direction == "ltr" || direction == "rtl" => {
+                        Some(direction.clone())
+                    }
+                    _ => return Err(error("invalid base direction", &direction.to_string())),
+                };
+            }
+            if let Some(propagate) = context.get("@propagate") {
+                if !propagate.is_boolean() {
+                    return Err(error("invalid @propagate value", &propagate.to_string()));
+                }
+            }
+
+            let protected = context
+                .get("@protected")
+                .and_then(|protected| protected.as_bool())
+                .unwrap_or(false);
+            let mut scope = TermScope {
+                local: &context,
+                defined: HashMap::new(),
+                base_url,
+                protected,
+                override_protected,
+                remote_contexts,
+            };
+            for term in context.keys() {
+                match term.as_str() {
+                    "@base" | "@direction" | "@import" | "@language" | "@propagate"
+                    | "@protected" | "@version" | "@vocab" => (),
+                    term => self.create_term_definition(&mut result, &mut scope, term)?,
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn expand_iri_defining(
+        &self,
+        active: &mut Context,
+        scope: &mut TermScope,
+        value: &str,
+        document_relative: bool,
+        vocab: bool,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if !is_keyword(value)
+            && scope.local.contains_key(value)
+            && scope.defined.get(value) != Some(&true)
+        {
+            self.create_term_definition(active, scope, value)?;
+        }
+        if let Some(index) = value
+            .get(1..)
+            .and_then(|rest| rest.find(':'))
+            .map(|i| i + 1)
+        {
+            let prefix = &value[..index];
+            if scope.local.contains_key(prefix) && scope.defined.get(prefix) != Some(&true) {
+                self.create_term_definition(active, scope, prefix)?;
+            }
+        }
+        Ok(active.expand_iri(value, document_relative, vocab))
+    }
+
+    fn create_term_definition(
+        &self,
+        active: &mut Context,
+        scope: &mut TermScope,
+        term: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match scope.defined.get(term) {
+            Some(true) => return Ok(()),
+            Some(false) => return Err(error("cyclic IRI mapping", term)),
+            None => (),
+        }
+        if term.is_empty() {
+            return Err(error("invalid term definition", "empty term"));
+        }
+        scope.defined.insert(term.to_string(), false);
+        let value = scope.local[term].clone();
+
+        if term == "@type" {
+            let valid = match &value {
+                Value::Object(object) => {
+                    !object.is_empty()
+                        && object.iter().all(|(key, value)| match key.as_str() {
+                            "@container" => value == "@set",
+                            "@protected" => value.is_boolean(),
+                            _ => false,
+                        })
+                }
+                _ => false,
+            };
+            if !valid {
+                return Err(error("keyword redefinition", term));
+            }
+        } else if is_keyword(term) {
+            return Err(error("keyword redefinition", term));
+        } else if looks_like_keyword(term) {
+            scope.defined.insert(term.to_string(), true);
+            return Ok(());
+        }
+
+        let previous = active.terms.remove(term);
+        let (value, simple_term) = match value {
+            Value::Null => (json!({ "@id": null }), false),
+            Value::String(id) => (json!({ "@id": id }), true),
+            Value::Object(_) => (value, false),
+            _ => return Err(error("invalid term definition", term)),
+        };
+        let value = value.as_object().cloned().unwrap_or_default();
+        let mut definition = TermDefinition {
+            protected: scope.protected,
+            ..TermDefinition::default()
+        };
+
+        for key in value.keys() {
+            match key.as_str() {
+                "@id" | "@reverse" | "@container" | "@context" | "@direction" | "@index"
+                | "@language" | "@nest" | "@prefix" | "@protected" | "@type" => (),
+                _ => return Err(error("invalid term definition", term)),
+            }
+        }
+        if let Some(protected) = value.get("@protected") {
+            definition.protected = protected
+                .as_bool()
+                .ok_or_else(|| error("invalid @protected value", term))?;
+        }
+        if let Some(type_mapping) = value.get("@type") {
+            let type_mapping = type_mapping
+                .as_str()
+                .ok_or_else(|| error("invalid type mapping", term))?;
+            let type_mapping = self
+                .expand_iri_defining(active, scope, type_mapping, false, true)?
+                .ok_or_else(|| error("invalid type mapping", term))?;
+            match type_mapping.as_str() {
+                "@id" | "@json" | "@none" | "@vocab" => (),
+                iri if is_absolute_iri(iri) => (),
+                _ => return Err(error("invalid type mapping", term)),
+            }
+            definition.type_mapping = Some(type_mapping);
+        }
+
+        if let Some(reverse) = value.get("@reverse") {
+            if value.contains_key("@id") || value.contains_key("@nest") {
+                return Err(error("invalid reverse property", term));
+            }
+            let reverse = reverse
+                .as_str()
+                .ok_or_else(|| error("invalid IRI mapping", term))?;
+            if looks_like_keyword(reverse) {
+                scope.defined.insert(term.to_string(), true);
+                return Ok(());
+            }
+            let iri = self
+                .expand_iri_defining(active, scope, reverse, false, true)?
+                .filter(|iri| is_absolute_iri(iri) || iri.starts_with("_:"))
+                .ok_or_else(|| error("invalid IRI mapping", term))?;
+            definition.iri = Some(iri);
+            if let Some(container) = value.get("@container") {
+                match container {
+                    Value::Null => (),
+                    Value::String(container) if container == "@set" || container == "@index" => {
+                        definition.container = vec![container.clone()]
+                    }
+                    _ => return Err(error("invalid reverse property", term)),
+                }
+            }
+            definition.reverse = true;
+            active.terms.insert(term.to_string(), definition);
+            scope.defined.insert(term.to_string(), true);
+            return Ok(());
+        }
+
+        let colon = term.get(1..).and_then(|rest| rest.find(':')).map(|i| i + 1);
+        match value.get("@id") {
+            Some(id) if id != term => match id {
+                Value::Null => definition.iri = None,
+                Value::String(id) => {
+                    if !is_keyword(id) && looks_like_keyword(id) {
+                        scope.defined.insert(term.to_string(), true);
+                        return Ok(());
+                    }
+                    let iri = self
+                        .expand_iri_defining(active, scope, id, false, true)?
+                        .filter(|iri| {
+                            is_keyword(iri) || is_absolute_iri(iri) || iri.starts_with("_:")
+                        })
+                        .ok_or_else(|| error("invalid IRI mapping", term))?;
+                    if iri == "@context" {
+                        return Err(error("invalid keyword alias", term));
+                    }
+                    let has_colon = colon.map(|i| i < term.len() - 1).unwrap_or(false);
+                    if has_colon || term.contains('/') {
+                        scope.defined.insert(term.to_string(), true);
+                        if self
+                            .expand_iri_defining(active, scope, term, false, true)?
+                            .as_ref()
+                            != Some(&iri)
+                        {
+                            return Err(error("invalid IRI mapping", term));
+                        }
+                    }
+                    if !term.contains(':')
+                        && !term.contains('/')
+                        && simple_term
+                        && (iri.ends_with(|c| ":/?#[]@".contains(c)) || iri.starts_with("_:"))
+                    {
+                        definition.prefix = true;
+                    }
+                    definition.iri = Some(iri);
+                }
+                _ => return Err(error("invalid IRI mapping", term)),
+            },
+            _ => {
+                if let Some(index) = colon {
+                    let (prefix, suffix) = (&term[..index], &term[index + 1..]);
+                    if scope.local.contains_key(prefix) {
+                        self.create_term_definition(active, scope, prefix)?;
+                    }
+                    definition.iri = match active.terms.get(prefix).and_then(|p| p.iri.clone()) {
+                        Some(iri) => Some(format!("{}{}", iri, suffix)),
+                        None => Some(term.to_string()),
+                    };
+                } else if term.contains('/') {
+                    definition.iri = active
+                        .expand_iri(term, false, true)
+                        .filter(|iri| is_absolute_iri(iri))
+                        .map(Some)
+                        .ok_or_else(|| error("invalid IRI mapping", term))?;
+                } else if term == "@type" {
+                    definition.iri = Some("@type".to_string());
+                } else {
+                    let vocab = active
+                        .vocab
+                        .as_ref()
+                        .ok_or_else(|| error("invalid IRI mapping", term))?;
+                    definition.iri = Some(format!("{}{}", vocab, term));
+                }
+            }
+        }
+
+        if let Some(container) = value.get("@container") {
+            let mut containers = Vec::new();
+            for container in as_array(container.clone()).into_iter() {
+                match container.as_str() {
+                    Some(
+                        c
+                        @ ("@graph" | "@id" | "@index" | "@language" | "@list" | "@set" | "@type"),
+                    ) => containers.push(c.to_string()),
+                    _ => return Err(error("invalid container mapping", term)),
+                }
+            }
+            containers.sort();
+            if containers.iter().any(|c| c == "@type") {
+                match definition.type_mapping.as_deref() {
+                    None => definition.type_mapping = Some("@id".to_string()),
+                    Some("@id") | Some("@vocab") => (),
+                    _ => return Err(error("invalid type mapping", term)),
+                }
+            }
+            definition.container = containers;
+        }
+        if let Some(index) = value.get("@index") {
+            if !definition.has_container("@index") {
+                return Err(error("invalid term definition", term));
+            }
+            let index = index
+                .as_str()
+                .filter(|index| !is_keyword(index))
+                .ok_or_else(|| error("invalid term definition", term))?;
+            definition.index = Some(index.to_string());
+        }
+        if let Some(context) = value.get("@context") {
+            // validate the scoped context, it is applied when the term is used
+            self.process_context(
+                active,
+                context,
+                scope.base_url,
+                &mut scope.remote_contexts.to_vec(),
+                true,
+                true,
+            )
+            .map_err(|e| error("invalid scoped context", &e.to_string()))?;
+            definition.context = Some(context.clone());
+            definition.base_url = scope.base_url.map(|url| url.to_string());
+        }
+        if let Some(language) = value.get("@language") {
+            if !value.contains_key("@type") {
+                definition.language = Some(match language {
+                    Value::Null => None,
+                    Value::String(language) => Some(language.to_lowercase()),
+                    _ => return Err(error("invalid language mapping", term)),
+                });
+            }
+        }
+        if let Some(direction) = value.get("@direction") {
+            if !value.contains_key("@type") {
+                definition.direction = Some(match direction {
+                    Value::Null => None,
+                    Value::String(d) if d == "ltr" || d == "rtl" => Some(d.clone()),
+                    _ => return Err(error("invalid base direction", term)),
+                });
+            }
+        }
+        if let Some(nest) = value.get("@nest") {
+            match nest.as_str() {
+                Some(nest) if !is_keyword(nest) || nest == "@nest" => {
+                    definition.nest = Some(nest.to_string())
+                }
+                _ => return Err(error("invalid @nest value", term)),
+            }
+        }
+        if let Some(prefix) = value.get("@prefix") {
+            if term.contains(':') || term.contains('/') {
+                return Err(error("invalid term definition", term));
+            }
+            definition.prefix = prefix
+                .as_bool()
+                .ok_or_else(|| error("invalid @prefix value", term))?;
+            if definition.prefix && definition.iri.as_deref().map(is_keyword).unwrap_or(false) {
+                return Err(error("invalid term definition", term));
+            }
+        }
+
+        if !scope.override_protected {
+            if let Some(previous) = previous.filter(|previous| previous.protected) {
+                let mut compared = definition.clone();
+                compared.protected = true;
+                if compared != previous {
+                    return Err(error("protected term redefinition", term));
+                }
+                definition = previous;
+            }
+        }
+        active.terms.insert(term.to_string(), definition);
+        scope.defined.insert(term.to_string(), true);
+        Ok(())
+    }
+
+    fn expand_element(
+        &self,
+        active: &Context,
+        active_property: Option<&str>,
+        element: &Value,
+        base_url: Option<&str>,
+        path: &str,
+        from_map: bool,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let property_definition = active_property.and_then(|p| active.terms.get(p)).cloned();
+        let property_scoped_context = property_definition
+            .as_ref()
+            .and_then(|definition| definition.context.clone());
+
+        let element = match element {
+            Value::Null => return Ok(Value::Null),
+            Value::Array(items) => {
+                let mut result = Vec::new();
+                let is_list = active_property
+                    .map(|p| active.container(p).iter().any(|c| c == "@list"))
+                    .unwrap_or(false);
+                for (index, item) in items.iter().enumerate() {
+                    let item_path = format!("{}/{}", path, index);
+                    let expanded = self.expand_element(
+                        active,
+                        active_property,
+                        item,
+                        base_url,
+                        &item_path,
+                        from_map,
+                    )?;
+                    match expanded {
+                        Value::Null => (),
+                        Value::Array(expanded) if is_list => {
+                            result.push(json!({ "@list": expanded }))
+                        }
+                        Value::Array(expanded) => result.extend(expanded),
+                        expanded => result.push(expanded),
+                    }
+                }
+                return Ok(Value::Array(result));
+            }
+            Value::Object(element) => element,
+            scalar => {
+                if active_property.is_none() || active_property == Some("@graph") {
+                    return Ok(Value::Null);
+                }
+                let active = match &property_scoped_context {
+                    Some(context) => self.process_context(
+                        active,
+                        context,
+                        property_definition
+                            .as_ref()
+                            .and_then(|d| d.base_url.as_deref()),
+                        &mut Vec::new(),
+                        true,
+                        true,
+                    )?,
+                    None => active.clone(),
+                };
+                return Ok(expand_value(&active, active_property, scalar));
+            }
+        };
+
+        let mut active = active.clone();
+        if let Some(previous) = &active.previous {
+            let mut keeps_context = from_map;
+            for key in element.keys() {
+                if active.expand_iri(key, false, true).as_deref() == Some("@value") {
+                    keeps_context = true;
+                }
+            }
+            if element.len() == 1 {
+                let key = element.keys().next().cloned().unwrap_or_default();
+                if active.expand_iri(&key, false, true).as_deref() == Some("@id") {
+                    keeps_context = true;
+                }
+            }
+            if !keeps_context {
+                active = *previous.clone();
+            }
+        }
+        if let Some(context) = &property_scoped_context {
+            active = self.process_context(
+                &active,
+                context,
+                property_definition
+                    .as_ref()
+                    .and_then(|d| d.base_url.as_deref()),
+                &mut Vec::new(),
+                true,
+                true,
+            )?;
+        }
+        if let Some(context) = element.get("@context") {
+            active =
+                self.process_context(&active, context, base_url, &mut Vec::new(), false, true)?;
+        }
+
+        let type_scoped_context = active.clone();
+        let mut input_type = None;
+        for (key, value) in element.iter() {
+            if active.expand_iri(key, false, true).as_deref() != Some("@type") {
+                continue;
+            }
+            let mut types: Vec<String> = as_array(value.clone())
+                .iter()
+                .filter_map(|t| t.as_str().map(|t| t.to_string()))
+                .collect();
+            if input_type.is_none() {
+                input_type = types.last().and_then(|t| active.expand_iri(t, false, true));
+            }
+            types.sort();
+            for term in types.iter() {
+                if let Some(definition) = type_scoped_context.terms.get(term) {
+                    if let Some(context) = &definition.context {
+                        active = self.process_context(
+                            &active,
+                            context,
+                            definition.base_url.as_deref(),
+                            &mut Vec::new(),
+                            false,
+                            false,
+                        )?;
+                    }
+                }
+            }
+        }
+
+        let mut result = Map::new();
+        self.expand_object(
+            element,
+            &active,
+            &type_scoped_context,
+            active_property,
+            input_type.as_deref(),
+            base_url,
+            path,
+            &mut result,
+        )?;
+
+        if let Some(value) = result.get("@value") {
+            let allowed = ["@direction", "@index", "@language", "@type", "@value"];
+            if result.keys().any(|key| !allowed.contains(&key.as_str()))
+                || (result.contains_key("@type")
+                    && (result.contains_key("@language") || result.contains_key("@direction")))
+            {
+                return Err(error(
+                    "invalid value object",
+                    &Value::Object(result).to_string(),
+                ));
+            }
+            if result.get("@type").and_then(|t| t.as_str()) != Some("@json") {
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+                if !value.is_string() && result.contains_key("@language") {
+                    return Err(error("invalid language-tagged value", &value.to_string()));
+                }
+                if let Some(datatype) = result.get("@type") {
+                    if !datatype.as_str().map(is_absolute_iri).unwrap_or(false) {
+                        return Err(error("invalid typed value", &datatype.to_string()));
+                    }
+                }
+            }
+        } else if let Some(types) = result.get_mut("@type") {
+            if !types.is_array() {
+                *types = Value::Array(vec![types.take()]);
+            }
+        } else if result.contains_key("@set") || result.contains_key("@list") {
+            if result.len() > 2 || (result.len() == 2 && !result.contains_key("@index")) {
+                return Err(error(
+                    "invalid set or list object",
+                    &Value::Object(result).to_string(),
+                ));
+            }
+            if let Some(set) = result.remove("@set") {
+                return Ok(set);
+            }
+        }
+
+        if result.len() == 1 && result.contains_key("@language") {
+            return Ok(Value::Null);
+        }
+        if (active_property.is_none() || active_property == Some("@graph"))
+            && (result.is_empty()
+                || result.contains_key("@value")
+                || result.contains_key("@list")
+                || (result.len() == 1 && result.contains_key("@id")))
+        {
+            return Ok(Value::Null);
+        }
+
+        if !result.contains_key("@value") && !result.contains_key("@list") {
+            if let Some(blank_nodes) = self.blank_nodes.borrow_mut().as_mut() {
+                match result.get("@id").and_then(|id| id.as_str()) {
+                    None => blank_nodes.push((path.to_string(), None)),
+                    Some(id) if id.starts_with("_:") => {
+                        let key = element
+                            .keys()
+                            .find(|key| {
+                                active.expand_iri(key, false, true).as_deref() == Some("@id")
+                            })
+                            .cloned();
+                        blank_nodes.push((path.to_string(), key));
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+
+        Ok(Value::Object(result))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn expand_object(
+        &self,
+        element: &Map<String, Value>,
+        active: &Context,
+        type_scoped_context: &Context,
+        active_property: Option<&str>,
+        input_type: Option<&str>,
+        base_url: Option<&str>,
+        path: &str,
+        result: &mut Map<String, Value>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut nests = Vec::new();
+        for (key, value) in element.iter() {
+            if key == "@context" {
+                continue;
+            }
+            let expanded_property = match active.expand_iri(key, false, true) {
+                Some(property) if property.contains(':') || is_keyword(&property) => property,
+                // dropping terms would leave them unsigned, so only keyword-like keys are ignored
+                _ if looks_like_keyword(key) => continue,
+                _ => return Err(error("undefined term", key)),
+            };
+            let value_path = format!("{}/{}", path, escape_pointer(key));
+
+            if is_keyword(&expanded_property) {
+                if active_property == Some("@reverse") {
+                    return Err(error("invalid reverse property map", key));
+                }
+                if result.contains_key(&expanded_property)
+                    && expanded_property != "@included"
+                    && expanded_property != "@type"
+                {
+                    return Err(error("colliding keywords", &expanded_property));
+                }
+                let expanded_value = match expanded_property.as_str() {
+                    "@id" => match value {
+                        Value::String(id) => active
+                            .expand_iri(id, true, false)
+                            .map(Value::from)
+                            .unwrap_or(Value::Null),
+                        _ => return Err(error("invalid @id value", &value.to_string())),
+                    },
+                    "@type" => {
+                        let expand_type = |t: &Value| -> Result<Value, Box<dyn std::error::Error>> {
+                            let t = t
+                                .as_str()
+                                .ok_or_else(|| error("invalid type value", &t.to_string()))?;
+                            match type_scoped_context.expand_iri(t, true, true) {
+                                Some(iri) if iri.contains(':') => Ok(Value::from(iri)),
+                                _ => Err(error("undefined type", t)),
+                            }
+                        };
+                        let expanded = match value {
+                            Value::Array(types) => Value::Array(
+                                types.iter().map(expand_type).collect::<Result<_, _>>()?,
+                            ),
+                            value => expand_type(value)?,
+                        };
+                        match result.remove("@type") {
+                            Some(existing) => {
+                                let mut types = as_array(existing);
+                                types.extend(as_array(expanded));
+                                Value::Array(types)
+                            }
+                            None => expanded,
+                        }
+                    }
+                    "@graph" => Value::Array(as_array(self.expand_element(
+                        active,
+                        Some("@graph"),
+                        value,
+                        base_url,
+                        &value_path,
+                        false,
+                    )?)),
+                    "@included" => {
+                        let included = as_array(self.expand_element(
+                            active,
+                            None,
+                            value,
+                            base_url,
+                            &value_path,
+                            false,
+                        )?);
+                        if included.iter().any(|node| {
+                            !node.is_object()
+                                || node.get("@value").is_some()
+                                || node.get("@list").is_some()
+                        }) {
+                            return Err(error("invalid @included value", &value.to_string()));
+                        }
+                        let mut existing =
+                            as_array(result.remove("@included").unwrap_or(json!([])));
+                        existing.extend(included);
+                        Value::Array(existing)
+                    }
+                    "@value" => {
+                        if input_type == Some("@json") {
+                            result.insert("@value".to_string(), value.clone());
+                            continue;
+                        }
+                        if value.is_object() || value.is_array() {
+                            return Err(error("invalid value object value", &value.to_string()));
+                        }
+                        result.insert("@value".to_string(), value.clone());
+                        continue;
+                    }
+                    "@language" => match value {
+                        Value::String(language) => Value::from(language.to_lowercase()),
+                        _ => {
+                            return Err(error("invalid language-tagged string", &value.to_string()))
+                        }
+                    },
+                    "@direction" => match value.as_str() {
+                        Some("ltr") | Some("rtl") => value.clone(),
+                        _ => return Err(error("invalid base direction", &value.to_string())),
+                    },
+                    "@index" => match value {
+                        Value::String(_) => value.clone(),
+                        _ => return Err(error("invalid @index value", &value.to_string())),
+                    },
+                    "@list" => {
+                        if active_property.is_none() || active_property == Some("@graph") {
+                            continue;
+                        }
+                        Value::Array(as_array(self.expand_element(
+                            active,
+                            active_property,
+                            value,
+                            base_url,
+                            &value_path,
+                            false,
+                        )?))
+                    }
+                    "@set" => self.expand_element(
+                        active,
+                        active_property,
+                        value,
+                        base_url,
+                        &value_path,
+                        false,
+                    )?,
+                    "@reverse" => {
+                        if !value.is_object() {
+                            return Err(error("invalid @reverse value", &value.to_string()));
+                        }
+                        let expanded = self.expand_element(
+                            active,
+                            Some("@reverse"),
+                            value,
+                            base_url,
+                            &value_path,
+                            false,
+                        )?;
+                        let mut expanded = match expanded {
+                            Value::Object(expanded) => expanded,
+                            _ => continue,
+                        };
+                        if let Some(Value::Object(reverse)) = expanded.remove("@reverse") {
+                            for (property, items) in reverse.into_iter() {
+                                add_value(result, &property, items, true);
+                            }
+                        }
+                        if !expanded.is_empty() {
+                            let mut reverse_map = match result.remove("@reverse") {
+                                Some(Value::Object(reverse_map)) => reverse_map,
+                                _ => Map::new(),
+                            };
+                            for (property, items) in expanded.into_iter() {
+                                for item in as_array(items).into_iter() {
+                                    if item.get("@value").is_some() || item.get("@list").is_some() {
+                                        return Err(error(
+                                            "invalid reverse property value",
+                                            &property,
+                                        ));
+                                    }
+                                    add_value(&mut reverse_map, &property, item, true);
+                                }
+                            }
+                            result.insert("@reverse".to_string(), Value::Object(reverse_map));
+                        }
+                        continue;
+                    }
+                    "@nest" => {
+                        nests.push(key.clone());
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if !expanded_value.is_null() {
+                    result.insert(expanded_property, expanded_value);
+                }
+                continue;
+            }
+
+            let definition = active.terms.get(key).cloned().unwrap_or_default();
+            let mut expanded_value = if definition.type_mapping.as_deref() == Some("@json") {
+                json!({ "@value": value, "@type": "@json" })
+            } else if definition.has_container("@language") && value.is_object() {
+                self.expand_language_map(active, &definition, value)?
+            } else if (definition.has_container("@index")
+                || definition.has_container("@type")
+                || definition.has_container("@id"))
+                && value.is_object()
+            {
+                self.expand_index_map(
+                    active,
+                    type_scoped_context,
+                    key,
+                    &definition,
+                    value,
+                    base_url,
+                    &value_path,
+                )?
+            } else {
+                self.expand_element(active, Some(key), value, base_url, &value_path, false)?
+            };
+            if expanded_value.is_null() {
+                continue;
+            }
+            if definition.has_container("@list") && expanded_value.get("@list").is_none() {
+                expanded_value = json!({ "@list": as_array(expanded_value) });
+            }
+            if definition.has_container("@graph")
+                && !definition.has_container("@id")
+                && !definition.has_container("@index")
+            {
+                expanded_value = Value::Array(
+                    as_array(expanded_value)
+                        .into_iter()
+                        .map(|item| json!({ "@graph": as_array(item) }))
+                        .collect(),
+                );
+            }
+            if definition.reverse {
+                let mut reverse_map = match result.remove("@reverse") {
+                    Some(Value::Object(reverse_map)) => reverse_map,
+                    _ => Map::new(),
+                };
+                for item in as_array(expanded_value).into_iter() {
+                    if item.get("@value").is_some() || item.get("@list").is_some() {
+                        return Err(error("invalid reverse property value", key));
+                    }
+                    add_value(&mut reverse_map, &expanded_property, item, true);
+                }
+                result.insert("@reverse".to_string(), Value::Object(reverse_map));
+            } else {
+                add_value(result, &expanded_property, expanded_value, true);
+            }
+        }
+
+        nests.sort();
+        for nest in nests.iter() {
+            for nested in as_array(element[nest].clone()).iter() {
+                let nested = match nested {
+                    Value::Object(nested)
+                        if !nested.keys().any(|key| {
+                            active.expand_iri(key, false, true).as_deref() == Some("@value")
+                        }) =>
+                    {
+                        nested
+                    }
+                    _ => return Err(error("invalid @nest value", nest)),
+                };
+                self.expand_object(
+                    nested,
+                    active,
+                    type_scoped_context,
+                    active_property,
+                    input_type,
+                    base_url,
+                    &format!("{}/{}", path, escape_pointer(nest)),
+                    result,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expand_language_map(
+        &self,
+        active: &Context,
+        definition: &TermDefinition,
+        value: &Value,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let direction = match &definition.direction {
+            Some(direction) => direction.clone(),
+            None => active.direction.clone(),
+        };
+        let mut result = Vec::new();
+        for (language, values) in value.as_object().cloned().unwrap_or_default().into_iter() {
+            for item in as_array(values).into_iter() {
+                let item = match item {
+                    Value::Null => continue,
+                    Value::String(item) => item,
+                    _ => return Err(error("invalid language map value", &item.to_string())),
+                };
+                let mut expanded = Map::new();
+                expanded.insert("@value".to_string(), Value::from(item));
+                if active.expand_iri(&language, false, true).as_deref() != Some("@none") {
+                    expanded.insert(
+                        "@language".to_string(),
+                        Value::from(language.to_lowercase()),
+                    );
+                }
+                if let Some(direction) = &direction {
+                    expanded.insert("@direction".to_string(), Value::from(direction.clone()));
+                }
+                result.push(Value::Object(expanded));
+            }
+        }
+        Ok(Value::Array(result))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn expand_index_map(
+        &self,
+        active: &Context,
+        type_scoped_context: &Context,
+        key: &str,
+        definition: &TermDefinition,
+        value: &Value,
+        base_url: Option<&str>,
+        path: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let index_key = definition
+            .index
+            .clone()
+            .unwrap_or_else(|| "@index".to_string());
+        let mut result = Vec::new();
+        for (index, index_value) in value.as_object().cloned().unwrap_or_default().into_iter() {
+            let mut map_context = active.clone();
+            if definition.has_container("@type") {
+                if let Some(previous) = &active.previous {
+                    map_context = *previous.clone();
+                }
+                if let Some(context) = type_scoped_context
+                    .terms
+                    .get(&index)
+                    .and_then(|d| d.context.clone())
+                {
+                    map_context = self.process_context(
+                        &map_context,
+                        &context,
+                        base_url,
+                        &mut Vec::new(),
+                        false,
+                        true,
+                    )?;
+                }
+            }
+            let expanded_index = active.expand_iri(&index, false, true);
+            let index_path = format!("{}/{}", path, escape_pointer(&index));
+            let items = self.expand_element(
+                &map_context,
+                Some(key),
+                &Value::Array(as_array(index_value)),
+                base_url,
+                &index_path,
+                true,
+            )?;
+            for mut item in as_array(items).into_iter() {
+                if definition.has_container("@graph") && !is_graph_object(&item) {
+                    item = json!({ "@graph": as_array(item) });
+                }
+                let is_none = expanded_index.as_deref() == Some("@none");
+                let object = match item.as_object_mut() {
+                    Some(object) => object,
+                    None => continue,
+                };
+                if definition.has_container("@index") && index_key != "@index" && !is_none {
+                    let re_expanded =
+                        expand_value(active, Some(&index_key), &Value::from(index.clone()));
+                    let expanded_index_key = active
+                        .expand_iri(&index_key, false, true)
+                        .ok_or_else(|| error("invalid term definition", &index_key))?;
+                    if object.contains_key("@value") {
+                        return Err(error("invalid value object", &index));
+                    }
+                    let mut values = vec![re_expanded];
+                    values.extend(as_array(
+                        object.remove(&expanded_index_key).unwrap_or(json!([])),
+                    ));
+                    object.insert(expanded_index_key, Value::Array(values));
+                } else if definition.has_container("@index")
+                    && !object.contains_key("@index")
+                    && !is_none
+                {
+                    object.insert("@index".to_string(), Value::from(index.clone()));
+                } else if definition.has_container("@id") && !object.contains_key("@id") && !is_none
+                {
+                    let id = active.expand_iri(&index, true, false).unwrap_or_default();
+                    object.insert("@id".to_string(), Value::from(id));
+                } else if definition.has_container("@type") && !is_none {
+                    let mut types = vec![Value::from(expanded_index.clone().unwrap_or_default())];
+                    types.extend(as_array(object.remove("@type").unwrap_or(json!([]))));
+                    object.insert("@type".to_string(), Value::Array(types));
+                }
+                result.push(item);
+            }
+        }
+        Ok(Value::Array(result))
+    }
+
+    /// Compacts given expanded element, see the
+    /// [compaction algorithm](https://www.w3.org/TR/json-ld11-api/#compaction-algorithm). Nested
+    /// properties and property-valued indexes are not supported.
+    fn compact_element(
+        &self,
+        active: &Context,
+        active_property: Option<&str>,
+        element: &Value,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let element = match element {
+            Value::Array(items) => {
+                let mut result = Vec::new();
+                for item in items.iter() {
+                    let compacted = self.compact_element(active, active_property, item)?;
+                    if !compacted.is_null() {
+                        result.push(compacted);
+                    }
+                }
+                let container = active_property
+                    .map(|property| active.container(property))
+                    .unwrap_or_default();
+                if result.len() != 1
+                    || active_property == Some("@graph")
+                    || active_property == Some("@set")
+                    || container.iter().any(|c| c == "@list" || c == "@set")
+                {
+                    return Ok(Value::Array(result));
+                }
+                return Ok(result.remove(0));
+            }
+            Value::Object(element) => element,
+            scalar => return Ok(scalar.clone()),
+        };
+
+        let definition = active_property
+            .and_then(|property| active.terms.get(property))
+            .cloned();
+        let mut active = active.clone();
+        if let Some(previous) = &active.previous {
+            let is_node_reference = element.len() == 1 && element.contains_key("@id");
+            if !element.contains_key("@value") && !is_node_reference {
+                active = *previous.clone();
+            }
+        }
+        if let Some(definition) = &definition {
+            if let Some(context) = &definition.context {
+                active = self.process_context(
+                    &active,
+                    context,
+                    definition.base_url.as_deref(),
+                    &mut Vec::new(),
+                    true,
+                    true,
+                )?;
+            }
+        }
+
+        if element.contains_key("@value") || element.contains_key("@id") {
+            let compacted = compact_value(&active, active_property, element)?;
+            let is_json = definition
+                .as_ref()
+                .and_then(|definition| definition.type_mapping.as_deref())
+                == Some("@json");
+            if !compacted.is_object() || is_json {
+                return Ok(compacted);
+            }
+        }
+        if let Some(list) = element.get("@list") {
+            if active_property
+                .map(|property| active.container(property).iter().any(|c| c == "@list"))
+                .unwrap_or(false)
+            {
+                return self.compact_element(&active, active_property, list);
+            }
+        }
+
+        let inside_reverse = active_property == Some("@reverse");
+        let type_scoped = active.clone();
+        if let Some(types) = element.get("@type") {
+            let mut compacted_types = Vec::new();
+            for value in as_array(types.clone()).iter() {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| error("invalid type value", &value.to_string()))?;
+                compacted_types.push(compact_iri(&active, value, None, true, false)?);
+            }
+            compacted_types.sort();
+            for term in compacted_types.iter() {
+                if let Some(definition) = type_scoped.terms.get(term) {
+                    if let Some(context) = &definition.context {
+                        active = self.process_context(
+                            &active,
+                            context,
+                            definition.base_url.as_deref(),
+                            &mut Vec::new(),
+                            false,
+                            false,
+                        )?;
+                    }
+                }
+            }
+        }
+
+        let mut result = Map::new();
+        let mut keys: Vec<&String> = element.keys().collect();
+        keys.sort();
+        for key in keys.into_iter() {
+            let value = &element[key];
+            match key.as_str() {
+                "@id" => {
+                    let id = value
+                        .as_str()
+                        .ok_or_else(|| error("invalid @id value", &value.to_string()))?;
+                    let alias = compact_iri(&active, "@id", None, true, false)?;
+                    let id = compact_iri(&active, id, None, false, false)?;
+                    result.insert(alias, Value::from(id));
+                    continue;
+                }
+                "@type" => {
+                    let mut types = Vec::new();
+                    for value in as_array(value.clone()).iter() {
+                        let value = value.as_str().unwrap_or_default();
+                        types.push(Value::from(compact_iri(
+                            &type_scoped,
+                            value,
+                            None,
+                            true,
+                            false,
+                        )?));
+                    }
+                    let alias = compact_iri(&active, "@type", None, true, false)?;
+                    let as_array =
+                        types.len() > 1 || active.container(&alias).iter().any(|c| c == "@set");
+                    add_value(&mut result, &alias, Value::Array(types), as_array);
+                    continue;
+                }
+                "@reverse" => {
+                    let compacted = self.compact_element(&active, Some("@reverse"), value)?;
+                    let mut remaining = Map::new();
+                    for (property, value) in compacted.as_object().cloned().unwrap_or_default() {
+                        if active
+                            .terms
+                            .get(&property)
+                            .map(|definition| definition.reverse)
+                            .unwrap_or(false)
+                        {
+                            let as_array = active.container(&property).iter().any(|c| c == "@set");
+                            add_value(&mut result, &property, value, as_array);
+                        } else {
+                            remaining.insert(property, value);
+                        }
+                    }
+                    if !remaining.is_empty() {
+                        let alias = compact_iri(&active, "@reverse", None, true, false)?;
+                        result.insert(alias, Value::Object(remaining));
+                    }
+                    continue;
+                }
+                "@preserve" => continue,
+                "@index"
+                    if active_property
+                        .map(|property| active.container(property).iter().any(|c| c == "@index"))
+                        .unwrap_or(false) =>
+                {
+                    continue
+                }
+                "@direction" | "@index" | "@language" | "@value" => {
+                    let alias = compact_iri(&active, key, None, true, false)?;
+                    result.insert(alias, value.clone());
+                    continue;
+                }
+                _ => (),
+            }
+
+            let items = as_array(value.clone());
+            if items.is_empty() {
+                let property = compact_iri(&active, key, Some(value), true, inside_reverse)?;
+                check_nest(&active, &property)?;
+                add_value(&mut result, &property, Value::Array(Vec::new()), true);
+            }
+            for item in items.iter() {
+                self.compact_item(&active, key, item, inside_reverse, &mut result)?;
+            }
+        }
+        Ok(Value::Object(result))
+    }
+
+    /// Compacts given expanded value of given property and adds it to given result.
+    fn compact_item(
+        &self,
+        active: &Context,
+        expanded_property: &str,
+        item: &Value,
+        inside_reverse: bool,
+        result: &mut Map<String, Value>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let property = compact_iri(active, expanded_property, Some(item), true, inside_reverse)?;
+        check_nest(active, &property)?;
+        let container = active.container(&property);
+        let has = |name: &str| container.iter().any(|c| c == name);
+        let as_array = has("@set") || expanded_property == "@graph" || expanded_property == "@list";
+        let alias = |keyword: &str| compact_iri(active, keyword, None, true, false);
+
+        let is_list = item.get("@list").is_some();
+        let is_graph = is_graph_object(item);
+        let inner = match (is_list, is_graph) {
+            (true, _) => &item["@list"],
+            (_, true) => &item["@graph"],
+            _ => item,
+        };
+        let mut compacted = self.compact_element(active, Some(&property), inner)?;
+
+        if is_list {
+            let compacted = Value::Array(self::as_array(compacted));
+            if has("@list") {
+                result.insert(property, compacted);
+            } else {
+                let mut list = Map::new();
+                list.insert(alias("@list")?, compacted);
+                if let Some(index) = item.get("@index") {
+                    list.insert(alias("@index")?, index.clone());
+                }
+                add_value(result, &property, Value::Object(list), as_array);
+            }
+            return Ok(());
+        }
+
+        if is_graph {
+            let map_key = if has("@graph") && has("@id") {
+                Some(match item.get("@id").and_then(Value::as_str) {
+                    Some(id) => compact_iri(active, id, None, false, false)?,
+                    None => alias("@none")?,
+                })
+            } else if has("@graph") && has("@index") && item.get("@id").is_none() {
+                Some(match item.get("@index").and_then(Value::as_str) {
+                    Some(index) => index.to_string(),
+                    None => alias("@none")?,
+                })
+            } else {
+                None
+            };
+            if let Some(map_key) = map_key {
+                if let Value::Object(map) = result
+                    .entry(property)
+                    .or_insert_with(|| Value::Object(Map::new()))
+                {
+                    add_value(map, &map_key, compacted, as_array);
+                }
+            } else if has("@graph") && item.get("@id").is_none() {
+                if compacted.as_array().map(|c| c.len() > 1).unwrap_or(false) {
+                    let mut included = Map::new();
+                    included.insert(alias("@included")?, compacted);
+                    compacted = Value::Object(included);
+                }
+                add_value(result, &property, compacted, as_array);
+            } else {
+                let mut graph = Map::new();
+                graph.insert(alias("@graph")?, Value::Array(self::as_array(compacted)));
+                if let Some(id) = item.get("@id").and_then(Value::as_str) {
+                    graph.insert(
+                        alias("@id")?,
+                        Value::from(compact_iri(active, id, None, false, false)?),
+                    );
+                }
+                if let Some(index) = item.get("@index") {
+                    graph.insert(alias("@index")?, index.clone());
+                }
+                add_value(result, &property, Value::Object(graph), as_array);
+            }
+            return Ok(());
+        }
+
+        if has("@language") || has("@index") || has("@id") || has("@type") {
+            let map_key = if has("@language") {
+                if let Some(value) = item.get("@value") {
+                    compacted = value.clone();
+                }
+                item.get("@language")
+                    .and_then(Value::as_str)
+                    .map(|language| language.to_string())
+            } else if has("@index") {
+                if active
+                    .terms
+                    .get(&property)
+                    .and_then(|definition| definition.index.as_ref())
+                    .is_some()
+                {
+                    return Err(error(
+                        "compaction of property-valued indexes is not supported",
+                        &property,
+                    ));
+                }
+                item.get("@index")
+                    .and_then(Value::as_str)
+                    .map(|index| index.to_string())
+            } else if has("@id") {
+                let id_alias = alias("@id")?;
+                compacted
+                    .as_object_mut()
+                    .and_then(|object| object.remove(&id_alias))
+                    .and_then(|id| id.as_str().map(|id| id.to_string()))
+            } else {
+                let type_alias = alias("@type")?;
+                let mut types = compacted
+                    .as_object_mut()
+                    .and_then(|object| object.remove(&type_alias))
+                    .map(self::as_array)
+                    .unwrap_or_default();
+                let map_key = match types.is_empty() {
+                    true => None,
+                    false => types.remove(0).as_str().map(|t| t.to_string()),
+                };
+                if let Value::Object(object) = &mut compacted {
+                    match types.len() {
+                        0 => (),
+                        1 => {
+                            object.insert(type_alias, types.remove(0));
+                        }
+                        _ => {
+                            object.insert(type_alias, Value::Array(types));
+                        }
+                    }
+                    if object.len() == 1 && object.contains_key(&alias("@id")?) {
+                        compacted = self.compact_element(
+                            active,
+                            Some(&property),
+                            &json!({ "@id": item["@id"] }),
+                        )?;
+                    }
+                }
+                map_key
+            };
+            let map_key = match map_key {
+                Some(map_key) => map_key,
+                None => alias("@none")?,
+            };
+            if let Value::Object(map) = result
+                .entry(property)
+                .or_insert_with(|| Value::Object(Map::new()))
+            {
+                add_value(map, &map_key, compacted, as_array);
+            }
+            return Ok(());
+        }
+
+        add_value(result, &property, compacted, as_array);
+        Ok(())
+    }
+}
+
+fn check_nest(active: &Context, property: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match active.terms.get(property).and_then(|d| d.nest.as_ref()) {
+        Some(_) => Err(error(
+            "compaction of nested properties is not supported",
+            property,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Compacts given IRI to a term, a compact IRI or an IRI relative to the vocabulary mapping, see
+/// [IRI compaction](https://www.w3.org/TR/json-ld11-api/#iri-compaction).
+///
+/// # Arguments
+///
+/// * `active` - active context
+/// * `iri` - IRI or keyword to compact
+/// * `value` - expanded value of the property, if `iri` is a property
+/// * `vocab` - `true` to compact relative to the vocabulary
+/// * `reverse` - `true` if `iri` is a reverse property
+fn compact_iri(
+    active: &Context,
+    iri: &str,
+    value: Option<&Value>,
+    vocab: bool,
+    reverse: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if vocab && active.inverse().contains_key(iri) {
+        let object = value.and_then(Value::as_object);
+        let has = |key: &str| object.map(|o| o.contains_key(key)).unwrap_or(false);
+        let get = |key: &str| {
+            object
+                .and_then(|o| o.get(key))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+        };
+        let is_graph = value.map(is_graph_object).unwrap_or(false);
+        let mut containers: Vec<&str> = Vec::new();
+        let mut type_language = "@language";
+        let mut type_language_value = "@null".to_string();
+
+        if has("@index") && !is_graph {
+            containers.extend(&["@index", "@index@set"]);
+        }
+        if reverse {
+            type_language = "@type";
+            type_language_value = "@reverse".to_string();
+            containers.push("@set");
+        } else if has("@list") {
+            if !has("@index") {
+                containers.push("@list");
+            }
+            let list = object
+                .and_then(|o| o["@list"].as_array())
+                .cloned()
+                .unwrap_or_default();
+            let mut common_language = match list.is_empty() {
+                true => Some(active.default_language()),
+                false => None,
+            };
+            let mut common_type: Option<String> = None;
+            for item in list.iter() {
+                let mut item_language = "@none".to_string();
+                let mut item_type = "@none".to_string();
+                if item.get("@value").is_some() {
+                    let language = item.get("@language").and_then(Value::as_str);
+                    if let Some(direction) = item.get("@direction").and_then(Value::as_str) {
+                        item_language = format!("{}_{}", language.unwrap_or_default(), direction);
+                    } else if let Some(language) = language {
+                        item_language = language.to_lowercase();
+                    } else if let Some(item_type_value) = item.get("@type").and_then(Value::as_str)
+                    {
+                        item_type = item_type_value.to_string();
+                    } else {
+                        item_language = "@null".to_string();
+                    }
+                } else {
+                    item_type = "@id".to_string();
+                }
+                match &common_language {
+                    None => common_language = Some(item_language),
+                    Some(common) if *common != item_language && item.get("@value").is_some() => {
+                        common_language = Some("@none".to_string())
+                    }
+                    _ => (),
+                }
+                match &common_type {
+                    None => common_type = Some(item_type),
+                    Some(common) if *common != item_type => common_type = Some("@none".to_string()),
+                    _ => (),
+                }
+                if common_language.as_deref() == Some("@none")
+                    && common_type.as_deref() == Some("@none")
+                {
+                    break;
+                }
+            }
+            let common_language = common_language.unwrap_or_else(|| "@none".to_string());
+            let common_type = common_type.unwrap_or_else(|| "@none".to_string());
+            if common_type != "@none" {
+                type_language = "@type";
+                type_language_value = common_type;
+            } else {
+                type_language_value = common_language;
+            }
+        } else if is_graph {
+            if has("@index") {
+                containers.extend(&["@graph@index", "@graph@index@set"]);
+            }
+            if has("@id") {
+                containers.extend(&["@graph@id", "@graph@id@set"]);
+            }
+            containers.extend(&["@graph", "@graph@set", "@set"]);
+            if !has("@index") {
+                containers.extend(&["@graph@index", "@graph@index@set"]);
+            }
+            if !has("@id") {
+                containers.extend(&["@graph@id", "@graph@id@set"]);
+            }
+            containers.extend(&["@index", "@index@set"]);
+            type_language = "@type";
+            type_language_value = "@id".to_string();
+        } else {
+            if has("@value") {
+                if has("@direction") && !has("@index") {
+                    type_language_value =
+                        format!("{}_{}", get("@language").to_lowercase(), get("@direction"));
+                    containers.extend(&["@language", "@language@set"]);
+                } else if has("@language") && !has("@index") {
+                    type_language_value = get("@language").to_lowercase();
+                    containers.extend(&["@language", "@language@set"]);
+                } else if has("@type") {
+                    type_language = "@type";
+                    type_language_value = get("@type").to_string();
+                }
+            } else {
+                type_language = "@type";
+                type_language_value = "@id".to_string();
+                containers.extend(&["@id", "@id@set", "@type", "@set@type"]);
+            }
+            containers.push("@set");
+        }
+        containers.push("@none");
+        if !has("@index") {
+            containers.extend(&["@index", "@index@set"]);
+        }
+        if object.map(|o| o.len() == 1).unwrap_or(false) && has("@value") {
+            containers.extend(&["@language", "@language@set"]);
+        }
+
+        let mut preferred_values = Vec::new();
+        if type_language_value == "@reverse" {
+            preferred_values.push("@reverse".to_string());
+        }
+        if (type_language_value == "@id" || type_language_value == "@reverse") && has("@id") {
+            let id = get("@id");
+            let compacted = compact_iri(active, id, None, true, false)?;
+            let is_term = active
+                .terms
+                .get(&compacted)
+                .and_then(|definition| definition.iri.as_deref())
+                == Some(id);
+            let order = match is_term {
+                true => ["@vocab", "@id", "@none"],
+                false => ["@id", "@vocab", "@none"],
+            };
+            preferred_values.extend(order.iter().map(|value| value.to_string()));
+        } else {
+            preferred_values.push(type_language_value.clone());
+            preferred_values.push("@none".to_string());
+            let is_empty_list = object
+                .and_then(|o| o.get("@list"))
+                .and_then(Value::as_array)
+                .map(|list| list.is_empty())
+                .unwrap_or(false);
+            if is_empty_list {
+                type_language = "@any";
+            }
+        }
+        preferred_values.push("@any".to_string());
+        if let Some(direction) = preferred_values
+            .iter()
+            .find_map(|value| value.find('_').map(|index| value[index..].to_string()))
+        {
+            preferred_values.push(direction);
+        }
+        if let Some(term) = active.select_term(iri, &containers, type_language, &preferred_values) {
+            return Ok(term);
+        }
+    }
+
+    if vocab {
+        if let Some(suffix) = active
+            .vocab
+            .as_deref()
+            .and_then(|vocab| iri.strip_prefix(vocab))
+        {
+            if !suffix.is_empty() && !active.terms.contains_key(suffix) {
+                return Ok(suffix.to_string());
+            }
+        }
+    }
+
+    let mut compact: Option<String> = None;
+    for (term, definition) in active.terms.iter() {
+        let prefix = match &definition.iri {
+            Some(prefix)
+                if definition.prefix && prefix != iri && iri.starts_with(prefix.as_str()) =>
+            {
+                prefix
+            }
+            _ => continue,
+        };
+        let candidate = format!("{}:{}", term, &iri[prefix.len()..]);
+        let shorter = match &compact {
+            Some(compact) => {
+                candidate.len() < compact.len()
+                    || (candidate.len() == compact.len() && candidate < *compact)
+            }
+            None => true,
+        };
+        let usable = match active.terms.get(&candidate) {
+            Some(definition) => definition.iri.as_deref() == Some(iri) && value.is_none(),
+            None => true,
+        };
+        if shorter && usable {
+            compact = Some(candidate);
+        }
+    }
+    if let Some(compact) = compact {
+        return Ok(compact);
+    }
+
+    if let Some(index) = iri.find(':') {
+        let is_prefix = active
+            .terms
+            .get(&iri[..index])
+            .map(|definition| definition.prefix)
+            .unwrap_or(false);
+        if is_prefix && !iri[index + 1..].starts_with("//") {
+            return Err(error("IRI confused with prefix", iri));
+        }
+    }
+    Ok(iri.to_string())
+}
+
+/// Compacts given value or node reference to a scalar if the term definition of given property
+/// allows it, see [value compaction](https://www.w3.org/TR/json-ld11-api/#value-compaction).
+fn compact_value(
+    active: &Context,
+    active_property: Option<&str>,
+    value: &Map<String, Value>,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let definition = active_property
+        .and_then(|property| active.terms.get(property))
+        .cloned()
+        .unwrap_or_default();
+    let type_mapping = definition.type_mapping.as_deref();
+    let index_container = definition.has_container("@index");
+    let only = |allowed: &[&str]| {
+        value
+            .keys()
+            .all(|key| allowed.contains(&key.as_str()) || (key == "@index" && index_container))
+    };
+
+    if let Some(id) = value.get("@id").and_then(Value::as_str) {
+        if only(&["@id"]) {
+            match type_mapping {
+                Some("@id") => {
+                    return Ok(Value::from(compact_iri(active, id, None, false, false)?))
+                }
+                Some("@vocab") => {
+                    return Ok(Value::from(compact_iri(active, id, None, true, false)?))
+                }
+                _ => (),
+            }
+        }
+    } else if let Some(value_type) = value.get("@type").and_then(Value::as_str) {
+        if Some(value_type) == type_mapping && only(&["@type", "@value"]) {
+            return Ok(value["@value"].clone());
+        }
+    } else if type_mapping != Some("@none") {
+        match value.get("@value") {
+            Some(Value::String(_)) => {
+                let language = definition
+                    .language
+                    .clone()
+                    .unwrap_or_else(|| active.language.clone());
+                let direction = definition
+                    .direction
+                    .clone()
+                    .unwrap_or_else(|| active.direction.clone());
+                let value_language = value
+                    .get("@language")
+                    .and_then(Value::as_str)
+                    .map(|language| language.to_lowercase());
+                let value_direction = value
+                    .get("@direction")
+                    .and_then(Value::as_str)
+                    .map(|direction| direction.to_string());
+                if value_language == language
+                    && value_direction == direction
+                    && only(&["@direction", "@language", "@value"])
+                {
+                    return Ok(value["@value"].clone());
+                }
+            }
+            Some(scalar) if only(&["@value"]) => return Ok(scalar.clone()),
+            _ => (),
+        }
+    }
+
+    let mut result = Map::new();
+    for (key, entry) in value.iter() {
+        if key == "@index" && index_container {
+            continue;
+        }
+        let entry = match (key.as_str(), entry) {
+            ("@id", Value::String(id)) => Value::from(compact_iri(active, id, None, false, false)?),
+            ("@type", Value::String(value_type)) if value_type != "@json" => {
+                Value::from(compact_iri(active, value_type, None, true, false)?)
+            }
+            _ => entry.clone(),
+        };
+        result.insert(compact_iri(active, key, None, true, false)?, entry);
+    }
+    Ok(Value::Object(result))
+}
+
+fn expand_value(active: &Context, active_property: Option<&str>, value: &Value) -> Value {
+    let definition = active_property
+        .and_then(|property| active.terms.get(property))
+        .cloned()
+        .unwrap_or_default();
+    match (definition.type_mapping.as_deref(), value) {
+        (Some("@id"), Value::String(id)) => {
+            return json!({ "@id": active.expand_iri(id, true, false) })
+        }
+        (Some("@vocab"), Value::String(id)) => {
+            return json!({ "@id": active.expand_iri(id, true, true) })
+        }
+        _ => (),
+    }
+    let mut result = Map::new();
+    result.insert("@value".to_string(), value.clone());
+    match definition.type_mapping.as_deref() {
+        Some("@id") | Some("@vocab") | Some("@none") | None => {
+            if value.is_string() {
+                let language = match definition.language {
+                    Some(language) => language,
+                    None => active.language.clone(),
+                };
+                let direction = match definition.direction {
+                    Some(direction) => direction,
+                    None => active.direction.clone(),
+                };
+                if let Some(language) = language {
+                    result.insert("@language".to_string(), Value::from(language));
+                }
+                if let Some(direction) = direction {
+                    result.insert("@direction".to_string(), Value::from(direction));
+                }
+            }
+        }
+        Some(type_mapping) => {
+            result.insert("@type".to_string(), Value::from(type_mapping));
+        }
+    }
+    Value::Object(result)
+}
+
+/// Converts expanded JSON-LD to quads.
+struct RdfWriter {
+    quads: Vec<Quad>,
+    blank_nodes: HashMap<String, String>,
+    counter: usize,
+}
+
+impl RdfWriter {
+    fn new() -> Self {
+        RdfWriter {
+            quads: Vec::new(),
+            blank_nodes: HashMap::new(),
+            counter: 0,
+        }
+    }
+
+    fn write(mut self, expanded: &[Value]) -> Vec<Quad> {
+        for node in expanded.iter() {
+            self.write_node(node, &None);
+        }
+        let mut seen = HashSet::new();
+        self.quads
+            .into_iter()
+            .filter(|quad| seen.insert(quad.clone()))
+            .collect()
+    }
+
+    fn new_blank_node(&mut self) -> Term {
+        self.counter += 1;
+        Term::Blank(format!("b{}", self.counter - 1))
+    }
+
+    /// Returns the term of given node identifier, `None` for relative IRIs.
+    fn get_node_term(&mut self, id: Option<&str>) -> Option<Term> {
+        match id {
+            None => Some(self.new_blank_node()),
+            Some(id) if id.starts_with("_:") => {
+                if !self.blank_nodes.contains_key(id) {
+                    let label = match self.new_blank_node() {
+                        Term::Blank(label) => label,
+                        _ => unreachable!(),
+                    };
+                    self.blank_nodes.insert(id.to_string(), label);
+                }
+                Some(Term::Blank(self.blank_nodes[id].clone()))
+            }
+            Some(id) if is_absolute_iri(id) => Some(Term::Iri(id.to_string())),
+            Some(_) => None,
+        }
+    }
+
+    fn push(&mut self, subject: &Term, predicate: &str, object: Term, graph: &Option<Term>) {
+        self.quads.push(Quad {
+            subject: subject.clone(),
+            predicate: Term::Iri(predicate.to_string()),
+            object,
+            graph: graph.clone(),
+        });
+    }
+
+    /// Writes the quads of given node object and returns its subject.
+    fn write_node(&mut self, node: &Value, graph: &Option<Term>) -> Option<Term> {
+        let node = node.as_object()?;
+        let subject = self.get_node_term(node.get("@id").and_then(|id| id.as_str()))?;
+
+        if let Some(Value::Array(nodes)) = node.get("@graph") {
+            let named_graph = Some(subject.clone());
+            for nested in nodes.iter() {
+                self.write_node(nested, &named_graph);
+            }
+        }
+        if let Some(Value::Array(types)) = node.get("@type") {
+            for node_type in types.iter() {
+                if let Some(node_type) = self.get_node_term(node_type.as_str()) {
+                    self.push(&subject, RDF_TYPE, node_type, graph);
+                }
+            }
+        }
+        for (property, values) in node.iter() {
+            if is_keyword(property) || !is_absolute_iri(property) || property.starts_with("_:") {
+                continue;
+            }
+            for item in as_array(values.clone()).iter() {
+                if let Some(object) = self.write_object(item, graph) {
+                    self.push(&subject, property, object, graph);
+                }
+            }
+        }
+        if let Some(Value::Object(reverse)) = node.get("@reverse") {
+            for (property, items) in reverse.iter() {
+                if !is_absolute_iri(property) {
+                    continue;
+                }
+                for item in as_array(items.clone()).iter() {
+                    if let Some(reverse_subject) = self.write_node(item, graph) {
+                        self.push(&reverse_subject, property, subject.clone(), graph);
+                    }
+                }
+            }
+        }
+        if let Some(Value::Array(included)) = node.get("@included") {
+            for nested in included.iter() {
+                self.write_node(nested, graph);
+            }
+        }
+        Some(subject)
+    }
+
+    fn write_object(&mut self, item: &Value, graph: &Option<Term>) -> Option<Term> {
+        if item.get("@value").is_some() {
+            return get_literal(item);
+        }
+        if let Some(list) = item.get("@list") {
+            return self.write_list(&as_array(list.clone()), graph);
+        }
+        self.write_node(item, graph)
+    }
+
+    fn write_list(&mut self, items: &[Value], graph: &Option<Term>) -> Option<Term> {
+        if items.is_empty() {
+            return Some(Term::Iri(RDF_NIL.to_string()));
+        }
+        let nodes: Vec<Term> = items.iter().map(|_| self.new_blank_node()).collect();
+        for (index, item) in items.iter().enumerate() {
+            if let Some(object) = self.write_object(item, graph) {
+                self.push(&nodes[index], RDF_FIRST, object, graph);
+            }
+            let rest = nodes
+                .get(index + 1)
+                .cloned()
+                .unwrap_or_else(|| Term::Iri(RDF_NIL.to_string()));
+            self.push(&nodes[index], RDF_REST, rest, graph);
+        }
+        nodes.into_iter().next()
+    }
+}
+
+fn get_literal(item: &Value) -> Option<Term> {
+    let value = &item["@value"];
+    let datatype = item.get("@type").and_then(|t| t.as_str());
+    if datatype == Some("@json") {
+        return Some(Term::Literal {
+            value: jcs::canonicalize(value),
+            datatype: RDF_JSON.to_string(),
+            language: None,
+        });
+    }
+    if let Some(datatype) = datatype {
+        if !is_absolute_iri(datatype) {
+            return None;
+        }
+    }
+    let (value, default_datatype) = match value {
+        Value::Bool(value) => (value.to_string(), XSD_BOOLEAN),
+        Value::Number(number) => {
+            let float = number.as_f64().unwrap_or(0.0);
+            let is_integer =
+                number.is_i64() || number.is_u64() || (float.fract() == 0.0 && float.abs() < 1e21);
+            if is_integer && datatype != Some(XSD_DOUBLE) {
+                let integer = if number.is_i64() || number.is_u64() {
+                    number.to_string()
+                } else {
+                    format!("{:.0}", float)
+                };
+                (integer, XSD_INTEGER)
+            } else {
+                (format_double(float), XSD_DOUBLE)
+            }
+        }
+        Value::String(value) => {
+            if let Some(language) = item.get("@language").and_then(|l| l.as_str()) {
+                return Some(Term::Literal {
+                    value: value.clone(),
+                    datatype: RDF_LANG_STRING.to_string(),
+                    language: Some(language.to_string()),
+                });
+            }
+            (value.clone(), XSD_STRING)
+        }
+        _ => return None,
+    };
+    Some(Term::Literal {
+        value,
+        datatype: datatype.unwrap_or(default_datatype).to_string(),
+        language: None,
+    })
+}
+
+/// Formats a double in the canonical lexical form of `xsd:double`, e.g. "1.1E0".
+fn format_double(value: f64) -> String {
+    let formatted = format!("{:.15e}", value);
+    let (mantissa, exponent) = formatted.split_at(formatted.find('e').unwrap_or(0));
+    let mut mantissa = mantissa.trim_end_matches('0').to_string();
+    if mantissa.ends_with('.') {
+        mantissa.push('0');
+    }
+    format!("{}E{}", mantissa, &exponent[1..])
+}
@@ -0,0 +1,106 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Conversion of public keys from and to JSON Web Keys
+//! ([RFC 7517](https://tools.ietf.org/html/rfc7517)).
+
+use crate::multikey::KeyType;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use serde_json::{json, Value};
+
+/// Converts given JWK to a public key, elliptic curve keys are returned compressed. Fails for
+/// JWKs containing private key material.
+pub(crate) fn public_key_from_jwk(
+    jwk: &Value,
+) -> Result<(KeyType, Vec<u8>), Box<dyn std::error::Error>> {
+    if jwk.get("d").is_some() {
+        return Err(Box::from("JWK must not contain private key material"));
+    }
+    let get_coordinate = |name: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let encoded = jwk[name]
+            .as_str()
+            .ok_or_else(|| format!(r#"JWK has no "{}""#, name))?;
+        Ok(URL_SAFE_NO_PAD.decode(encoded)?)
+    };
+    let crv = jwk["crv"].as_str().unwrap_or_default();
+    match (jwk["kty"].as_str().unwrap_or_default(), crv) {
+        ("OKP", "Ed25519") => Ok((KeyType::Ed25519, get_coordinate("x")?)),
+        ("OKP", "X25519") => Ok((KeyType::X25519, get_coordinate("x")?)),
+        ("EC", "P-256") | ("EC", "secp256k1") => {
+            let mut point = vec![0x04];
+            point.extend_from_slice(&get_coordinate("x")?);
+            point.extend_from_slice(&get_coordinate("y")?);
+            if crv == "P-256" {
+                let public_key = p256::PublicKey::from_sec1_bytes(&point)?;
+                Ok((
+                    KeyType::P256,
+                    public_key.to_encoded_point(true).as_bytes().to_vec(),
+                ))
+            } else {
+                let public_key = k256::PublicKey::from_sec1_bytes(&point)?;
+                Ok((
+                    KeyType::Secp256k1,
+                    public_key.to_encoded_point(true).as_bytes().to_vec(),
+                ))
+            }
+        }
+        (kty, crv) => Err(Box::from(format!(
+            r#"unsupported JWK with kty "{}" and crv "{}""#,
+            kty, crv
+        ))),
+    }
+}
+
+/// Converts given public key to a JWK.
+pub(crate) fn public_key_to_jwk(
+    key_type: KeyType,
+    public_key: &[u8],
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let (crv, point) = match key_type {
+        KeyType::Bls12381G2 => return Err(Box::from("BLS12-381 G2 keys cannot be used as JWK")),
+        KeyType::Ed25519 => {
+            return Ok(
+                json!({ "kty": "OKP", "crv": "Ed25519", "x": URL_SAFE_NO_PAD.encode(public_key) }),
+            )
+        }
+        KeyType::X25519 => {
+            return Ok(
+                json!({ "kty": "OKP", "crv": "X25519", "x": URL_SAFE_NO_PAD.encode(public_key) }),
+            )
+        }
+        KeyType::P256 => (
+            "P-256",
+            p256::PublicKey::from_sec1_bytes(public_key)?
+                .to_encoded_point(false)
+                .as_bytes()
+                .to_vec(),
+        ),
+        KeyType::Secp256k1 => (
+            "secp256k1",
+            k256::PublicKey::from_sec1_bytes(public_key)?
+                .to_encoded_point(false)
+                .as_bytes()
+                .to_vec(),
+        ),
+    };
+    Ok(json!({
+        "kty": "EC",
+        "crv": crv,
+        "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+        "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+    }))
+}
@@ -0,0 +1,162 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Signing and verification of compact JWS/JWTs shared by the JOSE based plugins.
+
+use crate::{crypto, key_store::KeyReference, multikey::KeyType, verification_method, Vade};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+
+/// A signed structure decoded, but not verified yet.
+pub(crate) struct Envelope {
+    pub(crate) alg: String,
+    pub(crate) kid: String,
+    pub(crate) typ: String,
+    pub(crate) payload: Vec<u8>,
+    pub(crate) signing_input: Vec<u8>,
+    pub(crate) signature: Vec<u8>,
+}
+
+/// Returns JOSE and COSE algorithm for given key type.
+pub(crate) fn get_algorithm(
+    key_type: KeyType,
+) -> Result<(&'static str, i64), Box<dyn std::error::Error>> {
+    match key_type {
+        KeyType::Ed25519 => Ok(("EdDSA", -8)),
+        KeyType::P256 => Ok(("ES256", -7)),
+        KeyType::Secp256k1 => Ok(("ES256K", -47)),
+        KeyType::X25519 => Err(Box::from("X25519 keys cannot be used for signing")),
+        KeyType::Bls12381G2 => Err(Box::from("BLS12-381 G2 keys cannot be used with JOSE")),
+    }
+}
+
+/// Signs given claims with given key and returns the compact JWT. The header's `alg` is set
+/// according to the key.
+pub(crate) async fn sign(
+    key: &KeyReference,
+    header: &Map<String, Value>,
+    claims: &Map<String, Value>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let (alg, _) = get_algorithm(key.info().key_type)?;
+    let mut header = header.clone();
+    header.insert("alg".to_string(), Value::from(alg));
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?),
+    );
+    let signature = key.sign(signing_input.as_bytes()).await?;
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        URL_SAFE_NO_PAD.encode(signature)
+    ))
+}
+
+/// Decodes given compact JWT without verifying it.
+pub(crate) fn decode(jwt: &str) -> Result<Envelope, Box<dyn std::error::Error>> {
+    let parts: Vec<&str> = jwt.split('.').collect();
+    if parts.len() != 3 {
+        return Err(Box::from("JWT must consist of 3 parts"));
+    }
+    let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[0])?)?;
+    let get_header = |name: &str| header[name].as_str().unwrap_or_default().to_string();
+    Ok(Envelope {
+        alg: get_header("alg"),
+        kid: get_header("kid"),
+        typ: get_header("typ"),
+        payload: URL_SAFE_NO_PAD.decode(parts[1])?,
+        signing_input: format!("{}.{}", parts[0], parts[1]).into_bytes(),
+        signature: URL_SAFE_NO_PAD.decode(parts[2])?,
+    })
+}
+
+/// Resolves the public key referenced by given `kid`, that has to belong to given signer DID and
+/// be authorized for given verification relationship. Relative `kid`s are resolved against the
+/// signer.
+pub(crate) async fn resolve_signer_key(
+    resolver: Option<&mut Vade>,
+    kid: &str,
+    signer: &str,
+    relationship: &str,
+) -> Result<(KeyType, Vec<u8>), String> {
+    let kid = if kid.starts_with('#') {
+        format!("{}{}", signer, kid)
+    } else {
+        kid.to_string()
+    };
+    let method =
+        verification_method::resolve_authorized_method(resolver, &kid, Some(signer), relationship)
+            .await?;
+    verification_method::get_public_key(&method).map_err(|e| e.to_string())
+}
+
+/// Checks the signature of given envelope and that its algorithm matches the key.
+pub(crate) fn verify_signature(
+    envelope: &Envelope,
+    key_type: KeyType,
+    public_key: &[u8],
+) -> Result<(), String> {
+    let (alg, _) = get_algorithm(key_type).map_err(|e| e.to_string())?;
+    if envelope.alg != alg {
+        return Err(format!(
+            r#"algorithm "{}" does not match key type {:?}"#,
+            envelope.alg, key_type
+        ));
+    }
+    let valid = crypto::verify(
+        key_type,
+        public_key,
+        &envelope.signing_input,
+        &envelope.signature,
+    )
+    .map_err(|e| e.to_string())?;
+    if !valid {
+        return Err("invalid signature".to_string());
+    }
+    Ok(())
+}
+
+/// Parses an optional RFC 3339 date to a unix timestamp.
+pub(crate) fn get_timestamp(value: Option<&Value>) -> Result<Option<i64>, String> {
+    match value.and_then(|value| value.as_str()) {
+        Some(time) => DateTime::parse_from_rfc3339(time)
+            .map(|time| Some(time.timestamp()))
+            .map_err(|_| format!(r#"invalid date "{}""#, time)),
+        None => Ok(None),
+    }
+}
+
+/// Checks, that `nbf` and `exp` (or `validFrom` and `validUntil`) include the current time.
+pub(crate) fn check_validity(claims: &Map<String, Value>) -> Result<(), String> {
+    let now = Utc::now().timestamp();
+    let not_before = match claims.get("nbf").and_then(|nbf| nbf.as_i64()) {
+        Some(nbf) => Some(nbf),
+        None => get_timestamp(claims.get("validFrom"))?,
+    };
+    let expires = match claims.get("exp").and_then(|exp| exp.as_i64()) {
+        Some(exp) => Some(exp),
+        None => get_timestamp(claims.get("validUntil"))?,
+    };
+    if not_before.map(|nbf| nbf > now).unwrap_or(false) {
+        return Err("not valid yet".to_string());
+    }
+    if expires.map(|exp| exp <= now).unwrap_or(false) {
+        return Err("expired".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,204 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Rotation of the keys of a DID's verification methods on top of `did_update`: a new key is
+//! generated in the registered key store, replaces the current key in the DID document, the
+//! update is signed with the current key and checked by resolving the DID again, before the
+//! current key is retired.
+
+use crate::{
+    multikey::{self, KeyType},
+    plugins::parse_options,
+    verification_method::{self, RELATIONSHIPS},
+    KeyInfo, Vade,
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::rc::Rc;
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RotateKeyOptions {
+    /// algorithm of new key, defaults to the algorithm of the current key
+    key_type: Option<KeyType>,
+    /// `false` to keep the current key in key store after rotation
+    retire: Option<bool>,
+    /// plugin specific options passed to `did_update`
+    update_options: Option<Map<String, Value>>,
+}
+
+/// Rotates the key with given key store id, that is used by verification methods of given DID.
+///
+/// # Arguments
+///
+/// * `vade` - `Vade` instance to update and resolve DID with
+/// * `did` - DID to rotate key of
+/// * `key_id` - key store id of current key
+/// * `options` - JSON with optional `keyType`, `retire` and `updateOptions`
+pub(crate) async fn rotate_key(
+    vade: &mut Vade,
+    did: &str,
+    key_id: &str,
+    options: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let options: RotateKeyOptions = parse_options(options)?;
+    let key_store = vade
+        .key_store
+        .as_ref()
+        .map(Rc::clone)
+        .ok_or("rotating keys requires a registered key store")?;
+    let current = key_store.get_key(key_id).await?;
+
+    let document = verification_method::resolve_did_document(Some(vade), did).await?;
+    let rotated = find_methods_with_key(&document, did, &current)?;
+    if rotated.is_empty() {
+        return Err(Box::from(format!(
+            r#"key "{}" is not used by any verification method of "{}""#,
+            key_id, did
+        )));
+    }
+
+    let new = key_store
+        .generate_key(options.key_type.unwrap_or(current.key_type))
+        .await?;
+    let new_method_id = format!("{}#{}", did, new.public_key);
+    let new_method = json!({
+        "id": new_method_id,
+        "type": "Multikey",
+        "controller": did,
+        "publicKeyMultibase": new.public_key,
+    });
+    let updated = replace_methods(document, did, &rotated, &new_method);
+
+    let mut update_options = options.update_options.unwrap_or_default();
+    update_options.insert("keyId".to_string(), Value::from(current.id.clone()));
+    update_options.insert("newKeyId".to_string(), Value::from(new.id.clone()));
+    let results = vade
+        .did_update(
+            did,
+            &Value::Object(update_options).to_string(),
+            &updated.to_string(),
+        )
+        .await?;
+    if results.is_empty() {
+        return Err(Box::from(format!(r#"no plugin updated "{}""#, did)));
+    }
+
+    let resolved = verification_method::resolve_did_document(Some(vade), did).await?;
+    let (method, _) = verification_method::find_verification_method(&resolved, &new_method_id)
+        .map_err(|e| format!("rotated key has not been published; {}", e))?;
+    if verification_method::get_public_key(&method)? != decode(&new)? {
+        return Err(Box::from(
+            "rotated key has not been published; public key does not match",
+        ));
+    }
+    if !find_methods_with_key(&resolved, did, &current)?.is_empty() {
+        return Err(Box::from("current key is still used by DID document"));
+    }
+
+    let retired = options.retire != Some(false);
+    if retired {
+        key_store.delete_key(&current.id).await?;
+    }
+
+    Ok(serde_json::to_string(&json!({
+        "did": did,
+        "keyId": new.id,
+        "publicKey": new.public_key,
+        "verificationMethod": new_method_id,
+        "rotated": rotated,
+        "retiredKeyId": if retired { Some(&current.id) } else { None },
+    }))?)
+}
+
+fn decode(key: &KeyInfo) -> Result<(KeyType, Vec<u8>), Box<dyn std::error::Error>> {
+    multikey::decode_public_key(&key.public_key)
+}
+
+/// Returns the absolute ids of all verification methods of given document using given key.
+fn find_methods_with_key(
+    document: &Value,
+    did: &str,
+    key: &KeyInfo,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let key = decode(key)?;
+    let mut ids = Vec::new();
+    let embedded = RELATIONSHIPS
+        .iter()
+        .filter_map(|relationship| document.get(*relationship).and_then(Value::as_array))
+        .flatten();
+    let methods = document
+        .get("verificationMethod")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .chain(embedded)
+        .filter(|method| method.is_object());
+    for method in methods {
+        // methods with key formats not supported cannot use the key
+        if verification_method::get_public_key(method).ok().as_ref() != Some(&key) {
+            continue;
+        }
+        let id = absolute_id(
+            did,
+            method.get("id").and_then(Value::as_str).unwrap_or_default(),
+        );
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+fn absolute_id(did: &str, id: &str) -> String {
+    match id.starts_with('#') {
+        true => format!("{}{}", did, id),
+        false => id.to_string(),
+    }
+}
+
+/// Replaces the verification methods with given ids and all references to them with given method.
+fn replace_methods(mut document: Value, did: &str, ids: &[String], new_method: &Value) -> Value {
+    let is_rotated = |id: Option<&str>| id.is_some_and(|id| ids.contains(&absolute_id(did, id)));
+    let new_method_id = new_method["id"].clone();
+    for property in std::iter::once("verificationMethod").chain(RELATIONSHIPS.iter().copied()) {
+        let entries = match document.get_mut(property).and_then(Value::as_array_mut) {
+            Some(entries) => entries,
+            None => continue,
+        };
+        let mut replaced = false;
+        entries.retain_mut(|entry| {
+            let rotated = match entry {
+                Value::String(reference) => is_rotated(Some(reference)),
+                _ => is_rotated(entry.get("id").and_then(Value::as_str)),
+            };
+            if !rotated {
+                return true;
+            }
+            // a document referencing multiple rotated methods gets a single new one
+            if replaced {
+                return false;
+            }
+            replaced = true;
+            *entry = match entry {
+                Value::String(_) if property != "verificationMethod" => new_method_id.clone(),
+                _ => new_method.clone(),
+            };
+            true
+        });
+    }
+    document
+}
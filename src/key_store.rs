@@ -0,0 +1,609 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{
+    crypto,
+    multikey::{self, KeyType},
+};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// PBKDF2 iterations for new key store files, as recommended by OWASP for PBKDF2-HMAC-SHA256
+const DEFAULT_ITERATIONS: u32 = 600_000;
+/// fewest PBKDF2 iterations accepted, as required by NIST SP 800-63B
+const MIN_ITERATIONS: u32 = 10_000;
+/// most PBKDF2 iterations accepted, so crafted files cannot keep the CPU busy for long
+const MAX_ITERATIONS: u32 = 10_000_000;
+const KDF_NAME: &str = "PBKDF2-HMAC-SHA256";
+
+/// Public information about a key held in a [`KeyStore`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyInfo {
+    /// id to refer to key with in [`KeyStore`] calls
+    pub id: String,
+    /// algorithm of key
+    pub key_type: KeyType,
+    /// public key as multikey, e.g. "z6Mk..."
+    pub public_key: String,
+}
+
+/// Holds secret keys and uses them on behalf of plugins, so plugins can request signatures and
+/// shared secrets by key id without ever holding key material themselves.
+///
+/// A key store is registered on [`Vade`](https://docs.rs/vade/*/vade/struct.Vade.html) with
+/// [`register_key_store`](https://docs.rs/vade/*/vade/struct.Vade.html#method.register_key_store)
+/// and handed to all plugins with
+/// [`set_key_store`](https://docs.rs/vade/*/vade/trait.VadePlugin.html#method.set_key_store).
+/// Implementations for remote key management systems or hardware modules can be plugged in by
+/// implementing this trait.
+///
+/// Signatures are created as follows:
+///
+/// - Ed25519: 64 bytes signature of the message
+/// - secp256k1, P-256: 64 bytes ECDSA signature `r || s` of the SHA-256 hash of the message
+/// - X25519: keys cannot be used for signing
+/// - BLS12-381 G2: keys only create BBS signatures over several messages with
+///   [`sign_bbs`](#method.sign_bbs)
+///
+/// # Example
+///
+/// ```
+/// use vade::{multikey::KeyType, InMemoryKeyStore, KeyStore};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let key_store = InMemoryKeyStore::new();
+///     let key = key_store.generate_key(KeyType::Ed25519).await?;
+///     let signature = key_store.sign(&key.id, b"hello").await?;
+///     assert!(key_store.verify(&key.public_key, b"hello", &signature).await?);
+///     Ok(())
+/// }
+/// ```
+#[async_trait(?Send)]
+pub trait KeyStore {
+    /// Generates a new key and returns its public information.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_type` - algorithm of key to generate
+    async fn generate_key(&self, key_type: KeyType) -> Result<KeyInfo, Box<dyn std::error::Error>>;
+
+    /// Lists all keys held in key store.
+    async fn list_keys(&self) -> Result<Vec<KeyInfo>, Box<dyn std::error::Error>>;
+
+    /// Returns public information about given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - id of key to get
+    async fn get_key(&self, key_id: &str) -> Result<KeyInfo, Box<dyn std::error::Error>> {
+        self.list_keys()
+            .await?
+            .into_iter()
+            .find(|key| key.id == key_id)
+            .ok_or_else(|| Box::from(format!(r#"unknown key "{}""#, key_id)))
+    }
+
+    /// Deletes given key, e.g. to retire it after a key rotation. Key stores, that cannot delete
+    /// keys, keep the default implementation, that fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - id of key to delete
+    async fn delete_key(&self, key_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Err(Box::from(format!(
+            r#"key store does not support deleting key "{}""#,
+            key_id
+        )))
+    }
+
+    /// Signs given message with given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - id of key to sign with
+    /// * `message` - message to sign
+    async fn sign(
+        &self,
+        key_id: &str,
+        message: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
+
+    /// Signs given messages and header with a BBS signature of the `BLS12-381-SHA-256`
+    /// ciphersuite, as used by `bbs-2023` credentials. Key stores, that do not support BBS, keep
+    /// the default implementation, that fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - id of BLS12-381 G2 key to sign with
+    /// * `header` - header bound to the signature
+    /// * `messages` - messages to sign
+    async fn sign_bbs(
+        &self,
+        key_id: &str,
+        _header: &[u8],
+        _messages: &[Vec<u8>],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Err(Box::from(format!(
+            r#"key store does not support BBS signatures with key "{}""#,
+            key_id
+        )))
+    }
+
+    /// Checks if given signature of given message has been created with the secret key of given
+    /// public key. Does not require the key to be held in key store.
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key` - public key as multikey
+    /// * `message` - signed message
+    /// * `signature` - signature to check
+    async fn verify(
+        &self,
+        public_key: &str,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let (key_type, public_key) = multikey::decode_public_key(public_key)?;
+        crypto::verify(key_type, &public_key, message, signature)
+    }
+
+    /// Computes the raw Diffie-Hellman shared secret of given key and another party's public key.
+    /// Supported for X25519, P-256 and secp256k1 keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - id of own key
+    /// * `public_key` - other party's public key as multikey of the same type
+    async fn agree(
+        &self,
+        key_id: &str,
+        public_key: &str,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
+}
+
+/// Key a plugin signs with, either given as `secretKey` multikey in its options or referenced as
+/// `keyId` of a key in the registered key store.
+pub(crate) enum KeyReference {
+    SecretKey(KeyInfo, Vec<u8>),
+    KeyStore(Rc<dyn KeyStore>, KeyInfo),
+}
+
+impl KeyReference {
+    /// Gets the key to sign with from given options values, a secret key takes precedence.
+    pub(crate) async fn from_options(
+        key_store: Option<&Rc<dyn KeyStore>>,
+        secret_key: Option<&str>,
+        key_id: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        match (secret_key, key_id, key_store) {
+            (Some(secret_key), _, _) => {
+                let (key_type, secret_key) = multikey::decode_secret_key(secret_key)?;
+                let public_key = multikey::encode_public_key(
+                    key_type,
+                    &crypto::get_public_key(key_type, &secret_key)?,
+                );
+                let info = KeyInfo {
+                    id: public_key.clone(),
+                    key_type,
+                    public_key,
+                };
+                Ok(KeyReference::SecretKey(info, secret_key))
+            }
+            (None, Some(key_id), Some(key_store)) => Ok(KeyReference::KeyStore(
+                Rc::clone(key_store),
+                key_store.get_key(key_id).await?,
+            )),
+            (None, Some(_), None) => Err(Box::from("keyId given, but no key store registered")),
+            (None, None, _) => Err(Box::from("either secretKey or keyId has to be given")),
+        }
+    }
+
+    pub(crate) fn info(&self) -> &KeyInfo {
+        match self {
+            KeyReference::SecretKey(info, _) | KeyReference::KeyStore(_, info) => info,
+        }
+    }
+
+    pub(crate) async fn sign(&self, message: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match self {
+            KeyReference::SecretKey(info, secret_key) => {
+                crypto::sign(info.key_type, secret_key, message)
+            }
+            KeyReference::KeyStore(key_store, info) => key_store.sign(&info.id, message).await,
+        }
+    }
+
+    #[cfg(feature = "bbs")]
+    pub(crate) async fn sign_bbs(
+        &self,
+        header: &[u8],
+        messages: &[Vec<u8>],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match self {
+            KeyReference::SecretKey(info, secret_key) => match info.key_type {
+                KeyType::Bls12381G2 => crypto::sign_bbs(secret_key, header, messages),
+                key_type => Err(Box::from(format!(
+                    "BBS signatures require a BLS12-381 G2 key, got a {:?} key",
+                    key_type
+                ))),
+            },
+            KeyReference::KeyStore(key_store, info) => {
+                key_store.sign_bbs(&info.id, header, messages).await
+            }
+        }
+    }
+
+    #[cfg(feature = "didcomm")]
+    pub(crate) async fn agree(
+        &self,
+        public_key: &str,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match self {
+            KeyReference::SecretKey(info, secret_key) => {
+                let (key_type, public_key) = multikey::decode_public_key(public_key)?;
+                if key_type != info.key_type {
+                    return Err(Box::from(format!(
+                        "cannot agree on a secret between {:?} and {:?} keys",
+                        info.key_type, key_type
+                    )));
+                }
+                crypto::agree(key_type, secret_key, &public_key)
+            }
+            KeyReference::KeyStore(key_store, info) => key_store.agree(&info.id, public_key).await,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredKey {
+    #[serde(flatten)]
+    info: KeyInfo,
+    secret_key: String,
+}
+
+/// [`KeyStore`] holding its keys in memory only. Keys are referenced by their public key multikey.
+#[derive(Default)]
+pub struct InMemoryKeyStore {
+    keys: RefCell<Vec<StoredKey>>,
+}
+
+impl InMemoryKeyStore {
+    /// Creates a new, empty `InMemoryKeyStore`.
+    pub fn new() -> Self {
+        InMemoryKeyStore {
+            keys: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Adds an existing secret key to key store, e.g. when migrating keys from plugin options.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_type` - algorithm of key
+    /// * `secret_key` - raw secret key bytes
+    pub fn import_key(
+        &self,
+        key_type: KeyType,
+        secret_key: &[u8],
+    ) -> Result<KeyInfo, Box<dyn std::error::Error>> {
+        let public_key = crypto::get_public_key(key_type, secret_key)?;
+        let public_key = multikey::encode_public_key(key_type, &public_key);
+        let info = KeyInfo {
+            id: public_key.clone(),
+            key_type,
+            public_key,
+        };
+        let mut keys = self.keys.borrow_mut();
+        if !keys.iter().any(|key| key.info.id == info.id) {
+            keys.push(StoredKey {
+                info: info.clone(),
+                secret_key: multikey::encode_secret_key(key_type, secret_key),
+            });
+        }
+        Ok(info)
+    }
+
+    fn get_secret_key(
+        &self,
+        key_id: &str,
+    ) -> Result<(KeyType, Vec<u8>), Box<dyn std::error::Error>> {
+        let keys = self.keys.borrow();
+        let key = keys
+            .iter()
+            .find(|key| key.info.id == key_id)
+            .ok_or_else(|| format!(r#"unknown key "{}""#, key_id))?;
+        multikey::decode_secret_key(&key.secret_key)
+    }
+}
+
+#[async_trait(?Send)]
+impl KeyStore for InMemoryKeyStore {
+    async fn generate_key(&self, key_type: KeyType) -> Result<KeyInfo, Box<dyn std::error::Error>> {
+        self.import_key(key_type, &crypto::generate_secret_key(key_type)?)
+    }
+
+    async fn list_keys(&self) -> Result<Vec<KeyInfo>, Box<dyn std::error::Error>> {
+        Ok(self
+            .keys
+            .borrow()
+            .iter()
+            .map(|key| key.info.clone())
+            .collect())
+    }
+
+    async fn delete_key(&self, key_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut keys = self.keys.borrow_mut();
+        let count = keys.len();
+        keys.retain(|key| key.info.id != key_id);
+        if keys.len() == count {
+            return Err(Box::from(format!(r#"unknown key "{}""#, key_id)));
+        }
+        Ok(())
+    }
+
+    async fn sign(
+        &self,
+        key_id: &str,
+        message: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let (key_type, secret_key) = self.get_secret_key(key_id)?;
+        crypto::sign(key_type, &secret_key, message)
+    }
+
+    #[cfg(feature = "bbs")]
+    async fn sign_bbs(
+        &self,
+        key_id: &str,
+        header: &[u8],
+        messages: &[Vec<u8>],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match self.get_secret_key(key_id)? {
+            (KeyType::Bls12381G2, secret_key) => crypto::sign_bbs(&secret_key, header, messages),
+            (key_type, _) => Err(Box::from(format!(
+                "BBS signatures require a BLS12-381 G2 key, got a {:?} key",
+                key_type
+            ))),
+        }
+    }
+
+    async fn agree(
+        &self,
+        key_id: &str,
+        public_key: &str,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let (key_type, secret_key) = self.get_secret_key(key_id)?;
+        let (other_key_type, public_key) = multikey::decode_public_key(public_key)?;
+        if key_type != other_key_type {
+            return Err(Box::from(format!(
+                "cannot agree on a secret between {:?} and {:?} keys",
+                key_type, other_key_type
+            )));
+        }
+        crypto::agree(key_type, &secret_key, &public_key)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyStoreFile {
+    kdf: String,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// [`KeyStore`] persisting its keys in a file, that is encrypted with AES-256-GCM and a key
+/// derived from a passphrase with PBKDF2-HMAC-SHA256. The file is rewritten after every change.
+pub struct EncryptedFileKeyStore {
+    path: PathBuf,
+    keys: InMemoryKeyStore,
+    encryption_key: [u8; 32],
+    salt: Vec<u8>,
+    iterations: u32,
+}
+
+impl EncryptedFileKeyStore {
+    /// Opens the key store at given path or starts a new one, if the file does not exist yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of key store file
+    /// * `passphrase` - passphrase to derive encryption key from
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        passphrase: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        EncryptedFileKeyStore::open_with_iterations(path, passphrase, DEFAULT_ITERATIONS)
+    }
+
+    /// Like [`open`](#method.open), but new files use given number of PBKDF2 iterations. Existing
+    /// files keep the number of iterations they have been created with. Iterations have to be
+    /// between 10,000 and 10,000,000.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of key store file
+    /// * `passphrase` - passphrase to derive encryption key from
+    /// * `iterations` - PBKDF2 iterations for new files
+    pub fn open_with_iterations<P: AsRef<Path>>(
+        path: P,
+        passphrase: &str,
+        iterations: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref().to_path_buf();
+        if !path.exists() {
+            check_iterations(iterations)?;
+            let mut salt = vec![0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            return Ok(EncryptedFileKeyStore {
+                encryption_key: derive_key(passphrase, &salt, iterations),
+                path,
+                keys: InMemoryKeyStore::new(),
+                salt,
+                iterations,
+            });
+        }
+
+        let file: KeyStoreFile = serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .map_err(|e| format!(r#"invalid key store file "{}"; {}"#, path.display(), e))?;
+        if file.kdf != KDF_NAME {
+            return Err(Box::from(format!(
+                r#"unsupported key derivation "{}""#,
+                file.kdf
+            )));
+        }
+        check_iterations(file.iterations)
+            .map_err(|e| format!(r#"invalid key store "{}"; {}"#, path.display(), e))?;
+        let salt = STANDARD.decode(&file.salt)?;
+        let nonce = STANDARD.decode(&file.nonce)?;
+        if nonce.len() != 12 {
+            return Err(Box::from(format!(
+                r#"invalid nonce length in key store "{}""#,
+                path.display()
+            )));
+        }
+        let encryption_key = derive_key(passphrase, &salt, file.iterations);
+        let plaintext = Aes256Gcm::new(&encryption_key.into())
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                STANDARD.decode(&file.ciphertext)?.as_ref(),
+            )
+            .map_err(|_| {
+                format!(
+                    r#"could not decrypt key store "{}", passphrase may be wrong"#,
+                    path.display()
+                )
+            })?;
+        let keys: Vec<StoredKey> = serde_json::from_slice(&plaintext)?;
+
+        Ok(EncryptedFileKeyStore {
+            path,
+            keys: InMemoryKeyStore {
+                keys: RefCell::new(keys),
+            },
+            encryption_key,
+            salt,
+            iterations: file.iterations,
+        })
+    }
+
+    /// Adds an existing secret key to key store and saves it.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_type` - algorithm of key
+    /// * `secret_key` - raw secret key bytes
+    pub fn import_key(
+        &self,
+        key_type: KeyType,
+        secret_key: &[u8],
+    ) -> Result<KeyInfo, Box<dyn std::error::Error>> {
+        let info = self.keys.import_key(key_type, secret_key)?;
+        self.save()?;
+        Ok(info)
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let plaintext = serde_json::to_vec(&*self.keys.keys.borrow())?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(&self.encryption_key.into())
+            .encrypt(&nonce, plaintext.as_ref())
+            .map_err(|_| "could not encrypt key store")?;
+        let file = KeyStoreFile {
+            kdf: KDF_NAME.to_string(),
+            iterations: self.iterations,
+            salt: STANDARD.encode(&self.salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+        // write to a temporary file first, so an interrupted write does not lose all keys
+        let temporary_path = self.path.with_extension("tmp");
+        std::fs::write(&temporary_path, serde_json::to_string_pretty(&file)?)?;
+        std::fs::rename(&temporary_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl KeyStore for EncryptedFileKeyStore {
+    async fn generate_key(&self, key_type: KeyType) -> Result<KeyInfo, Box<dyn std::error::Error>> {
+        self.import_key(key_type, &crypto::generate_secret_key(key_type)?)
+    }
+
+    async fn list_keys(&self) -> Result<Vec<KeyInfo>, Box<dyn std::error::Error>> {
+        self.keys.list_keys().await
+    }
+
+    async fn delete_key(&self, key_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.keys.delete_key(key_id).await?;
+        self.save()
+    }
+
+    async fn sign(
+        &self,
+        key_id: &str,
+        message: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.keys.sign(key_id, message).await
+    }
+
+    async fn sign_bbs(
+        &self,
+        key_id: &str,
+        header: &[u8],
+        messages: &[Vec<u8>],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.keys.sign_bbs(key_id, header, messages).await
+    }
+
+    async fn agree(
+        &self,
+        key_id: &str,
+        public_key: &str,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.keys.agree(key_id, public_key).await
+    }
+}
+
+fn check_iterations(iterations: u32) -> Result<(), String> {
+    if !(MIN_ITERATIONS..=MAX_ITERATIONS).contains(&iterations) {
+        return Err(format!(
+            "PBKDF2 iterations must be between {} and {}, got {}",
+            MIN_ITERATIONS, MAX_ITERATIONS, iterations
+        ));
+    }
+    Ok(())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
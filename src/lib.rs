@@ -35,7 +35,9 @@
 //! | ------ | ---- |
 //! | did:evan | [![crates.io](https://img.shields.io/crates/v/vade-evan.svg)](https://crates.io/crates/vade-evan) |
 //! | did:example | [vade-example-plugin](https://github.com/evannetwork/vade-example-plugin) |
-//! | (universal resolver method list) | in development  |
+//! | did:ion (long-form) and other Sidetree based methods | [`SidetreeVadePlugin`] (offline) |
+//! | (universal resolver method list) | [`UniversalResolverVadePlugin`] (proxies to a [DIF Universal Resolver](https://github.com/decentralized-identity/universal-resolver) instance) |
+//! | did:webvh, did:tdw | [`WebVhVadePlugin`] (verifies DID logs, creates and updates DIDs) |
 //!
 //! More coming soon. To write your own plugins, have a look at [writing own plugins].
 //!
@@ -44,9 +46,17 @@
 //! | Method | Info |
 //! | ------ | ---- |
 //! | did:evan | [![crates.io](https://img.shields.io/crates/v/vade-evan.svg)](https://crates.io/crates/vade-evan) |
+//! | bbs | [`BbsVadePlugin`] (zero-knowledge credentials with BBS signatures (`bbs-2023`), selective disclosure and unlinkable proofs) |
+//! | data-integrity | [`DataIntegrityVadePlugin`] (Data Integrity proofs with EdDSA and ECDSA cryptosuites, incl. selective disclosure) |
+//! | jwt, cose | [`JoseVadePlugin`] (credentials and presentations secured with JOSE or COSE) |
+//! | revocation-registry | [`RevocationRegistryVadePlugin`] (offline revocation registries with tails files and non-revocation witnesses) |
+//! | sd-jwt | [`SdJwtVadePlugin`] (SD-JWT and SD-JWT VC with selective disclosure and key binding) |
+//! | status-list | [`StatusListVadePlugin`] (revocation and suspension with Bitstring Status Lists and Status List 2021) |
 //!
 //! More coming soon. To write your own plugins, have a look at [writing own plugins].
 //!
+//! Plugins with network access or additional cryptographic libraries and DIDComm messaging can be disabled with cargo features, that are enabled by default: `bbs`, `didcomm`, `revocation-registry`, `status-list`, `universal-resolver` and `webvh`.
+//!
 //! ## Example Usage
 //!
 //! ```rust
@@ -90,11 +100,13 @@
 //!
 //! ## Vade Features
 //!
-//! The current set of features can be grouped into 3 clusters:
+//! The current set of features can be grouped into 5 clusters:
 //!
 //! - management functions
 //! - DID interaction
+//! - VC interaction
 //! - zero knowledge proof VC interaction
+//! - DIDComm messaging
 //!
 //! ### Management Functions
 //!
@@ -102,6 +114,18 @@
 //!
 //! Registers a new plugin. See [`VadePlugin`](https://docs.rs/vade/*/vade/struct.VadePlugin.html) for details about how they work.
 //!
+//! **[`register_verification_checks`]**
+//!
+//! Registers checks `Vade` runs after `vc_verify`, `vp_verify` and `vc_zkp_verify_proof`: validity periods are checked against an injectable clock and statuses via `vc_status_list_check`. Results are then returned as [`VerificationReport`](https://docs.rs/vade/*/vade/struct.VerificationReport.html) listing every check. Issuers can be checked against a [`TrustRegistry`](https://docs.rs/vade/*/vade/trait.TrustRegistry.html) like a [`TrustList`](https://docs.rs/vade/*/vade/struct.TrustList.html) read from a local file, a signed list or a trust-list credential.
+//!
+//! **[`register_mediator`]**
+//!
+//! Registers a [`Mediator`](https://docs.rs/vade/*/vade/struct.Mediator.html), which `didcomm_receive` passes messages to first. It grants mediation to recipients with Coordinate Mediation 3.0, queues `forward` messages encrypted for it for registered recipients in size-limited queues until they expire and delivers them on authenticated Message Pickup 3.0 requests.
+//!
+//! **[`register_didcomm_handler`]**
+//!
+//! Registers a [`DidCommHandler`](https://docs.rs/vade/*/vade/trait.DidCommHandler.html) for DIDComm protocols or message types. `didcomm_receive` passes received messages to the handler matching their protocol and major version, preferring the same minor version. Unsupported messages are answered with a `problem-report`, if no plugin handles them.
+//!
 //! ### DID Interaction
 //!
 //! **[`did_create`]**
@@ -120,6 +144,65 @@
 //!
 //! Updates data related to a DID. May also persist a DID document for it, depending on plugin implementation.
 //!
+//! -----
+//!
+//! **[`rotate_key`]**
+//!
+//! Replaces a key of a DID's verification methods with a new key from the registered key store via `did_update` and retires the old key.
+//!
+//! ### VC Interaction
+//!
+//! **[`vc_issue`]**
+//!
+//! Issues a new verifiable credential. The credential is secured with an embedded proof or an
+//! enveloping signature, depending on plugin implementation.
+//!
+//! -----
+//!
+//! **[`vc_status_list_create`]**
+//!
+//! Creates a new status list, e.g. a Bitstring Status List, and returns its status list credential,
+//! that has to be secured and published by the issuer.
+//!
+//! -----
+//!
+//! **[`vc_status_list_allocate`]**
+//!
+//! Allocates an index in a status list for a credential to be issued and adds a matching
+//! `credentialStatus` entry to the credential.
+//!
+//! -----
+//!
+//! **[`vc_status_list_update`]**
+//!
+//! Sets the status of an issued credential in its status list, e.g. to revoke or suspend it, and
+//! returns the updated status list credential to publish.
+//!
+//! -----
+//!
+//! **[`vc_status_list_check`]**
+//!
+//! Checks the `credentialStatus` of a verifiable credential against its status lists.
+//!
+//! -----
+//!
+//! **[`vc_verify`]**
+//!
+//! Verifies a verifiable credential, e.g. its proof or signature and its validity period.
+//!
+//! -----
+//!
+//! **[`vp_create`]**
+//!
+//! Creates a new verifiable presentation from one or multiple verifiable credentials and secures it
+//! with a proof or an enveloping signature of the holder, depending on plugin implementation.
+//!
+//! -----
+//!
+//! **[`vp_verify`]**
+//!
+//! Verifies a verifiable presentation and the credentials presented in it.
+//!
 //! ### Zero Knowledge Proof VC Interaction
 //!
 //! **[`vc_zkp_create_credential_schema`]**
@@ -202,6 +285,48 @@
 //!
 //! Verifies a one or multiple proofs sent in a proof presentation.
 //!
+//! -----
+//!
+//! **[`vc_zkp_request_proof_with_policy`]**
+//!
+//! Requests a zero-knowledge proof for the credentials listed in a [`VerifierPolicy`](https://docs.rs/vade/*/vade/struct.VerifierPolicy.html). Policies are written as JSON and name required credential types, schemas, disclosed claims, predicates, allowed issuers, maximum ages and whether credentials have to be non-revoked.
+//!
+//! -----
+//!
+//! **[`vc_zkp_verify_proof_with_policy`]**
+//!
+//! Verifies a proof presentation and evaluates the result against a verifier policy, returning a [`PolicyEvaluation`](https://docs.rs/vade/*/vade/struct.PolicyEvaluation.html) with the outcome of every requirement.
+//!
+//! ### DIDComm Messaging
+//!
+//! **[`didcomm_pack`]**
+//!
+//! Packs a plaintext DIDComm v2 [`DidCommMessage`](https://docs.rs/vade/*/vade/struct.DidCommMessage.html) as signed JWS or as JWE encrypted with authcrypt (ECDH-1PU) or anoncrypt (ECDH-ES) for the X25519 or P-256 `keyAgreement` keys of its recipients, that are resolved with `did_resolve`.
+//!
+//! -----
+//!
+//! **[`didcomm_unpack`]**
+//!
+//! Decrypts and verifies a packed DIDComm v2 message with keys of the registered key store and returns the plaintext message with metadata about its encryption and signature.
+//!
+//! -----
+//!
+//! **[`didcomm_send`]**
+//!
+//! Sends a DIDComm message with the registered plugins. With a `pack` option the message is packed first and wrapped in anoncrypted `forward` messages for the `routingKeys` of the recipient's `DIDCommMessaging` service; the resulting message and its `serviceEndpoint` are then passed on to the plugins.
+//!
+//! -----
+//!
+//! **[`IssueCredential`]**
+//!
+//! Protocol engine for Issue Credential 3.0 and Aries Issue Credential 2.0, both in DIDComm v2 messages (the Aries RFC 0453 wire format with `@type` and `~thread` is not supported). Registered as DIDComm handler, it calls `vc_zkp_create_credential_proposal`, `vc_zkp_create_credential_offer`, `vc_zkp_request_credential`, `vc_zkp_issue_credential` and `vc_zkp_finish_credential` in protocol order for issuers and holders, exchanges their results with `didcomm_send` and `didcomm_receive` and persists the state of every thread in a [`ThreadStore`](https://docs.rs/vade/*/vade/trait.ThreadStore.html).
+//!
+//! -----
+//!
+//! **[`PresentProof`]**
+//!
+//! Protocol engine for Present Proof 3.0. Registered as DIDComm handler, it calls `vc_zkp_propose_proof`, `vc_zkp_request_proof`, `vc_zkp_present_proof` and `vc_zkp_verify_proof` in protocol order for provers and verifiers, acknowledges verified presentations, answers failed verifications, unexpected and expired messages with problem reports, abandons threads after a configurable timeout and persists the state of every thread in a [`ThreadStore`](https://docs.rs/vade/*/vade/trait.ThreadStore.html).
+//!
 //! ### Custom Functions
 //!
 //! **[`run_custom_function`]**
@@ -230,11 +355,35 @@
 //! [`did_create`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.did_create
 //! [`did_resolve`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.did_resolve
 //! [`did_update`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.did_update
+//! [`didcomm_pack`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.didcomm_pack
+//! [`didcomm_unpack`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.didcomm_unpack
+//! [`didcomm_send`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.didcomm_send
+//! [`IssueCredential`]: https://docs.rs/vade/*/vade/struct.IssueCredential.html
+//! [`PresentProof`]: https://docs.rs/vade/*/vade/struct.PresentProof.html
+//! [`register_didcomm_handler`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.register_didcomm_handler
+//! [`register_mediator`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.register_mediator
 //! [`register_plugin`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.register_plugin
+//! [`rotate_key`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.rotate_key
+//! [`register_verification_checks`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.register_verification_checks
 //! [`run_custom_function`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.run_custom_function
 //! [`vade-evan`]: https://docs.rs/vade-evan
+//! [`SidetreeVadePlugin`]: https://docs.rs/vade/*/vade/plugins/struct.SidetreeVadePlugin.html
+//! [`UniversalResolverVadePlugin`]: https://docs.rs/vade/*/vade/plugins/struct.UniversalResolverVadePlugin.html
+//! [`BbsVadePlugin`]: https://docs.rs/vade/*/vade/plugins/struct.BbsVadePlugin.html
+//! [`DataIntegrityVadePlugin`]: https://docs.rs/vade/*/vade/plugins/struct.DataIntegrityVadePlugin.html
+//! [`JoseVadePlugin`]: https://docs.rs/vade/*/vade/plugins/struct.JoseVadePlugin.html
+//! [`RevocationRegistryVadePlugin`]: https://docs.rs/vade/*/vade/plugins/struct.RevocationRegistryVadePlugin.html
+//! [`SdJwtVadePlugin`]: https://docs.rs/vade/*/vade/plugins/struct.SdJwtVadePlugin.html
+//! [`StatusListVadePlugin`]: https://docs.rs/vade/*/vade/plugins/struct.StatusListVadePlugin.html
+//! [`WebVhVadePlugin`]: https://docs.rs/vade/*/vade/plugins/struct.WebVhVadePlugin.html
 //! [`Vade`]: https://docs.rs/vade/*/vade/struct.Vade.html
 //! [`VadePlugin`]: https://docs.rs/vade/*/vade/trait.VadePlugin.html
+//! [`vc_issue`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_issue
+//! [`vc_status_list_create`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_status_list_create
+//! [`vc_status_list_allocate`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_status_list_allocate
+//! [`vc_status_list_update`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_status_list_update
+//! [`vc_status_list_check`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_status_list_check
+//! [`vc_verify`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_verify
 //! [`vc_zkp_create_credential_definition`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_create_credential_definition
 //! [`vc_zkp_create_credential_offer`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_create_credential_offer
 //! [`vc_zkp_create_credential_proposal`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_create_credential_proposal
@@ -244,9 +393,13 @@
 //! [`vc_zkp_present_proof`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_present_proof
 //! [`vc_zkp_request_credential`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_request_credential
 //! [`vc_zkp_request_proof`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_request_proof
+//! [`vc_zkp_request_proof_with_policy`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_request_proof_with_policy
 //! [`vc_zkp_revoke_credential`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_revoke_credential
 //! [`vc_zkp_update_revocation_registry`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_update_revocation_registry
 //! [`vc_zkp_verify_proof`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_verify_proof
+//! [`vc_zkp_verify_proof_with_policy`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_verify_proof_with_policy
+//! [`vp_create`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vp_create
+//! [`vp_verify`]: https://docs.rs/vade/*/vade/struct.Vade.html#method.vp_verify
 //! [vade-wasm-example]: https://github.com/evannetwork/vade-wasm-example
 //! <!-- for lib.rs -->
 //! [wasm support]: https://docs.rs/vade/*/vade/#wasm-support
@@ -260,8 +413,74 @@ extern crate env_logger;
 #[macro_use]
 extern crate log;
 
+#[macro_use]
+mod plugin_call;
+mod crypto;
+mod data_integrity;
+mod did_resolution;
+#[cfg(feature = "didcomm")]
+mod didcomm;
+#[cfg(feature = "didcomm")]
+mod didcomm_protocols;
+#[cfg(feature = "didcomm")]
+mod didcomm_routing;
+#[cfg(feature = "didcomm")]
+mod issue_credential;
+mod jwk;
+mod jwt;
+mod key_rotation;
+mod key_store;
+#[cfg(feature = "mock")]
+mod mock_vade_plugin;
+#[cfg(feature = "didcomm")]
+mod present_proof;
+mod presentation;
+mod rdfc;
+mod record_replay;
+mod selective_disclosure;
+#[cfg(feature = "didcomm")]
+mod thread_store;
+mod trust_registry;
 mod vade;
 mod vade_plugin;
+mod verification_method;
+mod verification_report;
+mod verifier_policy;
+
+pub mod jcs;
+pub mod json_ld;
+pub mod multikey;
+pub mod plugins;
 
+pub use self::did_resolution::{DidResolutionResult, HostResolver};
+#[cfg(feature = "didcomm")]
+pub use self::didcomm::{DidCommMessage, UnpackMetadata, UnpackedMessage};
+#[cfg(feature = "didcomm")]
+pub use self::didcomm_protocols::{DidCommHandler, ProtocolAction};
+#[cfg(feature = "didcomm")]
+pub use self::didcomm_routing::Mediator;
+#[cfg(feature = "didcomm")]
+pub use self::issue_credential::{
+    CredentialExchange, IssuanceRole, IssuanceState, IssueCredential,
+};
+pub use self::key_store::{EncryptedFileKeyStore, InMemoryKeyStore, KeyInfo, KeyStore};
+#[cfg(feature = "mock")]
+pub use self::mock_vade_plugin::{Matcher, MockExpectation, MockVadePlugin};
+pub use self::plugin_call::PluginCall;
+#[cfg(feature = "didcomm")]
+pub use self::present_proof::{PresentProof, ProofExchange, ProofRole, ProofState};
+pub use self::presentation::{ChallengeStore, InMemoryChallengeStore};
+pub use self::record_replay::{
+    Cassette, Interaction, RecordedResult, RecordingVadePlugin, ReplayVadePlugin,
+};
+#[cfg(feature = "didcomm")]
+pub use self::thread_store::{FileThreadStore, InMemoryThreadStore, ThreadStore};
+pub use self::trust_registry::{TrustList, TrustRegistry, TrustedIssuer};
 pub use self::vade::Vade;
 pub use self::vade_plugin::{VadePlugin, VadePluginResultValue};
+pub use self::verification_report::{
+    Clock, FixedClock, SystemClock, VerificationCheck, VerificationChecks, VerificationReport,
+};
+pub use self::verifier_policy::{
+    CredentialRequirement, PolicyEvaluation, Predicate, RequirementEvaluation, VerifierPolicy,
+};
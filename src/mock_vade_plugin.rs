@@ -0,0 +1,394 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{KeyStore, PluginCall, VadePluginResultValue};
+use serde_json::Value;
+use std::rc::Rc;
+
+/// Checks a single argument of a call against an expectation.
+pub enum Matcher {
+    /// Matches every value.
+    Any,
+    /// Matches values equal to given string.
+    Exact(String),
+    /// Matches values that parse to JSON equal to given value, so formatting and key order do not
+    /// matter.
+    Json(Value),
+    /// Matches values containing given string.
+    Contains(String),
+    /// Matches values for which given function returns `true`.
+    Predicate(Box<dyn Fn(&str) -> bool>),
+}
+
+impl Matcher {
+    /// Checks if given value matches this matcher.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - argument value of a call
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Any => true,
+            Matcher::Exact(expected) => expected == value,
+            Matcher::Json(expected) => match serde_json::from_str::<Value>(value) {
+                Ok(parsed) => &parsed == expected,
+                Err(_) => false,
+            },
+            Matcher::Contains(expected) => value.contains(expected.as_str()),
+            Matcher::Predicate(predicate) => predicate(value),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Matcher::Any => "any".to_string(),
+            Matcher::Exact(expected) => format!(r#""{}""#, expected),
+            Matcher::Json(expected) => format!("json {}", expected),
+            Matcher::Contains(expected) => format!(r#"containing "{}""#, expected),
+            Matcher::Predicate(_) => "predicate".to_string(),
+        }
+    }
+}
+
+impl From<&str> for Matcher {
+    fn from(value: &str) -> Self {
+        Matcher::Exact(value.to_string())
+    }
+}
+
+impl From<String> for Matcher {
+    fn from(value: String) -> Self {
+        Matcher::Exact(value)
+    }
+}
+
+impl From<Value> for Matcher {
+    fn from(value: Value) -> Self {
+        Matcher::Json(value)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Times {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Times {
+    fn is_saturated(&self, count: usize) -> bool {
+        match self {
+            Times::Exactly(expected) => count >= *expected,
+            Times::AtLeast(_) => false,
+        }
+    }
+
+    fn is_satisfied(&self, count: usize) -> bool {
+        match self {
+            Times::Exactly(expected) => count == *expected,
+            Times::AtLeast(expected) => count >= *expected,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Times::Exactly(expected) => format!("exactly {}", expected),
+            Times::AtLeast(expected) => format!("at least {}", expected),
+        }
+    }
+}
+
+enum MockResponse {
+    Value(VadePluginResultValue<Option<String>>),
+    Error(String),
+}
+
+/// An expected call of a [`MockVadePlugin`] with its canned response.
+///
+/// Expectations are created with [`MockVadePlugin::expect`] and configured with its builder
+/// functions. By default an expectation matches all arguments, has to be called at least once
+/// and responds with `VadePluginResultValue::Ignored`.
+pub struct MockExpectation {
+    function: String,
+    method: Matcher,
+    custom_function: Matcher,
+    options: Matcher,
+    payload: Matcher,
+    times: Times,
+    response: MockResponse,
+    call_count: usize,
+}
+
+impl MockExpectation {
+    fn new(function: &str) -> Self {
+        MockExpectation {
+            function: function.to_string(),
+            method: Matcher::Any,
+            custom_function: Matcher::Any,
+            options: Matcher::Any,
+            payload: Matcher::Any,
+            times: Times::AtLeast(1),
+            response: MockResponse::Value(VadePluginResultValue::Ignored),
+            call_count: 0,
+        }
+    }
+
+    /// Only match calls whose method or DID matches given matcher.
+    pub fn with_method<M: Into<Matcher>>(&mut self, matcher: M) -> &mut Self {
+        self.method = matcher.into();
+        self
+    }
+
+    /// Only match calls of `run_custom_function` whose custom function name matches given matcher.
+    pub fn with_custom_function<M: Into<Matcher>>(&mut self, matcher: M) -> &mut Self {
+        self.custom_function = matcher.into();
+        self
+    }
+
+    /// Only match calls whose options match given matcher.
+    pub fn with_options<M: Into<Matcher>>(&mut self, matcher: M) -> &mut Self {
+        self.options = matcher.into();
+        self
+    }
+
+    /// Only match calls whose payload matches given matcher.
+    pub fn with_payload<M: Into<Matcher>>(&mut self, matcher: M) -> &mut Self {
+        self.payload = matcher.into();
+        self
+    }
+
+    /// Expect exactly `count` matching calls.
+    pub fn times(&mut self, count: usize) -> &mut Self {
+        self.times = Times::Exactly(count);
+        self
+    }
+
+    /// Expect `count` or more matching calls.
+    pub fn at_least(&mut self, count: usize) -> &mut Self {
+        self.times = Times::AtLeast(count);
+        self
+    }
+
+    /// Expect no matching calls at all.
+    pub fn never(&mut self) -> &mut Self {
+        self.times = Times::Exactly(0);
+        self
+    }
+
+    /// Respond to matching calls with given value.
+    pub fn returning(&mut self, value: VadePluginResultValue<Option<String>>) -> &mut Self {
+        self.response = MockResponse::Value(value);
+        self
+    }
+
+    /// Respond to matching calls with `VadePluginResultValue::Success` and given value.
+    pub fn returning_success(&mut self, value: &str) -> &mut Self {
+        self.returning(VadePluginResultValue::Success(Some(value.to_string())))
+    }
+
+    /// Respond to matching calls with an error with given message.
+    pub fn returning_error(&mut self, message: &str) -> &mut Self {
+        self.response = MockResponse::Error(message.to_string());
+        self
+    }
+
+    /// Number of calls this expectation has handled so far.
+    pub fn call_count(&self) -> usize {
+        self.call_count
+    }
+
+    fn matches(&self, call: &PluginCall) -> bool {
+        self.function == call.function
+            && self.method.matches(&call.method)
+            && match &call.custom_function {
+                Some(custom_function) => self.custom_function.matches(custom_function),
+                None => true,
+            }
+            && self.options.matches(&call.options)
+            && self.payload.matches(&call.payload)
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{}(method: {}, custom function: {}, options: {}, payload: {})",
+            self.function,
+            self.method.describe(),
+            self.custom_function.describe(),
+            self.options.describe(),
+            self.payload.describe(),
+        )
+    }
+}
+
+/// A scriptable [`VadePlugin`](https://docs.rs/vade/*/vade/trait.VadePlugin.html) for tests.
+///
+/// Expected calls are registered per function with matchers for method, options and payload,
+/// a canned response and the number of times they are expected to be called. Calls that do not
+/// match any expectation are answered with an error.
+///
+/// Expectations are verified when the plugin is dropped, e.g. at the end of a test or when the
+/// [`Vade`](https://docs.rs/vade/*/vade/struct.Vade.html) instance it has been registered with
+/// goes out of scope. Verification can also be triggered manually with
+/// [`verify`](#method.verify). As unmet expectations panic, the plugin is only available with
+/// the `mock` feature, that should only be enabled for dev-dependencies.
+///
+/// # Example
+///
+/// ```
+/// use vade::{Matcher, MockVadePlugin, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut mock = MockVadePlugin::new();
+///     mock.expect("did_resolve")
+///         .with_method("did:example:123")
+///         .times(1)
+///         .returning_success(r#"{ "id": "did:example:123" }"#);
+///     mock.expect("did_update")
+///         .with_options(Matcher::Contains("privateKey".to_string()))
+///         .returning_error("not allowed");
+///
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(mock));
+///     let results = vade.did_resolve("did:example:123").await?;
+///     assert_eq!(results[0].as_ref().ok_or("result not found")?, r#"{ "id": "did:example:123" }"#);
+///     assert!(vade.did_update("did:example:123", r#"{ "privateKey": "..." }"#, "").await.is_err());
+///     Ok(())
+/// }
+/// ```
+pub struct MockVadePlugin {
+    expectations: Vec<MockExpectation>,
+    calls: Vec<PluginCall>,
+    unexpected_calls: Vec<PluginCall>,
+    allow_unexpected_calls: bool,
+}
+
+impl MockVadePlugin {
+    /// Creates a new `MockVadePlugin` without any expectations.
+    pub fn new() -> Self {
+        MockVadePlugin {
+            expectations: Vec::new(),
+            calls: Vec::new(),
+            unexpected_calls: Vec::new(),
+            allow_unexpected_calls: false,
+        }
+    }
+
+    /// Adds a new expectation for given
+    /// [`VadePlugin`](https://docs.rs/vade/*/vade/trait.VadePlugin.html) function and returns it
+    /// for further configuration. If multiple expectations match a call, the first one that has
+    /// not yet reached its expected call count handles it.
+    ///
+    /// # Arguments
+    ///
+    /// * `function` - name of the expected function, e.g. "did_create"
+    pub fn expect(&mut self, function: &str) -> &mut MockExpectation {
+        self.expectations.push(MockExpectation::new(function));
+        let last = self.expectations.len() - 1;
+        &mut self.expectations[last]
+    }
+
+    /// Answers calls without a matching expectation with `VadePluginResultValue::NotImplemented`
+    /// instead of an error, which is useful when the mock is registered next to other plugins.
+    pub fn allow_unexpected_calls(&mut self) -> &mut Self {
+        self.allow_unexpected_calls = true;
+        self
+    }
+
+    /// All calls this plugin has received so far, in order of arrival.
+    pub fn calls(&self) -> &[PluginCall] {
+        &self.calls
+    }
+
+    /// Checks that all expectations have been met and no unexpected calls have been made.
+    pub fn verify(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut problems = Vec::new();
+        for expectation in self.expectations.iter() {
+            if !expectation.times.is_satisfied(expectation.call_count) {
+                problems.push(format!(
+                    "expected {} to be called {} times, but was called {} times",
+                    expectation.describe(),
+                    expectation.times.describe(),
+                    expectation.call_count,
+                ));
+            }
+        }
+        if !self.allow_unexpected_calls {
+            for call in self.unexpected_calls.iter() {
+                problems.push(format!("unexpected call {}", call));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::from(format!(
+                "mock plugin expectations not met; {}",
+                problems.join("; "),
+            )))
+        }
+    }
+
+    async fn handle_call(
+        &mut self,
+        call: PluginCall,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        self.calls.push(call.clone());
+        let mut candidate = None;
+        for (index, expectation) in self.expectations.iter().enumerate() {
+            if expectation.matches(&call) {
+                candidate = Some(index);
+                if !expectation.times.is_saturated(expectation.call_count) {
+                    break;
+                }
+            }
+        }
+        match candidate {
+            Some(index) => {
+                let expectation = &mut self.expectations[index];
+                expectation.call_count += 1;
+                match &expectation.response {
+                    MockResponse::Value(value) => Ok(value.clone()),
+                    MockResponse::Error(message) => Err(Box::from(message.clone())),
+                }
+            }
+            None if self.allow_unexpected_calls => Ok(VadePluginResultValue::NotImplemented),
+            None => {
+                let message = format!("unexpected call {}", &call);
+                self.unexpected_calls.push(call);
+                Err(Box::from(message))
+            }
+        }
+    }
+
+    fn handle_key_store(&mut self, _key_store: Rc<dyn KeyStore>) {}
+}
+
+impl Default for MockVadePlugin {
+    fn default() -> Self {
+        MockVadePlugin::new()
+    }
+}
+
+impl Drop for MockVadePlugin {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        if let Err(e) = self.verify() {
+            panic!("{}", e);
+        }
+    }
+}
+
+impl_vade_plugin_with_handle_call!(MockVadePlugin);
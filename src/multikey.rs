@@ -0,0 +1,177 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Encoding and decoding of keys in the [Multikey](https://www.w3.org/TR/cid-1.0/#Multikey)
+//! format, e.g. `z6Mk...` for Ed25519 public keys. Keys are prefixed with their multicodec code
+//! and encoded as base58btc multibase strings.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Key algorithms supported in multikeys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyType {
+    /// Ed25519 signing keys
+    Ed25519,
+    /// secp256k1 (ES256K) signing keys
+    Secp256k1,
+    /// NIST P-256 (ES256) signing and key agreement keys
+    P256,
+    /// X25519 key agreement keys
+    X25519,
+    /// BLS12-381 G2 keys for BBS signatures of `bbs-2023` credentials
+    Bls12381G2,
+}
+
+impl KeyType {
+    fn public_key_code(&self) -> u64 {
+        match self {
+            KeyType::Ed25519 => 0xed,
+            KeyType::Secp256k1 => 0xe7,
+            KeyType::P256 => 0x1200,
+            KeyType::X25519 => 0xec,
+            KeyType::Bls12381G2 => 0xeb,
+        }
+    }
+
+    fn secret_key_code(&self) -> u64 {
+        match self {
+            KeyType::Ed25519 => 0x1300,
+            KeyType::Secp256k1 => 0x1301,
+            KeyType::P256 => 0x1306,
+            KeyType::X25519 => 0x1302,
+            KeyType::Bls12381G2 => 0x130a,
+        }
+    }
+
+    fn all() -> [KeyType; 5] {
+        [
+            KeyType::Ed25519,
+            KeyType::Secp256k1,
+            KeyType::P256,
+            KeyType::X25519,
+            KeyType::Bls12381G2,
+        ]
+    }
+}
+
+/// Encodes given public key as multikey.
+///
+/// # Arguments
+///
+/// * `key_type` - algorithm of key
+/// * `key` - raw public key bytes, compressed for elliptic curve keys
+pub fn encode_public_key(key_type: KeyType, key: &[u8]) -> String {
+    encode(key_type.public_key_code(), key)
+}
+
+/// Encodes given secret key as multikey.
+///
+/// # Arguments
+///
+/// * `key_type` - algorithm of key
+/// * `key` - raw secret key bytes
+pub fn encode_secret_key(key_type: KeyType, key: &[u8]) -> String {
+    encode(key_type.secret_key_code(), key)
+}
+
+/// Decodes given public key multikey into its type and raw bytes.
+///
+/// # Arguments
+///
+/// * `multikey` - multikey to decode, e.g. "z6Mk..."
+pub fn decode_public_key(multikey: &str) -> Result<(KeyType, Vec<u8>), Box<dyn std::error::Error>> {
+    let (code, key) = decode(multikey)?;
+    match KeyType::all()
+        .iter()
+        .find(|key_type| key_type.public_key_code() == code)
+    {
+        Some(key_type) => Ok((*key_type, key)),
+        None => Err(Box::from(format!(
+            r#"multikey "{}" is not a supported public key"#,
+            multikey
+        ))),
+    }
+}
+
+/// Decodes given secret key multikey into its type and raw bytes.
+///
+/// # Arguments
+///
+/// * `multikey` - multikey to decode
+pub fn decode_secret_key(multikey: &str) -> Result<(KeyType, Vec<u8>), Box<dyn std::error::Error>> {
+    let (code, key) = decode(multikey)?;
+    match KeyType::all()
+        .iter()
+        .find(|key_type| key_type.secret_key_code() == code)
+    {
+        Some(key_type) => Ok((*key_type, key)),
+        None => Err(Box::from("multikey is not a supported secret key")),
+    }
+}
+
+/// Hashes given value with SHA2-256 and encodes the result as base58btc multihash, as used for
+/// self-certifying identifiers and key commitments, e.g. "QmfGEUAc...".
+///
+/// # Arguments
+///
+/// * `value` - value to hash
+pub fn hash_base58btc(value: &[u8]) -> String {
+    let mut multihash = vec![0x12, 0x20];
+    multihash.extend_from_slice(&Sha256::digest(value));
+    bs58::encode(multihash).into_string()
+}
+
+/// Encodes given key with given multicodec code as multikey.
+pub(crate) fn encode(code: u64, key: &[u8]) -> String {
+    let mut bytes = Vec::new();
+    let mut remaining = code;
+    loop {
+        let byte = (remaining & 0x7f) as u8;
+        remaining >>= 7;
+        if remaining == 0 {
+            bytes.push(byte);
+            break;
+        }
+        bytes.push(byte | 0x80);
+    }
+    bytes.extend_from_slice(key);
+    format!("z{}", bs58::encode(bytes).into_string())
+}
+
+/// Decodes given multikey into its multicodec code and raw key bytes.
+pub(crate) fn decode(multikey: &str) -> Result<(u64, Vec<u8>), Box<dyn std::error::Error>> {
+    if !multikey.starts_with('z') {
+        return Err(Box::from(format!(
+            r#"multikey "{}" is not base58btc encoded"#,
+            multikey
+        )));
+    }
+    let bytes = bs58::decode(&multikey[1..])
+        .into_vec()
+        .map_err(|e| format!(r#"could not decode multikey "{}"; {}"#, multikey, e))?;
+    let mut code = 0u64;
+    for (index, byte) in bytes.iter().enumerate().take(9) {
+        code |= ((byte & 0x7f) as u64) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok((code, bytes[index + 1..].to_vec()));
+        }
+    }
+    Err(Box::from(format!(
+        r#"multikey "{}" has an invalid prefix"#,
+        multikey
+    )))
+}
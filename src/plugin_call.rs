@@ -0,0 +1,308 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{VadePlugin, VadePluginResultValue};
+use serde::{Deserialize, Serialize};
+
+/// Implements [`VadePlugin`] for a type by handing all calls over to its
+/// `async fn handle_call(&mut self, call: PluginCall)` function and key stores over to its
+/// `fn handle_key_store(&mut self, key_store: Rc<dyn KeyStore>)` function.
+///
+/// Functions with a signature deviating from `(method, options, payload)` are implemented
+/// explicitly.
+///
+/// # Arguments
+///
+/// `$type` - type to implement [`VadePlugin`] for
+macro_rules! impl_vade_plugin_with_handle_call {
+    ($type:ty) => {
+        impl_vade_plugin_with_handle_call!(
+            $type,
+            did_create(did_method),
+            did_update(did),
+            vc_issue(method),
+            vc_status_list_create(method),
+            vc_status_list_allocate(method),
+            vc_status_list_update(method),
+            vc_status_list_check(method),
+            vc_verify(method),
+            vc_zkp_create_credential_definition(method),
+            vc_zkp_create_credential_offer(method),
+            vc_zkp_create_credential_proposal(method),
+            vc_zkp_create_credential_schema(method),
+            vc_zkp_create_revocation_registry_definition(method),
+            vc_zkp_update_revocation_registry(method),
+            vc_zkp_issue_credential(method),
+            vc_zkp_finish_credential(method),
+            vc_zkp_present_proof(method),
+            vc_zkp_propose_proof(method),
+            vc_zkp_request_credential(method),
+            vc_zkp_request_proof(method),
+            vc_zkp_revoke_credential(method),
+            vc_zkp_verify_proof(method),
+            vp_create(method),
+            vp_verify(method),
+        );
+    };
+    ($type:ty, $($function:ident($method:ident)),* $(,)?) => {
+        #[async_trait::async_trait(?Send)]
+        impl crate::VadePlugin for $type {
+            $(
+                async fn $function(
+                    &mut self,
+                    $method: &str,
+                    options: &str,
+                    payload: &str,
+                ) -> Result<
+                    crate::VadePluginResultValue<Option<String>>,
+                    Box<dyn std::error::Error>,
+                > {
+                    self.handle_call(crate::PluginCall::new(
+                        stringify!($function),
+                        $method,
+                        None,
+                        options,
+                        payload,
+                    ))
+                    .await
+                }
+            )*
+
+            async fn did_resolve(
+                &mut self,
+                did: &str,
+            ) -> Result<crate::VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>>
+            {
+                self.handle_call(crate::PluginCall::new("did_resolve", did, None, "", ""))
+                    .await
+            }
+
+            async fn didcomm_receive(
+                &mut self,
+                options: &str,
+                payload: &str,
+            ) -> Result<crate::VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>>
+            {
+                self.handle_call(crate::PluginCall::new(
+                    "didcomm_receive",
+                    "",
+                    None,
+                    options,
+                    payload,
+                ))
+                .await
+            }
+
+            async fn didcomm_send(
+                &mut self,
+                options: &str,
+                payload: &str,
+            ) -> Result<crate::VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>>
+            {
+                self.handle_call(crate::PluginCall::new(
+                    "didcomm_send",
+                    "",
+                    None,
+                    options,
+                    payload,
+                ))
+                .await
+            }
+
+            async fn run_custom_function(
+                &mut self,
+                method: &str,
+                function: &str,
+                options: &str,
+                payload: &str,
+            ) -> Result<crate::VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>>
+            {
+                self.handle_call(crate::PluginCall::new(
+                    "run_custom_function",
+                    method,
+                    Some(function),
+                    options,
+                    payload,
+                ))
+                .await
+            }
+
+            fn set_key_store(&mut self, key_store: std::rc::Rc<dyn crate::KeyStore>) {
+                self.handle_key_store(key_store)
+            }
+        }
+    };
+}
+
+/// A call of a [`VadePlugin`] function with its arguments, e.g. as received by a
+/// [`MockVadePlugin`](https://docs.rs/vade/*/vade/struct.MockVadePlugin.html) or stored in a
+/// [`Cassette`](https://docs.rs/vade/*/vade/struct.Cassette.html).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginCall {
+    /// name of called [`VadePlugin`] function, e.g. "did_create"
+    pub function: String,
+    /// method or DID the function has been called with, empty for `didcomm_*` functions
+    pub method: String,
+    /// name of custom function for calls of `run_custom_function`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_function: Option<String>,
+    /// options argument of call
+    pub options: String,
+    /// payload argument of call
+    pub payload: String,
+}
+
+impl PluginCall {
+    /// Creates a new `PluginCall` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `function` - name of called function
+    /// * `method` - method or DID the function has been called with
+    /// * `custom_function` - name of custom function for calls of `run_custom_function`
+    /// * `options` - options argument of call
+    /// * `payload` - payload argument of call
+    pub fn new(
+        function: &str,
+        method: &str,
+        custom_function: Option<&str>,
+        options: &str,
+        payload: &str,
+    ) -> Self {
+        PluginCall {
+            function: function.to_string(),
+            method: method.to_string(),
+            custom_function: custom_function.map(|f| f.to_string()),
+            options: options.to_string(),
+            payload: payload.to_string(),
+        }
+    }
+
+    /// Calls the function described by this call on given plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `plugin` - plugin to call function on
+    pub async fn call(
+        &self,
+        plugin: &mut dyn VadePlugin,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        let method = self.method.as_str();
+        let options = self.options.as_str();
+        let payload = self.payload.as_str();
+        match self.function.as_str() {
+            "did_create" => plugin.did_create(method, options, payload).await,
+            "did_resolve" => plugin.did_resolve(method).await,
+            "did_update" => plugin.did_update(method, options, payload).await,
+            "didcomm_receive" => plugin.didcomm_receive(options, payload).await,
+            "didcomm_send" => plugin.didcomm_send(options, payload).await,
+            "run_custom_function" => {
+                let function = self.custom_function.as_deref().unwrap_or("");
+                plugin
+                    .run_custom_function(method, function, options, payload)
+                    .await
+            }
+            "vc_issue" => plugin.vc_issue(method, options, payload).await,
+            "vc_status_list_create" => plugin.vc_status_list_create(method, options, payload).await,
+            "vc_status_list_allocate" => {
+                plugin
+                    .vc_status_list_allocate(method, options, payload)
+                    .await
+            }
+            "vc_status_list_update" => plugin.vc_status_list_update(method, options, payload).await,
+            "vc_status_list_check" => plugin.vc_status_list_check(method, options, payload).await,
+            "vc_verify" => plugin.vc_verify(method, options, payload).await,
+            "vc_zkp_create_credential_definition" => {
+                plugin
+                    .vc_zkp_create_credential_definition(method, options, payload)
+                    .await
+            }
+            "vc_zkp_create_credential_offer" => {
+                plugin
+                    .vc_zkp_create_credential_offer(method, options, payload)
+                    .await
+            }
+            "vc_zkp_create_credential_proposal" => {
+                plugin
+                    .vc_zkp_create_credential_proposal(method, options, payload)
+                    .await
+            }
+            "vc_zkp_create_credential_schema" => {
+                plugin
+                    .vc_zkp_create_credential_schema(method, options, payload)
+                    .await
+            }
+            "vc_zkp_create_revocation_registry_definition" => {
+                plugin
+                    .vc_zkp_create_revocation_registry_definition(method, options, payload)
+                    .await
+            }
+            "vc_zkp_update_revocation_registry" => {
+                plugin
+                    .vc_zkp_update_revocation_registry(method, options, payload)
+                    .await
+            }
+            "vc_zkp_issue_credential" => {
+                plugin
+                    .vc_zkp_issue_credential(method, options, payload)
+                    .await
+            }
+            "vc_zkp_finish_credential" => {
+                plugin
+                    .vc_zkp_finish_credential(method, options, payload)
+                    .await
+            }
+            "vc_zkp_present_proof" => plugin.vc_zkp_present_proof(method, options, payload).await,
+            "vc_zkp_propose_proof" => plugin.vc_zkp_propose_proof(method, options, payload).await,
+            "vc_zkp_request_credential" => {
+                plugin
+                    .vc_zkp_request_credential(method, options, payload)
+                    .await
+            }
+            "vc_zkp_request_proof" => plugin.vc_zkp_request_proof(method, options, payload).await,
+            "vc_zkp_revoke_credential" => {
+                plugin
+                    .vc_zkp_revoke_credential(method, options, payload)
+                    .await
+            }
+            "vc_zkp_verify_proof" => plugin.vc_zkp_verify_proof(method, options, payload).await,
+            "vp_create" => plugin.vp_create(method, options, payload).await,
+            "vp_verify" => plugin.vp_verify(method, options, payload).await,
+            _ => Err(Box::from(format!(
+                r#"unknown plugin function "{}""#,
+                &self.function
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for PluginCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.custom_function {
+            Some(custom_function) => write!(
+                f,
+                r#"{}("{}", "{}", "{}", "{}")"#,
+                self.function, self.method, custom_function, self.options, self.payload,
+            ),
+            None => write!(
+                f,
+                r#"{}("{}", "{}", "{}")"#,
+                self.function, self.method, self.options, self.payload,
+            ),
+        }
+    }
+}
@@ -0,0 +1,708 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use super::parse_options;
+use crate::{
+    crypto,
+    data_integrity::{
+        self, decode_indexes, decode_pointers, decode_proof_value, encode_proof_value,
+        get_proof_config, hash_nquads, parse_document, PROOF_TYPE,
+    },
+    json_ld::{self, ContextLoader, DocumentLoader},
+    jwt,
+    key_store::KeyReference,
+    multikey::{self, KeyType},
+    rdfc,
+    selective_disclosure::{self, CanonicalGroups},
+    HostResolver, KeyStore, Vade, VadePlugin, VadePluginResultValue,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use ciborium::value::Value as CborValue;
+use rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, rc::Rc};
+use zkryptium::{
+    bbsplus::keys::BBSplusPublicKey,
+    schemes::{algorithms::BbsBls12381Sha256, generics::PoKSignature},
+};
+
+const METHOD: &str = "bbs";
+const CRYPTOSUITE: &str = "bbs-2023";
+const PROOF_PURPOSE: &str = "assertionMethod";
+/// CBOR tags of `bbs-2023` base and derived proof values
+const BASE_PROOF_TAG: u64 = 0x5d02;
+const DERIVED_PROOF_TAG: u64 = 0x5d03;
+const PUBLIC_KEY_LENGTH: usize = 96;
+const SIGNATURE_LENGTH: usize = 80;
+/// points and scalars of a BBS proof without undisclosed messages
+const MIN_PROOF_LENGTH: usize = 3 * 48 + 4 * 32;
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DefinitionOptions {
+    controller: Option<String>,
+    #[serde(default)]
+    export_secret_key: bool,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueOptions {
+    secret_key: Option<String>,
+    key_id: Option<String>,
+    verification_method: Option<String>,
+    created: Option<String>,
+    #[serde(default)]
+    mandatory_pointers: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PresentOptions {
+    #[serde(default)]
+    selective_pointers: Vec<String>,
+    nonce: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyOptions {
+    nonce: Option<String>,
+}
+
+/// Issues, presents and verifies zero-knowledge credentials secured with
+/// [Data Integrity](https://www.w3.org/TR/vc-data-integrity/) proofs of the cryptosuite
+/// `bbs-2023` ([VC-DI-BBS](https://www.w3.org/TR/vc-di-bbs/)), based on BBS signatures over
+/// BLS12-381. The plugin works offline and handles the `vc_zkp_*` functions for the method "bbs":
+///
+/// - `vc_zkp_create_credential_definition` generates a BLS12-381 G2 issuer key in the key store
+///   registered on `Vade` and returns JSON with the `credentialDefinition`, the Multikey
+///   verification method of the public key, and the `keyId` of the key. The verification method
+///   belongs to the `did:key` of the key, unless a DID is given as `controller` in options.
+///   Without a key store, the call fails, unless `exportSecretKey` is set to `true` in options:
+///   then the key is not stored at all and its `secretKey` is returned instead of a `keyId`, so
+///   the caller is responsible for keeping it secret.
+/// - `vc_zkp_issue_credential` takes the unsecured credential as payload and returns it with a
+///   base proof. Options give the `keyId` of the key in the key store, or the exported
+///   `secretKey`, the `verificationMethod` of the credential definition, `mandatoryPointers`
+///   listing the JSON pointers a holder always has to disclose and optionally `created`.
+/// - `vc_zkp_present_proof` takes the credential with base proof as payload and returns a
+///   derived credential, that only discloses the mandatory parts and the parts given as
+///   `selectivePointers` in options. Every presentation creates a new zero-knowledge proof, so
+///   presentations of the same credential cannot be linked by their proofs. A `nonce` of the
+///   verifier given in options is bound to the proof.
+/// - `vc_zkp_verify_proof` takes the derived credential as payload and returns JSON with
+///   `verified` and either the verified `credential` or an `error`. A `nonce` given in options has
+///   to match the proof.
+///
+/// Verification methods of `did:key` DIDs are resolved locally, all others with the plugins
+/// registered next to this plugin on the same `Vade` instance, or with the resolver given to
+/// [`with_resolver`](#method.with_resolver) instead. They have to belong to the issuer, which has
+/// to be a DID, and be authorized for "assertionMethod". JSON-LD contexts are never fetched from
+/// the network, only the contexts bundled with `vade` are available unless another loader is
+/// given to [`with_context_loader`](#method.with_context_loader).
+///
+/// # Example
+///
+/// ```
+/// use vade::{plugins::BbsVadePlugin, InMemoryKeyStore, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     vade.register_key_store(Box::from(InMemoryKeyStore::new()));
+///     vade.register_plugin(Box::from(BbsVadePlugin::new()));
+///     let results = vade.vc_zkp_create_credential_definition("bbs", "", "").await?;
+///     if !results.is_empty() {
+///         println!("credential definition: {}", results[0].as_ref().ok_or("result not found")?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct BbsVadePlugin {
+    resolver: Option<Vade>,
+    host_resolver: Option<Rc<HostResolver>>,
+    context_loader: Box<dyn DocumentLoader>,
+    key_store: Option<Rc<dyn KeyStore>>,
+}
+
+impl BbsVadePlugin {
+    /// Creates a new `BbsVadePlugin`, that resolves verification methods other than `did:key`
+    /// with the plugins registered next to it.
+    pub fn new() -> Self {
+        BbsVadePlugin {
+            resolver: None,
+            host_resolver: None,
+            context_loader: Box::new(ContextLoader::new()),
+            key_store: None,
+        }
+    }
+
+    /// Resolves verification methods with given `Vade` instance, that has DID resolver plugins
+    /// registered, instead of the plugins registered next to this plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolver` - `Vade` instance to call `did_resolve` on
+    pub fn with_resolver(mut self, resolver: Vade) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Loads the JSON-LD contexts of credentials with given loader instead of a
+    /// [`ContextLoader`](https://docs.rs/vade/*/vade/json_ld/struct.ContextLoader.html) serving
+    /// the bundled contexts.
+    ///
+    /// # Arguments
+    ///
+    /// * `context_loader` - loader of JSON-LD contexts
+    pub fn with_context_loader(mut self, context_loader: Box<dyn DocumentLoader>) -> Self {
+        self.context_loader = context_loader;
+        self
+    }
+
+    /// Verifies the derived proof of given credential.
+    async fn verify_credential(
+        &mut self,
+        document: &Map<String, Value>,
+        options: &VerifyOptions,
+    ) -> Result<(), String> {
+        let mut unsecured = document.clone();
+        let proof = match unsecured.remove("proof") {
+            Some(Value::Object(proof)) => proof,
+            _ => return Err("credential has no single proof".to_string()),
+        };
+        data_integrity::check_proof_options(&proof, PROOF_PURPOSE, None, None)?;
+        if proof.get("cryptosuite").and_then(Value::as_str) != Some(CRYPTOSUITE) {
+            return Err(format!(
+                r#"unsupported cryptosuite "{}""#,
+                proof
+                    .get("cryptosuite")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+            ));
+        }
+        let public_key = self
+            .resolve_public_key(&proof, data_integrity::get_issuer_did(document)?)
+            .await?;
+        verify_derived_proof(
+            &Value::Object(unsecured.clone()),
+            &get_proof_config(&unsecured, &proof),
+            proof
+                .get("proofValue")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+            &public_key,
+            options.nonce.as_deref(),
+            self.context_loader.as_ref(),
+        )
+        .map_err(|e| e.to_string())?;
+        jwt::check_validity(document)
+    }
+
+    /// Returns the BLS12-381 public key of the verification method of given proof, that has to
+    /// belong to given issuer DID and be authorized for assertions.
+    async fn resolve_public_key(
+        &mut self,
+        proof: &Map<String, Value>,
+        issuer: &str,
+    ) -> Result<Vec<u8>, String> {
+        let mut host = self.host_resolver.as_ref().and_then(|host| host.take());
+        let method = data_integrity::resolve_verification_method(
+            self.resolver.as_mut().or(host.as_mut()),
+            proof,
+            Some(issuer),
+        )
+        .await;
+        if let Some(host_resolver) = &self.host_resolver {
+            host_resolver.lend(host);
+        }
+        let method = method?;
+        let public_key = method["publicKeyMultibase"].as_str().ok_or_else(|| {
+            format!(
+                r#"verification method "{}" has no publicKeyMultibase"#,
+                method["id"].as_str().unwrap_or_default()
+            )
+        })?;
+        decode_public_key(public_key).map_err(|e| e.to_string())
+    }
+}
+
+impl Default for BbsVadePlugin {
+    fn default() -> Self {
+        BbsVadePlugin::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl VadePlugin for BbsVadePlugin {
+    /// Sets key store to generate issuer keys in and sign with.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_store` - key store to use
+    fn set_key_store(&mut self, key_store: Rc<dyn KeyStore>) {
+        self.key_store = Some(key_store);
+    }
+
+    /// Sets the resolver of the hosting `Vade` instance to resolve verification methods with.
+    ///
+    /// # Arguments
+    ///
+    /// * `host_resolver` - resolver using the other plugins of `Vade`
+    fn set_host_resolver(&mut self, host_resolver: Rc<HostResolver>) {
+        self.host_resolver = Some(host_resolver);
+    }
+
+    /// Generates a new BLS12-381 G2 key in the key store for issuing `bbs-2023` credentials.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "bbs", other methods are ignored
+    /// * `options` - JSON with optional `controller` DID of verification method and optional
+    ///   `exportSecretKey` to return the secret key instead of storing it
+    /// * `_payload` - unused
+    async fn vc_zkp_create_credential_definition(
+        &mut self,
+        method: &str,
+        options: &str,
+        _payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let options: DefinitionOptions = parse_options(options)?;
+        let (public_key, key) = match (options.export_secret_key, &self.key_store) {
+            (true, _) => {
+                let secret_key = crypto::generate_secret_key(KeyType::Bls12381G2)?;
+                let public_key = multikey::encode_public_key(
+                    KeyType::Bls12381G2,
+                    &crypto::get_public_key(KeyType::Bls12381G2, &secret_key)?,
+                );
+                let secret_key = multikey::encode_secret_key(KeyType::Bls12381G2, &secret_key);
+                (public_key, json!({ "secretKey": secret_key }))
+            }
+            (false, Some(key_store)) => {
+                let info = key_store.generate_key(KeyType::Bls12381G2).await?;
+                (info.public_key, json!({ "keyId": info.id }))
+            }
+            (false, None) => return Err(Box::from(
+                "no key store registered, set exportSecretKey to receive the secret key instead",
+            )),
+        };
+        let controller = options
+            .controller
+            .unwrap_or_else(|| format!("did:key:{}", public_key));
+        let mut definition = key;
+        definition["credentialDefinition"] = json!({
+            "id": format!("{}#{}", controller, public_key),
+            "type": "Multikey",
+            "controller": controller,
+            "publicKeyMultibase": public_key,
+        });
+        Ok(VadePluginResultValue::Success(Some(definition.to_string())))
+    }
+
+    /// Adds a `bbs-2023` base proof to given credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "bbs", other methods are ignored
+    /// * `options` - JSON with `keyId` or `secretKey` and optional `verificationMethod`,
+    ///   `mandatoryPointers` and `created`
+    /// * `payload` - credential to secure
+    async fn vc_zkp_issue_credential(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let options: IssueOptions = parse_options(options)?;
+        let key = KeyReference::from_options(
+            self.key_store.as_ref(),
+            options.secret_key.as_deref(),
+            options.key_id.as_deref(),
+        )
+        .await?;
+        let secured =
+            issue_credential(&key, options, payload, self.context_loader.as_ref()).await?;
+        Ok(VadePluginResultValue::Success(Some(secured)))
+    }
+
+    /// Derives a credential with a `bbs-2023` proof, that only discloses selected parts of given
+    /// credential with base proof.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "bbs", other methods are ignored
+    /// * `options` - JSON with `selectivePointers` to disclose and optional `nonce` of verifier
+    /// * `payload` - credential with base proof
+    async fn vc_zkp_present_proof(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let options: PresentOptions = parse_options(options)?;
+        let derived = derive_credential(options, payload, self.context_loader.as_ref())?;
+        Ok(VadePluginResultValue::Success(Some(derived)))
+    }
+
+    /// Verifies the `bbs-2023` derived proof of given credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "bbs", other methods are ignored
+    /// * `options` - JSON with optional `nonce` the proof has to be bound to
+    /// * `payload` - derived credential
+    async fn vc_zkp_verify_proof(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let options: VerifyOptions = parse_options(options)?;
+        let document = parse_document(payload)?;
+        let result = match self.verify_credential(&document, &options).await {
+            Ok(()) => json!({ "verified": true, "credential": document }),
+            Err(error) => json!({ "verified": false, "error": error }),
+        };
+        Ok(VadePluginResultValue::Success(Some(result.to_string())))
+    }
+}
+
+/// Signs every statement of given credential with BBS, with the hashes of the proof options and
+/// the mandatory statements as header.
+async fn issue_credential(
+    key: &KeyReference,
+    options: IssueOptions,
+    payload: &str,
+    loader: &dyn DocumentLoader,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let encoded_public_key = &key.info().public_key;
+    let public_key = decode_public_key(encoded_public_key)?;
+    let mut document = parse_document(payload)?;
+    if document.contains_key("proof") {
+        return Err(Box::from("credential is already secured"));
+    }
+
+    let verification_method = options
+        .verification_method
+        .unwrap_or_else(|| format!("did:key:{}#{}", encoded_public_key, encoded_public_key));
+    let created = options
+        .created
+        .unwrap_or_else(|| Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string());
+    let mut proof = Map::new();
+    proof.insert("type".to_string(), Value::from(PROOF_TYPE));
+    proof.insert("cryptosuite".to_string(), Value::from(CRYPTOSUITE));
+    proof.insert("created".to_string(), Value::from(created));
+    proof.insert(
+        "verificationMethod".to_string(),
+        Value::from(verification_method),
+    );
+    proof.insert("proofPurpose".to_string(), Value::from(PROOF_PURPOSE));
+
+    let proof_hash = Sha256::digest(json_ld::canonicalize(
+        &get_proof_config(&document, &proof),
+        loader,
+    )?);
+    let unsecured = Value::Object(document.clone());
+    let mut hmac_key = [0u8; 32];
+    OsRng.fill_bytes(&mut hmac_key);
+    let CanonicalGroups { groups, .. } = selective_disclosure::canonicalize_and_group(
+        &unsecured,
+        loader,
+        selective_disclosure::shuffled_label_map(&hmac_key, count_blank_nodes(&unsecured, loader)?),
+        &[&options.mandatory_pointers],
+    )?;
+    let mandatory = &groups[0];
+
+    let mut header = proof_hash.to_vec();
+    header.extend_from_slice(&hash_nquads(mandatory.matching.values()));
+    let messages: Vec<Vec<u8>> = mandatory
+        .non_matching
+        .values()
+        .map(|nquad| nquad.as_bytes().to_vec())
+        .collect();
+    let signature = key.sign_bbs(&header, &messages).await?;
+
+    let proof_value = encode_proof_value(
+        BASE_PROOF_TAG,
+        vec![
+            CborValue::Bytes(signature),
+            CborValue::Bytes(header),
+            CborValue::Bytes(public_key),
+            CborValue::Bytes(hmac_key.to_vec()),
+            to_cbor_pointers(&options.mandatory_pointers),
+        ],
+    )?;
+    proof.insert("proofValue".to_string(), Value::from(proof_value));
+    document.insert("proof".to_string(), Value::Object(proof));
+    Ok(serde_json::to_string(&document)?)
+}
+
+/// Derives a credential, that only discloses the mandatory and given selected parts of given
+/// credential, with a BBS proof of knowledge of the base signature.
+fn derive_credential(
+    options: PresentOptions,
+    payload: &str,
+    loader: &dyn DocumentLoader,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut document = parse_document(payload)?;
+    let mut proof = match document.remove("proof") {
+        Some(Value::Object(proof))
+            if proof.get("cryptosuite").and_then(Value::as_str) == Some(CRYPTOSUITE) =>
+        {
+            proof
+        }
+        _ => return Err(Box::from("credential has no bbs-2023 proof")),
+    };
+    let components = decode_proof_value(
+        proof
+            .get("proofValue")
+            .and_then(Value::as_str)
+            .unwrap_or_default(),
+        BASE_PROOF_TAG,
+    )?;
+    let (signature, header, public_key, hmac_key, mandatory_pointers) = match &components[..] {
+        [CborValue::Bytes(signature), CborValue::Bytes(header), CborValue::Bytes(public_key), CborValue::Bytes(hmac_key), pointers] => {
+            (
+                signature,
+                header,
+                public_key,
+                hmac_key,
+                decode_pointers(pointers)?,
+            )
+        }
+        _ => return Err(Box::from("invalid bbs-2023 base proof value")),
+    };
+    if signature.len() != SIGNATURE_LENGTH {
+        return Err(Box::from("invalid BBS signature"));
+    }
+    let public_key = parse_public_key(public_key)?;
+    let mut combined_pointers = mandatory_pointers.clone();
+    combined_pointers.extend_from_slice(&options.selective_pointers);
+
+    let document = Value::Object(document);
+    let CanonicalGroups { groups, label_map } = selective_disclosure::canonicalize_and_group(
+        &document,
+        loader,
+        selective_disclosure::shuffled_label_map(hmac_key, count_blank_nodes(&document, loader)?),
+        &[&mandatory_pointers, &combined_pointers],
+    )?;
+    let (mandatory, combined) = (&groups[0], &groups[1]);
+
+    // positions of mandatory statements within the disclosed statements and of the disclosed
+    // non-mandatory statements within the signed messages
+    let mandatory_indexes =
+        selective_disclosure::relative_indexes(&mandatory.matching, &combined.matching);
+    let selective_indexes =
+        selective_disclosure::relative_indexes(&combined.matching, &mandatory.non_matching);
+    let messages: Vec<Vec<u8>> = mandatory
+        .non_matching
+        .values()
+        .map(|nquad| nquad.as_bytes().to_vec())
+        .collect();
+    let presentation_header = options.nonce.unwrap_or_default().into_bytes();
+    let bbs_proof = PoKSignature::<BbsBls12381Sha256>::proof_gen(
+        &public_key,
+        signature,
+        Some(header),
+        Some(&presentation_header),
+        Some(&messages),
+        Some(&selective_indexes),
+    )?;
+
+    let verifier_label_map =
+        selective_disclosure::create_verifier_label_map(&combined.quads, &label_map)?;
+    let mut compressed_labels: Vec<(u64, u64)> = Vec::new();
+    for (canonical, label) in verifier_label_map.iter() {
+        let index = canonical
+            .strip_prefix("c14n")
+            .and_then(|index| index.parse().ok())
+            .ok_or("invalid canonical label")?;
+        let label = label
+            .strip_prefix('b')
+            .and_then(|label| label.parse().ok())
+            .ok_or("invalid blank node label")?;
+        compressed_labels.push((index, label));
+    }
+    compressed_labels.sort_unstable();
+
+    let mut revealed = selective_disclosure::select_json_ld(&combined_pointers, &document)?
+        .ok_or("no pointers to disclose given")?;
+    proof.insert(
+        "proofValue".to_string(),
+        Value::from(encode_proof_value(
+            DERIVED_PROOF_TAG,
+            vec![
+                CborValue::Bytes(bbs_proof.to_bytes()),
+                CborValue::Map(
+                    compressed_labels
+                        .into_iter()
+                        .map(|(index, label)| (CborValue::from(index), CborValue::from(label)))
+                        .collect(),
+                ),
+                to_cbor_indexes(&mandatory_indexes),
+                to_cbor_indexes(&selective_indexes),
+                CborValue::Bytes(presentation_header),
+            ],
+        )?),
+    );
+    revealed["proof"] = Value::Object(proof);
+    Ok(serde_json::to_string(&revealed)?)
+}
+
+/// Verifies a `bbs-2023` derived proof of given document.
+fn verify_derived_proof(
+    document: &Value,
+    proof_config: &Value,
+    proof_value: &str,
+    public_key: &[u8],
+    nonce: Option<&str>,
+    loader: &dyn DocumentLoader,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if decode_proof_value(proof_value, BASE_PROOF_TAG).is_ok() {
+        return Err(Box::from(
+            "bbs-2023 base proofs have to be presented before verification",
+        ));
+    }
+    let components = decode_proof_value(proof_value, DERIVED_PROOF_TAG)?;
+    let (bbs_proof, labels, mandatory_indexes, selective_indexes, presentation_header) =
+        match &components[..] {
+            [CborValue::Bytes(bbs_proof), CborValue::Map(labels), mandatory_indexes, selective_indexes, CborValue::Bytes(presentation_header)] => {
+                (
+                    bbs_proof,
+                    labels,
+                    decode_indexes(mandatory_indexes)?,
+                    decode_indexes(selective_indexes)?,
+                    presentation_header,
+                )
+            }
+            _ => return Err(Box::from("invalid bbs-2023 derived proof value")),
+        };
+    if let Some(nonce) = nonce {
+        if nonce.as_bytes() != presentation_header.as_slice() {
+            return Err(Box::from("nonce does not match"));
+        }
+    }
+    let mut label_map = HashMap::new();
+    for (index, label) in labels.iter() {
+        match (index.as_integer(), label.as_integer()) {
+            (Some(index), Some(label)) => label_map.insert(
+                format!("c14n{}", i128::from(index)),
+                format!("b{}", i128::from(label)),
+            ),
+            _ => return Err(Box::from("invalid label map")),
+        };
+    }
+
+    let (_, nquads) = selective_disclosure::label_replacement_canonicalize(
+        &json_ld::to_rdf(document, loader)?,
+        |canonical| {
+            label_map
+                .get(canonical)
+                .cloned()
+                .unwrap_or_else(|| canonical.to_string())
+        },
+    )?;
+    let mut mandatory = Vec::new();
+    let mut disclosed_messages = Vec::new();
+    for (index, nquad) in nquads.iter().enumerate() {
+        if mandatory_indexes.contains(&index) {
+            mandatory.push(nquad);
+        } else {
+            disclosed_messages.push(nquad.as_bytes().to_vec());
+        }
+    }
+    if disclosed_messages.len() != selective_indexes.len() {
+        return Err(Box::from(
+            "selective indexes do not match disclosed statements",
+        ));
+    }
+
+    let mut header = Sha256::digest(json_ld::canonicalize(proof_config, loader)?).to_vec();
+    header.extend_from_slice(&hash_nquads(mandatory.into_iter()));
+    if bbs_proof.len() < MIN_PROOF_LENGTH
+        || !(bbs_proof.len() - MIN_PROOF_LENGTH).is_multiple_of(32)
+    {
+        return Err(Box::from("invalid BBS proof"));
+    }
+    PoKSignature::<BbsBls12381Sha256>::from_bytes(bbs_proof)?
+        .proof_verify(
+            &parse_public_key(public_key)?,
+            Some(&disclosed_messages),
+            Some(&selective_indexes),
+            Some(&header),
+            Some(presentation_header),
+        )
+        .map_err(|_| Box::from("invalid BBS proof"))
+}
+
+/// Returns the number of blank nodes of given JSON-LD document.
+fn count_blank_nodes(
+    document: &Value,
+    loader: &dyn DocumentLoader,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let (_, canonical_ids) = rdfc::canonicalize(&json_ld::to_rdf(document, loader)?)?;
+    Ok(canonical_ids.len())
+}
+
+fn decode_public_key(multikey: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match multikey::decode_public_key(multikey)? {
+        (KeyType::Bls12381G2, public_key) => Ok(public_key),
+        _ => Err(Box::from(format!(
+            r#"multikey "{}" is no BLS12-381 G2 public key"#,
+            multikey
+        ))),
+    }
+}
+
+fn parse_public_key(public_key: &[u8]) -> Result<BBSplusPublicKey, Box<dyn std::error::Error>> {
+    if public_key.len() != PUBLIC_KEY_LENGTH {
+        return Err(Box::from("invalid BLS12-381 G2 public key"));
+    }
+    Ok(BBSplusPublicKey::from_bytes(public_key)?)
+}
+
+fn to_cbor_pointers(pointers: &[String]) -> CborValue {
+    CborValue::Array(
+        pointers
+            .iter()
+            .map(|pointer| CborValue::from(pointer.as_str()))
+            .collect(),
+    )
+}
+
+fn to_cbor_indexes(indexes: &[usize]) -> CborValue {
+    CborValue::Array(
+        indexes
+            .iter()
+            .map(|index| CborValue::from(*index as u64))
+            .collect(),
+    )
+}
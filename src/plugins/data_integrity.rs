@@ -0,0 +1,913 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use super::parse_options;
+use crate::{
+    crypto,
+    data_integrity::{
+        self, decode_proof_value, encode_proof_value, get_proof_config, hash_nquads,
+        parse_document, PROOF_TYPE,
+    },
+    jcs,
+    json_ld::{self, ContextLoader, DocumentLoader},
+    jwt,
+    key_store::KeyReference,
+    multikey::{self, KeyType},
+    presentation::{self, PresentationOptions, Presenter, UsedChallenges},
+    selective_disclosure::{self, CanonicalGroups},
+    verification_method, ChallengeStore, HostResolver, KeyStore, Vade, VadePlugin,
+    VadePluginResultValue,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use ciborium::value::Value as CborValue;
+use rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, rc::Rc};
+
+const METHOD: &str = "data-integrity";
+const DERIVE_PROOF_FUNCTION: &str = "derive_proof";
+/// CBOR tags of `ecdsa-sd-2023` base and derived proof values
+const SD_BASE_PROOF_TAG: u64 = 0x5d00;
+const SD_DERIVED_PROOF_TAG: u64 = 0x5d01;
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProofOptions {
+    cryptosuite: Option<String>,
+    secret_key: Option<String>,
+    key_id: Option<String>,
+    verification_method: Option<String>,
+    proof_purpose: Option<String>,
+    created: Option<String>,
+    expires: Option<String>,
+    challenge: Option<String>,
+    domain: Option<String>,
+    holder: Option<String>,
+    #[serde(default)]
+    mandatory_pointers: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeriveOptions {
+    #[serde(default)]
+    selective_pointers: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Cryptosuite {
+    EddsaRdfc2022,
+    EddsaJcs2022,
+    EcdsaRdfc2019,
+    EcdsaSd2023,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Credential,
+    Presentation,
+}
+
+impl Cryptosuite {
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "eddsa-rdfc-2022" => Ok(Cryptosuite::EddsaRdfc2022),
+            "eddsa-jcs-2022" => Ok(Cryptosuite::EddsaJcs2022),
+            "ecdsa-rdfc-2019" => Ok(Cryptosuite::EcdsaRdfc2019),
+            "ecdsa-sd-2023" => Ok(Cryptosuite::EcdsaSd2023),
+            _ => Err(format!(r#"unsupported cryptosuite "{}""#, name)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Cryptosuite::EddsaRdfc2022 => "eddsa-rdfc-2022",
+            Cryptosuite::EddsaJcs2022 => "eddsa-jcs-2022",
+            Cryptosuite::EcdsaRdfc2019 => "ecdsa-rdfc-2019",
+            Cryptosuite::EcdsaSd2023 => "ecdsa-sd-2023",
+        }
+    }
+
+    fn key_type(&self) -> KeyType {
+        match self {
+            Cryptosuite::EddsaRdfc2022 | Cryptosuite::EddsaJcs2022 => KeyType::Ed25519,
+            Cryptosuite::EcdsaRdfc2019 | Cryptosuite::EcdsaSd2023 => KeyType::P256,
+        }
+    }
+
+    /// Returns the cryptosuite used for keys of given type if none is given in options.
+    fn default_for(key_type: KeyType) -> Result<Self, String> {
+        match key_type {
+            KeyType::Ed25519 => Ok(Cryptosuite::EddsaRdfc2022),
+            KeyType::P256 => Ok(Cryptosuite::EcdsaRdfc2019),
+            _ => Err(format!(
+                "{:?} keys are not supported by any cryptosuite",
+                key_type
+            )),
+        }
+    }
+
+    /// Hashes the data a proof of this cryptosuite signs. Not used for `ecdsa-sd-2023`, that
+    /// signs the parts of the document separately.
+    fn hash_data(
+        &self,
+        proof_config: &Value,
+        document: &Value,
+        loader: &dyn DocumentLoader,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let canonicalize = |value: &Value| -> Result<String, Box<dyn std::error::Error>> {
+            match self {
+                Cryptosuite::EddsaJcs2022 => Ok(jcs::canonicalize(value)),
+                _ => json_ld::canonicalize(value, loader),
+            }
+        };
+        let mut data = Sha256::digest(canonicalize(proof_config)?.as_bytes()).to_vec();
+        data.extend_from_slice(&Sha256::digest(canonicalize(document)?.as_bytes()));
+        Ok(data)
+    }
+}
+
+impl Kind {
+    fn proof_purpose(&self) -> &'static str {
+        match self {
+            Kind::Credential => "assertionMethod",
+            Kind::Presentation => "authentication",
+        }
+    }
+}
+
+/// Creates and verifies [Data Integrity](https://www.w3.org/TR/vc-data-integrity/) proofs of
+/// type `DataIntegrityProof` with the cryptosuites `eddsa-rdfc-2022`, `eddsa-jcs-2022`
+/// ([VC-DI-EdDSA](https://www.w3.org/TR/vc-di-eddsa/)), `ecdsa-rdfc-2019` and `ecdsa-sd-2023`
+/// ([VC-DI-ECDSA](https://www.w3.org/TR/vc-di-ecdsa/)) with P-256 keys.
+///
+/// The plugin handles `vc_issue`, `vc_verify`, `vp_create` and `vp_verify` for the method
+/// "data-integrity":
+///
+/// - `vc_issue` and `vp_create` take the unsecured credential or presentation as payload and
+///   return it with a proof added, existing proofs are kept as proof set. `vp_create` also takes
+///   a single credential or an array of credentials, that are wrapped into a presentation of the
+///   `holder` given in options, which defaults to the DID of the verification method. Keys are
+///   given in options as `secretKey` multikey or as `keyId` of a key in the registered
+///   [`KeyStore`](https://docs.rs/vade/*/vade/trait.KeyStore.html). `cryptosuite` defaults to
+///   `eddsa-rdfc-2022` for Ed25519 and `ecdsa-rdfc-2019` for P-256 keys, `verificationMethod` to
+///   the `did:key` of the signing key and `proofPurpose` to "assertionMethod" for credentials and
+///   "authentication" for presentations. `created`, `expires`, `challenge` and `domain` are added
+///   to the proof if given, `mandatoryPointers` lists the JSON pointers an `ecdsa-sd-2023` holder
+///   always has to disclose.
+/// - `ecdsa-sd-2023` base proofs are turned into verifiable derived proofs by the holder with the
+///   custom function "derive_proof", whose options give the `selectivePointers` to disclose.
+/// - `vc_verify` and `vp_verify` take the secured credential or presentation as payload and return
+///   JSON with `verified` and either the verified `credential` or `presentation` (and its
+///   `credentials`) or an `error`. `challenge` and `domain` given in options have to match the
+///   proof. Presentations have to be signed by their `holder` and every credential has to be
+///   bound to the holder by its subject `id` or `cnf` key, unless `holderBinding` is set to
+///   `false`. A challenge bound to a proof is only accepted once and `maxAge` limits the age of
+///   the presentation in seconds. Used challenges are kept in memory per plugin instance, unless a
+///   shared store is given to [`with_challenge_store`](#method.with_challenge_store).
+///
+/// Verification methods are resolved with the plugins registered next to this plugin on the same
+/// `Vade` instance, or with the resolver given to [`with_resolver`](#method.with_resolver)
+/// instead, `did:key` DIDs are resolved locally. The
+/// verification method has to be authorized for the proof purpose in its DID document and belong
+/// to the issuer of a credential or the holder of a presentation, which have to be DIDs. JSON-LD
+/// contexts are never fetched from the network, only the contexts bundled with `vade` are
+/// available unless another loader is given to [`with_context_loader`](#method.with_context_loader).
+/// Terms not defined by the contexts are rejected.
+///
+/// # Example
+///
+/// ```
+/// use vade::{plugins::DataIntegrityVadePlugin, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(DataIntegrityVadePlugin::new()));
+///     let results = vade.vc_verify("data-integrity", "", r#"{"proof": {}}"#).await?;
+///     if !results.is_empty() {
+///         println!("verification result: {}", results[0].as_ref().ok_or("result not found")?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct DataIntegrityVadePlugin {
+    key_store: Option<Rc<dyn KeyStore>>,
+    resolver: Option<Vade>,
+    host_resolver: Option<Rc<HostResolver>>,
+    context_loader: Box<dyn DocumentLoader>,
+    used_challenges: UsedChallenges,
+}
+
+impl DataIntegrityVadePlugin {
+    /// Creates a new `DataIntegrityVadePlugin`, that resolves verification methods other than
+    /// `did:key` with the plugins registered next to it.
+    pub fn new() -> Self {
+        DataIntegrityVadePlugin {
+            key_store: None,
+            resolver: None,
+            host_resolver: None,
+            context_loader: Box::new(ContextLoader::new()),
+            used_challenges: UsedChallenges::default(),
+        }
+    }
+
+    /// Keeps the challenges of verified presentations in given store instead of the plugin's
+    /// own memory, e.g. to share them with other plugins or instances verifying presentations.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - store to check and record used challenges with
+    pub fn with_challenge_store(mut self, store: Rc<dyn ChallengeStore>) -> Self {
+        self.used_challenges = UsedChallenges::new(store);
+        self
+    }
+
+    /// Resolves verification methods with given `Vade` instance, that has DID resolver plugins
+    /// registered, instead of the plugins registered next to this plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolver` - `Vade` instance to call `did_resolve` on
+    pub fn with_resolver(mut self, resolver: Vade) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Loads the JSON-LD contexts of documents with given loader instead of a
+    /// [`ContextLoader`](https://docs.rs/vade/*/vade/json_ld/struct.ContextLoader.html) serving
+    /// the bundled contexts.
+    ///
+    /// # Arguments
+    ///
+    /// * `context_loader` - loader of JSON-LD contexts
+    pub fn with_context_loader(mut self, context_loader: Box<dyn DocumentLoader>) -> Self {
+        self.context_loader = context_loader;
+        self
+    }
+
+    async fn add_proof(
+        &self,
+        kind: Kind,
+        options: &str,
+        payload: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let options: ProofOptions = parse_options(options)?;
+        let key = KeyReference::from_options(
+            self.key_store.as_ref(),
+            options.secret_key.as_deref(),
+            options.key_id.as_deref(),
+        )
+        .await?;
+        let key_type = key.info().key_type;
+        let cryptosuite = match &options.cryptosuite {
+            Some(name) => Cryptosuite::from_name(name)?,
+            None => Cryptosuite::default_for(key_type)?,
+        };
+        if key_type != cryptosuite.key_type() {
+            return Err(Box::from(format!(
+                "cryptosuite {} requires {:?} keys",
+                cryptosuite.name(),
+                cryptosuite.key_type()
+            )));
+        }
+        let public_key = &key.info().public_key;
+        let verification_method = options
+            .verification_method
+            .unwrap_or_else(|| format!("did:key:{}#{}", public_key, public_key));
+        let mut document = match kind {
+            Kind::Credential => parse_document(payload)?,
+            Kind::Presentation => {
+                let holder = match &options.holder {
+                    Some(holder) => holder.as_str(),
+                    None => verification_method.split('#').next().unwrap_or_default(),
+                };
+                presentation::wrap_credentials(payload, holder)?
+            }
+        };
+        let existing = document.remove("proof");
+
+        let mut proof = Map::new();
+        proof.insert("type".to_string(), Value::from(PROOF_TYPE));
+        proof.insert("cryptosuite".to_string(), Value::from(cryptosuite.name()));
+        let created = options
+            .created
+            .unwrap_or_else(|| Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string());
+        proof.insert("created".to_string(), Value::from(created));
+        proof.insert(
+            "verificationMethod".to_string(),
+            Value::from(verification_method),
+        );
+        let proof_purpose = options
+            .proof_purpose
+            .unwrap_or_else(|| kind.proof_purpose().to_string());
+        proof.insert("proofPurpose".to_string(), Value::from(proof_purpose));
+        let optional = [
+            ("expires", options.expires),
+            ("challenge", options.challenge),
+            ("domain", options.domain),
+        ];
+        for (name, value) in optional.iter() {
+            if let Some(value) = value {
+                proof.insert(name.to_string(), Value::from(value.as_str()));
+            }
+        }
+
+        let proof_config = get_proof_config(&document, &proof);
+        let unsecured = Value::Object(document.clone());
+        let proof_value = match cryptosuite {
+            Cryptosuite::EcdsaSd2023 => {
+                create_base_proof_value(
+                    &key,
+                    &unsecured,
+                    &proof_config,
+                    &options.mandatory_pointers,
+                    self.context_loader.as_ref(),
+                )
+                .await?
+            }
+            _ => {
+                let signature = key
+                    .sign(&cryptosuite.hash_data(
+                        &proof_config,
+                        &unsecured,
+                        self.context_loader.as_ref(),
+                    )?)
+                    .await?;
+                format!("z{}", bs58::encode(signature).into_string())
+            }
+        };
+        proof.insert("proofValue".to_string(), Value::from(proof_value));
+
+        let proof = match existing {
+            None => Value::Object(proof),
+            Some(Value::Array(mut proofs)) => {
+                proofs.push(Value::Object(proof));
+                Value::Array(proofs)
+            }
+            Some(existing) => Value::Array(vec![existing, Value::Object(proof)]),
+        };
+        document.insert("proof".to_string(), proof);
+        Ok(serde_json::to_string(&document)?)
+    }
+
+    /// Verifies all proofs of given document and returns their signers.
+    async fn verify_document(
+        &mut self,
+        kind: Kind,
+        document: &Map<String, Value>,
+        options: &PresentationOptions,
+    ) -> Result<Vec<Presenter>, String> {
+        let proofs = match document.get("proof") {
+            Some(Value::Array(proofs)) if !proofs.is_empty() => proofs.clone(),
+            Some(proof @ Value::Object(_)) => vec![proof.clone()],
+            _ => return Err("document has no proof".to_string()),
+        };
+        let mut unsecured = document.clone();
+        unsecured.remove("proof");
+        let mut signers = Vec::new();
+        for (index, proof) in proofs.iter().enumerate() {
+            let signer = self
+                .verify_proof(kind, &unsecured, proof, options)
+                .await
+                .map_err(|e| format!("proof {} is invalid; {}", index, e))?;
+            signers.push(signer);
+        }
+        jwt::check_validity(document)?;
+        Ok(signers)
+    }
+
+    async fn verify_proof(
+        &mut self,
+        kind: Kind,
+        unsecured: &Map<String, Value>,
+        proof: &Value,
+        options: &PresentationOptions,
+    ) -> Result<Presenter, String> {
+        let proof = proof.as_object().ok_or("proof is no JSON object")?;
+        let get = |name: &str| proof.get(name).and_then(|value| value.as_str());
+        data_integrity::check_proof_options(
+            proof,
+            kind.proof_purpose(),
+            options.challenge.as_deref(),
+            options.domain.as_deref(),
+        )?;
+        let cryptosuite = Cryptosuite::from_name(get("cryptosuite").unwrap_or_default())?;
+        let controller = match kind {
+            Kind::Credential => Some(data_integrity::get_issuer_did(unsecured)?),
+            Kind::Presentation => data_integrity::get_holder_did(unsecured)?,
+        };
+        let mut host = self.host_resolver.as_ref().and_then(|host| host.take());
+        let method = data_integrity::resolve_verification_method(
+            self.resolver.as_mut().or(host.as_mut()),
+            proof,
+            controller,
+        )
+        .await;
+        if let Some(host_resolver) = &self.host_resolver {
+            host_resolver.lend(host);
+        }
+        let method = method?;
+        let (key_type, public_key) =
+            verification_method::get_public_key(&method).map_err(|e| e.to_string())?;
+        if key_type != cryptosuite.key_type() {
+            return Err(format!(
+                "cryptosuite {} requires {:?} keys",
+                cryptosuite.name(),
+                cryptosuite.key_type()
+            ));
+        }
+
+        let proof_config = get_proof_config(unsecured, proof);
+        let unsecured = Value::Object(unsecured.clone());
+        let proof_value = get("proofValue").ok_or("proof has no proofValue")?;
+        if cryptosuite == Cryptosuite::EcdsaSd2023 {
+            verify_derived_proof(
+                &unsecured,
+                &proof_config,
+                proof_value,
+                &public_key,
+                self.context_loader.as_ref(),
+            )
+            .map_err(|e| e.to_string())?;
+        } else {
+            let signature = proof_value
+                .strip_prefix('z')
+                .and_then(|value| bs58::decode(value).into_vec().ok())
+                .ok_or("invalid proofValue")?;
+            let data = cryptosuite
+                .hash_data(&proof_config, &unsecured, self.context_loader.as_ref())
+                .map_err(|e| e.to_string())?;
+            if crypto::verify(key_type, &public_key, &data, &signature).ok() != Some(true) {
+                return Err("invalid signature".to_string());
+            }
+        }
+        Ok(Presenter {
+            verification_method: get("verificationMethod").unwrap_or_default().to_string(),
+            key_type,
+            public_key,
+            created: jwt::get_timestamp(proof.get("created"))?,
+            challenge: get("challenge").map(String::from),
+        })
+    }
+
+    async fn verify(
+        &mut self,
+        kind: Kind,
+        options: &str,
+        payload: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let options: PresentationOptions = parse_options(options)?;
+        let document = parse_document(payload)?;
+        let result = match kind {
+            Kind::Credential => self
+                .verify_document(kind, &document, &options)
+                .await
+                .map(|_| serde_json::json!({ "verified": true, "credential": document })),
+            Kind::Presentation => self.verify_presentation(&document, &options).await,
+        };
+        Ok(match result {
+            Ok(result) => result,
+            Err(error) => serde_json::json!({ "verified": false, "error": error }),
+        })
+    }
+
+    /// Verifies given presentation, the credentials in it, their binding to the holder and that
+    /// the challenge has not been used before.
+    async fn verify_presentation(
+        &mut self,
+        document: &Map<String, Value>,
+        options: &PresentationOptions,
+    ) -> Result<Value, String> {
+        let presenters = self
+            .verify_document(Kind::Presentation, document, options)
+            .await?;
+        self.used_challenges.check(&presenters)?;
+        presentation::check_freshness(&presenters, options.max_age)?;
+
+        let credentials = match document.get("verifiableCredential") {
+            Some(Value::Array(credentials)) => credentials.clone(),
+            Some(credential) => vec![credential.clone()],
+            None => Vec::new(),
+        };
+        for (index, credential) in credentials.iter().enumerate() {
+            let result = match credential.as_object() {
+                Some(credential) => self
+                    .verify_document(
+                        Kind::Credential,
+                        credential,
+                        &PresentationOptions::default(),
+                    )
+                    .await
+                    .map(|_| ()),
+                None => Err("credential is no JSON object".to_string()),
+            };
+            result.map_err(|error| format!("credential {} is invalid; {}", index, error))?;
+        }
+        if options.holder_binding != Some(false) {
+            let holder = match document.get("holder") {
+                Some(Value::Object(holder)) => holder.get("id").and_then(Value::as_str),
+                Some(holder) => holder.as_str(),
+                None => None,
+            };
+            presentation::check_holder_binding(holder, &presenters, &credentials)?;
+        }
+        self.used_challenges.insert(&presenters, options.max_age)?;
+
+        Ok(serde_json::json!({
+            "verified": true,
+            "presentation": document,
+            "credentials": credentials,
+        }))
+    }
+}
+
+impl Default for DataIntegrityVadePlugin {
+    fn default() -> Self {
+        DataIntegrityVadePlugin::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl VadePlugin for DataIntegrityVadePlugin {
+    /// Keeps given key store to sign with keys given as `keyId` in options.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_store` - key store to use
+    fn set_key_store(&mut self, key_store: Rc<dyn KeyStore>) {
+        self.key_store = Some(key_store);
+    }
+
+    /// Sets the resolver of the hosting `Vade` instance to resolve verification methods with.
+    ///
+    /// # Arguments
+    ///
+    /// * `host_resolver` - resolver using the other plugins of `Vade`
+    fn set_host_resolver(&mut self, host_resolver: Rc<HostResolver>) {
+        self.host_resolver = Some(host_resolver);
+    }
+
+    /// Adds a Data Integrity proof to given credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "data-integrity", other methods are ignored
+    /// * `options` - JSON with `secretKey` or `keyId` and optional proof options
+    /// * `payload` - credential to secure
+    async fn vc_issue(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let secured = self.add_proof(Kind::Credential, options, payload).await?;
+        Ok(VadePluginResultValue::Success(Some(secured)))
+    }
+
+    /// Verifies the Data Integrity proofs of given credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "data-integrity", other methods are ignored
+    /// * `options` - JSON with optional `challenge` and `domain`
+    /// * `payload` - secured credential
+    async fn vc_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let result = self.verify(Kind::Credential, options, payload).await?;
+        Ok(VadePluginResultValue::Success(Some(result.to_string())))
+    }
+
+    /// Adds a Data Integrity proof to given presentation.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "data-integrity", other methods are ignored
+    /// * `options` - JSON with `secretKey` or `keyId` of holder, optional `holder`, `challenge`,
+    ///   `domain` and other proof options
+    /// * `payload` - presentation to secure or credentials to present
+    async fn vp_create(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let secured = self.add_proof(Kind::Presentation, options, payload).await?;
+        Ok(VadePluginResultValue::Success(Some(secured)))
+    }
+
+    /// Verifies the Data Integrity proofs of given presentation and the credentials in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "data-integrity", other methods are ignored
+    /// * `options` - JSON with optional `challenge`, `domain`, `maxAge` in seconds and
+    ///   `holderBinding`, challenges are only accepted once per challenge store
+    /// * `payload` - secured presentation
+    async fn vp_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let result = self.verify(Kind::Presentation, options, payload).await?;
+        Ok(VadePluginResultValue::Success(Some(result.to_string())))
+    }
+
+    /// Derives a credential disclosing only selected claims from a credential with an
+    /// `ecdsa-sd-2023` base proof with function "derive_proof".
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "data-integrity", other methods are ignored
+    /// * `function` - "derive_proof", other functions are ignored
+    /// * `options` - JSON with `selectivePointers` to disclose
+    /// * `payload` - credential with base proof
+    async fn run_custom_function(
+        &mut self,
+        method: &str,
+        function: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != METHOD || function != DERIVE_PROOF_FUNCTION {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let options: DeriveOptions = parse_options(options)?;
+        let derived = derive_credential(
+            &options.selective_pointers,
+            payload,
+            self.context_loader.as_ref(),
+        )?;
+        Ok(VadePluginResultValue::Success(Some(derived)))
+    }
+}
+
+/// Creates the `ecdsa-sd-2023` base proof value, that signs every statement not mandatory to
+/// disclose with a new ephemeral key and the mandatory statements with the issuer key.
+async fn create_base_proof_value(
+    key: &KeyReference,
+    document: &Value,
+    proof_config: &Value,
+    mandatory_pointers: &[String],
+    loader: &dyn DocumentLoader,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let proof_hash = Sha256::digest(json_ld::canonicalize(proof_config, loader)?.as_bytes());
+    let mut hmac_key = [0u8; 32];
+    OsRng.fill_bytes(&mut hmac_key);
+    let CanonicalGroups { groups, .. } = selective_disclosure::canonicalize_and_group(
+        document,
+        loader,
+        selective_disclosure::hmac_label_map(&hmac_key),
+        &[mandatory_pointers],
+    )?;
+    let mandatory = &groups[0];
+
+    let ephemeral_key = crypto::generate_secret_key(KeyType::P256)?;
+    let ephemeral_public_key = get_multikey_bytes(&multikey::encode_public_key(
+        KeyType::P256,
+        &crypto::get_public_key(KeyType::P256, &ephemeral_key)?,
+    ))?;
+    let mut signatures = Vec::new();
+    for nquad in mandatory.non_matching.values() {
+        let signature = crypto::sign(KeyType::P256, &ephemeral_key, nquad.as_bytes())?;
+        signatures.push(CborValue::Bytes(signature));
+    }
+    let mut data = proof_hash.to_vec();
+    data.extend_from_slice(&ephemeral_public_key);
+    data.extend_from_slice(&hash_nquads(mandatory.matching.values()));
+    let base_signature = key.sign(&data).await?;
+
+    encode_proof_value(
+        SD_BASE_PROOF_TAG,
+        vec![
+            CborValue::Bytes(base_signature),
+            CborValue::Bytes(ephemeral_public_key),
+            CborValue::Bytes(hmac_key.to_vec()),
+            CborValue::Array(signatures),
+            CborValue::Array(
+                mandatory_pointers
+                    .iter()
+                    .map(|pointer| CborValue::from(pointer.as_str()))
+                    .collect(),
+            ),
+        ],
+    )
+}
+
+/// Derives a credential with an `ecdsa-sd-2023` proof, that only discloses the mandatory and
+/// given selected parts of given credential with base proof.
+fn derive_credential(
+    selective_pointers: &[String],
+    payload: &str,
+    loader: &dyn DocumentLoader,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut document = parse_document(payload)?;
+    let mut proof = match document.remove("proof") {
+        Some(Value::Object(proof))
+            if proof.get("cryptosuite").and_then(Value::as_str) == Some("ecdsa-sd-2023") =>
+        {
+            proof
+        }
+        _ => return Err(Box::from("credential has no ecdsa-sd-2023 proof")),
+    };
+    let components = decode_proof_value(
+        proof
+            .get("proofValue")
+            .and_then(Value::as_str)
+            .unwrap_or_default(),
+        SD_BASE_PROOF_TAG,
+    )?;
+    let (base_signature, public_key, hmac_key, signatures, mandatory_pointers) = match &components[..]
+    {
+        [CborValue::Bytes(base_signature), CborValue::Bytes(public_key), CborValue::Bytes(hmac_key), CborValue::Array(signatures), pointers] =>
+        {
+            let pointers = data_integrity::decode_pointers(pointers)?;
+            (base_signature, public_key, hmac_key, signatures, pointers)
+        }
+        _ => return Err(Box::from("invalid ecdsa-sd-2023 base proof value")),
+    };
+    let mut combined_pointers = mandatory_pointers.clone();
+    combined_pointers.extend_from_slice(selective_pointers);
+
+    let document = Value::Object(document);
+    let CanonicalGroups {
+        groups, label_map, ..
+    } = selective_disclosure::canonicalize_and_group(
+        &document,
+        loader,
+        selective_disclosure::hmac_label_map(hmac_key),
+        &[&mandatory_pointers, selective_pointers, &combined_pointers],
+    )?;
+    let (mandatory, selective, combined) = (&groups[0], &groups[1], &groups[2]);
+    if signatures.len() != mandatory.non_matching.len() {
+        return Err(Box::from("signature count does not match base proof"));
+    }
+
+    // positions of mandatory statements within the disclosed statements
+    let mandatory_indexes: Vec<CborValue> =
+        selective_disclosure::relative_indexes(&mandatory.matching, &combined.matching)
+            .into_iter()
+            .map(|index| CborValue::from(index as u64))
+            .collect();
+    let signatures: Vec<CborValue> = mandatory
+        .non_matching
+        .keys()
+        .zip(signatures.iter())
+        .filter(|(index, _)| selective.matching.contains_key(index))
+        .map(|(_, signature)| signature.clone())
+        .collect();
+
+    // map canonical labels of the disclosed document to the HMAC labels of the full document
+    let verifier_label_map =
+        selective_disclosure::create_verifier_label_map(&combined.quads, &label_map)?;
+    let mut compressed_labels: Vec<(u64, Vec<u8>)> = Vec::new();
+    for (canonical, label) in verifier_label_map.iter() {
+        let index = canonical
+            .strip_prefix("c14n")
+            .and_then(|index| index.parse().ok())
+            .ok_or("invalid canonical label")?;
+        let label = label
+            .strip_prefix('u')
+            .and_then(|label| URL_SAFE_NO_PAD.decode(label).ok())
+            .ok_or("invalid HMAC label")?;
+        compressed_labels.push((index, label));
+    }
+    compressed_labels.sort();
+
+    let mut revealed = selective_disclosure::select_json_ld(&combined_pointers, &document)?
+        .ok_or("no pointers to disclose given")?;
+    proof.insert(
+        "proofValue".to_string(),
+        Value::from(encode_proof_value(
+            SD_DERIVED_PROOF_TAG,
+            vec![
+                CborValue::Bytes(base_signature.clone()),
+                CborValue::Bytes(public_key.clone()),
+                CborValue::Map(
+                    compressed_labels
+                        .into_iter()
+                        .map(|(index, label)| (CborValue::from(index), CborValue::Bytes(label)))
+                        .collect(),
+                ),
+                CborValue::Array(mandatory_indexes),
+                CborValue::Array(signatures),
+            ],
+        )?),
+    );
+    revealed["proof"] = Value::Object(proof);
+    Ok(serde_json::to_string(&revealed)?)
+}
+
+/// Verifies an `ecdsa-sd-2023` derived proof of given document.
+fn verify_derived_proof(
+    document: &Value,
+    proof_config: &Value,
+    proof_value: &str,
+    issuer_key: &[u8],
+    loader: &dyn DocumentLoader,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if decode_proof_value(proof_value, SD_BASE_PROOF_TAG).is_ok() {
+        return Err(Box::from(
+            "ecdsa-sd-2023 base proofs have to be derived before verification",
+        ));
+    }
+    let components = decode_proof_value(proof_value, SD_DERIVED_PROOF_TAG)?;
+    let (base_signature, public_key, labels, mandatory_indexes, signatures) = match &components[..]
+    {
+        [CborValue::Bytes(base_signature), CborValue::Bytes(public_key), CborValue::Map(labels), indexes, CborValue::Array(signatures)] => {
+            (base_signature, public_key, labels, indexes, signatures)
+        }
+        _ => return Err(Box::from("invalid ecdsa-sd-2023 derived proof value")),
+    };
+    let mut label_map = HashMap::new();
+    for (index, label) in labels.iter() {
+        match (index.as_integer(), label.as_bytes()) {
+            (Some(index), Some(label)) => label_map.insert(
+                format!("c14n{}", i128::from(index)),
+                format!("u{}", URL_SAFE_NO_PAD.encode(label)),
+            ),
+            _ => return Err(Box::from("invalid label map")),
+        };
+    }
+    let mandatory_indexes = data_integrity::decode_indexes(mandatory_indexes)?;
+
+    let (_, nquads) = selective_disclosure::label_replacement_canonicalize(
+        &json_ld::to_rdf(document, loader)?,
+        |canonical| {
+            label_map
+                .get(canonical)
+                .cloned()
+                .unwrap_or_else(|| canonical.to_string())
+        },
+    )?;
+    let mut mandatory = Vec::new();
+    let mut non_mandatory = Vec::new();
+    for (index, nquad) in nquads.iter().enumerate() {
+        if mandatory_indexes.contains(&index) {
+            mandatory.push(nquad);
+        } else {
+            non_mandatory.push(nquad);
+        }
+    }
+    if signatures.len() != non_mandatory.len() {
+        return Err(Box::from(
+            "signature count does not match disclosed statements",
+        ));
+    }
+
+    let mut data = Sha256::digest(json_ld::canonicalize(proof_config, loader)?.as_bytes()).to_vec();
+    data.extend_from_slice(public_key);
+    data.extend_from_slice(&hash_nquads(mandatory.into_iter()));
+    if !crypto::verify(KeyType::P256, issuer_key, &data, base_signature)? {
+        return Err(Box::from("invalid base signature"));
+    }
+    let (key_type, public_key) =
+        multikey::decode_public_key(&format!("z{}", bs58::encode(public_key).into_string()))?;
+    for (nquad, signature) in non_mandatory.iter().zip(signatures.iter()) {
+        let signature = signature.as_bytes().ok_or("invalid signature")?;
+        if key_type != KeyType::P256
+            || !crypto::verify(key_type, &public_key, nquad.as_bytes(), signature)?
+        {
+            return Err(Box::from("invalid signature of disclosed statement"));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the raw bytes of a multikey, including its multicodec prefix.
+fn get_multikey_bytes(multikey: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(bs58::decode(multikey.strip_prefix('z').ok_or("invalid multikey")?).into_vec()?)
+}
@@ -0,0 +1,672 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use super::parse_options;
+use crate::{
+    jwt::{self, Envelope},
+    key_store::KeyReference,
+    presentation::{self, PresentationOptions, Presenter, UsedChallenges},
+    ChallengeStore, HostResolver, KeyStore, Vade, VadePlugin, VadePluginResultValue,
+};
+use async_trait::async_trait;
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use chrono::Utc;
+use ciborium::value::Value as CborValue;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::rc::Rc;
+
+const JWT_METHOD: &str = "jwt";
+const COSE_METHOD: &str = "cose";
+const CREDENTIALS_V2_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+/// CBOR tag of COSE_Sign1 structures
+const COSE_SIGN1_TAG: u64 = 18;
+/// COSE header labels
+const COSE_ALG: i64 = 1;
+const COSE_CONTENT_TYPE: i64 = 3;
+const COSE_KID: i64 = 4;
+const COSE_TYP: i64 = 16;
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SecureOptions {
+    secret_key: Option<String>,
+    key_id: Option<String>,
+    kid: Option<String>,
+    holder: Option<String>,
+    challenge: Option<String>,
+    domain: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Jwt,
+    Cose,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Credential,
+    Presentation,
+}
+
+impl Format {
+    fn from_method(method: &str) -> Option<Self> {
+        match method {
+            JWT_METHOD => Some(Format::Jwt),
+            COSE_METHOD => Some(Format::Cose),
+            _ => None,
+        }
+    }
+
+    /// Returns `typ` and content type header values.
+    fn get_types(&self, kind: Kind) -> (&'static str, &'static str) {
+        match (self, kind) {
+            (Format::Jwt, Kind::Credential) => ("vc+jwt", "vc"),
+            (Format::Jwt, Kind::Presentation) => ("vp+jwt", "vp"),
+            (Format::Cose, Kind::Credential) => ("application/vc+cose", "application/vc"),
+            (Format::Cose, Kind::Presentation) => ("application/vp+cose", "application/vp"),
+        }
+    }
+}
+
+/// Issues and verifies credentials and presentations secured with JOSE (JWT) or COSE (COSE_Sign1)
+/// as defined in [VC-JOSE-COSE](https://www.w3.org/TR/vc-jose-cose/).
+///
+/// The plugin handles `vc_issue`, `vc_verify`, `vp_create` and `vp_verify` for the methods "jwt"
+/// and "cose":
+///
+/// - `vc_issue` and `vp_create` take the unsecured credential or presentation as payload and return
+///   the compact JWT or the base64url encoded COSE_Sign1 structure. Keys are given in options as
+///   `secretKey` multikey or as `keyId` of a key in the registered
+///   [`KeyStore`](https://docs.rs/vade/*/vade/trait.KeyStore.html), `kid` defaults to the
+///   `did:key` of the signing key. Secured credentials given as strings in a presentation's
+///   `verifiableCredential` are wrapped as `EnvelopedVerifiableCredential`. `vp_create` also
+///   takes a single credential or an array of credentials, that are wrapped into a presentation
+///   of the `holder` given in options, which defaults to the DID of the `kid`. `challenge` and
+///   `domain` in options are added as `nonce` and `aud` claims.
+/// - `vc_verify` and `vp_verify` take the secured credential or presentation as payload and return
+///   JSON with `verified` and either the verified `credential` or `presentation` (and its
+///   `credentials`) or an `error`. `challenge` and `domain` given to `vp_verify` have to match
+///   `nonce` and `aud`, every credential has to be bound to the holder by its subject `id` or
+///   `cnf` key unless `holderBinding` is set to `false`, a `nonce` is only accepted once and
+///   `maxAge` limits the age of the presentation in seconds. Used nonces are kept in memory per
+///   plugin instance, unless a shared store is given to
+///   [`with_challenge_store`](#method.with_challenge_store).
+///
+/// Verification resolves the `kid` with the plugins registered next to this plugin on the same
+/// `Vade` instance, or with the resolver given to [`with_resolver`](#method.with_resolver)
+/// instead, `did:key` DIDs are resolved locally. The DID of
+/// the `kid` has to be the issuer or holder and `nbf`, `exp`, `validFrom` and `validUntil` have to
+/// include the current time. Ed25519 (`EdDSA`), P-256 (`ES256`) and secp256k1 (`ES256K`) keys are
+/// supported.
+///
+/// # Example
+///
+/// ```
+/// use vade::{plugins::JoseVadePlugin, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(JoseVadePlugin::new()));
+///     let results = vade.vc_verify("jwt", "", "eyJ...").await?;
+///     if !results.is_empty() {
+///         println!("verification result: {}", results[0].as_ref().ok_or("result not found")?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct JoseVadePlugin {
+    key_store: Option<Rc<dyn KeyStore>>,
+    resolver: Option<Vade>,
+    host_resolver: Option<Rc<HostResolver>>,
+    used_challenges: UsedChallenges,
+}
+
+impl JoseVadePlugin {
+    /// Creates a new `JoseVadePlugin`, that resolves issuers other than `did:key` with the plugins
+    /// registered next to it.
+    pub fn new() -> Self {
+        JoseVadePlugin {
+            key_store: None,
+            resolver: None,
+            host_resolver: None,
+            used_challenges: UsedChallenges::default(),
+        }
+    }
+
+    /// Keeps the challenges of verified presentations in given store instead of the plugin's
+    /// own memory, e.g. to share them with other plugins or instances verifying presentations.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - store to check and record used challenges with
+    pub fn with_challenge_store(mut self, store: Rc<dyn ChallengeStore>) -> Self {
+        self.used_challenges = UsedChallenges::new(store);
+        self
+    }
+
+    /// Resolves `kid`s with given `Vade` instance, that has DID resolver plugins registered,
+    /// instead of the plugins registered next to this plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolver` - `Vade` instance to call `did_resolve` on
+    pub fn with_resolver(mut self, resolver: Vade) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    async fn secure(
+        &self,
+        format: Format,
+        kind: Kind,
+        options: &str,
+        payload: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let options: SecureOptions = parse_options(options)?;
+        let key = KeyReference::from_options(
+            self.key_store.as_ref(),
+            options.secret_key.as_deref(),
+            options.key_id.as_deref(),
+        )
+        .await?;
+        let (_, cose_alg) = jwt::get_algorithm(key.info().key_type)?;
+        let kid = options.kid.unwrap_or_else(|| {
+            format!(
+                "did:key:{}#{}",
+                key.info().public_key,
+                key.info().public_key
+            )
+        });
+        let mut claims = match kind {
+            Kind::Credential => match serde_json::from_str(payload)? {
+                Value::Object(claims) => claims,
+                _ => return Err(Box::from("payload must be a JSON object")),
+            },
+            Kind::Presentation => {
+                let holder = match &options.holder {
+                    Some(holder) => holder.as_str(),
+                    None => kid.split('#').next().unwrap_or_default(),
+                };
+                presentation::wrap_credentials(payload, holder)?
+            }
+        };
+        if kind == Kind::Presentation {
+            envelope_credentials(&mut claims);
+            // both formats bind presentations to the verifier with the claims of JWTs
+            let binding = [("nonce", options.challenge), ("aud", options.domain)];
+            for (name, value) in binding.iter() {
+                if let Some(value) = value {
+                    claims.insert(name.to_string(), Value::from(value.as_str()));
+                }
+            }
+            claims
+                .entry("iat")
+                .or_insert_with(|| Value::from(Utc::now().timestamp()));
+        }
+        let (typ, content_type) = format.get_types(kind);
+
+        match format {
+            Format::Jwt => {
+                add_registered_claims(&mut claims, kind)?;
+                let header = json!({ "kid": kid, "typ": typ, "cty": content_type });
+                jwt::sign(&key, header.as_object().ok_or("invalid header")?, &claims).await
+            }
+            Format::Cose => {
+                let protected = encode_cbor(&CborValue::Map(vec![
+                    (CborValue::from(COSE_ALG), CborValue::from(cose_alg)),
+                    (
+                        CborValue::from(COSE_CONTENT_TYPE),
+                        CborValue::from(content_type),
+                    ),
+                    (
+                        CborValue::from(COSE_KID),
+                        CborValue::Bytes(kid.into_bytes()),
+                    ),
+                    (CborValue::from(COSE_TYP), CborValue::from(typ)),
+                ]))?;
+                let payload = serde_json::to_vec(&claims)?;
+                let signature = key
+                    .sign(&get_cose_signing_input(&protected, &payload)?)
+                    .await?;
+                let sign1 = CborValue::Tag(
+                    COSE_SIGN1_TAG,
+                    Box::new(CborValue::Array(vec![
+                        CborValue::Bytes(protected),
+                        CborValue::Map(Vec::new()),
+                        CborValue::Bytes(payload),
+                        CborValue::Bytes(signature),
+                    ])),
+                );
+                Ok(URL_SAFE_NO_PAD.encode(encode_cbor(&sign1)?))
+            }
+        }
+    }
+
+    /// Verifies given envelope and returns its claims and signer.
+    async fn verify_envelope(
+        &mut self,
+        format: Format,
+        kind: Kind,
+        secured: &str,
+    ) -> Result<(Map<String, Value>, Presenter), String> {
+        let envelope = match format {
+            Format::Jwt => jwt::decode(secured),
+            Format::Cose => decode_cose(secured),
+        }
+        .map_err(|e| format!("could not decode envelope; {}", e))?;
+        let (typ, _) = format.get_types(kind);
+        if envelope.typ != typ {
+            return Err(format!(r#"expected typ "{}", got "{}""#, typ, envelope.typ));
+        }
+        let claims: Map<String, Value> = serde_json::from_slice(&envelope.payload)
+            .map_err(|e| format!("payload is no JSON object; {}", e))?;
+        let signer = match kind {
+            Kind::Credential => match &claims.get("issuer") {
+                Some(Value::Object(issuer)) => issuer.get("id").and_then(|id| id.as_str()),
+                Some(issuer) => issuer.as_str(),
+                None => None,
+            },
+            Kind::Presentation => claims.get("holder").and_then(|holder| holder.as_str()),
+        }
+        .or_else(|| claims.get("iss").and_then(|iss| iss.as_str()))
+        .unwrap_or_default()
+        .to_string();
+        let mut host = self.host_resolver.as_ref().and_then(|host| host.take());
+        let key = jwt::resolve_signer_key(
+            self.resolver.as_mut().or(host.as_mut()),
+            &envelope.kid,
+            &signer,
+            match kind {
+                Kind::Credential => "assertionMethod",
+                Kind::Presentation => "authentication",
+            },
+        )
+        .await;
+        if let Some(host_resolver) = &self.host_resolver {
+            host_resolver.lend(host);
+        }
+        let (key_type, public_key) = key?;
+        jwt::verify_signature(&envelope, key_type, &public_key)?;
+        jwt::check_validity(&claims)?;
+
+        let verification_method = match envelope.kid.starts_with('#') {
+            true => format!("{}{}", signer, envelope.kid),
+            false => envelope.kid.clone(),
+        };
+        let presenter = Presenter {
+            verification_method,
+            key_type,
+            public_key,
+            created: claims.get("iat").and_then(Value::as_i64),
+            challenge: claims
+                .get("nonce")
+                .and_then(Value::as_str)
+                .map(String::from),
+        };
+        Ok((claims, presenter))
+    }
+
+    async fn verify(
+        &mut self,
+        format: Format,
+        kind: Kind,
+        options: &str,
+        secured: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let options: PresentationOptions = parse_options(options)?;
+        let result = match kind {
+            Kind::Credential => self
+                .verify_envelope(format, kind, secured.trim())
+                .await
+                .map(|(claims, _)| json!({ "verified": true, "credential": claims })),
+            Kind::Presentation => {
+                self.verify_presentation(format, &options, secured.trim())
+                    .await
+            }
+        };
+        Ok(match result {
+            Ok(result) => result,
+            Err(error) => json!({ "verified": false, "error": error }),
+        })
+    }
+
+    /// Verifies given presentation, the credentials in it, its binding to the verifier and
+    /// holder and that the challenge has not been used before.
+    async fn verify_presentation(
+        &mut self,
+        format: Format,
+        options: &PresentationOptions,
+        secured: &str,
+    ) -> Result<Value, String> {
+        let (claims, presenter) = self
+            .verify_envelope(format, Kind::Presentation, secured)
+            .await?;
+        if let Some(challenge) = &options.challenge {
+            if claims.get("nonce").and_then(Value::as_str) != Some(challenge.as_str()) {
+                return Err("challenge does not match".to_string());
+            }
+        }
+        if let Some(domain) = &options.domain {
+            let matches = match claims.get("aud") {
+                Some(Value::Array(audiences)) => audiences.iter().any(|aud| aud == domain),
+                Some(aud) => aud == domain,
+                None => false,
+            };
+            if !matches {
+                return Err("domain does not match".to_string());
+            }
+        }
+        let presenters = [presenter];
+        self.used_challenges.check(&presenters)?;
+        presentation::check_freshness(&presenters, options.max_age)?;
+
+        let mut credentials = Vec::new();
+        for (index, credential) in get_enveloped_credentials(&claims).into_iter().enumerate() {
+            let (format, secured) = credential
+                .ok_or_else(|| format!("credential {} is not an enveloped credential", index))?;
+            let (credential, _) = self
+                .verify_envelope(format, Kind::Credential, &secured)
+                .await
+                .map_err(|error| format!("credential {} is invalid; {}", index, error))?;
+            credentials.push(Value::Object(credential));
+        }
+        if options.holder_binding != Some(false) {
+            let holder = claims.get("holder").and_then(Value::as_str);
+            presentation::check_holder_binding(holder, &presenters, &credentials)?;
+        }
+        self.used_challenges.insert(&presenters, options.max_age)?;
+
+        Ok(json!({ "verified": true, "presentation": claims, "credentials": credentials }))
+    }
+}
+
+impl Default for JoseVadePlugin {
+    fn default() -> Self {
+        JoseVadePlugin::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl VadePlugin for JoseVadePlugin {
+    /// Keeps given key store to sign with keys given as `keyId` in options.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_store` - key store to use
+    fn set_key_store(&mut self, key_store: Rc<dyn KeyStore>) {
+        self.key_store = Some(key_store);
+    }
+
+    /// Sets the resolver of the hosting `Vade` instance to resolve verification methods with.
+    ///
+    /// # Arguments
+    ///
+    /// * `host_resolver` - resolver using the other plugins of `Vade`
+    fn set_host_resolver(&mut self, host_resolver: Rc<HostResolver>) {
+        self.host_resolver = Some(host_resolver);
+    }
+
+    /// Secures given credential as JWT or COSE_Sign1.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "jwt" or "cose", other methods are ignored
+    /// * `options` - JSON with `secretKey` or `keyId` and optional `kid`
+    /// * `payload` - credential to secure
+    async fn vc_issue(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        let format = match Format::from_method(method) {
+            Some(format) => format,
+            None => return Ok(VadePluginResultValue::Ignored),
+        };
+        let secured = self
+            .secure(format, Kind::Credential, options, payload)
+            .await?;
+        Ok(VadePluginResultValue::Success(Some(secured)))
+    }
+
+    /// Verifies given JWT or COSE_Sign1 secured credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "jwt" or "cose", other methods are ignored
+    /// * `options` - currently unused
+    /// * `payload` - secured credential
+    async fn vc_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        let format = match Format::from_method(method) {
+            Some(format) => format,
+            None => return Ok(VadePluginResultValue::Ignored),
+        };
+        let result = self
+            .verify(format, Kind::Credential, options, payload)
+            .await?;
+        Ok(VadePluginResultValue::Success(Some(result.to_string())))
+    }
+
+    /// Secures given presentation as JWT or COSE_Sign1.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "jwt" or "cose", other methods are ignored
+    /// * `options` - JSON with `secretKey` or `keyId` of holder and optional `kid`, `holder`,
+    ///   `challenge` and `domain`
+    /// * `payload` - presentation to secure or credentials to present
+    async fn vp_create(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        let format = match Format::from_method(method) {
+            Some(format) => format,
+            None => return Ok(VadePluginResultValue::Ignored),
+        };
+        let secured = self
+            .secure(format, Kind::Presentation, options, payload)
+            .await?;
+        Ok(VadePluginResultValue::Success(Some(secured)))
+    }
+
+    /// Verifies given JWT or COSE_Sign1 secured presentation and the credentials in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "jwt" or "cose", other methods are ignored
+    /// * `options` - JSON with optional `challenge`, `domain`, `maxAge` in seconds and
+    ///   `holderBinding`, challenges are only accepted once per challenge store
+    /// * `payload` - secured presentation
+    async fn vp_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        let format = match Format::from_method(method) {
+            Some(format) => format,
+            None => return Ok(VadePluginResultValue::Ignored),
+        };
+        let result = self
+            .verify(format, Kind::Presentation, options, payload)
+            .await?;
+        Ok(VadePluginResultValue::Success(Some(result.to_string())))
+    }
+}
+
+/// Adds `iss`, `jti`, `iat`, `nbf` and `exp` claims derived from given credential or presentation.
+fn add_registered_claims(
+    claims: &mut Map<String, Value>,
+    kind: Kind,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let issuer = match kind {
+        Kind::Credential => match claims.get("issuer") {
+            Some(Value::Object(issuer)) => issuer.get("id").cloned(),
+            issuer => issuer.cloned(),
+        },
+        Kind::Presentation => claims.get("holder").cloned(),
+    };
+    if let Some(issuer) = issuer.filter(|issuer| issuer.is_string()) {
+        claims.entry("iss").or_insert(issuer);
+    }
+    if let Some(id) = claims.get("id").cloned() {
+        claims.entry("jti").or_insert(id);
+    }
+    claims
+        .entry("iat")
+        .or_insert_with(|| Value::from(Utc::now().timestamp()));
+    if let Some(valid_from) = jwt::get_timestamp(claims.get("validFrom"))? {
+        claims
+            .entry("nbf")
+            .or_insert_with(|| Value::from(valid_from));
+    }
+    if let Some(valid_until) = jwt::get_timestamp(claims.get("validUntil"))? {
+        claims
+            .entry("exp")
+            .or_insert_with(|| Value::from(valid_until));
+    }
+    Ok(())
+}
+
+/// Wraps secured credentials given as strings as `EnvelopedVerifiableCredential`s.
+fn envelope_credentials(claims: &mut Map<String, Value>) {
+    let credentials = match claims.get_mut("verifiableCredential") {
+        Some(Value::Array(credentials)) => credentials,
+        _ => return,
+    };
+    for credential in credentials.iter_mut() {
+        if let Value::String(secured) = credential {
+            let id = if secured.matches('.').count() == 2 {
+                format!("data:application/vc+jwt,{}", secured)
+            } else {
+                let bytes = URL_SAFE_NO_PAD.decode(secured.as_str()).unwrap_or_default();
+                format!("data:application/vc+cose;base64,{}", STANDARD.encode(bytes))
+            };
+            *credential = json!({
+                "@context": CREDENTIALS_V2_CONTEXT,
+                "id": id,
+                "type": "EnvelopedVerifiableCredential",
+            });
+        }
+    }
+}
+
+/// Returns format and secured credential of all credentials in given presentation, `None` for
+/// credentials, that are not enveloped.
+fn get_enveloped_credentials(claims: &Map<String, Value>) -> Vec<Option<(Format, String)>> {
+    let credentials = match claims.get("verifiableCredential") {
+        Some(Value::Array(credentials)) => credentials.clone(),
+        Some(credential) => vec![credential.clone()],
+        None => Vec::new(),
+    };
+    credentials
+        .iter()
+        .map(|credential| {
+            if credential["type"] != "EnvelopedVerifiableCredential" {
+                return None;
+            }
+            let id = credential["id"].as_str().unwrap_or_default();
+            if let Some(jwt) = id.strip_prefix("data:application/vc+jwt,") {
+                Some((Format::Jwt, jwt.to_string()))
+            } else if let Some(cose) = id.strip_prefix("data:application/vc+cose;base64,") {
+                let bytes = STANDARD.decode(cose).ok()?;
+                Some((Format::Cose, URL_SAFE_NO_PAD.encode(bytes)))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn decode_cose(encoded: &str) -> Result<Envelope, Box<dyn std::error::Error>> {
+    let bytes = URL_SAFE_NO_PAD.decode(encoded)?;
+    let sign1: CborValue = ciborium::de::from_reader(bytes.as_slice())?;
+    let sign1 = match sign1 {
+        CborValue::Tag(COSE_SIGN1_TAG, sign1) => *sign1,
+        sign1 => sign1,
+    };
+    let (protected, payload, signature) = match sign1 {
+        CborValue::Array(items) => match &items[..] {
+            [CborValue::Bytes(protected), _, CborValue::Bytes(payload), CborValue::Bytes(signature)] => {
+                (protected.clone(), payload.clone(), signature.clone())
+            }
+            _ => return Err(Box::from("invalid COSE_Sign1 structure")),
+        },
+        _ => return Err(Box::from("invalid COSE_Sign1 structure")),
+    };
+
+    let headers: CborValue = ciborium::de::from_reader(protected.as_slice())?;
+    let mut alg = String::new();
+    let mut kid = String::new();
+    let mut typ = String::new();
+    for (label, value) in headers.as_map().cloned().unwrap_or_default() {
+        match (label.as_integer().map(i128::from), value) {
+            (Some(label), CborValue::Integer(value)) if label == COSE_ALG as i128 => {
+                alg = match i128::from(value) {
+                    -8 => "EdDSA",
+                    -7 => "ES256",
+                    -47 => "ES256K",
+                    _ => "",
+                }
+                .to_string()
+            }
+            (Some(label), CborValue::Bytes(value)) if label == COSE_KID as i128 => {
+                kid = String::from_utf8(value)?
+            }
+            (Some(label), CborValue::Text(value)) if label == COSE_TYP as i128 => typ = value,
+            _ => (),
+        }
+    }
+    Ok(Envelope {
+        alg,
+        kid,
+        typ,
+        signing_input: get_cose_signing_input(&protected, &payload)?,
+        payload,
+        signature,
+    })
+}
+
+/// Encodes the `Sig_structure` of a COSE_Sign1 structure without external data.
+fn get_cose_signing_input(
+    protected: &[u8],
+    payload: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    encode_cbor(&CborValue::Array(vec![
+        CborValue::from("Signature1"),
+        CborValue::Bytes(protected.to_vec()),
+        CborValue::Bytes(Vec::new()),
+        CborValue::Bytes(payload.to_vec()),
+    ]))
+}
+
+fn encode_cbor(value: &CborValue) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(value, &mut bytes)?;
+    Ok(bytes)
+}
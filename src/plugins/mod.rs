@@ -0,0 +1,58 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Plugins shipped with `vade` itself. Plugins for specific networks or ledgers reside in their
+//! own crates, e.g. [`vade-evan`](https://docs.rs/vade-evan).
+
+#[cfg(feature = "bbs")]
+mod bbs;
+mod data_integrity;
+mod jose;
+#[cfg(feature = "revocation-registry")]
+mod revocation_registry;
+mod sd_jwt;
+mod sidetree;
+#[cfg(feature = "status-list")]
+mod status_list;
+#[cfg(feature = "universal-resolver")]
+mod universal_resolver;
+#[cfg(feature = "webvh")]
+mod webvh;
+
+#[cfg(feature = "bbs")]
+pub use self::bbs::BbsVadePlugin;
+pub use self::data_integrity::DataIntegrityVadePlugin;
+pub use self::jose::JoseVadePlugin;
+#[cfg(feature = "revocation-registry")]
+pub use self::revocation_registry::RevocationRegistryVadePlugin;
+pub use self::sd_jwt::SdJwtVadePlugin;
+pub use self::sidetree::SidetreeVadePlugin;
+#[cfg(feature = "status-list")]
+pub use self::status_list::{StatusListStore, StatusListVadePlugin};
+#[cfg(feature = "universal-resolver")]
+pub use self::universal_resolver::UniversalResolverVadePlugin;
+#[cfg(feature = "webvh")]
+pub use self::webvh::WebVhVadePlugin;
+
+/// Parses given plugin options, empty options are parsed as default options.
+pub(crate) fn parse_options<T: Default + serde::de::DeserializeOwned>(
+    options: &str,
+) -> Result<T, Box<dyn std::error::Error>> {
+    if options.trim().is_empty() {
+        return Ok(T::default());
+    }
+    Ok(serde_json::from_str(options)?)
+}
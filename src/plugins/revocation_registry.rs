@@ -0,0 +1,770 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use super::parse_options;
+use crate::{Clock, SystemClock, VadePlugin, VadePluginResultValue};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bls12_381_plus::{
+    ff::Field, group::Curve, pairing, G1Affine, G1Projective, G2Affine, G2Projective, Gt, Scalar,
+};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    convert::{TryFrom, TryInto},
+    path::{Path, PathBuf},
+};
+
+const REVOCATION_REGISTRY_METHOD: &str = "revocation-registry";
+const REGISTRY_TYPE: &str = "CL_ACCUM";
+const DEFAULT_MAX_CRED_NUM: usize = 100;
+const MAX_MAX_CRED_NUM: usize = 100_000;
+const G1_LENGTH: usize = 48;
+const G2_LENGTH: usize = 96;
+
+/// Public part of a revocation registry, that is published by the issuer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RevocationRegistryDefinition {
+    id: String,
+    issuer: String,
+    #[serde(rename = "type")]
+    registry_type: String,
+    max_cred_num: usize,
+    tails_hash: String,
+    tails_location: String,
+    /// `e(g1, g2)^(γ^(L+1))`, the value every valid witness pairs up to
+    accum_key: String,
+}
+
+/// Change of a revocation registry's accumulator. The first delta of a registry holds its initial
+/// accumulator, in which all credentials are issued.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RevocationRegistryDelta {
+    timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prev_accum: Option<String>,
+    accum: String,
+    revoked: Vec<usize>,
+}
+
+/// A revocation registry as stored on disk.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredRegistry {
+    revocation_registry_definition: RevocationRegistryDefinition,
+    deltas: Vec<RevocationRegistryDelta>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    private_key: Option<String>,
+}
+
+impl StoredRegistry {
+    fn id(&self) -> &str {
+        &self.revocation_registry_definition.id
+    }
+
+    fn revoked_until(&self, count: usize) -> HashSet<usize> {
+        self.deltas[..count]
+            .iter()
+            .flat_map(|delta| delta.revoked.iter().cloned())
+            .collect()
+    }
+
+    /// Returns the number of deltas, that make up the registry's state at given time.
+    fn count_at(&self, time: i64) -> usize {
+        self.deltas
+            .iter()
+            .take_while(|delta| delta.timestamp <= time)
+            .count()
+    }
+
+    fn to_published(&self) -> Value {
+        json!({
+            "revocationRegistryDefinition": self.revocation_registry_definition,
+            "deltas": self.deltas,
+        })
+    }
+}
+
+/// A holder's proof, that a credential has not been revoked as of a registry's state.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RevocationState {
+    revocation_registry_definition: String,
+    credential_revocation_id: usize,
+    timestamp: i64,
+    accum: String,
+    witness: String,
+}
+
+/// Tails of a registry with a capacity of `L`: `g2^(γ^k)` for `k` in `1..=2L` (with the identity
+/// at `L+1`) and `g1^(γ^i)` for `i` in `1..=L`.
+struct Tails {
+    g2: Vec<G2Affine>,
+    g1: Vec<G1Affine>,
+}
+
+impl Tails {
+    fn generate(gamma: &Scalar, max_cred_num: usize) -> Self {
+        let mut g2 = Vec::with_capacity(2 * max_cred_num);
+        let mut point = G2Projective::GENERATOR;
+        for k in 1..=2 * max_cred_num {
+            point *= gamma;
+            g2.push(match k == max_cred_num + 1 {
+                true => G2Affine::identity(),
+                false => point.to_affine(),
+            });
+        }
+        let mut g1 = Vec::with_capacity(max_cred_num);
+        let mut point = G1Projective::GENERATOR;
+        for _ in 0..max_cred_num {
+            point *= gamma;
+            g1.push(point.to_affine());
+        }
+        Tails { g2, g1 }
+    }
+
+    fn parse(bytes: &[u8], max_cred_num: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        if bytes.len() != max_cred_num * (2 * G2_LENGTH + G1_LENGTH) {
+            return Err("tails file has an invalid length".into());
+        }
+        let (g2_bytes, g1_bytes) = bytes.split_at(2 * max_cred_num * G2_LENGTH);
+        let g2 = g2_bytes
+            .chunks(G2_LENGTH)
+            .map(|chunk| {
+                Option::from(G2Affine::from_compressed(<&[u8; G2_LENGTH]>::try_from(
+                    chunk,
+                )?))
+                .ok_or_else(|| "tails file contains an invalid point".into())
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+        let g1 = g1_bytes
+            .chunks(G1_LENGTH)
+            .map(|chunk| {
+                Option::from(G1Affine::from_compressed(<&[u8; G1_LENGTH]>::try_from(
+                    chunk,
+                )?))
+                .ok_or_else(|| "tails file contains an invalid point".into())
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+        Ok(Tails { g2, g1 })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for point in self.g2.iter() {
+            bytes.extend_from_slice(&point.to_compressed());
+        }
+        for point in self.g1.iter() {
+            bytes.extend_from_slice(&point.to_compressed());
+        }
+        bytes
+    }
+
+    fn max_cred_num(&self) -> usize {
+        self.g1.len()
+    }
+
+    /// Returns `g2^(γ^k)`.
+    fn tail(&self, k: usize) -> G2Projective {
+        G2Projective::from(self.g2[k - 1])
+    }
+
+    /// Computes the accumulator of all credentials, that are not revoked.
+    fn accumulate(&self, revoked: &HashSet<usize>) -> G2Projective {
+        let max = self.max_cred_num();
+        (1..=max)
+            .filter(|index| !revoked.contains(index))
+            .fold(G2Projective::IDENTITY, |accum, index| {
+                accum + self.tail(max + 1 - index)
+            })
+    }
+
+    /// Computes the witness of a credential, that is not revoked.
+    fn witness(&self, index: usize, revoked: &HashSet<usize>) -> G2Projective {
+        let max = self.max_cred_num();
+        (1..=max)
+            .filter(|other| *other != index && !revoked.contains(other))
+            .fold(G2Projective::IDENTITY, |witness, other| {
+                witness + self.tail(max + 1 - other + index)
+            })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatePayload {
+    issuer: String,
+    id: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateOptions {
+    max_cred_num: Option<usize>,
+    tails_location: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RevokePayload {
+    revocation_registry_definition: String,
+    credential_revocation_id: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdatePayload {
+    revocation_registry_definition: Value,
+    #[serde(default)]
+    deltas: Vec<RevocationRegistryDelta>,
+    credential_revocation_id: Option<usize>,
+    interval: Option<Interval>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateOptions {
+    tails_file: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct Interval {
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+/// Plugin for revocation registries, that are managed offline in a local directory instead of on
+/// a ledger. Registries use a pairing based accumulator (as CL_ACCUM registries of AnonCreds do):
+/// credentials are identified by their `credentialRevocationId` (`1` to `maxCredNum`), issued by
+/// default and removed from the accumulator when revoked. Holders prove, that their credential
+/// has not been revoked, with a witness computed from the registry's tails file.
+///
+/// Registry definitions and their deltas are kept as JSON files in `registries`, tails files in
+/// `tails` below the plugin's directory. Tails files are only used after checking them against
+/// the `tailsHash` of their definition and imported deltas are only kept after recomputing their
+/// accumulators from the tails file.
+///
+/// The plugin handles the method "revocation-registry":
+///
+/// - `vc_zkp_create_revocation_registry_definition` creates a registry for the `issuer` (and
+///   optional `id`) given as payload with `maxCredNum` (default 100) and `tailsLocation` to
+///   publish the tails file at given as options. Returns the `revocationRegistryDefinition`, its
+///   initial `deltas` and the `revocationRegistryPrivateKey`, that is kept on disk as well.
+/// - `vc_zkp_revoke_credential` revokes the credential with the `credentialRevocationId` in the
+///   registry with the id `revocationRegistryDefinition` given as payload. Only registries created
+///   by the plugin can be revoked in. Returns the delta to publish.
+/// - `vc_zkp_update_revocation_registry` imports the `revocationRegistryDefinition` (with its
+///   tails file given as `tailsFile` in options) and new `deltas` given as payload and returns the
+///   registry with all deltas to publish. If a `credentialRevocationId` is given, a revocation
+///   state with a witness for this credential is returned instead, as of the registry's state at
+///   the end of the `interval` (`from` and `to` as unix timestamps, `to` defaults to now).
+///
+/// Revocation states are verified with
+/// [`verify_revocation_state`](#method.verify_revocation_state).
+///
+/// # Example
+///
+/// ```
+/// use vade::{plugins::RevocationRegistryVadePlugin, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(RevocationRegistryVadePlugin::new(
+///         std::env::temp_dir().join("vade-revocation-registries"),
+///     )));
+///     let results = vade
+///         .vc_zkp_create_revocation_registry_definition(
+///             "revocation-registry",
+///             r#"{ "maxCredNum": 10 }"#,
+///             r#"{ "issuer": "did:example:issuer" }"#,
+///         )
+///         .await?;
+///     if !results.is_empty() {
+///         println!("registry: {}", results[0].as_ref().ok_or("result not found")?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct RevocationRegistryVadePlugin {
+    directory: PathBuf,
+    clock: Box<dyn Clock>,
+}
+
+impl RevocationRegistryVadePlugin {
+    /// Creates a new `RevocationRegistryVadePlugin`, that stores registries below given directory.
+    /// The directory is created when storing the first registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - directory to store registry definitions, deltas and tails files in
+    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
+        RevocationRegistryVadePlugin {
+            directory: directory.as_ref().to_path_buf(),
+            clock: Box::from(SystemClock),
+        }
+    }
+
+    /// Takes timestamps of deltas and the default end of intervals from given clock instead of
+    /// the system time.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - clock to get current time from
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Verifies a revocation state created by `vc_zkp_update_revocation_registry`: checks, that its
+    /// accumulator is part of the registry as known to the plugin, that the state is current for
+    /// the given interval and that its witness proves the credential to be not revoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - revocation state as JSON
+    /// * `from` - optional unix timestamp, the state has to be still current at
+    /// * `to` - optional unix timestamp, the state must not be newer than
+    pub fn verify_revocation_state(
+        &self,
+        state: &str,
+        from: Option<i64>,
+        to: Option<i64>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let state: RevocationState = serde_json::from_str(state)?;
+        let registry = self.read_registry(&state.revocation_registry_definition)?;
+        let count = registry.count_at(state.timestamp);
+        if count == 0 || registry.deltas[count - 1].accum != state.accum {
+            return Err(format!(
+                r#"revocation state does not match revocation registry "{}" at {}"#,
+                registry.id(),
+                state.timestamp,
+            )
+            .into());
+        }
+        if to.is_some_and(|to| state.timestamp > to) {
+            return Err("revocation state is newer than requested interval".into());
+        }
+        if let (Some(from), Some(next)) = (from, registry.deltas.get(count)) {
+            if next.timestamp <= from {
+                return Err("revocation state is outdated for requested interval".into());
+            }
+        }
+
+        let definition = &registry.revocation_registry_definition;
+        let index = state.credential_revocation_id;
+        if index == 0 || index > definition.max_cred_num {
+            return Err(format!("invalid credentialRevocationId {}", index).into());
+        }
+        let tails = self.read_tails(definition)?;
+        let accum = decode_g2(&state.accum)?;
+        let witness = decode_g2(&state.witness)?;
+        let accum_key = decode_gt(&definition.accum_key)?;
+        if pairing(&tails.g1[index - 1], &accum)
+            != accum_key + pairing(&G1Affine::generator(), &witness)
+        {
+            return Err(format!(
+                r#"credential {} has been revoked in revocation registry "{}""#,
+                index,
+                registry.id(),
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn create(&self, options: &str, payload: &str) -> Result<Value, Box<dyn std::error::Error>> {
+        let options: CreateOptions = parse_options(options)?;
+        let payload: CreatePayload = serde_json::from_str(payload)?;
+        let max_cred_num = options.max_cred_num.unwrap_or(DEFAULT_MAX_CRED_NUM);
+        if max_cred_num == 0 || max_cred_num > MAX_MAX_CRED_NUM {
+            return Err(format!("maxCredNum must be between 1 and {}", MAX_MAX_CRED_NUM).into());
+        }
+
+        let gamma = Scalar::random(OsRng);
+        let tails = Tails::generate(&gamma, max_cred_num);
+        let tails_bytes = tails.to_bytes();
+        let tails_hash = bs58::encode(Sha256::digest(&tails_bytes)).into_string();
+        let tails_path = self.tails_path(&tails_hash);
+        write_file(&tails_path, &tails_bytes)?;
+        let accum_key = pairing(
+            &G1Affine::generator(),
+            &(tails.tail(max_cred_num) * gamma).to_affine(),
+        );
+
+        let id = match payload.id {
+            Some(id) => id,
+            None => format!("{}/revocation-registry/{}", payload.issuer, tails_hash),
+        };
+        let definition = RevocationRegistryDefinition {
+            id,
+            issuer: payload.issuer,
+            registry_type: REGISTRY_TYPE.to_string(),
+            max_cred_num,
+            tails_hash,
+            tails_location: options
+                .tails_location
+                .unwrap_or_else(|| tails_path.to_string_lossy().to_string()),
+            accum_key: URL_SAFE_NO_PAD.encode(accum_key.to_bytes()),
+        };
+        if self.registry_path(&definition.id).exists() {
+            return Err(
+                format!(r#"revocation registry "{}" already exists"#, definition.id).into(),
+            );
+        }
+        let private_key = URL_SAFE_NO_PAD.encode(gamma.to_be_bytes());
+        let registry = StoredRegistry {
+            revocation_registry_definition: definition,
+            deltas: vec![RevocationRegistryDelta {
+                timestamp: self.clock.now(),
+                prev_accum: None,
+                accum: encode_g2(&tails.accumulate(&HashSet::new())),
+                revoked: Vec::new(),
+            }],
+            private_key: Some(private_key.clone()),
+        };
+        self.write_registry(&registry)?;
+
+        let mut result = registry.to_published();
+        result["revocationRegistryPrivateKey"] = Value::from(private_key);
+        Ok(result)
+    }
+
+    fn revoke(&self, payload: &str) -> Result<Value, Box<dyn std::error::Error>> {
+        let payload: RevokePayload = serde_json::from_str(payload)?;
+        let mut registry = self.read_registry(&payload.revocation_registry_definition)?;
+        if registry.private_key.is_none() {
+            return Err(format!(
+                r#"revocation registry "{}" has not been created by this plugin"#,
+                registry.id(),
+            )
+            .into());
+        }
+        let index = payload.credential_revocation_id;
+        let mut revoked = registry.revoked_until(registry.deltas.len());
+        let previous = registry
+            .deltas
+            .last()
+            .ok_or("revocation registry has no deltas")?;
+        if index == 0 || index > registry.revocation_registry_definition.max_cred_num {
+            return Err(format!("invalid credentialRevocationId {}", index).into());
+        }
+        if !revoked.insert(index) {
+            return Err(format!("credential {} has already been revoked", index).into());
+        }
+        let tails = self.read_tails(&registry.revocation_registry_definition)?;
+        let delta = RevocationRegistryDelta {
+            timestamp: self.clock.now().max(previous.timestamp),
+            prev_accum: Some(previous.accum.clone()),
+            accum: encode_g2(&tails.accumulate(&revoked)),
+            revoked: vec![index],
+        };
+        registry.deltas.push(delta.clone());
+        self.write_registry(&registry)?;
+        Ok(serde_json::to_value(&delta)?)
+    }
+
+    fn update(&self, options: &str, payload: &str) -> Result<Value, Box<dyn std::error::Error>> {
+        let options: UpdateOptions = parse_options(options)?;
+        let payload: UpdatePayload = serde_json::from_str(payload)?;
+        let mut registry = match payload.revocation_registry_definition {
+            Value::String(id) => self.read_registry(&id)?,
+            definition => self.import_definition(serde_json::from_value(definition)?, &options)?,
+        };
+        if !payload.deltas.is_empty() {
+            self.import_deltas(&mut registry, payload.deltas)?;
+        }
+        match payload.credential_revocation_id {
+            Some(index) => {
+                self.create_revocation_state(&registry, index, payload.interval.unwrap_or_default())
+            }
+            None => Ok(registry.to_published()),
+        }
+    }
+
+    fn import_definition(
+        &self,
+        definition: RevocationRegistryDefinition,
+        options: &UpdateOptions,
+    ) -> Result<StoredRegistry, Box<dyn std::error::Error>> {
+        if self.registry_path(&definition.id).exists() {
+            let registry = self.read_registry(&definition.id)?;
+            if registry.revocation_registry_definition != definition {
+                return Err(format!(
+                    r#"revocation registry "{}" differs from known definition"#,
+                    definition.id,
+                )
+                .into());
+            }
+            return Ok(registry);
+        }
+        if definition.registry_type != REGISTRY_TYPE {
+            return Err(format!(
+                r#"unsupported revocation registry type "{}""#,
+                definition.registry_type
+            )
+            .into());
+        }
+        if let Some(tails_file) = &options.tails_file {
+            let bytes = std::fs::read(tails_file)
+                .map_err(|e| format!(r#"could not read tails file "{}"; {}"#, tails_file, e))?;
+            verify_tails_hash(&definition, &bytes)?;
+            write_file(&self.tails_path(&definition.tails_hash), &bytes)?;
+        }
+        // checks, that tails file is known and valid
+        self.read_tails(&definition)?;
+        Ok(StoredRegistry {
+            revocation_registry_definition: definition,
+            deltas: Vec::new(),
+            private_key: None,
+        })
+    }
+
+    /// Verifies given deltas against the tails file and appends new ones to the registry.
+    fn import_deltas(
+        &self,
+        registry: &mut StoredRegistry,
+        deltas: Vec<RevocationRegistryDelta>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let tails = self.read_tails(&registry.revocation_registry_definition)?;
+        let mut revoked = registry.revoked_until(registry.deltas.len());
+        for delta in deltas {
+            if registry.deltas.contains(&delta) {
+                continue;
+            }
+            let previous = registry.deltas.last();
+            if previous.map(|previous| &previous.accum) != delta.prev_accum.as_ref() {
+                return Err("revocation registry delta does not follow known deltas".into());
+            }
+            if previous.is_some_and(|previous| previous.timestamp > delta.timestamp) {
+                return Err("revocation registry delta is older than known deltas".into());
+            }
+            for index in delta.revoked.iter() {
+                if *index == 0 || *index > tails.max_cred_num() || !revoked.insert(*index) {
+                    return Err(format!("invalid revocation of credential {}", index).into());
+                }
+            }
+            if encode_g2(&tails.accumulate(&revoked)) != delta.accum {
+                return Err("accumulator of revocation registry delta is invalid".into());
+            }
+            registry.deltas.push(delta);
+        }
+        self.write_registry(registry)
+    }
+
+    fn create_revocation_state(
+        &self,
+        registry: &StoredRegistry,
+        index: usize,
+        interval: Interval,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let to = interval.to.unwrap_or_else(|| self.clock.now());
+        if interval.from.is_some_and(|from| from > to) {
+            return Err("interval must not end before it starts".into());
+        }
+        let definition = &registry.revocation_registry_definition;
+        if index == 0 || index > definition.max_cred_num {
+            return Err(format!("invalid credentialRevocationId {}", index).into());
+        }
+        let count = registry.count_at(to);
+        if count == 0 {
+            return Err(format!(
+                r#"revocation registry "{}" did not exist at {}"#,
+                definition.id, to
+            )
+            .into());
+        }
+        let revoked = registry.revoked_until(count);
+        if revoked.contains(&index) {
+            return Err(format!(
+                r#"credential {} has been revoked in revocation registry "{}""#,
+                index, definition.id,
+            )
+            .into());
+        }
+        let tails = self.read_tails(definition)?;
+        let delta = &registry.deltas[count - 1];
+        Ok(serde_json::to_value(RevocationState {
+            revocation_registry_definition: definition.id.clone(),
+            credential_revocation_id: index,
+            timestamp: delta.timestamp,
+            accum: delta.accum.clone(),
+            witness: encode_g2(&tails.witness(index, &revoked)),
+        })?)
+    }
+
+    fn registry_path(&self, id: &str) -> PathBuf {
+        let name = bs58::encode(Sha256::digest(id.as_bytes())).into_string();
+        self.directory
+            .join("registries")
+            .join(format!("{}.json", name))
+    }
+
+    fn tails_path(&self, tails_hash: &str) -> PathBuf {
+        self.directory.join("tails").join(tails_hash)
+    }
+
+    fn read_registry(&self, id: &str) -> Result<StoredRegistry, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(self.registry_path(id))
+            .map_err(|_| format!(r#"unknown revocation registry "{}""#, id))?;
+        let registry: StoredRegistry = serde_json::from_str(&content)
+            .map_err(|e| format!(r#"invalid revocation registry "{}"; {}"#, id, e))?;
+        if registry.id() != id {
+            return Err(format!(r#"invalid revocation registry "{}""#, id).into());
+        }
+        Ok(registry)
+    }
+
+    fn write_registry(&self, registry: &StoredRegistry) -> Result<(), Box<dyn std::error::Error>> {
+        write_file(
+            &self.registry_path(registry.id()),
+            serde_json::to_string_pretty(registry)?.as_bytes(),
+        )
+    }
+
+    fn read_tails(
+        &self,
+        definition: &RevocationRegistryDefinition,
+    ) -> Result<Tails, Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(self.tails_path(&definition.tails_hash)).map_err(|_| {
+            format!(
+                r#"tails file of revocation registry "{}" not found"#,
+                definition.id
+            )
+        })?;
+        verify_tails_hash(definition, &bytes)?;
+        Tails::parse(&bytes, definition.max_cred_num)
+    }
+}
+
+#[async_trait(?Send)]
+impl VadePlugin for RevocationRegistryVadePlugin {
+    /// Creates a new revocation registry with its tails file.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "revocation-registry", other methods are ignored
+    /// * `options` - JSON with optional `maxCredNum` and `tailsLocation`
+    /// * `payload` - JSON with `issuer` and optional `id` of registry
+    async fn vc_zkp_create_revocation_registry_definition(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != REVOCATION_REGISTRY_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let result = self.create(options, payload)?;
+        Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+            &result,
+        )?)))
+    }
+
+    /// Imports a registry and its deltas or creates a revocation state for a credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "revocation-registry", other methods are ignored
+    /// * `options` - JSON with optional `tailsFile`
+    /// * `payload` - JSON with `revocationRegistryDefinition` (or its id), optional `deltas`,
+    ///   `credentialRevocationId` and `interval`
+    async fn vc_zkp_update_revocation_registry(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != REVOCATION_REGISTRY_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let result = self.update(options, payload)?;
+        Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+            &result,
+        )?)))
+    }
+
+    /// Revokes a credential in a registry created by this plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "revocation-registry", other methods are ignored
+    /// * `options` - not used
+    /// * `payload` - JSON with `revocationRegistryDefinition` id and `credentialRevocationId`
+    async fn vc_zkp_revoke_credential(
+        &mut self,
+        method: &str,
+        _options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != REVOCATION_REGISTRY_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let delta = self.revoke(payload)?;
+        Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+            &delta,
+        )?)))
+    }
+}
+
+fn verify_tails_hash(
+    definition: &RevocationRegistryDefinition,
+    bytes: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if bs58::encode(Sha256::digest(bytes)).into_string() != definition.tails_hash {
+        return Err(format!(
+            r#"tails file of revocation registry "{}" does not match its hash"#,
+            definition.id
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Writes given content to a temporary file first, so files are never left half written.
+fn write_file(path: &Path, content: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temporary_path = path.with_extension("tmp");
+    std::fs::write(&temporary_path, content)?;
+    std::fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
+fn encode_g2(point: &G2Projective) -> String {
+    URL_SAFE_NO_PAD.encode(point.to_affine().to_compressed())
+}
+
+fn decode_g2(encoded: &str) -> Result<G2Affine, Box<dyn std::error::Error>> {
+    let bytes: [u8; G2_LENGTH] = URL_SAFE_NO_PAD
+        .decode(encoded)?
+        .try_into()
+        .map_err(|_| "invalid point length")?;
+    Option::from(G2Affine::from_compressed(&bytes)).ok_or_else(|| "invalid point".into())
+}
+
+fn decode_gt(encoded: &str) -> Result<Gt, Box<dyn std::error::Error>> {
+    let bytes: [u8; Gt::BYTES] = URL_SAFE_NO_PAD
+        .decode(encoded)?
+        .try_into()
+        .map_err(|_| "invalid accumulator key length")?;
+    Option::from(Gt::from_bytes(&bytes)).ok_or_else(|| "invalid accumulator key".into())
+}
@@ -0,0 +1,739 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use super::parse_options;
+use crate::{
+    jwk, jwt, key_store::KeyReference, multikey, HostResolver, KeyStore, Vade, VadePlugin,
+    VadePluginResultValue,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+const SD_JWT_METHOD: &str = "sd-jwt";
+const SD_ALG: &str = "sha-256";
+const SD_JWT_VC_TYP: &str = "dc+sd-jwt";
+const LEGACY_SD_JWT_VC_TYP: &str = "vc+sd-jwt";
+const KB_JWT_TYP: &str = "kb+jwt";
+/// claims, that must always be disclosed, see SD-JWT VC
+const NON_DISCLOSABLE_CLAIMS: [&str; 9] = [
+    "_sd",
+    "_sd_alg",
+    "iss",
+    "nbf",
+    "exp",
+    "cnf",
+    "vct",
+    "vct#integrity",
+    "status",
+];
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueOptions {
+    secret_key: Option<String>,
+    key_id: Option<String>,
+    kid: Option<String>,
+    typ: Option<String>,
+    #[serde(default)]
+    disclosable: Vec<String>,
+    #[serde(default)]
+    decoys: usize,
+    holder_key: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PresentOptions {
+    secret_key: Option<String>,
+    key_id: Option<String>,
+    #[serde(default)]
+    disclose: Vec<String>,
+    aud: Option<String>,
+    nonce: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyOptions {
+    aud: Option<String>,
+    nonce: Option<String>,
+    key_binding: Option<bool>,
+}
+
+/// An SD-JWT split into its parts.
+struct SdJwt<'a> {
+    jwt: &'a str,
+    disclosures: Vec<&'a str>,
+    key_binding: Option<&'a str>,
+}
+
+impl<'a> SdJwt<'a> {
+    fn parse(sd_jwt: &'a str) -> Result<Self, Box<dyn std::error::Error>> {
+        let parts: Vec<&str> = sd_jwt.trim().split('~').collect();
+        if parts.len() < 2 {
+            return Err(Box::from("SD-JWT must contain at least one '~'"));
+        }
+        let last = parts[parts.len() - 1];
+        Ok(SdJwt {
+            jwt: parts[0],
+            disclosures: parts[1..parts.len() - 1].to_vec(),
+            key_binding: if last.is_empty() { None } else { Some(last) },
+        })
+    }
+
+    /// Returns the SD-JWT without key binding, which is the input for `sd_hash`.
+    fn without_key_binding(&self) -> String {
+        let mut serialized = format!("{}~", self.jwt);
+        for disclosure in self.disclosures.iter() {
+            serialized.push_str(disclosure);
+            serialized.push('~');
+        }
+        serialized
+    }
+}
+
+/// Issues, presents and verifies selective disclosure JWTs as defined in
+/// [SD-JWT](https://www.rfc-editor.org/rfc/rfc9901) and
+/// [SD-JWT VC](https://datatracker.ietf.org/doc/draft-ietf-oauth-sd-jwt-vc/).
+///
+/// The plugin handles the method "sd-jwt" in these functions:
+///
+/// - `vc_issue` takes the claims to issue as payload. Options contain the issuer's key as
+///   `secretKey` multikey or `keyId` of a key in the registered
+///   [`KeyStore`](https://docs.rs/vade/*/vade/trait.KeyStore.html), an optional `kid` (defaults
+///   to the `did:key` of the signing key), `disclosable` claims as JSON pointers, the number of
+///   `decoys` added per object with disclosable claims and the holder's public key multikey as
+///   `holderKey` for key binding. Claims nested in disclosable claims can be disclosable
+///   themselves. Claims with `vct` are issued as SD-JWT VC with `typ` "dc+sd-jwt".
+/// - `vp_create` takes an issued SD-JWT as payload and returns it with the disclosures needed for
+///   the JSON pointers in option `disclose`. If the holder's key is given as `secretKey` or
+///   `keyId`, a key binding JWT for given `aud` and `nonce` is appended.
+/// - `vc_verify` and `vp_verify` take an SD-JWT as payload and return JSON with `verified` and
+///   either the disclosed `claims` or an `error`. `vp_verify` requires key binding, `vc_verify`
+///   only if option `keyBinding` is `true`. `aud` and `nonce` in options are checked against the
+///   key binding JWT.
+///
+/// Issuers are resolved from `iss` and `kid` with the plugins registered next to this plugin on
+/// the same `Vade` instance, or with the resolver given to [`with_resolver`](#method.with_resolver)
+/// instead, `did:key` DIDs are resolved locally.
+///
+/// # Example
+///
+/// ```
+/// use vade::{plugins::SdJwtVadePlugin, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(SdJwtVadePlugin::new()));
+///     let options = r#"{ "nonce": "1234", "aud": "https://verifier.example" }"#;
+///     let results = vade.vp_verify("sd-jwt", options, "eyJ...~WyJ...~eyJ...").await?;
+///     if !results.is_empty() {
+///         println!("verification result: {}", results[0].as_ref().ok_or("result not found")?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct SdJwtVadePlugin {
+    key_store: Option<Rc<dyn KeyStore>>,
+    resolver: Option<Vade>,
+    host_resolver: Option<Rc<HostResolver>>,
+}
+
+impl SdJwtVadePlugin {
+    /// Creates a new `SdJwtVadePlugin`, that resolves issuers other than `did:key` with the
+    /// plugins registered next to it.
+    pub fn new() -> Self {
+        SdJwtVadePlugin {
+            key_store: None,
+            resolver: None,
+            host_resolver: None,
+        }
+    }
+
+    /// Resolves issuer keys with given `Vade` instance, that has DID resolver plugins registered,
+    /// instead of the plugins registered next to this plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolver` - `Vade` instance to call `did_resolve` on
+    pub fn with_resolver(mut self, resolver: Vade) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    async fn issue(
+        &self,
+        options: &str,
+        payload: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let options: IssueOptions = parse_options(options)?;
+        let key = KeyReference::from_options(
+            self.key_store.as_ref(),
+            options.secret_key.as_deref(),
+            options.key_id.as_deref(),
+        )
+        .await?;
+        let kid = options.kid.unwrap_or_else(|| {
+            format!(
+                "did:key:{}#{}",
+                key.info().public_key,
+                key.info().public_key
+            )
+        });
+        let mut claims = match serde_json::from_str(payload)? {
+            Value::Object(claims) => claims,
+            _ => return Err(Box::from("payload must be a JSON object")),
+        };
+
+        let pointers: HashSet<String> = options.disclosable.iter().cloned().collect();
+        for pointer in pointers.iter() {
+            let name = pointer.split('/').nth(1).unwrap_or_default();
+            if !pointer.starts_with('/') || pointer.len() == 1 {
+                return Err(Box::from(format!(r#"invalid JSON pointer "{}""#, pointer)));
+            }
+            if NON_DISCLOSABLE_CLAIMS.contains(&name) {
+                return Err(Box::from(format!(
+                    r#"claim "{}" must not be disclosable"#,
+                    name
+                )));
+            }
+            if Value::Object(claims.clone()).pointer(pointer).is_none() {
+                return Err(Box::from(format!(r#"claim "{}" not found"#, pointer)));
+            }
+        }
+        if let Some(holder_key) = options.holder_key {
+            let (key_type, public_key) = multikey::decode_public_key(&holder_key)?;
+            claims.insert(
+                "cnf".to_string(),
+                json!({ "jwk": jwk::public_key_to_jwk(key_type, &public_key)? }),
+            );
+        }
+        if !kid.starts_with('#') {
+            let did = kid.split('#').next().unwrap_or_default();
+            claims.entry("iss").or_insert_with(|| Value::from(did));
+        }
+        claims
+            .entry("iat")
+            .or_insert_with(|| Value::from(Utc::now().timestamp()));
+
+        let mut disclosures = Vec::new();
+        let mut claims = match make_disclosable(
+            Value::Object(claims),
+            "",
+            &pointers,
+            options.decoys,
+            &mut disclosures,
+        )? {
+            Value::Object(claims) => claims,
+            _ => return Err(Box::from("payload must be a JSON object")),
+        };
+        claims.insert("_sd_alg".to_string(), Value::from(SD_ALG));
+
+        let mut header = Map::new();
+        header.insert("kid".to_string(), Value::from(kid));
+        let typ = options
+            .typ
+            .or_else(|| claims.get("vct").map(|_| SD_JWT_VC_TYP.to_string()));
+        if let Some(typ) = typ {
+            header.insert("typ".to_string(), Value::from(typ));
+        }
+        let issued = jwt::sign(&key, &header, &claims).await?;
+
+        let sd_jwt = SdJwt {
+            jwt: &issued,
+            disclosures: disclosures.iter().map(|d| d.as_str()).collect(),
+            key_binding: None,
+        };
+        Ok(sd_jwt.without_key_binding())
+    }
+
+    async fn present(
+        &self,
+        options: &str,
+        payload: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let options: PresentOptions = parse_options(options)?;
+        let sd_jwt = SdJwt::parse(payload)?;
+        let claims: Map<String, Value> = serde_json::from_slice(&jwt::decode(sd_jwt.jwt)?.payload)?;
+        let (_, paths) = disclose(&claims, &sd_jwt.disclosures)?;
+
+        let is_prefix =
+            |prefix: &str, path: &str| path == prefix || path.starts_with(&format!("{}/", prefix));
+        let presented = SdJwt {
+            jwt: sd_jwt.jwt,
+            disclosures: sd_jwt
+                .disclosures
+                .iter()
+                .filter(|disclosure| {
+                    let path = &paths[&get_digest(disclosure)];
+                    options
+                        .disclose
+                        .iter()
+                        .any(|requested| is_prefix(path, requested) || is_prefix(requested, path))
+                })
+                .cloned()
+                .collect(),
+            key_binding: None,
+        };
+        let mut serialized = presented.without_key_binding();
+
+        if options.secret_key.is_none() && options.key_id.is_none() {
+            return Ok(serialized);
+        }
+        let key = KeyReference::from_options(
+            self.key_store.as_ref(),
+            options.secret_key.as_deref(),
+            options.key_id.as_deref(),
+        )
+        .await?;
+        let cnf = claims
+            .get("cnf")
+            .and_then(|cnf| cnf.get("jwk"))
+            .ok_or("SD-JWT has no holder key to bind to")?;
+        let (_, public_key) = jwk::public_key_from_jwk(cnf)?;
+        let (_, holder_key) = multikey::decode_public_key(&key.info().public_key)?;
+        if public_key != holder_key {
+            return Err(Box::from("given key is not the holder key of the SD-JWT"));
+        }
+        let mut header = Map::new();
+        header.insert("typ".to_string(), Value::from(KB_JWT_TYP));
+        let mut kb_claims = Map::new();
+        kb_claims.insert("iat".to_string(), Value::from(Utc::now().timestamp()));
+        kb_claims.insert("sd_hash".to_string(), Value::from(get_digest(&serialized)));
+        if let Some(aud) = options.aud {
+            kb_claims.insert("aud".to_string(), Value::from(aud));
+        }
+        if let Some(nonce) = options.nonce {
+            kb_claims.insert("nonce".to_string(), Value::from(nonce));
+        }
+        serialized.push_str(&jwt::sign(&key, &header, &kb_claims).await?);
+
+        Ok(serialized)
+    }
+
+    /// Verifies given SD-JWT and returns its disclosed claims.
+    async fn verify_sd_jwt(
+        &mut self,
+        options: &VerifyOptions,
+        require_key_binding: bool,
+        payload: &str,
+    ) -> Result<Value, String> {
+        let sd_jwt = SdJwt::parse(payload).map_err(|e| e.to_string())?;
+        let envelope =
+            jwt::decode(sd_jwt.jwt).map_err(|e| format!("could not decode SD-JWT; {}", e))?;
+        let claims: Map<String, Value> = serde_json::from_slice(&envelope.payload)
+            .map_err(|e| format!("payload is no JSON object; {}", e))?;
+        if (envelope.typ == SD_JWT_VC_TYP || envelope.typ == LEGACY_SD_JWT_VC_TYP)
+            && !claims
+                .get("vct")
+                .map(|vct| vct.is_string())
+                .unwrap_or(false)
+        {
+            return Err("SD-JWT VC has no vct".to_string());
+        }
+        match claims.get("_sd_alg").and_then(|alg| alg.as_str()) {
+            Some(SD_ALG) | None => (),
+            Some(alg) => return Err(format!(r#"unsupported _sd_alg "{}""#, alg)),
+        }
+        let issuer = claims
+            .get("iss")
+            .and_then(|iss| iss.as_str())
+            .ok_or("SD-JWT has no iss")?;
+        let mut host = self.host_resolver.as_ref().and_then(|host| host.take());
+        let key = jwt::resolve_signer_key(
+            self.resolver.as_mut().or(host.as_mut()),
+            &envelope.kid,
+            issuer,
+            "assertionMethod",
+        )
+        .await;
+        if let Some(host_resolver) = &self.host_resolver {
+            host_resolver.lend(host);
+        }
+        let (key_type, public_key) = key?;
+        jwt::verify_signature(&envelope, key_type, &public_key)?;
+        jwt::check_validity(&claims)?;
+        let (disclosed, _) = disclose(&claims, &sd_jwt.disclosures).map_err(|e| e.to_string())?;
+
+        match sd_jwt.key_binding {
+            Some(key_binding) => verify_key_binding(&claims, &sd_jwt, key_binding, options)?,
+            None if require_key_binding || options.key_binding.unwrap_or(false) => {
+                return Err("key binding required".to_string())
+            }
+            None => (),
+        }
+
+        Ok(disclosed)
+    }
+
+    async fn verify(
+        &mut self,
+        options: &str,
+        require_key_binding: bool,
+        payload: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let options: VerifyOptions = parse_options(options)?;
+        Ok(
+            match self
+                .verify_sd_jwt(&options, require_key_binding, payload)
+                .await
+            {
+                Ok(claims) => json!({ "verified": true, "claims": claims }),
+                Err(error) => json!({ "verified": false, "error": error }),
+            },
+        )
+    }
+}
+
+impl Default for SdJwtVadePlugin {
+    fn default() -> Self {
+        SdJwtVadePlugin::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl VadePlugin for SdJwtVadePlugin {
+    /// Keeps given key store to sign with keys given as `keyId` in options.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_store` - key store to use
+    fn set_key_store(&mut self, key_store: Rc<dyn KeyStore>) {
+        self.key_store = Some(key_store);
+    }
+
+    /// Sets the resolver of the hosting `Vade` instance to resolve verification methods with.
+    ///
+    /// # Arguments
+    ///
+    /// * `host_resolver` - resolver using the other plugins of `Vade`
+    fn set_host_resolver(&mut self, host_resolver: Rc<HostResolver>) {
+        self.host_resolver = Some(host_resolver);
+    }
+
+    /// Issues given claims as SD-JWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "sd-jwt", other methods are ignored
+    /// * `options` - JSON with `secretKey` or `keyId`, optional `kid`, `typ`, `disclosable`,
+    ///   `decoys` and `holderKey`
+    /// * `payload` - claims to issue
+    async fn vc_issue(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != SD_JWT_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        Ok(VadePluginResultValue::Success(Some(
+            self.issue(options, payload).await?,
+        )))
+    }
+
+    /// Verifies given SD-JWT, key binding is only required if requested in options.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "sd-jwt", other methods are ignored
+    /// * `options` - JSON with optional `keyBinding`, `aud` and `nonce`
+    /// * `payload` - SD-JWT to verify
+    async fn vc_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != SD_JWT_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let result = self.verify(options, false, payload).await?;
+        Ok(VadePluginResultValue::Success(Some(result.to_string())))
+    }
+
+    /// Selects disclosures of given SD-JWT and adds a key binding JWT if a holder key is given.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "sd-jwt", other methods are ignored
+    /// * `options` - JSON with `disclose` and optional `secretKey` or `keyId`, `aud` and `nonce`
+    /// * `payload` - SD-JWT as issued
+    async fn vp_create(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != SD_JWT_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        Ok(VadePluginResultValue::Success(Some(
+            self.present(options, payload).await?,
+        )))
+    }
+
+    /// Verifies given SD-JWT with its required key binding JWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "sd-jwt", other methods are ignored
+    /// * `options` - JSON with optional `aud` and `nonce`
+    /// * `payload` - SD-JWT to verify
+    async fn vp_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != SD_JWT_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let result = self.verify(options, true, payload).await?;
+        Ok(VadePluginResultValue::Success(Some(result.to_string())))
+    }
+}
+
+/// Returns the base64url encoded SHA-256 digest of given ASCII value.
+fn get_digest(value: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(value.as_bytes()))
+}
+
+fn get_salt() -> String {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    URL_SAFE_NO_PAD.encode(salt)
+}
+
+/// Creates a disclosure for given array and returns it with its digest.
+fn create_disclosure(disclosure: &Value) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let encoded = URL_SAFE_NO_PAD.encode(serde_json::to_vec(disclosure)?);
+    let digest = get_digest(&encoded);
+    Ok((encoded, digest))
+}
+
+/// Replaces claims at given JSON pointers with digests, children first to support nested
+/// disclosures.
+fn make_disclosable(
+    value: Value,
+    path: &str,
+    pointers: &HashSet<String>,
+    decoys: usize,
+    disclosures: &mut Vec<String>,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    match value {
+        Value::Object(object) => {
+            let mut result = Map::new();
+            let mut digests = Vec::new();
+            for (name, child) in object.into_iter() {
+                if name == "_sd" || name == "..." {
+                    return Err(Box::from(format!(r#"claim name "{}" is reserved"#, name)));
+                }
+                let child_path = format!("{}/{}", path, name.replace('~', "~0").replace('/', "~1"));
+                let child = make_disclosable(child, &child_path, pointers, decoys, disclosures)?;
+                if pointers.contains(&child_path) {
+                    let (disclosure, digest) =
+                        create_disclosure(&json!([get_salt(), name, child]))?;
+                    disclosures.push(disclosure);
+                    digests.push(digest);
+                } else {
+                    result.insert(name, child);
+                }
+            }
+            if !digests.is_empty() {
+                for _ in 0..decoys {
+                    digests.push(get_digest(&get_salt()));
+                }
+                digests.sort();
+                result.insert("_sd".to_string(), json!(digests));
+            }
+            Ok(Value::Object(result))
+        }
+        Value::Array(items) => {
+            let mut result = Vec::new();
+            for (index, item) in items.into_iter().enumerate() {
+                let item_path = format!("{}/{}", path, index);
+                let item = make_disclosable(item, &item_path, pointers, decoys, disclosures)?;
+                if pointers.contains(&item_path) {
+                    let (disclosure, digest) = create_disclosure(&json!([get_salt(), item]))?;
+                    disclosures.push(disclosure);
+                    result.push(json!({ "...": digest }));
+                } else {
+                    result.push(item);
+                }
+            }
+            Ok(Value::Array(result))
+        }
+        value => Ok(value),
+    }
+}
+
+/// Replaces digests in given claims with the values of given disclosures. Returns the disclosed
+/// claims and the JSON pointer of each disclosure by its digest.
+fn disclose(
+    claims: &Map<String, Value>,
+    disclosures: &[&str],
+) -> Result<(Value, HashMap<String, String>), Box<dyn std::error::Error>> {
+    let mut by_digest = HashMap::new();
+    for disclosure in disclosures.iter() {
+        let decoded: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(disclosure)?)
+            .map_err(|e| format!("invalid disclosure; {}", e))?;
+        if by_digest.insert(get_digest(disclosure), decoded).is_some() {
+            return Err(Box::from("disclosure given more than once"));
+        }
+    }
+    let mut claims = claims.clone();
+    claims.remove("_sd_alg");
+    let mut paths = HashMap::new();
+    let disclosed = resolve_digests(Value::Object(claims), "", &mut by_digest, &mut paths)?;
+    if !by_digest.is_empty() {
+        return Err(Box::from("disclosure not referenced by SD-JWT"));
+    }
+    Ok((disclosed, paths))
+}
+
+fn resolve_digests(
+    value: Value,
+    path: &str,
+    disclosures: &mut HashMap<String, Value>,
+    paths: &mut HashMap<String, String>,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    match value {
+        Value::Object(mut object) => {
+            let digests = match object.remove("_sd") {
+                Some(Value::Array(digests)) => digests,
+                Some(_) => return Err(Box::from("_sd must be an array")),
+                None => Vec::new(),
+            };
+            let mut result = Map::new();
+            for (name, child) in object.into_iter() {
+                let child_path = format!("{}/{}", path, name.replace('~', "~0").replace('/', "~1"));
+                result.insert(
+                    name,
+                    resolve_digests(child, &child_path, disclosures, paths)?,
+                );
+            }
+            for digest in digests.iter() {
+                let digest = digest.as_str().ok_or("digests must be strings")?;
+                // decoys and undisclosed claims have no disclosure
+                let disclosure = match disclosures.remove(digest) {
+                    Some(disclosure) => disclosure,
+                    None => continue,
+                };
+                let (name, child) = match disclosure.as_array().map(|d| &d[..]) {
+                    Some([_, Value::String(name), child]) => (name.clone(), child.clone()),
+                    _ => return Err(Box::from("object disclosure must have 3 elements")),
+                };
+                if name == "_sd" || name == "..." || result.contains_key(&name) {
+                    return Err(Box::from(format!(
+                        r#"disclosed claim "{}" is not allowed"#,
+                        name
+                    )));
+                }
+                let child_path = format!("{}/{}", path, name.replace('~', "~0").replace('/', "~1"));
+                let child = resolve_digests(child, &child_path, disclosures, paths)?;
+                paths.insert(digest.to_string(), child_path);
+                result.insert(name, child);
+            }
+            Ok(Value::Object(result))
+        }
+        Value::Array(items) => {
+            let mut result = Vec::new();
+            for (index, item) in items.into_iter().enumerate() {
+                let item_path = format!("{}/{}", path, index);
+                let digest = match &item {
+                    Value::Object(object) if object.len() == 1 && object.contains_key("...") => {
+                        object["..."]
+                            .as_str()
+                            .ok_or("digests must be strings")?
+                            .to_string()
+                    }
+                    _ => {
+                        result.push(resolve_digests(item, &item_path, disclosures, paths)?);
+                        continue;
+                    }
+                };
+                if let Some(disclosure) = disclosures.remove(&digest) {
+                    let child = match disclosure.as_array().map(|d| &d[..]) {
+                        Some([_, child]) => child.clone(),
+                        _ => return Err(Box::from("array disclosure must have 2 elements")),
+                    };
+                    result.push(resolve_digests(child, &item_path, disclosures, paths)?);
+                    paths.insert(digest, item_path);
+                }
+            }
+            Ok(Value::Array(result))
+        }
+        value => Ok(value),
+    }
+}
+
+fn verify_key_binding(
+    claims: &Map<String, Value>,
+    sd_jwt: &SdJwt,
+    key_binding: &str,
+    options: &VerifyOptions,
+) -> Result<(), String> {
+    let envelope =
+        jwt::decode(key_binding).map_err(|e| format!("could not decode key binding JWT; {}", e))?;
+    if envelope.typ != KB_JWT_TYP {
+        return Err(format!(
+            r#"expected typ "{}", got "{}""#,
+            KB_JWT_TYP, envelope.typ
+        ));
+    }
+    let cnf = claims
+        .get("cnf")
+        .and_then(|cnf| cnf.get("jwk"))
+        .ok_or("SD-JWT has no holder key")?;
+    let (key_type, public_key) = jwk::public_key_from_jwk(cnf).map_err(|e| e.to_string())?;
+    jwt::verify_signature(&envelope, key_type, &public_key)
+        .map_err(|e| format!("invalid key binding; {}", e))?;
+
+    let kb_claims: Value = serde_json::from_slice(&envelope.payload)
+        .map_err(|e| format!("key binding payload is no JSON object; {}", e))?;
+    if kb_claims["sd_hash"] != get_digest(&sd_jwt.without_key_binding()) {
+        return Err("key binding does not match SD-JWT".to_string());
+    }
+    if kb_claims["iat"]
+        .as_i64()
+        .map(|iat| iat > Utc::now().timestamp())
+        != Some(false)
+    {
+        return Err("key binding has no valid iat".to_string());
+    }
+    if let Some(nonce) = &options.nonce {
+        if kb_claims["nonce"] != nonce.as_str() {
+            return Err("key binding nonce does not match".to_string());
+        }
+    }
+    if let Some(aud) = &options.aud {
+        if kb_claims["aud"] != aud.as_str() {
+            return Err("key binding audience does not match".to_string());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,449 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{jcs, DidResolutionResult, VadePlugin, VadePluginResultValue};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+/// multihash code for SHA2-256
+const SHA2_256_CODE: u8 = 0x12;
+/// verification relationships a Sidetree public key may be used for
+const PUBLIC_KEY_PURPOSES: [&str; 5] = [
+    "authentication",
+    "assertionMethod",
+    "capabilityInvocation",
+    "capabilityDelegation",
+    "keyAgreement",
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitialState {
+    suffix_data: SuffixData,
+    delta: Delta,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SuffixData {
+    delta_hash: String,
+    recovery_commitment: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Delta {
+    patches: Vec<Value>,
+    update_commitment: String,
+}
+
+#[derive(Default)]
+struct DocumentState {
+    public_keys: Vec<Map<String, Value>>,
+    services: Vec<Map<String, Value>>,
+}
+
+/// Resolves long-form [Sidetree](https://identity.foundation/sidetree/spec/) DIDs like
+/// `did:ion:<suffix>:<initial state>` offline, without asking a Sidetree node.
+///
+/// A long-form DID carries the create operation of a DID as its last segment. This plugin decodes
+/// it, checks that the DID suffix and the delta hash commit to its content, applies its patches and
+/// builds the DID document from them. The resulting document is not necessarily the latest one,
+/// as the DID may have been anchored and updated since.
+///
+/// Short-form DIDs cannot be resolved without a network and are ignored, so they can be handled
+/// by other plugins, e.g. a
+/// [`UniversalResolverVadePlugin`](https://docs.rs/vade/*/vade/plugins/struct.UniversalResolverVadePlugin.html).
+///
+/// # Example
+///
+/// ```
+/// use vade::{plugins::SidetreeVadePlugin, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(SidetreeVadePlugin::new()));
+///     let results = vade.did_resolve("did:ion:EiD...:eyJ...").await?;
+///     if !results.is_empty() {
+///         println!("got did: {}", results[0].as_ref().ok_or("result not found")?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct SidetreeVadePlugin {
+    methods: Vec<String>,
+    return_resolution_result: bool,
+}
+
+impl SidetreeVadePlugin {
+    /// Creates a new `SidetreeVadePlugin`, that resolves `did:ion` DIDs.
+    pub fn new() -> Self {
+        SidetreeVadePlugin {
+            methods: vec!["did:ion".to_string()],
+            return_resolution_result: false,
+        }
+    }
+
+    /// Resolves DIDs of given Sidetree based methods instead of `did:ion`.
+    ///
+    /// # Arguments
+    ///
+    /// * `methods` - DID methods to resolve, e.g. "did:ion" or "did:elem"
+    pub fn with_methods(mut self, methods: &[&str]) -> Self {
+        self.methods = methods.iter().map(|method| method.to_string()).collect();
+        self
+    }
+
+    /// Return the full DID resolution result as JSON instead of the DID document only.
+    pub fn with_resolution_result(mut self) -> Self {
+        self.return_resolution_result = true;
+        self
+    }
+
+    /// Resolves given long-form DID. Returns `None` if given DID is not a long-form DID of a
+    /// configured method.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to resolve
+    pub fn resolve(
+        &self,
+        did: &str,
+    ) -> Result<Option<DidResolutionResult>, Box<dyn std::error::Error>> {
+        let method = match self
+            .methods
+            .iter()
+            .find(|method| did.starts_with(&format!("{}:", method)))
+        {
+            Some(method) => method,
+            None => return Ok(None),
+        };
+        let segments: Vec<&str> = did[method.len() + 1..].split(':').collect();
+        if segments.len() < 2 {
+            return Ok(None);
+        }
+        let encoded_state = segments[segments.len() - 1];
+        let suffix = segments[segments.len() - 2];
+        let initial_state = match decode_initial_state(encoded_state) {
+            Some(initial_state) => initial_state,
+            // last segment is not an initial state, so this is a short-form DID with a network
+            None => return Ok(None),
+        };
+        let short_form_did = &did[..did.len() - encoded_state.len() - 1];
+
+        let resolve_error = |message: String| -> Box<dyn std::error::Error> {
+            Box::from(format!(
+                r#"could not resolve "{}"; {}"#,
+                short_form_did, message
+            ))
+        };
+        let state: InitialState = serde_json::from_value(initial_state.clone())
+            .map_err(|e| resolve_error(format!("invalid initial state; {}", e)))?;
+        let suffix_data = initial_state
+            .get("suffixData")
+            .ok_or_else(|| resolve_error("missing suffix data".to_string()))?;
+        let delta = initial_state
+            .get("delta")
+            .ok_or_else(|| resolve_error("missing delta".to_string()))?;
+
+        if !matches_hash(suffix, suffix_data) {
+            return Err(resolve_error(
+                "DID suffix does not match hash of suffix data".to_string(),
+            ));
+        }
+        if !matches_hash(&state.suffix_data.delta_hash, delta) {
+            return Err(resolve_error(
+                "delta hash does not match hash of delta".to_string(),
+            ));
+        }
+
+        let mut document_state = DocumentState::default();
+        for patch in state.delta.patches.iter() {
+            apply_patch(&mut document_state, patch).map_err(resolve_error)?;
+        }
+
+        Ok(Some(DidResolutionResult {
+            did_document: Some(get_did_document(did, &document_state)),
+            did_resolution_metadata: json!({ "contentType": "application/did+ld+json" })
+                .as_object()
+                .cloned()
+                .unwrap_or_default(),
+            did_document_metadata: json!({
+                "equivalentId": [short_form_did],
+                "method": {
+                    "published": false,
+                    "recoveryCommitment": state.suffix_data.recovery_commitment,
+                    "updateCommitment": state.delta.update_commitment,
+                },
+            })
+            .as_object()
+            .cloned()
+            .unwrap_or_default(),
+        }))
+    }
+}
+
+impl Default for SidetreeVadePlugin {
+    fn default() -> Self {
+        SidetreeVadePlugin::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl VadePlugin for SidetreeVadePlugin {
+    /// Resolves given DID from its initial state, if it is a long-form DID of a configured method.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to resolve
+    async fn did_resolve(
+        &mut self,
+        did: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        let result = match self.resolve(did)? {
+            Some(result) => result,
+            None => return Ok(VadePluginResultValue::Ignored),
+        };
+        let serialized = if self.return_resolution_result {
+            serde_json::to_string(&result)?
+        } else {
+            serde_json::to_string(&result.did_document)?
+        };
+        Ok(VadePluginResultValue::Success(Some(serialized)))
+    }
+}
+
+fn decode_initial_state(encoded: &str) -> Option<Value> {
+    let decoded = URL_SAFE_NO_PAD.decode(encoded).ok()?;
+    match serde_json::from_slice::<Value>(&decoded).ok()? {
+        Value::Object(object) if object.contains_key("suffixData") => Some(Value::Object(object)),
+        _ => None,
+    }
+}
+
+/// Checks if given encoded multihash is the hash of the canonicalized value.
+fn matches_hash(encoded_multihash: &str, value: &Value) -> bool {
+    let multihash = match URL_SAFE_NO_PAD.decode(encoded_multihash) {
+        Ok(multihash) => multihash,
+        Err(_) => return false,
+    };
+    if multihash.len() < 2 || multihash[0] != SHA2_256_CODE {
+        return false;
+    }
+    let digest = Sha256::digest(jcs::canonicalize(value).as_bytes());
+    multihash[1] as usize == digest.len() && multihash[2..] == digest[..]
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 50
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn get_entries(value: Option<&Value>, name: &str) -> Result<Vec<Map<String, Value>>, String> {
+    let entries = match value {
+        Some(Value::Array(entries)) => entries,
+        None => return Ok(Vec::new()),
+        _ => return Err(format!("{} must be an array", name)),
+    };
+    entries
+        .iter()
+        .map(|entry| match entry {
+            Value::Object(entry) => match entry.get("id").and_then(|id| id.as_str()) {
+                Some(id) if is_valid_id(id) => Ok(entry.clone()),
+                _ => Err(format!("{} entry has an invalid id", name)),
+            },
+            _ => Err(format!("{} entries must be objects", name)),
+        })
+        .collect()
+}
+
+fn validate_public_key(key: &Map<String, Value>) -> Result<(), String> {
+    let id = key["id"].as_str().unwrap_or_default();
+    if !key.get("type").map(|t| t.is_string()).unwrap_or(false) {
+        return Err(format!(r#"public key "{}" has no type"#, id));
+    }
+    match key.get("publicKeyJwk") {
+        Some(Value::Object(jwk)) if jwk.contains_key("d") => {
+            return Err(format!(
+                r#"public key "{}" contains private key material"#,
+                id
+            ))
+        }
+        Some(Value::Object(_)) => (),
+        _ => {
+            if !key.contains_key("publicKeyMultibase") {
+                return Err(format!(r#"public key "{}" has no key material"#, id));
+            }
+        }
+    }
+    if let Some(purposes) = key.get("purposes") {
+        let purposes = purposes
+            .as_array()
+            .ok_or_else(|| format!(r#"purposes of public key "{}" must be an array"#, id))?;
+        for purpose in purposes.iter() {
+            match purpose.as_str() {
+                Some(purpose) if PUBLIC_KEY_PURPOSES.contains(&purpose) => (),
+                _ => return Err(format!(r#"public key "{}" has an invalid purpose"#, id)),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_service(service: &Map<String, Value>) -> Result<(), String> {
+    let id = service["id"].as_str().unwrap_or_default();
+    if !service.get("type").map(|t| t.is_string()).unwrap_or(false) {
+        return Err(format!(r#"service "{}" has no type"#, id));
+    }
+    match service.get("serviceEndpoint") {
+        Some(Value::String(_)) | Some(Value::Object(_)) => Ok(()),
+        _ => Err(format!(r#"service "{}" has an invalid endpoint"#, id)),
+    }
+}
+
+fn get_ids(value: Option<&Value>) -> Result<Vec<String>, String> {
+    match value {
+        Some(Value::Array(ids)) => ids
+            .iter()
+            .map(|id| {
+                id.as_str()
+                    .map(|id| id.to_string())
+                    .ok_or_else(|| "ids must be strings".to_string())
+            })
+            .collect(),
+        _ => Err("ids must be an array".to_string()),
+    }
+}
+
+fn upsert(entries: &mut Vec<Map<String, Value>>, new_entries: Vec<Map<String, Value>>) {
+    for new_entry in new_entries.into_iter() {
+        entries.retain(|entry| entry["id"] != new_entry["id"]);
+        entries.push(new_entry);
+    }
+}
+
+fn apply_patch(state: &mut DocumentState, patch: &Value) -> Result<(), String> {
+    let action = patch
+        .get("action")
+        .and_then(|action| action.as_str())
+        .unwrap_or_default();
+    match action {
+        "replace" => {
+            let document = patch
+                .get("document")
+                .ok_or_else(|| "replace patch has no document".to_string())?;
+            let public_keys = get_entries(document.get("publicKeys"), "publicKeys")?;
+            let services = get_entries(document.get("services"), "services")?;
+            public_keys.iter().try_for_each(validate_public_key)?;
+            services.iter().try_for_each(validate_service)?;
+            state.public_keys = Vec::new();
+            state.services = Vec::new();
+            upsert(&mut state.public_keys, public_keys);
+            upsert(&mut state.services, services);
+        }
+        "add-public-keys" => {
+            let public_keys = get_entries(patch.get("publicKeys"), "publicKeys")?;
+            public_keys.iter().try_for_each(validate_public_key)?;
+            upsert(&mut state.public_keys, public_keys);
+        }
+        "remove-public-keys" => {
+            let ids = get_ids(patch.get("ids"))?;
+            state
+                .public_keys
+                .retain(|key| !ids.iter().any(|id| key["id"] == id.as_str()));
+        }
+        "add-services" => {
+            let services = get_entries(patch.get("services"), "services")?;
+            services.iter().try_for_each(validate_service)?;
+            upsert(&mut state.services, services);
+        }
+        "remove-services" => {
+            let ids = get_ids(patch.get("ids"))?;
+            state
+                .services
+                .retain(|service| !ids.iter().any(|id| service["id"] == id.as_str()));
+        }
+        _ => return Err(format!(r#"unsupported patch action "{}""#, action)),
+    }
+    Ok(())
+}
+
+fn get_did_document(did: &str, state: &DocumentState) -> Value {
+    let mut document = Map::new();
+    document.insert("id".to_string(), Value::from(did));
+    document.insert(
+        "@context".to_string(),
+        json!(["https://www.w3.org/ns/did/v1", { "@base": did }]),
+    );
+
+    let mut verification_methods = Vec::new();
+    for key in state.public_keys.iter() {
+        let reference = format!("#{}", key["id"].as_str().unwrap_or_default());
+        let mut method = Map::new();
+        method.insert("id".to_string(), Value::from(reference.clone()));
+        method.insert("controller".to_string(), Value::from(did));
+        method.insert("type".to_string(), key["type"].clone());
+        for material in ["publicKeyJwk", "publicKeyMultibase"].iter() {
+            if let Some(value) = key.get(*material) {
+                method.insert(material.to_string(), value.clone());
+            }
+        }
+        verification_methods.push(Value::Object(method));
+
+        let purposes = key
+            .get("purposes")
+            .and_then(|purposes| purposes.as_array())
+            .cloned()
+            .unwrap_or_default();
+        for purpose in purposes.iter().filter_map(|purpose| purpose.as_str()) {
+            let relationship = document
+                .entry(purpose.to_string())
+                .or_insert_with(|| Value::Array(Vec::new()));
+            if let Value::Array(references) = relationship {
+                references.push(Value::from(reference.clone()));
+            }
+        }
+    }
+    if !verification_methods.is_empty() {
+        document.insert(
+            "verificationMethod".to_string(),
+            Value::Array(verification_methods),
+        );
+    }
+
+    let services: Vec<Value> = state
+        .services
+        .iter()
+        .map(|service| {
+            json!({
+                "id": format!("#{}", service["id"].as_str().unwrap_or_default()),
+                "type": service["type"],
+                "serviceEndpoint": service["serviceEndpoint"],
+            })
+        })
+        .collect();
+    if !services.is_empty() {
+        document.insert("service".to_string(), Value::Array(services));
+    }
+
+    Value::Object(document)
+}
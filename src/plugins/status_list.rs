@@ -0,0 +1,930 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use super::parse_options;
+use crate::{jwt, Vade, VadePlugin, VadePluginResultValue};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{SecondsFormat, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
+};
+
+const STATUS_LIST_METHOD: &str = "status-list";
+const CREDENTIALS_V1_CONTEXT: &str = "https://www.w3.org/2018/credentials/v1";
+const CREDENTIALS_V2_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+const STATUS_LIST_2021_CONTEXT: &str = "https://w3id.org/vc/status-list/2021/v1";
+/// minimum number of entries of a status list, so credentials hide in a large enough group
+const MIN_LENGTH: usize = 131_072;
+/// maximum size of decompressed status lists in bytes
+const MAX_LIST_SIZE: u64 = 16 * 1024 * 1024;
+const PURPOSES: [&str; 2] = ["revocation", "suspension"];
+
+/// Kinds of status lists, that differ in their types, contexts and the encoding of their bits.
+#[derive(Clone, Copy, PartialEq)]
+enum ListType {
+    /// [Bitstring Status List v1.0](https://www.w3.org/TR/vc-bitstring-status-list/)
+    Bitstring,
+    /// [Status List 2021](https://www.w3.org/community/reports/credentials/CG-FINAL-vc-status-list-2021-20230102/)
+    StatusList2021,
+}
+
+impl ListType {
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "BitstringStatusList" => Ok(ListType::Bitstring),
+            "StatusList2021" => Ok(ListType::StatusList2021),
+            _ => Err(format!(r#"unsupported status list type "{}""#, name)),
+        }
+    }
+
+    fn from_entry_type(entry_type: &str) -> Option<Self> {
+        match entry_type {
+            "BitstringStatusListEntry" => Some(ListType::Bitstring),
+            "StatusList2021Entry" => Some(ListType::StatusList2021),
+            _ => None,
+        }
+    }
+
+    fn list_type(&self) -> &'static str {
+        match self {
+            ListType::Bitstring => "BitstringStatusList",
+            ListType::StatusList2021 => "StatusList2021",
+        }
+    }
+
+    fn entry_type(&self) -> String {
+        format!("{}Entry", self.list_type())
+    }
+
+    fn credential_type(&self) -> String {
+        format!("{}Credential", self.list_type())
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateOptions {
+    status_purpose: Option<String>,
+    #[serde(rename = "type")]
+    list_type: Option<String>,
+    length: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatePayload {
+    id: String,
+    issuer: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AllocateOptions {
+    status_lists: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateOptions {
+    status_purpose: String,
+    status: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckOptions {
+    status_list_credentials: Option<Vec<Value>>,
+}
+
+/// State of a [`StatusList`] as persisted in a [`StatusListStore`].
+#[derive(Deserialize, Serialize)]
+struct StoredStatusList {
+    credential: Value,
+    allocated: Vec<usize>,
+}
+
+/// A status list created by this plugin, whose bits are managed by the issuer.
+struct StatusList {
+    list_type: ListType,
+    id: String,
+    issuer: Value,
+    purpose: String,
+    bits: Vec<u8>,
+    allocated: HashSet<usize>,
+    valid_from: String,
+}
+
+impl StatusList {
+    fn length(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    /// Restores a status list from its state, as stored by [`StatusList::to_state`].
+    fn from_state(state: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let stored: StoredStatusList = serde_json::from_str(state)?;
+        let credential = stored.credential;
+        let subject = credential
+            .get("credentialSubject")
+            .ok_or("status list credential has no credentialSubject")?;
+        let get = |value: &Value, name: &str| -> Result<String, String> {
+            value
+                .get(name)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| format!("stored status list has no {}", name))
+        };
+        let list_type = ListType::from_name(&get(subject, "type")?)?;
+        let bits = decode_list(&get(subject, "encodedList")?)?;
+        if stored
+            .allocated
+            .iter()
+            .any(|index| *index >= bits.len() * 8)
+        {
+            return Err(Box::from("stored status list index out of range"));
+        }
+        Ok(StatusList {
+            list_type,
+            id: get(&credential, "id")?,
+            issuer: credential.get("issuer").cloned().unwrap_or_default(),
+            purpose: get(subject, "statusPurpose")?,
+            bits,
+            allocated: stored.allocated.into_iter().collect(),
+            valid_from: match list_type {
+                ListType::Bitstring => get(&credential, "validFrom")?,
+                ListType::StatusList2021 => get(&credential, "issuanceDate")?,
+            },
+        })
+    }
+
+    /// Returns the state to persist, i.e. the unsecured status list credential and the allocated
+    /// indexes.
+    fn to_state(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut allocated: Vec<usize> = self.allocated.iter().copied().collect();
+        allocated.sort_unstable();
+        Ok(serde_json::to_string(&StoredStatusList {
+            credential: self.to_credential()?,
+            allocated,
+        })?)
+    }
+
+    fn to_credential(&self) -> Result<Value, Box<dyn std::error::Error>> {
+        let encoded_list = encode_list(self.list_type, &self.bits)?;
+        Ok(match self.list_type {
+            ListType::Bitstring => json!({
+                "@context": [CREDENTIALS_V2_CONTEXT],
+                "id": self.id,
+                "type": ["VerifiableCredential", self.list_type.credential_type()],
+                "issuer": self.issuer,
+                "validFrom": self.valid_from,
+                "credentialSubject": {
+                    "id": format!("{}#list", self.id),
+                    "type": self.list_type.list_type(),
+                    "statusPurpose": self.purpose,
+                    "encodedList": encoded_list,
+                },
+            }),
+            ListType::StatusList2021 => json!({
+                "@context": [CREDENTIALS_V1_CONTEXT, STATUS_LIST_2021_CONTEXT],
+                "id": self.id,
+                "type": ["VerifiableCredential", self.list_type.credential_type()],
+                "issuer": self.issuer,
+                "issuanceDate": self.valid_from,
+                "credentialSubject": {
+                    "id": format!("{}#list", self.id),
+                    "type": self.list_type.list_type(),
+                    "statusPurpose": self.purpose,
+                    "encodedList": encoded_list,
+                },
+            }),
+        })
+    }
+
+    /// Allocates a random, unused index, so indexes do not reveal the order of issuance.
+    fn allocate(&mut self) -> Result<usize, String> {
+        if self.allocated.len() >= self.length() {
+            return Err(format!(r#"status list "{}" is full"#, self.id));
+        }
+        loop {
+            let index = (OsRng.next_u64() % self.length() as u64) as usize;
+            if self.allocated.insert(index) {
+                return Ok(index);
+            }
+        }
+    }
+}
+
+/// Persists the status lists of a [`StatusListVadePlugin`], so issuers can keep allocating
+/// indexes and updating statuses after restarts. States are JSON with the unsecured status list
+/// credential and the allocated indexes and are stored under the id of the status list credential.
+pub trait StatusListStore {
+    /// Loads the state of the status list with given id.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - id of status list credential
+    fn load(&self, id: &str) -> Result<Option<String>, Box<dyn std::error::Error>>;
+
+    /// Stores given state of a status list, replacing a previously stored state.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - id of status list credential
+    /// * `state` - state of status list as JSON
+    fn save(&self, id: &str, state: &str) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// Manages and checks [Bitstring Status Lists](https://www.w3.org/TR/vc-bitstring-status-list/)
+/// and their predecessor Status List 2021 to revoke and suspend credentials without zero-knowledge
+/// proofs. All functions are called with the method "status-list":
+///
+/// - `vc_status_list_create` creates a status list for the `statusPurpose` ("revocation" or
+///   "suspension", default "revocation") and `type` ("BitstringStatusList" or "StatusList2021")
+///   given in options with at least 131,072 entries. The payload holds the `id` (URL the status
+///   list credential will be published at) and the `issuer`. The unsecured status list credential
+///   is returned and has to be secured, e.g. with `vc_issue`, and published by the issuer.
+/// - `vc_status_list_allocate` allocates a random index in every status list given as
+///   `statusLists` in options and adds matching `credentialStatus` entries to the credential given
+///   as payload, that can be secured afterwards. If a list is unknown or full, no index is
+///   allocated in any list.
+/// - `vc_status_list_update` sets (or with `"status": false` clears) the bit of the entry with the
+///   `statusPurpose` given in options of the credential given as payload and returns the status
+///   list credential with the updated, compressed bitstring to publish. Revocations are final.
+/// - `vc_status_list_check` checks the `credentialStatus` entries of the credential given as
+///   payload and returns JSON with `verified`, the `statuses` of all entries and an `error`, if the
+///   credential is revoked or suspended.
+///
+/// Status list credentials for checks are taken from the `statusListCredentials` in options, from
+/// lists added with [`add_status_list_credential`](#method.add_status_list_credential), from lists
+/// created by the plugin itself or, after enabling [`with_fetch`](#method.with_fetch), are
+/// downloaded from their URL. Status list credentials not created by the plugin are verified with
+/// the `Vade` instance given to [`with_verifier`](#method.with_verifier). Lists given in options
+/// or downloaded require a verifier, lists added by the application are trusted as they are
+/// without it. Lists have to be issued by the issuer of the checked credential. Status entries of
+/// other types than `BitstringStatusListEntry` and `StatusList2021Entry` cannot be checked and
+/// fail the check.
+///
+/// Created lists are kept in memory, unless a [`StatusListStore`] is given with
+/// [`with_store`](#method.with_store). With a store, every change of a list is saved before it is
+/// returned and lists unknown to the plugin are loaded from the store.
+///
+/// # Example
+///
+/// ```
+/// use vade::{plugins::StatusListVadePlugin, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(StatusListVadePlugin::new()));
+///     let results = vade
+///         .vc_status_list_create(
+///             "status-list",
+///             r#"{ "statusPurpose": "revocation" }"#,
+///             r#"{ "id": "https://example.com/status/1", "issuer": "did:example:issuer" }"#,
+///         )
+///         .await?;
+///     if !results.is_empty() {
+///         println!("status list: {}", results[0].as_ref().ok_or("result not found")?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct StatusListVadePlugin {
+    client: reqwest::Client,
+    fetch: bool,
+    lists: HashMap<String, StatusList>,
+    published: HashMap<String, Value>,
+    store: Option<Box<dyn StatusListStore>>,
+    verifier: Option<(Vade, String)>,
+}
+
+impl StatusListVadePlugin {
+    /// Creates a new `StatusListVadePlugin`, that checks statuses with lists known to it only.
+    pub fn new() -> Self {
+        StatusListVadePlugin {
+            client: reqwest::Client::new(),
+            fetch: false,
+            lists: HashMap::new(),
+            published: HashMap::new(),
+            store: None,
+            verifier: None,
+        }
+    }
+
+    /// Persists created status lists in given store and loads lists unknown to the plugin from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - store to save and load status lists with
+    pub fn with_store(mut self, store: Box<dyn StatusListStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Download status list credentials, that are not known to the plugin, from their URL.
+    pub fn with_fetch(mut self) -> Self {
+        self.fetch = true;
+        self
+    }
+
+    /// Verifies status list credentials not created by this plugin with given `Vade` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `verifier` - `Vade` instance to call `vc_verify` on
+    /// * `method` - method to call `vc_verify` with, e.g. "data-integrity" or "jwt"
+    pub fn with_verifier(mut self, verifier: Vade, method: &str) -> Self {
+        self.verifier = Some((verifier, method.to_string()));
+        self
+    }
+
+    /// Verifies given, published status list credential and keeps it for checking statuses.
+    /// Replaces an already known credential with the same id.
+    ///
+    /// # Arguments
+    ///
+    /// * `credential` - status list credential, as JSON or as secured by the verifier's plugins
+    pub async fn add_status_list_credential(
+        &mut self,
+        credential: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let credential = self.verify_list_credential(credential, false).await?;
+        let id = credential
+            .get("id")
+            .and_then(Value::as_str)
+            .ok_or("status list credential has no id")?
+            .to_string();
+        self.published.insert(id, credential);
+        Ok(())
+    }
+
+    /// Returns the current, unsecured status list credential of a list created by this plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - id of status list credential
+    pub fn get_status_list_credential(
+        &self,
+        id: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        if let Some(list) = self.lists.get(id) {
+            return list.to_credential();
+        }
+        match self
+            .store
+            .as_ref()
+            .map(|store| store.load(id))
+            .transpose()?
+        {
+            Some(Some(state)) => StatusList::from_state(&state)?.to_credential(),
+            _ => Err(Box::from(format!(r#"unknown status list "{}""#, id))),
+        }
+    }
+
+    /// Returns the status list with given id, loading it from the store if necessary.
+    fn get_list(&mut self, id: &str) -> Result<&mut StatusList, Box<dyn std::error::Error>> {
+        if !self.lists.contains_key(id) {
+            let state = self
+                .store
+                .as_ref()
+                .map(|store| store.load(id))
+                .transpose()?;
+            let state = state
+                .flatten()
+                .ok_or_else(|| format!(r#"unknown status list "{}""#, id))?;
+            let list = StatusList::from_state(&state)
+                .map_err(|e| format!(r#"invalid stored status list "{}"; {}"#, id, e))?;
+            self.lists.insert(id.to_string(), list);
+        }
+        self.lists
+            .get_mut(id)
+            .ok_or_else(|| Box::from(format!(r#"unknown status list "{}""#, id)))
+    }
+
+    /// Saves the status list with given id to the store, if any.
+    fn save_list(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let (Some(store), Some(list)) = (&self.store, self.lists.get(id)) {
+            store.save(id, &list.to_state()?)?;
+        }
+        Ok(())
+    }
+
+    /// Verifies given status list credential with the verifier, if any. Credentials from untrusted
+    /// sources are rejected without verifier.
+    async fn verify_list_credential(
+        &mut self,
+        credential: &str,
+        untrusted: bool,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        if untrusted && self.verifier.is_none() {
+            return Err(Box::from(
+                "status list credentials from options or URLs require a verifier",
+            ));
+        }
+        let credential = match self.verifier.as_mut() {
+            Some((verifier, method)) => {
+                let results = verifier.vc_verify(method, "", credential).await?;
+                let result: Value = serde_json::from_str(
+                    results
+                        .into_iter()
+                        .flatten()
+                        .next()
+                        .ok_or("status list credential could not be verified")?
+                        .as_str(),
+                )?;
+                if result.get("verified").and_then(Value::as_bool) != Some(true) {
+                    return Err(Box::from(format!(
+                        "status list credential is invalid; {}",
+                        result
+                            .get("error")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                    )));
+                }
+                result
+                    .get("credential")
+                    .cloned()
+                    .ok_or("verification result has no credential")?
+            }
+            None => serde_json::from_str(credential)?,
+        };
+        let object = credential
+            .as_object()
+            .ok_or("status list credential is no JSON object")?;
+        jwt::check_validity(object).map_err(|e| format!("status list credential is {}", e))?;
+        Ok(credential)
+    }
+
+    /// Returns the status list credential with given id for checking a status.
+    async fn get_list_credential(
+        &mut self,
+        id: &str,
+        given: &[Value],
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        if let Some(credential) = given
+            .iter()
+            .find(|credential| credential.get("id").and_then(Value::as_str) == Some(id))
+        {
+            return self
+                .verify_list_credential(&credential.to_string(), true)
+                .await;
+        }
+        if let Some(credential) = self.published.get(id) {
+            return Ok(credential.clone());
+        }
+        if let Ok(list) = self.get_list(id) {
+            return list.to_credential();
+        }
+        if !self.fetch {
+            return Err(Box::from(format!(r#"unknown status list "{}""#, id)));
+        }
+        let response = self.client.get(id).send().await?;
+        if !response.status().is_success() {
+            return Err(Box::from(format!(
+                "could not fetch status list from {} (HTTP status {})",
+                id,
+                response.status().as_u16()
+            )));
+        }
+        let credential = self
+            .verify_list_credential(&response.text().await?, true)
+            .await?;
+        self.published.insert(id.to_string(), credential.clone());
+        Ok(credential)
+    }
+
+    fn create(
+        &mut self,
+        options: &str,
+        payload: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let options: CreateOptions = parse_options(options)?;
+        let payload: CreatePayload = serde_json::from_str(payload)?;
+        let purpose = options
+            .status_purpose
+            .unwrap_or_else(|| PURPOSES[0].to_string());
+        if !PURPOSES.contains(&purpose.as_str()) {
+            return Err(Box::from(format!(
+                r#"unsupported status purpose "{}""#,
+                purpose
+            )));
+        }
+        let length = options.length.unwrap_or(MIN_LENGTH);
+        if length < MIN_LENGTH || !length.is_multiple_of(8) {
+            return Err(Box::from(format!(
+                "length has to be a multiple of 8 and at least {}",
+                MIN_LENGTH
+            )));
+        }
+        if self.get_list(&payload.id).is_ok() {
+            return Err(Box::from(format!(
+                r#"status list "{}" already exists"#,
+                payload.id
+            )));
+        }
+        let list = StatusList {
+            list_type: ListType::from_name(
+                options
+                    .list_type
+                    .as_deref()
+                    .unwrap_or("BitstringStatusList"),
+            )?,
+            id: payload.id.clone(),
+            issuer: payload.issuer,
+            purpose,
+            bits: vec![0u8; length / 8],
+            allocated: HashSet::new(),
+            valid_from: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        };
+        let credential = list.to_credential()?;
+        self.lists.insert(payload.id.clone(), list);
+        if let Err(e) = self.save_list(&payload.id) {
+            self.lists.remove(&payload.id);
+            return Err(e);
+        }
+        Ok(credential)
+    }
+
+    fn allocate(
+        &mut self,
+        options: &str,
+        payload: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let options: AllocateOptions = serde_json::from_str(options)?;
+        let mut credential: Map<String, Value> = serde_json::from_str(payload)?;
+        let mut entries = match credential.remove("credentialStatus") {
+            Some(Value::Array(entries)) => entries,
+            Some(entry) => vec![entry],
+            None => Vec::new(),
+        };
+        let mut allocated = Vec::new();
+        for id in options.status_lists.iter() {
+            let result = self
+                .get_list(id)
+                .and_then(|list| Ok((list.allocate()?, list)));
+            let (index, list) = match result {
+                Ok(allocated) => allocated,
+                Err(e) => {
+                    self.release(&allocated);
+                    return Err(e);
+                }
+            };
+            allocated.push((id, index));
+            entries.push(json!({
+                "id": format!("{}#{}", id, index),
+                "type": list.list_type.entry_type(),
+                "statusPurpose": list.purpose,
+                "statusListIndex": index.to_string(),
+                "statusListCredential": id,
+            }));
+        }
+        for (id, _) in allocated.iter() {
+            if let Err(e) = self.save_list(id) {
+                self.release(&allocated);
+                return Err(e);
+            }
+        }
+        let status = match entries.len() {
+            1 => entries.remove(0),
+            _ => Value::Array(entries),
+        };
+        credential.insert("credentialStatus".to_string(), status);
+        Ok(Value::Object(credential))
+    }
+
+    /// Releases given indexes allocated in given lists, so failed calls do not use them up.
+    fn release(&mut self, allocated: &[(&String, usize)]) {
+        for (id, index) in allocated {
+            if let Some(list) = self.lists.get_mut(*id) {
+                list.allocated.remove(index);
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        options: &str,
+        payload: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let options: UpdateOptions = serde_json::from_str(options)?;
+        let credential: Value = serde_json::from_str(payload)?;
+        let (entries, _) = get_entries(&credential)?;
+        let entry = entries
+            .into_iter()
+            .find(|entry| entry.purpose == options.status_purpose)
+            .ok_or_else(|| {
+                format!(
+                    r#"credential has no status entry for "{}""#,
+                    options.status_purpose
+                )
+            })?;
+        let list = self.get_list(&entry.list)?;
+        if entry.index >= list.length() {
+            return Err(Box::from("status list index out of range"));
+        }
+        let status = options.status.unwrap_or(true);
+        if !status && list.purpose == "revocation" && get_bit(&list.bits, entry.index) {
+            return Err(Box::from("revoked credentials cannot be reinstated"));
+        }
+        let previous = get_bit(&list.bits, entry.index);
+        set_bit(&mut list.bits, entry.index, status);
+        let credential = list.to_credential()?;
+        if let Err(e) = self.save_list(&entry.list) {
+            set_bit(&mut self.get_list(&entry.list)?.bits, entry.index, previous);
+            return Err(e);
+        }
+        Ok(credential)
+    }
+
+    async fn check(
+        &mut self,
+        options: &str,
+        payload: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let options: CheckOptions = parse_options(options)?;
+        let credential: Value = serde_json::from_str(payload)?;
+        let given = options.status_list_credentials.unwrap_or_default();
+        let issuer = get_issuer(&credential).ok_or("credential has no issuer")?;
+        let (entries, unsupported) = get_entries(&credential)?;
+        let mut statuses = Vec::new();
+        let mut error = None;
+        for entry in entries {
+            let is_set = self
+                .check_entry(&entry, &given, issuer)
+                .await
+                .map_err(|e| format!("status of {} could not be checked; {}", entry.list, e))?;
+            if is_set && error.is_none() {
+                error = Some(match entry.purpose.as_str() {
+                    "revocation" => "credential has been revoked",
+                    _ => "credential is suspended",
+                });
+            }
+            statuses.push(json!({
+                "statusPurpose": entry.purpose,
+                "statusListCredential": entry.list,
+                "statusListIndex": entry.index.to_string(),
+                "status": is_set,
+            }));
+        }
+        for entry_type in unsupported {
+            if error.is_none() {
+                error = Some("credential has status entries, that cannot be checked");
+            }
+            statuses.push(json!({ "type": entry_type, "checked": false }));
+        }
+        let mut result = json!({ "verified": error.is_none(), "statuses": statuses });
+        if let Some(error) = error {
+            result["error"] = Value::from(error);
+        }
+        Ok(result)
+    }
+
+    /// Returns if the bit of given entry is set in its status list, that has to be issued by given
+    /// issuer.
+    async fn check_entry(
+        &mut self,
+        entry: &StatusEntry,
+        given: &[Value],
+        issuer: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let credential = self.get_list_credential(&entry.list, given).await?;
+        if get_issuer(&credential) != Some(issuer) {
+            return Err(Box::from(
+                "status list has not been issued by the issuer of the credential",
+            ));
+        }
+        let subject = credential
+            .get("credentialSubject")
+            .ok_or("status list credential has no credentialSubject")?;
+        let get = |name: &str| subject.get(name).and_then(Value::as_str);
+        if get("type").map(ListType::from_name).transpose()? != Some(entry.list_type) {
+            return Err(Box::from("status list type does not match status entry"));
+        }
+        if get("statusPurpose") != Some(entry.purpose.as_str()) {
+            return Err(Box::from("status purpose does not match status list"));
+        }
+        let bits = decode_list(get("encodedList").ok_or("status list has no encodedList")?)?;
+        if bits.len() * 8 < MIN_LENGTH {
+            return Err(Box::from("status list is too short"));
+        }
+        if entry.index >= bits.len() * 8 {
+            return Err(Box::from("status list index out of range"));
+        }
+        Ok(get_bit(&bits, entry.index))
+    }
+}
+
+impl Default for StatusListVadePlugin {
+    fn default() -> Self {
+        StatusListVadePlugin::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl VadePlugin for StatusListVadePlugin {
+    /// Creates a new status list and returns its unsecured status list credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "status-list", other methods are ignored
+    /// * `options` - JSON with optional `statusPurpose`, `type` and `length`
+    /// * `payload` - JSON with `id` and `issuer` of status list credential
+    async fn vc_status_list_create(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != STATUS_LIST_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let credential = self.create(options, payload)?;
+        Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+            &credential,
+        )?)))
+    }
+
+    /// Allocates indexes in status lists and adds `credentialStatus` entries to a credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "status-list", other methods are ignored
+    /// * `options` - JSON with `statusLists`, ids of status lists to allocate indexes in
+    /// * `payload` - unsecured credential
+    async fn vc_status_list_allocate(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != STATUS_LIST_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let credential = self.allocate(options, payload)?;
+        Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+            &credential,
+        )?)))
+    }
+
+    /// Sets the status of a credential and returns the updated status list credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "status-list", other methods are ignored
+    /// * `options` - JSON with `statusPurpose` of entry to update and optional `status`
+    /// * `payload` - credential with `credentialStatus`
+    async fn vc_status_list_update(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != STATUS_LIST_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let credential = self.update(options, payload)?;
+        Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+            &credential,
+        )?)))
+    }
+
+    /// Checks the status of a credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - "status-list", other methods are ignored
+    /// * `options` - JSON with optional `statusListCredentials`
+    /// * `payload` - credential with `credentialStatus`
+    async fn vc_status_list_check(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if method != STATUS_LIST_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let result = self.check(options, payload).await?;
+        Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+            &result,
+        )?)))
+    }
+}
+
+/// A `credentialStatus` entry referencing a status list.
+struct StatusEntry {
+    list_type: ListType,
+    purpose: String,
+    index: usize,
+    list: String,
+}
+
+/// Returns the status list entries of given credential and the types of entries, that do not
+/// reference status lists.
+fn get_entries(
+    credential: &Value,
+) -> Result<(Vec<StatusEntry>, Vec<String>), Box<dyn std::error::Error>> {
+    let entries = match credential.get("credentialStatus") {
+        Some(Value::Array(entries)) => entries.iter().collect(),
+        Some(entry) => vec![entry],
+        None => Vec::new(),
+    };
+    let mut result = Vec::new();
+    let mut unsupported = Vec::new();
+    for entry in entries {
+        let get = |name: &str| entry.get(name).and_then(Value::as_str);
+        let list_type = match get("type").and_then(ListType::from_entry_type) {
+            Some(list_type) => list_type,
+            None => {
+                unsupported.push(get("type").unwrap_or_default().to_string());
+                continue;
+            }
+        };
+        let index = get("statusListIndex")
+            .ok_or("status entry has no statusListIndex")?
+            .parse::<usize>()
+            .map_err(|_| "statusListIndex is no number")?;
+        result.push(StatusEntry {
+            list_type,
+            purpose: get("statusPurpose")
+                .ok_or("status entry has no statusPurpose")?
+                .to_string(),
+            index,
+            list: get("statusListCredential")
+                .ok_or("status entry has no statusListCredential")?
+                .to_string(),
+        });
+    }
+    Ok((result, unsupported))
+}
+
+/// Returns the id of the issuer of given credential.
+fn get_issuer(credential: &Value) -> Option<&str> {
+    match credential.get("issuer") {
+        Some(Value::Object(issuer)) => issuer.get("id").and_then(Value::as_str),
+        Some(issuer) => issuer.as_str(),
+        None => None,
+    }
+}
+
+/// Compresses given bits with GZIP and encodes them as base64url, Bitstring Status Lists prefix
+/// them with "u" as multibase.
+fn encode_list(list_type: ListType, bits: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bits)?;
+    let encoded = URL_SAFE_NO_PAD.encode(encoder.finish()?);
+    Ok(match list_type {
+        ListType::Bitstring => format!("u{}", encoded),
+        ListType::StatusList2021 => encoded,
+    })
+}
+
+fn decode_list(encoded_list: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    // GZIP data starts with "H4sI" in base64, so a leading "u" is the multibase prefix
+    let encoded_list = encoded_list.strip_prefix('u').unwrap_or(encoded_list);
+    let compressed = URL_SAFE_NO_PAD
+        .decode(encoded_list.trim_end_matches('='))
+        .map_err(|e| format!("invalid encodedList; {}", e))?;
+    let mut bits = Vec::new();
+    GzDecoder::new(compressed.as_slice())
+        .take(MAX_LIST_SIZE + 1)
+        .read_to_end(&mut bits)
+        .map_err(|e| format!("invalid encodedList; {}", e))?;
+    if bits.len() as u64 > MAX_LIST_SIZE {
+        return Err(Box::from(format!(
+            "status list is larger than {} bytes",
+            MAX_LIST_SIZE
+        )));
+    }
+    Ok(bits)
+}
+
+/// Returns the bit at given index, index 0 is the most significant bit of the first byte.
+fn get_bit(bits: &[u8], index: usize) -> bool {
+    bits[index / 8] & (0x80 >> (index % 8)) != 0
+}
+
+fn set_bit(bits: &mut [u8], index: usize, value: bool) {
+    match value {
+        true => bits[index / 8] |= 0x80 >> (index % 8),
+        false => bits[index / 8] &= !(0x80 >> (index % 8)),
+    }
+}
@@ -0,0 +1,219 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{DidResolutionResult, VadePlugin, VadePluginResultValue};
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+const RESOLUTION_RESULT_ACCEPT_HEADER: &str =
+    r#"application/ld+json;profile="https://w3id.org/did-resolution""#;
+
+/// Resolves DIDs by forwarding `did_resolve` calls to a
+/// [DIF Universal Resolver](https://github.com/decentralized-identity/universal-resolver)
+/// compatible HTTP endpoint.
+///
+/// Only DIDs of the configured methods are forwarded, requests for all other DIDs are ignored, so
+/// the plugin can be registered next to plugins handling methods hosted by yourself.
+///
+/// By default `did_resolve` returns the resolved DID document, like other DID plugins do. The full
+/// resolution result including resolution and document metadata can be returned instead by
+/// enabling [`with_resolution_result`](#method.with_resolution_result).
+///
+/// # Example
+///
+/// ```
+/// use vade::{plugins::UniversalResolverVadePlugin, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let resolver = UniversalResolverVadePlugin::new(
+///         "https://dev.uniresolver.io",
+///         &["did:key", "did:web"],
+///     );
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(resolver));
+///     let results = vade.did_resolve("did:web:example.com").await?;
+///     if !results.is_empty() {
+///         println!("got did: {}", results[0].as_ref().ok_or("result not found")?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct UniversalResolverVadePlugin {
+    client: reqwest::Client,
+    endpoint: String,
+    methods: Vec<String>,
+    return_resolution_result: bool,
+}
+
+impl UniversalResolverVadePlugin {
+    /// Creates a new `UniversalResolverVadePlugin`.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - base URL of resolver, `/1.0/identifiers/{did}` is appended to it
+    /// * `methods` - DID methods to forward to resolver, e.g. "did:web"
+    pub fn new(endpoint: &str, methods: &[&str]) -> Self {
+        UniversalResolverVadePlugin {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.to_string(),
+            methods: methods.iter().map(|method| method.to_string()).collect(),
+            return_resolution_result: false,
+        }
+    }
+
+    /// Return the full DID resolution result as JSON instead of the DID document only.
+    pub fn with_resolution_result(mut self) -> Self {
+        self.return_resolution_result = true;
+        self
+    }
+
+    /// Checks if given DID is forwarded to the resolver.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to check, e.g. "did:web:example.com"
+    pub fn handles_did(&self, did: &str) -> bool {
+        self.methods.iter().any(|method| {
+            did.starts_with(method.as_str())
+                && did[method.len()..].starts_with(':')
+                && did.len() > method.len() + 1
+        })
+    }
+
+    /// Resolves given DID at the configured endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to resolve
+    pub async fn resolve(
+        &self,
+        did: &str,
+    ) -> Result<DidResolutionResult, Box<dyn std::error::Error>> {
+        let mut url = reqwest::Url::parse(&self.endpoint).map_err(|e| {
+            format!(
+                r#"invalid universal resolver endpoint "{}"; {}"#,
+                &self.endpoint, e
+            )
+        })?;
+        url.path_segments_mut()
+            .map_err(|_| {
+                format!(
+                    r#"universal resolver endpoint "{}" cannot be used as base URL"#,
+                    &self.endpoint
+                )
+            })?
+            .pop_if_empty()
+            .extend(&["1.0", "identifiers", did]);
+
+        debug!(r#"resolving "{}" via "{}""#, did, url);
+        let response = self
+            .client
+            .get(url)
+            .header(reqwest::header::ACCEPT, RESOLUTION_RESULT_ACCEPT_HEADER)
+            .send()
+            .await
+            .map_err(|e| format!(r#"could not reach universal resolver for "{}"; {}"#, did, e))?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| {
+            format!(
+                r#"could not read universal resolver response for "{}"; {}"#,
+                did, e
+            )
+        })?;
+
+        let result = match serde_json::from_str::<Value>(&body) {
+            Ok(Value::Object(object))
+                if object.contains_key("didDocument")
+                    || object.contains_key("didResolutionMetadata") =>
+            {
+                serde_json::from_value(Value::Object(object))?
+            }
+            Ok(document) if status.is_success() => DidResolutionResult {
+                did_document: Some(document),
+                ..Default::default()
+            },
+            _ => DidResolutionResult {
+                did_resolution_metadata: get_error_metadata(status),
+                ..Default::default()
+            },
+        };
+
+        if let Some(error) = result.error() {
+            let message = result
+                .did_resolution_metadata
+                .get("errorMessage")
+                .and_then(|message| message.as_str())
+                .map(|message| format!("{}; {}", error, message))
+                .unwrap_or_else(|| error.to_string());
+            return Err(Box::from(format!(
+                r#"could not resolve "{}" (HTTP status {}); {}"#,
+                did,
+                status.as_u16(),
+                message
+            )));
+        }
+        if result.did_document.is_none() {
+            return Err(Box::from(format!(
+                r#"could not resolve "{}" (HTTP status {}); no DID document returned"#,
+                did,
+                status.as_u16()
+            )));
+        }
+
+        Ok(result)
+    }
+}
+
+#[async_trait(?Send)]
+impl VadePlugin for UniversalResolverVadePlugin {
+    /// Resolves given DID at the universal resolver, if its method has been configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to resolve
+    async fn did_resolve(
+        &mut self,
+        did: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if !self.handles_did(did) {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let result = self.resolve(did).await?;
+        let serialized = if self.return_resolution_result {
+            serde_json::to_string(&result)?
+        } else {
+            serde_json::to_string(&result.did_document)?
+        };
+        Ok(VadePluginResultValue::Success(Some(serialized)))
+    }
+}
+
+/// Maps HTTP status codes defined in the
+/// [DID resolution HTTP(S) binding](https://w3c-ccg.github.io/did-resolution/#bindings-https)
+/// to resolution errors for resolvers that do not return a resolution result on errors.
+fn get_error_metadata(status: reqwest::StatusCode) -> Map<String, Value> {
+    let error = match status.as_u16() {
+        400 => "invalidDid",
+        404 => "notFound",
+        406 => "representationNotSupported",
+        410 => "deactivated",
+        501 => "methodNotSupported",
+        _ => "internalError",
+    };
+    let mut metadata = Map::new();
+    metadata.insert("error".to_string(), Value::from(error));
+    metadata
+}
@@ -0,0 +1,836 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use super::parse_options;
+use crate::{
+    jcs,
+    key_store::KeyReference,
+    multikey::{self, KeyType},
+    DidResolutionResult, KeyStore, VadePlugin, VadePluginResultValue,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Utc};
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, rc::Rc};
+
+const WEBVH_METHOD: &str = "did:webvh";
+const TDW_METHOD: &str = "did:tdw";
+const WEBVH_METHOD_VERSION: &str = "did:webvh:0.5";
+const TDW_METHOD_VERSION: &str = "did:tdw:0.4";
+const SCID_PLACEHOLDER: &str = "{SCID}";
+const CRYPTOSUITE: &str = "eddsa-jcs-2022";
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateOptions {
+    secret_key: Option<String>,
+    key_id: Option<String>,
+    update_keys: Option<Vec<String>>,
+    next_key_hashes: Option<Vec<String>>,
+    portable: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatePayload {
+    domain: String,
+    path: Option<String>,
+    document: Option<Value>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateOptions {
+    secret_key: Option<String>,
+    key_id: Option<String>,
+    update_keys: Option<Vec<String>>,
+    next_key_hashes: Option<Vec<String>>,
+    deactivated: Option<bool>,
+    /// key store key replacing the signing key in `updateKeys`, e.g. given by `Vade::rotate_key`
+    new_key_id: Option<String>,
+}
+
+/// Parameters in effect after applying the parameters of a log entry.
+#[derive(Clone, Default)]
+struct Parameters {
+    method: String,
+    scid: String,
+    update_keys: Vec<String>,
+    next_key_hashes: Vec<String>,
+    portable: bool,
+    deactivated: bool,
+}
+
+/// A log entry, that passed verification.
+struct VerifiedEntry {
+    version_id: String,
+    version_time: DateTime<FixedOffset>,
+    state: Value,
+    parameters: Parameters,
+}
+
+/// Creates, updates and resolves [`did:webvh`](https://identity.foundation/didwebvh/v0.5/) DIDs
+/// (formerly known as `did:tdw`) from their DID logs.
+///
+/// A DID log is a list of JSON lines, each holding a version of the DID document. Entries are
+/// chained by hashes, the DID itself contains a self-certifying identifier (SCID) derived from the
+/// first entry and every entry is signed by an authorized update key with an `eddsa-jcs-2022`
+/// Data Integrity proof. If an entry commits to the hashes of its next update keys, only these
+/// keys can be used for the next entry (pre-rotation). Every log is verified as a whole before a
+/// version of it is returned.
+///
+/// Logs are kept in memory. `did_create` and `did_update` append entries signed with an Ed25519
+/// key, that is given in their options either as `secretKey` multikey or as `keyId` of a key in
+/// the [`KeyStore`](https://docs.rs/vade/*/vade/trait.KeyStore.html) registered on `Vade`. They
+/// return the DID and the full log, that has to be published as `did.jsonl` by the DID
+/// controller. Logs published elsewhere can be added with [`add_log`](#method.add_log) or, after
+/// enabling [`with_fetch`](#method.with_fetch), are downloaded from the DID's web location.
+///
+/// `did_resolve` accepts the query parameters `versionId`, `versionTime` and `versionNumber` to
+/// resolve earlier versions. `did:tdw` DIDs are only supported with logs following version 0.4
+/// of the specification, witnesses and watchers are not supported.
+///
+/// # Example
+///
+/// ```
+/// use vade::{plugins::WebVhVadePlugin, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(WebVhVadePlugin::new()));
+///     let results = vade
+///         .did_create(
+///             "did:webvh",
+///             r#"{ "secretKey": "z3u2RhErZEGEPQoX72wwNiAQiFxJjUEx7pmQ6djbr6Lbzgyt" }"#,
+///             r#"{ "domain": "example.com" }"#,
+///         )
+///         .await?;
+///     if !results.is_empty() {
+///         println!("created did: {}", results[0].as_ref().ok_or("result not found")?);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct WebVhVadePlugin {
+    client: reqwest::Client,
+    fetch: bool,
+    key_store: Option<Rc<dyn KeyStore>>,
+    logs: HashMap<String, String>,
+    return_resolution_result: bool,
+}
+
+impl WebVhVadePlugin {
+    /// Creates a new `WebVhVadePlugin`, that resolves DIDs from logs held in memory only.
+    pub fn new() -> Self {
+        WebVhVadePlugin {
+            client: reqwest::Client::new(),
+            fetch: false,
+            key_store: None,
+            logs: HashMap::new(),
+            return_resolution_result: false,
+        }
+    }
+
+    /// Download logs of DIDs, that are not held in memory, from their web location.
+    pub fn with_fetch(mut self) -> Self {
+        self.fetch = true;
+        self
+    }
+
+    /// Return the full DID resolution result as JSON instead of the DID document only.
+    pub fn with_resolution_result(mut self) -> Self {
+        self.return_resolution_result = true;
+        self
+    }
+
+    /// Verifies given DID log and keeps it in memory for resolving its DID. Replaces an already
+    /// known log of the same DID. Returns the DID of the latest log entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `log` - DID log as JSON lines
+    pub fn add_log(&mut self, log: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let entries = verify_log(log).map_err(|e| format!("invalid DID log; {}", e))?;
+        let last = last_entry(&entries)?;
+        self.logs
+            .insert(last.parameters.scid.clone(), log.trim_end().to_string());
+        Ok(get_id(&last.state)?.to_string())
+    }
+
+    /// Returns the DID log of given DID, if it is held in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to get log for, e.g. "did:webvh:QmS...:example.com"
+    pub fn get_log(&self, did: &str) -> Option<&str> {
+        let scid = did.split(':').nth(2)?;
+        self.logs.get(scid).map(|log| log.as_str())
+    }
+
+    /// Returns the URL of the `did.jsonl` file of given DID.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to get log URL for, e.g. "did:webvh:QmS...:example.com:dids:issuer"
+    pub fn get_log_url(did: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let segments: Vec<&str> = did.split(':').collect();
+        if segments.len() < 4 || !is_handled_did(did) {
+            return Err(Box::from(format!(
+                r#""{}" is not a valid did:webvh DID"#,
+                did
+            )));
+        }
+        let domain = segments[3].replace("%3A", ":");
+        if segments.len() == 4 {
+            Ok(format!("https://{}/.well-known/did.jsonl", domain))
+        } else {
+            Ok(format!(
+                "https://{}/{}/did.jsonl",
+                domain,
+                segments[4..].join("/")
+            ))
+        }
+    }
+
+    /// Resolves given DID, optionally with `versionId`, `versionTime` or `versionNumber` query.
+    /// Returns `None` if given DID is no `did:webvh` or `did:tdw` DID.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to resolve, e.g. "did:webvh:QmS...:example.com?versionNumber=1"
+    pub async fn resolve(
+        &self,
+        did: &str,
+    ) -> Result<Option<DidResolutionResult>, Box<dyn std::error::Error>> {
+        if !is_handled_did(did) {
+            return Ok(None);
+        }
+        let (bare_did, query) = match did.find('?') {
+            Some(index) => (&did[..index], &did[index + 1..]),
+            None => (did, ""),
+        };
+        let resolve_error = |message: String| -> Box<dyn std::error::Error> {
+            Box::from(format!(r#"could not resolve "{}"; {}"#, bare_did, message))
+        };
+        let scid = bare_did
+            .split(':')
+            .nth(2)
+            .filter(|scid| !scid.is_empty())
+            .ok_or_else(|| resolve_error("invalidDid; DID has no SCID".to_string()))?;
+
+        let log = match self.logs.get(scid) {
+            Some(log) => log.to_string(),
+            None if self.fetch => {
+                let url = Self::get_log_url(bare_did)?;
+                let response = self.client.get(&url).send().await?;
+                if !response.status().is_success() {
+                    return Err(resolve_error(format!(
+                        "notFound; could not fetch log from {} (HTTP status {})",
+                        url,
+                        response.status().as_u16()
+                    )));
+                }
+                response.text().await?
+            }
+            None => return Err(resolve_error("notFound; no DID log known".to_string())),
+        };
+        let entries = verify_log(&log).map_err(|e| resolve_error(format!("invalidDid; {}", e)))?;
+        if entries[0].parameters.scid != scid {
+            return Err(resolve_error(
+                "invalidDid; SCID of DID does not match log".to_string(),
+            ));
+        }
+        let entry = select_entry(&entries, query).map_err(resolve_error)?;
+        if get_id(&entry.state)? != bare_did {
+            return Err(resolve_error(
+                "notFound; DID does not match id of DID document".to_string(),
+            ));
+        }
+
+        let created = entries[0].version_time;
+        Ok(Some(DidResolutionResult {
+            did_document: Some(entry.state.clone()),
+            did_resolution_metadata: json!({ "contentType": "application/did+ld+json" })
+                .as_object()
+                .cloned()
+                .unwrap_or_default(),
+            did_document_metadata: json!({
+                "versionId": entry.version_id,
+                "versionTime": format_time(&entry.version_time),
+                "created": format_time(&created),
+                "updated": format_time(&entry.version_time),
+                "scid": entry.parameters.scid,
+                "portable": entry.parameters.portable,
+                "deactivated": entry.parameters.deactivated,
+            })
+            .as_object()
+            .cloned()
+            .unwrap_or_default(),
+        }))
+    }
+}
+
+impl Default for WebVhVadePlugin {
+    fn default() -> Self {
+        WebVhVadePlugin::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl VadePlugin for WebVhVadePlugin {
+    /// Creates a new `did:webvh` DID and its log with a single, signed entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `did_method` - "did:webvh", other methods are ignored
+    /// * `options` - JSON with `secretKey` (Ed25519 secret key multikey) or `keyId` and optional
+    ///               `updateKeys`, `nextKeyHashes` and `portable`
+    /// * `payload` - JSON with `domain`, optional `path` (segments separated with ":") and
+    ///               optional `document`, that may reference the DID's SCID as "{SCID}"
+    async fn did_create(
+        &mut self,
+        did_method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if did_method != WEBVH_METHOD {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let options: CreateOptions = parse_options(options)?;
+        let payload: CreatePayload = serde_json::from_str(payload)?;
+        let signer = get_signer(
+            self.key_store.as_ref(),
+            options.secret_key.as_deref(),
+            options.key_id.as_deref(),
+        )
+        .await?;
+        let public_key = signer.info().public_key.clone();
+
+        let mut did = format!(
+            "{}:{}:{}",
+            WEBVH_METHOD,
+            SCID_PLACEHOLDER,
+            payload.domain.replace(':', "%3A")
+        );
+        if let Some(path) = payload.path.as_ref().filter(|path| !path.is_empty()) {
+            did = format!("{}:{}", did, path);
+        }
+        let mut document = match payload.document {
+            Some(Value::Object(document)) => document,
+            None => Map::new(),
+            Some(_) => return Err(Box::from("document must be an object")),
+        };
+        document
+            .entry("@context")
+            .or_insert_with(|| json!(["https://www.w3.org/ns/did/v1"]));
+        document.insert("id".to_string(), Value::from(did));
+
+        let mut parameters = Map::new();
+        parameters.insert("method".to_string(), Value::from(WEBVH_METHOD_VERSION));
+        parameters.insert("scid".to_string(), Value::from(SCID_PLACEHOLDER));
+        parameters.insert(
+            "updateKeys".to_string(),
+            json!(options.update_keys.unwrap_or_else(|| vec![public_key])),
+        );
+        if let Some(next_key_hashes) = options.next_key_hashes {
+            parameters.insert("nextKeyHashes".to_string(), json!(next_key_hashes));
+        }
+        if let Some(portable) = options.portable {
+            parameters.insert("portable".to_string(), Value::from(portable));
+        }
+
+        let preliminary = json!({
+            "versionId": SCID_PLACEHOLDER,
+            "versionTime": format_time(&Utc::now().into()),
+            "parameters": parameters,
+            "state": document,
+        });
+        let scid = multikey::hash_base58btc(jcs::canonicalize(&preliminary).as_bytes());
+        let mut entry: Value =
+            serde_json::from_str(&preliminary.to_string().replace(SCID_PLACEHOLDER, &scid))?;
+        finish_entry(&mut entry, 1, &scid, &signer).await?;
+
+        let log = jcs::canonicalize(&entry);
+        let did = self.add_log(&log)?;
+        Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+            &json!({ "did": did, "versionId": entry["versionId"], "log": log }),
+        )?)))
+    }
+
+    /// Resolves given DID from its log, if it is a `did:webvh` or `did:tdw` DID.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to resolve, optionally with `versionId`, `versionTime` or `versionNumber` query
+    async fn did_resolve(
+        &mut self,
+        did: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        let result = match self.resolve(did).await? {
+            Some(result) => result,
+            None => return Ok(VadePluginResultValue::Ignored),
+        };
+        let serialized = if self.return_resolution_result {
+            serde_json::to_string(&result)?
+        } else {
+            serde_json::to_string(&result.did_document)?
+        };
+        Ok(VadePluginResultValue::Success(Some(serialized)))
+    }
+
+    /// Appends a new, signed entry to the log of given DID.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to update, its log has to be held in memory
+    /// * `options` - JSON with `secretKey` (Ed25519 secret key multikey of an authorized update
+    ///               key) or `keyId` and optional `updateKeys`, `nextKeyHashes` and `deactivated`
+    ///               or `newKeyId` of a key store key replacing the signing key in `updateKeys`,
+    ///               that also signs the entry, if pre-rotation is active
+    /// * `payload` - new DID document, the current one is kept if empty
+    async fn did_update(
+        &mut self,
+        did: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        if !did.starts_with(&format!("{}:", WEBVH_METHOD)) {
+            return Ok(VadePluginResultValue::Ignored);
+        }
+        let log = self
+            .get_log(did)
+            .ok_or_else(|| format!(r#"no DID log known for "{}""#, did))?
+            .to_string();
+        let options: UpdateOptions = parse_options(options)?;
+        let signer = get_signer(
+            self.key_store.as_ref(),
+            options.secret_key.as_deref(),
+            options.key_id.as_deref(),
+        )
+        .await?;
+        let entries = verify_log(&log)?;
+        let last = last_entry(&entries)?;
+
+        let state = if payload.trim().is_empty() {
+            last.state.clone()
+        } else {
+            serde_json::from_str(payload)?
+        };
+        let pre_rotation = !last.parameters.next_key_hashes.is_empty();
+        let mut new_signer = None;
+        let update_keys = match (options.update_keys, options.new_key_id) {
+            (Some(update_keys), _) => Some(update_keys),
+            (None, Some(new_key_id)) => {
+                let new_key_signer =
+                    get_signer(self.key_store.as_ref(), None, Some(&new_key_id)).await?;
+                let new_key = new_key_signer.info().public_key.clone();
+                new_signer = Some(new_key_signer);
+                if pre_rotation {
+                    // all previous update keys are used up, only committed keys may follow
+                    Some(vec![new_key])
+                } else {
+                    let signing_key = &signer.info().public_key;
+                    Some(
+                        last.parameters
+                            .update_keys
+                            .iter()
+                            .map(|key| match key == signing_key {
+                                true => new_key.clone(),
+                                false => key.clone(),
+                            })
+                            .collect(),
+                    )
+                }
+            }
+            (None, None) => None,
+        };
+        // while pre-rotation is active, entries are signed with the new update keys
+        let signer = match new_signer {
+            Some(new_signer) if pre_rotation => new_signer,
+            _ => signer,
+        };
+        let mut parameters = Map::new();
+        if let Some(update_keys) = update_keys {
+            parameters.insert("updateKeys".to_string(), json!(update_keys));
+        }
+        if let Some(next_key_hashes) = options.next_key_hashes {
+            parameters.insert("nextKeyHashes".to_string(), json!(next_key_hashes));
+        }
+        if let Some(deactivated) = options.deactivated {
+            parameters.insert("deactivated".to_string(), Value::from(deactivated));
+        }
+        let mut entry = json!({
+            "versionId": "",
+            "versionTime": format_time(&Utc::now().into()),
+            "parameters": parameters,
+            "state": state,
+        });
+        finish_entry(&mut entry, entries.len() + 1, &last.version_id, &signer).await?;
+
+        let log = format!("{}\n{}", log, jcs::canonicalize(&entry));
+        let did = self.add_log(&log)?;
+        Ok(VadePluginResultValue::Success(Some(serde_json::to_string(
+            &json!({ "did": did, "versionId": entry["versionId"], "log": log }),
+        )?)))
+    }
+
+    /// Keeps given key store to sign log entries with keys given as `keyId` in options.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_store` - key store to use
+    fn set_key_store(&mut self, key_store: Rc<dyn KeyStore>) {
+        self.key_store = Some(key_store);
+    }
+}
+
+fn is_handled_did(did: &str) -> bool {
+    did.starts_with(&format!("{}:", WEBVH_METHOD)) || did.starts_with(&format!("{}:", TDW_METHOD))
+}
+
+fn format_time(time: &DateTime<FixedOffset>) -> String {
+    time.with_timezone(&Utc)
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string()
+}
+
+fn get_id(state: &Value) -> Result<&str, Box<dyn std::error::Error>> {
+    state["id"]
+        .as_str()
+        .ok_or_else(|| Box::from("DID document has no id"))
+}
+
+async fn get_signer(
+    key_store: Option<&Rc<dyn KeyStore>>,
+    secret_key: Option<&str>,
+    key_id: Option<&str>,
+) -> Result<KeyReference, Box<dyn std::error::Error>> {
+    let signer = KeyReference::from_options(key_store, secret_key, key_id).await?;
+    if signer.info().key_type != KeyType::Ed25519 {
+        return Err(Box::from(
+            "log entries have to be signed with an Ed25519 key",
+        ));
+    }
+    Ok(signer)
+}
+
+fn last_entry(entries: &[VerifiedEntry]) -> Result<&VerifiedEntry, Box<dyn std::error::Error>> {
+    entries.last().ok_or_else(|| Box::from("DID log is empty"))
+}
+
+/// Hashes the data an `eddsa-jcs-2022` proof signs.
+fn hash_proof_data(proof_config: &Value, document: &Value) -> Vec<u8> {
+    let mut data = Sha256::digest(jcs::canonicalize(proof_config).as_bytes()).to_vec();
+    data.extend_from_slice(&Sha256::digest(jcs::canonicalize(document).as_bytes()));
+    data
+}
+
+/// Returns the entry hash of given entry, that is computed with the version id of the previous
+/// entry, or the SCID for the first entry.
+fn hash_entry(entry: &Value, previous_version_id: &str) -> String {
+    let mut entry = entry.clone();
+    if let Value::Object(object) = &mut entry {
+        object.remove("proof");
+        object.insert("versionId".to_string(), Value::from(previous_version_id));
+    }
+    multikey::hash_base58btc(jcs::canonicalize(&entry).as_bytes())
+}
+
+/// Sets version id of given entry and signs it.
+async fn finish_entry(
+    entry: &mut Value,
+    version_number: usize,
+    previous_version_id: &str,
+    signer: &KeyReference,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let version_id = format!(
+        "{}-{}",
+        version_number,
+        hash_entry(entry, previous_version_id)
+    );
+    entry["versionId"] = Value::from(version_id);
+    let public_key = &signer.info().public_key;
+    let mut proof = json!({
+        "type": "DataIntegrityProof",
+        "cryptosuite": CRYPTOSUITE,
+        "verificationMethod": format!("did:key:{}#{}", public_key, public_key),
+        "created": entry["versionTime"],
+        "proofPurpose": "assertionMethod",
+    });
+    let signature = signer.sign(&hash_proof_data(&proof, entry)).await?;
+    proof["proofValue"] = Value::from(format!("z{}", bs58::encode(signature).into_string()));
+    entry["proof"] = json!([proof]);
+    Ok(())
+}
+
+fn verify_proof(document: &Value, proof: &Value, authorized_keys: &[String]) -> Result<(), String> {
+    if proof["type"] != "DataIntegrityProof" || proof["cryptosuite"] != CRYPTOSUITE {
+        return Err(format!("proofs must use {}", CRYPTOSUITE));
+    }
+    if proof["proofPurpose"] != "assertionMethod" && proof["proofPurpose"] != "authentication" {
+        return Err("invalid proof purpose".to_string());
+    }
+    let verification_method = proof["verificationMethod"].as_str().unwrap_or_default();
+    let key = match verification_method.split('#').collect::<Vec<&str>>()[..] {
+        [did, key] if did == format!("did:key:{}", key) => key,
+        _ => {
+            return Err(format!(
+                r#"invalid verification method "{}""#,
+                verification_method
+            ))
+        }
+    };
+    if !authorized_keys.iter().any(|authorized| authorized == key) {
+        return Err(format!(r#"key "{}" is not authorized"#, key));
+    }
+    let public_key = match multikey::decode_public_key(key) {
+        Ok((KeyType::Ed25519, public_key)) if public_key.len() == 32 => public_key,
+        _ => return Err(format!(r#"key "{}" is no Ed25519 key"#, key)),
+    };
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&public_key);
+    let verifying_key = VerifyingKey::from_bytes(&bytes).map_err(|e| e.to_string())?;
+    let signature = proof["proofValue"]
+        .as_str()
+        .filter(|value| value.starts_with('z'))
+        .and_then(|value| bs58::decode(&value[1..]).into_vec().ok())
+        .and_then(|value| Signature::from_slice(&value).ok())
+        .ok_or_else(|| "invalid proof value".to_string())?;
+
+    let mut proof_config = proof.clone();
+    if let Value::Object(object) = &mut proof_config {
+        object.remove("proofValue");
+    }
+    verifying_key
+        .verify_strict(&hash_proof_data(&proof_config, document), &signature)
+        .map_err(|_| "invalid signature".to_string())
+}
+
+fn apply_parameters(
+    parameters: &mut Parameters,
+    update: &Map<String, Value>,
+) -> Result<(), String> {
+    let get_strings = |value: &Value, name: &str| -> Result<Vec<String>, String> {
+        serde_json::from_value(value.clone())
+            .map_err(|_| format!("{} must be an array of strings", name))
+    };
+    for (name, value) in update.iter() {
+        match name.as_str() {
+            "method" => {
+                parameters.method = value
+                    .as_str()
+                    .ok_or_else(|| "method must be a string".to_string())?
+                    .to_string()
+            }
+            "scid" => {
+                parameters.scid = value
+                    .as_str()
+                    .ok_or_else(|| "scid must be a string".to_string())?
+                    .to_string()
+            }
+            "updateKeys" => parameters.update_keys = get_strings(value, name)?,
+            "nextKeyHashes" => parameters.next_key_hashes = get_strings(value, name)?,
+            "portable" => parameters.portable = value.as_bool().unwrap_or_default(),
+            "deactivated" => parameters.deactivated = value.as_bool().unwrap_or_default(),
+            // witnesses, watchers and caching hints do not affect verification here
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn verify_scid(entry: &Value, scid: &str) -> Result<(), String> {
+    let mut preliminary = entry.clone();
+    if let Value::Object(object) = &mut preliminary {
+        object.remove("proof");
+        object.insert("versionId".to_string(), Value::from(SCID_PLACEHOLDER));
+    }
+    let preliminary: Value =
+        serde_json::from_str(&preliminary.to_string().replace(scid, SCID_PLACEHOLDER))
+            .map_err(|e| e.to_string())?;
+    if multikey::hash_base58btc(jcs::canonicalize(&preliminary).as_bytes()) != scid {
+        return Err("SCID does not match first entry".to_string());
+    }
+    Ok(())
+}
+
+/// Verifies all entries of given log and returns them in order.
+fn verify_log(log: &str) -> Result<Vec<VerifiedEntry>, String> {
+    let mut entries: Vec<VerifiedEntry> = Vec::new();
+    let mut parameters = Parameters::default();
+    for (index, line) in log
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+    {
+        let entry_error = |message: String| format!("entry {}: {}", index + 1, message);
+        let entry: Value = serde_json::from_str(line).map_err(|e| entry_error(e.to_string()))?;
+        let version_id = entry["versionId"]
+            .as_str()
+            .ok_or_else(|| entry_error("missing versionId".to_string()))?;
+        let entry_hash = match version_id.split_once('-') {
+            Some((number, hash)) if number == (index + 1).to_string() => hash,
+            _ => return Err(entry_error("invalid versionId".to_string())),
+        };
+        let version_time = entry["versionTime"]
+            .as_str()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .ok_or_else(|| entry_error("invalid versionTime".to_string()))?;
+        let update = entry["parameters"]
+            .as_object()
+            .ok_or_else(|| entry_error("missing parameters".to_string()))?;
+        let previous = entries.last();
+        if previous.map(|p| p.parameters.deactivated).unwrap_or(false) {
+            return Err(entry_error("DID has been deactivated".to_string()));
+        }
+
+        let previous_parameters = parameters.clone();
+        apply_parameters(&mut parameters, update).map_err(entry_error)?;
+        if parameters.method != WEBVH_METHOD_VERSION && parameters.method != TDW_METHOD_VERSION {
+            return Err(entry_error(format!(
+                r#"unsupported method "{}""#,
+                parameters.method
+            )));
+        }
+        match previous {
+            None => verify_scid(&entry, &parameters.scid).map_err(entry_error)?,
+            Some(_) if parameters.scid != previous_parameters.scid => {
+                return Err(entry_error("scid must not be changed".to_string()))
+            }
+            Some(_) => (),
+        }
+
+        let previous_version_id = previous
+            .map(|p| p.version_id.as_str())
+            .unwrap_or(&parameters.scid);
+        if hash_entry(&entry, previous_version_id) != entry_hash {
+            return Err(entry_error("entry hash does not match".to_string()));
+        }
+        if version_time > Utc::now() {
+            return Err(entry_error("versionTime is in the future".to_string()));
+        }
+        if previous
+            .map(|p| p.version_time > version_time)
+            .unwrap_or(false)
+        {
+            return Err(entry_error(
+                "versionTime is before previous entry".to_string(),
+            ));
+        }
+
+        let authorized_keys = if previous.is_none() {
+            &parameters.update_keys
+        } else if !previous_parameters.next_key_hashes.is_empty() {
+            // pre-rotation: new keys must have been committed to and sign their own entry
+            if !update.contains_key("updateKeys") {
+                return Err(entry_error(
+                    "updateKeys must be rotated while pre-rotation is active".to_string(),
+                ));
+            }
+            for key in parameters.update_keys.iter() {
+                let hash = multikey::hash_base58btc(key.as_bytes());
+                if !previous_parameters.next_key_hashes.contains(&hash) {
+                    return Err(entry_error(format!(
+                        r#"update key "{}" is not in previous nextKeyHashes"#,
+                        key
+                    )));
+                }
+            }
+            &parameters.update_keys
+        } else {
+            &previous_parameters.update_keys
+        };
+        let proofs = match &entry["proof"] {
+            Value::Array(proofs) => proofs.clone(),
+            Value::Object(_) => vec![entry["proof"].clone()],
+            _ => return Err(entry_error("missing proof".to_string())),
+        };
+        let mut document = entry.clone();
+        if let Value::Object(object) = &mut document {
+            object.remove("proof");
+        }
+        proofs
+            .iter()
+            .find(|proof| verify_proof(&document, proof, authorized_keys).is_ok())
+            .ok_or_else(|| {
+                let reason = proofs
+                    .first()
+                    .map(|proof| verify_proof(&document, proof, authorized_keys))
+                    .and_then(|result| result.err())
+                    .unwrap_or_default();
+                entry_error(format!("no valid proof by an authorized key; {}", reason))
+            })?;
+
+        let id = entry["state"]["id"]
+            .as_str()
+            .ok_or_else(|| entry_error("DID document has no id".to_string()))?;
+        if id.split(':').nth(2) != Some(parameters.scid.as_str()) {
+            return Err(entry_error("id of DID document has wrong SCID".to_string()));
+        }
+        if let Some(previous) = previous {
+            if previous.state["id"] != id && !parameters.portable {
+                return Err(entry_error("DID is not portable".to_string()));
+            }
+        }
+
+        entries.push(VerifiedEntry {
+            version_id: version_id.to_string(),
+            version_time,
+            state: entry["state"].clone(),
+            parameters: parameters.clone(),
+        });
+    }
+    if entries.is_empty() {
+        return Err("DID log is empty".to_string());
+    }
+    Ok(entries)
+}
+
+/// Selects the entry requested by given query, the latest one if no version is requested.
+fn select_entry<'a>(
+    entries: &'a [VerifiedEntry],
+    query: &str,
+) -> Result<&'a VerifiedEntry, String> {
+    let mut selected = entries.last();
+    for (name, value) in query
+        .split('&')
+        .filter_map(|parameter| parameter.split_once('='))
+    {
+        selected = match name {
+            "versionId" => entries.iter().find(|entry| entry.version_id == value),
+            "versionNumber" => value
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| entries.get(number.wrapping_sub(1))),
+            "versionTime" => {
+                let time = DateTime::parse_from_rfc3339(value)
+                    .map_err(|_| format!(r#"invalidDidUrl; invalid versionTime "{}""#, value))?;
+                entries
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.version_time <= time)
+            }
+            _ => continue,
+        };
+        if selected.is_none() {
+            return Err(format!(r#"notFound; no version for {}="{}""#, name, value));
+        }
+    }
+    selected.ok_or_else(|| "notFound; DID log is empty".to_string())
+}
@@ -0,0 +1,621 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Present Proof 3.0 as protocol engine, that drives the `vc_zkp_*` proof functions for provers
+//! and verifiers, expires threads after a timeout and keeps the state of every thread in a
+//! [`ThreadStore`].
+
+use crate::{
+    didcomm::get_random_id,
+    didcomm_protocols::{
+        add_artifacts, get_attachment, get_first_result, get_name, Exchange, ProtocolAction,
+        Threads,
+    },
+    Clock, DidCommHandler, DidCommMessage, ThreadStore, UnpackedMessage, Vade,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::rc::Rc;
+
+const VERSIONS: &[&str] = &["3.0"];
+
+/// Role in a proof exchange.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProofRole {
+    /// presents proofs
+    Prover,
+    /// requests and verifies proofs
+    Verifier,
+}
+
+/// State of a proof exchange.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProofState {
+    /// prover has sent a proposal
+    ProposalSent,
+    /// verifier has received a proposal
+    ProposalReceived,
+    /// verifier has sent a proof request
+    RequestSent,
+    /// prover has received a proof request
+    RequestReceived,
+    /// prover has sent a presentation
+    PresentationSent,
+    /// verifier has received a presentation
+    PresentationReceived,
+    /// verifier has verified and acknowledged the presentation, prover has received the ack
+    Done,
+    /// exchange has been abandoned with a problem report, e.g. after a failed verification or
+    /// a timeout
+    Abandoned,
+}
+
+/// State of a proof exchange thread, as persisted by [`PresentProof`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofExchange {
+    /// id of thread
+    pub thid: String,
+    /// own role in exchange
+    pub role: ProofRole,
+    /// current state
+    pub state: ProofState,
+    /// DID of prover or verifier on the other side
+    pub counterparty: String,
+    /// result of `vc_zkp_propose_proof`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proposal: Option<Value>,
+    /// result of `vc_zkp_request_proof`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<Value>,
+    /// result of `vc_zkp_present_proof`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presentation: Option<Value>,
+    /// result of `vc_zkp_verify_proof`, verifiers only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Value>,
+    /// body of problem report, the exchange has been abandoned with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub problem_report: Option<Value>,
+    /// `expires_time` of the last message of the thread, the exchange is abandoned if it has not
+    /// been continued until then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_time: Option<i64>,
+    /// time of last change as unix timestamp
+    pub updated_time: i64,
+}
+
+impl Exchange for ProofExchange {
+    type Role = ProofRole;
+    type State = ProofState;
+
+    fn thid(&self) -> &str {
+        &self.thid
+    }
+
+    fn version(&self) -> &str {
+        VERSIONS[0]
+    }
+
+    fn role(&self) -> ProofRole {
+        self.role
+    }
+
+    fn state(&self) -> ProofState {
+        self.state
+    }
+
+    fn counterparty(&self) -> &str {
+        &self.counterparty
+    }
+
+    fn is_final(&self) -> bool {
+        matches!(self.state, ProofState::Done | ProofState::Abandoned)
+    }
+
+    fn expires_time(&self) -> Option<i64> {
+        self.expires_time
+    }
+
+    fn touch(&mut self, expires_time: Option<i64>, now: i64) {
+        self.expires_time = expires_time;
+        self.updated_time = now;
+    }
+
+    fn abandon(&mut self, problem_report: Value, now: i64) {
+        self.state = ProofState::Abandoned;
+        self.problem_report = Some(problem_report);
+        self.updated_time = now;
+    }
+}
+
+/// Steps of exchange, that call a `vc_zkp_*` function and send its result.
+#[derive(Clone, Copy)]
+enum Step {
+    Propose,
+    Request,
+    Present,
+}
+
+impl Step {
+    fn message_name(self) -> &'static str {
+        match self {
+            Step::Propose => "propose-presentation",
+            Step::Request => "request-presentation",
+            Step::Present => "presentation",
+        }
+    }
+
+    fn next_state(self) -> ProofState {
+        match self {
+            Step::Propose => ProofState::ProposalSent,
+            Step::Request => ProofState::RequestSent,
+            Step::Present => ProofState::PresentationSent,
+        }
+    }
+
+    async fn call(
+        self,
+        vade: &mut Vade,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let (function, results) = match self {
+            Step::Propose => (
+                "vc_zkp_propose_proof",
+                vade.vc_zkp_propose_proof(method, options, payload).await?,
+            ),
+            Step::Request => (
+                "vc_zkp_request_proof",
+                vade.vc_zkp_request_proof(method, options, payload).await?,
+            ),
+            Step::Present => (
+                "vc_zkp_present_proof",
+                vade.vc_zkp_present_proof(method, options, payload).await?,
+            ),
+        };
+        get_first_result(function, method, results)
+    }
+}
+
+/// Protocol engine for Present Proof 3.0 (`https://didcomm.org/present-proof/3.0`).
+///
+/// Applications start and continue exchanges with the engine's functions for their role, that
+/// call the matching `vc_zkp_*` function of `Vade` and send its result as attachment with
+/// `didcomm_send`. Artifacts of the thread so far are added to the payload of these calls as
+/// `proposal`, `request` and `presentation`, unless the payload has these properties already.
+/// Messages of the other side are handled, after the engine has been registered with
+/// [`register_didcomm_handler`](https://docs.rs/vade/*/vade/struct.Vade.html#method.register_didcomm_handler),
+/// by `didcomm_receive`. Functions and messages are only accepted in the order of the protocol:
+///
+/// - prover: [`propose`](#method.propose) (optional), receive request,
+///   [`present`](#method.present), receive ack
+/// - verifier: receive proposal and [`request_for_proposal`](#method.request_for_proposal) or
+///   [`request`](#method.request), receive presentation, [`verify`](#method.verify) (sends ack
+///   or problem report)
+///
+/// With [`with_timeout`](#method.with_timeout) sent messages, that await a reply, get an
+/// `expires_time`. Exchanges are abandoned, if they are not continued until the `expires_time` of
+/// their last message; expired messages are answered with a problem report and
+/// [`abandon_expired`](#method.abandon_expired) reports expired exchanges to their counterparty.
+/// Unexpected messages and messages of other senders than the counterparty of their thread are
+/// answered with a `problem-report`, received problem reports abandon their thread. Every change
+/// is saved in the engine's [`ThreadStore`], so exchanges can be continued after restarts.
+///
+/// # Example
+///
+/// ```
+/// use vade::{InMemoryThreadStore, PresentProof, Vade};
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut vade = Vade::new();
+///     // // register plugins for "did:example" and a key store e.g. with
+///     // vade.register_plugin(example_plugin);
+///     let presentation = PresentProof::new(
+///         "did:example:verifier",
+///         "did:example",
+///         Box::from(InMemoryThreadStore::new()),
+///     )
+///     .with_send_options(r###"{ "pack": { "sender": { "kid": "did:example:verifier#key-1", "keyId": "z6LS..." } } }"###)
+///     .with_timeout(3600);
+///     vade.register_didcomm_handler(Box::from(presentation.clone()))?;
+///     let requested = presentation
+///         .request(&mut vade, "did:example:prover", "", r#"{ "schema": "did:example:schema" }"#)
+///         .await?;
+///     println!("requested proof in thread {}", requested.exchange.thid);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct PresentProof {
+    threads: Threads,
+    timeout: Option<i64>,
+}
+
+impl PresentProof {
+    /// Creates a new `PresentProof` engine without timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - own DID, messages are sent from
+    /// * `method` - method to call `vc_zkp_*` functions for (e.g. "did:example")
+    /// * `store` - store to persist exchanges in
+    pub fn new(did: &str, method: &str, store: Box<dyn ThreadStore>) -> Self {
+        PresentProof {
+            threads: Threads::new(
+                "present-proof",
+                "proof exchange",
+                VERSIONS,
+                did,
+                method,
+                store,
+            ),
+            timeout: None,
+        }
+    }
+
+    /// Sends messages with given options for `didcomm_send`, e.g. with `pack` options to
+    /// authcrypt and route them.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - options for `didcomm_send`
+    pub fn with_send_options(mut self, options: &str) -> Self {
+        self.threads.send_options = options.to_string();
+        self
+    }
+
+    /// Expects replies to sent proposals, requests and presentations within given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - time in seconds, after which sent messages expire
+    pub fn with_timeout(mut self, seconds: i64) -> Self {
+        self.timeout = Some(seconds);
+        self
+    }
+
+    /// Uses given clock for timeouts and timestamps instead of the clock of the verification
+    /// checks registered on `Vade` or the system time.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - clock to use
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.threads.clock = Some(Rc::from(clock));
+        self
+    }
+
+    /// Returns the exchange with given thread id, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `thid` - id of thread
+    pub fn get_exchange(
+        &self,
+        thid: &str,
+    ) -> Result<Option<ProofExchange>, Box<dyn std::error::Error>> {
+        self.threads.get_exchange(thid)
+    }
+
+    /// Returns all exchanges of the engine's store.
+    pub fn list_exchanges(&self) -> Result<Vec<ProofExchange>, Box<dyn std::error::Error>> {
+        self.threads.list_exchanges()
+    }
+
+    /// Creates a proof proposal with `vc_zkp_propose_proof` and sends it to given verifier in a
+    /// new exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `verifier` - DID of verifier
+    /// * `options` - options for `vc_zkp_propose_proof`
+    /// * `payload` - payload for `vc_zkp_propose_proof`
+    pub async fn propose(
+        &self,
+        vade: &mut Vade,
+        verifier: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<ProofExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.start(vade, ProofRole::Prover, verifier, Step::Propose);
+        self.run(vade, exchange, Step::Propose, options, payload)
+            .await
+    }
+
+    /// Creates a proof request with `vc_zkp_request_proof` and sends it to given prover in a
+    /// new exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `prover` - DID of prover
+    /// * `options` - options for `vc_zkp_request_proof`
+    /// * `payload` - payload for `vc_zkp_request_proof`
+    pub async fn request(
+        &self,
+        vade: &mut Vade,
+        prover: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<ProofExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.start(vade, ProofRole::Verifier, prover, Step::Request);
+        self.run(vade, exchange, Step::Request, options, payload)
+            .await
+    }
+
+    /// Answers a received proposal with a proof request created with `vc_zkp_request_proof`.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `thid` - id of exchange in state "proposal-received"
+    /// * `options` - options for `vc_zkp_request_proof`
+    /// * `payload` - payload for `vc_zkp_request_proof`, `proposal` is added
+    pub async fn request_for_proposal(
+        &self,
+        vade: &mut Vade,
+        thid: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<ProofExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.threads.continue_exchange(
+            vade,
+            thid,
+            ProofRole::Verifier,
+            ProofState::ProposalReceived,
+        )?;
+        self.run(vade, exchange, Step::Request, options, payload)
+            .await
+    }
+
+    /// Answers a received proof request with a presentation created with
+    /// `vc_zkp_present_proof`.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `thid` - id of exchange in state "request-received"
+    /// * `options` - options for `vc_zkp_present_proof`
+    /// * `payload` - payload for `vc_zkp_present_proof`, `proposal` and `request` are added
+    pub async fn present(
+        &self,
+        vade: &mut Vade,
+        thid: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<ProofExchange>, Box<dyn std::error::Error>> {
+        let exchange = self.threads.continue_exchange(
+            vade,
+            thid,
+            ProofRole::Prover,
+            ProofState::RequestReceived,
+        )?;
+        self.run(vade, exchange, Step::Present, options, payload)
+            .await
+    }
+
+    /// Verifies a received presentation with `vc_zkp_verify_proof` and stores the result as the
+    /// exchange's `verification`. If the result is `verified`, the presentation is acknowledged
+    /// and the exchange is done, otherwise the exchange is abandoned with a problem report with
+    /// code "e.p.trust.verification-failed".
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to call zkp functions and send messages with
+    /// * `thid` - id of exchange in state "presentation-received"
+    /// * `options` - options for `vc_zkp_verify_proof`
+    /// * `payload` - payload for `vc_zkp_verify_proof`, `proposal`, `request` and
+    ///   `presentation` are added
+    pub async fn verify(
+        &self,
+        vade: &mut Vade,
+        thid: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<ProofExchange>, Box<dyn std::error::Error>> {
+        let mut exchange = self.threads.continue_exchange(
+            vade,
+            thid,
+            ProofRole::Verifier,
+            ProofState::PresentationReceived,
+        )?;
+        let payload = add_exchange_artifacts(payload, &exchange)?;
+        let method = &self.threads.method;
+        let results = vade.vc_zkp_verify_proof(method, options, &payload).await?;
+        let verification = get_first_result("vc_zkp_verify_proof", method, results)?;
+        let message = if verification["verified"] == Value::Bool(true) {
+            exchange.state = ProofState::Done;
+            self.threads
+                .get_message(&exchange, "ack", json!({ "status": "OK" }), None)
+        } else {
+            let message = self.threads.get_problem_report(
+                &exchange,
+                "e.p.trust.verification-failed",
+                "presentation could not be verified",
+            );
+            exchange.abandon(message.body.clone(), self.threads.now(vade));
+            message
+        };
+        exchange.verification = Some(verification);
+        self.threads.send(vade, exchange, &message).await
+    }
+
+    /// Abandons all exchanges, that have not been continued until the `expires_time` of their
+    /// last message, and sends a problem report with code "e.p.req.time" to their counterparty.
+    ///
+    /// # Arguments
+    ///
+    /// * `vade` - `Vade` instance to send messages with
+    pub async fn abandon_expired(
+        &self,
+        vade: &mut Vade,
+    ) -> Result<Vec<ProtocolAction<ProofExchange>>, Box<dyn std::error::Error>> {
+        self.threads.abandon_expired(vade).await
+    }
+
+    fn start(&self, vade: &Vade, role: ProofRole, counterparty: &str, step: Step) -> ProofExchange {
+        get_exchange(
+            &get_random_id(),
+            role,
+            step.next_state(),
+            counterparty,
+            self.threads.now(vade),
+        )
+    }
+
+    /// Calls the zkp function of given step and sends its result to the counterparty.
+    async fn run(
+        &self,
+        vade: &mut Vade,
+        mut exchange: ProofExchange,
+        step: Step,
+        options: &str,
+        payload: &str,
+    ) -> Result<ProtocolAction<ProofExchange>, Box<dyn std::error::Error>> {
+        let payload = add_exchange_artifacts(payload, &exchange)?;
+        let result = step
+            .call(vade, &self.threads.method, options, &payload)
+            .await?;
+        let mut message =
+            self.threads
+                .get_message(&exchange, step.message_name(), json!({}), Some(&result));
+        message.expires_time = self.timeout.map(|timeout| self.threads.now(vade) + timeout);
+        match step {
+            Step::Propose => exchange.proposal = Some(result),
+            Step::Request => exchange.request = Some(result),
+            Step::Present => exchange.presentation = Some(result),
+        }
+        exchange.state = step.next_state();
+        self.threads.send(vade, exchange, &message).await
+    }
+
+    /// Applies a received message to its exchange. Returns the updated exchange or a problem
+    /// code and comment, if the message is not expected.
+    fn apply(
+        exchange: Option<ProofExchange>,
+        name: &str,
+        message: &DidCommMessage,
+        from: &str,
+        now: i64,
+    ) -> Result<ProofExchange, (&'static str, String)> {
+        use ProofRole::{Prover, Verifier};
+        use ProofState::*;
+        let thid = message.thid.clone().unwrap_or_else(|| message.id.clone());
+        let current = exchange
+            .as_ref()
+            .map(|exchange| (exchange.role, exchange.state));
+        let (role, next) = match (current, name) {
+            (None, "propose-presentation") => (Verifier, ProposalReceived),
+            (None, "request-presentation")
+            | (Some((Prover, ProposalSent)), "request-presentation") => (Prover, RequestReceived),
+            (Some((Verifier, RequestSent)), "presentation") => (Verifier, PresentationReceived),
+            (Some((Prover, PresentationSent)), "ack") => (Prover, Done),
+            (None, _) => {
+                return Err((
+                    "e.p.msg.unexpected-message",
+                    format!(r#"unknown proof exchange "{}""#, thid),
+                ))
+            }
+            (Some((role, state)), name) => {
+                return Err((
+                    "e.p.msg.unexpected-message",
+                    format!(
+                        r#"unexpected message "{}" in state "{}" as {}"#,
+                        name,
+                        get_name(&state),
+                        get_name(&role),
+                    ),
+                ))
+            }
+        };
+        let attachment = get_attachment(message);
+        if next != Done && attachment.is_none() {
+            return Err((
+                "e.p.msg.missing-attachment",
+                format!(r#"message "{}" has no JSON attachment"#, name),
+            ));
+        }
+        let mut exchange = exchange.unwrap_or_else(|| get_exchange(&thid, role, next, from, now));
+        match next {
+            ProposalReceived => exchange.proposal = attachment,
+            RequestReceived => exchange.request = attachment,
+            PresentationReceived => exchange.presentation = attachment,
+            _ => (),
+        }
+        exchange.state = next;
+        Ok(exchange)
+    }
+}
+
+#[async_trait(?Send)]
+impl DidCommHandler for PresentProof {
+    fn message_types(&self) -> Vec<String> {
+        self.threads.message_types()
+    }
+
+    async fn handle(
+        &self,
+        vade: &mut Vade,
+        unpacked: &UnpackedMessage,
+    ) -> Result<Option<DidCommMessage>, Box<dyn std::error::Error>> {
+        self.threads
+            .handle(vade, unpacked, |exchange, _, name, from, now| {
+                Self::apply(exchange, name, &unpacked.message, from, now)
+            })
+    }
+}
+
+fn get_exchange(
+    thid: &str,
+    role: ProofRole,
+    state: ProofState,
+    counterparty: &str,
+    now: i64,
+) -> ProofExchange {
+    ProofExchange {
+        thid: thid.to_string(),
+        role,
+        state,
+        counterparty: counterparty.to_string(),
+        proposal: None,
+        request: None,
+        presentation: None,
+        verification: None,
+        problem_report: None,
+        expires_time: None,
+        updated_time: now,
+    }
+}
+
+fn add_exchange_artifacts(
+    payload: &str,
+    exchange: &ProofExchange,
+) -> Result<String, Box<dyn std::error::Error>> {
+    add_artifacts(
+        payload,
+        &[
+            ("proposal", &exchange.proposal),
+            ("request", &exchange.request),
+            ("presentation", &exchange.presentation),
+        ],
+    )
+}
@@ -0,0 +1,336 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Creation of verifiable presentations from credentials and the checks of presentations shared
+//! by the plugins securing them: freshness of challenges and binding of credentials to the holder.
+
+use crate::{jwk, multikey::KeyType};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+const CREDENTIALS_V1_CONTEXT: &str = "https://www.w3.org/2018/credentials/v1";
+const CREDENTIALS_V2_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+const PRESENTATION_TYPE: &str = "VerifiablePresentation";
+/// seconds a presentation may have been created in the future due to clock differences
+const CLOCK_SKEW: i64 = 60;
+/// seconds used challenges are kept, if presentations are verified without a maximum age
+const CHALLENGE_LIFETIME: i64 = 86400;
+
+/// Options of `vp_verify` shared by plugins.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PresentationOptions {
+    /// challenge (nonce) of the verifier the presentation has to be bound to
+    pub(crate) challenge: Option<String>,
+    /// domain (audience) of the verifier the presentation has to be bound to
+    pub(crate) domain: Option<String>,
+    /// maximum age of the presentation in seconds
+    pub(crate) max_age: Option<i64>,
+    /// `false` to accept credentials not bound to the holder
+    pub(crate) holder_binding: Option<bool>,
+}
+
+/// Signer of a presentation: its verification method, public key and when it signed.
+pub(crate) struct Presenter {
+    pub(crate) verification_method: String,
+    pub(crate) key_type: KeyType,
+    pub(crate) public_key: Vec<u8>,
+    /// unix timestamp of signature
+    pub(crate) created: Option<i64>,
+    /// challenge (nonce) the signature is bound to
+    pub(crate) challenge: Option<String>,
+}
+
+impl Presenter {
+    /// Returns the DID (or other identifier) the verification method belongs to.
+    pub(crate) fn controller(&self) -> &str {
+        self.verification_method
+            .split('#')
+            .next()
+            .unwrap_or_default()
+    }
+}
+
+/// Keeps the challenges of presentations verified by `vp_verify`, so replayed presentations are
+/// rejected. Challenges are kept as long as a replayed presentation would pass the freshness
+/// check. Plugins use their own [`InMemoryChallengeStore`] by default, a store shared by all
+/// plugins and instances verifying presentations for the same verifier can be given with their
+/// `with_challenge_store` functions.
+pub trait ChallengeStore {
+    /// Returns if given challenge has been used and is still kept at given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `challenge` - challenge bound to a presentation
+    /// * `now` - current time as unix timestamp in seconds
+    fn contains(&self, challenge: &str, now: i64) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Keeps given challenge until given time. Challenges kept until before `now` can be dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `challenge` - challenge bound to a verified presentation
+    /// * `until` - unix timestamp in seconds until the challenge has to be kept
+    /// * `now` - current time as unix timestamp in seconds
+    fn insert(
+        &self,
+        challenge: &str,
+        until: i64,
+        now: i64,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// [`ChallengeStore`] keeping challenges in memory only.
+#[derive(Default)]
+pub struct InMemoryChallengeStore {
+    /// unix timestamps until challenges are kept by challenge
+    challenges: RefCell<HashMap<String, i64>>,
+}
+
+impl InMemoryChallengeStore {
+    /// Creates a new, empty `InMemoryChallengeStore`.
+    pub fn new() -> Self {
+        InMemoryChallengeStore::default()
+    }
+}
+
+impl ChallengeStore for InMemoryChallengeStore {
+    fn contains(&self, challenge: &str, now: i64) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self
+            .challenges
+            .borrow()
+            .get(challenge)
+            .is_some_and(|until| *until >= now))
+    }
+
+    fn insert(
+        &self,
+        challenge: &str,
+        until: i64,
+        now: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut challenges = self.challenges.borrow_mut();
+        challenges.retain(|_, until| *until >= now);
+        challenges.insert(challenge.to_string(), until);
+        Ok(())
+    }
+}
+
+/// Challenges of presentations verified before by a plugin, kept in a [`ChallengeStore`].
+pub(crate) struct UsedChallenges {
+    store: Rc<dyn ChallengeStore>,
+}
+
+impl Default for UsedChallenges {
+    fn default() -> Self {
+        UsedChallenges::new(Rc::new(InMemoryChallengeStore::new()))
+    }
+}
+
+impl UsedChallenges {
+    pub(crate) fn new(store: Rc<dyn ChallengeStore>) -> Self {
+        UsedChallenges { store }
+    }
+
+    /// Fails if a challenge bound to given presenters has been used by a verified presentation
+    /// before.
+    pub(crate) fn check(&self, presenters: &[Presenter]) -> Result<(), String> {
+        let now = Utc::now().timestamp();
+        for challenge in presenters.iter().filter_map(|p| p.challenge.as_ref()) {
+            let used = self
+                .store
+                .contains(challenge, now)
+                .map_err(|e| format!("could not check challenge; {}", e))?;
+            if used {
+                return Err(format!(
+                    r#"challenge "{}" has already been used"#,
+                    challenge
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the challenges bound to given presenters of a verified presentation for the
+    /// maximum age of presentations (a day, if not limited).
+    ///
+    /// # Arguments
+    ///
+    /// * `presenters` - signers of verified presentation
+    /// * `max_age` - maximum age in seconds, the presentation has been verified with
+    pub(crate) fn insert(
+        &self,
+        presenters: &[Presenter],
+        max_age: Option<i64>,
+    ) -> Result<(), String> {
+        let now = Utc::now().timestamp();
+        let until = now + max_age.map_or(CHALLENGE_LIFETIME, |max_age| max_age + CLOCK_SKEW);
+        for challenge in presenters.iter().filter_map(|p| p.challenge.as_ref()) {
+            self.store
+                .insert(challenge, until, now)
+                .map_err(|e| format!("could not record challenge; {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns given payload as presentation. A payload with the type "VerifiablePresentation" or
+/// with `verifiableCredential` is returned as is, a single credential or an array of credentials
+/// (as objects or secured strings) is wrapped into a new presentation of given holder.
+///
+/// # Arguments
+///
+/// * `payload` - presentation, credential or array of credentials
+/// * `holder` - holder of new presentations
+pub(crate) fn wrap_credentials(
+    payload: &str,
+    holder: &str,
+) -> Result<Map<String, Value>, Box<dyn std::error::Error>> {
+    let credentials = match serde_json::from_str(payload)? {
+        Value::Object(object) if is_presentation(&object) => return Ok(object),
+        Value::Array(credentials) if !credentials.is_empty() => credentials,
+        credential @ Value::Object(_) => vec![credential],
+        _ => {
+            return Err(Box::from(
+                "payload must be a presentation, a credential or an array of credentials",
+            ))
+        }
+    };
+    let uses_v1 = credentials.iter().any(|credential| {
+        credential
+            .get("@context")
+            .and_then(|context| context.get(0))
+            .and_then(Value::as_str)
+            == Some(CREDENTIALS_V1_CONTEXT)
+    });
+    let context = match uses_v1 {
+        true => CREDENTIALS_V1_CONTEXT,
+        false => CREDENTIALS_V2_CONTEXT,
+    };
+    match json!({
+        "@context": [context],
+        "type": [PRESENTATION_TYPE],
+        "holder": holder,
+        "verifiableCredential": credentials,
+    }) {
+        Value::Object(presentation) => Ok(presentation),
+        _ => Err(Box::from("could not create presentation")),
+    }
+}
+
+fn is_presentation(object: &Map<String, Value>) -> bool {
+    if object.contains_key("verifiableCredential") {
+        return true;
+    }
+    match object.get("type") {
+        Some(Value::Array(types)) => types.iter().any(|t| t == PRESENTATION_TYPE),
+        Some(value) => value == PRESENTATION_TYPE,
+        None => false,
+    }
+}
+
+/// Checks, that the latest signature of given presenters is not older than given maximum age in
+/// seconds.
+///
+/// # Arguments
+///
+/// * `presenters` - signers of presentation
+/// * `max_age` - maximum age in seconds, if limited
+pub(crate) fn check_freshness(
+    presenters: &[Presenter],
+    max_age: Option<i64>,
+) -> Result<(), String> {
+    let max_age = match max_age {
+        Some(max_age) => max_age,
+        None => return Ok(()),
+    };
+    let created = presenters
+        .iter()
+        .filter_map(|presenter| presenter.created)
+        .max()
+        .ok_or("presentation has no creation time")?;
+    let now = Utc::now().timestamp();
+    if created > now + CLOCK_SKEW {
+        return Err("presentation has been created in the future".to_string());
+    }
+    if now - created > max_age {
+        return Err(format!("presentation is older than {} seconds", max_age));
+    }
+    Ok(())
+}
+
+/// Checks, that the presentation is signed by its holder and that every credential is bound to
+/// one of the presenters, either as subject or by its `cnf` key.
+///
+/// # Arguments
+///
+/// * `holder` - `holder` of presentation, if any
+/// * `presenters` - signers of presentation
+/// * `credentials` - verified credentials of presentation
+pub(crate) fn check_holder_binding(
+    holder: Option<&str>,
+    presenters: &[Presenter],
+    credentials: &[Value],
+) -> Result<(), String> {
+    if let Some(holder) = holder {
+        if !presenters.iter().any(|p| p.controller() == holder) {
+            return Err(format!(
+                r#"presentation is not signed by holder "{}""#,
+                holder
+            ));
+        }
+    }
+    for (index, credential) in credentials.iter().enumerate() {
+        if !presenters
+            .iter()
+            .any(|presenter| is_bound_to(credential, presenter))
+        {
+            return Err(format!("credential {} is not bound to the holder", index));
+        }
+    }
+    Ok(())
+}
+
+fn is_bound_to(credential: &Value, presenter: &Presenter) -> bool {
+    let subjects = match credential.get("credentialSubject") {
+        Some(Value::Array(subjects)) => subjects.iter().collect(),
+        Some(subject) => vec![subject],
+        None => Vec::new(),
+    };
+    let is_subject = subjects
+        .iter()
+        .any(|subject| subject.get("id").and_then(Value::as_str) == Some(presenter.controller()));
+    if is_subject {
+        return true;
+    }
+    let cnf = match credential.get("cnf") {
+        Some(cnf) => cnf,
+        None => return false,
+    };
+    if let Some(kid) = cnf.get("kid").and_then(Value::as_str) {
+        if kid == presenter.verification_method {
+            return true;
+        }
+    }
+    match cnf.get("jwk").map(jwk::public_key_from_jwk) {
+        Some(Ok((key_type, public_key))) => {
+            key_type == presenter.key_type && public_key == presenter.public_key
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,432 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! RDF datasets and their canonicalization with
+//! [RDFC-1.0](https://www.w3.org/TR/rdf-canon/), serialized as canonical N-Quads.
+
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+/// maximum number of calls and permutations of the Hash N-Degree Quads algorithm, guards against
+/// poison graphs
+const MAX_DEEP_ITERATIONS: usize = 100_000;
+
+/// An RDF term.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Term {
+    Iri(String),
+    /// blank node with its label without `_:`
+    Blank(String),
+    Literal {
+        value: String,
+        datatype: String,
+        language: Option<String>,
+    },
+}
+
+/// An RDF quad, the graph is `None` for the default graph.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Quad {
+    pub(crate) subject: Term,
+    pub(crate) predicate: Term,
+    pub(crate) object: Term,
+    pub(crate) graph: Option<Term>,
+}
+
+impl Term {
+    fn blank_label(&self) -> Option<&str> {
+        match self {
+            Term::Blank(label) => Some(label),
+            _ => None,
+        }
+    }
+
+    fn write(&self, output: &mut String) {
+        match self {
+            Term::Iri(iri) => {
+                output.push('<');
+                output.push_str(iri);
+                output.push('>');
+            }
+            Term::Blank(label) => {
+                output.push_str("_:");
+                output.push_str(label);
+            }
+            Term::Literal {
+                value,
+                datatype,
+                language,
+            } => {
+                output.push('"');
+                escape_literal(value, output);
+                output.push('"');
+                if let Some(language) = language {
+                    output.push('@');
+                    output.push_str(language);
+                } else if datatype != XSD_STRING {
+                    output.push_str("^^<");
+                    output.push_str(datatype);
+                    output.push('>');
+                }
+            }
+        }
+    }
+}
+
+/// blank node labels mapped to other labels, all without `_:`
+pub(crate) type LabelMap = HashMap<String, String>;
+
+pub(crate) const XSD_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+
+impl Quad {
+    /// Serializes the quad as canonical N-Quads line including its line break.
+    pub(crate) fn to_nquad(&self) -> String {
+        let mut output = String::new();
+        self.subject.write(&mut output);
+        output.push(' ');
+        self.predicate.write(&mut output);
+        output.push(' ');
+        self.object.write(&mut output);
+        output.push(' ');
+        if let Some(graph) = &self.graph {
+            graph.write(&mut output);
+            output.push(' ');
+        }
+        output.push_str(".\n");
+        output
+    }
+
+    /// Returns a copy of the quad with blank nodes renamed by given function.
+    pub(crate) fn map_blank_nodes<F: Fn(&str) -> String>(&self, rename: F) -> Quad {
+        let map = |term: &Term| match term {
+            Term::Blank(label) => Term::Blank(rename(label)),
+            term => term.clone(),
+        };
+        Quad {
+            subject: map(&self.subject),
+            predicate: map(&self.predicate),
+            object: map(&self.object),
+            graph: self.graph.as_ref().map(map),
+        }
+    }
+
+    fn blank_nodes(&self) -> Vec<&str> {
+        let mut labels = vec![self.subject.blank_label(), self.object.blank_label()];
+        labels.push(self.graph.as_ref().and_then(|graph| graph.blank_label()));
+        labels.into_iter().flatten().collect()
+    }
+}
+
+/// Escapes a literal value as in canonical N-Triples.
+fn escape_literal(value: &str, output: &mut String) {
+    for character in value.chars() {
+        match character {
+            '\u{8}' => output.push_str("\\b"),
+            '\t' => output.push_str("\\t"),
+            '\n' => output.push_str("\\n"),
+            '\u{c}' => output.push_str("\\f"),
+            '\r' => output.push_str("\\r"),
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\u{0}'..='\u{7}' | '\u{b}' | '\u{e}'..='\u{1f}' | '\u{7f}' => {
+                output.push_str(&format!("\\u{:04X}", character as u32))
+            }
+            character => output.push(character),
+        }
+    }
+}
+
+/// Issues identifiers for blank nodes in the order they are requested.
+#[derive(Clone)]
+struct IdentifierIssuer {
+    prefix: &'static str,
+    issued: HashMap<String, String>,
+    order: Vec<String>,
+}
+
+impl IdentifierIssuer {
+    fn new(prefix: &'static str) -> Self {
+        IdentifierIssuer {
+            prefix,
+            issued: HashMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    fn issue(&mut self, existing: &str) -> String {
+        if let Some(issued) = self.issued.get(existing) {
+            return issued.clone();
+        }
+        let issued = format!("{}{}", self.prefix, self.order.len());
+        self.issued.insert(existing.to_string(), issued.clone());
+        self.order.push(existing.to_string());
+        issued
+    }
+
+    fn get(&self, existing: &str) -> Option<&String> {
+        self.issued.get(existing)
+    }
+}
+
+struct Canonicalizer<'a> {
+    blank_node_to_quads: HashMap<&'a str, Vec<&'a Quad>>,
+    canonical_issuer: IdentifierIssuer,
+    deep_iterations: usize,
+}
+
+/// Canonicalizes given dataset. Returns the canonical N-Quads in code point order and the map of
+/// input blank node labels to canonical labels.
+///
+/// # Arguments
+///
+/// * `quads` - dataset to canonicalize
+pub(crate) fn canonicalize(
+    quads: &[Quad],
+) -> Result<(Vec<String>, LabelMap), Box<dyn std::error::Error>> {
+    let mut quads = quads.to_vec();
+    quads.sort();
+    quads.dedup();
+    let mut canonicalizer = Canonicalizer {
+        blank_node_to_quads: HashMap::new(),
+        canonical_issuer: IdentifierIssuer::new("c14n"),
+        deep_iterations: 0,
+    };
+    for quad in quads.iter() {
+        for label in quad.blank_nodes() {
+            let entry = canonicalizer.blank_node_to_quads.entry(label).or_default();
+            if !entry.iter().any(|existing| std::ptr::eq(*existing, quad)) {
+                entry.push(quad);
+            }
+        }
+    }
+
+    let mut hash_to_blank_nodes: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    let mut labels: Vec<&str> = canonicalizer.blank_node_to_quads.keys().cloned().collect();
+    labels.sort_unstable();
+    for label in labels.iter() {
+        hash_to_blank_nodes
+            .entry(canonicalizer.hash_first_degree_quads(label))
+            .or_default()
+            .push(label);
+    }
+
+    let mut non_unique = Vec::new();
+    for (_, blank_nodes) in hash_to_blank_nodes.into_iter() {
+        if blank_nodes.len() == 1 {
+            canonicalizer.canonical_issuer.issue(blank_nodes[0]);
+        } else {
+            non_unique.push(blank_nodes);
+        }
+    }
+    for blank_nodes in non_unique.into_iter() {
+        let mut hash_path_list = Vec::new();
+        for blank_node in blank_nodes.into_iter() {
+            if canonicalizer.canonical_issuer.get(blank_node).is_some() {
+                continue;
+            }
+            let mut issuer = IdentifierIssuer::new("b");
+            issuer.issue(blank_node);
+            hash_path_list.push(canonicalizer.hash_n_degree_quads(blank_node, issuer)?);
+        }
+        hash_path_list.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, issuer) in hash_path_list.into_iter() {
+            for existing in issuer.order.iter() {
+                canonicalizer.canonical_issuer.issue(existing);
+            }
+        }
+    }
+
+    let issued = canonicalizer.canonical_issuer.issued;
+    let mut nquads: Vec<String> = quads
+        .iter()
+        .map(|quad| {
+            quad.map_blank_nodes(|label| issued.get(label).cloned().unwrap_or_default())
+                .to_nquad()
+        })
+        .collect();
+    nquads.sort();
+    nquads.dedup();
+    Ok((nquads, issued))
+}
+
+fn hash(data: &str) -> String {
+    Sha256::digest(data.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl<'a> Canonicalizer<'a> {
+    fn check_iterations(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.deep_iterations > MAX_DEEP_ITERATIONS {
+            return Err(Box::from(
+                "maximum number of iterations exceeded while canonicalizing dataset",
+            ));
+        }
+        Ok(())
+    }
+
+    fn hash_first_degree_quads(&self, reference: &str) -> String {
+        let mut nquads: Vec<String> = self.blank_node_to_quads[reference]
+            .iter()
+            .map(|quad| {
+                quad.map_blank_nodes(|label| {
+                    if label == reference {
+                        "a".to_string()
+                    } else {
+                        "z".to_string()
+                    }
+                })
+                .to_nquad()
+            })
+            .collect();
+        nquads.sort();
+        hash(&nquads.concat())
+    }
+
+    fn hash_related_blank_node(
+        &self,
+        related: &str,
+        quad: &Quad,
+        issuer: &IdentifierIssuer,
+        position: char,
+    ) -> String {
+        let identifier = match self
+            .canonical_issuer
+            .get(related)
+            .or_else(|| issuer.get(related))
+        {
+            Some(identifier) => format!("_:{}", identifier),
+            None => self.hash_first_degree_quads(related),
+        };
+        let mut input = position.to_string();
+        if position != 'g' {
+            if let Term::Iri(predicate) = &quad.predicate {
+                input.push_str(&format!("<{}>", predicate));
+            }
+        }
+        input.push_str(&identifier);
+        hash(&input)
+    }
+
+    fn hash_n_degree_quads(
+        &mut self,
+        identifier: &str,
+        mut issuer: IdentifierIssuer,
+    ) -> Result<(String, IdentifierIssuer), Box<dyn std::error::Error>> {
+        self.deep_iterations += 1;
+        self.check_iterations()?;
+
+        let mut hash_to_related: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for quad in self.blank_node_to_quads[identifier].clone().iter() {
+            let components = [
+                ('s', Some(&quad.subject)),
+                ('o', Some(&quad.object)),
+                ('g', quad.graph.as_ref()),
+            ];
+            for (position, term) in components.iter() {
+                if let Some(Term::Blank(related)) = term {
+                    if related != identifier {
+                        let hash = self.hash_related_blank_node(related, quad, &issuer, *position);
+                        hash_to_related
+                            .entry(hash)
+                            .or_default()
+                            .push(related.clone());
+                    }
+                }
+            }
+        }
+
+        let mut data_to_hash = String::new();
+        for (related_hash, blank_nodes) in hash_to_related.into_iter() {
+            data_to_hash.push_str(&related_hash);
+            let mut chosen_path = String::new();
+            let mut chosen_issuer = None;
+            for permutation in permutations(&blank_nodes).into_iter() {
+                self.deep_iterations += 1;
+                self.check_iterations()?;
+                let mut issuer_copy = issuer.clone();
+                let mut path = String::new();
+                let mut recursion_list = Vec::new();
+                let mut skip = false;
+                for related in permutation.iter() {
+                    match self.canonical_issuer.get(related) {
+                        Some(canonical) => path.push_str(&format!("_:{}", canonical)),
+                        None => {
+                            if issuer_copy.get(related).is_none() {
+                                recursion_list.push(related.clone());
+                            }
+                            path.push_str(&format!("_:{}", issuer_copy.issue(related)));
+                        }
+                    }
+                    if !chosen_path.is_empty()
+                        && path.len() >= chosen_path.len()
+                        && path > chosen_path
+                    {
+                        skip = true;
+                        break;
+                    }
+                }
+                if skip {
+                    continue;
+                }
+                for related in recursion_list.iter() {
+                    let (result_hash, result_issuer) =
+                        self.hash_n_degree_quads(related, issuer_copy.clone())?;
+                    path.push_str(&format!("_:{}", issuer_copy.issue(related)));
+                    path.push_str(&format!("<{}>", result_hash));
+                    issuer_copy = result_issuer;
+                    if !chosen_path.is_empty()
+                        && path.len() >= chosen_path.len()
+                        && path > chosen_path
+                    {
+                        skip = true;
+                        break;
+                    }
+                }
+                if skip {
+                    continue;
+                }
+                if chosen_path.is_empty() || path < chosen_path {
+                    chosen_path = path;
+                    chosen_issuer = Some(issuer_copy);
+                }
+            }
+            data_to_hash.push_str(&chosen_path);
+            if let Some(chosen_issuer) = chosen_issuer {
+                issuer = chosen_issuer;
+            }
+        }
+
+        Ok((hash(&data_to_hash), issuer))
+    }
+}
+
+fn permutations(items: &[String]) -> Vec<Vec<String>> {
+    if items.len() <= 1 {
+        return vec![items.to_vec()];
+    }
+    let mut result = Vec::new();
+    for index in 0..items.len() {
+        let mut rest = items.to_vec();
+        let item = rest.remove(index);
+        for mut permutation in permutations(&rest).into_iter() {
+            permutation.insert(0, item.clone());
+            result.push(permutation);
+        }
+    }
+    result
+}
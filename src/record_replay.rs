@@ -0,0 +1,310 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use crate::{KeyStore, PluginCall, VadePlugin, VadePluginResultValue};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// Result of a recorded plugin call.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum RecordedResult {
+    /// plugin returned `VadePluginResultValue::NotImplemented`
+    NotImplemented,
+    /// plugin returned `VadePluginResultValue::Ignored`
+    Ignored,
+    /// plugin returned `VadePluginResultValue::Success` with given value
+    Success(Option<String>),
+    /// plugin returned an error with given message
+    Error(String),
+}
+
+impl RecordedResult {
+    /// Creates a `RecordedResult` from a plugin function's result.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - result of a [`VadePlugin`] function
+    pub fn from_result(
+        result: &Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>>,
+    ) -> Self {
+        match result {
+            Ok(VadePluginResultValue::NotImplemented) => RecordedResult::NotImplemented,
+            Ok(VadePluginResultValue::Ignored) => RecordedResult::Ignored,
+            Ok(VadePluginResultValue::Success(value)) => RecordedResult::Success(value.clone()),
+            Err(e) => RecordedResult::Error(e.to_string()),
+        }
+    }
+
+    /// Converts recorded result back to a plugin function's result.
+    pub fn to_result(
+        &self,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        match self {
+            RecordedResult::NotImplemented => Ok(VadePluginResultValue::NotImplemented),
+            RecordedResult::Ignored => Ok(VadePluginResultValue::Ignored),
+            RecordedResult::Success(value) => Ok(VadePluginResultValue::Success(value.clone())),
+            RecordedResult::Error(message) => Err(Box::from(message.clone())),
+        }
+    }
+}
+
+/// A single recorded plugin call and its result.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// call made to plugin
+    pub call: PluginCall,
+    /// result returned by plugin
+    pub result: RecordedResult,
+}
+
+/// A list of recorded plugin calls, that can be stored as a JSON file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    /// recorded calls in order of their occurrence
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Creates a new, empty `Cassette`.
+    pub fn new() -> Self {
+        Cassette {
+            interactions: Vec::new(),
+        }
+    }
+
+    /// Loads a cassette from given JSON file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of cassette file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            format!(
+                "could not read cassette file \"{}\"; {}",
+                path.as_ref().display(),
+                e
+            )
+        })?;
+        let cassette = serde_json::from_str(&content).map_err(|e| {
+            format!(
+                "could not parse cassette file \"{}\"; {}",
+                path.as_ref().display(),
+                e
+            )
+        })?;
+        Ok(cassette)
+    }
+
+    /// Writes cassette to given JSON file, an existing file will be overwritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of cassette file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path.as_ref(), content).map_err(|e| {
+            format!(
+                "could not write cassette file \"{}\"; {}",
+                path.as_ref().display(),
+                e
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Compares this cassette with another one, e.g. one recorded against a newer version of a
+    /// plugin, and describes every interaction that differs between them. An empty result means
+    /// that both cassettes describe the same behavior.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - cassette to compare with
+    pub fn diff(&self, other: &Cassette) -> Vec<String> {
+        let mut differences = Vec::new();
+        let count = std::cmp::max(self.interactions.len(), other.interactions.len());
+        for index in 0..count {
+            match (self.interactions.get(index), other.interactions.get(index)) {
+                (Some(own), Some(theirs)) if own.call != theirs.call => {
+                    differences.push(format!(
+                        "interaction {}: call {} differs from {}",
+                        index, own.call, theirs.call
+                    ));
+                }
+                (Some(own), Some(theirs)) if own.result != theirs.result => {
+                    differences.push(format!(
+                        "interaction {}: result of {} changed from {:?} to {:?}",
+                        index, own.call, own.result, theirs.result
+                    ));
+                }
+                (Some(own), None) => differences.push(format!(
+                    "interaction {}: call {} is missing",
+                    index, own.call
+                )),
+                (None, Some(theirs)) => differences.push(format!(
+                    "interaction {}: call {} has been added",
+                    index, theirs.call
+                )),
+                _ => (),
+            }
+        }
+        differences
+    }
+}
+
+/// Wraps a [`VadePlugin`] and records all calls and results flowing through it.
+///
+/// Calls are delegated to the wrapped plugin unchanged. If a path has been given, the cassette is
+/// written to it after each call, so the recording is complete even if the plugin is owned by a
+/// [`Vade`](https://docs.rs/vade/*/vade/struct.Vade.html) instance until the end of a test.
+///
+/// # Example
+///
+/// ```
+/// use vade::{RecordingVadePlugin, Vade, VadePlugin};
+/// # struct ExamplePlugin { }
+/// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+/// # impl VadePlugin for ExamplePlugin {}
+///
+/// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+///     let plugin = ExamplePlugin::new();
+///     let recorder = RecordingVadePlugin::new(Box::from(plugin))
+///         .with_path("tests/cassettes/example.json");
+///     let mut vade = Vade::new();
+///     vade.register_plugin(Box::from(recorder));
+///     vade.did_resolve("did:example:123").await?;
+///     Ok(())
+/// }
+/// ```
+pub struct RecordingVadePlugin {
+    plugin: Box<dyn VadePlugin>,
+    cassette: Cassette,
+    path: Option<PathBuf>,
+}
+
+impl RecordingVadePlugin {
+    /// Creates a new `RecordingVadePlugin` that delegates calls to given plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `plugin` - plugin to record calls for
+    pub fn new(plugin: Box<dyn VadePlugin>) -> Self {
+        RecordingVadePlugin {
+            plugin,
+            cassette: Cassette::new(),
+            path: None,
+        }
+    }
+
+    /// Writes recorded cassette to given path after each call.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of cassette file
+    pub fn with_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Cassette recorded so far.
+    pub fn cassette(&self) -> &Cassette {
+        &self.cassette
+    }
+
+    async fn handle_call(
+        &mut self,
+        call: PluginCall,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        let result = call.call(self.plugin.as_mut()).await;
+        self.cassette.interactions.push(Interaction {
+            result: RecordedResult::from_result(&result),
+            call,
+        });
+        if let Some(path) = &self.path {
+            self.cassette.save(path)?;
+        }
+        result
+    }
+
+    fn handle_key_store(&mut self, key_store: Rc<dyn KeyStore>) {
+        self.plugin.set_key_store(key_store);
+    }
+}
+
+impl_vade_plugin_with_handle_call!(RecordingVadePlugin);
+
+/// Serves calls from a [`Cassette`] recorded with [`RecordingVadePlugin`], without the recorded
+/// plugin being present.
+///
+/// Each call is answered with the result of the first recorded interaction with equal function
+/// and arguments, that has not been replayed yet. Calls without such an interaction fail, so
+/// replaying a flow is deterministic and detects calls the recording did not cover.
+pub struct ReplayVadePlugin {
+    interactions: Vec<Interaction>,
+    replayed: Vec<bool>,
+}
+
+impl ReplayVadePlugin {
+    /// Creates a new `ReplayVadePlugin` that serves calls from given cassette.
+    ///
+    /// # Arguments
+    ///
+    /// * `cassette` - recorded interactions
+    pub fn new(cassette: Cassette) -> Self {
+        let replayed = vec![false; cassette.interactions.len()];
+        ReplayVadePlugin {
+            interactions: cassette.interactions,
+            replayed,
+        }
+    }
+
+    /// Creates a new `ReplayVadePlugin` that serves calls from a cassette file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of cassette file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(ReplayVadePlugin::new(Cassette::load(path)?))
+    }
+
+    /// Number of recorded interactions that have not been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.replayed.iter().filter(|replayed| !**replayed).count()
+    }
+
+    async fn handle_call(
+        &mut self,
+        call: PluginCall,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        for (index, interaction) in self.interactions.iter().enumerate() {
+            if !self.replayed[index] && interaction.call == call {
+                self.replayed[index] = true;
+                return interaction.result.to_result();
+            }
+        }
+        Err(Box::from(format!(
+            "no recorded interaction for call {}",
+            &call
+        )))
+    }
+
+    fn handle_key_store(&mut self, _key_store: Rc<dyn KeyStore>) {}
+}
+
+impl_vade_plugin_with_handle_call!(ReplayVadePlugin);
@@ -0,0 +1,346 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Selective disclosure primitives of
+//! [Data Integrity](https://www.w3.org/TR/vc-di-ecdsa/#selective-disclosure-functions):
+//! selection of document parts by JSON pointers, canonicalization with blank node labels derived
+//! from an HMAC key and grouping of canonical N-Quads by selected parts.
+
+use crate::{
+    json_ld::{self, DocumentLoader},
+    rdfc::{self, LabelMap, Quad},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde_json::{Map, Value};
+use sha2::Sha256;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+/// Canonical N-Quads of a group, by their index in all canonical N-Quads of the document.
+pub(crate) struct Group {
+    pub(crate) matching: BTreeMap<usize, String>,
+    pub(crate) non_matching: BTreeMap<usize, String>,
+    /// quads of the selection with original blank node labels
+    pub(crate) quads: Vec<Quad>,
+}
+
+/// Result of [`canonicalize_and_group`].
+pub(crate) struct CanonicalGroups {
+    pub(crate) groups: Vec<Group>,
+    /// blank node labels of the document mapped to their replacement labels
+    pub(crate) label_map: HashMap<String, String>,
+}
+
+/// Returns a function, that replaces canonical blank node labels with the base64url encoded
+/// HMAC-SHA256 of the label, prefixed with "u".
+///
+/// # Arguments
+///
+/// * `key` - HMAC key
+pub(crate) fn hmac_label_map(key: &[u8]) -> impl Fn(&str) -> String + '_ {
+    move |canonical_label| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(canonical_label.as_bytes());
+        format!("u{}", URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+}
+
+/// Returns a function, that replaces the canonical blank node labels of a document with `count`
+/// blank nodes with "b" and the position of the label's HMAC among the HMACs of all labels. Unlike
+/// the HMAC labels themselves, these labels can be signed as BBS messages without revealing more
+/// than the shuffled order of blank nodes.
+///
+/// # Arguments
+///
+/// * `key` - HMAC key
+/// * `count` - number of blank nodes in canonicalized document
+#[cfg(feature = "bbs")]
+pub(crate) fn shuffled_label_map(key: &[u8], count: usize) -> impl Fn(&str) -> String {
+    let hmac = hmac_label_map(key);
+    let mut labels: Vec<(String, String)> = (0..count)
+        .map(|index| {
+            let canonical = format!("c14n{}", index);
+            (hmac(&canonical), canonical)
+        })
+        .collect();
+    labels.sort();
+    let shuffled: HashMap<String, String> = labels
+        .into_iter()
+        .enumerate()
+        .map(|(position, (_, canonical))| (canonical, format!("b{}", position)))
+        .collect();
+    move |canonical_label| {
+        shuffled
+            .get(canonical_label)
+            .cloned()
+            .unwrap_or_else(|| canonical_label.to_string())
+    }
+}
+
+/// Canonicalizes given quads and replaces the canonical blank node labels with given function.
+/// Returns the map of input labels to replacement labels and the relabeled N-Quads in code point
+/// order.
+///
+/// # Arguments
+///
+/// * `quads` - quads to canonicalize
+/// * `relabel` - function mapping canonical labels (e.g. "c14n0") to replacement labels
+pub(crate) fn label_replacement_canonicalize<F: Fn(&str) -> String>(
+    quads: &[Quad],
+    relabel: F,
+) -> Result<(LabelMap, Vec<String>), Box<dyn std::error::Error>> {
+    let (_, canonical_ids) = rdfc::canonicalize(quads)?;
+    let label_map: HashMap<String, String> = canonical_ids
+        .iter()
+        .map(|(input, canonical)| (input.clone(), relabel(canonical)))
+        .collect();
+    let nquads = relabel_nquads(quads, &label_map)?;
+    Ok((label_map, nquads))
+}
+
+/// Canonicalizes given document and groups its canonical N-Quads by the parts selected with each
+/// given list of JSON pointers.
+///
+/// # Arguments
+///
+/// * `document` - compacted JSON-LD document
+/// * `loader` - loader of the contexts of the document
+/// * `relabel` - function mapping canonical labels to replacement labels
+/// * `pointer_groups` - JSON pointers of each group
+pub(crate) fn canonicalize_and_group<F: Fn(&str) -> String>(
+    document: &Value,
+    loader: &dyn DocumentLoader,
+    relabel: F,
+    pointer_groups: &[&[String]],
+) -> Result<CanonicalGroups, Box<dyn std::error::Error>> {
+    let skolemized = json_ld::skolemize(document, loader)?;
+    let quads = json_ld::deskolemize(&json_ld::to_rdf(&skolemized, loader)?);
+    let (label_map, nquads) = label_replacement_canonicalize(&quads, relabel)?;
+
+    let mut groups = Vec::new();
+    for pointers in pointer_groups.iter() {
+        let quads = match select_json_ld(pointers, &skolemized)? {
+            Some(selection) => json_ld::deskolemize(&json_ld::to_rdf(&selection, loader)?),
+            None => Vec::new(),
+        };
+        let selected: HashSet<String> = relabel_nquads(&quads, &label_map)?.into_iter().collect();
+        let mut matching = BTreeMap::new();
+        let mut non_matching = BTreeMap::new();
+        for (index, nquad) in nquads.iter().enumerate() {
+            if selected.contains(nquad) {
+                matching.insert(index, nquad.clone());
+            } else {
+                non_matching.insert(index, nquad.clone());
+            }
+        }
+        groups.push(Group {
+            matching,
+            non_matching,
+            quads,
+        });
+    }
+
+    Ok(CanonicalGroups { groups, label_map })
+}
+
+/// Returns the positions of the entries of `selected`, that are also in `within`, relative to the
+/// entries of `within`, e.g. the positions of mandatory statements within all disclosed ones.
+///
+/// # Arguments
+///
+/// * `selected` - N-Quads to find by their index in the document
+/// * `within` - N-Quads to count positions in
+pub(crate) fn relative_indexes(
+    selected: &BTreeMap<usize, String>,
+    within: &BTreeMap<usize, String>,
+) -> Vec<usize> {
+    within
+        .keys()
+        .enumerate()
+        .filter(|(_, index)| selected.contains_key(index))
+        .map(|(relative, _)| relative)
+        .collect()
+}
+
+/// Canonicalizes given quads of a disclosed document and maps their canonical labels to the
+/// replacement labels of the full document, so that a verifier can restore them.
+///
+/// # Arguments
+///
+/// * `quads` - quads of the disclosed parts with original blank node labels
+/// * `label_map` - original blank node labels mapped to replacement labels
+pub(crate) fn create_verifier_label_map(
+    quads: &[Quad],
+    label_map: &LabelMap,
+) -> Result<LabelMap, Box<dyn std::error::Error>> {
+    let (_, canonical_ids) = rdfc::canonicalize(quads)?;
+    canonical_ids
+        .into_iter()
+        .map(|(input, canonical)| match label_map.get(&input) {
+            Some(label) => Ok((canonical, label.clone())),
+            None => Err(Box::from(format!(r#"blank node "_:{}" is unknown"#, input))),
+        })
+        .collect()
+}
+
+/// Selects the parts of given document referenced by given JSON pointers. Ids and types of all
+/// objects on the way to a selected value are kept, so that the selection is a valid JSON-LD
+/// document describing the same nodes. Returns `None` if no pointers are given.
+///
+/// # Arguments
+///
+/// * `pointers` - JSON pointers to select
+/// * `document` - compacted JSON-LD document
+pub(crate) fn select_json_ld(
+    pointers: &[String],
+    document: &Value,
+) -> Result<Option<Value>, Box<dyn std::error::Error>> {
+    if pointers.is_empty() {
+        return Ok(None);
+    }
+    let mut selection = initial_selection(document);
+    if let Some(context) = document.get("@context") {
+        selection["@context"] = context.clone();
+    }
+    let mut arrays = Vec::new();
+
+    for pointer in pointers.iter() {
+        let tokens = parse_pointer(pointer)?;
+        let mut value = document;
+        let mut selected = &mut selection;
+        for (depth, token) in tokens.iter().enumerate() {
+            value = get_child(value, token)
+                .ok_or_else(|| format!(r#"JSON pointer "{}" does not match document"#, pointer))?;
+            let is_last = depth == tokens.len() - 1;
+            let initial = if is_last {
+                value.clone()
+            } else if value.is_array() {
+                arrays.push(tokens[..=depth].to_vec());
+                Value::Array(Vec::new())
+            } else {
+                initial_selection(value)
+            };
+            selected = match selected {
+                Value::Array(items) => {
+                    let index: usize = token.parse()?;
+                    if items.len() <= index {
+                        items.resize(index + 1, Value::Null);
+                    }
+                    if is_last || items[index].is_null() {
+                        items[index] = initial;
+                    }
+                    &mut items[index]
+                }
+                Value::Object(object) => {
+                    if is_last || !object.contains_key(token) {
+                        object.insert(token.clone(), initial);
+                    }
+                    object
+                        .get_mut(token)
+                        .ok_or("selection could not be created")?
+                }
+                _ => return Err(Box::from("selection could not be created")),
+            };
+        }
+    }
+
+    // remove gaps of unselected entries from arrays, starting with the innermost arrays
+    arrays.sort_by_key(|tokens| std::cmp::Reverse(tokens.len()));
+    for tokens in arrays.iter() {
+        let path: String = tokens
+            .iter()
+            .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+            .collect();
+        if let Some(Value::Array(items)) = selection.pointer_mut(&path) {
+            items.retain(|item| !item.is_null());
+        }
+    }
+    Ok(Some(selection))
+}
+
+/// Splits given JSON pointer into its unescaped reference tokens.
+///
+/// # Arguments
+///
+/// * `pointer` - JSON pointer, e.g. "/credentialSubject/name"
+pub(crate) fn parse_pointer(pointer: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return Err(Box::from(format!(r#"invalid JSON pointer "{}""#, pointer)));
+    }
+    Ok(pointer[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// Creates the selection of an object, that only contains its id and type.
+fn initial_selection(value: &Value) -> Value {
+    let mut selection = Map::new();
+    if let Value::Object(object) = value {
+        for key in ["id", "@id", "type", "@type"].iter() {
+            match object.get(*key) {
+                Some(Value::String(id)) if key.ends_with("id") && id.starts_with("_:") => (),
+                Some(value) => {
+                    selection.insert(key.to_string(), value.clone());
+                }
+                None => (),
+            }
+        }
+    }
+    Value::Object(selection)
+}
+
+fn get_child<'a>(value: &'a Value, token: &str) -> Option<&'a Value> {
+    match value {
+        Value::Array(items) => items.get(token.parse::<usize>().ok()?),
+        Value::Object(object) => object.get(token),
+        _ => None,
+    }
+}
+
+/// Replaces blank node labels of given quads with labels from given map and serializes them as
+/// sorted N-Quads.
+fn relabel_nquads(
+    quads: &[Quad],
+    label_map: &HashMap<String, String>,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let missing = RefCell::new(None);
+    let mut nquads: Vec<String> = quads
+        .iter()
+        .map(|quad| {
+            quad.map_blank_nodes(|label| match label_map.get(label) {
+                Some(replacement) => replacement.clone(),
+                None => {
+                    *missing.borrow_mut() = Some(label.to_string());
+                    label.to_string()
+                }
+            })
+            .to_nquad()
+        })
+        .collect();
+    if let Some(label) = missing.into_inner() {
+        return Err(Box::from(format!(r#"blank node "_:{}" is unknown"#, label)));
+    }
+    nquads.sort();
+    nquads.dedup();
+    Ok(nquads)
+}
@@ -0,0 +1,143 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Persists the state of DIDComm protocol threads as JSON, so protocols can be resumed after
+/// restarts. States are stored under keys chosen by the protocol engines, e.g. a protocol name and
+/// thread id.
+pub trait ThreadStore {
+    /// Loads the state stored under given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - key of thread
+    fn load(&self, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>>;
+
+    /// Stores given state under given key, replacing a previously stored state.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - key of thread
+    /// * `state` - state of thread as JSON
+    fn save(&self, key: &str, state: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Lists the keys of all stored threads.
+    fn list(&self) -> Result<Vec<String>, Box<dyn std::error::Error>>;
+}
+
+/// [`ThreadStore`] keeping states in memory only.
+#[derive(Default)]
+pub struct InMemoryThreadStore {
+    states: RefCell<HashMap<String, String>>,
+}
+
+impl InMemoryThreadStore {
+    /// Creates a new, empty `InMemoryThreadStore`.
+    pub fn new() -> Self {
+        InMemoryThreadStore::default()
+    }
+}
+
+impl ThreadStore for InMemoryThreadStore {
+    fn load(&self, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(self.states.borrow().get(key).cloned())
+    }
+
+    fn save(&self, key: &str, state: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.states
+            .borrow_mut()
+            .insert(key.to_string(), state.to_string());
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(self.states.borrow().keys().cloned().collect())
+    }
+}
+
+/// [`ThreadStore`] writing every state to a JSON file in a directory. File names are the
+/// base64url encoded keys, so any key can be stored.
+pub struct FileThreadStore {
+    directory: PathBuf,
+}
+
+impl FileThreadStore {
+    /// Creates a new `FileThreadStore`, that stores states in given directory. The directory is
+    /// created when storing the first state.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - directory to store states in
+    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
+        FileThreadStore {
+            directory: directory.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory
+            .join(format!("{}.json", URL_SAFE_NO_PAD.encode(key)))
+    }
+}
+
+impl ThreadStore for FileThreadStore {
+    fn load(&self, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let path = self.path(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(std::fs::read_to_string(&path).map_err(|e| {
+            format!(r#"could not read thread "{}"; {}"#, key, e)
+        })?))
+    }
+
+    fn save(&self, key: &str, state: &str) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(&self.directory)?;
+        // write to a temporary file first, so states are never left half written
+        let path = self.path(key);
+        let temporary_path = path.with_extension("tmp");
+        std::fs::write(&temporary_path, state)?;
+        std::fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+        let mut keys = Vec::new();
+        for entry in std::fs::read_dir(&self.directory)? {
+            let file_name = entry?.file_name();
+            let encoded = match file_name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+            {
+                Some(encoded) => encoded,
+                None => continue,
+            };
+            if let Ok(key) = URL_SAFE_NO_PAD.decode(encoded) {
+                keys.push(String::from_utf8(key)?);
+            }
+        }
+        Ok(keys)
+    }
+}
@@ -0,0 +1,316 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Trust registries naming the issuers, that are accepted for given credential types and schemas,
+//! as checked by `Vade` after verifying credentials.
+
+use crate::{jwt, verification_report, Vade};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Decides, which issuers are trusted to issue which credentials. Registered on `Vade` with
+/// [`VerificationChecks::with_trust_registry`](https://docs.rs/vade/*/vade/struct.VerificationChecks.html#method.with_trust_registry).
+pub trait TrustRegistry {
+    /// Checks, that given issuer may issue credentials of given types and schemas.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer` - DID (or other id) of issuer
+    /// * `types` - types of credential, without the generic "VerifiableCredential"
+    /// * `schemas` - ids of schemas referenced by credential
+    fn check_issuer(
+        &self,
+        issuer: &str,
+        types: &[String],
+        schemas: &[String],
+    ) -> Result<(), String>;
+}
+
+/// Entry of a [`TrustList`]. An issuer without `credential_types` or `schemas` is trusted for any
+/// type or schema, otherwise all types or schemas of a credential have to be listed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedIssuer {
+    /// DID of issuer
+    pub issuer: String,
+    /// credential types, the issuer may issue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_types: Option<Vec<String>>,
+    /// ids of schemas, the issuer may issue credentials for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schemas: Option<Vec<String>>,
+}
+
+impl TrustedIssuer {
+    fn authorizes(&self, types: &[String], schemas: &[String]) -> bool {
+        let covers = |allowed: &Option<Vec<String>>, values: &[String]| match allowed {
+            Some(allowed) => !values.is_empty() && values.iter().all(|v| allowed.contains(v)),
+            None => true,
+        };
+        covers(&self.credential_types, types) && covers(&self.schemas, schemas)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrustListDocument {
+    trusted_issuers: Vec<TrustedIssuer>,
+}
+
+/// [`TrustRegistry`] backed by a list of [`TrustedIssuer`]s. Lists are read from JSON like
+/// `{ "trustedIssuers": [{ "issuer": "did:example:issuer", "credentialTypes": ["ExampleCredential"] }] }`,
+/// that is given as local file, as payload of a signed JWS or as `credentialSubject` of a
+/// trust-list credential. Signed lists and credentials are only accepted from given trust anchors.
+///
+/// # Example
+///
+/// ```
+/// use vade::{TrustList, TrustRegistry, TrustedIssuer};
+///
+/// let list = TrustList::new().with_issuer(TrustedIssuer {
+///     issuer: "did:example:issuer".to_string(),
+///     credential_types: Some(vec!["ExampleCredential".to_string()]),
+///     schemas: None,
+/// });
+/// assert!(list
+///     .check_issuer("did:example:issuer", &["ExampleCredential".to_string()], &[])
+///     .is_ok());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TrustList {
+    issuers: Vec<TrustedIssuer>,
+}
+
+impl TrustList {
+    /// Creates a new, empty `TrustList`, that trusts no issuer.
+    pub fn new() -> Self {
+        TrustList {
+            issuers: Vec::new(),
+        }
+    }
+
+    /// Adds given issuer to list.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer` - issuer to trust
+    pub fn with_issuer(mut self, issuer: TrustedIssuer) -> Self {
+        self.issuers.push(issuer);
+        self
+    }
+
+    /// Returns the issuers of list.
+    pub fn issuers(&self) -> &[TrustedIssuer] {
+        &self.issuers
+    }
+
+    /// Reads a list from JSON with `trustedIssuers`.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - list as JSON
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let document: TrustListDocument =
+            serde_json::from_str(json).map_err(|e| format!("invalid trust list; {}", e))?;
+        Ok(TrustList {
+            issuers: document.trusted_issuers,
+        })
+    }
+
+    /// Reads a list from a local JSON file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            format!(
+                r#"could not read trust list "{}"; {}"#,
+                path.as_ref().display(),
+                e
+            )
+        })?;
+        TrustList::from_json(&content)
+    }
+
+    /// Reads a list from the payload of a compact JWS. The JWS has to be signed with an
+    /// `assertionMethod` of its `iss`, which has to be one of given trust anchors.
+    ///
+    /// # Arguments
+    ///
+    /// * `jws` - signed list as compact JWS
+    /// * `trust_anchors` - DIDs allowed to sign trust lists
+    /// * `resolver` - `Vade` instance to resolve the signer's DID with, DIDs like did:key are
+    ///   resolved without one
+    pub async fn from_signed_list(
+        jws: &str,
+        trust_anchors: &[&str],
+        resolver: Option<&mut Vade>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let envelope = jwt::decode(jws)?;
+        let claims: Map<String, Value> = serde_json::from_slice(&envelope.payload)?;
+        let signer = claims
+            .get("iss")
+            .and_then(Value::as_str)
+            .ok_or("signed trust list has no iss")?;
+        check_anchor(signer, trust_anchors)?;
+        let (key_type, public_key) =
+            jwt::resolve_signer_key(resolver, &envelope.kid, signer, "assertionMethod").await?;
+        jwt::verify_signature(&envelope, key_type, &public_key)?;
+        jwt::check_validity(&claims).map_err(|e| format!("signed trust list is {}", e))?;
+        TrustList::from_json(&Value::Object(claims).to_string())
+    }
+
+    /// Reads a list from the `credentialSubject` of a trust-list credential, that is verified
+    /// with `vc_verify` of given `Vade` instance and has to be issued by one of given trust
+    /// anchors.
+    ///
+    /// # Arguments
+    ///
+    /// * `credential` - trust-list credential, as secured by the plugins of `vade`
+    /// * `trust_anchors` - DIDs allowed to issue trust-list credentials
+    /// * `vade` - `Vade` instance to verify credential with
+    /// * `method` - method to call `vc_verify` with, e.g. "data-integrity" or "jwt"
+    pub async fn from_credential(
+        credential: &str,
+        trust_anchors: &[&str],
+        vade: &mut Vade,
+        method: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let result = vade
+            .vc_verify(method, "", credential)
+            .await?
+            .into_iter()
+            .flatten()
+            .next()
+            .ok_or_else(|| format!(r#"no plugin verified trust list with "{}""#, method))?;
+        let mut result: Value = serde_json::from_str(&result)?;
+        let check = verification_report::check_result(&result);
+        if !check.passed {
+            return Err(format!(
+                "invalid trust list credential; {}",
+                check.error.unwrap_or_default()
+            )
+            .into());
+        }
+        if result.get("checks").is_some() {
+            // result is a verification report wrapping the plugin's result
+            result = result["result"].take();
+        }
+        let credential = verification_report::get_credentials(&result, credential)
+            .into_iter()
+            .next()
+            .ok_or("trust list credential not found in verification result")?;
+        let issuer = get_issuer(&credential).ok_or("trust list credential has no issuer")?;
+        check_anchor(&issuer, trust_anchors)?;
+        let subject = credential
+            .get("credentialSubject")
+            .or_else(|| {
+                credential
+                    .get("vc")
+                    .and_then(|vc| vc.get("credentialSubject"))
+            })
+            .ok_or("trust list credential has no credentialSubject")?;
+        TrustList::from_json(&subject.to_string())
+    }
+}
+
+impl TrustRegistry for TrustList {
+    fn check_issuer(
+        &self,
+        issuer: &str,
+        types: &[String],
+        schemas: &[String],
+    ) -> Result<(), String> {
+        let entries: Vec<&TrustedIssuer> = self
+            .issuers
+            .iter()
+            .filter(|entry| entry.issuer == issuer)
+            .collect();
+        if entries.is_empty() {
+            return Err(format!(r#"issuer "{}" is not trusted"#, issuer));
+        }
+        if !entries.iter().any(|entry| entry.authorizes(types, schemas)) {
+            return Err(format!(
+                r#"issuer "{}" is not trusted to issue credentials of type "{}"{}"#,
+                issuer,
+                types.join(", "),
+                match schemas.is_empty() {
+                    true => String::new(),
+                    false => format!(r#" with schema "{}""#, schemas.join(", ")),
+                },
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Checks the issuer of given credential, as plain JSON, JWT claims or SD-JWT VC claims, against
+/// given trust registry.
+pub(crate) fn check_credential(
+    registry: &dyn TrustRegistry,
+    credential: &Map<String, Value>,
+) -> Result<(), String> {
+    let issuer = get_issuer(credential).ok_or("credential has no issuer")?;
+    let vc = credential
+        .get("vc")
+        .and_then(Value::as_object)
+        .unwrap_or(credential);
+    let types: Vec<String> = match vc.get("type").or_else(|| credential.get("vct")) {
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        Some(Value::String(credential_type)) => vec![credential_type.to_string()],
+        _ => Vec::new(),
+    }
+    .into_iter()
+    .filter(|credential_type| credential_type != "VerifiableCredential")
+    .collect();
+    let schemas: Vec<String> = match vc.get("credentialSchema").or_else(|| vc.get("schema")) {
+        Some(Value::Array(schemas)) => schemas.iter().filter_map(get_id).collect(),
+        Some(schema) => get_id(schema).into_iter().collect(),
+        None => Vec::new(),
+    };
+    registry.check_issuer(&issuer, &types, &schemas)
+}
+
+fn get_issuer(credential: &Map<String, Value>) -> Option<String> {
+    credential
+        .get("issuer")
+        .or_else(|| credential.get("iss"))
+        .and_then(get_id)
+}
+
+fn get_id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.to_string()),
+        Value::Object(object) => object.get("id").and_then(Value::as_str).map(str::to_string),
+        _ => None,
+    }
+}
+
+fn check_anchor(signer: &str, trust_anchors: &[&str]) -> Result<(), String> {
+    if !trust_anchors.contains(&signer) {
+        return Err(format!(
+            r#""{}" is not a trust anchor for trust lists"#,
+            signer
+        ));
+    }
+    Ok(())
+}
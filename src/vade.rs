@@ -14,8 +14,17 @@
   limitations under the License.
 */
 
-use crate::{VadePlugin, VadePluginResultValue};
+#[cfg(feature = "didcomm")]
+use crate::{
+    didcomm, didcomm_protocols, didcomm_protocols::Routed, didcomm_routing, DidCommHandler,
+    Mediator,
+};
+use crate::{
+    key_rotation, verification_report, Clock, HostResolver, KeyStore, SystemClock, VadePlugin,
+    VadePluginResultValue, VerificationChecks, VerifierPolicy,
+};
 use futures::future::try_join_all;
+use std::rc::Rc;
 
 /// Calls `try_join_all` on given functions. Logs messages depending on given task name and
 /// DID or method.
@@ -52,6 +61,18 @@ macro_rules! handle_results {
 pub struct Vade {
     /// registered plugins
     pub plugins: Vec<Box<dyn VadePlugin>>,
+    /// registered key store, that is shared with all plugins
+    pub key_store: Option<Rc<dyn KeyStore>>,
+    /// registered checks, that are run on verification results of plugins
+    pub verification_checks: Option<Rc<VerificationChecks>>,
+    /// resolver, that is shared with all plugins and lent the other plugins during verification
+    pub host_resolver: Rc<HostResolver>,
+    /// registered mediator, that handles forward, coordinate mediation and pickup messages
+    #[cfg(feature = "didcomm")]
+    pub mediator: Option<Rc<Mediator>>,
+    /// registered handlers for received DIDComm messages
+    #[cfg(feature = "didcomm")]
+    pub didcomm_handlers: Vec<Rc<dyn DidCommHandler>>,
 }
 
 impl Vade {
@@ -62,6 +83,13 @@ impl Vade {
         };
         Vade {
             plugins: Vec::new(),
+            key_store: None,
+            verification_checks: None,
+            host_resolver: Rc::new(HostResolver::new()),
+            #[cfg(feature = "didcomm")]
+            mediator: None,
+            #[cfg(feature = "didcomm")]
+            didcomm_handlers: Vec::new(),
         }
     }
 
@@ -95,7 +123,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "did_create";
-        self.log_fun_enter(&task_name, &did_method);
+        self.log_fun_enter(task_name, did_method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.did_create(did_method, options, payload));
@@ -129,7 +157,7 @@ impl Vade {
         did: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "did_resolve";
-        self.log_fun_enter(&task_name, &did);
+        self.log_fun_enter(task_name, did);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.did_resolve(did));
@@ -167,7 +195,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "did_update";
-        self.log_fun_enter(&task_name, &did);
+        self.log_fun_enter(task_name, did);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.did_update(did, options, payload));
@@ -175,12 +203,64 @@ impl Vade {
         handle_results!(self, task_name, futures, did)
     }
 
+    /// Rotates a key of a DID. A new key is generated in the registered key store and replaces
+    /// the current key in all verification methods of the DID document. The new document is
+    /// submitted with `did_update`, signed with the current key (given as `keyId` in the update
+    /// options, the new key as `newKeyId`), and the DID is resolved again to check, that the
+    /// rotation has been published. Afterwards the current key is deleted from key store.
+    ///
+    /// Returns a JSON with the `did`, `keyId`, `publicKey` and `verificationMethod` of the new key,
+    /// the ids of the `rotated` verification methods and the `retiredKeyId`.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - DID to rotate key of
+    /// * `key_id` - key store id of current key
+    /// * `options` - JSON with optional `keyType` of new key (defaults to type of current key),
+    ///   `retire` (`false` to keep current key in key store) and plugin specific
+    ///   `updateOptions` for `did_update`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register key store and DID plugin e.g. with
+    ///     // vade.register_key_store(Box::from(key_store));
+    ///     // vade.register_plugin(example_plugin);
+    ///     let result = vade.rotate_key("did:example:123", "z6Mk...", "").await?;
+    ///     println!("rotated key: {}", result);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn rotate_key(
+        &mut self,
+        did: &str,
+        key_id: &str,
+        options: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        key_rotation::rotate_key(self, did, key_id, options).await
+    }
+
     /// Processes a DIDComm message as received, this may prepare a matching response for it
     /// if the DIDComm message can be interpreted and answered by a plugin's implementation.
     ///
     /// This response **may** be sent, depending on the configuration and implementation of
     /// underlying plugins, but it is usually also returned as response to this request.
     ///
+    /// If a [`Mediator`](https://docs.rs/vade/*/vade/struct.Mediator.html) is registered, messages
+    /// addressed to it are handled by the mediator instead of the plugins: `forward` messages for
+    /// recipients, that have been granted mediation, are queued for their next recipient
+    /// (returning `None`), coordinate mediation and pickup requests are answered with an
+    /// authcrypted reply and rejected messages with a problem report.
+    ///
+    /// If DIDComm handlers are registered with
+    /// [`register_didcomm_handler`](https://docs.rs/vade/*/vade/struct.Vade.html#method.register_didcomm_handler),
+    /// messages are unpacked and passed to the handler for their message type. Its reply (or a
+    /// `problem-report` for unsupported messages) is returned as plaintext message or packed with
+    /// the `pack` property of `options`, if given.
+    ///
     /// # Arguments
     ///
     /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
@@ -206,18 +286,46 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "didcomm_receive";
-        self.log_fun_enter(&task_name, &task_name);
+        self.log_fun_enter(task_name, task_name);
+        #[cfg(feature = "didcomm")]
+        let problem_report = {
+            if let Some(mediator) = self.mediator.as_ref().map(Rc::clone) {
+                if let Some(reply) = mediator.receive(self, options, payload).await? {
+                    return Ok(vec![reply]);
+                }
+            }
+            match didcomm_protocols::route(self, options, payload).await? {
+                Some(Routed::Handled(reply)) => return Ok(vec![reply]),
+                Some(Routed::Unsupported(problem_report)) => problem_report,
+                None => None,
+            }
+        };
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.didcomm_receive(options, payload));
         }
-        handle_results!(self, task_name, futures, task_name)
+        let results: Result<_, Box<dyn std::error::Error>> =
+            handle_results!(self, task_name, futures, task_name);
+        let results = results?;
+        #[cfg(feature = "didcomm")]
+        if let Some(problem_report) = problem_report.filter(|_| results.is_empty()) {
+            return Ok(vec![Some(problem_report)]);
+        }
+        Ok(results)
     }
 
     /// Processes a DIDComm message and prepares it for sending.
     ///
     /// It **may** be sent, depending on the configuration and implementation of underlying plugins.
     ///
+    /// If `options` contain a `pack` property, the message is packed with these options like with
+    /// `didcomm_pack` and the `DIDCommMessaging` service of its first recipient is resolved. For
+    /// each of the service's `routingKeys` the packed message is wrapped in an anoncrypted
+    /// `forward` message, services pointing to a mediator's DID use the mediator's service and
+    /// routing keys. Plugins then receive the resulting message with the service's URI as
+    /// `serviceEndpoint` in their options. If no plugin sends the message, a JSON with
+    /// `message` and `serviceEndpoint` is returned.
+    ///
     /// # Arguments
     ///
     /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
@@ -244,12 +352,245 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "didcomm_send";
-        self.log_fun_enter(&task_name, &task_name);
+        self.log_fun_enter(task_name, task_name);
+        #[cfg(feature = "didcomm")]
+        let prepared = didcomm_routing::prepare_send(self, options, payload).await?;
+        #[cfg(feature = "didcomm")]
+        let (options, payload) = match &prepared {
+            Some(prepared) => (prepared.options.as_str(), prepared.message.as_str()),
+            None => (options, payload),
+        };
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.didcomm_send(options, payload));
         }
-        handle_results!(self, task_name, futures, task_name)
+        let results: Result<_, Box<dyn std::error::Error>> =
+            handle_results!(self, task_name, futures, task_name);
+        let results = results?;
+        #[cfg(feature = "didcomm")]
+        if let Some(prepared) = prepared.filter(|_| results.is_empty()) {
+            return Ok(vec![Some(prepared.result)]);
+        }
+        Ok(results)
+    }
+
+    /// Packs a plaintext DIDComm v2 message as plaintext, signed (JWS) or encrypted (JWE)
+    /// message. Messages are encrypted with authcrypt (ECDH-1PU+A256KW) if a sender key is given
+    /// and with anoncrypt (ECDH-ES+A256KW) otherwise, for all X25519 or P-256 `keyAgreement` keys
+    /// of the recipients, that are resolved with `did_resolve`. Keys of senders and signers are
+    /// given as `secretKey` or `keyId` of a key in the registered key store, along with their
+    /// `kid` in the sender's DID document.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - JSON with optional `packing` ("plaintext", "signed", "anoncrypt" or
+    ///   "authcrypt"), `sender` (`keyAgreement` key for authcrypt), `signer` (`authentication`
+    ///   key to sign with before encrypting), `enc` ("A256CBC-HS512" or "A256GCM" for anoncrypt),
+    ///   `protectSender` (`true` to wrap authcrypted messages in anoncrypt) and `to` (DIDs or
+    ///   DID URLs of keys to encrypt for, defaults to the message's `to`)
+    /// * `message` - plaintext message as JSON, see
+    ///   [`DidCommMessage`](https://docs.rs/vade/*/vade/struct.DidCommMessage.html)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{DidCommMessage, Vade};
+    /// use serde_json::json;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     let message = DidCommMessage::new(
+    ///         "https://didcomm.org/basicmessage/2.0/message",
+    ///         json!({ "content": "hello" }),
+    ///     )
+    ///     .with_to(&["did:key:z6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc"]);
+    ///     let packed = vade
+    ///         .didcomm_pack("", &serde_json::to_string(&message)?)
+    ///         .await?;
+    ///     println!("packed message: {}", packed);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "didcomm")]
+    pub async fn didcomm_pack(
+        &mut self,
+        options: &str,
+        message: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        didcomm::pack(self, options, message).await
+    }
+
+    /// Unpacks a plaintext, signed or encrypted DIDComm v2 message. Encrypted messages are
+    /// decrypted with a recipient key held in the registered key store or given in options.
+    /// Signatures and authcrypt senders are checked against the sender's DID document, which has
+    /// to be the `from` of the plaintext message.
+    ///
+    /// Returns an [`UnpackedMessage`](https://docs.rs/vade/*/vade/struct.UnpackedMessage.html)
+    /// with the plaintext message and metadata about how it has been packed.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - JSON with optional `secretKey` or `keyId` to decrypt with
+    /// * `message` - packed message
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{UnpackedMessage, Vade};
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register key store holding recipient keys e.g. with
+    ///     // vade.register_key_store(Box::from(key_store));
+    ///     let unpacked = vade.didcomm_unpack("", "{...}").await?;
+    ///     let unpacked: UnpackedMessage = serde_json::from_str(&unpacked)?;
+    ///     println!("received message of type: {}", unpacked.message.message_type);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "didcomm")]
+    pub async fn didcomm_unpack(
+        &mut self,
+        options: &str,
+        message: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(serde_json::to_string(
+            &didcomm::unpack(self, options, message).await?,
+        )?)
+    }
+
+    /// Registers a key store and hands it over to all plugins, plugins registered later receive it
+    /// as well. Replaces a previously registered key store. See
+    /// [`KeyStore`](https://docs.rs/vade/*/vade/trait.KeyStore.html) for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_store` - key store to register
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{multikey::KeyType, InMemoryKeyStore, Vade};
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     vade.register_key_store(Box::from(InMemoryKeyStore::new()));
+    ///     let key_store = vade.key_store.as_ref().ok_or("no key store")?;
+    ///     let key = key_store.generate_key(KeyType::Ed25519).await?;
+    ///     println!("generated key: {}", &key.id);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn register_key_store(&mut self, key_store: Box<dyn KeyStore>) {
+        debug!("registering key store");
+        let key_store: Rc<dyn KeyStore> = Rc::from(key_store);
+        for plugin in self.plugins.iter_mut() {
+            plugin.set_key_store(Rc::clone(&key_store));
+        }
+        self.key_store = Some(key_store);
+    }
+
+    /// Registers checks, that `Vade` runs after `vc_verify`, `vp_verify` and `vc_zkp_verify_proof`:
+    /// validity periods are checked against the clock of the checks and the `credentialStatus` of
+    /// credentials with the plugins handling `vc_status_list_check`. Plugin results are then
+    /// replaced by a [`VerificationReport`](https://docs.rs/vade/*/vade/struct.VerificationReport.html)
+    /// listing every check and its outcome. Replaces previously registered checks.
+    ///
+    /// # Arguments
+    ///
+    /// * `checks` - checks to run
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{Vade, VerificationChecks, VerificationReport};
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     vade.register_verification_checks(VerificationChecks::new());
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let results = vade.vc_verify("did:example", "", "").await?;
+    ///     if !results.is_empty() {
+    ///         let report: VerificationReport =
+    ///             serde_json::from_str(results[0].as_ref().ok_or("result not found")?)?;
+    ///         println!("verified: {}", report.verified);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn register_verification_checks(&mut self, checks: VerificationChecks) {
+        debug!("registering verification checks");
+        self.verification_checks = Some(Rc::new(checks));
+    }
+
+    /// Registers a mediator, that queues forwarded messages and serves pickup requests received
+    /// with `didcomm_receive`. Replaces a previously registered mediator. See
+    /// [`Mediator`](https://docs.rs/vade/*/vade/struct.Mediator.html) for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `mediator` - mediator to register
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{Mediator, Vade};
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register key store holding the mediator's keys e.g. with
+    ///     // vade.register_key_store(Box::from(key_store));
+    ///     vade.register_mediator(Mediator::new(
+    ///         "did:example:mediator",
+    ///         "did:example:mediator#key-x25519",
+    ///         "z6LS...",
+    ///     ));
+    ///     let results = vade.didcomm_receive("", "{...}").await?;
+    ///     if let Some(Some(reply)) = results.first() {
+    ///         println!("reply to pickup request: {}", reply);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "didcomm")]
+    pub fn register_mediator(&mut self, mediator: Mediator) {
+        debug!("registering mediator");
+        self.mediator = Some(Rc::new(mediator));
+    }
+
+    /// Registers a handler for received DIDComm messages of the protocols and message types
+    /// returned by its [`message_types`](https://docs.rs/vade/*/vade/trait.DidCommHandler.html#tymethod.message_types).
+    ///
+    /// Once handlers are registered, `didcomm_receive` unpacks incoming messages and passes them
+    /// to the handler matching their protocol, major version and message name. Handlers for the
+    /// message's minor version are preferred, otherwise the highest minor version is used.
+    /// Messages no handler supports are passed to the plugins and answered with a
+    /// `problem-report`, if no plugin returns a result for them.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - handler to register
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register handler e.g. with
+    ///     // vade.register_didcomm_handler(Box::from(trust_ping_handler))?;
+    ///     let results = vade.didcomm_receive("", "{...}").await?;
+    ///     if let Some(Some(reply)) = results.first() {
+    ///         println!("reply to received message: {}", reply);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "didcomm")]
+    pub fn register_didcomm_handler(
+        &mut self,
+        handler: Box<dyn DidCommHandler>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("registering DIDComm handler");
+        didcomm_protocols::check_handler(handler.as_ref())?;
+        self.didcomm_handlers.push(Rc::from(handler));
+        Ok(())
     }
 
     /// Registers a new plugin. See [`VadePlugin`](https://docs.rs/vade/*/vade/struct.VadePlugin.html) for details about how they work.
@@ -278,8 +619,12 @@ impl Vade {
     ///     Ok(())
     /// }
     /// ```
-    pub fn register_plugin(&mut self, plugin: Box<dyn VadePlugin>) {
+    pub fn register_plugin(&mut self, mut plugin: Box<dyn VadePlugin>) {
         debug!("registering new vade plugin");
+        if let Some(key_store) = &self.key_store {
+            plugin.set_key_store(Rc::clone(key_store));
+        }
+        plugin.set_host_resolver(Rc::clone(&self.host_resolver));
         self.plugins.push(plugin);
     }
 
@@ -316,7 +661,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "run_custom_function";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.run_custom_function(method, function, options, payload));
@@ -324,6 +669,236 @@ impl Vade {
         handle_results!(self, task_name, futures, method)
     }
 
+    /// Issues a new verifiable credential. The credential is secured with an embedded proof or an
+    /// enveloping signature, depending on plugin implementation.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to issue a credential for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let results = vade.vc_issue("did:example", "", "").await?;
+    ///     if !results.is_empty() {
+    ///         println!("issued credential: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vc_issue(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let task_name = "vc_issue";
+        self.log_fun_enter(task_name, method);
+        let mut futures = Vec::new();
+        for plugin in self.plugins.iter_mut() {
+            futures.push(plugin.vc_issue(method, options, payload));
+        }
+        handle_results!(self, task_name, futures, method)
+    }
+
+    /// Creates a new status list, e.g. a Bitstring Status List, and returns its status list credential,
+    /// that has to be secured and published by the issuer.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to create a status list for (e.g. "status-list")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let results = vade.vc_status_list_create("status-list", "", "").await?;
+    ///     if !results.is_empty() {
+    ///         println!("created status list credential: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vc_status_list_create(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let task_name = "vc_status_list_create";
+        self.log_fun_enter(task_name, method);
+        let mut futures = Vec::new();
+        for plugin in self.plugins.iter_mut() {
+            futures.push(plugin.vc_status_list_create(method, options, payload));
+        }
+        handle_results!(self, task_name, futures, method)
+    }
+
+    /// Allocates an index in a status list for a credential to be issued and adds a matching
+    /// `credentialStatus` entry to the credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to allocate a status list index with (e.g. "status-list")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let results = vade.vc_status_list_allocate("status-list", "", "").await?;
+    ///     if !results.is_empty() {
+    ///         println!("credential with status entry: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vc_status_list_allocate(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let task_name = "vc_status_list_allocate";
+        self.log_fun_enter(task_name, method);
+        let mut futures = Vec::new();
+        for plugin in self.plugins.iter_mut() {
+            futures.push(plugin.vc_status_list_allocate(method, options, payload));
+        }
+        handle_results!(self, task_name, futures, method)
+    }
+
+    /// Sets the status of an issued credential in its status list, e.g. to revoke or suspend it, and
+    /// returns the updated status list credential to publish.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to update a status list with (e.g. "status-list")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let results = vade.vc_status_list_update("status-list", "", "").await?;
+    ///     if !results.is_empty() {
+    ///         println!("updated status list credential: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vc_status_list_update(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let task_name = "vc_status_list_update";
+        self.log_fun_enter(task_name, method);
+        let mut futures = Vec::new();
+        for plugin in self.plugins.iter_mut() {
+            futures.push(plugin.vc_status_list_update(method, options, payload));
+        }
+        handle_results!(self, task_name, futures, method)
+    }
+
+    /// Checks the `credentialStatus` of a verifiable credential against its status lists.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to check a credential's status with (e.g. "status-list")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let results = vade.vc_status_list_check("status-list", "", "").await?;
+    ///     if !results.is_empty() {
+    ///         println!("status of credential: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vc_status_list_check(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let task_name = "vc_status_list_check";
+        self.log_fun_enter(task_name, method);
+        let mut futures = Vec::new();
+        for plugin in self.plugins.iter_mut() {
+            futures.push(plugin.vc_status_list_check(method, options, payload));
+        }
+        handle_results!(self, task_name, futures, method)
+    }
+
+    /// Verifies a verifiable credential, e.g. its proof or signature and its validity period.
+    /// Plugins verify one after another and resolve the DIDs of issuers and holders with the
+    /// other registered plugins, see
+    /// [`HostResolver`](https://docs.rs/vade/*/vade/struct.HostResolver.html).
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to verify a credential for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let results = vade.vc_verify("did:example", "", "").await?;
+    ///     if !results.is_empty() {
+    ///         println!("verification result: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vc_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let results = self.verify("vc_verify", method, options, payload).await?;
+        self.create_verification_reports(payload, results).await
+    }
+
     /// Creates a new zero-knowledge proof credential definition. A credential definition holds cryptographic key material
     /// and is needed by an issuer to issue a credential, thus needs to be created before issuance. A credential definition
     /// is always bound to one credential schema.
@@ -356,7 +931,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_create_credential_definition";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_create_credential_definition(method, options, payload));
@@ -394,7 +969,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_create_credential_offer";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_create_credential_offer(method, options, payload));
@@ -433,7 +1008,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_create_credential_proposal";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_create_credential_proposal(method, options, payload));
@@ -472,7 +1047,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_create_credential_schema";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_create_credential_schema(method, options, payload));
@@ -512,7 +1087,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_create_revocation_registry_definition";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(
@@ -553,7 +1128,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_update_revocation_registry";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_update_revocation_registry(method, options, payload));
@@ -592,7 +1167,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_issue_credential";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_issue_credential(method, options, payload));
@@ -630,7 +1205,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_finish_credential";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_finish_credential(method, options, payload));
@@ -669,7 +1244,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_present_proof";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_present_proof(method, options, payload));
@@ -707,7 +1282,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_propose_proof";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_propose_proof(method, options, payload));
@@ -745,7 +1320,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_request_credential";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_request_credential(method, options, payload));
@@ -783,7 +1358,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_request_proof";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_request_proof(method, options, payload));
@@ -791,6 +1366,47 @@ impl Vade {
         handle_results!(self, task_name, futures, method)
     }
 
+    /// Requests a zero-knowledge proof for the credentials required by a verifier policy. The
+    /// policy is compiled into the payload of `vc_zkp_request_proof`, see
+    /// [`VerifierPolicy::to_proof_request`](https://docs.rs/vade/*/vade/struct.VerifierPolicy.html#method.to_proof_request).
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to request a proof for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `policy` - verifier policy as JSON
+    /// * `payload` - JSON string with additional fields for the request (e.g. verifier and prover DIDs)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let policy = r#"{ "credentials": [{ "credentialTypes": ["IdCardCredential"] }] }"#;
+    ///     let results = vade
+    ///         .vc_zkp_request_proof_with_policy("did:example", "", policy, "")
+    ///         .await?;
+    ///     if !results.is_empty() {
+    ///         println!("created proof request: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vc_zkp_request_proof_with_policy(
+        &mut self,
+        method: &str,
+        options: &str,
+        policy: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let request = VerifierPolicy::from_json(policy)?.to_proof_request(payload)?;
+        self.vc_zkp_request_proof(method, options, &request.to_string())
+            .await
+    }
+
     /// Revokes a credential. After revocation the published revocation registry needs to be updated with information
     /// returned by this function.
     ///
@@ -822,7 +1438,7 @@ impl Vade {
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
         let task_name = "vc_zkp_revoke_credential";
-        self.log_fun_enter(&task_name, &method);
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
             futures.push(plugin.vc_zkp_revoke_credential(method, options, payload));
@@ -831,6 +1447,9 @@ impl Vade {
     }
 
     /// Verifies one or multiple proofs sent in a proof presentation.
+    /// Plugins verify one after another and resolve the DIDs of issuers and holders with the
+    /// other registered plugins, see
+    /// [`HostResolver`](https://docs.rs/vade/*/vade/struct.HostResolver.html).
     ///
     /// # Arguments
     ///
@@ -859,15 +1478,221 @@ impl Vade {
         options: &str,
         payload: &str,
     ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
-        let task_name = "vc_zkp_verify_proof";
-        self.log_fun_enter(&task_name, &method);
+        let results = self
+            .verify("vc_zkp_verify_proof", method, options, payload)
+            .await?;
+        self.create_verification_reports(payload, results).await
+    }
+
+    /// Verifies one or multiple proofs sent in a proof presentation and evaluates the results
+    /// against a verifier policy. Returns a [`PolicyEvaluation`](https://docs.rs/vade/*/vade/struct.PolicyEvaluation.html)
+    /// per plugin result, which is only `verified` if the proof is valid and every required
+    /// credential has been presented as required. Ages of credentials are checked against the
+    /// clock of the registered verification checks or the system time.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to verify a proof for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with the proof presentation to verify
+    /// * `policy` - verifier policy as JSON
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let policy = r#"{ "credentials": [{ "credentialTypes": ["IdCardCredential"] }] }"#;
+    ///     let results = vade
+    ///         .vc_zkp_verify_proof_with_policy("did:example", "", "", policy)
+    ///         .await?;
+    ///     if !results.is_empty() {
+    ///         println!("evaluated proof: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vc_zkp_verify_proof_with_policy(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+        policy: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let policy = VerifierPolicy::from_json(policy)?;
+        let results = self.vc_zkp_verify_proof(method, options, payload).await?;
+        let now = self.now();
+        let mut evaluations = Vec::new();
+        for result in results {
+            evaluations.push(match result {
+                Some(result) => Some(serde_json::to_string(
+                    &policy.evaluate(&result, payload, now),
+                )?),
+                None => None,
+            });
+        }
+        Ok(evaluations)
+    }
+
+    /// Creates a new verifiable presentation from one or multiple verifiable credentials and secures it
+    /// with a proof or an enveloping signature of the holder, depending on plugin implementation.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to create a presentation for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let results = vade.vp_create("did:example", "", "").await?;
+    ///     if !results.is_empty() {
+    ///         println!("created presentation: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vp_create(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let task_name = "vp_create";
+        self.log_fun_enter(task_name, method);
         let mut futures = Vec::new();
         for plugin in self.plugins.iter_mut() {
-            futures.push(plugin.vc_zkp_verify_proof(method, options, payload));
+            futures.push(plugin.vp_create(method, options, payload));
         }
         handle_results!(self, task_name, futures, method)
     }
 
+    /// Verifies a verifiable presentation and the credentials presented in it. Registered
+    /// verification checks are run on every presented credential.
+    /// Plugins verify one after another and resolve the DIDs of issuers and holders with the
+    /// other registered plugins, see
+    /// [`HostResolver`](https://docs.rs/vade/*/vade/struct.HostResolver.html).
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to verify a presentation for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::Vade;
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut vade = Vade::new();
+    ///     // // register example plugin e.g. with
+    ///     // vade.register_plugin(example_plugin);
+    ///     let results = vade.vp_verify("did:example", "", "").await?;
+    ///     if !results.is_empty() {
+    ///         println!("verification result: {}", results[0].as_ref().ok_or("result not found")?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn vp_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let results = self.verify("vp_verify", method, options, payload).await?;
+        self.create_verification_reports(payload, results).await
+    }
+
+    /// Runs given verification function on all plugins, one after another. While a plugin
+    /// verifies, all other plugins are lent to the host resolver, so the plugin can resolve the
+    /// DIDs of issuers and holders with them.
+    ///
+    /// # Arguments
+    ///
+    /// * `task_name` - "vc_verify", "vc_zkp_verify_proof" or "vp_verify"
+    /// * `method` - method to verify for
+    /// * `options` - options to pass to plugins
+    /// * `payload` - payload to pass to plugins
+    async fn verify(
+        &mut self,
+        task_name: &str,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        self.log_fun_enter(task_name, method);
+        let mut responses = Vec::new();
+        for index in 0..self.plugins.len() {
+            let mut plugin = self.plugins.remove(index);
+            let mut resolver = Vade::new();
+            resolver.plugins = std::mem::take(&mut self.plugins);
+            self.host_resolver.lend(Some(resolver));
+            let response = match task_name {
+                "vc_verify" => plugin.vc_verify(method, options, payload).await,
+                "vp_verify" => plugin.vp_verify(method, options, payload).await,
+                _ => plugin.vc_zkp_verify_proof(method, options, payload).await,
+            };
+            self.plugins = self
+                .host_resolver
+                .take()
+                .ok_or("plugins lent to host resolver have not been given back")?
+                .plugins;
+            self.plugins.insert(index, plugin);
+            match response {
+                Ok(VadePluginResultValue::Success(value)) => responses.push(value),
+                Ok(_) => (),
+                Err(e) => {
+                    return Err(Box::from(format!(
+                        "could not run {} for \"{}\"; {}",
+                        task_name, method, e
+                    )))
+                }
+            }
+        }
+        self.log_fun_leave(task_name, responses.len(), method);
+        Ok(responses)
+    }
+
+    /// Replaces given verification results with reports, if verification checks are registered.
+    async fn create_verification_reports(
+        &mut self,
+        payload: &str,
+        results: Vec<Option<String>>,
+    ) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let checks = match &self.verification_checks {
+            Some(checks) => Rc::clone(checks),
+            None => return Ok(results),
+        };
+        let mut reports = Vec::new();
+        for result in results {
+            reports.push(match result {
+                Some(result) => {
+                    Some(verification_report::create_report(self, &checks, payload, &result).await?)
+                }
+                None => None,
+            });
+        }
+        Ok(reports)
+    }
+
+    /// Returns the current time of the registered verification checks' clock or the system time.
+    pub(crate) fn now(&self) -> i64 {
+        match &self.verification_checks {
+            Some(checks) => checks.now(),
+            None => SystemClock.now(),
+        }
+    }
+
     /// Writes a debug message when entering a plugin function.
     ///
     /// # Arguments
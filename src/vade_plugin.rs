@@ -14,9 +14,12 @@
   limitations under the License.
 */
 
+use crate::{HostResolver, KeyStore};
 use async_trait::async_trait;
+use std::rc::Rc;
 
 /// Wrapper enum for a plugins return value
+#[derive(Clone, Debug, PartialEq)]
 pub enum VadePluginResultValue<T> {
     /// Plugin does not implement this function. This is returned by default as the
     /// [`VadePlugin`](https://docs.rs/vade/*/vade/trait.VadePlugin.html)
@@ -353,6 +356,266 @@ pub trait VadePlugin {
         Ok(VadePluginResultValue::NotImplemented)
     }
 
+    /// Hands over the key store registered on [`Vade`](https://docs.rs/vade/*/vade/struct.Vade.html),
+    /// so the plugin can sign and agree on secrets with keys referenced by their id, e.g. from a
+    /// `keyId` in `options`, instead of receiving secret keys. Called when the plugin or a key
+    /// store is registered. Plugins that do not use keys can ignore it.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_store` - key store to use
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use vade::{InMemoryKeyStore, KeyStore, VadePlugin};
+    /// // use some_crate:ExamplePlugin;
+    /// # struct ExamplePlugin { }
+    /// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+    /// # impl VadePlugin for ExamplePlugin {}
+    /// let mut ep: ExamplePlugin = ExamplePlugin::new();
+    /// let key_store: Rc<dyn KeyStore> = Rc::new(InMemoryKeyStore::new());
+    /// ep.set_key_store(Rc::clone(&key_store));
+    /// ```
+    fn set_key_store(&mut self, key_store: Rc<dyn KeyStore>) {}
+
+    /// Hands over the [`HostResolver`](https://docs.rs/vade/*/vade/struct.HostResolver.html) of
+    /// [`Vade`](https://docs.rs/vade/*/vade/struct.Vade.html), so the plugin can resolve DIDs with
+    /// the other plugins registered on the same `Vade` instance while verifying credentials and
+    /// presentations. Called when the plugin is registered. Plugins that do not resolve DIDs can
+    /// ignore it.
+    ///
+    /// # Arguments
+    ///
+    /// * `host_resolver` - resolver using the plugins of the hosting `Vade` instance
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use async_trait::async_trait;
+    /// use std::rc::Rc;
+    /// use vade::{HostResolver, VadePlugin};
+    /// struct ExamplePlugin {
+    ///     host_resolver: Option<Rc<HostResolver>>,
+    /// }
+    /// #[async_trait(?Send)]
+    /// impl VadePlugin for ExamplePlugin {
+    ///     fn set_host_resolver(&mut self, host_resolver: Rc<HostResolver>) {
+    ///         self.host_resolver = Some(host_resolver);
+    ///     }
+    /// }
+    /// ```
+    fn set_host_resolver(&mut self, host_resolver: Rc<HostResolver>) {}
+
+    /// Issues a new verifiable credential. The credential is secured with an embedded proof or an
+    /// enveloping signature, depending on plugin implementation.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to issue a credential for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{VadePlugin, VadePluginResultValue};
+    /// // use some_crate:ExamplePlugin;
+    /// # struct ExamplePlugin { }
+    /// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+    /// # impl VadePlugin for ExamplePlugin {}
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut ep: ExamplePlugin = ExamplePlugin::new();
+    ///     let result = ep.vc_issue("did:example", "", "").await?;
+    ///     if let VadePluginResultValue::Success(Some(value)) = result {
+    ///         println!("issued credential: {}", &value);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn vc_issue(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        Ok(VadePluginResultValue::NotImplemented)
+    }
+
+    /// Creates a new status list, e.g. a Bitstring Status List, and returns its status list credential,
+    /// that has to be secured and published by the issuer.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to create a status list for (e.g. "status-list")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{VadePlugin, VadePluginResultValue};
+    /// // use some_crate:ExamplePlugin;
+    /// # struct ExamplePlugin { }
+    /// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+    /// # impl VadePlugin for ExamplePlugin {}
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut ep: ExamplePlugin = ExamplePlugin::new();
+    ///     let result = ep.vc_status_list_create("status-list", "", "").await?;
+    ///     if let VadePluginResultValue::Success(Some(value)) = result {
+    ///         println!("created status list credential: {}", &value);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn vc_status_list_create(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        Ok(VadePluginResultValue::NotImplemented)
+    }
+
+    /// Allocates an index in a status list for a credential to be issued and adds a matching
+    /// `credentialStatus` entry to the credential.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to allocate a status list index with (e.g. "status-list")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{VadePlugin, VadePluginResultValue};
+    /// // use some_crate:ExamplePlugin;
+    /// # struct ExamplePlugin { }
+    /// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+    /// # impl VadePlugin for ExamplePlugin {}
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut ep: ExamplePlugin = ExamplePlugin::new();
+    ///     let result = ep.vc_status_list_allocate("status-list", "", "").await?;
+    ///     if let VadePluginResultValue::Success(Some(value)) = result {
+    ///         println!("credential with status entry: {}", &value);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn vc_status_list_allocate(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        Ok(VadePluginResultValue::NotImplemented)
+    }
+
+    /// Sets the status of an issued credential in its status list, e.g. to revoke or suspend it, and
+    /// returns the updated status list credential to publish.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to update a status list with (e.g. "status-list")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{VadePlugin, VadePluginResultValue};
+    /// // use some_crate:ExamplePlugin;
+    /// # struct ExamplePlugin { }
+    /// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+    /// # impl VadePlugin for ExamplePlugin {}
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut ep: ExamplePlugin = ExamplePlugin::new();
+    ///     let result = ep.vc_status_list_update("status-list", "", "").await?;
+    ///     if let VadePluginResultValue::Success(Some(value)) = result {
+    ///         println!("updated status list credential: {}", &value);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn vc_status_list_update(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        Ok(VadePluginResultValue::NotImplemented)
+    }
+
+    /// Checks the `credentialStatus` of a verifiable credential against its status lists.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to check a credential's status with (e.g. "status-list")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{VadePlugin, VadePluginResultValue};
+    /// // use some_crate:ExamplePlugin;
+    /// # struct ExamplePlugin { }
+    /// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+    /// # impl VadePlugin for ExamplePlugin {}
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut ep: ExamplePlugin = ExamplePlugin::new();
+    ///     let result = ep.vc_status_list_check("status-list", "", "").await?;
+    ///     if let VadePluginResultValue::Success(Some(value)) = result {
+    ///         println!("status of credential: {}", &value);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn vc_status_list_check(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        Ok(VadePluginResultValue::NotImplemented)
+    }
+
+    /// Verifies a verifiable credential, e.g. its proof or signature and its validity period.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to verify a credential for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{VadePlugin, VadePluginResultValue};
+    /// // use some_crate:ExamplePlugin;
+    /// # struct ExamplePlugin { }
+    /// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+    /// # impl VadePlugin for ExamplePlugin {}
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut ep: ExamplePlugin = ExamplePlugin::new();
+    ///     let result = ep.vc_verify("did:example", "", "").await?;
+    ///     if let VadePluginResultValue::Success(Some(value)) = result {
+    ///         println!("verification result: {}", &value);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn vc_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        Ok(VadePluginResultValue::NotImplemented)
+    }
+
     /// Creates a new zero-knowledge proof credential definition. A credential definition holds cryptographic key material
     /// and is needed by an issuer to issue a credential, thus needs to be created before issuance. A credential definition
     /// is always bound to one credential schema.
@@ -837,4 +1100,73 @@ pub trait VadePlugin {
     ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
         Ok(VadePluginResultValue::NotImplemented)
     }
+
+    /// Creates a new verifiable presentation from one or multiple verifiable credentials and secures it
+    /// with a proof or an enveloping signature of the holder, depending on plugin implementation.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to create a presentation for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{VadePlugin, VadePluginResultValue};
+    /// // use some_crate:ExamplePlugin;
+    /// # struct ExamplePlugin { }
+    /// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+    /// # impl VadePlugin for ExamplePlugin {}
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut ep: ExamplePlugin = ExamplePlugin::new();
+    ///     let result = ep.vp_create("did:example", "", "").await?;
+    ///     if let VadePluginResultValue::Success(Some(value)) = result {
+    ///         println!("created presentation: {}", &value);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn vp_create(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        Ok(VadePluginResultValue::NotImplemented)
+    }
+
+    /// Verifies a verifiable presentation and the credentials presented in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to verify a presentation for (e.g. "did:example")
+    /// * `options` - JSON string with additional information supporting the request (e.g. authentication data)
+    /// * `payload` - JSON string with information for the request (e.g. actual data to write)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use vade::{VadePlugin, VadePluginResultValue};
+    /// // use some_crate:ExamplePlugin;
+    /// # struct ExamplePlugin { }
+    /// # impl ExamplePlugin { pub fn new() -> Self { ExamplePlugin {} } }
+    /// # impl VadePlugin for ExamplePlugin {}
+    /// async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut ep: ExamplePlugin = ExamplePlugin::new();
+    ///     let result = ep.vp_verify("did:example", "", "").await?;
+    ///     if let VadePluginResultValue::Success(Some(value)) = result {
+    ///         println!("verification result: {}", &value);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    async fn vp_verify(
+        &mut self,
+        method: &str,
+        options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        Ok(VadePluginResultValue::NotImplemented)
+    }
 }
@@ -0,0 +1,213 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Resolution of verification methods referenced by DID URLs, e.g. in a JWS `kid` or a proof's
+//! `verificationMethod`. `did:key` DIDs are expanded locally, all other DIDs are resolved with a
+//! `Vade` instance given by the calling plugin, which is either a resolver configured on the
+//! plugin or the plugins of its hosting `Vade` instance lent by the
+//! [`HostResolver`](../struct.HostResolver.html). Verifying plugins use
+//! [`resolve_authorized_method`](fn.resolve_authorized_method.html) to check, that a signing key
+//! belongs to the issuer or holder and is authorized for the verification relationship of its use.
+
+use crate::{
+    jwk,
+    multikey::{self, KeyType},
+    Vade,
+};
+use serde_json::{json, Value};
+
+/// verification relationships a verification method can be referenced from
+pub(crate) const RELATIONSHIPS: [&str; 5] = [
+    "authentication",
+    "assertionMethod",
+    "keyAgreement",
+    "capabilityInvocation",
+    "capabilityDelegation",
+];
+
+/// Returns the DID document of given DID, with the fragment and query of a DID URL ignored.
+pub(crate) async fn resolve_did_document(
+    resolver: Option<&mut Vade>,
+    did_url: &str,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let did = did_url.split(['#', '?']).next().unwrap_or_default();
+    if did.starts_with("did:key:") {
+        return get_did_key_document(did);
+    }
+    let resolver = resolver.ok_or_else(|| {
+        format!(
+            r#"cannot resolve "{}" without a resolver, only did:key DIDs can be resolved locally"#,
+            did
+        )
+    })?;
+    let results = resolver.did_resolve(did).await?;
+    let resolved = results
+        .into_iter()
+        .flatten()
+        .next()
+        .ok_or_else(|| format!(r#"could not resolve "{}""#, did))?;
+    let resolved: Value = serde_json::from_str(&resolved)?;
+    // plugins may return full resolution results instead of DID documents
+    match resolved.get("didDocument") {
+        Some(document) if document.is_object() => Ok(document.clone()),
+        _ => Ok(resolved),
+    }
+}
+
+/// Finds the verification method with given id in given DID document. Returns the method and the
+/// verification relationships it is referenced from.
+pub(crate) fn find_verification_method(
+    document: &Value,
+    id: &str,
+) -> Result<(Value, Vec<String>), Box<dyn std::error::Error>> {
+    let document_id = document["id"].as_str().unwrap_or_default();
+    let matches = |candidate: &str| {
+        candidate == id
+            || (candidate.starts_with('#') && format!("{}{}", document_id, candidate) == id)
+    };
+
+    let mut method = None;
+    let mut relationships = Vec::new();
+    let methods = document["verificationMethod"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    for candidate in methods.iter() {
+        if matches(candidate["id"].as_str().unwrap_or_default()) {
+            method = Some(candidate.clone());
+        }
+    }
+    for relationship in RELATIONSHIPS.iter() {
+        let entries = document[*relationship]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        for entry in entries.iter() {
+            match entry {
+                Value::String(reference) if matches(reference) => {
+                    relationships.push(relationship.to_string())
+                }
+                Value::Object(_) if matches(entry["id"].as_str().unwrap_or_default()) => {
+                    method = Some(entry.clone());
+                    relationships.push(relationship.to_string());
+                }
+                _ => (),
+            }
+        }
+    }
+
+    match method {
+        Some(method) => Ok((method, relationships)),
+        None => Err(Box::from(format!(
+            r#"DID document "{}" has no verification method "{}""#,
+            document_id, id
+        ))),
+    }
+}
+
+/// Returns the public key of given verification method.
+pub(crate) fn get_public_key(
+    method: &Value,
+) -> Result<(KeyType, Vec<u8>), Box<dyn std::error::Error>> {
+    if let Some(public_key) = method["publicKeyMultibase"].as_str() {
+        return multikey::decode_public_key(public_key);
+    }
+    if method["publicKeyJwk"].is_object() {
+        return jwk::public_key_from_jwk(&method["publicKeyJwk"]);
+    }
+    Err(Box::from(format!(
+        r#"verification method "{}" has no supported key material"#,
+        method["id"].as_str().unwrap_or_default()
+    )))
+}
+
+/// Resolves the verification method with given DID URL and checks, that it belongs to given
+/// controller and is authorized for given verification relationship, e.g. "assertionMethod" for
+/// issuing credentials or "authentication" for presenting them.
+///
+/// # Arguments
+///
+/// * `resolver` - `Vade` instance to resolve DIDs other than `did:key` with
+/// * `id` - DID URL of verification method
+/// * `controller` - DID, that has to control the verification method, if known
+/// * `relationship` - verification relationship the method has to be referenced from
+pub(crate) async fn resolve_authorized_method(
+    resolver: Option<&mut Vade>,
+    id: &str,
+    controller: Option<&str>,
+    relationship: &str,
+) -> Result<Value, String> {
+    if !RELATIONSHIPS.contains(&relationship) {
+        return Err(format!(
+            r#"unknown verification relationship "{}""#,
+            relationship
+        ));
+    }
+    let did = id.split(['#', '?']).next().unwrap_or_default();
+    if let Some(controller) = controller {
+        if did != controller {
+            return Err(format!(
+                r#"verification method "{}" does not belong to "{}""#,
+                id, controller
+            ));
+        }
+    }
+    let document = resolve_did_document(resolver, id)
+        .await
+        .map_err(|e| format!(r#"could not resolve DID "{}"; {}"#, did, e))?;
+    let (mut method, relationships) =
+        find_verification_method(&document, id).map_err(|e| e.to_string())?;
+    if !relationships.iter().any(|r| r == relationship) {
+        return Err(format!(
+            r#"verification method "{}" is not authorized for "{}""#,
+            id, relationship
+        ));
+    }
+    // relative ids are returned as the absolute DID URL they have been resolved with
+    if method["id"]
+        .as_str()
+        .is_some_and(|method_id| method_id.starts_with('#'))
+    {
+        method["id"] = Value::from(id);
+    }
+    Ok(method)
+}
+
+/// Expands a `did:key` DID to its DID document.
+fn get_did_key_document(did: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    let public_key = &did["did:key:".len()..];
+    let (key_type, _) = multikey::decode_public_key(public_key)?;
+    let id = format!("{}#{}", did, public_key);
+    let mut document = json!({
+        "@context": ["https://www.w3.org/ns/did/v1", "https://w3id.org/security/multikey/v1"],
+        "id": did,
+        "verificationMethod": [{
+            "id": id,
+            "type": "Multikey",
+            "controller": did,
+            "publicKeyMultibase": public_key,
+        }],
+    });
+    // X25519 keys can only be used for key agreement, Ed25519 and BLS12-381 keys only for signing
+    for relationship in RELATIONSHIPS.iter().filter(|r| match key_type {
+        KeyType::X25519 => **r == "keyAgreement",
+        KeyType::Ed25519 | KeyType::Bls12381G2 => **r != "keyAgreement",
+        KeyType::P256 | KeyType::Secp256k1 => true,
+    }) {
+        document[*relationship] = json!([id]);
+    }
+    Ok(document)
+}
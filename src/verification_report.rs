@@ -0,0 +1,353 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Checks `Vade` runs on the results of credential verifications on top of the plugins' own
+//! checks: validity periods against an injectable clock, the `credentialStatus` of credentials
+//! and optionally their issuers against a trust registry, reported together with the plugins'
+//! results in a [`VerificationReport`].
+
+use crate::{jwt, trust_registry, TrustRegistry, Vade};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+const STATUS_LIST_METHOD: &str = "status-list";
+
+/// Source of the current time for checking validity periods, e.g. to verify credentials as of
+/// a given point in time or to get reproducible results in tests.
+pub trait Clock {
+    /// Returns the current time as unix timestamp in seconds.
+    fn now(&self) -> i64;
+}
+
+/// [`Clock`] returning the system time.
+#[derive(Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+}
+
+/// [`Clock`] always returning the same time.
+pub struct FixedClock {
+    time: i64,
+}
+
+impl FixedClock {
+    /// Creates a new `FixedClock`.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - unix timestamp in seconds to return
+    pub fn new(time: i64) -> Self {
+        FixedClock { time }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> i64 {
+        self.time
+    }
+}
+
+/// Checks `Vade` runs after `vc_verify`, `vp_verify` and `vc_zkp_verify_proof`, registered with
+/// [`register_verification_checks`](https://docs.rs/vade/*/vade/struct.Vade.html#method.register_verification_checks).
+/// By default, validity periods are checked against the system time and statuses with the
+/// plugins handling `vc_status_list_check` for the method "status-list".
+///
+/// # Example
+///
+/// ```
+/// use vade::{FixedClock, Vade, VerificationChecks};
+///
+/// let mut vade = Vade::new();
+/// vade.register_verification_checks(
+///     VerificationChecks::new().with_clock(Box::from(FixedClock::new(1_700_000_000))),
+/// );
+/// ```
+pub struct VerificationChecks {
+    clock: Box<dyn Clock>,
+    validity: bool,
+    status: bool,
+    status_method: String,
+    trust_registry: Option<Box<dyn TrustRegistry>>,
+}
+
+impl VerificationChecks {
+    /// Creates new `VerificationChecks` checking validity periods and statuses.
+    pub fn new() -> Self {
+        VerificationChecks {
+            clock: Box::from(SystemClock),
+            validity: true,
+            status: true,
+            status_method: STATUS_LIST_METHOD.to_string(),
+            trust_registry: None,
+        }
+    }
+
+    /// Checks validity periods against given clock instead of the system time.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - clock to get current time from
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Checks statuses by calling `vc_status_list_check` with given method.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - method to check statuses with
+    pub fn with_status_method(mut self, method: &str) -> Self {
+        self.status_method = method.to_string();
+        self
+    }
+
+    /// Checks, that issuers of credentials are trusted by given registry to issue them.
+    ///
+    /// # Arguments
+    ///
+    /// * `trust_registry` - registry to check issuers with, e.g. a [`TrustList`](crate::TrustList)
+    pub fn with_trust_registry(mut self, trust_registry: Box<dyn TrustRegistry>) -> Self {
+        self.trust_registry = Some(trust_registry);
+        self
+    }
+
+    /// Returns the current time of the checks' clock.
+    pub(crate) fn now(&self) -> i64 {
+        self.clock.now()
+    }
+
+    /// Does not check validity periods.
+    pub fn without_validity(mut self) -> Self {
+        self.validity = false;
+        self
+    }
+
+    /// Does not check statuses.
+    pub fn without_status(mut self) -> Self {
+        self.status = false;
+        self
+    }
+}
+
+impl Default for VerificationChecks {
+    fn default() -> Self {
+        VerificationChecks::new()
+    }
+}
+
+/// Outcome of a single check of a [`VerificationReport`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationCheck {
+    /// kind of check: "proof" for the plugin's verification, "validity", "status" or "trust"
+    pub check: String,
+    /// index of checked credential, if the check concerns a single credential
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<usize>,
+    /// `true` if check passed
+    pub passed: bool,
+    /// reason, why check failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// additional information, e.g. the statuses of a credential
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl VerificationCheck {
+    fn new(check: &str, credential: Option<usize>, error: Option<String>) -> Self {
+        VerificationCheck {
+            check: check.to_string(),
+            credential,
+            passed: error.is_none(),
+            error,
+            details: None,
+        }
+    }
+}
+
+/// Report `Vade` returns instead of a plugin's verification result, if
+/// [`VerificationChecks`] are registered. Lists every check with its outcome, checks of
+/// statuses are only listed for credentials with a `credentialStatus`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationReport {
+    /// `true` if all checks passed
+    pub verified: bool,
+    /// performed checks
+    pub checks: Vec<VerificationCheck>,
+    /// verification result of plugin
+    pub result: Value,
+}
+
+/// Creates a verification report for given result of a plugin.
+///
+/// # Arguments
+///
+/// * `vade` - `Vade` instance to check statuses with
+/// * `checks` - checks to run
+/// * `payload` - payload given to the plugin
+/// * `result` - verification result of plugin
+pub(crate) async fn create_report(
+    vade: &mut Vade,
+    checks: &VerificationChecks,
+    payload: &str,
+    result: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let result: Value = serde_json::from_str(result).unwrap_or_else(|_| Value::from(result));
+    let mut report = VerificationReport {
+        verified: false,
+        checks: vec![check_result(&result)],
+        result: Value::Null,
+    };
+    let credentials = get_credentials(&result, payload);
+    for (index, credential) in credentials.iter().enumerate() {
+        if checks.validity {
+            let error = check_validity(credential, checks.clock.now()).err();
+            report
+                .checks
+                .push(VerificationCheck::new("validity", Some(index), error));
+        }
+        if let Some(trust_registry) = &checks.trust_registry {
+            let error = trust_registry::check_credential(trust_registry.as_ref(), credential).err();
+            report
+                .checks
+                .push(VerificationCheck::new("trust", Some(index), error));
+        }
+        if checks.status && credential.contains_key("credentialStatus") {
+            report
+                .checks
+                .push(check_status(vade, &checks.status_method, credential, index).await);
+        }
+    }
+    report.verified = report.checks.iter().all(|check| check.passed);
+    report.result = result;
+    Ok(serde_json::to_string(&report)?)
+}
+
+/// Checks the plugin's own verdict, given as `verified` or as `status` "verified".
+pub(crate) fn check_result(result: &Value) -> VerificationCheck {
+    let verified = result
+        .get("verified")
+        .and_then(Value::as_bool)
+        .unwrap_or_else(|| result.get("status").and_then(Value::as_str) == Some("verified"));
+    let error = match verified {
+        true => None,
+        false => Some(
+            result
+                .get("error")
+                .or_else(|| result.get("reason"))
+                .and_then(Value::as_str)
+                .unwrap_or("verification failed")
+                .to_string(),
+        ),
+    };
+    VerificationCheck::new("proof", None, error)
+}
+
+/// Returns the verified credentials of given result, falls back to the payload given to the
+/// plugin, if the result does not contain them.
+pub(crate) fn get_credentials(result: &Value, payload: &str) -> Vec<Map<String, Value>> {
+    let credentials = match (
+        result.get("credential").or_else(|| result.get("claims")),
+        result.get("credentials"),
+    ) {
+        (Some(credential), _) => vec![credential.clone()],
+        (None, Some(Value::Array(credentials))) => credentials.clone(),
+        _ => match serde_json::from_str::<Value>(payload) {
+            Ok(Value::Object(mut payload)) => match payload.remove("verifiableCredential") {
+                Some(Value::Array(credentials)) => credentials,
+                Some(credential) => vec![credential],
+                None => vec![Value::Object(payload)],
+            },
+            _ => Vec::new(),
+        },
+    };
+    credentials
+        .into_iter()
+        .filter_map(|credential| match credential {
+            Value::Object(credential) => Some(credential),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Checks, that given time is within the validity period of given credential.
+fn check_validity(credential: &Map<String, Value>, now: i64) -> Result<(), String> {
+    let get = |names: [&str; 3]| -> Result<Option<i64>, String> {
+        for name in names.iter() {
+            match credential.get(*name) {
+                Some(Value::Number(time)) => return Ok(time.as_i64()),
+                Some(time) => return jwt::get_timestamp(Some(time)),
+                None => (),
+            }
+        }
+        Ok(None)
+    };
+    let valid_from = get(["validFrom", "issuanceDate", "nbf"])?;
+    let valid_until = get(["validUntil", "expirationDate", "exp"])?;
+    if valid_from.is_some_and(|valid_from| valid_from > now) {
+        return Err("credential is not valid yet".to_string());
+    }
+    if valid_until.is_some_and(|valid_until| valid_until <= now) {
+        return Err("credential has expired".to_string());
+    }
+    Ok(())
+}
+
+/// Checks the `credentialStatus` of given credential with the plugins registered on `Vade`.
+async fn check_status(
+    vade: &mut Vade,
+    method: &str,
+    credential: &Map<String, Value>,
+    index: usize,
+) -> VerificationCheck {
+    let payload = Value::Object(credential.clone()).to_string();
+    let result = match vade.vc_status_list_check(method, "", &payload).await {
+        Ok(results) => results.into_iter().flatten().next().ok_or_else(|| {
+            format!(
+                r#"no plugin could check credentialStatus with method "{}""#,
+                method
+            )
+        }),
+        Err(error) => Err(error.to_string()),
+    };
+    let result: Value = match result.and_then(|result| {
+        serde_json::from_str(&result).map_err(|e| format!("invalid status result; {}", e))
+    }) {
+        Ok(result) => result,
+        Err(error) => return VerificationCheck::new("status", Some(index), Some(error)),
+    };
+    let error = match result.get("verified").and_then(Value::as_bool) {
+        Some(true) => None,
+        _ => Some(
+            result
+                .get("error")
+                .and_then(Value::as_str)
+                .unwrap_or("status check failed")
+                .to_string(),
+        ),
+    };
+    let mut check = VerificationCheck::new("status", Some(index), error);
+    check.details = Some(json!({ "statuses": result.get("statuses") }));
+    check
+}
@@ -0,0 +1,426 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+//! Declarative policies of verifiers, that are compiled into proof requests and evaluated against
+//! verification results.
+
+use crate::{jwt, verification_report};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+const OPERATORS: [&str; 6] = [">=", ">", "<=", "<", "==", "!="];
+
+/// Constraint on the value of a claim, e.g. `{ "claim": "age", "operator": ">=", "value": 18 }`.
+/// Numbers and dates are compared by their value, other values can only be checked with "==" and
+/// "!=".
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Predicate {
+    /// path of claim in `credentialSubject`, e.g. "address.country"
+    pub claim: String,
+    /// one of ">=", ">", "<=", "<", "==" or "!="
+    pub operator: String,
+    /// value to compare claim with
+    pub value: Value,
+}
+
+/// Credential, a verifier asks for, with all conditions it has to fulfill.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialRequirement {
+    /// name of requirement, used in proof requests and evaluations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// types, the credential has to have
+    #[serde(default)]
+    pub credential_types: Vec<String>,
+    /// id of schema, the credential has to be issued for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    /// paths of claims in `credentialSubject`, that have to be disclosed
+    #[serde(default)]
+    pub required_claims: Vec<String>,
+    /// issuers, that are accepted, any issuer if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_issuers: Option<Vec<String>>,
+    /// maximum age of credential in seconds since its issuance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<i64>,
+    /// `true`, if the credential's status has to be checked and valid
+    #[serde(default)]
+    pub require_non_revoked: bool,
+    /// constraints on values of claims
+    #[serde(default)]
+    pub predicates: Vec<Predicate>,
+}
+
+/// Policy of a verifier, listing the credentials, that have to be presented. Policies are given
+/// as JSON, e.g.
+///
+/// ```json
+/// {
+///   "id": "age-check",
+///   "credentials": [{
+///     "id": "id-card",
+///     "credentialTypes": ["IdCardCredential"],
+///     "requiredClaims": ["name"],
+///     "allowedIssuers": ["did:example:authority"],
+///     "maxAge": 31536000,
+///     "requireNonRevoked": true,
+///     "predicates": [{ "claim": "age", "operator": ">=", "value": 18 }]
+///   }]
+/// }
+/// ```
+///
+/// A policy is turned into the payload of `vc_zkp_request_proof` with
+/// [`to_proof_request`](#method.to_proof_request) and checked against results of
+/// `vc_zkp_verify_proof` with [`evaluate`](#method.evaluate). `Vade` does both with
+/// [`vc_zkp_request_proof_with_policy`](https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_request_proof_with_policy)
+/// and [`vc_zkp_verify_proof_with_policy`](https://docs.rs/vade/*/vade/struct.Vade.html#method.vc_zkp_verify_proof_with_policy).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifierPolicy {
+    /// name of policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// credentials, that have to be presented
+    pub credentials: Vec<CredentialRequirement>,
+}
+
+/// Outcome of checking a single [`CredentialRequirement`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequirementEvaluation {
+    /// index of requirement in policy
+    pub requirement: usize,
+    /// id of requirement
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// `true` if a presented credential fulfills the requirement
+    pub passed: bool,
+    /// index of the fulfilling (or closest) credential in the presentation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<usize>,
+    /// conditions, that are not fulfilled
+    pub errors: Vec<String>,
+}
+
+/// Outcome of evaluating a verification result against a [`VerifierPolicy`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyEvaluation {
+    /// `true` if the proof is valid and all requirements are fulfilled
+    pub verified: bool,
+    /// id of policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// reason, why the proof itself has not been accepted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// outcomes of the policy's requirements
+    pub requirements: Vec<RequirementEvaluation>,
+    /// evaluated verification result
+    pub result: Value,
+}
+
+impl VerifierPolicy {
+    /// Parses and validates a policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - policy as JSON
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let policy: VerifierPolicy =
+            serde_json::from_str(json).map_err(|e| format!("invalid verifier policy; {}", e))?;
+        if policy.credentials.is_empty() {
+            return Err("verifier policy does not require any credential".into());
+        }
+        for predicate in policy.credentials.iter().flat_map(|c| c.predicates.iter()) {
+            if !OPERATORS.contains(&predicate.operator.as_str()) {
+                return Err(format!(
+                    r#"unsupported operator "{}" in verifier policy"#,
+                    predicate.operator
+                )
+                .into());
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Creates the payload for `vc_zkp_request_proof` with one entry in `subProofRequests` per
+    /// required credential. Plugins translate these entries into their own request formats.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - JSON with additional fields for the request, e.g. DIDs of verifier and
+    ///   prover, may be empty
+    pub fn to_proof_request(&self, payload: &str) -> Result<Value, Box<dyn std::error::Error>> {
+        let mut request = match payload.trim() {
+            "" => Map::new(),
+            payload => serde_json::from_str(payload)?,
+        };
+        let sub_proof_requests = self
+            .credentials
+            .iter()
+            .map(|requirement| {
+                let mut sub_proof_request = json!({
+                    "credentialTypes": requirement.credential_types,
+                    "revealedAttributes": requirement.required_claims,
+                    "predicates": requirement.predicates.iter().map(|predicate| json!({
+                        "attribute": predicate.claim,
+                        "pType": predicate.operator,
+                        "value": predicate.value,
+                    })).collect::<Vec<Value>>(),
+                    "requireNonRevoked": requirement.require_non_revoked,
+                });
+                for (name, value) in [
+                    ("id", json!(requirement.id)),
+                    ("schema", json!(requirement.schema)),
+                    ("issuers", json!(requirement.allowed_issuers)),
+                    ("maxAge", json!(requirement.max_age)),
+                ]
+                .iter()
+                {
+                    if !value.is_null() {
+                        sub_proof_request[*name] = value.clone();
+                    }
+                }
+                sub_proof_request
+            })
+            .collect::<Vec<Value>>();
+        if let Some(id) = &self.id {
+            request.insert("policy".to_string(), Value::from(id.as_str()));
+        }
+        request.insert(
+            "subProofRequests".to_string(),
+            Value::from(sub_proof_requests),
+        );
+        Ok(Value::Object(request))
+    }
+
+    /// Evaluates a result of `vc_zkp_verify_proof` against this policy. Results may be plain
+    /// plugin results or verification reports created by `Vade`, whose status checks are used to
+    /// decide about `requireNonRevoked`.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - verification result
+    /// * `payload` - presentation, that has been verified, to take credentials from, if the result
+    ///   does not contain them
+    /// * `now` - current time as unix timestamp to check ages of credentials against
+    pub fn evaluate(&self, result: &str, payload: &str, now: i64) -> PolicyEvaluation {
+        let result: Value = serde_json::from_str(result).unwrap_or_else(|_| Value::from(result));
+        let proof = verification_report::check_result(&result);
+        let (plugin_result, checks) = match result.get("checks") {
+            Some(checks) => (&result["result"], Some(checks)),
+            None => (&result, None),
+        };
+        let credentials = verification_report::get_credentials(plugin_result, payload);
+        let requirements: Vec<RequirementEvaluation> = self
+            .credentials
+            .iter()
+            .enumerate()
+            .map(|(index, requirement)| {
+                let mut evaluation = RequirementEvaluation {
+                    requirement: index,
+                    id: requirement.id.clone(),
+                    passed: false,
+                    credential: None,
+                    errors: vec!["no credential has been presented".to_string()],
+                };
+                for (credential_index, credential) in credentials.iter().enumerate() {
+                    let errors = check_requirement(
+                        requirement,
+                        credential,
+                        has_valid_status(checks, credential_index),
+                        now,
+                    );
+                    if evaluation.credential.is_none() || errors.len() < evaluation.errors.len() {
+                        evaluation.passed = errors.is_empty();
+                        evaluation.credential = Some(credential_index);
+                        evaluation.errors = errors;
+                    }
+                }
+                evaluation
+            })
+            .collect();
+        PolicyEvaluation {
+            verified: proof.passed && requirements.iter().all(|r| r.passed),
+            policy: self.id.clone(),
+            error: proof.error,
+            requirements,
+            result,
+        }
+    }
+}
+
+/// Returns `true`, if a verification report contains a passed status check for given credential.
+fn has_valid_status(checks: Option<&Value>, index: usize) -> bool {
+    let checks: Vec<verification_report::VerificationCheck> = checks
+        .and_then(|checks| serde_json::from_value(checks.clone()).ok())
+        .unwrap_or_default();
+    checks
+        .iter()
+        .any(|check| check.check == "status" && check.credential == Some(index) && check.passed)
+}
+
+/// Checks given credential against a requirement and returns all conditions, that are not met.
+fn check_requirement(
+    requirement: &CredentialRequirement,
+    credential: &Map<String, Value>,
+    has_valid_status: bool,
+    now: i64,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    let vc = credential
+        .get("vc")
+        .and_then(Value::as_object)
+        .unwrap_or(credential);
+
+    let types = get_strings(vc.get("type").or_else(|| credential.get("vct")));
+    for credential_type in requirement.credential_types.iter() {
+        if !types.contains(credential_type) {
+            errors.push(format!(
+                r#"credential is not of type "{}""#,
+                credential_type
+            ));
+        }
+    }
+    if let Some(schema) = &requirement.schema {
+        let schemas = get_strings(vc.get("credentialSchema").or_else(|| vc.get("schema")));
+        if !schemas.contains(schema) {
+            errors.push(format!(
+                r#"credential is not issued for schema "{}""#,
+                schema
+            ));
+        }
+    }
+    if let Some(allowed_issuers) = &requirement.allowed_issuers {
+        let issuer = get_strings(
+            credential
+                .get("issuer")
+                .or_else(|| credential.get("iss"))
+                .or_else(|| vc.get("issuer")),
+        );
+        if !issuer.iter().any(|issuer| allowed_issuers.contains(issuer)) {
+            errors.push(format!(
+                r#"issuer "{}" is not allowed"#,
+                issuer.first().map(String::as_str).unwrap_or_default()
+            ));
+        }
+    }
+    if let Some(max_age) = requirement.max_age {
+        match get_issuance_date(vc) {
+            Ok(Some(issued)) if now - issued > max_age => {
+                errors.push(format!("credential is older than {} seconds", max_age))
+            }
+            Ok(Some(_)) => (),
+            Ok(None) => errors.push("credential has no issuance date".to_string()),
+            Err(error) => errors.push(error),
+        }
+    }
+    if requirement.require_non_revoked && !has_valid_status {
+        errors.push("credential status has not been checked successfully".to_string());
+    }
+
+    let subject = match vc.get("credentialSubject") {
+        Some(Value::Array(subjects)) => subjects.first().unwrap_or(&Value::Null),
+        Some(subject) => subject,
+        None => &Value::Null,
+    };
+    let subject = match subject {
+        Value::Null => Value::Object(credential.clone()),
+        subject => subject.clone(),
+    };
+    for claim in requirement.required_claims.iter() {
+        if get_claim(&subject, claim).is_none() {
+            errors.push(format!(r#"claim "{}" is not disclosed"#, claim));
+        }
+    }
+    for predicate in requirement.predicates.iter() {
+        match get_claim(&subject, &predicate.claim) {
+            Some(value) if compare(value, &predicate.operator, &predicate.value) => (),
+            Some(_) => errors.push(format!(
+                r#"claim "{}" is not {} {}"#,
+                predicate.claim, predicate.operator, predicate.value
+            )),
+            None => errors.push(format!(r#"claim "{}" is not disclosed"#, predicate.claim)),
+        }
+    }
+    errors
+}
+
+/// Returns strings or ids of objects in given value or array.
+fn get_strings(value: Option<&Value>) -> Vec<String> {
+    let values = match value {
+        Some(Value::Array(values)) => values.iter().collect(),
+        Some(value) => vec![value],
+        None => Vec::new(),
+    };
+    values
+        .into_iter()
+        .filter_map(|value| match value {
+            Value::String(value) => Some(value.to_string()),
+            Value::Object(object) => object.get("id").and_then(Value::as_str).map(str::to_string),
+            _ => None,
+        })
+        .collect()
+}
+
+fn get_issuance_date(credential: &Map<String, Value>) -> Result<Option<i64>, String> {
+    for name in ["validFrom", "issuanceDate", "nbf", "iat"].iter() {
+        match credential.get(*name) {
+            Some(Value::Number(time)) => return Ok(time.as_i64()),
+            Some(time) => return jwt::get_timestamp(Some(time)),
+            None => (),
+        }
+    }
+    Ok(None)
+}
+
+fn get_claim<'a>(subject: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(subject, |value, name| value.get(name))
+        .filter(|value| !value.is_null())
+}
+
+fn compare(value: &Value, operator: &str, expected: &Value) -> bool {
+    let to_number = |value: &Value| match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.parse::<f64>().ok().or_else(|| {
+            jwt::get_timestamp(Some(value))
+                .ok()
+                .flatten()
+                .map(|time| time as f64)
+        }),
+        _ => None,
+    };
+    let ordering = match (to_number(value), to_number(expected)) {
+        (Some(value), Some(expected)) => value.partial_cmp(&expected),
+        _ => None,
+    };
+    match (operator, ordering) {
+        (">=", Some(ordering)) => ordering.is_ge(),
+        (">", Some(ordering)) => ordering.is_gt(),
+        ("<=", Some(ordering)) => ordering.is_le(),
+        ("<", Some(ordering)) => ordering.is_lt(),
+        ("==", Some(ordering)) => ordering.is_eq(),
+        ("!=", Some(ordering)) => ordering.is_ne(),
+        ("==", None) => value == expected,
+        ("!=", None) => value != expected,
+        _ => false,
+    }
+}
@@ -0,0 +1,265 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use vade::{plugins::BbsVadePlugin, InMemoryKeyStore, MockVadePlugin, Vade};
+
+const METHOD: &str = "bbs";
+
+fn get_vade() -> Vade {
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(InMemoryKeyStore::new()));
+    vade.register_plugin(Box::from(BbsVadePlugin::new()));
+    vade
+}
+
+fn get_credential(issuer: &str) -> Value {
+    json!({
+        "@context": [
+            "https://www.w3.org/ns/credentials/v2",
+            "https://www.w3.org/ns/credentials/examples/v2"
+        ],
+        "id": "urn:uuid:58172aac-d8ba-11ed-83dd-0b3aef56cc33",
+        "type": ["VerifiableCredential", "AlumniCredential"],
+        "name": "Alumni Credential",
+        "issuer": issuer,
+        "validFrom": "2023-01-01T00:00:00Z",
+        "credentialSubject": {
+            "name": "Erika Mustermann",
+            "alumniOf": "The School of Examples",
+            "degrees": [
+                { "type": "BachelorDegree", "name": "Bachelor of Science" },
+                { "type": "MasterDegree", "name": "Master of Science" },
+            ],
+        }
+    })
+}
+
+async fn call(vade: &mut Vade, function: &str, options: &Value, payload: &str) -> String {
+    let options = options.to_string();
+    let results = match function {
+        "vc_zkp_create_credential_definition" => {
+            vade.vc_zkp_create_credential_definition(METHOD, &options, payload)
+                .await
+        }
+        "vc_zkp_issue_credential" => {
+            vade.vc_zkp_issue_credential(METHOD, &options, payload)
+                .await
+        }
+        "vc_zkp_present_proof" => vade.vc_zkp_present_proof(METHOD, &options, payload).await,
+        _ => vade.vc_zkp_verify_proof(METHOD, &options, payload).await,
+    }
+    .unwrap();
+    results[0].clone().unwrap()
+}
+
+async fn verify(vade: &mut Vade, options: &Value, derived: &str) -> Value {
+    serde_json::from_str(&call(vade, "vc_zkp_verify_proof", options, derived).await).unwrap()
+}
+
+/// Issues a credential with a new `did:key` issuer, unless another issuer is given.
+async fn issue_as(vade: &mut Vade, issuer: Option<&str>, mandatory_pointers: &[&str]) -> String {
+    let definition = call(vade, "vc_zkp_create_credential_definition", &json!({}), "").await;
+    let definition: Value = serde_json::from_str(&definition).unwrap();
+    let method_id = definition["credentialDefinition"]["id"].as_str().unwrap();
+    let options = json!({
+        "keyId": definition["keyId"],
+        "verificationMethod": method_id,
+        "mandatoryPointers": mandatory_pointers,
+    });
+    let issuer = issuer.unwrap_or_else(|| method_id.split('#').next().unwrap());
+    call(
+        vade,
+        "vc_zkp_issue_credential",
+        &options,
+        &get_credential(issuer).to_string(),
+    )
+    .await
+}
+
+async fn issue(vade: &mut Vade, mandatory_pointers: &[&str]) -> String {
+    issue_as(vade, None, mandatory_pointers).await
+}
+
+#[tokio::test]
+async fn bbs_can_issue_present_and_verify_credentials() {
+    let mut vade = get_vade();
+    let definition = call(
+        &mut vade,
+        "vc_zkp_create_credential_definition",
+        &json!({}),
+        "",
+    )
+    .await;
+    let definition: Value = serde_json::from_str(&definition).unwrap();
+    let public_key = definition["credentialDefinition"]["publicKeyMultibase"]
+        .as_str()
+        .unwrap();
+    assert!(public_key.starts_with("zUC7"));
+    assert_eq!(
+        definition["credentialDefinition"]["id"],
+        format!("did:key:{}#{}", public_key, public_key)
+    );
+    assert!(definition["keyId"].is_string());
+    assert!(definition.get("secretKey").is_none());
+
+    let base = issue(&mut vade, &["/issuer", "/validFrom"]).await;
+    let base_value: Value = serde_json::from_str(&base).unwrap();
+    assert_eq!(base_value["proof"]["cryptosuite"], "bbs-2023");
+    // base proofs are only for the holder
+    let result = verify(&mut vade, &json!({}), &base).await;
+    assert_eq!(result["verified"], false);
+
+    let options = json!({
+        "selectivePointers": ["/credentialSubject/alumniOf", "/credentialSubject/degrees/1"],
+    });
+    let derived = call(&mut vade, "vc_zkp_present_proof", &options, &base).await;
+    let result = verify(&mut vade, &json!({}), &derived).await;
+    assert_eq!(result["verified"], true, "{}", result);
+    let disclosed = &result["credential"];
+    assert_eq!(disclosed["issuer"], base_value["issuer"]);
+    assert_eq!(
+        disclosed["credentialSubject"]["alumniOf"],
+        "The School of Examples"
+    );
+    assert!(disclosed["credentialSubject"].get("name").is_none());
+    assert!(disclosed.get("name").is_none());
+    assert_eq!(
+        disclosed["credentialSubject"]["degrees"],
+        json!([{ "type": "MasterDegree", "name": "Master of Science" }])
+    );
+}
+
+#[tokio::test]
+async fn bbs_creates_unlinkable_presentations() {
+    let mut vade = get_vade();
+    let base = issue(&mut vade, &["/issuer"]).await;
+    let options = json!({ "selectivePointers": ["/credentialSubject/alumniOf"] });
+    let first = call(&mut vade, "vc_zkp_present_proof", &options, &base).await;
+    let second = call(&mut vade, "vc_zkp_present_proof", &options, &base).await;
+    let first: Value = serde_json::from_str(&first).unwrap();
+    let second: Value = serde_json::from_str(&second).unwrap();
+    assert_ne!(first["proof"]["proofValue"], second["proof"]["proofValue"]);
+
+    for derived in [first, second].iter() {
+        let result = verify(&mut vade, &json!({}), &derived.to_string()).await;
+        assert_eq!(result["verified"], true, "{}", result);
+    }
+}
+
+#[tokio::test]
+async fn bbs_rejects_tampered_and_replayed_presentations() {
+    let mut vade = get_vade();
+    let base = issue(&mut vade, &["/issuer", "/validFrom"]).await;
+    let options = json!({
+        "selectivePointers": ["/credentialSubject/degrees"],
+        "nonce": "1f44d55f-f161-4938-a659-f8026467f126",
+    });
+    let derived = call(&mut vade, "vc_zkp_present_proof", &options, &base).await;
+    let nonce = json!({ "nonce": "1f44d55f-f161-4938-a659-f8026467f126" });
+    let result = verify(&mut vade, &nonce, &derived).await;
+    assert_eq!(result["verified"], true, "{}", result);
+
+    let result = verify(&mut vade, &json!({ "nonce": "other" }), &derived).await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["error"], "nonce does not match");
+
+    let mut tampered: Value = serde_json::from_str(&derived).unwrap();
+    tampered["credentialSubject"]["degrees"][1]["name"] = Value::from("Doctor of Science");
+    let result = verify(&mut vade, &nonce, &tampered.to_string()).await;
+    assert_eq!(result["verified"], false);
+
+    // mandatory claims cannot be withheld
+    let mut withheld: Value = serde_json::from_str(&derived).unwrap();
+    withheld.as_object_mut().unwrap().remove("validFrom");
+    let result = verify(&mut vade, &nonce, &withheld.to_string()).await;
+    assert_eq!(result["verified"], false);
+
+    // keys have to belong to the issuer, which has to be a DID
+    let options = json!({ "selectivePointers": ["/credentialSubject/degrees"] });
+    for (issuer, error) in [
+        (
+            "did:example:other",
+            r#"does not belong to "did:example:other""#,
+        ),
+        ("https://vc.example/issuers/5678", "issuer is no DID"),
+    ]
+    .iter()
+    {
+        let base = issue_as(&mut vade, Some(issuer), &["/issuer"]).await;
+        let derived = call(&mut vade, "vc_zkp_present_proof", &options, &base).await;
+        let result = verify(&mut vade, &json!({}), &derived).await;
+        assert_eq!(result["verified"], false);
+        assert!(
+            result["error"].as_str().unwrap().contains(error),
+            "{}",
+            result
+        );
+    }
+}
+
+#[tokio::test]
+async fn bbs_resolves_verification_methods_with_resolver() {
+    let issuer = "did:example:issuer";
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(BbsVadePlugin::new()));
+    // without key store, secret keys are only generated if explicitly exported
+    let results = vade
+        .vc_zkp_create_credential_definition(METHOD, "", "")
+        .await;
+    assert!(results.is_err());
+    let definition = call(
+        &mut vade,
+        "vc_zkp_create_credential_definition",
+        &json!({ "controller": issuer, "exportSecretKey": true }),
+        "",
+    )
+    .await;
+    let definition: Value = serde_json::from_str(&definition).unwrap();
+    assert!(definition.get("keyId").is_none());
+    let method = &definition["credentialDefinition"];
+    let mut mock = MockVadePlugin::new();
+    mock.expect("did_resolve")
+        .with_method(issuer)
+        .returning_success(
+            &json!({
+                "id": issuer,
+                "verificationMethod": [method],
+                "assertionMethod": [method["id"]],
+            })
+            .to_string(),
+        );
+    let mut resolver = Vade::new();
+    resolver.register_plugin(Box::from(mock));
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(BbsVadePlugin::new().with_resolver(resolver)));
+
+    let options = json!({
+        "secretKey": definition["secretKey"],
+        "verificationMethod": method["id"],
+    });
+    let base = call(
+        &mut vade,
+        "vc_zkp_issue_credential",
+        &options,
+        &get_credential(issuer).to_string(),
+    )
+    .await;
+    let options = json!({ "selectivePointers": ["/issuer"] });
+    let derived = call(&mut vade, "vc_zkp_present_proof", &options, &base).await;
+    let result = verify(&mut vade, &json!({}), &derived).await;
+    assert_eq!(result["verified"], true, "{}", result);
+}
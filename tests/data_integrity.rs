@@ -0,0 +1,533 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use std::rc::Rc;
+use vade::{
+    json_ld::ContextLoader, multikey::KeyType, plugins::DataIntegrityVadePlugin,
+    InMemoryChallengeStore, InMemoryKeyStore, KeyInfo, KeyStore, MockVadePlugin, Vade,
+};
+
+const METHOD: &str = "data-integrity";
+
+async fn get_vade(key_types: &[KeyType]) -> (Vade, Vec<KeyInfo>) {
+    let key_store = InMemoryKeyStore::new();
+    let mut keys = Vec::new();
+    for key_type in key_types.iter() {
+        keys.push(key_store.generate_key(*key_type).await.unwrap());
+    }
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(key_store));
+    vade.register_plugin(Box::from(DataIntegrityVadePlugin::new()));
+    (vade, keys)
+}
+
+/// Returns the `did:key` DID of given key.
+fn get_did(key: &KeyInfo) -> String {
+    format!("did:key:{}", key.public_key)
+}
+
+fn get_credential(issuer: &str) -> Value {
+    json!({
+        "@context": [
+            "https://www.w3.org/ns/credentials/v2",
+            "https://www.w3.org/ns/credentials/examples/v2"
+        ],
+        "id": "urn:uuid:58172aac-d8ba-11ed-83dd-0b3aef56cc33",
+        "type": ["VerifiableCredential", "AlumniCredential"],
+        "name": "Alumni Credential",
+        "description": "A minimum viable example of an Alumni Credential.",
+        "issuer": issuer,
+        "validFrom": "2023-01-01T00:00:00Z",
+        "credentialSubject": {
+            "id": "did:example:abcdefgh",
+            "alumniOf": "The School of Examples"
+        }
+    })
+}
+
+async fn call(vade: &mut Vade, function: &str, options: &Value, payload: &str) -> String {
+    let options = options.to_string();
+    let results = match function {
+        "vc_issue" => vade.vc_issue(METHOD, &options, payload).await,
+        "vc_verify" => vade.vc_verify(METHOD, &options, payload).await,
+        "vp_create" => vade.vp_create(METHOD, &options, payload).await,
+        "vp_verify" => vade.vp_verify(METHOD, &options, payload).await,
+        _ => {
+            vade.run_custom_function(METHOD, function, &options, payload)
+                .await
+        }
+    }
+    .unwrap();
+    results[0].clone().unwrap()
+}
+
+async fn verify(vade: &mut Vade, function: &str, options: &Value, secured: &str) -> Value {
+    serde_json::from_str(&call(vade, function, options, secured).await).unwrap()
+}
+
+#[tokio::test]
+async fn data_integrity_matches_eddsa_test_vectors() {
+    let (mut vade, _) = get_vade(&[]).await;
+    let credential = get_credential("https://vc.example/issuers/5678").to_string();
+    let vectors = [
+        (
+            "eddsa-rdfc-2022",
+            "z2YwC8z3ap7yx1nZYCg4L3j3ApHsF8kgPdSb5xoS1VR7vPG3F561B52hYnQF9iseabecm3ijx4K1FBTQsCZahKZme",
+        ),
+        (
+            "eddsa-jcs-2022",
+            "z2HnFSSPPBzR36zdDgK8PbEHeXbR56YF24jwMpt3R1eHXQzJDMWS93FCzpvJpwTWd3GAVFuUfjoJdcnTMuVor51aX",
+        ),
+    ];
+    for (cryptosuite, proof_value) in vectors.iter() {
+        let options = json!({
+            "cryptosuite": cryptosuite,
+            "secretKey": "z3u2en7t5LR2WtQH5PfFqMqwVHBeXouLzo6haApm8XHqvjxq",
+            "created": "2023-02-24T23:36:38Z",
+        });
+        let secured = call(&mut vade, "vc_issue", &options, &credential).await;
+        let secured: Value = serde_json::from_str(&secured).unwrap();
+        assert_eq!(
+            secured["proof"]["verificationMethod"],
+            "did:key:z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2#z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2"
+        );
+        assert_eq!(
+            secured["proof"]["proofValue"], *proof_value,
+            "{}",
+            cryptosuite
+        );
+
+        // the issuer of the test vectors is no DID, that could control the key
+        let result = verify(&mut vade, "vc_verify", &json!({}), &secured.to_string()).await;
+        assert_eq!(result["verified"], false);
+        assert_eq!(result["error"], "proof 0 is invalid; issuer is no DID");
+    }
+}
+
+#[tokio::test]
+async fn data_integrity_rejects_tampered_credentials_and_keeps_proof_sets() {
+    let (mut vade, keys) = get_vade(&[KeyType::Ed25519, KeyType::P256]).await;
+    let mut secured = get_credential(&get_did(&keys[0])).to_string();
+    for cryptosuite in ["eddsa-jcs-2022", "eddsa-rdfc-2022"].iter() {
+        let options = json!({ "keyId": keys[0].id, "cryptosuite": cryptosuite });
+        secured = call(&mut vade, "vc_issue", &options, &secured).await;
+    }
+    let credential: Value = serde_json::from_str(&secured).unwrap();
+    assert_eq!(credential["proof"].as_array().unwrap().len(), 2);
+    let result = verify(&mut vade, "vc_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], true, "{}", result);
+
+    let options = json!({ "keyId": keys[1].id, "cryptosuite": "ecdsa-rdfc-2019" });
+    let ecdsa = get_credential(&get_did(&keys[1])).to_string();
+    let ecdsa = call(&mut vade, "vc_issue", &options, &ecdsa).await;
+    let result = verify(&mut vade, "vc_verify", &json!({}), &ecdsa).await;
+    assert_eq!(result["verified"], true, "{}", result);
+
+    // keys of other DIDs than the issuer are rejected
+    let options = json!({ "keyId": keys[1].id, "cryptosuite": "ecdsa-rdfc-2019" });
+    let foreign = call(&mut vade, "vc_issue", &options, &secured).await;
+    let result = verify(&mut vade, "vc_verify", &json!({}), &foreign).await;
+    assert_eq!(result["verified"], false);
+    assert!(result["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("proof 2 is invalid; verification method"));
+
+    let mut tampered = credential.clone();
+    tampered["credentialSubject"]["alumniOf"] = Value::from("The School of Forgery");
+    let result = verify(&mut vade, "vc_verify", &json!({}), &tampered.to_string()).await;
+    assert_eq!(result["verified"], false);
+
+    // terms missing in the context would not be signed
+    let mut undefined = get_credential(&get_did(&keys[0]));
+    undefined["@context"] = json!(["https://www.w3.org/ns/credentials/v2"]);
+    let options = json!({ "keyId": keys[0].id }).to_string();
+    assert!(vade
+        .vc_issue(METHOD, &options, &undefined.to_string())
+        .await
+        .is_err());
+
+    let options = json!({ "keyId": keys[0].id, "cryptosuite": "ecdsa-rdfc-2019" }).to_string();
+    assert!(vade
+        .vc_issue(
+            METHOD,
+            &options,
+            &get_credential(&get_did(&keys[0])).to_string()
+        )
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn data_integrity_can_derive_ecdsa_sd_2023_credentials() {
+    let (mut vade, keys) = get_vade(&[KeyType::P256]).await;
+    let mut credential = get_credential(&get_did(&keys[0]));
+    credential["credentialSubject"] = json!({
+        "name": "Erika Mustermann",
+        "alumniOf": "The School of Examples",
+        "degrees": [
+            { "type": "BachelorDegree", "name": "Bachelor of Science" },
+            { "type": "MasterDegree", "name": "Master of Science" },
+        ],
+    });
+    let options = json!({
+        "keyId": keys[0].id,
+        "cryptosuite": "ecdsa-sd-2023",
+        "mandatoryPointers": ["/issuer", "/validFrom"],
+    });
+    let base = call(&mut vade, "vc_issue", &options, &credential.to_string()).await;
+    let result = verify(&mut vade, "vc_verify", &json!({}), &base).await;
+    assert_eq!(result["verified"], false);
+
+    let options = json!({
+        "selectivePointers": ["/credentialSubject/alumniOf", "/credentialSubject/degrees/1"],
+    });
+    let derived = call(&mut vade, "derive_proof", &options, &base).await;
+    let result = verify(&mut vade, "vc_verify", &json!({}), &derived).await;
+    assert_eq!(result["verified"], true, "{}", result);
+    let disclosed = &result["credential"];
+    assert_eq!(disclosed["issuer"], get_did(&keys[0]).as_str());
+    assert_eq!(
+        disclosed["credentialSubject"]["alumniOf"],
+        "The School of Examples"
+    );
+    assert!(disclosed["credentialSubject"].get("name").is_none());
+    assert!(disclosed.get("description").is_none());
+    assert_eq!(
+        disclosed["credentialSubject"]["degrees"],
+        json!([{ "type": "MasterDegree", "name": "Master of Science" }])
+    );
+
+    let mut tampered: Value = serde_json::from_str(&derived).unwrap();
+    tampered["credentialSubject"]["degrees"][0]["name"] = Value::from("Doctor of Science");
+    let result = verify(&mut vade, "vc_verify", &json!({}), &tampered.to_string()).await;
+    assert_eq!(result["verified"], false);
+
+    // mandatory claims cannot be withheld
+    let mut withheld: Value = serde_json::from_str(&derived).unwrap();
+    withheld.as_object_mut().unwrap().remove("validFrom");
+    let result = verify(&mut vade, "vc_verify", &json!({}), &withheld.to_string()).await;
+    assert_eq!(result["verified"], false);
+}
+
+#[tokio::test]
+async fn data_integrity_can_create_and_verify_presentations() {
+    let (mut vade, keys) = get_vade(&[KeyType::P256, KeyType::Ed25519]).await;
+    let holder = get_did(&keys[1]);
+    let mut credential = get_credential(&get_did(&keys[0]));
+    credential["credentialSubject"]["id"] = Value::from(holder.as_str());
+    let options = json!({ "keyId": keys[0].id });
+    let credential = call(&mut vade, "vc_issue", &options, &credential.to_string()).await;
+    let presentation = json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "type": "VerifiablePresentation",
+        "holder": holder,
+        "verifiableCredential": [serde_json::from_str::<Value>(&credential).unwrap()],
+    });
+    let options = json!({
+        "keyId": keys[1].id,
+        "challenge": "1f44d55f-f161-4938-a659-f8026467f126",
+        "domain": "https://verifier.example",
+    });
+    let secured = call(&mut vade, "vp_create", &options, &presentation.to_string()).await;
+    let secured_value: Value = serde_json::from_str(&secured).unwrap();
+    assert_eq!(secured_value["proof"]["proofPurpose"], "authentication");
+
+    let result = verify(
+        &mut vade,
+        "vp_verify",
+        &json!({ "challenge": "other" }),
+        &secured,
+    )
+    .await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(
+        result["error"],
+        "proof 0 is invalid; challenge does not match"
+    );
+
+    let options = json!({
+        "challenge": "1f44d55f-f161-4938-a659-f8026467f126",
+        "domain": "https://verifier.example",
+        "maxAge": 300,
+    });
+    let result = verify(&mut vade, "vp_verify", &options, &secured).await;
+    assert_eq!(result["verified"], true, "{}", result);
+    assert_eq!(result["credentials"].as_array().unwrap().len(), 1);
+
+    // challenges are only accepted once
+    let result = verify(&mut vade, "vp_verify", &options, &secured).await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(
+        result["error"],
+        r#"challenge "1f44d55f-f161-4938-a659-f8026467f126" has already been used"#
+    );
+    // also if verifiers do not ask for it
+    let result = verify(&mut vade, "vp_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(
+        result["error"],
+        r#"challenge "1f44d55f-f161-4938-a659-f8026467f126" has already been used"#
+    );
+
+    // credentials must not be used as presentations
+    let result = verify(&mut vade, "vp_verify", &json!({}), &credential).await;
+    assert_eq!(result["verified"], false);
+
+    // verifiers sharing a challenge store reject presentations replayed to other instances
+    let options = json!({ "keyId": keys[1].id, "challenge": "c2" });
+    let secured = call(&mut vade, "vp_create", &options, &presentation.to_string()).await;
+    let store = Rc::new(InMemoryChallengeStore::new());
+    let mut verifiers = Vec::new();
+    for _ in 0..2 {
+        let mut verifier = Vade::new();
+        verifier.register_plugin(Box::from(
+            DataIntegrityVadePlugin::new().with_challenge_store(store.clone()),
+        ));
+        verifiers.push(verifier);
+    }
+    let result = verify(&mut verifiers[0], "vp_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], true, "{}", result);
+    let result = verify(&mut verifiers[1], "vp_verify", &json!({}), &secured).await;
+    assert_eq!(result["error"], r#"challenge "c2" has already been used"#);
+}
+
+#[tokio::test]
+async fn data_integrity_binds_presented_credentials_to_holder() {
+    let (mut vade, keys) = get_vade(&[KeyType::Ed25519, KeyType::Ed25519]).await;
+    let options = json!({ "keyId": keys[0].id });
+    let credential = call(
+        &mut vade,
+        "vc_issue",
+        &options,
+        &get_credential(&get_did(&keys[0])).to_string(),
+    )
+    .await;
+
+    // credentials given as payload are wrapped into a presentation of the signer
+    let options = json!({ "keyId": keys[1].id, "challenge": "c1" });
+    let secured = call(&mut vade, "vp_create", &options, &credential).await;
+    let presentation: Value = serde_json::from_str(&secured).unwrap();
+    assert_eq!(
+        presentation["holder"],
+        format!("did:key:{}", keys[1].public_key)
+    );
+    assert_eq!(presentation["type"], json!(["VerifiablePresentation"]));
+
+    // the subject of the credential is not the holder
+    let result = verify(&mut vade, "vp_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["error"], "credential 0 is not bound to the holder");
+    let options = json!({ "holderBinding": false });
+    let result = verify(&mut vade, "vp_verify", &options, &secured).await;
+    assert_eq!(result["verified"], true, "{}", result);
+
+    // a presentation has to be signed by its holder
+    let options = json!({ "keyId": keys[1].id, "holder": "did:example:abcdefgh" });
+    let secured = call(&mut vade, "vp_create", &options, &credential).await;
+    let result = verify(&mut vade, "vp_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], false);
+    assert!(result["error"]
+        .as_str()
+        .unwrap()
+        .contains(r#"does not belong to "did:example:abcdefgh""#));
+
+    // presentations have to be fresh
+    let options = json!({ "keyId": keys[1].id, "created": "2020-01-01T00:00:00Z" });
+    let secured = call(&mut vade, "vp_create", &options, &credential).await;
+    let options = json!({ "maxAge": 300, "holderBinding": false });
+    let result = verify(&mut vade, "vp_verify", &options, &secured).await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["error"], "presentation is older than 300 seconds");
+}
+
+#[tokio::test]
+async fn data_integrity_resolves_verification_methods_with_resolver() {
+    let key_store = InMemoryKeyStore::new();
+    let key = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let issuer = "did:example:issuer";
+    let mut mock = MockVadePlugin::new();
+    mock.expect("did_resolve")
+        .with_method(issuer)
+        .returning_success(
+            &json!({
+                "id": issuer,
+                "verificationMethod": [{
+                    "id": "#key-1",
+                    "type": "Multikey",
+                    "controller": issuer,
+                    "publicKeyMultibase": key.public_key,
+                }],
+                "assertionMethod": ["#key-1"],
+            })
+            .to_string(),
+        );
+    let mut resolver = Vade::new();
+    resolver.register_plugin(Box::from(mock));
+
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(key_store));
+    vade.register_plugin(Box::from(
+        DataIntegrityVadePlugin::new().with_resolver(resolver),
+    ));
+    let credential = get_credential(issuer);
+    let options = json!({ "keyId": key.id, "verificationMethod": "did:example:issuer#key-1" });
+    let secured = call(&mut vade, "vc_issue", &options, &credential.to_string()).await;
+    let result = verify(&mut vade, "vc_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], true, "{}", result);
+
+    // key is not authorized for authentication
+    let presentation = json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "type": "VerifiablePresentation",
+    });
+    let secured = call(&mut vade, "vp_create", &options, &presentation.to_string()).await;
+    let result = verify(&mut vade, "vp_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], false);
+    assert!(result["error"]
+        .as_str()
+        .unwrap()
+        .contains("is not authorized for \"authentication\""));
+}
+
+#[tokio::test]
+async fn data_integrity_resolves_verification_methods_with_plugins_of_same_vade() {
+    let key_store = InMemoryKeyStore::new();
+    let key = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let issuer = "did:example:issuer";
+    let mut mock = MockVadePlugin::new();
+    mock.allow_unexpected_calls();
+    mock.expect("did_resolve")
+        .with_method(issuer)
+        .returning_success(
+            &json!({
+                "id": issuer,
+                "verificationMethod": [{
+                    "id": "#key-1",
+                    "type": "Multikey",
+                    "controller": issuer,
+                    "publicKeyMultibase": key.public_key,
+                }],
+                "assertionMethod": ["#key-1"],
+            })
+            .to_string(),
+        );
+
+    // did:example is only served by the mock registered next to the verifying plugin
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(key_store));
+    vade.register_plugin(Box::from(DataIntegrityVadePlugin::new()));
+    vade.register_plugin(Box::from(mock));
+    let credential = get_credential(issuer);
+    let options = json!({ "keyId": key.id, "verificationMethod": "did:example:issuer#key-1" });
+    let secured = call(&mut vade, "vc_issue", &options, &credential.to_string()).await;
+    let result = verify(&mut vade, "vc_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], true, "{}", result);
+
+    // resolution still works after the plugins have been given back
+    let results = vade.did_resolve(issuer).await.unwrap();
+    assert!(results[0].is_some());
+    let result = verify(&mut vade, "vc_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], true, "{}", result);
+}
+
+#[tokio::test]
+async fn data_integrity_rejects_keys_not_controlled_by_issuer() {
+    let (mut vade, keys) = get_vade(&[KeyType::Ed25519]).await;
+    let credential = get_credential("did:example:issuer");
+    let secured = call(
+        &mut vade,
+        "vc_issue",
+        &json!({ "keyId": keys[0].id }),
+        &credential.to_string(),
+    )
+    .await;
+
+    let result = verify(&mut vade, "vc_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], false);
+    assert!(result["error"]
+        .as_str()
+        .unwrap()
+        .contains(r#"does not belong to "did:example:issuer""#));
+}
+
+#[tokio::test]
+async fn data_integrity_rejects_unknown_verification_methods() {
+    let key_store = InMemoryKeyStore::new();
+    let key = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let issuer = "did:example:issuer";
+    let mut mock = MockVadePlugin::new();
+    mock.expect("did_resolve")
+        .with_method(issuer)
+        .returning_success(
+            &json!({
+                "id": issuer,
+                "verificationMethod": [{
+                    "id": "#key-1",
+                    "type": "Multikey",
+                    "controller": issuer,
+                    "publicKeyMultibase": key.public_key,
+                }],
+                "assertionMethod": ["#key-1"],
+            })
+            .to_string(),
+        );
+    let mut resolver = Vade::new();
+    resolver.register_plugin(Box::from(mock));
+
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(key_store));
+    vade.register_plugin(Box::from(
+        DataIntegrityVadePlugin::new().with_resolver(resolver),
+    ));
+    let credential = get_credential(issuer);
+    let options = json!({ "keyId": key.id, "verificationMethod": "did:example:issuer#key-2" });
+    let secured = call(&mut vade, "vc_issue", &options, &credential.to_string()).await;
+    let result = verify(&mut vade, "vc_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], false);
+    assert!(result["error"]
+        .as_str()
+        .unwrap()
+        .contains(r#"has no verification method "did:example:issuer#key-2""#));
+}
+
+#[tokio::test]
+async fn data_integrity_loads_contexts_with_context_loader() {
+    let context_url = "https://example.org/contexts/alumni/v1";
+    let key_store = InMemoryKeyStore::new();
+    let key = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let mut credential = get_credential(&get_did(&key));
+    credential["@context"] = json!(["https://www.w3.org/ns/credentials/v2", context_url]);
+    let loader = ContextLoader::new().with_context(
+        context_url,
+        json!({ "@context": { "@vocab": "https://example.org/alumni#" } }),
+    );
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(key_store));
+    vade.register_plugin(Box::from(
+        DataIntegrityVadePlugin::new().with_context_loader(Box::from(loader)),
+    ));
+    let options = json!({ "keyId": key.id });
+    let secured = call(&mut vade, "vc_issue", &options, &credential.to_string()).await;
+    let result = verify(&mut vade, "vc_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], true, "{}", result);
+
+    // the context is not fetched by plugins with the default loader
+    let (mut default_vade, _) = get_vade(&[]).await;
+    let result = verify(&mut default_vade, "vc_verify", &json!({}), &secured).await;
+    assert_eq!(result["verified"], false);
+}
@@ -0,0 +1,296 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use vade::{
+    multikey::KeyType, DidCommMessage, InMemoryKeyStore, KeyInfo, KeyStore, MockVadePlugin,
+    UnpackedMessage, Vade,
+};
+
+const ALICE: &str = "did:example:alice";
+const BOB: &str = "did:example:bob";
+const MESSAGE_TYPE: &str = "https://didcomm.org/basicmessage/2.0/message";
+
+struct Party {
+    vade: Vade,
+    signing_key: KeyInfo,
+    agreement_key: KeyInfo,
+}
+
+fn get_document(did: &str, signing_key: &KeyInfo, agreement_key: &KeyInfo) -> Value {
+    json!({
+        "id": did,
+        "verificationMethod": [
+            { "id": "#sign", "type": "Multikey", "controller": did, "publicKeyMultibase": signing_key.public_key },
+            { "id": "#agree", "type": "Multikey", "controller": did, "publicKeyMultibase": agreement_key.public_key },
+        ],
+        "authentication": ["#sign"],
+        "keyAgreement": ["#agree"],
+    })
+}
+
+/// Creates Alice and Bob with their own key stores, resolving each other's DID documents.
+async fn get_parties(key_type: KeyType) -> (Party, Party) {
+    let mut keys = Vec::new();
+    let mut key_stores = Vec::new();
+    for _ in 0..2 {
+        let key_store = InMemoryKeyStore::new();
+        let signing_key = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+        let agreement_key = key_store.generate_key(key_type).await.unwrap();
+        keys.push((signing_key, agreement_key));
+        key_stores.push(key_store);
+    }
+    let documents: Vec<(&str, Value)> = [ALICE, BOB]
+        .iter()
+        .zip(keys.iter())
+        .map(|(did, (signing_key, agreement_key))| {
+            (*did, get_document(did, signing_key, agreement_key))
+        })
+        .collect();
+    let mut parties = Vec::new();
+    for key_store in key_stores.into_iter() {
+        let mut mock = MockVadePlugin::new();
+        for (did, document) in documents.iter() {
+            mock.expect("did_resolve")
+                .with_method(*did)
+                .returning_success(&document.to_string())
+                .at_least(0);
+        }
+        let mut vade = Vade::new();
+        vade.register_plugin(Box::from(mock));
+        vade.register_key_store(Box::from(key_store));
+        let (signing_key, agreement_key) = keys.remove(0);
+        parties.push(Party {
+            vade,
+            signing_key,
+            agreement_key,
+        });
+    }
+    let bob = parties.pop().unwrap();
+    (parties.pop().unwrap(), bob)
+}
+
+fn get_message() -> String {
+    serde_json::to_string(
+        &DidCommMessage::new(MESSAGE_TYPE, json!({ "content": "hello Bob" }))
+            .with_from(ALICE)
+            .with_to(&[BOB]),
+    )
+    .unwrap()
+}
+
+async fn unpack(party: &mut Party, packed: &str) -> Result<UnpackedMessage, String> {
+    let unpacked = party
+        .vade
+        .didcomm_unpack("", packed)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(serde_json::from_str(&unpacked).unwrap())
+}
+
+#[tokio::test]
+async fn didcomm_authcrypt_authenticates_and_signs_messages() {
+    let (mut alice, mut bob) = get_parties(KeyType::X25519).await;
+    let options = json!({
+        "sender": { "kid": format!("{}#agree", ALICE), "keyId": alice.agreement_key.id },
+        "signer": { "kid": format!("{}#sign", ALICE), "keyId": alice.signing_key.id },
+    });
+    let packed = alice
+        .vade
+        .didcomm_pack(&options.to_string(), &get_message())
+        .await
+        .unwrap();
+    let envelope: Value = serde_json::from_str(&packed).unwrap();
+    assert_eq!(
+        envelope["recipients"][0]["header"]["kid"],
+        "did:example:bob#agree"
+    );
+    assert!(!packed.contains("hello Bob"));
+
+    let unpacked = unpack(&mut bob, &packed).await.unwrap();
+    assert_eq!(unpacked.message.body["content"], "hello Bob");
+    assert_eq!(unpacked.message.from.as_deref(), Some(ALICE));
+    let metadata = unpacked.metadata;
+    assert!(metadata.encrypted && metadata.authenticated && metadata.non_repudiation);
+    assert!(!metadata.anonymous_sender);
+    assert_eq!(
+        metadata.encrypted_from_kid.as_deref(),
+        Some("did:example:alice#agree")
+    );
+    assert_eq!(
+        metadata.sign_from.as_deref(),
+        Some("did:example:alice#sign")
+    );
+    assert_eq!(metadata.enc.as_deref(), Some("A256CBC-HS512"));
+
+    // the sender cannot decrypt, as the message is encrypted for Bob's keys only
+    let error = unpack(&mut alice, &packed).await.unwrap_err();
+    assert_eq!(error, "no key found to decrypt DIDComm message with");
+
+    let mut tampered = envelope.clone();
+    let ciphertext = tampered["ciphertext"].as_str().unwrap().to_string();
+    tampered["ciphertext"] = Value::from(format!("A{}", &ciphertext[1..]));
+    let error = unpack(&mut bob, &tampered.to_string()).await.unwrap_err();
+    assert_eq!(error, "could not decrypt DIDComm message");
+
+    // signed, authcrypted and wrapped in anoncrypt to hide the sender
+    let mut options = options;
+    options["protectSender"] = Value::from(true);
+    let packed = alice
+        .vade
+        .didcomm_pack(&options.to_string(), &get_message())
+        .await
+        .unwrap();
+    let envelope: Value = serde_json::from_str(&packed).unwrap();
+    let protected = {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+        URL_SAFE_NO_PAD
+            .decode(envelope["protected"].as_str().unwrap())
+            .unwrap()
+    };
+    let header: Value = serde_json::from_slice(&protected).unwrap();
+    assert_eq!(header["alg"], "ECDH-ES+A256KW");
+    assert!(header.get("skid").is_none());
+    let unpacked = unpack(&mut bob, &packed).await.unwrap();
+    assert_eq!(unpacked.message.body["content"], "hello Bob");
+    let metadata = unpacked.metadata;
+    assert!(metadata.encrypted && metadata.authenticated && metadata.non_repudiation);
+    assert!(!metadata.anonymous_sender);
+    assert_eq!(
+        metadata.encrypted_from_kid.as_deref(),
+        Some("did:example:alice#agree")
+    );
+}
+
+#[tokio::test]
+async fn didcomm_anoncrypt_encrypts_for_all_recipients() {
+    let (mut alice, mut bob) = get_parties(KeyType::P256).await;
+    let carol_key = InMemoryKeyStore::new()
+        .generate_key(KeyType::P256)
+        .await
+        .unwrap();
+    let carol = format!("did:key:{}", carol_key.public_key);
+    let options = json!({ "packing": "anoncrypt", "enc": "A256GCM", "to": [BOB, carol] });
+    let packed = alice
+        .vade
+        .didcomm_pack(&options.to_string(), &get_message())
+        .await
+        .unwrap();
+    let envelope: Value = serde_json::from_str(&packed).unwrap();
+    assert_eq!(envelope["recipients"].as_array().unwrap().len(), 2);
+
+    let unpacked = unpack(&mut bob, &packed).await.unwrap();
+    assert_eq!(unpacked.message.body["content"], "hello Bob");
+    assert!(unpacked.metadata.encrypted && unpacked.metadata.anonymous_sender);
+    assert!(!unpacked.metadata.authenticated);
+    assert_eq!(unpacked.metadata.enc.as_deref(), Some("A256GCM"));
+
+    // X25519 and P-256 keys cannot be mixed in one message
+    let options = json!({
+        "sender": { "kid": format!("{}#agree", ALICE), "keyId": alice.agreement_key.id },
+        "to": ["did:key:z6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc"],
+    });
+    let error = alice
+        .vade
+        .didcomm_pack(&options.to_string(), &get_message())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("has no P256 keyAgreement key"));
+}
+
+#[tokio::test]
+async fn didcomm_signed_messages_have_to_be_signed_by_sender() {
+    let (mut alice, mut bob) = get_parties(KeyType::X25519).await;
+    let plaintext = alice
+        .vade
+        .didcomm_pack(r#"{ "packing": "plaintext" }"#, &get_message())
+        .await
+        .unwrap();
+    let unpacked = unpack(&mut bob, &plaintext).await.unwrap();
+    assert_eq!(
+        unpacked.message.typ.as_deref(),
+        Some("application/didcomm-plain+json")
+    );
+    assert!(!unpacked.metadata.encrypted && !unpacked.metadata.non_repudiation);
+
+    let options = json!({
+        "packing": "signed",
+        "signer": { "kid": format!("{}#sign", ALICE), "keyId": alice.signing_key.id },
+    });
+    let signed = alice
+        .vade
+        .didcomm_pack(&options.to_string(), &get_message())
+        .await
+        .unwrap();
+    let unpacked = unpack(&mut bob, &signed).await.unwrap();
+    assert!(unpacked.metadata.non_repudiation && !unpacked.metadata.encrypted);
+
+    // Alice's key cannot sign messages from Bob
+    let mut envelope: Value = serde_json::from_str(&signed).unwrap();
+    let mut message: Value = serde_json::from_str(&get_message()).unwrap();
+    message["from"] = Value::from(BOB);
+    let options = json!({
+        "packing": "signed",
+        "signer": { "kid": format!("{}#sign", BOB), "keyId": alice.signing_key.id },
+    });
+    let error = alice
+        .vade
+        .didcomm_pack(&options.to_string(), &message.to_string())
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("does not match verification method"));
+    envelope["payload"] = Value::from(base64_url(&message.to_string()));
+    let error = unpack(&mut bob, &envelope.to_string()).await.unwrap_err();
+    assert!(error.contains("invalid signature"), "{}", error);
+}
+
+#[tokio::test]
+async fn didcomm_rejects_invalid_iv_and_tag_lengths() {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    let (mut alice, mut bob) = get_parties(KeyType::X25519).await;
+    for enc in ["A256GCM", "A256CBC-HS512"].iter() {
+        let options = json!({ "packing": "anoncrypt", "enc": enc, "to": [BOB] });
+        let packed = alice
+            .vade
+            .didcomm_pack(&options.to_string(), &get_message())
+            .await
+            .unwrap();
+        let envelope: Value = serde_json::from_str(&packed).unwrap();
+
+        // IVs of A256GCM have 12 bytes, IVs of A256CBC-HS512 16 bytes
+        let iv_length = if *enc == "A256GCM" { 16 } else { 12 };
+        let mut tampered = envelope.clone();
+        tampered["iv"] = Value::from(URL_SAFE_NO_PAD.encode(vec![0u8; iv_length]));
+        let error = unpack(&mut bob, &tampered.to_string()).await.unwrap_err();
+        assert_eq!(error, "initialization vector has an invalid length");
+
+        // a truncated tag would otherwise only have to match its first byte
+        let mut tampered = envelope.clone();
+        let tag = URL_SAFE_NO_PAD
+            .decode(envelope["tag"].as_str().unwrap())
+            .unwrap();
+        tampered["tag"] = Value::from(URL_SAFE_NO_PAD.encode(&tag[..1]));
+        let error = unpack(&mut bob, &tampered.to_string()).await.unwrap_err();
+        assert_eq!(error, "authentication tag has an invalid length");
+    }
+}
+
+fn base64_url(value: &str) -> String {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    URL_SAFE_NO_PAD.encode(value)
+}
@@ -0,0 +1,208 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::{cell::RefCell, rc::Rc};
+use vade::{
+    multikey::KeyType, DidCommHandler, DidCommMessage, InMemoryKeyStore, KeyStore, Matcher,
+    MockVadePlugin, UnpackedMessage, Vade,
+};
+
+const ALICE: &str = "did:example:alice";
+const BOB: &str = "did:example:bob";
+const BASIC_MESSAGE: &str = "https://didcomm.org/basicmessage";
+const TRUST_PING: &str = "https://didcomm.org/trust-ping/2.0";
+
+/// Logs handled messages as "<name>: <message type>" and replies to pings.
+struct Recorder {
+    name: &'static str,
+    message_types: Vec<String>,
+    log: Rc<RefCell<Vec<String>>>,
+}
+
+#[async_trait(?Send)]
+impl DidCommHandler for Recorder {
+    fn message_types(&self) -> Vec<String> {
+        self.message_types.clone()
+    }
+
+    async fn handle(
+        &self,
+        _vade: &mut Vade,
+        unpacked: &UnpackedMessage,
+    ) -> Result<Option<DidCommMessage>, Box<dyn std::error::Error>> {
+        let message = &unpacked.message;
+        self.log
+            .borrow_mut()
+            .push(format!("{}: {}", self.name, message.message_type));
+        Ok(match message.message_type.ends_with("/ping") {
+            true => Some(message.reply(&format!("{}/ping-response", TRUST_PING), json!({}))),
+            false => None,
+        })
+    }
+}
+
+fn get_vade(log: &Rc<RefCell<Vec<String>>>) -> Vade {
+    let mut vade = Vade::new();
+    let handlers = [
+        ("basic 2.0", format!("{}/2.0", BASIC_MESSAGE)),
+        ("basic 2.1", format!("{}/2.1", BASIC_MESSAGE)),
+        ("ping", format!("{}/ping", TRUST_PING)),
+    ];
+    for (name, message_type) in handlers.iter() {
+        vade.register_didcomm_handler(Box::from(Recorder {
+            name,
+            message_types: vec![message_type.to_string()],
+            log: Rc::clone(log),
+        }))
+        .unwrap();
+    }
+    vade
+}
+
+fn get_message(message_type: &str) -> DidCommMessage {
+    DidCommMessage::new(message_type, json!({ "content": "hello Bob" }))
+        .with_from(ALICE)
+        .with_to(&[BOB])
+}
+
+async fn receive(vade: &mut Vade, options: &str, message: &DidCommMessage) -> Vec<Option<String>> {
+    vade.didcomm_receive(options, &serde_json::to_string(message).unwrap())
+        .await
+        .unwrap()
+}
+
+async fn receive_problem_report(vade: &mut Vade, message_type: &str) -> DidCommMessage {
+    let message = get_message(message_type);
+    let results = receive(vade, "", &message).await;
+    let report = DidCommMessage::from_json(results[0].as_ref().unwrap()).unwrap();
+    assert_eq!(
+        report.message_type,
+        "https://didcomm.org/report-problem/2.0/problem-report"
+    );
+    assert_eq!(report.pthid, Some(message.id));
+    assert_eq!(report.to, Some(vec![ALICE.to_string()]));
+    report
+}
+
+#[tokio::test]
+async fn didcomm_protocols_route_messages_by_version() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut vade = get_vade(&log);
+    for version in ["2.0", "2.1", "2.3"].iter() {
+        let message = get_message(&format!("{}/{}/message", BASIC_MESSAGE, version));
+        assert_eq!(receive(&mut vade, "", &message).await, vec![None]);
+    }
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            "basic 2.0: https://didcomm.org/basicmessage/2.0/message",
+            "basic 2.1: https://didcomm.org/basicmessage/2.1/message",
+            "basic 2.1: https://didcomm.org/basicmessage/2.3/message",
+        ]
+    );
+
+    let ping = get_message(&format!("{}/ping", TRUST_PING));
+    let results = receive(&mut vade, "", &ping).await;
+    let response = DidCommMessage::from_json(results[0].as_ref().unwrap()).unwrap();
+    assert_eq!(
+        response.message_type,
+        "https://didcomm.org/trust-ping/2.0/ping-response"
+    );
+    assert_eq!(response.thid, Some(ping.id));
+    assert_eq!(response.from.as_deref(), Some(BOB));
+    assert_eq!(response.to, Some(vec![ALICE.to_string()]));
+}
+
+#[tokio::test]
+async fn didcomm_protocols_reply_with_problem_reports() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut vade = get_vade(&log);
+
+    let report = receive_problem_report(&mut vade, "https://didcomm.org/unknown/1.0/message").await;
+    assert_eq!(report.body["code"], "e.p.msg.unsupported-protocol");
+    assert_eq!(report.body["args"], json!(["https://didcomm.org/unknown"]));
+
+    let report = receive_problem_report(&mut vade, &format!("{}/3.0/message", BASIC_MESSAGE)).await;
+    assert_eq!(report.body["code"], "e.p.msg.unsupported-version");
+    assert_eq!(
+        report.body["args"],
+        json!(["3.0", BASIC_MESSAGE, "2.0, 2.1"])
+    );
+
+    let report = receive_problem_report(&mut vade, &format!("{}/pong", TRUST_PING)).await;
+    assert_eq!(report.body["code"], "e.p.msg.unsupported-message-type");
+    assert!(log.borrow().is_empty());
+
+    // problem reports and messages without sender are not answered
+    let message = report.reply(&report.message_type, report.body.clone());
+    assert!(receive(&mut vade, "", &message).await.is_empty());
+    let mut message = get_message("https://didcomm.org/unknown/1.0/message");
+    message.from = None;
+    assert!(receive(&mut vade, "", &message).await.is_empty());
+
+    // unsupported messages are still passed to plugins
+    let mut plugin = MockVadePlugin::new();
+    plugin
+        .expect("didcomm_receive")
+        .with_payload(Matcher::Contains("unknown/1.0".to_string()))
+        .returning_success("handled by plugin");
+    vade.register_plugin(Box::from(plugin));
+    let message = get_message("https://didcomm.org/unknown/1.0/message");
+    assert_eq!(
+        receive(&mut vade, "", &message).await,
+        vec![Some("handled by plugin".to_string())]
+    );
+
+    let error = vade
+        .register_didcomm_handler(Box::from(Recorder {
+            name: "invalid",
+            message_types: vec!["https://didcomm.org/basicmessage/message".to_string()],
+            log: Rc::clone(&log),
+        }))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"invalid DIDComm message type "https://didcomm.org/basicmessage/message""#
+    );
+}
+
+#[tokio::test]
+async fn didcomm_protocols_pack_replies() {
+    let key_store = InMemoryKeyStore::new();
+    let key = key_store.generate_key(KeyType::X25519).await.unwrap();
+    let alice = format!("did:key:{}", key.public_key);
+    let mut alice_vade = Vade::new();
+    alice_vade.register_key_store(Box::from(key_store));
+    let mut vade = get_vade(&Rc::new(RefCell::new(Vec::new())));
+
+    let ping = DidCommMessage::new(&format!("{}/ping", TRUST_PING), json!({}))
+        .with_from(&alice)
+        .with_to(&[BOB]);
+    let options = json!({ "pack": { "packing": "anoncrypt" } });
+    let results = receive(&mut vade, &options.to_string(), &ping).await;
+    let packed: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    assert!(packed.get("ciphertext").is_some());
+
+    let unpacked = alice_vade
+        .didcomm_unpack("", &packed.to_string())
+        .await
+        .unwrap();
+    let unpacked: UnpackedMessage = serde_json::from_str(&unpacked).unwrap();
+    assert_eq!(unpacked.message.thid, Some(ping.id));
+    assert!(unpacked.metadata.anonymous_sender);
+}
@@ -0,0 +1,466 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use vade::{
+    multikey::KeyType, DidCommMessage, FixedClock, InMemoryKeyStore, KeyInfo, KeyStore, Matcher,
+    Mediator, MockVadePlugin, UnpackedMessage, Vade, VerificationChecks,
+};
+
+const ALICE: &str = "did:example:alice";
+const BOB: &str = "did:example:bob";
+const MEDIATOR: &str = "did:example:mediator";
+const MEDIATOR_URI: &str = "https://mediator.example.com";
+const PICKUP: &str = "https://didcomm.org/messagepickup/3.0/";
+const COORDINATE_MEDIATION: &str = "https://didcomm.org/coordinate-mediation/3.0/";
+
+struct Party {
+    did: &'static str,
+    vade: Vade,
+    key: KeyInfo,
+}
+
+impl Party {
+    fn sender_options(&self) -> Value {
+        json!({ "sender": { "kid": format!("{}#agree", self.did), "keyId": self.key.id } })
+    }
+
+    async fn send(&mut self, message: &DidCommMessage, pack: Value) -> Value {
+        let results = self
+            .vade
+            .didcomm_send(
+                &json!({ "pack": pack }).to_string(),
+                &serde_json::to_string(message).unwrap(),
+            )
+            .await
+            .unwrap();
+        serde_json::from_str(results[0].as_ref().unwrap()).unwrap()
+    }
+
+    async fn unpack(&mut self, message: &Value) -> UnpackedMessage {
+        let unpacked = self
+            .vade
+            .didcomm_unpack("", &message.to_string())
+            .await
+            .unwrap();
+        serde_json::from_str(&unpacked).unwrap()
+    }
+
+    /// Sends a pickup request to the mediator and returns the unpacked reply.
+    async fn pickup(&mut self, mediator: &mut Party, name: &str, body: Value) -> DidCommMessage {
+        self.request(mediator, &format!("{}{}", PICKUP, name), body)
+            .await
+    }
+
+    /// Requests mediation and registers the party's DID with the mediator.
+    async fn register(&mut self, mediator: &mut Party) {
+        let grant = self
+            .request(
+                mediator,
+                &format!("{}mediate-request", COORDINATE_MEDIATION),
+                json!({}),
+            )
+            .await;
+        assert_eq!(grant.body["routing_did"], json!([MEDIATOR]));
+        let updates = json!({ "updates": [{ "recipient_did": self.did, "action": "add" }] });
+        let response = self
+            .request(
+                mediator,
+                &format!("{}recipient-update", COORDINATE_MEDIATION),
+                updates,
+            )
+            .await;
+        assert_eq!(response.body["updated"][0]["result"], "success");
+    }
+
+    /// Sends an authcrypted request to the mediator and returns the unpacked reply.
+    async fn request(
+        &mut self,
+        mediator: &mut Party,
+        message_type: &str,
+        body: Value,
+    ) -> DidCommMessage {
+        let request = DidCommMessage::new(message_type, body)
+            .with_from(self.did)
+            .with_to(&[MEDIATOR]);
+        let prepared = self.send(&request, self.sender_options()).await;
+        let results = mediator
+            .vade
+            .didcomm_receive("", &prepared["message"].to_string())
+            .await
+            .unwrap();
+        let reply: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+        let reply = self.unpack(&reply).await;
+        assert!(reply.metadata.authenticated);
+        assert_eq!(reply.message.thid.as_deref(), Some(request.id.as_str()));
+        reply.message
+    }
+}
+
+/// Creates Alice, Bob and a mediator, Bob's service endpoint is given as argument.
+async fn get_parties(bob_endpoint: Value) -> (Party, Party, Party) {
+    let mut keys = Vec::new();
+    let mut documents = Vec::new();
+    for did in [ALICE, BOB, MEDIATOR].iter() {
+        let key_store = InMemoryKeyStore::new();
+        let key = key_store.generate_key(KeyType::X25519).await.unwrap();
+        let mut document = json!({
+            "id": did,
+            "verificationMethod": [{
+                "id": "#agree",
+                "type": "Multikey",
+                "controller": did,
+                "publicKeyMultibase": key.public_key,
+            }],
+            "keyAgreement": ["#agree"],
+        });
+        let endpoint = match *did {
+            BOB => bob_endpoint.clone(),
+            MEDIATOR => Value::from(MEDIATOR_URI),
+            _ => Value::Null,
+        };
+        if !endpoint.is_null() {
+            document["service"] = json!([{
+                "id": "#didcomm",
+                "type": "DIDCommMessaging",
+                "serviceEndpoint": endpoint,
+            }]);
+        }
+        documents.push((*did, document));
+        keys.push((*did, key_store, key));
+    }
+    let mut parties = Vec::new();
+    for (did, key_store, key) in keys.into_iter() {
+        let mut mock = MockVadePlugin::new();
+        for (did, document) in documents.iter() {
+            mock.expect("did_resolve")
+                .with_method(*did)
+                .returning_success(&document.to_string())
+                .at_least(0);
+        }
+        mock.expect("didcomm_send").at_least(0);
+        mock.expect("didcomm_receive").at_least(0);
+        let mut vade = Vade::new();
+        vade.register_plugin(Box::from(mock));
+        vade.register_key_store(Box::from(key_store));
+        if did == MEDIATOR {
+            vade.register_mediator(Mediator::new(
+                MEDIATOR,
+                &format!("{}#agree", MEDIATOR),
+                &key.id,
+            ));
+        }
+        parties.push(Party { did, vade, key });
+    }
+    let mediator = parties.pop().unwrap();
+    let bob = parties.pop().unwrap();
+    (parties.pop().unwrap(), bob, mediator)
+}
+
+fn get_message() -> DidCommMessage {
+    DidCommMessage::new(
+        "https://didcomm.org/basicmessage/2.0/message",
+        json!({ "content": "hello Bob" }),
+    )
+    .with_from(ALICE)
+    .with_to(&[BOB])
+}
+
+#[tokio::test]
+async fn didcomm_routing_mediator_queues_forwards_for_pickup() {
+    let endpoint = json!({
+        "uri": MEDIATOR_URI,
+        "routingKeys": [format!("{}#agree", MEDIATOR)],
+    });
+    let (mut alice, mut bob, mut mediator) = get_parties(endpoint).await;
+    bob.register(&mut mediator).await;
+
+    let prepared = alice.send(&get_message(), alice.sender_options()).await;
+    assert_eq!(prepared["serviceEndpoint"], MEDIATOR_URI);
+    let forward = mediator.unpack(&prepared["message"]).await;
+    assert_eq!(
+        forward.message.message_type,
+        "https://didcomm.org/routing/2.0/forward"
+    );
+    assert_eq!(forward.message.body["next"], BOB);
+    assert!(forward.metadata.anonymous_sender);
+
+    let results = mediator
+        .vade
+        .didcomm_receive("", &prepared["message"].to_string())
+        .await
+        .unwrap();
+    assert_eq!(results, vec![None]);
+    let queued = |mediator: &Party| mediator.vade.mediator.as_ref().unwrap().message_count(BOB);
+    assert_eq!(queued(&mediator), 1);
+
+    let status = bob.pickup(&mut mediator, "status-request", json!({})).await;
+    assert_eq!(status.message_type, format!("{}status", PICKUP));
+    assert_eq!(status.body["message_count"], 1);
+    assert_eq!(status.body["live_delivery"], false);
+
+    let delivery = bob
+        .pickup(&mut mediator, "delivery-request", json!({ "limit": 10 }))
+        .await;
+    let attachment = &delivery.attachments.unwrap()[0];
+    let message = bob.unpack(&attachment["data"]["json"]).await;
+    assert_eq!(message.message.body["content"], "hello Bob");
+    assert_eq!(
+        message.metadata.encrypted_from_kid.as_deref(),
+        Some("did:example:alice#agree")
+    );
+
+    let status = bob
+        .pickup(
+            &mut mediator,
+            "messages-received",
+            json!({ "message_id_list": [attachment["id"]] }),
+        )
+        .await;
+    assert_eq!(status.body["message_count"], 0);
+    assert_eq!(queued(&mediator), 0);
+
+    let report = bob
+        .pickup(
+            &mut mediator,
+            "live-delivery-change",
+            json!({ "live_delivery": true }),
+        )
+        .await;
+    assert_eq!(report.body["code"], "e.m.live-mode-not-supported");
+}
+
+#[tokio::test]
+async fn didcomm_routing_mediator_serves_own_messages_only() {
+    let endpoint = json!({
+        "uri": MEDIATOR_URI,
+        "routingKeys": [format!("{}#agree", MEDIATOR)],
+    });
+    let (mut alice, mut bob, mut mediator) = get_parties(endpoint).await;
+    bob.register(&mut mediator).await;
+    let prepared = alice.send(&get_message(), alice.sender_options()).await;
+    mediator
+        .vade
+        .didcomm_receive("", &prepared["message"].to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        mediator.vade.mediator.as_ref().unwrap().message_count(BOB),
+        1
+    );
+
+    let status = alice
+        .pickup(&mut mediator, "status-request", json!({}))
+        .await;
+    assert_eq!(status.body["message_count"], 0);
+    let report = alice
+        .pickup(
+            &mut mediator,
+            "delivery-request",
+            json!({ "limit": 1, "recipient_did": BOB }),
+        )
+        .await;
+    assert_eq!(report.body["code"], "e.p.req.unauthorized");
+    assert!(report.body["comment"]
+        .as_str()
+        .unwrap()
+        .contains("cannot pick up messages"));
+
+    // anonymous pickup requests are answered with a plaintext problem report
+    let request = DidCommMessage::new(&format!("{}status-request", PICKUP), json!({}))
+        .with_from(BOB)
+        .with_to(&[MEDIATOR]);
+    let prepared = bob.send(&request, json!({ "packing": "anoncrypt" })).await;
+    let results = mediator
+        .vade
+        .didcomm_receive("", &prepared["message"].to_string())
+        .await
+        .unwrap();
+    let report = DidCommMessage::from_json(results[0].as_ref().unwrap()).unwrap();
+    assert_eq!(report.body["code"], "e.p.trust.crypto");
+    assert_eq!(report.pthid.as_deref(), Some(request.id.as_str()));
+}
+
+/// Passes given message to the mediator and returns its plaintext problem report.
+async fn get_report(mediator: &mut Party, message: String) -> DidCommMessage {
+    let results = mediator.vade.didcomm_receive("", &message).await.unwrap();
+    DidCommMessage::from_json(results[0].as_ref().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn didcomm_routing_mediator_rejects_forwards_without_granted_mediation() {
+    let endpoint = json!({
+        "uri": MEDIATOR_URI,
+        "routingKeys": [format!("{}#agree", MEDIATOR)],
+    });
+    let (mut alice, mut bob, mut mediator) = get_parties(endpoint).await;
+
+    // Bob has not requested mediation yet
+    let prepared = alice.send(&get_message(), alice.sender_options()).await;
+    let report = get_report(&mut mediator, prepared["message"].to_string()).await;
+    assert_eq!(report.body["code"], "e.p.req.not-granted");
+    let updates = json!({ "updates": [{ "recipient_did": BOB, "action": "add" }] });
+    let report = bob
+        .request(
+            &mut mediator,
+            &format!("{}recipient-update", COORDINATE_MEDIATION),
+            updates,
+        )
+        .await;
+    assert_eq!(report.body["code"], "e.p.req.not-granted");
+
+    // recipients can register their own DIDs only
+    bob.register(&mut mediator).await;
+    let updates = json!({ "updates": [
+        { "recipient_did": ALICE, "action": "add" },
+        { "recipient_did": BOB, "action": "add" },
+    ] });
+    let response = bob
+        .request(
+            &mut mediator,
+            &format!("{}recipient-update", COORDINATE_MEDIATION),
+            updates,
+        )
+        .await;
+    assert_eq!(response.body["updated"][0]["result"], "client_error");
+    assert_eq!(response.body["updated"][1]["result"], "no_change");
+    let keylist = bob
+        .request(
+            &mut mediator,
+            &format!("{}recipient-query", COORDINATE_MEDIATION),
+            json!({}),
+        )
+        .await;
+    assert_eq!(keylist.body["dids"], json!([{ "recipient_did": BOB }]));
+
+    // forwards have to be encrypted for the mediator
+    let forward = DidCommMessage::new(
+        "https://didcomm.org/routing/2.0/forward",
+        json!({ "next": BOB }),
+    )
+    .with_to(&[MEDIATOR]);
+    let forward = DidCommMessage {
+        attachments: Some(vec![json!({ "data": { "json": { "content": "spam" } } })]),
+        ..forward
+    };
+    let report = get_report(&mut mediator, serde_json::to_string(&forward).unwrap()).await;
+    assert_eq!(report.body["code"], "e.p.trust.crypto");
+    assert_eq!(report.pthid.as_deref(), Some(forward.id.as_str()));
+    assert_eq!(
+        mediator.vade.mediator.as_ref().unwrap().message_count(BOB),
+        0
+    );
+
+    let prepared = alice.send(&get_message(), alice.sender_options()).await;
+    let results = mediator
+        .vade
+        .didcomm_receive("", &prepared["message"].to_string())
+        .await
+        .unwrap();
+    assert_eq!(results, vec![None]);
+    assert_eq!(
+        mediator.vade.mediator.as_ref().unwrap().message_count(BOB),
+        1
+    );
+}
+
+#[tokio::test]
+async fn didcomm_routing_mediator_limits_queues() {
+    let endpoint = json!({
+        "uri": MEDIATOR_URI,
+        "routingKeys": [format!("{}#agree", MEDIATOR)],
+    });
+    let (mut alice, mut bob, mut mediator) = get_parties(endpoint).await;
+    let kid = format!("{}#agree", MEDIATOR);
+    let limited = Mediator::new(MEDIATOR, &kid, &mediator.key.id)
+        .with_limits(1, 10_000)
+        .with_lifetime(60);
+    mediator.vade.register_mediator(limited);
+    let now = 1_700_000_000;
+    let set_time = |mediator: &mut Party, time: i64| {
+        mediator.vade.register_verification_checks(
+            VerificationChecks::new().with_clock(Box::from(FixedClock::new(time))),
+        )
+    };
+    set_time(&mut mediator, now);
+    bob.register(&mut mediator).await;
+
+    let prepared = alice.send(&get_message(), alice.sender_options()).await;
+    let results = mediator
+        .vade
+        .didcomm_receive("", &prepared["message"].to_string())
+        .await
+        .unwrap();
+    assert_eq!(results, vec![None]);
+    let prepared = alice.send(&get_message(), alice.sender_options()).await;
+    let report = get_report(&mut mediator, prepared["message"].to_string()).await;
+    assert_eq!(report.body["code"], "e.p.xfer.queue-full");
+    let status = bob.pickup(&mut mediator, "status-request", json!({})).await;
+    assert_eq!(status.body["message_count"], 1);
+
+    // queued messages are dropped after their lifetime
+    set_time(&mut mediator, now + 61);
+    let status = bob.pickup(&mut mediator, "status-request", json!({})).await;
+    assert_eq!(status.body["message_count"], 0);
+    let results = mediator
+        .vade
+        .didcomm_receive("", &prepared["message"].to_string())
+        .await
+        .unwrap();
+    assert_eq!(results, vec![None]);
+    assert_eq!(
+        mediator.vade.mediator.as_ref().unwrap().message_count(BOB),
+        1
+    );
+}
+
+#[tokio::test]
+async fn didcomm_routing_resolves_mediator_dids_as_endpoints() {
+    let (mut alice, mut bob, mut mediator) = get_parties(Value::from(MEDIATOR)).await;
+    let mut transport = MockVadePlugin::new();
+    transport
+        .allow_unexpected_calls()
+        .expect("didcomm_send")
+        .with_options(Matcher::Json(json!({
+            "transport": "https",
+            "serviceEndpoint": MEDIATOR_URI,
+        })))
+        .returning_success("sent")
+        .times(1);
+    alice.vade.register_plugin(Box::from(transport));
+
+    let results = alice
+        .vade
+        .didcomm_send(
+            &json!({ "pack": alice.sender_options(), "transport": "https" }).to_string(),
+            &serde_json::to_string(&get_message()).unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(results, vec![Some("sent".to_string())]);
+
+    // without routing keys of the mediator, the message is sent to the mediator without forward
+    let prepared = alice.send(&get_message(), alice.sender_options()).await;
+    assert_eq!(prepared["serviceEndpoint"], MEDIATOR_URI);
+    let message = bob.unpack(&prepared["message"]).await;
+    assert_eq!(message.message.body["content"], "hello Bob");
+    let results = mediator
+        .vade
+        .didcomm_receive("", &prepared["message"].to_string())
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+}
@@ -0,0 +1,338 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use vade::{
+    multikey::KeyType, DidCommMessage, FileThreadStore, InMemoryKeyStore, InMemoryThreadStore,
+    IssuanceRole, IssuanceState, IssueCredential, KeyStore, Matcher, MockVadePlugin,
+    ProtocolAction, ThreadStore, Vade,
+};
+
+const ISSUER: &str = "did:example:issuer";
+const HOLDER: &str = "did:example:holder";
+const METHOD: &str = "did:example";
+
+struct Party {
+    vade: Vade,
+    issuance: IssueCredential,
+    send_options: String,
+}
+
+/// Creates issuer and holder, whose plugins return `{ "step": "<function>" }` for the zkp
+/// functions of their role, if the payload contains the artifact needed for it.
+async fn get_parties(store: Box<dyn ThreadStore>, version: &str) -> (Party, Party) {
+    let mut keys = Vec::new();
+    let mut documents = Vec::new();
+    for did in [ISSUER, HOLDER].iter() {
+        let key_store = InMemoryKeyStore::new();
+        let key = key_store.generate_key(KeyType::X25519).await.unwrap();
+        documents.push(json!({
+            "id": did,
+            "verificationMethod": [{
+                "id": "#agree",
+                "type": "Multikey",
+                "controller": did,
+                "publicKeyMultibase": key.public_key,
+            }],
+            "keyAgreement": ["#agree"],
+        }));
+        keys.push((*did, key_store, key));
+    }
+    let mut stores = vec![store, Box::from(InMemoryThreadStore::new())];
+    let mut parties = Vec::new();
+    for (did, key_store, key) in keys.into_iter() {
+        let mut mock = MockVadePlugin::new();
+        for document in documents.iter() {
+            mock.expect("did_resolve")
+                .with_method(document["id"].as_str().unwrap().to_string())
+                .returning_success(&document.to_string())
+                .at_least(0);
+        }
+        mock.expect("didcomm_send").at_least(0);
+        mock.expect("didcomm_receive").at_least(0);
+        let functions: &[(&str, &str)] = match did {
+            ISSUER => &[
+                ("vc_zkp_create_credential_offer", ""),
+                ("vc_zkp_issue_credential", r#""request":"#),
+            ],
+            _ => &[
+                ("vc_zkp_create_credential_proposal", ""),
+                ("vc_zkp_request_credential", r#""offer":"#),
+                ("vc_zkp_finish_credential", r#""credential":"#),
+            ],
+        };
+        for (function, artifact) in functions.iter() {
+            mock.expect(function)
+                .with_method(METHOD)
+                .with_payload(Matcher::Contains(artifact.to_string()))
+                .returning_success(&json!({ "step": function }).to_string())
+                .at_least(0);
+        }
+        let mut vade = Vade::new();
+        vade.register_plugin(Box::from(mock));
+        vade.register_key_store(Box::from(key_store));
+        let send_options = json!({
+            "pack": { "sender": { "kid": format!("{}#agree", did), "keyId": key.id } },
+        })
+        .to_string();
+        let issuance = IssueCredential::new(did, METHOD, stores.remove(0))
+            .with_version(version)
+            .with_send_options(&send_options);
+        vade.register_didcomm_handler(Box::from(issuance.clone()))
+            .unwrap();
+        parties.push(Party {
+            vade,
+            issuance,
+            send_options,
+        });
+    }
+    let holder = parties.pop().unwrap();
+    (parties.pop().unwrap(), holder)
+}
+
+/// Delivers the message sent in given action to given party and returns its reply, if any.
+async fn deliver<T>(action: &ProtocolAction<T>, to: &mut Party) -> Option<String> {
+    let sent: Value = serde_json::from_str(action.results[0].as_ref().unwrap()).unwrap();
+    let results = to
+        .vade
+        .didcomm_receive("", &sent["message"].to_string())
+        .await
+        .unwrap();
+    results.into_iter().next().flatten()
+}
+
+fn get_state(party: &Party, thid: &str) -> IssuanceState {
+    party.issuance.get_exchange(thid).unwrap().unwrap().state
+}
+
+#[tokio::test]
+async fn issue_credential_runs_exchange_in_protocol_order() {
+    let (mut issuer, mut holder) = get_parties(Box::from(InMemoryThreadStore::new()), "3.0").await;
+
+    let proposed = holder
+        .issuance
+        .propose(
+            &mut holder.vade,
+            ISSUER,
+            "",
+            r#"{ "schema": "did:example:schema" }"#,
+        )
+        .await
+        .unwrap();
+    let thid = proposed.exchange.thid.clone();
+    assert_eq!(proposed.exchange.state, IssuanceState::ProposalSent);
+    assert_eq!(deliver(&proposed, &mut issuer).await, None);
+    let exchanges = issuer.issuance.list_exchanges().unwrap();
+    assert_eq!(exchanges.len(), 1);
+    assert_eq!(exchanges[0].role, IssuanceRole::Issuer);
+    assert_eq!(exchanges[0].state, IssuanceState::ProposalReceived);
+    assert_eq!(
+        exchanges[0].proposal,
+        Some(json!({ "step": "vc_zkp_create_credential_proposal" }))
+    );
+
+    // the holder cannot skip the offer
+    let error = holder
+        .issuance
+        .request(&mut holder.vade, &thid, "", "")
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains(r#"is in state "proposal-sent" as holder, expected state "offer-received""#));
+
+    let offered = issuer
+        .issuance
+        .offer_for_proposal(&mut issuer.vade, &thid, "", "")
+        .await
+        .unwrap();
+    deliver(&offered, &mut holder).await;
+    assert_eq!(get_state(&holder, &thid), IssuanceState::OfferReceived);
+
+    let requested = holder
+        .issuance
+        .request(&mut holder.vade, &thid, "", "")
+        .await
+        .unwrap();
+    deliver(&requested, &mut issuer).await;
+    let issued = issuer
+        .issuance
+        .issue(&mut issuer.vade, &thid, "", "")
+        .await
+        .unwrap();
+    assert_eq!(issued.exchange.state, IssuanceState::CredentialIssued);
+    deliver(&issued, &mut holder).await;
+    assert_eq!(get_state(&holder, &thid), IssuanceState::CredentialReceived);
+
+    let finished = holder
+        .issuance
+        .finish(&mut holder.vade, &thid, "", "")
+        .await
+        .unwrap();
+    assert_eq!(
+        finished.exchange.credential,
+        Some(json!({ "step": "vc_zkp_finish_credential" }))
+    );
+    deliver(&finished, &mut issuer).await;
+    assert_eq!(get_state(&issuer, &thid), IssuanceState::Done);
+    assert_eq!(get_state(&holder, &thid), IssuanceState::Done);
+}
+
+#[tokio::test]
+async fn issue_credential_resumes_aries_exchanges_from_store() {
+    let directory =
+        std::env::temp_dir().join(format!("vade_issue_credential_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let (mut issuer, mut holder) =
+        get_parties(Box::from(FileThreadStore::new(&directory)), "2.0").await;
+
+    let offered = issuer
+        .issuance
+        .offer(&mut issuer.vade, HOLDER, "", "")
+        .await
+        .unwrap();
+    let thid = offered.exchange.thid.clone();
+    let sent: Value = serde_json::from_str(offered.results[0].as_ref().unwrap()).unwrap();
+    let unpacked = holder
+        .vade
+        .didcomm_unpack("", &sent["message"].to_string())
+        .await
+        .unwrap();
+    let message: Value = serde_json::from_str(&unpacked).unwrap();
+    assert_eq!(
+        message["message"]["type"],
+        "https://didcomm.org/issue-credential/2.0/offer-credential"
+    );
+    assert_eq!(
+        message["message"]["body"]["formats"],
+        json!([{ "attach_id": "offer-credential", "format": METHOD }])
+    );
+    deliver(&offered, &mut holder).await;
+    let requested = holder
+        .issuance
+        .request(&mut holder.vade, &thid, "", "")
+        .await
+        .unwrap();
+    deliver(&requested, &mut issuer).await;
+
+    // a new engine on the same directory continues the exchange
+    let issuance =
+        IssueCredential::new(ISSUER, METHOD, Box::from(FileThreadStore::new(&directory)))
+            .with_send_options(&issuer.send_options);
+    let exchange = issuance.get_exchange(&thid).unwrap().unwrap();
+    assert_eq!(exchange.state, IssuanceState::RequestReceived);
+    assert_eq!(exchange.version, "2.0");
+    let issued = issuance
+        .issue(&mut issuer.vade, &thid, "", "")
+        .await
+        .unwrap();
+    deliver(&issued, &mut holder).await;
+    assert_eq!(get_state(&holder, &thid), IssuanceState::CredentialReceived);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn issue_credential_answers_unexpected_messages_with_problem_reports() {
+    let (mut issuer, mut holder) = get_parties(Box::from(InMemoryThreadStore::new()), "3.0").await;
+    let offered = issuer
+        .issuance
+        .offer(&mut issuer.vade, HOLDER, "", "")
+        .await
+        .unwrap();
+    let thid = offered.exchange.thid.clone();
+
+    // the offer is delivered twice, so the second one is unexpected
+    deliver(&offered, &mut holder).await;
+    let reply = deliver(&offered, &mut holder).await.unwrap();
+    let report = DidCommMessage::from_json(&reply).unwrap();
+    assert_eq!(
+        report.message_type,
+        "https://didcomm.org/issue-credential/3.0/problem-report"
+    );
+    assert_eq!(report.pthid.as_deref(), Some(thid.as_str()));
+    assert_eq!(report.body["code"], "e.p.msg.unexpected-message");
+    assert_eq!(
+        report.body["comment"],
+        r#"unexpected message "offer-credential" in state "offer-received" as holder"#
+    );
+
+    // the issuer abandons the exchange on receiving the problem report
+    let results = holder
+        .vade
+        .didcomm_send(&holder.send_options, &reply)
+        .await
+        .unwrap();
+    let sent: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    issuer
+        .vade
+        .didcomm_receive("", &sent["message"].to_string())
+        .await
+        .unwrap();
+    let abandoned = issuer.issuance.get_exchange(&thid).unwrap().unwrap();
+    assert_eq!(abandoned.state, IssuanceState::Abandoned);
+    assert_eq!(
+        abandoned.problem_report.as_ref().unwrap()["code"],
+        "e.p.msg.unexpected-message"
+    );
+
+    // other parties cannot take over an existing thread
+    let message = DidCommMessage::new(
+        "https://didcomm.org/issue-credential/3.0/propose-credential",
+        json!({}),
+    )
+    .with_from(ISSUER)
+    .with_to(&[ISSUER])
+    .with_thid(&thid);
+    let message = DidCommMessage {
+        attachments: Some(vec![json!({ "data": { "json": { "step": "hijack" } } })]),
+        ..message
+    };
+    let results = issuer
+        .vade
+        .didcomm_send(
+            &issuer.send_options,
+            &serde_json::to_string(&message).unwrap(),
+        )
+        .await
+        .unwrap();
+    let sent: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    let results = issuer
+        .vade
+        .didcomm_receive("", &sent["message"].to_string())
+        .await
+        .unwrap();
+    let report = DidCommMessage::from_json(results[0].as_ref().unwrap()).unwrap();
+    assert_eq!(report.body["code"], "e.p.trust.unexpected-sender");
+    let stored = issuer.issuance.get_exchange(&thid).unwrap().unwrap();
+    assert_eq!(stored, abandoned);
+
+    // unauthenticated messages are rejected
+    let message = DidCommMessage::new(
+        "https://didcomm.org/issue-credential/3.0/offer-credential",
+        json!({}),
+    )
+    .with_from(ISSUER)
+    .with_to(&[HOLDER]);
+    let error = holder
+        .vade
+        .didcomm_receive("", &serde_json::to_string(&message).unwrap())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "issue-credential messages have to be authcrypted"
+    );
+}
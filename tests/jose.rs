@@ -0,0 +1,369 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{json, Value};
+use vade::{
+    multikey::{self, KeyType},
+    plugins::JoseVadePlugin,
+    InMemoryKeyStore, KeyInfo, KeyStore, MockVadePlugin, Vade,
+};
+
+async fn get_vade(key_type: KeyType) -> (Vade, KeyInfo) {
+    let key_store = InMemoryKeyStore::new();
+    let key = key_store.generate_key(key_type).await.unwrap();
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(key_store));
+    vade.register_plugin(Box::from(JoseVadePlugin::new()));
+    (vade, key)
+}
+
+fn get_credential(issuer: &str) -> Value {
+    json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "id": "urn:uuid:2f4a1e5e-7e3b-4c6b-9a54-6d0f0b0f1c2d",
+        "type": ["VerifiableCredential"],
+        "issuer": issuer,
+        "validFrom": "2020-01-01T00:00:00Z",
+        "credentialSubject": { "id": "did:example:subject", "name": "Alice" },
+    })
+}
+
+async fn call(
+    vade: &mut Vade,
+    function: &str,
+    method: &str,
+    options: &str,
+    payload: &str,
+) -> String {
+    let results = match function {
+        "vc_issue" => vade.vc_issue(method, options, payload).await,
+        "vc_verify" => vade.vc_verify(method, options, payload).await,
+        "vp_create" => vade.vp_create(method, options, payload).await,
+        _ => vade.vp_verify(method, options, payload).await,
+    }
+    .unwrap();
+    results[0].clone().unwrap()
+}
+
+#[tokio::test]
+async fn jose_can_issue_and_verify_credentials() {
+    for (method, key_type) in [
+        ("jwt", KeyType::Ed25519),
+        ("jwt", KeyType::P256),
+        ("cose", KeyType::Secp256k1),
+        ("cose", KeyType::Ed25519),
+    ]
+    .iter()
+    {
+        let (mut vade, key) = get_vade(*key_type).await;
+        let issuer = format!("did:key:{}", key.public_key);
+        let options = json!({ "keyId": key.id }).to_string();
+        let credential = get_credential(&issuer).to_string();
+
+        let secured = call(&mut vade, "vc_issue", method, &options, &credential).await;
+        if *method == "jwt" {
+            assert_eq!(secured.split('.').count(), 3);
+        }
+        let result: Value =
+            serde_json::from_str(&call(&mut vade, "vc_verify", method, "", &secured).await)
+                .unwrap();
+        assert_eq!(result["verified"], true, "{}", result);
+        assert_eq!(result["credential"]["credentialSubject"]["name"], "Alice");
+
+        // formats must not be mixed up
+        let other = if *method == "jwt" { "cose" } else { "jwt" };
+        let result: Value =
+            serde_json::from_str(&call(&mut vade, "vc_verify", other, "", &secured).await).unwrap();
+        assert_eq!(result["verified"], false);
+    }
+}
+
+#[tokio::test]
+async fn jose_rejects_tampered_and_expired_credentials() {
+    let (mut vade, key) = get_vade(KeyType::Ed25519).await;
+    let issuer = format!("did:key:{}", key.public_key);
+    let options = json!({ "keyId": key.id }).to_string();
+
+    let secured = call(
+        &mut vade,
+        "vc_issue",
+        "jwt",
+        &options,
+        &get_credential(&issuer).to_string(),
+    )
+    .await;
+    let parts: Vec<&str> = secured.split('.').collect();
+    let other = call(
+        &mut vade,
+        "vc_issue",
+        "jwt",
+        &options,
+        &get_credential("did:example:other").to_string(),
+    )
+    .await;
+    let tampered = format!(
+        "{}.{}.{}",
+        parts[0],
+        other.split('.').nth(1).unwrap(),
+        parts[2]
+    );
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vc_verify", "jwt", "", &tampered).await).unwrap();
+    assert_eq!(result["verified"], false);
+
+    let mut expired = get_credential(&issuer);
+    expired["validUntil"] = json!("2021-01-01T00:00:00Z");
+    let secured = call(&mut vade, "vc_issue", "jwt", &options, &expired.to_string()).await;
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vc_verify", "jwt", "", &secured).await).unwrap();
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["error"], "expired");
+
+    let mut future = get_credential(&issuer);
+    future["validFrom"] = json!("2999-01-01T00:00:00Z");
+    let secured = call(&mut vade, "vc_issue", "cose", &options, &future.to_string()).await;
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vc_verify", "cose", "", &secured).await).unwrap();
+    assert_eq!(result["error"], "not valid yet");
+}
+
+#[tokio::test]
+async fn jose_can_create_and_verify_presentations_with_enveloped_credentials() {
+    let (mut vade, key) = get_vade(KeyType::P256).await;
+    let did = format!("did:key:{}", key.public_key);
+    let options = json!({ "keyId": key.id }).to_string();
+    let mut credential = get_credential(&did);
+    credential["credentialSubject"]["id"] = Value::from(did.as_str());
+
+    let jwt = call(
+        &mut vade,
+        "vc_issue",
+        "jwt",
+        &options,
+        &credential.to_string(),
+    )
+    .await;
+    let cose = call(
+        &mut vade,
+        "vc_issue",
+        "cose",
+        &options,
+        &credential.to_string(),
+    )
+    .await;
+    let presentation = json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "type": ["VerifiablePresentation"],
+        "holder": did,
+        "verifiableCredential": [jwt, cose],
+    });
+
+    let secured = call(
+        &mut vade,
+        "vp_create",
+        "jwt",
+        &options,
+        &presentation.to_string(),
+    )
+    .await;
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vp_verify", "jwt", "", &secured).await).unwrap();
+    assert_eq!(result["verified"], true, "{}", result);
+    assert_eq!(
+        result["presentation"]["verifiableCredential"][0]["type"],
+        "EnvelopedVerifiableCredential"
+    );
+    assert_eq!(result["credentials"].as_array().unwrap().len(), 2);
+    assert_eq!(result["credentials"][1]["issuer"], did.as_str());
+
+    // credentials must be verified as well
+    let presentation = json!({
+        "holder": did,
+        "verifiableCredential": [format!("{}x", jwt)],
+    });
+    let secured = call(
+        &mut vade,
+        "vp_create",
+        "cose",
+        &options,
+        &presentation.to_string(),
+    )
+    .await;
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vp_verify", "cose", "", &secured).await).unwrap();
+    assert_eq!(result["verified"], false);
+}
+
+#[tokio::test]
+async fn jose_resolves_kid_with_resolver() {
+    let key_store = InMemoryKeyStore::new();
+    let key = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let issuer = "did:example:issuer";
+    let mut mock = MockVadePlugin::new();
+    mock.expect("did_resolve")
+        .with_method(issuer)
+        .returning_success(
+            &json!({
+                "id": issuer,
+                "verificationMethod": [{
+                    "id": "#key-1",
+                    "type": "Multikey",
+                    "controller": issuer,
+                    "publicKeyMultibase": key.public_key,
+                }],
+                "assertionMethod": ["#key-1"],
+            })
+            .to_string(),
+        );
+    let mut resolver = Vade::new();
+    resolver.register_plugin(Box::from(mock));
+
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(key_store));
+    vade.register_plugin(Box::from(JoseVadePlugin::new().with_resolver(resolver)));
+    let options = json!({ "keyId": key.id, "kid": "#key-1" }).to_string();
+    let secured = call(
+        &mut vade,
+        "vc_issue",
+        "jwt",
+        &options,
+        &get_credential(issuer).to_string(),
+    )
+    .await;
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vc_verify", "jwt", "", &secured).await).unwrap();
+    assert_eq!(result["verified"], true, "{}", result);
+
+    // keys of other DIDs must not be accepted for the issuer
+    let options = json!({ "keyId": key.id }).to_string();
+    let secured = call(
+        &mut vade,
+        "vc_issue",
+        "jwt",
+        &options,
+        &get_credential(issuer).to_string(),
+    )
+    .await;
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vc_verify", "jwt", "", &secured).await).unwrap();
+    assert_eq!(result["verified"], false);
+
+    // assertion keys must not be accepted for presenting
+    let options = json!({ "keyId": key.id, "kid": "#key-1" }).to_string();
+    let presentation = json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "type": ["VerifiablePresentation"],
+        "holder": issuer,
+    });
+    let secured = call(
+        &mut vade,
+        "vp_create",
+        "jwt",
+        &options,
+        &presentation.to_string(),
+    )
+    .await;
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vp_verify", "jwt", "", &secured).await).unwrap();
+    assert_eq!(result["verified"], false);
+    assert!(result["error"]
+        .as_str()
+        .unwrap()
+        .contains(r#"is not authorized for "authentication""#));
+}
+
+#[tokio::test]
+async fn jose_binds_presentations_to_verifier_and_holder() {
+    let key_store = InMemoryKeyStore::new();
+    let issuer_key = key_store.generate_key(KeyType::P256).await.unwrap();
+    let holder_key = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(key_store));
+    vade.register_plugin(Box::from(JoseVadePlugin::new()));
+
+    let (_, holder_public_key) = multikey::decode_public_key(&holder_key.public_key).unwrap();
+    let mut credential = get_credential(&format!("did:key:{}", issuer_key.public_key));
+    credential["cnf"] = json!({
+        "jwk": { "kty": "OKP", "crv": "Ed25519", "x": URL_SAFE_NO_PAD.encode(holder_public_key) },
+    });
+    let options = json!({ "keyId": issuer_key.id }).to_string();
+    let jwt = call(
+        &mut vade,
+        "vc_issue",
+        "jwt",
+        &options,
+        &credential.to_string(),
+    )
+    .await;
+
+    // credentials given as payload are wrapped into a presentation of the signer
+    let options = json!({
+        "keyId": holder_key.id,
+        "challenge": "1f44d55f-f161-4938-a659-f8026467f126",
+        "domain": "https://verifier.example",
+    })
+    .to_string();
+    let payload = json!([jwt]).to_string();
+    let secured = call(&mut vade, "vp_create", "jwt", &options, &payload).await;
+
+    let result: Value = serde_json::from_str(
+        &call(
+            &mut vade,
+            "vp_verify",
+            "jwt",
+            &json!({ "domain": "https://other.example" }).to_string(),
+            &secured,
+        )
+        .await,
+    )
+    .unwrap();
+    assert_eq!(result["error"], "domain does not match");
+
+    let options = json!({
+        "challenge": "1f44d55f-f161-4938-a659-f8026467f126",
+        "domain": "https://verifier.example",
+        "maxAge": 300,
+    })
+    .to_string();
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vp_verify", "jwt", &options, &secured).await)
+            .unwrap();
+    assert_eq!(result["verified"], true, "{}", result);
+    assert_eq!(
+        result["presentation"]["holder"],
+        format!("did:key:{}", holder_key.public_key)
+    );
+
+    // replayed presentations are rejected, also if verifiers do not ask for the nonce
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vp_verify", "jwt", &options, &secured).await)
+            .unwrap();
+    assert_eq!(result["verified"], false);
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vp_verify", "jwt", "", &secured).await).unwrap();
+    assert_eq!(
+        result["error"],
+        r#"challenge "1f44d55f-f161-4938-a659-f8026467f126" has already been used"#
+    );
+
+    // the issuer does not hold the cnf key
+    let options = json!({ "keyId": issuer_key.id }).to_string();
+    let secured = call(&mut vade, "vp_create", "cose", &options, &payload).await;
+    let result: Value =
+        serde_json::from_str(&call(&mut vade, "vp_verify", "cose", "", &secured).await).unwrap();
+    assert_eq!(result["error"], "credential 0 is not bound to the holder");
+}
@@ -0,0 +1,147 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use vade::json_ld::{self, ContextLoader, DocumentLoader};
+
+const EXAMPLE_CONTEXT: &str = "https://example.org/contexts/library/v1";
+
+fn get_credential() -> Value {
+    json!({
+        "@context": [
+            "https://www.w3.org/ns/credentials/v2",
+            "https://www.w3.org/ns/credentials/examples/v2"
+        ],
+        "id": "urn:uuid:58172aac-d8ba-11ed-83dd-0b3aef56cc33",
+        "type": ["VerifiableCredential", "AlumniCredential"],
+        "issuer": "https://vc.example/issuers/5678",
+        "validFrom": "2023-01-01T00:00:00Z",
+        "credentialSubject": {
+            "id": "did:example:abcdefgh",
+            "alumniOf": "The School of Examples",
+        }
+    })
+}
+
+fn get_loader() -> ContextLoader {
+    ContextLoader::new().with_context(
+        EXAMPLE_CONTEXT,
+        json!({
+            "@context": {
+                "@version": 1.1,
+                "lib": "https://example.org/library#",
+                "title": "lib:title",
+                "borrowedBy": { "@id": "lib:borrowedBy", "@type": "@id" },
+                "tags": { "@id": "lib:tag", "@container": "@set" },
+            }
+        }),
+    )
+}
+
+#[test]
+fn json_ld_expands_and_compacts_credentials() {
+    let loader = ContextLoader::new();
+    let credential = get_credential();
+    let expanded = json_ld::expand(&credential, &loader).unwrap();
+    assert_eq!(
+        expanded[0]["https://www.w3.org/2018/credentials#issuer"][0]["@id"],
+        "https://vc.example/issuers/5678"
+    );
+
+    let compacted =
+        json_ld::compact(&Value::Array(expanded), &credential["@context"], &loader).unwrap();
+    assert_eq!(compacted, credential);
+}
+
+#[test]
+fn json_ld_compacts_with_configured_context() {
+    let loader = get_loader();
+    let expanded = json!([{
+        "@id": "https://example.org/books/1",
+        "https://example.org/library#title": [{ "@value": "Example Book" }],
+        "https://example.org/library#borrowedBy": [{ "@id": "did:example:reader" }],
+        "https://example.org/library#tag": [{ "@value": "fiction" }],
+        "https://example.org/library#isbn": [{ "@value": "978-3-16-148410-0" }],
+    }]);
+
+    let compacted = json_ld::compact(&expanded, &json!(EXAMPLE_CONTEXT), &loader).unwrap();
+    assert_eq!(
+        compacted,
+        json!({
+            "@context": EXAMPLE_CONTEXT,
+            "@id": "https://example.org/books/1",
+            "title": "Example Book",
+            "borrowedBy": "did:example:reader",
+            "tags": ["fiction"],
+            "lib:isbn": "978-3-16-148410-0",
+        })
+    );
+}
+
+#[test]
+fn json_ld_canonicalizes_documents_with_configured_contexts() {
+    let loader = get_loader();
+    let document = json!({
+        "@context": EXAMPLE_CONTEXT,
+        "title": "Example Book",
+        "borrowedBy": "did:example:reader",
+    });
+
+    assert_eq!(
+        json_ld::canonicalize(&document, &loader).unwrap(),
+        concat!(
+            "_:c14n0 <https://example.org/library#borrowedBy> <did:example:reader> .\n",
+            "_:c14n0 <https://example.org/library#title> \"Example Book\" .\n",
+        )
+    );
+}
+
+#[test]
+fn json_ld_does_not_fetch_remote_contexts() {
+    let document = json!({
+        "@context": EXAMPLE_CONTEXT,
+        "title": "Example Book",
+    });
+
+    let error = json_ld::canonicalize(&document, &ContextLoader::new())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("loading remote context failed"), "{}", error);
+    assert!(error.contains(EXAMPLE_CONTEXT), "{}", error);
+}
+
+#[test]
+fn json_ld_uses_custom_document_loaders() {
+    struct VocabLoader {}
+
+    impl DocumentLoader for VocabLoader {
+        fn load_context(&self, url: &str) -> Result<Value, Box<dyn std::error::Error>> {
+            Ok(json!({ "@context": { "@vocab": format!("{}#", url) } }))
+        }
+    }
+
+    let document = json!({
+        "@context": EXAMPLE_CONTEXT,
+        "title": "Example Book",
+    });
+    let expanded = json_ld::expand(&document, &VocabLoader {}).unwrap();
+    assert_eq!(
+        expanded,
+        vec![json!({
+            "https://example.org/contexts/library/v1#title": [{ "@value": "Example Book" }],
+        })]
+    );
+}
@@ -0,0 +1,163 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use vade::{
+    multikey::KeyType, plugins::WebVhVadePlugin, EncryptedFileKeyStore, InMemoryKeyStore, KeyStore,
+    Vade,
+};
+
+/// low iteration count to keep tests fast, do not use this outside of tests
+const TEST_ITERATIONS: u32 = 10_000;
+
+#[tokio::test]
+async fn key_store_can_sign_and_verify() {
+    let key_store = InMemoryKeyStore::new();
+
+    for key_type in [KeyType::Ed25519, KeyType::Secp256k1, KeyType::P256].iter() {
+        let key = key_store.generate_key(*key_type).await.unwrap();
+        let signature = key_store.sign(&key.id, b"hello").await.unwrap();
+
+        assert_eq!(signature.len(), 64);
+        assert!(key_store
+            .verify(&key.public_key, b"hello", &signature)
+            .await
+            .unwrap());
+        assert!(!key_store
+            .verify(&key.public_key, b"hello!", &signature)
+            .await
+            .unwrap());
+    }
+    assert_eq!(key_store.list_keys().await.unwrap().len(), 3);
+
+    let key = key_store.generate_key(KeyType::X25519).await.unwrap();
+    assert!(key_store.sign(&key.id, b"hello").await.is_err());
+    assert!(key_store.sign("z6MkUnknown", b"hello").await.is_err());
+}
+
+#[tokio::test]
+async fn key_store_can_agree_on_secrets() {
+    let key_store = InMemoryKeyStore::new();
+
+    for key_type in [KeyType::X25519, KeyType::P256, KeyType::Secp256k1].iter() {
+        let alice = key_store.generate_key(*key_type).await.unwrap();
+        let bob = key_store.generate_key(*key_type).await.unwrap();
+
+        let alice_secret = key_store.agree(&alice.id, &bob.public_key).await.unwrap();
+        let bob_secret = key_store.agree(&bob.id, &alice.public_key).await.unwrap();
+        assert_eq!(alice_secret.len(), 32);
+        assert_eq!(alice_secret, bob_secret);
+    }
+
+    let ed25519 = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let x25519 = key_store.generate_key(KeyType::X25519).await.unwrap();
+    assert!(key_store
+        .agree(&ed25519.id, &ed25519.public_key)
+        .await
+        .is_err());
+    assert!(key_store
+        .agree(&x25519.id, &ed25519.public_key)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn key_store_can_persist_keys_encrypted() {
+    let path = std::env::temp_dir().join(format!("vade-key-store-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let key_store =
+        EncryptedFileKeyStore::open_with_iterations(&path, "passphrase", TEST_ITERATIONS).unwrap();
+    let key = key_store.generate_key(KeyType::P256).await.unwrap();
+    let file = std::fs::read_to_string(&path).unwrap();
+    assert!(!file.contains(&key.public_key));
+
+    let reopened = EncryptedFileKeyStore::open(&path, "passphrase").unwrap();
+    assert_eq!(reopened.list_keys().await.unwrap(), vec![key.clone()]);
+    let signature = reopened.sign(&key.id, b"hello").await.unwrap();
+    assert!(reopened
+        .verify(&key.public_key, b"hello", &signature)
+        .await
+        .unwrap());
+
+    reopened.delete_key(&key.id).await.unwrap();
+    assert!(reopened.delete_key(&key.id).await.is_err());
+    let reopened = EncryptedFileKeyStore::open(&path, "passphrase").unwrap();
+    assert!(reopened.list_keys().await.unwrap().is_empty());
+
+    let error = EncryptedFileKeyStore::open(&path, "wrong").err().unwrap();
+    assert!(error.to_string().contains("passphrase may be wrong"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn key_store_rejects_files_with_invalid_parameters() {
+    let path =
+        std::env::temp_dir().join(format!("vade-key-store-nonce-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let key_store =
+        EncryptedFileKeyStore::open_with_iterations(&path, "passphrase", TEST_ITERATIONS).unwrap();
+    key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let mut file: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    file["nonce"] = Value::from("AAAA");
+    std::fs::write(&path, file.to_string()).unwrap();
+
+    let error = EncryptedFileKeyStore::open(&path, "passphrase")
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("invalid nonce length"));
+
+    // iteration counts are bounded for stored and new files
+    for iterations in [1, u32::MAX] {
+        file["iterations"] = Value::from(iterations);
+        std::fs::write(&path, file.to_string()).unwrap();
+        let error = EncryptedFileKeyStore::open(&path, "passphrase")
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .contains("PBKDF2 iterations must be between"));
+    }
+    std::fs::remove_file(&path).unwrap();
+    assert!(EncryptedFileKeyStore::open_with_iterations(&path, "passphrase", 1).is_err());
+}
+
+#[tokio::test]
+async fn key_store_is_shared_with_plugins() {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(WebVhVadePlugin::new()));
+    vade.register_key_store(Box::from(InMemoryKeyStore::new()));
+    let key = vade
+        .key_store
+        .as_ref()
+        .unwrap()
+        .generate_key(KeyType::Ed25519)
+        .await
+        .unwrap();
+
+    let results = vade
+        .did_create(
+            "did:webvh",
+            &json!({ "keyId": key.id }).to_string(),
+            r#"{ "domain": "example.com" }"#,
+        )
+        .await
+        .unwrap();
+
+    let created: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    assert!(created["log"].as_str().unwrap().contains(&key.public_key));
+}
@@ -0,0 +1,152 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::json;
+use vade::{Matcher, MockVadePlugin, Vade, VadePlugin, VadePluginResultValue};
+
+const EXAMPLE_DID_DOCUMENT_STR: &str = r###"{
+    "@context": "https://www.w3.org/ns/did/v1",
+    "id": "did:example:123456789abcdefghi"
+}"###;
+
+#[tokio::test]
+async fn mock_vade_plugin_returns_canned_responses_for_matching_calls() {
+    let mut mock = MockVadePlugin::new();
+    mock.expect("did_resolve")
+        .with_method("did:example:123456789abcdefghi")
+        .times(1)
+        .returning_success(EXAMPLE_DID_DOCUMENT_STR);
+    mock.expect("did_resolve")
+        .with_method("did:other:123")
+        .returning(VadePluginResultValue::Ignored);
+
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(mock));
+
+    let results = vade
+        .did_resolve("did:example:123456789abcdefghi")
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0].as_ref().unwrap(),
+        &EXAMPLE_DID_DOCUMENT_STR.to_string()
+    );
+    let results = vade.did_resolve("did:other:123").await.unwrap();
+    assert!(results.is_empty());
+}
+
+#[tokio::test]
+async fn mock_vade_plugin_matches_options_and_payload() {
+    let mut mock = MockVadePlugin::new();
+    mock.expect("vc_zkp_issue_credential")
+        .with_method("did:example")
+        .with_options(json!({ "privateKey": "abc", "identity": "did:example:issuer" }))
+        .with_payload(Matcher::Contains("credentialRequest".to_string()))
+        .returning_success("issued");
+    mock.expect("run_custom_function")
+        .with_custom_function("test connection")
+        .with_payload(Matcher::Predicate(Box::new(|payload| payload.is_empty())))
+        .returning_success("connected");
+
+    let result = mock
+        .vc_zkp_issue_credential(
+            "did:example",
+            r#"{"identity":"did:example:issuer","privateKey":"abc"}"#,
+            r#"{"credentialRequest":{}}"#,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        VadePluginResultValue::Success(Some("issued".to_string()))
+    );
+
+    let result = mock
+        .run_custom_function("did:example", "test connection", "", "")
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        VadePluginResultValue::Success(Some("connected".to_string()))
+    );
+
+    assert_eq!(mock.calls().len(), 2);
+    assert_eq!(
+        mock.calls()[1].custom_function.as_deref(),
+        Some("test connection")
+    );
+}
+
+#[tokio::test]
+async fn mock_vade_plugin_returns_errors_and_sequences_responses() {
+    let mut mock = MockVadePlugin::new();
+    mock.expect("did_update").times(1).returning_error("yikes");
+    mock.expect("did_update")
+        .times(1)
+        .returning_success("updated");
+
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(mock));
+
+    match vade.did_update("did:example:123", "", "").await {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => assert!(e.to_string().contains("yikes")),
+    }
+    let results = vade.did_update("did:example:123", "", "").await.unwrap();
+    assert_eq!(results[0].as_ref().unwrap(), "updated");
+}
+
+#[tokio::test]
+async fn mock_vade_plugin_reports_unexpected_calls() {
+    let mut mock = MockVadePlugin::new();
+    mock.expect("did_create").never();
+
+    assert!(mock.did_create("did:example", "", "").await.is_ok());
+    assert!(mock
+        .vc_zkp_verify_proof("did:example", "", "")
+        .await
+        .is_err());
+
+    let error = mock.verify().unwrap_err().to_string();
+    assert!(error.contains("called exactly 0 times, but was called 1 times"));
+    assert!(error.contains(r#"unexpected call vc_zkp_verify_proof("did:example", "", "")"#));
+    // expectations are deliberately unmet, so skip verification on drop
+    std::mem::forget(mock);
+}
+
+#[tokio::test]
+async fn mock_vade_plugin_can_allow_unexpected_calls() {
+    let mut mock = MockVadePlugin::new();
+    mock.allow_unexpected_calls();
+
+    let result = mock.didcomm_send("", "").await.unwrap();
+    assert_eq!(result, VadePluginResultValue::NotImplemented);
+    assert!(mock.verify().is_ok());
+}
+
+#[tokio::test]
+#[should_panic(expected = "mock plugin expectations not met")]
+async fn mock_vade_plugin_verifies_expectations_when_dropped() {
+    let mut mock = MockVadePlugin::new();
+    mock.expect("did_create")
+        .times(2)
+        .returning_success("did:example:123");
+
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(mock));
+    vade.did_create("did:example", "", "").await.unwrap();
+}
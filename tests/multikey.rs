@@ -0,0 +1,56 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use vade::multikey::{self, KeyType};
+
+#[test]
+fn multikey_encodes_keys_with_well_known_prefixes() {
+    let ed25519 = multikey::encode_public_key(KeyType::Ed25519, &[1u8; 32]);
+    let x25519 = multikey::encode_public_key(KeyType::X25519, &[1u8; 32]);
+    let p256 = multikey::encode_public_key(KeyType::P256, &[2u8; 33]);
+    let secp256k1 = multikey::encode_public_key(KeyType::Secp256k1, &[2u8; 33]);
+
+    assert!(ed25519.starts_with("z6Mk"));
+    assert!(x25519.starts_with("z6LS"));
+    assert!(p256.starts_with("zDn"));
+    assert!(secp256k1.starts_with("zQ3s"));
+}
+
+#[test]
+fn multikey_can_decode_encoded_keys() {
+    for key_type in [
+        KeyType::Ed25519,
+        KeyType::Secp256k1,
+        KeyType::P256,
+        KeyType::X25519,
+    ]
+    .iter()
+    {
+        let public_key = multikey::encode_public_key(*key_type, &[3u8; 32]);
+        let secret_key = multikey::encode_secret_key(*key_type, &[4u8; 32]);
+
+        assert_eq!(
+            multikey::decode_public_key(&public_key).unwrap(),
+            (*key_type, vec![3u8; 32])
+        );
+        assert_eq!(
+            multikey::decode_secret_key(&secret_key).unwrap(),
+            (*key_type, vec![4u8; 32])
+        );
+        assert!(multikey::decode_public_key(&secret_key).is_err());
+    }
+    assert!(multikey::decode_public_key("6Mk").is_err());
+}
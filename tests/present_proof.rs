@@ -0,0 +1,381 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use std::{cell::Cell, rc::Rc};
+use vade::{
+    multikey::KeyType, Clock, DidCommMessage, FileThreadStore, InMemoryKeyStore,
+    InMemoryThreadStore, KeyStore, Matcher, MockVadePlugin, PresentProof, ProofRole, ProofState,
+    ProtocolAction, ThreadStore, Vade,
+};
+
+const VERIFIER: &str = "did:example:verifier";
+const PROVER: &str = "did:example:prover";
+const METHOD: &str = "did:example";
+
+/// [`Clock`] returning a time, that can be changed by tests.
+struct TestClock(Rc<Cell<i64>>);
+
+impl Clock for TestClock {
+    fn now(&self) -> i64 {
+        self.0.get()
+    }
+}
+
+struct Party {
+    vade: Vade,
+    presentation: PresentProof,
+    send_options: String,
+}
+
+/// Creates verifier and prover, whose plugins return `{ "step": "<function>" }` for the zkp
+/// functions of their role, if the payload contains the artifact needed for it. Proofs are
+/// verified, unless the payload contains `"fail":true`.
+async fn get_parties(
+    store: Box<dyn ThreadStore>,
+    timeout: Option<i64>,
+    time: &Rc<Cell<i64>>,
+) -> (Party, Party) {
+    let mut keys = Vec::new();
+    let mut documents = Vec::new();
+    for did in [VERIFIER, PROVER].iter() {
+        let key_store = InMemoryKeyStore::new();
+        let key = key_store.generate_key(KeyType::X25519).await.unwrap();
+        documents.push(json!({
+            "id": did,
+            "verificationMethod": [{
+                "id": "#agree",
+                "type": "Multikey",
+                "controller": did,
+                "publicKeyMultibase": key.public_key,
+            }],
+            "keyAgreement": ["#agree"],
+        }));
+        keys.push((*did, key_store, key));
+    }
+    let mut stores = vec![store, Box::from(InMemoryThreadStore::new())];
+    let mut parties = Vec::new();
+    for (did, key_store, key) in keys.into_iter() {
+        let mut mock = MockVadePlugin::new();
+        for document in documents.iter() {
+            mock.expect("did_resolve")
+                .with_method(document["id"].as_str().unwrap().to_string())
+                .returning_success(&document.to_string())
+                .at_least(0);
+        }
+        mock.expect("didcomm_send").at_least(0);
+        mock.expect("didcomm_receive").at_least(0);
+        mock.expect("vc_zkp_verify_proof")
+            .with_payload(Matcher::Contains(r#""fail":true"#.to_string()))
+            .returning_success(r#"{ "verified": false }"#)
+            .at_least(0);
+        mock.expect("vc_zkp_verify_proof")
+            .with_payload(Matcher::Contains(r#""presentation":"#.to_string()))
+            .returning_success(r#"{ "verified": true }"#)
+            .at_least(0);
+        let functions: &[(&str, &str)] = match did {
+            VERIFIER => &[("vc_zkp_request_proof", "")],
+            _ => &[
+                ("vc_zkp_propose_proof", ""),
+                ("vc_zkp_present_proof", r#""request":"#),
+            ],
+        };
+        for (function, artifact) in functions.iter() {
+            mock.expect(function)
+                .with_method(METHOD)
+                .with_payload(Matcher::Contains(artifact.to_string()))
+                .returning_success(&json!({ "step": function }).to_string())
+                .at_least(0);
+        }
+        let mut vade = Vade::new();
+        vade.register_plugin(Box::from(mock));
+        vade.register_key_store(Box::from(key_store));
+        let send_options = json!({
+            "pack": { "sender": { "kid": format!("{}#agree", did), "keyId": key.id } },
+        })
+        .to_string();
+        let mut presentation = PresentProof::new(did, METHOD, stores.remove(0))
+            .with_send_options(&send_options)
+            .with_clock(Box::from(TestClock(Rc::clone(time))));
+        if let Some(timeout) = timeout {
+            presentation = presentation.with_timeout(timeout);
+        }
+        vade.register_didcomm_handler(Box::from(presentation.clone()))
+            .unwrap();
+        parties.push(Party {
+            vade,
+            presentation,
+            send_options,
+        });
+    }
+    let prover = parties.pop().unwrap();
+    (parties.pop().unwrap(), prover)
+}
+
+/// Delivers the message sent in given action to given party and returns its reply, if any.
+async fn deliver<T>(action: &ProtocolAction<T>, to: &mut Party) -> Option<String> {
+    let sent: Value = serde_json::from_str(action.results[0].as_ref().unwrap()).unwrap();
+    let results = to
+        .vade
+        .didcomm_receive("", &sent["message"].to_string())
+        .await
+        .unwrap();
+    results.into_iter().next().flatten()
+}
+
+/// Sends given plaintext reply of a party with its send options and delivers it to given party.
+async fn deliver_reply(reply: &str, from: &mut Party, to: &mut Party) {
+    let results = from
+        .vade
+        .didcomm_send(&from.send_options, reply)
+        .await
+        .unwrap();
+    let action = ProtocolAction {
+        exchange: (),
+        results,
+    };
+    assert_eq!(deliver(&action, to).await, None);
+}
+
+fn get_state(party: &Party, thid: &str) -> ProofState {
+    party
+        .presentation
+        .get_exchange(thid)
+        .unwrap()
+        .unwrap()
+        .state
+}
+
+#[tokio::test]
+async fn present_proof_runs_exchange_in_protocol_order() {
+    let time = Rc::new(Cell::new(1_700_000_000));
+    let (mut verifier, mut prover) =
+        get_parties(Box::from(InMemoryThreadStore::new()), None, &time).await;
+
+    let proposed = prover
+        .presentation
+        .propose(
+            &mut prover.vade,
+            VERIFIER,
+            "",
+            r#"{ "schema": "did:example:schema" }"#,
+        )
+        .await
+        .unwrap();
+    let thid = proposed.exchange.thid.clone();
+    assert_eq!(proposed.exchange.state, ProofState::ProposalSent);
+    assert_eq!(proposed.exchange.expires_time, None);
+    assert_eq!(deliver(&proposed, &mut verifier).await, None);
+    let exchanges = verifier.presentation.list_exchanges().unwrap();
+    assert_eq!(exchanges.len(), 1);
+    assert_eq!(exchanges[0].role, ProofRole::Verifier);
+    assert_eq!(exchanges[0].state, ProofState::ProposalReceived);
+    assert_eq!(
+        exchanges[0].proposal,
+        Some(json!({ "step": "vc_zkp_propose_proof" }))
+    );
+
+    // the prover cannot present before receiving a request
+    let error = prover
+        .presentation
+        .present(&mut prover.vade, &thid, "", "")
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains(r#"is in state "proposal-sent" as prover, expected state "request-received""#));
+
+    let requested = verifier
+        .presentation
+        .request_for_proposal(&mut verifier.vade, &thid, "", "")
+        .await
+        .unwrap();
+    deliver(&requested, &mut prover).await;
+    assert_eq!(get_state(&prover, &thid), ProofState::RequestReceived);
+
+    let presented = prover
+        .presentation
+        .present(&mut prover.vade, &thid, "", "")
+        .await
+        .unwrap();
+    assert_eq!(presented.exchange.state, ProofState::PresentationSent);
+    deliver(&presented, &mut verifier).await;
+    assert_eq!(
+        get_state(&verifier, &thid),
+        ProofState::PresentationReceived
+    );
+
+    let verified = verifier
+        .presentation
+        .verify(&mut verifier.vade, &thid, "", "")
+        .await
+        .unwrap();
+    assert_eq!(verified.exchange.state, ProofState::Done);
+    assert_eq!(
+        verified.exchange.verification,
+        Some(json!({ "verified": true }))
+    );
+    deliver(&verified, &mut prover).await;
+    assert_eq!(get_state(&prover, &thid), ProofState::Done);
+}
+
+#[tokio::test]
+async fn present_proof_abandons_exchange_on_failed_verification() {
+    let time = Rc::new(Cell::new(1_700_000_000));
+    let (mut verifier, mut prover) =
+        get_parties(Box::from(InMemoryThreadStore::new()), None, &time).await;
+    let requested = verifier
+        .presentation
+        .request(&mut verifier.vade, PROVER, "", "")
+        .await
+        .unwrap();
+    let thid = requested.exchange.thid.clone();
+    deliver(&requested, &mut prover).await;
+
+    // the request is delivered twice, so the second one is unexpected
+    let reply = deliver(&requested, &mut prover).await.unwrap();
+    let report = DidCommMessage::from_json(&reply).unwrap();
+    assert_eq!(
+        report.message_type,
+        "https://didcomm.org/present-proof/3.0/problem-report"
+    );
+    assert_eq!(report.pthid.as_deref(), Some(thid.as_str()));
+    assert_eq!(
+        report.body["comment"],
+        r#"unexpected message "request-presentation" in state "request-received" as prover"#
+    );
+
+    // other parties cannot take over an existing thread
+    let exchange = prover.presentation.get_exchange(&thid).unwrap().unwrap();
+    let message = DidCommMessage::new(
+        "https://didcomm.org/present-proof/3.0/request-presentation",
+        json!({}),
+    )
+    .with_from(PROVER)
+    .with_to(&[PROVER])
+    .with_thid(&thid);
+    let message = DidCommMessage {
+        attachments: Some(vec![json!({ "data": { "json": { "step": "hijack" } } })]),
+        ..message
+    };
+    let results = prover
+        .vade
+        .didcomm_send(
+            &prover.send_options,
+            &serde_json::to_string(&message).unwrap(),
+        )
+        .await
+        .unwrap();
+    let action = ProtocolAction {
+        exchange: (),
+        results,
+    };
+    let report = DidCommMessage::from_json(&deliver(&action, &mut prover).await.unwrap()).unwrap();
+    assert_eq!(report.body["code"], "e.p.trust.unexpected-sender");
+    assert_eq!(
+        prover.presentation.get_exchange(&thid).unwrap().unwrap(),
+        exchange
+    );
+
+    let presented = prover
+        .presentation
+        .present(&mut prover.vade, &thid, "", r#"{ "fail": true }"#)
+        .await
+        .unwrap();
+    deliver(&presented, &mut verifier).await;
+    let verified = verifier
+        .presentation
+        .verify(&mut verifier.vade, &thid, "", r#"{ "fail": true }"#)
+        .await
+        .unwrap();
+    assert_eq!(verified.exchange.state, ProofState::Abandoned);
+    assert_eq!(
+        verified.exchange.verification,
+        Some(json!({ "verified": false }))
+    );
+
+    // the prover abandons the exchange on receiving the problem report
+    deliver(&verified, &mut prover).await;
+    let exchange = prover.presentation.get_exchange(&thid).unwrap().unwrap();
+    assert_eq!(exchange.state, ProofState::Abandoned);
+    assert_eq!(
+        exchange.problem_report.unwrap()["code"],
+        "e.p.trust.verification-failed"
+    );
+}
+
+#[tokio::test]
+async fn present_proof_abandons_expired_exchanges() {
+    let directory = std::env::temp_dir().join(format!("vade_present_proof_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let time = Rc::new(Cell::new(1_700_000_000));
+    let (mut verifier, mut prover) =
+        get_parties(Box::from(FileThreadStore::new(&directory)), Some(60), &time).await;
+
+    let requested = verifier
+        .presentation
+        .request(&mut verifier.vade, PROVER, "", "")
+        .await
+        .unwrap();
+    let thid = requested.exchange.thid.clone();
+    assert_eq!(requested.exchange.expires_time, Some(1_700_000_060));
+    deliver(&requested, &mut prover).await;
+    let exchange = prover.presentation.get_exchange(&thid).unwrap().unwrap();
+    assert_eq!(exchange.expires_time, Some(1_700_000_060));
+
+    time.set(1_700_000_100);
+    let error = prover
+        .presentation
+        .present(&mut prover.vade, &thid, "", "")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(r#"proof exchange "{}" has expired"#, thid)
+    );
+
+    // a new engine on the same directory finds and reports the expired exchange
+    let presentation = PresentProof::new(
+        VERIFIER,
+        METHOD,
+        Box::from(FileThreadStore::new(&directory)),
+    )
+    .with_send_options(&verifier.send_options)
+    .with_clock(Box::from(TestClock(Rc::clone(&time))));
+    let abandoned = presentation
+        .abandon_expired(&mut verifier.vade)
+        .await
+        .unwrap();
+    assert_eq!(abandoned.len(), 1);
+    assert_eq!(abandoned[0].exchange.state, ProofState::Abandoned);
+    assert!(presentation
+        .abandon_expired(&mut verifier.vade)
+        .await
+        .unwrap()
+        .is_empty());
+    deliver(&abandoned[0], &mut prover).await;
+    let exchange = prover.presentation.get_exchange(&thid).unwrap().unwrap();
+    assert_eq!(exchange.state, ProofState::Abandoned);
+    assert_eq!(exchange.problem_report.unwrap()["code"], "e.p.req.time");
+
+    // expired messages are answered with a problem report
+    let reply = deliver(&requested, &mut prover).await.unwrap();
+    let report = DidCommMessage::from_json(&reply).unwrap();
+    assert_eq!(report.body["code"], "e.p.req.time");
+    deliver_reply(&reply, &mut prover, &mut verifier).await;
+    assert_eq!(get_state(&verifier, &thid), ProofState::Abandoned);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
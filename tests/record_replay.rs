@@ -0,0 +1,155 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use std::path::PathBuf;
+use vade::{
+    Cassette, MockVadePlugin, RecordedResult, RecordingVadePlugin, ReplayVadePlugin, Vade,
+    VadePlugin, VadePluginResultValue,
+};
+
+fn get_cassette_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("vade_{}_{}.json", name, std::process::id()))
+}
+
+fn get_issuing_plugin() -> MockVadePlugin {
+    let mut mock = MockVadePlugin::new();
+    mock.expect("vc_zkp_create_credential_offer")
+        .returning_success(r#"{"nonce":"123"}"#);
+    mock.expect("vc_zkp_issue_credential")
+        .returning_success(r#"{"credential":{}}"#);
+    mock.expect("vc_zkp_verify_proof")
+        .returning_error("invalid proof");
+    mock
+}
+
+#[tokio::test]
+async fn record_replay_can_record_calls_to_file() {
+    let path = get_cassette_path("record");
+    let recorder = RecordingVadePlugin::new(Box::from(get_issuing_plugin())).with_path(&path);
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(recorder));
+
+    vade.vc_zkp_create_credential_offer("did:example", "{}", r#"{"schema":"s"}"#)
+        .await
+        .unwrap();
+    vade.vc_zkp_issue_credential("did:example", "{}", "{}")
+        .await
+        .unwrap();
+    assert!(vade
+        .vc_zkp_verify_proof("did:example", "", "")
+        .await
+        .is_err());
+
+    let cassette = Cassette::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(cassette.interactions.len(), 3);
+    assert_eq!(
+        cassette.interactions[0].call.function,
+        "vc_zkp_create_credential_offer"
+    );
+    assert_eq!(cassette.interactions[0].call.payload, r#"{"schema":"s"}"#);
+    assert_eq!(
+        cassette.interactions[0].result,
+        RecordedResult::Success(Some(r#"{"nonce":"123"}"#.to_string()))
+    );
+    assert!(
+        matches!(&cassette.interactions[2].result, RecordedResult::Error(e) if e == "invalid proof")
+    );
+}
+
+#[tokio::test]
+async fn record_replay_can_replay_recorded_calls() {
+    let mut recorder = RecordingVadePlugin::new(Box::from(get_issuing_plugin()));
+    recorder
+        .vc_zkp_create_credential_offer("did:example", "{}", "{}")
+        .await
+        .unwrap();
+    recorder
+        .vc_zkp_issue_credential("did:example", "{}", "{}")
+        .await
+        .unwrap();
+    assert!(recorder
+        .vc_zkp_verify_proof("did:example", "", "")
+        .await
+        .is_err());
+
+    let path = get_cassette_path("replay");
+    recorder.cassette().save(&path).unwrap();
+    let mut replay = ReplayVadePlugin::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        replay
+            .vc_zkp_issue_credential("did:example", "{}", "{}")
+            .await
+            .unwrap(),
+        VadePluginResultValue::Success(Some(r#"{"credential":{}}"#.to_string())),
+    );
+    assert_eq!(
+        replay
+            .vc_zkp_create_credential_offer("did:example", "{}", "{}")
+            .await
+            .unwrap(),
+        VadePluginResultValue::Success(Some(r#"{"nonce":"123"}"#.to_string())),
+    );
+    match replay.vc_zkp_verify_proof("did:example", "", "").await {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => assert_eq!(e.to_string(), "invalid proof"),
+    }
+    assert_eq!(replay.remaining(), 0);
+}
+
+#[tokio::test]
+async fn record_replay_fails_for_calls_not_recorded() {
+    let mut replay = ReplayVadePlugin::new(Cassette::new());
+
+    match replay.did_resolve("did:example:123").await {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => assert!(e
+            .to_string()
+            .contains(r#"no recorded interaction for call did_resolve("did:example:123""#)),
+    }
+}
+
+#[tokio::test]
+async fn record_replay_can_detect_changed_behavior() {
+    let mut old_recorder = RecordingVadePlugin::new(Box::from(get_issuing_plugin()));
+    old_recorder
+        .vc_zkp_verify_proof("did:example", "", "")
+        .await
+        .unwrap_err();
+
+    let mut new_plugin = MockVadePlugin::new();
+    new_plugin
+        .expect("vc_zkp_verify_proof")
+        .returning_success(r#"{"verified":true}"#);
+    let mut new_recorder = RecordingVadePlugin::new(Box::from(new_plugin));
+    new_recorder
+        .vc_zkp_verify_proof("did:example", "", "")
+        .await
+        .unwrap();
+
+    assert!(old_recorder
+        .cassette()
+        .diff(old_recorder.cassette())
+        .is_empty());
+    let differences = old_recorder.cassette().diff(new_recorder.cassette());
+    assert_eq!(differences.len(), 1);
+    assert!(differences[0].contains("result of vc_zkp_verify_proof"));
+
+    // the old recording never triggered the other expectations
+    std::mem::forget(old_recorder);
+}
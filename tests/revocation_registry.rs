@@ -0,0 +1,281 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use vade::{
+    plugins::RevocationRegistryVadePlugin, FixedClock, Vade, VadePlugin, VadePluginResultValue,
+};
+
+const METHOD: &str = "revocation-registry";
+const ISSUER: &str = "did:example:issuer";
+
+fn get_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!(
+        "vade_revocation_registry_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&directory);
+    directory
+}
+
+fn get_plugin(directory: &PathBuf, time: i64) -> RevocationRegistryVadePlugin {
+    RevocationRegistryVadePlugin::new(directory).with_clock(Box::from(FixedClock::new(time)))
+}
+
+async fn call(
+    plugin: &mut RevocationRegistryVadePlugin,
+    function: &str,
+    options: &Value,
+    payload: &Value,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let options = options.to_string();
+    let payload = payload.to_string();
+    let result = match function {
+        "create" => {
+            plugin
+                .vc_zkp_create_revocation_registry_definition(METHOD, &options, &payload)
+                .await
+        }
+        "revoke" => {
+            plugin
+                .vc_zkp_revoke_credential(METHOD, &options, &payload)
+                .await
+        }
+        _ => {
+            plugin
+                .vc_zkp_update_revocation_registry(METHOD, &options, &payload)
+                .await
+        }
+    }?;
+    match result {
+        VadePluginResultValue::Success(Some(value)) => Ok(serde_json::from_str(&value)?),
+        _ => Err("no result".into()),
+    }
+}
+
+async fn create_registry(directory: &PathBuf) -> Value {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(get_plugin(directory, 1000)));
+    let results = vade
+        .vc_zkp_create_revocation_registry_definition(
+            METHOD,
+            r#"{ "maxCredNum": 8 }"#,
+            &json!({ "issuer": ISSUER }).to_string(),
+        )
+        .await
+        .unwrap();
+    serde_json::from_str(results[0].as_ref().unwrap()).unwrap()
+}
+
+fn get_state_request(id: &Value, index: usize, to: i64) -> Value {
+    json!({
+        "revocationRegistryDefinition": id,
+        "credentialRevocationId": index,
+        "interval": { "to": to },
+    })
+}
+
+#[tokio::test]
+async fn revocation_registry_can_revoke_credentials_offline() {
+    let directory = get_directory("issuer");
+    let registry = create_registry(&directory).await;
+    let definition = &registry["revocationRegistryDefinition"];
+    let id = &definition["id"];
+    assert_eq!(definition["type"], "CL_ACCUM");
+    assert_eq!(definition["maxCredNum"], 8);
+    assert!(id.as_str().unwrap().starts_with(ISSUER));
+    assert!(registry["revocationRegistryPrivateKey"].is_string());
+    assert_eq!(registry["deltas"][0]["timestamp"], 1000);
+
+    // plugins with the same directory share their registries
+    let mut plugin = get_plugin(&directory, 2000);
+    let state = call(
+        &mut plugin,
+        "update",
+        &json!({}),
+        &get_state_request(id, 3, 1500),
+    )
+    .await
+    .unwrap();
+    assert_eq!(state["timestamp"], 1000);
+    plugin
+        .verify_revocation_state(&state.to_string(), Some(1000), Some(1500))
+        .unwrap();
+
+    let revoke = json!({ "revocationRegistryDefinition": id, "credentialRevocationId": 3 });
+    let delta = call(&mut plugin, "revoke", &json!({}), &revoke)
+        .await
+        .unwrap();
+    assert_eq!(delta["timestamp"], 2000);
+    assert_eq!(delta["revoked"], json!([3]));
+    assert_eq!(delta["prevAccum"], registry["deltas"][0]["accum"]);
+    let error = call(&mut plugin, "revoke", &json!({}), &revoke)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("already been revoked"));
+
+    // the old state is valid up to the revocation only
+    plugin
+        .verify_revocation_state(&state.to_string(), Some(1500), Some(2500))
+        .unwrap();
+    let error = plugin
+        .verify_revocation_state(&state.to_string(), Some(2000), None)
+        .unwrap_err();
+    assert!(error.to_string().contains("outdated"));
+    let error = call(
+        &mut plugin,
+        "update",
+        &json!({}),
+        &get_state_request(id, 3, 2500),
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("has been revoked"));
+
+    // a revoked credential's witness does not match the new accumulator
+    let mut forged = state.clone();
+    forged["timestamp"] = 2000.into();
+    forged["accum"] = delta["accum"].clone();
+    let error = plugin
+        .verify_revocation_state(&forged.to_string(), None, None)
+        .unwrap_err();
+    assert!(error.to_string().contains("has been revoked"));
+
+    let state = call(
+        &mut plugin,
+        "update",
+        &json!({}),
+        &get_state_request(id, 4, 2500),
+    )
+    .await
+    .unwrap();
+    assert_eq!(state["timestamp"], 2000);
+    plugin
+        .verify_revocation_state(&state.to_string(), Some(2000), Some(2500))
+        .unwrap();
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn revocation_registry_verifies_imported_tails_files_and_deltas() {
+    let issuer_directory = get_directory("published");
+    let registry = create_registry(&issuer_directory).await;
+    let definition = registry["revocationRegistryDefinition"].clone();
+    let id = definition["id"].clone();
+    let mut issuer = get_plugin(&issuer_directory, 2000);
+    call(
+        &mut issuer,
+        "revoke",
+        &json!({}),
+        &json!({ "revocationRegistryDefinition": id, "credentialRevocationId": 1 }),
+    )
+    .await
+    .unwrap();
+    let published = call(
+        &mut issuer,
+        "update",
+        &json!({}),
+        &json!({ "revocationRegistryDefinition": id }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(published["deltas"].as_array().unwrap().len(), 2);
+    let tails_file = definition["tailsLocation"].as_str().unwrap().to_string();
+
+    let holder_directory = get_directory("holder");
+    let mut holder = get_plugin(&holder_directory, 3000);
+    let error = call(&mut holder, "update", &json!({}), &published)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("tails file"));
+    let mut tampered = std::fs::read(&tails_file).unwrap();
+    tampered[0] ^= 1;
+    let tampered_file = holder_directory.with_extension("tails");
+    std::fs::write(&tampered_file, &tampered).unwrap();
+    let error = call(
+        &mut holder,
+        "update",
+        &json!({ "tailsFile": tampered_file }),
+        &published,
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("does not match its hash"));
+
+    let mut forged = published.clone();
+    forged["deltas"][1]["revoked"] = json!([2]);
+    let error = call(
+        &mut holder,
+        "update",
+        &json!({ "tailsFile": tails_file }),
+        &forged,
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("accumulator"));
+
+    let imported = call(
+        &mut holder,
+        "update",
+        &json!({ "tailsFile": tails_file }),
+        &published,
+    )
+    .await
+    .unwrap();
+    assert_eq!(imported, published);
+    let error = call(
+        &mut holder,
+        "revoke",
+        &json!({}),
+        &json!({
+            "revocationRegistryDefinition": id,
+            "credentialRevocationId": 2,
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("not been created by this plugin"));
+
+    let state = call(
+        &mut holder,
+        "update",
+        &json!({}),
+        &get_state_request(&id, 2, 3000),
+    )
+    .await
+    .unwrap();
+    holder
+        .verify_revocation_state(&state.to_string(), None, Some(3000))
+        .unwrap();
+    let error = call(
+        &mut holder,
+        "update",
+        &json!({}),
+        &get_state_request(&id, 1, 3000),
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("has been revoked"));
+
+    std::fs::remove_dir_all(&issuer_directory).unwrap();
+    std::fs::remove_dir_all(&holder_directory).unwrap();
+    std::fs::remove_file(&tampered_file).unwrap();
+}
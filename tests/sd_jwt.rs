@@ -0,0 +1,204 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{json, Value};
+use vade::{
+    multikey::KeyType, plugins::SdJwtVadePlugin, InMemoryKeyStore, KeyInfo, KeyStore, Vade,
+};
+
+async fn get_vade() -> (Vade, KeyInfo, KeyInfo) {
+    let key_store = InMemoryKeyStore::new();
+    let issuer = key_store.generate_key(KeyType::P256).await.unwrap();
+    let holder = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(key_store));
+    vade.register_plugin(Box::from(SdJwtVadePlugin::new()));
+    (vade, issuer, holder)
+}
+
+fn get_claims() -> Value {
+    json!({
+        "vct": "https://credentials.example.com/identity_credential",
+        "given_name": "Erika",
+        "family_name": "Mustermann",
+        "address": { "street_address": "Heidestraße 17", "locality": "Köln" },
+        "nationalities": ["DE", "AT"],
+    })
+}
+
+async fn issue(vade: &mut Vade, issuer: &KeyInfo, holder: &KeyInfo) -> String {
+    let options = json!({
+        "keyId": issuer.id,
+        "disclosable": [
+            "/given_name",
+            "/family_name",
+            "/address",
+            "/address/street_address",
+            "/address/locality",
+            "/nationalities/1",
+        ],
+        "decoys": 2,
+        "holderKey": holder.public_key,
+    });
+    let results = vade
+        .vc_issue("sd-jwt", &options.to_string(), &get_claims().to_string())
+        .await
+        .unwrap();
+    results[0].clone().unwrap()
+}
+
+async fn verify(vade: &mut Vade, function: &str, options: &Value, sd_jwt: &str) -> Value {
+    let results = if function == "vc_verify" {
+        vade.vc_verify("sd-jwt", &options.to_string(), sd_jwt).await
+    } else {
+        vade.vp_verify("sd-jwt", &options.to_string(), sd_jwt).await
+    }
+    .unwrap();
+    serde_json::from_str(results[0].as_ref().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn sd_jwt_can_issue_and_verify_with_nested_disclosures() {
+    let (mut vade, issuer, holder) = get_vade().await;
+    let sd_jwt = issue(&mut vade, &issuer, &holder).await;
+
+    // 6 disclosures and no key binding
+    assert_eq!(sd_jwt.split('~').count(), 8);
+    assert!(sd_jwt.ends_with('~'));
+    let jwt: Vec<&str> = sd_jwt.split('~').next().unwrap().split('.').collect();
+    let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(jwt[0]).unwrap()).unwrap();
+    let payload: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(jwt[1]).unwrap()).unwrap();
+    assert_eq!(header["typ"], "dc+sd-jwt");
+    assert_eq!(payload["_sd_alg"], "sha-256");
+    assert!(payload.get("given_name").is_none());
+    // 3 disclosable claims and 2 decoys
+    assert_eq!(payload["_sd"].as_array().unwrap().len(), 5);
+    assert!(payload["nationalities"][1].get("...").is_some());
+    assert_eq!(payload["cnf"]["jwk"]["crv"], "Ed25519");
+
+    let result = verify(&mut vade, "vc_verify", &json!({}), &sd_jwt).await;
+    assert_eq!(result["verified"], true, "{}", result);
+    let claims = &result["claims"];
+    assert_eq!(claims["given_name"], "Erika");
+    assert_eq!(claims["address"]["locality"], "Köln");
+    assert_eq!(claims["nationalities"], json!(["DE", "AT"]));
+    assert!(claims.get("_sd").is_none());
+    assert!(claims["address"].get("_sd").is_none());
+}
+
+#[tokio::test]
+async fn sd_jwt_can_select_disclosures_and_bind_keys() {
+    let (mut vade, issuer, holder) = get_vade().await;
+    let sd_jwt = issue(&mut vade, &issuer, &holder).await;
+
+    let options = json!({
+        "keyId": holder.id,
+        "disclose": ["/address/locality", "/nationalities"],
+        "aud": "https://verifier.example",
+        "nonce": "n-0S6_WzA2Mj",
+    });
+    let results = vade
+        .vp_create("sd-jwt", &options.to_string(), &sd_jwt)
+        .await
+        .unwrap();
+    let presentation = results[0].clone().unwrap();
+    assert!(!presentation.ends_with('~'));
+
+    let options = json!({ "aud": "https://verifier.example", "nonce": "n-0S6_WzA2Mj" });
+    let result = verify(&mut vade, "vp_verify", &options, &presentation).await;
+    assert_eq!(result["verified"], true, "{}", result);
+    let claims = &result["claims"];
+    assert_eq!(claims["address"], json!({ "locality": "Köln" }));
+    assert_eq!(claims["nationalities"], json!(["DE", "AT"]));
+    assert!(claims.get("given_name").is_none());
+    assert_eq!(
+        claims["vct"],
+        "https://credentials.example.com/identity_credential"
+    );
+
+    let options = json!({ "aud": "https://verifier.example", "nonce": "other" });
+    let result = verify(&mut vade, "vp_verify", &options, &presentation).await;
+    assert_eq!(result["verified"], false);
+
+    // key binding is required for presentations
+    let result = verify(&mut vade, "vp_verify", &json!({}), &sd_jwt).await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["error"], "key binding required");
+
+    // key binding JWTs must not be moved to other presentations
+    let key_binding = presentation.rsplit('~').next().unwrap();
+    let moved = format!("{}{}", sd_jwt, key_binding);
+    let result = verify(&mut vade, "vp_verify", &json!({}), &moved).await;
+    assert_eq!(result["verified"], false);
+}
+
+#[tokio::test]
+async fn sd_jwt_rejects_invalid_disclosures() {
+    let (mut vade, issuer, holder) = get_vade().await;
+    let sd_jwt = issue(&mut vade, &issuer, &holder).await;
+    let parts: Vec<&str> = sd_jwt.split('~').collect();
+
+    let forged = URL_SAFE_NO_PAD.encode(json!(["c2FsdA", "given_name", "Max"]).to_string());
+    let added = format!("{}{}~", sd_jwt, forged);
+    let result = verify(&mut vade, "vc_verify", &json!({}), &added).await;
+    assert_eq!(result["verified"], false);
+
+    let duplicated = format!("{}{}~", sd_jwt, parts[1]);
+    let result = verify(&mut vade, "vc_verify", &json!({}), &duplicated).await;
+    assert_eq!(result["verified"], false);
+
+    let options = json!({ "keyId": issuer.id, "disclosable": ["/vct"] });
+    assert!(vade
+        .vc_issue("sd-jwt", &options.to_string(), &get_claims().to_string())
+        .await
+        .is_err());
+    let options = json!({ "keyId": issuer.id, "disclosable": ["/unknown"] });
+    assert!(vade
+        .vc_issue("sd-jwt", &options.to_string(), &get_claims().to_string())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn sd_jwt_requires_holder_key_for_key_binding() {
+    let (mut vade, issuer, holder) = get_vade().await;
+    let sd_jwt = issue(&mut vade, &issuer, &holder).await;
+
+    let options = json!({ "keyId": issuer.id, "disclose": ["/given_name"] });
+    assert!(vade
+        .vp_create("sd-jwt", &options.to_string(), &sd_jwt)
+        .await
+        .is_err());
+
+    // presentations without key binding can still be verified as credentials
+    let options = json!({ "disclose": ["/given_name"] });
+    let results = vade
+        .vp_create("sd-jwt", &options.to_string(), &sd_jwt)
+        .await
+        .unwrap();
+    let result = verify(
+        &mut vade,
+        "vc_verify",
+        &json!({}),
+        results[0].as_ref().unwrap(),
+    )
+    .await;
+    assert_eq!(result["verified"], true, "{}", result);
+    assert_eq!(result["claims"]["given_name"], "Erika");
+    assert!(result["claims"].get("address").is_none());
+    assert_eq!(result["claims"]["nationalities"], json!(["DE"]));
+}
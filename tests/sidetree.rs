@@ -0,0 +1,111 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::Value;
+use vade::{plugins::SidetreeVadePlugin, DidResolutionResult, Vade};
+
+/// long-form DID from the [Sidetree specification](https://identity.foundation/sidetree/spec/v1.0.0/#did)
+const LONG_FORM_DID: &str = "did:sidetree:EiDyOQbbZAa3aiRzeCkV7LOx3SERjjH93EXoIM3UoN4oWg:eyJkZWx0YSI6eyJwYXRjaGVzIjpbeyJhY3Rpb24iOiJyZXBsYWNlIiwiZG9jdW1lbnQiOnsicHVibGljS2V5cyI6W3siaWQiOiJwdWJsaWNLZXlNb2RlbDFJZCIsInB1YmxpY0tleUp3ayI6eyJjcnYiOiJzZWNwMjU2azEiLCJrdHkiOiJFQyIsIngiOiJ0WFNLQl9ydWJYUzdzQ2pYcXVwVkpFelRjVzNNc2ptRXZxMVlwWG45NlpnIiwieSI6ImRPaWNYcWJqRnhvR0otSzAtR0oxa0hZSnFpY19EX09NdVV3a1E3T2w2bmsifSwicHVycG9zZXMiOlsiYXV0aGVudGljYXRpb24iLCJrZXlBZ3JlZW1lbnQiXSwidHlwZSI6IkVjZHNhU2VjcDI1NmsxVmVyaWZpY2F0aW9uS2V5MjAxOSJ9XSwic2VydmljZXMiOlt7ImlkIjoic2VydmljZTFJZCIsInNlcnZpY2VFbmRwb2ludCI6Imh0dHA6Ly93d3cuc2VydmljZTEuY29tIiwidHlwZSI6InNlcnZpY2UxVHlwZSJ9XX19XSwidXBkYXRlQ29tbWl0bWVudCI6IkVpREtJa3dxTzY5SVBHM3BPbEhrZGI4Nm5ZdDBhTnhTSFp1MnItYmhFem5qZEEifSwic3VmZml4RGF0YSI6eyJkZWx0YUhhc2giOiJFaUNmRFdSbllsY0Q5RUdBM2RfNVoxQUh1LWlZcU1iSjluZmlxZHo1UzhWRGJnIiwicmVjb3ZlcnlDb21taXRtZW50IjoiRWlCZk9aZE10VTZPQnc4UGs4NzlRdFotMkotOUZiYmpTWnlvYUFfYnFENHpoQSJ9fQ";
+const SHORT_FORM_DID: &str = "did:sidetree:EiDyOQbbZAa3aiRzeCkV7LOx3SERjjH93EXoIM3UoN4oWg";
+
+#[tokio::test]
+async fn sidetree_can_resolve_long_form_dids() {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(
+        SidetreeVadePlugin::new().with_methods(&["did:sidetree"]),
+    ));
+
+    let results = vade.did_resolve(LONG_FORM_DID).await.unwrap();
+
+    assert_eq!(results.len(), 1);
+    let document: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    assert_eq!(document["id"], LONG_FORM_DID);
+    assert_eq!(
+        document["verificationMethod"][0]["id"],
+        "#publicKeyModel1Id"
+    );
+    assert_eq!(
+        document["verificationMethod"][0]["type"],
+        "EcdsaSecp256k1VerificationKey2019"
+    );
+    assert_eq!(
+        document["verificationMethod"][0]["publicKeyJwk"]["x"],
+        "tXSKB_rubXS7sCjXqupVJEzTcW3MsjmEvq1YpXn96Zg"
+    );
+    assert_eq!(document["authentication"][0], "#publicKeyModel1Id");
+    assert_eq!(document["keyAgreement"][0], "#publicKeyModel1Id");
+    assert!(document.get("assertionMethod").is_none());
+    assert_eq!(document["service"][0]["id"], "#service1Id");
+    assert_eq!(
+        document["service"][0]["serviceEndpoint"],
+        "http://www.service1.com"
+    );
+}
+
+#[tokio::test]
+async fn sidetree_can_return_method_metadata() {
+    let plugin = SidetreeVadePlugin::new().with_methods(&["did:sidetree"]);
+
+    let result: DidResolutionResult = plugin.resolve(LONG_FORM_DID).unwrap().unwrap();
+
+    assert_eq!(
+        result.did_document_metadata["equivalentId"][0],
+        SHORT_FORM_DID
+    );
+    assert_eq!(result.did_document_metadata["method"]["published"], false);
+    assert_eq!(
+        result.did_document_metadata["method"]["recoveryCommitment"],
+        "EiBfOZdMtU6OBw8Pk879QtZ-2J-9FbbjSZyoaA_bqD4zhA"
+    );
+    assert_eq!(
+        result.did_document_metadata["method"]["updateCommitment"],
+        "EiDKIkwqO69IPG3pOlHkdb86nYt0aNxSHZu2r-bhEznjdA"
+    );
+}
+
+#[tokio::test]
+async fn sidetree_ignores_short_form_dids_and_other_methods() {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(SidetreeVadePlugin::new()));
+
+    assert!(vade
+        .did_resolve("did:ion:EiDyOQbbZAa3aiRzeCkV7LOx3SERjjH93EXoIM3UoN4oWg")
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(vade
+        .did_resolve("did:ion:test:EiDyOQbbZAa3aiRzeCkV7LOx3SERjjH93EXoIM3UoN4oWg")
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(vade.did_resolve(LONG_FORM_DID).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sidetree_rejects_suffix_not_matching_initial_state() {
+    let plugin = SidetreeVadePlugin::new().with_methods(&["did:sidetree"]);
+    let tampered = LONG_FORM_DID.replace(
+        "EiDyOQbbZAa3aiRzeCkV7LOx3SERjjH93EXoIM3UoN4oWg",
+        "EiDyOQbbZAa3aiRzeCkV7LOx3SERjjH93EXoIM3UoN4oWh",
+    );
+
+    match plugin.resolve(&tampered) {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => assert!(e
+            .to_string()
+            .contains("DID suffix does not match hash of suffix data")),
+    }
+}
@@ -0,0 +1,379 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use vade::{
+    plugins::{DataIntegrityVadePlugin, StatusListStore, StatusListVadePlugin},
+    Vade,
+};
+
+const METHOD: &str = "status-list";
+const ISSUER: &str = "did:key:z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2";
+const SECRET_KEY: &str = "z3u2en7t5LR2WtQH5PfFqMqwVHBeXouLzo6haApm8XHqvjxq";
+const REVOCATION_LIST: &str = "https://example.com/status/revocation";
+const SUSPENSION_LIST: &str = "https://example.com/status/suspension";
+
+/// Store sharing its states between plugins, as a database would across restarts.
+#[derive(Clone, Default)]
+struct SharedStore(Rc<RefCell<HashMap<String, String>>>);
+
+impl StatusListStore for SharedStore {
+    fn load(&self, id: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(self.0.borrow().get(id).cloned())
+    }
+
+    fn save(&self, id: &str, state: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.0
+            .borrow_mut()
+            .insert(id.to_string(), state.to_string());
+        Ok(())
+    }
+}
+
+fn get_credential() -> Value {
+    json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "type": ["VerifiableCredential"],
+        "issuer": ISSUER,
+        "credentialSubject": { "id": "did:example:subject" },
+    })
+}
+
+async fn call(vade: &mut Vade, function: &str, options: &Value, payload: &Value) -> Value {
+    let options = options.to_string();
+    let payload = payload.to_string();
+    let results = match function {
+        "create" => vade.vc_status_list_create(METHOD, &options, &payload).await,
+        "allocate" => {
+            vade.vc_status_list_allocate(METHOD, &options, &payload)
+                .await
+        }
+        "update" => vade.vc_status_list_update(METHOD, &options, &payload).await,
+        _ => vade.vc_status_list_check(METHOD, &options, &payload).await,
+    }
+    .unwrap();
+    serde_json::from_str(results[0].as_ref().unwrap()).unwrap()
+}
+
+async fn create_lists(vade: &mut Vade) {
+    for (id, purpose) in [
+        (REVOCATION_LIST, "revocation"),
+        (SUSPENSION_LIST, "suspension"),
+    ]
+    .iter()
+    {
+        let list = call(
+            vade,
+            "create",
+            &json!({ "statusPurpose": purpose }),
+            &json!({ "id": id, "issuer": ISSUER }),
+        )
+        .await;
+        assert_eq!(list["type"][1], "BitstringStatusListCredential");
+        assert_eq!(list["credentialSubject"]["statusPurpose"], *purpose);
+        assert!(list["credentialSubject"]["encodedList"]
+            .as_str()
+            .unwrap()
+            .starts_with("uH4sI"));
+    }
+}
+
+#[tokio::test]
+async fn status_list_can_revoke_and_suspend_credentials() {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(StatusListVadePlugin::new()));
+    create_lists(&mut vade).await;
+
+    let options = json!({ "statusLists": [REVOCATION_LIST, SUSPENSION_LIST] });
+    let credential = call(&mut vade, "allocate", &options, &get_credential()).await;
+    let entries = credential["credentialStatus"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["type"], "BitstringStatusListEntry");
+    assert_eq!(entries[0]["statusListCredential"], REVOCATION_LIST);
+    assert_eq!(entries[1]["statusPurpose"], "suspension");
+    let other = call(&mut vade, "allocate", &options, &get_credential()).await;
+    assert_ne!(
+        other["credentialStatus"][0]["statusListIndex"],
+        entries[0]["statusListIndex"]
+    );
+
+    let result = call(&mut vade, "check", &json!({}), &credential).await;
+    assert_eq!(result["verified"], true, "{}", result);
+    assert_eq!(result["statuses"].as_array().unwrap().len(), 2);
+
+    let suspend = json!({ "statusPurpose": "suspension" });
+    let list = call(&mut vade, "update", &suspend, &credential).await;
+    assert_eq!(list["id"], SUSPENSION_LIST);
+    let result = call(&mut vade, "check", &json!({}), &credential).await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["error"], "credential is suspended");
+    let result = call(&mut vade, "check", &json!({}), &other).await;
+    assert_eq!(result["verified"], true, "{}", result);
+
+    // lists have to be issued by the credential's issuer
+    let mut forged = other.clone();
+    forged["issuer"] = Value::from("did:example:other");
+    let error = vade
+        .vc_status_list_check(METHOD, "", &forged.to_string())
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("has not been issued by the issuer of the credential"));
+
+    // unknown status entries fail the check
+    let mut unknown = other.clone();
+    unknown["credentialStatus"]
+        .as_array_mut()
+        .unwrap()
+        .push(json!({ "type": "RevocationList2020Status" }));
+    let result = call(&mut vade, "check", &json!({}), &unknown).await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(
+        result["statuses"][2],
+        json!({ "type": "RevocationList2020Status", "checked": false })
+    );
+
+    let reinstate = json!({ "statusPurpose": "suspension", "status": false });
+    call(&mut vade, "update", &reinstate, &credential).await;
+    let result = call(&mut vade, "check", &json!({}), &credential).await;
+    assert_eq!(result["verified"], true, "{}", result);
+
+    let revoke = json!({ "statusPurpose": "revocation" });
+    call(&mut vade, "update", &revoke, &credential).await;
+    let result = call(&mut vade, "check", &json!({}), &credential).await;
+    assert_eq!(result["error"], "credential has been revoked");
+    let error = vade
+        .vc_status_list_update(
+            METHOD,
+            r#"{ "statusPurpose": "revocation", "status": false }"#,
+            &credential.to_string(),
+        )
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("cannot be reinstated"));
+}
+
+#[tokio::test]
+async fn status_list_checks_published_and_verified_lists() {
+    let mut issuer = Vade::new();
+    issuer.register_plugin(Box::from(StatusListVadePlugin::new()));
+    issuer.register_plugin(Box::from(DataIntegrityVadePlugin::new()));
+    create_lists(&mut issuer).await;
+    let credential = call(
+        &mut issuer,
+        "allocate",
+        &json!({ "statusLists": [REVOCATION_LIST] }),
+        &get_credential(),
+    )
+    .await;
+    let list = call(
+        &mut issuer,
+        "update",
+        &json!({ "statusPurpose": "revocation" }),
+        &credential,
+    )
+    .await;
+    let secured = issuer
+        .vc_issue(
+            "data-integrity",
+            &json!({ "secretKey": SECRET_KEY }).to_string(),
+            &list.to_string(),
+        )
+        .await
+        .unwrap()[0]
+        .clone()
+        .unwrap();
+    let secured: Value = serde_json::from_str(&secured).unwrap();
+
+    let mut verifier = Vade::new();
+    verifier.register_plugin(Box::from(DataIntegrityVadePlugin::new()));
+    let mut plugin = StatusListVadePlugin::new().with_verifier(verifier, "data-integrity");
+    let mut tampered = secured.clone();
+    tampered["credentialSubject"]["encodedList"] = list["credentialSubject"]["encodedList"]
+        .as_str()
+        .unwrap()
+        .replace("uH4sI", "uH4sJ")
+        .into();
+    assert!(plugin
+        .add_status_list_credential(&tampered.to_string())
+        .await
+        .is_err());
+    plugin
+        .add_status_list_credential(&secured.to_string())
+        .await
+        .unwrap();
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(plugin));
+    let result = call(&mut vade, "check", &json!({}), &credential).await;
+    assert_eq!(result["verified"], false);
+    assert_eq!(result["error"], "credential has been revoked");
+
+    // lists can also be given with the call, if they can be verified
+    let mut verifier = Vade::new();
+    verifier.register_plugin(Box::from(DataIntegrityVadePlugin::new()));
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(
+        StatusListVadePlugin::new().with_verifier(verifier, "data-integrity"),
+    ));
+    let options = json!({ "statusListCredentials": [secured] });
+    let result = call(&mut vade, "check", &options, &credential).await;
+    assert_eq!(result["statuses"][0]["status"], true);
+    let error = vade
+        .vc_status_list_check(METHOD, "", &credential.to_string())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("unknown status list"));
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(StatusListVadePlugin::new()));
+    let error = vade
+        .vc_status_list_check(METHOD, &options.to_string(), &credential.to_string())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("require a verifier"));
+}
+
+#[tokio::test]
+async fn status_list_supports_status_list_2021() {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(StatusListVadePlugin::new()));
+    let list = call(
+        &mut vade,
+        "create",
+        &json!({ "type": "StatusList2021" }),
+        &json!({ "id": REVOCATION_LIST, "issuer": ISSUER }),
+    )
+    .await;
+    assert_eq!(list["type"][1], "StatusList2021Credential");
+    assert!(list["credentialSubject"]["encodedList"]
+        .as_str()
+        .unwrap()
+        .starts_with("H4sI"));
+
+    let credential = call(
+        &mut vade,
+        "allocate",
+        &json!({ "statusLists": [REVOCATION_LIST] }),
+        &get_credential(),
+    )
+    .await;
+    assert_eq!(
+        credential["credentialStatus"]["type"],
+        "StatusList2021Entry"
+    );
+    call(
+        &mut vade,
+        "update",
+        &json!({ "statusPurpose": "revocation" }),
+        &credential,
+    )
+    .await;
+    let result = call(&mut vade, "check", &json!({}), &credential).await;
+    assert_eq!(result["error"], "credential has been revoked");
+
+    let error = vade
+        .vc_status_list_create(
+            METHOD,
+            r#"{ "length": 8 }"#,
+            r#"{ "id": "https://example.com/status/short", "issuer": "did:example:issuer" }"#,
+        )
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("at least 131072"));
+}
+
+#[tokio::test]
+async fn status_list_releases_indexes_of_failed_allocations() {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(StatusListVadePlugin::new()));
+    create_lists(&mut vade).await;
+    // leave one free index in the revocation list
+    let options = json!({ "statusLists": vec![REVOCATION_LIST; 131_071] });
+    call(&mut vade, "allocate", &options, &get_credential()).await;
+
+    for lists in [
+        json!([REVOCATION_LIST, "https://example.com/status/unknown"]),
+        json!([SUSPENSION_LIST, REVOCATION_LIST, REVOCATION_LIST]),
+    ] {
+        let options = json!({ "statusLists": lists });
+        assert!(vade
+            .vc_status_list_allocate(METHOD, &options.to_string(), &get_credential().to_string())
+            .await
+            .is_err());
+    }
+    let options = json!({ "statusLists": [REVOCATION_LIST] });
+    let credential = call(&mut vade, "allocate", &options, &get_credential()).await;
+    assert_eq!(
+        credential["credentialStatus"]["statusListCredential"],
+        REVOCATION_LIST
+    );
+    let error = vade
+        .vc_status_list_allocate(METHOD, &options.to_string(), &get_credential().to_string())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("is full"));
+}
+
+#[tokio::test]
+async fn status_list_restores_lists_from_store() {
+    let store = SharedStore::default();
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(
+        StatusListVadePlugin::new().with_store(Box::from(store.clone())),
+    ));
+    create_lists(&mut vade).await;
+    let options = json!({ "statusLists": [REVOCATION_LIST, SUSPENSION_LIST] });
+    let credential = call(&mut vade, "allocate", &options, &get_credential()).await;
+    let index: usize = credential["credentialStatus"][0]["statusListIndex"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    drop(vade);
+
+    // simulate a restart with a new plugin using the same store
+    let mut vade = Vade::new();
+    let plugin = StatusListVadePlugin::new().with_store(Box::from(store.clone()));
+    let list = plugin.get_status_list_credential(REVOCATION_LIST).unwrap();
+    assert_eq!(list["credentialSubject"]["statusPurpose"], "revocation");
+    vade.register_plugin(Box::from(plugin));
+    let error = vade
+        .vc_status_list_create(
+            METHOD,
+            "",
+            &json!({ "id": REVOCATION_LIST, "issuer": ISSUER }).to_string(),
+        )
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("already exists"));
+    let state: Value =
+        serde_json::from_str(&store.load(REVOCATION_LIST).unwrap().unwrap()).unwrap();
+    assert_eq!(state["allocated"], json!([index]));
+
+    let revoke = json!({ "statusPurpose": "revocation" });
+    let list = call(&mut vade, "update", &revoke, &credential).await;
+    assert_eq!(list["id"], REVOCATION_LIST);
+    let result = call(&mut vade, "check", &json!({}), &credential).await;
+    assert_eq!(result["error"], "credential has been revoked");
+
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(
+        StatusListVadePlugin::new().with_store(Box::from(store)),
+    ));
+    let result = call(&mut vade, "check", &json!({}), &credential).await;
+    assert_eq!(result["error"], "credential has been revoked");
+}
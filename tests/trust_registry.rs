@@ -0,0 +1,197 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{json, Value};
+use vade::{
+    multikey::KeyType, plugins::DataIntegrityVadePlugin, InMemoryKeyStore, KeyStore, TrustList,
+    TrustRegistry, Vade, VerificationChecks, VerificationReport,
+};
+
+const ISSUER: &str = "did:key:z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2";
+const SECRET_KEY: &str = "z3u2en7t5LR2WtQH5PfFqMqwVHBeXouLzo6haApm8XHqvjxq";
+const SCHEMA: &str = "https://example.com/schemas/degree.json";
+
+fn get_list(credential_types: &[&str]) -> Value {
+    json!({
+        "trustedIssuers": [
+            { "issuer": "did:example:other" },
+            { "issuer": ISSUER, "credentialTypes": credential_types, "schemas": [SCHEMA] },
+        ],
+    })
+}
+
+async fn issue(vade: &mut Vade, credential: &Value) -> String {
+    let results = vade
+        .vc_issue(
+            "data-integrity",
+            &json!({ "secretKey": SECRET_KEY, "cryptosuite": "eddsa-jcs-2022" }).to_string(),
+            &credential.to_string(),
+        )
+        .await
+        .unwrap();
+    results[0].clone().unwrap()
+}
+
+fn get_credential() -> Value {
+    json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "type": ["VerifiableCredential", "DegreeCredential"],
+        "issuer": ISSUER,
+        "credentialSchema": { "id": SCHEMA, "type": "JsonSchema" },
+        "credentialSubject": { "id": "did:example:subject" },
+    })
+}
+
+#[tokio::test]
+async fn trust_registry_reports_untrusted_issuers() {
+    let path = std::env::temp_dir().join(format!("vade_trust_list_{}.json", std::process::id()));
+    std::fs::write(&path, get_list(&["DegreeCredential"]).to_string()).unwrap();
+    let list = TrustList::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(list.issuers().len(), 2);
+
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(DataIntegrityVadePlugin::new()));
+    let credential = issue(&mut vade, &get_credential()).await;
+    vade.register_verification_checks(
+        VerificationChecks::new()
+            .without_status()
+            .with_trust_registry(Box::from(list)),
+    );
+    let results = vade
+        .vc_verify("data-integrity", "", &credential)
+        .await
+        .unwrap();
+    let report: VerificationReport = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    assert!(report.verified, "{:?}", report);
+    assert_eq!(report.checks[2].check, "trust");
+
+    let mut other = get_credential();
+    other["type"][1] = "ExamCredential".into();
+    let other = issue(&mut vade, &other).await;
+    let results = vade.vc_verify("data-integrity", "", &other).await.unwrap();
+    let report: VerificationReport = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    assert!(!report.verified);
+    assert!(report.checks[0].passed);
+    assert_eq!(
+        report.checks[2].error.as_deref(),
+        Some(&*format!(
+            r#"issuer "{}" is not trusted to issue credentials of type "ExamCredential" with schema "{}""#,
+            ISSUER, SCHEMA
+        ))
+    );
+
+    let list = TrustList::from_json(&get_list(&["DegreeCredential"]).to_string()).unwrap();
+    let error = list
+        .check_issuer(
+            "did:example:unknown",
+            &["DegreeCredential".to_string()],
+            &[],
+        )
+        .unwrap_err();
+    assert_eq!(error, r#"issuer "did:example:unknown" is not trusted"#);
+    assert!(list.check_issuer("did:example:other", &[], &[]).is_ok());
+}
+
+#[tokio::test]
+async fn trust_registry_can_be_read_from_signed_lists() {
+    let key_store = InMemoryKeyStore::new();
+    let key = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let anchor = format!("did:key:{}", key.public_key);
+    let header = json!({ "alg": "EdDSA", "kid": format!("{}#{}", anchor, key.public_key) });
+    let mut claims = get_list(&["DegreeCredential"]);
+    claims["iss"] = Value::from(anchor.clone());
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string()),
+    );
+    let signature = key_store
+        .sign(&key.id, signing_input.as_bytes())
+        .await
+        .unwrap();
+    let jws = format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature));
+
+    let list = TrustList::from_signed_list(&jws, &[&anchor], None)
+        .await
+        .unwrap();
+    assert_eq!(list.issuers()[1].issuer, ISSUER);
+
+    let error = TrustList::from_signed_list(&jws, &[ISSUER], None)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("is not a trust anchor"));
+    let mut tampered = claims.clone();
+    tampered["trustedIssuers"][0]["issuer"] = "did:example:attacker".into();
+    let parts: Vec<&str> = jws.split('.').collect();
+    let tampered = format!(
+        "{}.{}.{}",
+        parts[0],
+        URL_SAFE_NO_PAD.encode(tampered.to_string()),
+        parts[2]
+    );
+    let error = TrustList::from_signed_list(&tampered, &[&anchor], None)
+        .await
+        .unwrap_err();
+    assert_eq!(error.to_string(), "invalid signature");
+}
+
+#[tokio::test]
+async fn trust_registry_can_be_read_from_trust_list_credentials() {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(DataIntegrityVadePlugin::new()));
+    let list_credential = issue(
+        &mut vade,
+        &json!({
+            "@context": ["https://www.w3.org/ns/credentials/v2"],
+            "type": ["VerifiableCredential", "TrustListCredential"],
+            "issuer": ISSUER,
+            "credentialSubject": get_list(&["DegreeCredential"]),
+        }),
+    )
+    .await;
+
+    let list = TrustList::from_credential(&list_credential, &[ISSUER], &mut vade, "data-integrity")
+        .await
+        .unwrap();
+    assert_eq!(list.issuers().len(), 2);
+    assert_eq!(
+        list.issuers()[1].schemas.as_deref(),
+        Some(&[SCHEMA.to_string()][..])
+    );
+
+    let error = TrustList::from_credential(
+        &list_credential,
+        &["did:example:anchor"],
+        &mut vade,
+        "data-integrity",
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("is not a trust anchor"));
+    let mut tampered: Value = serde_json::from_str(&list_credential).unwrap();
+    tampered["credentialSubject"]["trustedIssuers"][0]["issuer"] = "did:example:attacker".into();
+    let error = TrustList::from_credential(
+        &tampered.to_string(),
+        &[ISSUER],
+        &mut vade,
+        "data-integrity",
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("invalid trust list credential"));
+}
@@ -0,0 +1,178 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use vade::{plugins::UniversalResolverVadePlugin, DidResolutionResult, Vade};
+
+const EXAMPLE_RESOLUTION_RESULT_STR: &str = r###"{
+    "@context": "https://w3id.org/did-resolution/v1",
+    "didDocument": {
+        "@context": "https://www.w3.org/ns/did/v1",
+        "id": "did:web:example.com"
+    },
+    "didResolutionMetadata": { "contentType": "application/did+ld+json" },
+    "didDocumentMetadata": { "created": "2020-01-01T00:00:00Z" }
+}"###;
+
+const NOT_FOUND_RESOLUTION_RESULT_STR: &str = r###"{
+    "didDocument": null,
+    "didResolutionMetadata": { "error": "notFound", "errorMessage": "no such domain" },
+    "didDocumentMetadata": {}
+}"###;
+
+/// Starts a stand-in universal resolver, that answers requests for known paths with given
+/// status and body and with 404 for all other paths. Returns the server's base URL.
+fn start_resolver(routes: Vec<(&'static str, u16, &'static str)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut accept = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.to_lowercase().starts_with("accept:") {
+                    accept = line.clone();
+                }
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+            }
+            let path = request_line.split(' ').nth(1).unwrap_or("").to_string();
+            let (status, body) = routes
+                .iter()
+                .find(|(route, _, _)| *route == path)
+                .map(|(_, status, body)| (*status, *body))
+                .unwrap_or((404, ""));
+            let body = if accept.contains("did-resolution") {
+                body
+            } else {
+                r#"{"error":"unexpected accept header"}"#
+            };
+            let response = format!(
+                "HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    format!("http://{}", address)
+}
+
+#[tokio::test]
+async fn universal_resolver_can_resolve_did_documents() {
+    let endpoint = start_resolver(vec![(
+        "/1.0/identifiers/did:web:example.com",
+        200,
+        EXAMPLE_RESOLUTION_RESULT_STR,
+    )]);
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(UniversalResolverVadePlugin::new(
+        &endpoint,
+        &["did:web"],
+    )));
+
+    let results = vade.did_resolve("did:web:example.com").await.unwrap();
+
+    assert_eq!(results.len(), 1);
+    let document: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    assert_eq!(document["id"], "did:web:example.com");
+}
+
+#[tokio::test]
+async fn universal_resolver_can_return_resolution_metadata() {
+    let endpoint = start_resolver(vec![(
+        "/1.0/identifiers/did:web:example.com",
+        200,
+        EXAMPLE_RESOLUTION_RESULT_STR,
+    )]);
+    let mut resolver = UniversalResolverVadePlugin::new(&format!("{}/", endpoint), &["did:web"])
+        .with_resolution_result();
+
+    let result = vade::VadePlugin::did_resolve(&mut resolver, "did:web:example.com")
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    let result: DidResolutionResult = serde_json::from_str(&result).unwrap();
+    assert_eq!(
+        result.did_document.unwrap()["id"],
+        Value::from("did:web:example.com")
+    );
+    assert_eq!(
+        result.did_resolution_metadata["contentType"],
+        "application/did+ld+json"
+    );
+    assert_eq!(
+        result.did_document_metadata["created"],
+        "2020-01-01T00:00:00Z"
+    );
+}
+
+#[tokio::test]
+async fn universal_resolver_ignores_unconfigured_methods() {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(UniversalResolverVadePlugin::new(
+        "http://127.0.0.1:1",
+        &["did:web"],
+    )));
+
+    let results = vade.did_resolve("did:webvh:example.com").await.unwrap();
+    assert!(results.is_empty());
+    let results = vade.did_resolve("did:key:z6Mk").await.unwrap();
+    assert!(results.is_empty());
+}
+
+#[tokio::test]
+async fn universal_resolver_maps_resolution_errors() {
+    let endpoint = start_resolver(vec![
+        (
+            "/1.0/identifiers/did:web:unknown.example.com",
+            404,
+            NOT_FOUND_RESOLUTION_RESULT_STR,
+        ),
+        ("/1.0/identifiers/did:web:broken.example.com", 500, "oops"),
+    ]);
+    let resolver = UniversalResolverVadePlugin::new(&endpoint, &["did:web"]);
+
+    let error = resolver
+        .resolve("did:web:unknown.example.com")
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("HTTP status 404"));
+    assert!(error.contains("notFound; no such domain"));
+
+    let error = resolver
+        .resolve("did:web:broken.example.com")
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("HTTP status 500"));
+    assert!(error.contains("internalError"));
+}
@@ -67,6 +67,16 @@ impl VadePlugin for TestPlugin {
     ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
         Err(Box::from("yikes"))
     }
+
+    // test plugin vc_issue echoes the credential to issue
+    async fn vc_issue(
+        &mut self,
+        _method: &str,
+        _options: &str,
+        payload: &str,
+    ) -> Result<VadePluginResultValue<Option<String>>, Box<dyn std::error::Error>> {
+        Ok(VadePluginResultValue::Success(Some(payload.to_string())))
+    }
 }
 
 #[tokio::test]
@@ -106,8 +116,38 @@ async fn vade_plugin_vade_can_call_functions_implemented_in_plugin() {
                 EXAMPLE_DID_DOCUMENT_STR.to_string()
             );
 
-            println!("created did: {}", results[0].as_ref().unwrap().to_string());
+            println!("created did: {}", results[0].as_ref().unwrap());
         }
         Err(e) => panic!("{}", e),
     };
 }
+
+#[tokio::test]
+async fn vade_plugin_vade_can_call_vc_functions() {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(TestPlugin::new()));
+
+    let issued = vade
+        .vc_issue("did:example", "", r#"{"type":["VerifiableCredential"]}"#)
+        .await
+        .unwrap();
+    assert_eq!(
+        issued,
+        vec![Some(r#"{"type":["VerifiableCredential"]}"#.to_string())]
+    );
+    assert!(vade
+        .vc_verify("did:example", "", "")
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(vade
+        .vp_create("did:example", "", "")
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(vade
+        .vp_verify("did:example", "", "")
+        .await
+        .unwrap()
+        .is_empty());
+}
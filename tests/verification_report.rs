@@ -0,0 +1,270 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use vade::{
+    plugins::{DataIntegrityVadePlugin, StatusListVadePlugin},
+    FixedClock, MockVadePlugin, Vade, VerificationChecks, VerificationReport,
+};
+
+const ISSUER: &str = "did:key:z6MkrJVnaZkeFzdQyMZu1cgjg7k1pZZ6pvBQ7XJPt4swbTQ2";
+const SECRET_KEY: &str = "z3u2en7t5LR2WtQH5PfFqMqwVHBeXouLzo6haApm8XHqvjxq";
+const STATUS_LIST: &str = "https://example.com/status/revocation";
+/// 2030-01-01T00:00:00Z
+const VALID_UNTIL: i64 = 1_893_456_000;
+
+async fn get_vade() -> Vade {
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(DataIntegrityVadePlugin::new()));
+    vade.register_plugin(Box::from(StatusListVadePlugin::new()));
+    vade
+}
+
+async fn issue(vade: &mut Vade) -> Value {
+    let created = vade
+        .vc_status_list_create(
+            "status-list",
+            "",
+            &json!({ "id": STATUS_LIST, "issuer": ISSUER }).to_string(),
+        )
+        .await
+        .unwrap();
+    assert!(created[0].is_some());
+    let credential = json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "type": ["VerifiableCredential"],
+        "issuer": ISSUER,
+        "validFrom": "2024-01-01T00:00:00Z",
+        "validUntil": "2030-01-01T00:00:00Z",
+        "credentialSubject": { "id": "did:example:subject" },
+    });
+    let results = vade
+        .vc_status_list_allocate(
+            "status-list",
+            &json!({ "statusLists": [STATUS_LIST] }).to_string(),
+            &credential.to_string(),
+        )
+        .await
+        .unwrap();
+    let results = vade
+        .vc_issue(
+            "data-integrity",
+            &json!({ "secretKey": SECRET_KEY }).to_string(),
+            results[0].as_ref().unwrap(),
+        )
+        .await
+        .unwrap();
+    serde_json::from_str(results[0].as_ref().unwrap()).unwrap()
+}
+
+async fn verify(vade: &mut Vade, credential: &Value) -> VerificationReport {
+    let results = vade
+        .vc_verify("data-integrity", "", &credential.to_string())
+        .await
+        .unwrap();
+    serde_json::from_str(results[0].as_ref().unwrap()).unwrap()
+}
+
+async fn verify_presentation(vade: &mut Vade, presentation: &str) -> VerificationReport {
+    let results = vade
+        .vp_verify(
+            "data-integrity",
+            r#"{ "holderBinding": false }"#,
+            presentation,
+        )
+        .await
+        .unwrap();
+    serde_json::from_str(results[0].as_ref().unwrap()).unwrap()
+}
+
+fn get_check<'a>(report: &'a VerificationReport, check: &str) -> &'a vade::VerificationCheck {
+    report
+        .checks
+        .iter()
+        .find(|c| c.check == check)
+        .unwrap_or_else(|| panic!("no {} check in report", check))
+}
+
+#[tokio::test]
+async fn verification_report_lists_proof_validity_and_status_checks() {
+    let mut vade = get_vade().await;
+    let credential = issue(&mut vade).await;
+
+    vade.register_verification_checks(
+        VerificationChecks::new().with_clock(Box::from(FixedClock::new(VALID_UNTIL - 1))),
+    );
+    let report = verify(&mut vade, &credential).await;
+    assert!(report.verified, "{:?}", report);
+    assert_eq!(report.checks.len(), 3);
+    assert!(get_check(&report, "proof").passed);
+    assert_eq!(get_check(&report, "validity").credential, Some(0));
+    assert_eq!(report.result["verified"], true);
+    assert_eq!(
+        get_check(&report, "status").details.as_ref().unwrap()["statuses"][0]["status"],
+        false
+    );
+
+    vade.vc_status_list_update(
+        "status-list",
+        r#"{ "statusPurpose": "revocation" }"#,
+        &credential.to_string(),
+    )
+    .await
+    .unwrap();
+    let report = verify(&mut vade, &credential).await;
+    assert!(!report.verified);
+    assert!(get_check(&report, "proof").passed);
+    let status = get_check(&report, "status");
+    assert!(!status.passed);
+    assert_eq!(status.error.as_deref(), Some("credential has been revoked"));
+}
+
+#[tokio::test]
+async fn verification_report_checks_validity_against_clock() {
+    let mut vade = get_vade().await;
+    let credential = issue(&mut vade).await;
+
+    // plugins check against the system time and accept the credential
+    let results = vade
+        .vc_verify("data-integrity", "", &credential.to_string())
+        .await
+        .unwrap();
+    let result: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    assert_eq!(result["verified"], true);
+    assert!(result.get("checks").is_none());
+
+    vade.register_verification_checks(
+        VerificationChecks::new()
+            .with_clock(Box::from(FixedClock::new(VALID_UNTIL)))
+            .without_status(),
+    );
+    let report = verify(&mut vade, &credential).await;
+    assert!(!report.verified);
+    assert_eq!(report.checks.len(), 2);
+    assert_eq!(
+        get_check(&report, "validity").error.as_deref(),
+        Some("credential has expired")
+    );
+
+    vade.register_verification_checks(
+        VerificationChecks::new().with_clock(Box::from(FixedClock::new(1_700_000_000))),
+    );
+    let report = verify(&mut vade, &credential).await;
+    assert_eq!(
+        get_check(&report, "validity").error.as_deref(),
+        Some("credential is not valid yet")
+    );
+}
+
+#[tokio::test]
+async fn verification_report_wraps_zkp_proof_verifications() {
+    let mut mock = MockVadePlugin::new();
+    mock.expect("vc_zkp_verify_proof")
+        .with_method("did:example")
+        .returning_success(r#"{ "status": "verified" }"#);
+    mock.expect("vc_status_list_check")
+        .returning_success(r#"{ "verified": false, "error": "credential is suspended" }"#);
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(mock));
+    vade.register_verification_checks(
+        VerificationChecks::new()
+            .with_clock(Box::from(FixedClock::new(VALID_UNTIL - 1)))
+            .with_status_method("did:example"),
+    );
+
+    let presentation = json!({
+        "verifiableCredential": [
+            { "expirationDate": "2030-01-01T00:00:00Z" },
+            { "expirationDate": "2020-01-01T00:00:00Z", "credentialStatus": {} },
+        ],
+    });
+    let results = vade
+        .vc_zkp_verify_proof("did:example", "", &presentation.to_string())
+        .await
+        .unwrap();
+    let report: VerificationReport = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    assert!(!report.verified);
+    assert!(get_check(&report, "proof").passed);
+    let outcomes: Vec<(&str, Option<usize>, bool)> = report
+        .checks
+        .iter()
+        .map(|check| (check.check.as_str(), check.credential, check.passed))
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            ("proof", None, true),
+            ("validity", Some(0), true),
+            ("validity", Some(1), false),
+            ("status", Some(1), false),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn verification_report_checks_credentials_in_presentations() {
+    let mut vade = get_vade().await;
+    let credential = issue(&mut vade).await;
+    let presentation = json!({
+        "@context": ["https://www.w3.org/ns/credentials/v2"],
+        "type": "VerifiablePresentation",
+        "holder": ISSUER,
+        "verifiableCredential": [credential],
+    });
+    let results = vade
+        .vp_create(
+            "data-integrity",
+            &json!({ "secretKey": SECRET_KEY }).to_string(),
+            &presentation.to_string(),
+        )
+        .await
+        .unwrap();
+    let presentation = results[0].clone().unwrap();
+
+    vade.register_verification_checks(
+        VerificationChecks::new().with_clock(Box::from(FixedClock::new(VALID_UNTIL - 1))),
+    );
+    let report = verify_presentation(&mut vade, &presentation).await;
+    assert!(report.verified, "{:?}", report);
+    assert_eq!(get_check(&report, "status").credential, Some(0));
+
+    vade.vc_status_list_update(
+        "status-list",
+        r#"{ "statusPurpose": "revocation" }"#,
+        &credential.to_string(),
+    )
+    .await
+    .unwrap();
+    let report = verify_presentation(&mut vade, &presentation).await;
+    assert!(!report.verified);
+    assert!(get_check(&report, "proof").passed);
+    assert_eq!(
+        get_check(&report, "status").error.as_deref(),
+        Some("credential has been revoked")
+    );
+
+    vade.register_verification_checks(
+        VerificationChecks::new()
+            .with_clock(Box::from(FixedClock::new(VALID_UNTIL)))
+            .without_status(),
+    );
+    let report = verify_presentation(&mut vade, &presentation).await;
+    assert!(!report.verified);
+    assert_eq!(
+        get_check(&report, "validity").error.as_deref(),
+        Some("credential has expired")
+    );
+}
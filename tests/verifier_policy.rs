@@ -0,0 +1,175 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use serde_json::{json, Value};
+use vade::{
+    FixedClock, Matcher, MockVadePlugin, PolicyEvaluation, Vade, VerificationChecks, VerifierPolicy,
+};
+
+const ISSUER: &str = "did:example:authority";
+/// 2025-01-01T00:00:00Z
+const NOW: i64 = 1_735_689_600;
+
+fn get_policy() -> Value {
+    json!({
+        "id": "age-check",
+        "credentials": [{
+            "id": "id-card",
+            "credentialTypes": ["IdCardCredential"],
+            "requiredClaims": ["name"],
+            "allowedIssuers": [ISSUER],
+            "maxAge": 31_536_000,
+            "predicates": [{ "claim": "age", "operator": ">=", "value": 18 }],
+        }],
+    })
+}
+
+fn get_presentation(issuer: &str, issued: &str, subject: Value) -> Value {
+    json!({
+        "verifiableCredential": [{
+            "type": ["VerifiableCredential", "IdCardCredential"],
+            "issuer": issuer,
+            "issuanceDate": issued,
+            "credentialSubject": subject,
+        }],
+    })
+}
+
+async fn evaluate(vade: &mut Vade, presentation: &Value, policy: &Value) -> PolicyEvaluation {
+    let results = vade
+        .vc_zkp_verify_proof_with_policy(
+            "did:example",
+            "",
+            &presentation.to_string(),
+            &policy.to_string(),
+        )
+        .await
+        .unwrap();
+    serde_json::from_str(results[0].as_ref().unwrap()).unwrap()
+}
+
+fn get_vade() -> Vade {
+    let mut mock = MockVadePlugin::new();
+    mock.expect("vc_zkp_verify_proof")
+        .returning_success(r#"{ "status": "verified" }"#);
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(mock));
+    vade.register_verification_checks(
+        VerificationChecks::new()
+            .with_clock(Box::from(FixedClock::new(NOW)))
+            .without_status(),
+    );
+    vade
+}
+
+#[tokio::test]
+async fn verifier_policy_can_be_compiled_into_proof_requests() {
+    let mut mock = MockVadePlugin::new();
+    mock.expect("vc_zkp_request_proof")
+        .with_payload(Matcher::Json(json!({
+            "verifierDid": "did:example:verifier",
+            "policy": "age-check",
+            "subProofRequests": [{
+                "id": "id-card",
+                "credentialTypes": ["IdCardCredential"],
+                "revealedAttributes": ["name"],
+                "predicates": [{ "attribute": "age", "pType": ">=", "value": 18 }],
+                "requireNonRevoked": false,
+                "issuers": [ISSUER],
+                "maxAge": 31_536_000,
+            }],
+        })))
+        .returning_success(r#"{ "nonce": "1234" }"#)
+        .times(1);
+    let mut vade = Vade::new();
+    vade.register_plugin(Box::from(mock));
+
+    let results = vade
+        .vc_zkp_request_proof_with_policy(
+            "did:example",
+            "",
+            &get_policy().to_string(),
+            r#"{ "verifierDid": "did:example:verifier" }"#,
+        )
+        .await
+        .unwrap();
+    assert_eq!(results[0].as_deref(), Some(r#"{ "nonce": "1234" }"#));
+
+    let error = VerifierPolicy::from_json(
+        &json!({ "credentials": [{ "predicates": [{ "claim": "age", "operator": "~", "value": 1 }] }] })
+            .to_string(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"unsupported operator "~" in verifier policy"#
+    );
+    assert!(VerifierPolicy::from_json(r#"{ "credentials": [] }"#).is_err());
+}
+
+#[tokio::test]
+async fn verifier_policy_accepts_presentations_fulfilling_it() {
+    let mut vade = get_vade();
+    let presentation = get_presentation(
+        ISSUER,
+        "2024-06-01T00:00:00Z",
+        json!({ "name": "Alice", "age": 21 }),
+    );
+
+    let evaluation = evaluate(&mut vade, &presentation, &get_policy()).await;
+    assert!(evaluation.verified, "{:?}", evaluation);
+    assert_eq!(evaluation.policy.as_deref(), Some("age-check"));
+    assert_eq!(evaluation.requirements[0].credential, Some(0));
+    assert!(evaluation.requirements[0].errors.is_empty());
+    assert_eq!(evaluation.result["result"]["status"], "verified");
+}
+
+#[tokio::test]
+async fn verifier_policy_lists_unmet_requirements() {
+    let mut vade = get_vade();
+    let presentation = get_presentation(
+        "did:example:other",
+        "2023-01-01T00:00:00Z",
+        json!({ "age": 17 }),
+    );
+
+    let evaluation = evaluate(&mut vade, &presentation, &get_policy()).await;
+    assert!(!evaluation.verified);
+    assert_eq!(
+        evaluation.requirements[0].errors,
+        vec![
+            r#"issuer "did:example:other" is not allowed"#,
+            "credential is older than 31536000 seconds",
+            r#"claim "name" is not disclosed"#,
+            r#"claim "age" is not >= 18"#,
+        ]
+    );
+
+    // revocation has to be proven by a passed status check
+    let mut policy = get_policy();
+    policy["credentials"][0]["requireNonRevoked"] = true.into();
+    let presentation = get_presentation(
+        ISSUER,
+        "2024-06-01T00:00:00Z",
+        json!({ "name": "Alice", "age": 21 }),
+    );
+    let evaluation = evaluate(&mut vade, &presentation, &policy).await;
+    assert!(!evaluation.verified);
+    assert_eq!(
+        evaluation.requirements[0].errors,
+        vec!["credential status has not been checked successfully"]
+    );
+}
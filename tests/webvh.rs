@@ -0,0 +1,315 @@
+/*
+  Copyright (c) 2018-present evan GmbH.
+
+  Licensed under the Apache License, Version 2.0 (the "License");
+  you may not use this file except in compliance with the License.
+  You may obtain a copy of the License at
+
+      http://www.apache.org/licenses/LICENSE-2.0
+
+  Unless required by applicable law or agreed to in writing, software
+  distributed under the License is distributed on an "AS IS" BASIS,
+  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+  See the License for the specific language governing permissions and
+  limitations under the License.
+*/
+
+use ed25519_dalek::SigningKey;
+use serde_json::{json, Value};
+use std::rc::Rc;
+use vade::{
+    multikey::{self, KeyType},
+    plugins::WebVhVadePlugin,
+    DidResolutionResult, InMemoryKeyStore, KeyStore, Vade, VadePlugin, VadePluginResultValue,
+};
+
+fn get_secret_key(seed: u8) -> String {
+    multikey::encode_secret_key(KeyType::Ed25519, &[seed; 32])
+}
+
+fn get_public_key(seed: u8) -> String {
+    let signing_key = SigningKey::from_bytes(&[seed; 32]);
+    multikey::encode_public_key(KeyType::Ed25519, signing_key.verifying_key().as_bytes())
+}
+
+fn unwrap_result(result: VadePluginResultValue<Option<String>>) -> Value {
+    match result {
+        VadePluginResultValue::Success(Some(value)) => serde_json::from_str(&value).unwrap(),
+        _ => panic!("expected a successful result"),
+    }
+}
+
+async fn create_did(plugin: &mut WebVhVadePlugin, options: Value) -> String {
+    let created = plugin
+        .did_create(
+            "did:webvh",
+            &options.to_string(),
+            r#"{ "domain": "example.com", "path": "dids:issuer" }"#,
+        )
+        .await
+        .unwrap();
+    unwrap_result(created)["did"].as_str().unwrap().to_string()
+}
+
+async fn resolve(plugin: &mut WebVhVadePlugin, did: &str) -> DidResolutionResult {
+    serde_json::from_value(unwrap_result(plugin.did_resolve(did).await.unwrap())).unwrap()
+}
+
+#[tokio::test]
+async fn webvh_can_create_and_resolve_dids() {
+    let mut plugin = WebVhVadePlugin::new().with_resolution_result();
+    let did = create_did(&mut plugin, json!({ "secretKey": get_secret_key(1) })).await;
+
+    assert!(did.starts_with("did:webvh:Qm"));
+    assert!(did.ends_with(":example.com:dids:issuer"));
+    let result = resolve(&mut plugin, &did).await;
+    assert_eq!(result.did_document.unwrap()["id"], Value::from(did.clone()));
+    assert!(result.did_document_metadata["versionId"]
+        .as_str()
+        .unwrap()
+        .starts_with("1-Qm"));
+    assert_eq!(
+        result.did_document_metadata["scid"],
+        Value::from(did.split(':').nth(2).unwrap())
+    );
+    assert_eq!(
+        WebVhVadePlugin::get_log_url(&did).unwrap(),
+        "https://example.com/dids/issuer/did.jsonl",
+    );
+
+    // logs can be verified and resolved by other instances
+    let mut other = WebVhVadePlugin::new();
+    assert_eq!(other.add_log(plugin.get_log(&did).unwrap()).unwrap(), did);
+    assert!(other.did_resolve(&did).await.is_ok());
+    assert!(matches!(
+        other.did_resolve("did:web:example.com").await.unwrap(),
+        VadePluginResultValue::Ignored
+    ));
+}
+
+#[tokio::test]
+async fn webvh_can_update_and_resolve_versions() {
+    let mut plugin = WebVhVadePlugin::new().with_resolution_result();
+    let did = create_did(&mut plugin, json!({ "secretKey": get_secret_key(1) })).await;
+    let document = json!({
+        "@context": ["https://www.w3.org/ns/did/v1"],
+        "id": did,
+        "service": [{ "id": "#files", "type": "LinkedDomains", "serviceEndpoint": "https://example.com" }],
+    });
+
+    let updated = plugin
+        .did_update(
+            &did,
+            &json!({ "secretKey": get_secret_key(1) }).to_string(),
+            &document.to_string(),
+        )
+        .await
+        .unwrap();
+    let updated = unwrap_result(updated);
+    assert_eq!(updated["log"].as_str().unwrap().lines().count(), 2);
+
+    let latest = resolve(&mut plugin, &did).await;
+    assert_eq!(latest.did_document.unwrap()["service"][0]["id"], "#files");
+    assert_eq!(
+        latest.did_document_metadata["versionId"],
+        updated["versionId"]
+    );
+    let first = resolve(&mut plugin, &format!("{}?versionNumber=1", did)).await;
+    assert!(first.did_document.unwrap().get("service").is_none());
+    let by_id = resolve(
+        &mut plugin,
+        &format!(
+            "{}?versionId={}",
+            did,
+            updated["versionId"].as_str().unwrap()
+        ),
+    )
+    .await;
+    assert!(by_id.did_document.unwrap().get("service").is_some());
+    assert!(plugin
+        .did_resolve(&format!("{}?versionNumber=3", did))
+        .await
+        .is_err());
+
+    plugin
+        .did_update(
+            &did,
+            &json!({ "secretKey": get_secret_key(1), "deactivated": true }).to_string(),
+            "",
+        )
+        .await
+        .unwrap();
+    let deactivated = resolve(&mut plugin, &did).await;
+    assert_eq!(deactivated.did_document_metadata["deactivated"], true);
+    assert!(plugin
+        .did_update(
+            &did,
+            &json!({ "secretKey": get_secret_key(1) }).to_string(),
+            ""
+        )
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn webvh_rejects_tampered_logs_and_unauthorized_keys() {
+    let mut plugin = WebVhVadePlugin::new();
+    let did = create_did(&mut plugin, json!({ "secretKey": get_secret_key(1) })).await;
+
+    let result = plugin
+        .did_update(
+            &did,
+            &json!({ "secretKey": get_secret_key(2) }).to_string(),
+            "",
+        )
+        .await;
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("is not authorized"));
+
+    let log = plugin.get_log(&did).unwrap().to_string();
+    let tampered = log.replace("https://www.w3.org/ns/did/v1", "https://example.com/v1");
+    let error = WebVhVadePlugin::new().add_log(&tampered).unwrap_err();
+    assert!(error.to_string().contains("SCID does not match"));
+}
+
+#[tokio::test]
+async fn webvh_enforces_pre_rotation() {
+    let mut plugin = WebVhVadePlugin::new();
+    let next_key_hash = multikey::hash_base58btc(get_public_key(2).as_bytes());
+    let did = create_did(
+        &mut plugin,
+        json!({ "secretKey": get_secret_key(1), "nextKeyHashes": [next_key_hash] }),
+    )
+    .await;
+
+    // the current key cannot be used anymore, once next keys have been committed to
+    let result = plugin
+        .did_update(
+            &did,
+            &json!({ "secretKey": get_secret_key(1), "updateKeys": [get_public_key(1)] })
+                .to_string(),
+            "",
+        )
+        .await;
+    assert!(result.is_err());
+
+    let result = plugin
+        .did_update(
+            &did,
+            &json!({
+                "secretKey": get_secret_key(2),
+                "updateKeys": [get_public_key(2)],
+                "nextKeyHashes": [multikey::hash_base58btc(get_public_key(3).as_bytes())],
+            })
+            .to_string(),
+            "",
+        )
+        .await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn webvh_can_rotate_keys_with_vade() {
+    let mut vade = Vade::new();
+    vade.register_key_store(Box::from(InMemoryKeyStore::new()));
+    vade.register_plugin(Box::from(WebVhVadePlugin::new()));
+    let key_store = vade.key_store.clone().unwrap();
+    let key = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let payload = json!({
+        "domain": "example.com",
+        "document": {
+            "verificationMethod": [{
+                "id": "#key-1",
+                "type": "Multikey",
+                "publicKeyMultibase": key.public_key,
+            }],
+            "authentication": ["#key-1"],
+            "assertionMethod": ["#key-1"],
+        },
+    });
+    let results = vade
+        .did_create(
+            "did:webvh",
+            &json!({ "keyId": key.id }).to_string(),
+            &payload.to_string(),
+        )
+        .await
+        .unwrap();
+    let created: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    let did = created["did"].as_str().unwrap().to_string();
+
+    let rotated = vade.rotate_key(&did, &key.id, "").await.unwrap();
+    let rotated: Value = serde_json::from_str(&rotated).unwrap();
+    assert_eq!(rotated["rotated"], json!([format!("{}#key-1", did)]));
+    assert_eq!(rotated["retiredKeyId"], key.id);
+    assert!(key_store.get_key(&key.id).await.is_err());
+
+    let results = vade.did_resolve(&did).await.unwrap();
+    let document: Value = serde_json::from_str(results[0].as_ref().unwrap()).unwrap();
+    let method_id = rotated["verificationMethod"].clone();
+    assert_eq!(document["verificationMethod"][0]["id"], method_id);
+    assert_eq!(
+        document["verificationMethod"][0]["publicKeyMultibase"],
+        rotated["publicKey"]
+    );
+    assert_eq!(document["assertionMethod"], json!([method_id]));
+    assert_eq!(document["authentication"], json!([method_id]));
+
+    // the new key has become the update key and can rotate again
+    let new_key_id = rotated["keyId"].as_str().unwrap();
+    let rotated = vade
+        .rotate_key(&did, new_key_id, r#"{ "retire": false }"#)
+        .await
+        .unwrap();
+    let rotated: Value = serde_json::from_str(&rotated).unwrap();
+    assert_eq!(rotated["retiredKeyId"], Value::Null);
+    assert!(key_store.get_key(new_key_id).await.is_ok());
+
+    let error = vade.rotate_key(&did, new_key_id, "").await.unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("is not used by any verification method"));
+}
+
+#[tokio::test]
+async fn webvh_can_rotate_pre_rotated_keys() {
+    let key_store = InMemoryKeyStore::new();
+    let current = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let next = key_store.generate_key(KeyType::Ed25519).await.unwrap();
+    let mut plugin = WebVhVadePlugin::new().with_resolution_result();
+    plugin.set_key_store(Rc::new(key_store));
+    let did = create_did(
+        &mut plugin,
+        json!({
+            "keyId": current.id,
+            "nextKeyHashes": [multikey::hash_base58btc(next.public_key.as_bytes())],
+        }),
+    )
+    .await;
+
+    let updated = plugin
+        .did_update(
+            &did,
+            &json!({ "keyId": current.id, "newKeyId": next.id }).to_string(),
+            "",
+        )
+        .await
+        .unwrap();
+    let log = unwrap_result(updated)["log"].as_str().unwrap().to_string();
+    let entry: Value = serde_json::from_str(log.lines().last().unwrap()).unwrap();
+    assert_eq!(entry["parameters"]["updateKeys"], json!([next.public_key]));
+    assert!(entry["proof"][0]["verificationMethod"]
+        .as_str()
+        .unwrap()
+        .contains(&next.public_key));
+
+    // the log is accepted by other instances
+    let mut other = WebVhVadePlugin::new().with_resolution_result();
+    let did = other.add_log(&log).unwrap();
+    assert_eq!(
+        resolve(&mut other, &did).await.did_document_metadata["versionId"],
+        entry["versionId"]
+    );
+}